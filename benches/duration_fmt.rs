@@ -0,0 +1,27 @@
+//! The build timer gets formatted every frame
+
+use std::time::Duration;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use evil_android::duration_fmt::{format, Style};
+
+fn duration_fmt(c: &mut Criterion) {
+    // Early on, after a few weeks and off the charts
+    let durations = [
+        Duration::from_secs(59),
+        Duration::from_secs(3 * 7 * 24 * 3600 + 4 * 3600 + 5),
+        Duration::MAX,
+    ];
+    for style in [Style::Compact, Style::Verbose] {
+        c.bench_function(&format!("duration_fmt {style:?}"), |b| {
+            b.iter(|| {
+                for d in durations {
+                    criterion::black_box(format(criterion::black_box(d), style));
+                }
+            })
+        });
+    }
+}
+
+criterion_group!(benches, duration_fmt);
+criterion_main!(benches);
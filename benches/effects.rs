@@ -0,0 +1,78 @@
+//! Glitches and a whole escalation-like frame, on the frame buffer the firmware uses
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::{Dimensions, Point, Size},
+    mono_font::{iso_8859_1::FONT_6X10, MonoTextStyle},
+    pixelcolor::{Rgb565, RgbColor},
+    text::{Alignment, Text},
+    Drawable,
+};
+use embedded_graphics_framebuf::FrameBuf;
+use evil_android::{
+    effects::{add_noise, glitch, glitch_rows},
+    framebuffer::SliceFrameBufferBackend,
+    intensity::Intensity,
+    region::Region,
+};
+use rand::{rngs::StdRng, SeedableRng};
+
+/// The ST7735 on the original hardware
+const SIZE: Size = Size::new(160, 128);
+
+fn glitches(c: &mut Criterion) {
+    let mut backend = SliceFrameBufferBackend::new(SIZE, Rgb565::RED);
+    let mut rng = StdRng::seed_from_u64(0);
+    let (width, height) = (SIZE.width as usize, SIZE.height as usize);
+
+    for max_offset in [16, 160] {
+        c.bench_function(&format!("glitch {max_offset}"), |b| {
+            b.iter(|| {
+                let mut fb = FrameBuf::new(&mut backend, width, height);
+                glitch(&mut fb, &mut rng, max_offset, 0.25);
+            })
+        });
+    }
+    c.bench_function("glitch wrapped", |b| {
+        b.iter(|| {
+            let mut fb = FrameBuf::new(&mut backend, width, height);
+            glitch_rows(&mut fb, &mut rng, 16, 0.25, true, &Region::Full);
+        })
+    });
+}
+
+/// Clear, text, glitches and noise, then a copy to another buffer standing in for the LCD
+fn frame(c: &mut Criterion) {
+    let mut backend = SliceFrameBufferBackend::new(SIZE, Rgb565::BLACK);
+    let mut lcd_backend = SliceFrameBufferBackend::new(SIZE, Rgb565::BLACK);
+    let mut rng = StdRng::seed_from_u64(0);
+    let (width, height) = (SIZE.width as usize, SIZE.height as usize);
+    let style = MonoTextStyle::new(&FONT_6X10, Rgb565::WHITE);
+
+    c.bench_function("frame", |b| {
+        b.iter(|| {
+            let mut fb = FrameBuf::new(&mut backend, width, height);
+            fb.clear(Rgb565::new(20, 0, 0)).unwrap();
+            let center = fb.bounding_box().center();
+            Text::with_alignment(
+                "1d 2:03:04\nAnalyzing Android.bp...\nSOC temp: 90°C (simulated)",
+                center - Point::new(0, 10),
+                style,
+                Alignment::Center,
+            )
+            .draw(&mut fb)
+            .unwrap();
+            glitch(&mut fb, &mut rng, 16, 0.25);
+            add_noise(&mut fb, &mut rng, Intensity::new(0.02));
+
+            let area = fb.bounding_box();
+            let mut lcd = FrameBuf::new(&mut lcd_backend, width, height);
+            lcd.fill_contiguous(&area, backend.pixels.iter().copied())
+                .unwrap();
+        })
+    });
+}
+
+criterion_group!(benches, glitches, frame);
+criterion_main!(benches);
@@ -0,0 +1,62 @@
+//! Noise over a whole ST7735 sized frame, compared to asking the RNG for every decision
+//! separately like it used to be done
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use embedded_graphics::{
+    geometry::Size,
+    pixelcolor::{Rgb565, RgbColor},
+};
+use embedded_graphics_framebuf::FrameBuf;
+use evil_android::{
+    effects::add_noise,
+    framebuffer::SliceFrameBufferBackend,
+    intensity::Intensity,
+    noise::{Flavor, NoiseEffect},
+    region::Region,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const SIZE: Size = Size::new(160, 128);
+
+/// Color noise the way it was before the RNG only seeded a cheaper generator for each row
+fn per_call_noise(pixels: &mut [Rgb565], rng: &mut impl Rng, intensity: f32) {
+    for pixel in pixels {
+        if rng.gen::<f32>() < intensity {
+            *pixel = Rgb565::new(
+                (rng.next_u32() % 32) as u8,
+                (rng.next_u32() % 64) as u8,
+                (rng.next_u32() % 32) as u8,
+            );
+        }
+    }
+}
+
+fn noise(c: &mut Criterion) {
+    let mut backend = SliceFrameBufferBackend::new(SIZE, Rgb565::BLACK);
+    let mut rng = StdRng::seed_from_u64(0);
+    let (width, height) = (SIZE.width as usize, SIZE.height as usize);
+
+    for intensity in [0.1, 1.0] {
+        c.bench_function(&format!("add_noise {intensity}"), |b| {
+            b.iter(|| {
+                let mut fb = FrameBuf::new(&mut backend, width, height);
+                add_noise(&mut fb, &mut rng, Intensity::new(intensity));
+            })
+        });
+        c.bench_function(&format!("per call noise {intensity}"), |b| {
+            b.iter(|| per_call_noise(&mut backend.pixels, &mut rng, intensity))
+        });
+    }
+    for flavor in [Flavor::Monochrome, Flavor::Sensor, Flavor::SaltAndPepper] {
+        let effect = NoiseEffect::new(flavor, Intensity::new(1.0));
+        c.bench_function(&format!("{flavor:?} noise"), |b| {
+            b.iter(|| {
+                let mut fb = FrameBuf::new(&mut backend, width, height);
+                effect.apply(&mut fb, &mut rng, &Region::Full);
+            })
+        });
+    }
+}
+
+criterion_group!(benches, noise);
+criterion_main!(benches);
@@ -1,22 +1,50 @@
-use std::{env, path::{Path, PathBuf}, process::Command};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use serde::Deserialize;
 
+/// Images converted to Rgb565 and a transparency mask at build time, each with the prefix of
+/// the env vars that tell the program where to find the results, and the feature that embeds it
+const IMAGES: &[(&str, &str, &str)] = &[("data/dumpster-fire.png", "DUMPSTER_FIRE", "fire")];
+const IMAGE_CONVERTER: &str = "scripts/to-rgb565.py";
+
 #[derive(Deserialize)]
 struct ImageSize {
     width: usize,
     height: usize,
 }
 
+/// 64-bit FNV-1a, plenty to tell whether anything changed since the last build
+fn content_hash(parts: &[&[u8]]) -> u64 {
+    parts
+        .iter()
+        .flat_map(|part| part.iter())
+        .fold(0xcbf29ce484222325, |hash, &byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+        })
+}
+
+/// Converts `input` unless OUT_DIR still has what came out of the same image and converter
+/// last time
 fn preprocess_image(input: &Path, output_env: &str) {
-    let src_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR env var not set"));
+    let src_dir =
+        PathBuf::from(env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR env var not set"));
     let input = src_dir.join(input);
-    let generator_path = src_dir.join("scripts/to-rgb565.py");
+    let generator_path = src_dir.join(IMAGE_CONVERTER);
+    println!("cargo::rerun-if-changed={}", input.display());
 
     let input_relative = input
         .canonicalize()
         .expect("failed to canonicalize input path")
-        .strip_prefix(src_dir.canonicalize().expect("failed to canonicalize src_dir path"))
+        .strip_prefix(
+            src_dir
+                .canonicalize()
+                .expect("failed to canonicalize src_dir path"),
+        )
         .expect("input not relative to source dir")
         .to_owned();
 
@@ -25,7 +53,50 @@ fn preprocess_image(input: &Path, output_env: &str) {
         .join(input_relative);
     let output_color = output_base.with_extension("rgb565");
     let output_mask = output_base.with_extension("mask");
+    let output_size = output_base.with_extension("json");
+    let output_hash = output_base.with_extension("hash");
+
+    let hash = content_hash(&[
+        &fs::read(&input).expect("failed to read input image"),
+        &fs::read(&generator_path).expect("failed to read generator"),
+    ])
+    .to_string();
+    let cached = fs::read_to_string(&output_hash).is_ok_and(|cached| cached == hash)
+        && [&output_color, &output_mask, &output_size]
+            .iter()
+            .all(|path| path.exists());
+    let size_json = match cached {
+        true => fs::read_to_string(&output_size).expect("failed to read cached image size"),
+        false => {
+            let size_json = run_generator(&generator_path, &input, &output_color, &output_mask);
+            fs::write(&output_size, &size_json).expect("failed to cache image size");
+            // Last, so that a conversion cut short doesn't look finished
+            fs::write(&output_hash, &hash).expect("failed to write image hash");
+            size_json
+        }
+    };
+    let ImageSize { width, height } =
+        serde_json::from_str::<ImageSize>(&size_json).expect("invalid generator output format");
+
+    println!(
+        "cargo::rustc-env={output_env}_MASK={mask}",
+        mask = output_mask.display()
+    );
+    println!(
+        "cargo::rustc-env={output_env}_COLOR={color}",
+        color = output_color.display()
+    );
+    println!("cargo::rustc-env={output_env}_WIDTH={width}");
+    println!("cargo::rustc-env={output_env}_HEIGHT={height}");
+}
 
+/// Returns the size of the image, as JSON
+fn run_generator(
+    generator_path: &Path,
+    input: &Path,
+    output_color: &Path,
+    output_mask: &Path,
+) -> String {
     let output = Command::new(generator_path)
         .args([
             "--input",
@@ -39,28 +110,127 @@ fn preprocess_image(input: &Path, output_env: &str) {
         .output()
         .expect("generator exec failed");
     assert!(output.status.success(), "generator failed");
-    let output = output.stdout;
+    String::from_utf8(output.stdout).expect("generator output not valid utf-8")
+}
 
-    let ImageSize { width, height } = serde_json::from_str::<ImageSize>(
-        &String::from_utf8(output).expect("generator output not valid utf-8"),
-    )
-    .expect("invalid generator output format");
+/// Chip-specific code is selected with `#[cfg(esp32)]`, `#[cfg(esp32c3)]` or `#[cfg(esp32s3)]`,
+/// based on the same MCU env var esp-idf-sys uses
+fn emit_chip_cfg() {
+    println!("cargo::rustc-check-cfg=cfg(esp32, esp32c3, esp32s3)");
+    println!("cargo::rerun-if-env-changed=MCU");
+    if env::var("CARGO_CFG_TARGET_OS").as_deref() != Ok("espidf") {
+        return;
+    }
+    let mcu = env::var("MCU").unwrap_or_else(|_| "esp32".to_owned());
+    match mcu.as_str() {
+        "esp32" | "esp32c3" | "esp32s3" => println!("cargo::rustc-cfg={mcu}"),
+        _ => panic!("unsupported MCU: {mcu}"),
+    }
+}
 
+/// Linker args and cfgs of ESP-IDF, passed on from esp-idf-sys. `embuild::espidf` doesn't build
+/// for the desktop, and `#[cfg]`s of the target aren't set for build scripts, hence the feature.
+fn emit_esp_idf_env() {
+    if env::var("CARGO_CFG_TARGET_OS").as_deref() != Ok("espidf") {
+        return;
+    }
+    #[cfg(feature = "esp32")]
+    embuild::espidf::sysenv::output();
+    #[cfg(not(feature = "esp32"))]
+    panic!("ESP builds need the esp32 feature, e.g. cargo run --release --features esp32");
+}
+
+/// Output of a git command, None if it fails or there's no git at all
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+/// `YYYY-MM-DD` of a Unix timestamp, in UTC
+fn date(secs: u64) -> String {
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = (secs / 86400) as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Commit, build date and enabled features, so that deployed androids can be identified. See
+/// src/version.rs.
+fn emit_version_info() {
+    // Not built from a checkout, e.g. from a source tarball. Uncommitted changes aren't marked,
+    // committing them wouldn't get this to run again and drop the mark.
+    let commit = git(&["rev-parse", "--short=8", "HEAD"]).unwrap_or_else(|| "unknown".to_owned());
+    println!("cargo::rustc-env=EVIL_ANDROID_GIT_COMMIT={commit}");
+    // Commits move the branch HEAD points at, checkouts move HEAD itself, and staging changes
+    // the index
+    if let Some(git_dir) = git(&["rev-parse", "--git-dir"]).map(PathBuf::from) {
+        println!("cargo::rerun-if-changed={}", git_dir.join("HEAD").display());
+        println!(
+            "cargo::rerun-if-changed={}",
+            git_dir.join("index").display()
+        );
+        if let Some(branch) = git(&["symbolic-ref", "HEAD"]) {
+            println!("cargo::rerun-if-changed={}", git_dir.join(branch).display());
+        }
+    }
+
+    // Of the last time this ran. Reproducible builds pin it with SOURCE_DATE_EPOCH.
+    println!("cargo::rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    let secs = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|secs| secs.parse().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("clock before 1970")
+                .as_secs()
+        });
+    println!("cargo::rustc-env=EVIL_ANDROID_BUILD_DATE={}", date(secs));
+
+    // Named as in Cargo.toml, CARGO_FEATURE_* vars have them upper-cased and with `_` for `-`
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR env var not set");
+    let manifest: toml::Table = fs::read_to_string(Path::new(&manifest_dir).join("Cargo.toml"))
+        .expect("failed to read Cargo.toml")
+        .parse()
+        .expect("invalid Cargo.toml");
+    let mut features: Vec<&str> = manifest
+        .get("features")
+        .and_then(toml::Value::as_table)
+        .into_iter()
+        .flat_map(|features| features.keys())
+        .map(String::as_str)
+        .filter(|name| {
+            let var = format!("CARGO_FEATURE_{}", name.to_uppercase().replace('-', "_"));
+            env::var_os(var).is_some()
+        })
+        .collect();
+    features.sort_unstable();
     println!(
-        "cargo::rustc-env={output_env}_MASK={mask}",
-        mask = output_mask.display()
-    );
-    println!(
-        "cargo::rustc-env={output_env}_COLOR={color}",
-        color = output_color.display()
+        "cargo::rustc-env=EVIL_ANDROID_FEATURES={}",
+        features.join(",")
     );
-    println!("cargo::rustc-env={output_env}_WIDTH={width}");
-    println!("cargo::rustc-env={output_env}_HEIGHT={height}");
 }
 
 fn main() {
-    preprocess_image(&Path::new("data/dumpster-fire.png"), "DUMPSTER_FIRE");
-
-    // I give up, just comment this out for non-esp builds
-    //embuild::espidf::sysenv::output();
+    println!("cargo::rerun-if-changed={IMAGE_CONVERTER}");
+    for (input, output_env, feature) in IMAGES {
+        // No need for Python in builds that leave the image out
+        let feature_env = format!("CARGO_FEATURE_{}", feature.to_uppercase());
+        if env::var_os(feature_env).is_some() {
+            preprocess_image(Path::new(input), output_env);
+        }
+    }
+    emit_chip_cfg();
+    emit_version_info();
+    emit_esp_idf_env();
 }
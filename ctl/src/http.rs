@@ -0,0 +1,74 @@
+use std::io::Read;
+
+use anyhow::{bail, Result};
+
+use crate::Device;
+
+/// The HTTP server of an android on WiFi
+pub struct Http {
+    /// `http://<host>`, without a trailing slash
+    base: String,
+    /// EVIL_ANDROID_HTTP_TOKEN the android was built with, for console commands and uploads
+    token: Option<String>,
+}
+
+impl Http {
+    pub fn new(host: &str, token: Option<String>) -> Self {
+        let base = match host.contains("://") {
+            true => host.trim_end_matches('/').to_owned(),
+            false => format!("http://{}", host.trim_end_matches('/')),
+        };
+        Self { base, token }
+    }
+
+    fn authorized(&self, request: ureq::Request) -> ureq::Request {
+        match &self.token {
+            Some(token) => request.set("Authorization", &format!("Bearer {token}")),
+            None => request,
+        }
+    }
+}
+
+/// Error statuses fail with the body, which says what went wrong
+fn check(response: Result<ureq::Response, ureq::Error>) -> Result<ureq::Response> {
+    match response {
+        Ok(response) => Ok(response),
+        Err(ureq::Error::Status(status, response)) => {
+            let body = response.into_string().unwrap_or_default();
+            bail!("HTTP {status}: {}", body.trim())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+impl Device for Http {
+    fn execute(&mut self, command: &str) -> Result<String> {
+        let url = format!("{}/console", self.base);
+        let request = self.authorized(ureq::post(&url));
+        Ok(check(request.send_string(command))?.into_string()?)
+    }
+
+    fn screenshot(&mut self) -> Result<Vec<u8>> {
+        let url = format!("{}/screenshot.png", self.base);
+        let mut png = Vec::new();
+        check(ureq::get(&url).call())?
+            .into_reader()
+            .read_to_end(&mut png)?;
+        Ok(png)
+    }
+
+    fn logs(&mut self) -> Result<()> {
+        print!("{}", self.execute("dump")?);
+        Ok(())
+    }
+
+    fn push(&mut self, name: &str, data: &[u8], sd_card: bool) -> Result<()> {
+        let dir = match sd_card {
+            true => "sdcard",
+            false => "storage",
+        };
+        let url = format!("{}/{dir}/{name}", self.base);
+        check(self.authorized(ureq::put(&url)).send_bytes(data))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+//! `evil-androidctl`: controls an android from the PC, over its serial console or, with WiFi,
+//! over HTTP
+
+use std::{fs, path::Path};
+
+use anyhow::{bail, ensure, Context, Result};
+
+mod http;
+mod serial;
+
+const USAGE: &str = "\
+usage: evil-androidctl (--serial <port> [--baud <n>] | --http <host> [--token <token>])
+                      <command> [args]
+
+commands:
+  message [text]        replaces the message under the timer, without text brings it back
+  pause                 pauses or resumes
+  theme [name]          switches to a theme, or lists them
+  send <command>        runs any serial console command, 'send help' lists them
+  screenshot <out.png>  saves what's on the LCD
+  logs                  follows the log over serial, prints what's buffered over HTTP
+  push <file> [--sd]    copies a file to flash storage, or to the SD card. HTTP only. Files
+                        there are read at boot.
+
+<host> is an address or a name like evil-android-1a2b3c.local. <token> is the
+EVIL_ANDROID_HTTP_TOKEN it was built with, taken from the environment by default. Everything
+but screenshots needs it over HTTP.
+";
+
+/// An android, whichever way it's reached
+trait Device {
+    /// Runs a serial console command, returns what it printed
+    fn execute(&mut self, command: &str) -> Result<String>;
+    /// PNG of what's on the LCD
+    fn screenshot(&mut self) -> Result<Vec<u8>>;
+    /// Prints the log until interrupted, or what's buffered of it if that's all there is
+    fn logs(&mut self) -> Result<()>;
+    /// Writes `data` to `name` on flash storage, or on the SD card
+    fn push(&mut self, name: &str, data: &[u8], sd_card: bool) -> Result<()>;
+}
+
+fn connect(args: &mut Vec<String>) -> Result<Box<dyn Device>> {
+    let mut take = |flag: &str| -> Result<Option<String>> {
+        let Some(index) = args.iter().position(|arg| arg == flag) else {
+            return Ok(None);
+        };
+        args.remove(index);
+        ensure!(index < args.len(), "{flag} needs a value");
+        Ok(Some(args.remove(index)))
+    };
+    let baud = match take("--baud")? {
+        Some(baud) => baud.parse().context("invalid --baud")?,
+        None => serial::DEFAULT_BAUD,
+    };
+    match (take("--serial")?, take("--http")?) {
+        (Some(port), None) => Ok(Box::new(serial::Serial::open(&port, baud)?)),
+        (None, Some(host)) => {
+            let token = take("--token")?.or_else(|| std::env::var("EVIL_ANDROID_HTTP_TOKEN").ok());
+            Ok(Box::new(http::Http::new(&host, token)))
+        }
+        _ => bail!("either --serial or --http is needed\n\n{USAGE}"),
+    }
+}
+
+fn main() -> Result<()> {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() || args.iter().any(|arg| arg == "--help") {
+        print!("{USAGE}");
+        return Ok(());
+    }
+    let mut device = connect(&mut args)?;
+    let Some((command, rest)) = args.split_first() else {
+        bail!("no command given\n\n{USAGE}");
+    };
+    let output = match (command.as_str(), rest) {
+        ("message", text) => device.execute(&format!("message {}", text.join(" ")))?,
+        ("pause", []) => device.execute("pause")?,
+        ("theme", []) => device.execute("theme")?,
+        ("theme", [name]) => device.execute(&format!("theme {name}"))?,
+        ("send", command) if !command.is_empty() => device.execute(&command.join(" "))?,
+        ("screenshot", [out]) => {
+            fs::write(out, device.screenshot()?).with_context(|| format!("cannot write {out}"))?;
+            String::new()
+        }
+        ("logs", []) => {
+            device.logs()?;
+            String::new()
+        }
+        ("push", [file, flags @ ..]) if flags.iter().all(|flag| flag == "--sd") => {
+            let data = fs::read(file).with_context(|| format!("cannot read {file}"))?;
+            let name = Path::new(file)
+                .file_name()
+                .context("not a file")?
+                .to_string_lossy();
+            device.push(&name, &data, !flags.is_empty())?;
+            String::new()
+        }
+        _ => bail!("invalid command: {}\n\n{USAGE}", args.join(" ")),
+    };
+    print!("{output}");
+    Ok(())
+}
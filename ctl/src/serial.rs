@@ -0,0 +1,183 @@
+use std::{
+    collections::VecDeque,
+    fs::{File, OpenOptions},
+    io::{Read, Write},
+    process::Command,
+};
+
+use anyhow::{bail, ensure, Context, Result};
+use embedded_graphics::pixelcolor::{Rgb565, Rgb888, RgbColor};
+use evil_android_frame_codec::{Chunk, Decoder, Header, Splitter};
+
+use crate::Device;
+
+pub const DEFAULT_BAUD: u32 = 115200;
+/// Half-second reads with nothing in them before a screenshot gives up
+const MAX_QUIET_READS: usize = 10;
+
+/// What comes in over the port
+enum Incoming {
+    Text(Vec<u8>),
+    Packet(Header, Vec<u8>),
+    /// Nothing for a while
+    Quiet,
+}
+
+/// The serial console of an android, with its log and streamed frames coming in between
+/// replies
+pub struct Serial<R, W> {
+    input: R,
+    output: W,
+    splitter: Splitter,
+    /// Split off the last read, not taken yet
+    incoming: VecDeque<Incoming>,
+}
+
+impl<R, W> Serial<R, W> {
+    fn new(input: R, output: W) -> Self {
+        Self {
+            input,
+            output,
+            splitter: Splitter::default(),
+            incoming: VecDeque::new(),
+        }
+    }
+}
+
+impl Serial<File, File> {
+    pub fn open(path: &str, baud: u32) -> Result<Self> {
+        // Raw, so that frames come through intact. Reads give up after half a second of
+        // silence, which is how replies end.
+        let status = Command::new("stty")
+            .args(["-F", path, "raw", "-echo", &baud.to_string()])
+            .args(["min", "0", "time", "5"])
+            .status()
+            .context("cannot run stty")?;
+        ensure!(status.success(), "stty failed to set up {path}: {status}");
+        let port = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .with_context(|| format!("cannot open {path}"))?;
+        Ok(Self::new(port.try_clone()?, port))
+    }
+}
+
+impl<R: Read, W: Write> Serial<R, W> {
+    fn send(&mut self, command: &str) -> Result<()> {
+        writeln!(self.output, "{command}").context("cannot write to the serial port")
+    }
+
+    fn receive(&mut self) -> Result<Incoming> {
+        let mut buf = [0; 4096];
+        while self.incoming.is_empty() {
+            let len = self.input.read(&mut buf)?;
+            if len == 0 {
+                return Ok(Incoming::Quiet);
+            }
+            let incoming = &mut self.incoming;
+            self.splitter.push(&buf[..len], |chunk| {
+                incoming.push_back(match chunk {
+                    Chunk::Text(bytes) => Incoming::Text(bytes.to_vec()),
+                    Chunk::Packet(header, payload) => Incoming::Packet(header, payload.to_vec()),
+                })
+            });
+        }
+        Ok(self.incoming.pop_front().unwrap_or(Incoming::Quiet))
+    }
+}
+
+impl<R: Read, W: Write> Device for Serial<R, W> {
+    /// Whatever got printed until the port went quiet, log lines included
+    fn execute(&mut self, command: &str) -> Result<String> {
+        self.send(command)?;
+        let mut reply = Vec::new();
+        loop {
+            match self.receive()? {
+                Incoming::Text(text) => reply.extend_from_slice(&text),
+                Incoming::Packet(..) => {}
+                Incoming::Quiet => return Ok(String::from_utf8_lossy(&reply).into_owned()),
+            }
+        }
+    }
+
+    /// The first whole frame streamed, there's no other way to get one over serial
+    fn screenshot(&mut self) -> Result<Vec<u8>> {
+        self.send("stream on")?;
+        let mut decoder = Decoder::default();
+        let mut quiet_reads = 0;
+        let frame = loop {
+            match self.receive()? {
+                Incoming::Text(_) => {}
+                Incoming::Packet(header, payload) => {
+                    // Joined halfway, the next key frame will do
+                    if decoder.decode(&header, &payload).is_err() {
+                        continue;
+                    }
+                    if let Some((size, pixels)) = decoder.frame() {
+                        break (size, pixels.collect::<Vec<Rgb565>>());
+                    }
+                }
+                Incoming::Quiet if quiet_reads < MAX_QUIET_READS => quiet_reads += 1,
+                Incoming::Quiet => bail!("no frames came in, is the android asleep?"),
+            }
+        };
+        self.send("stream off")?;
+        let (size, pixels) = frame;
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, size.width, size.height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let rgb: Vec<u8> = pixels
+            .into_iter()
+            .flat_map(|pixel| {
+                let pixel = Rgb888::from(pixel);
+                [pixel.r(), pixel.g(), pixel.b()]
+            })
+            .collect();
+        encoder.write_header()?.write_image_data(&rgb)?;
+        Ok(png)
+    }
+
+    fn logs(&mut self) -> Result<()> {
+        loop {
+            if let Incoming::Text(text) = self.receive()? {
+                let mut stdout = std::io::stdout().lock();
+                stdout.write_all(&text)?;
+                stdout.flush()?;
+            }
+        }
+    }
+
+    fn push(&mut self, _name: &str, _data: &[u8], _sd_card: bool) -> Result<()> {
+        bail!("pushing files needs --http, the serial console only takes commands")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_graphics::geometry::Size;
+    use evil_android_frame_codec::Encoder;
+
+    use super::*;
+
+    #[test]
+    fn takes_screenshots_between_log_lines() {
+        let size = Size::new(4, 2);
+        let pixels = [Rgb565::RED; 8];
+        let mut input = b"I (1234) evil_android: hello\n".to_vec();
+        Encoder::default().encode(size, &pixels, &mut input);
+        input.extend_from_slice(b"theme: dark, available: dark, light\n");
+        // Joined halfway through a packet
+        let input = [&[0x00, 0xef, 0x01][..], &input].concat();
+        let mut serial = Serial::new(input.as_slice(), Vec::new());
+        let png = serial.screenshot().unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+        assert_eq!(serial.output, b"stream on\nstream off\n");
+        assert_eq!(
+            serial.execute("theme").unwrap(),
+            "theme: dark, available: dark, light\n"
+        );
+        assert!(serial.push("config.toml", b"", false).is_err());
+    }
+}
@@ -0,0 +1,486 @@
+//! Compact frames for slow links like a serial port. Every packet is a header followed by
+//! PackBits-style run-length encoded pixels, either of a whole frame (a key frame) or of its
+//! XOR with the previous one (a delta), which is mostly zeros when little changes.
+//!
+//! Packets start with a byte no text ever contains, so they can share the link with log lines
+//! and console commands. Readers skip anything up to `MAGIC`, and a checksum catches packets
+//! that got text mixed into them.
+#![cfg_attr(not(test), no_std)]
+#![deny(clippy::std_instead_of_core, clippy::std_instead_of_alloc)]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use anyhow::{bail, Result};
+use embedded_graphics::{
+    geometry::Size,
+    pixelcolor::{raw::RawU16, Rgb565},
+    prelude::RawData,
+};
+
+pub const MAGIC: [u8; 2] = [0x00, 0xef];
+/// Magic, kind, width and height as u16, payload length as u32 and checksum as u16, all
+/// big-endian
+pub const HEADER_SIZE: usize = 13;
+/// More than any LCD the android drives, and a frame that size still fits in RAM
+pub const MAX_PIXELS: usize = 320 * 240;
+/// So that a reader that missed a packet, or joined halfway, doesn't wait long for a picture
+const KEY_FRAME_INTERVAL: usize = 30;
+/// Control bytes up to this start literal pixels, the rest start runs
+const MAX_LITERAL: usize = 128;
+const MIN_RUN: usize = 2;
+const MAX_RUN: usize = 256 - MAX_LITERAL + MIN_RUN - 1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    Key,
+    Delta,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Header {
+    pub kind: Kind,
+    pub size: Size,
+    pub payload_len: usize,
+    pub checksum: u16,
+}
+
+impl Header {
+    pub fn parse(bytes: &[u8; HEADER_SIZE]) -> Result<Self> {
+        if bytes[..2] != MAGIC {
+            bail!("not a frame packet");
+        }
+        let kind = match bytes[2] {
+            0 => Kind::Key,
+            1 => Kind::Delta,
+            other => bail!("unknown packet kind: {other}"),
+        };
+        let width = u16::from_be_bytes([bytes[3], bytes[4]]);
+        let height = u16::from_be_bytes([bytes[5], bytes[6]]);
+        let pixels = usize::from(width) * usize::from(height);
+        if pixels == 0 || pixels > MAX_PIXELS {
+            bail!("invalid frame size: {width}x{height}");
+        }
+        let payload_len = u32::from_be_bytes([bytes[7], bytes[8], bytes[9], bytes[10]]) as usize;
+        // Worst case is all literals, one control byte per MAX_LITERAL pixels
+        if payload_len > pixels * 2 + pixels.div_ceil(MAX_LITERAL) {
+            bail!("payload too long for {width}x{height}: {payload_len}");
+        }
+        Ok(Self {
+            kind,
+            size: Size::new(width.into(), height.into()),
+            payload_len,
+            checksum: u16::from_be_bytes([bytes[11], bytes[12]]),
+        })
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend(MAGIC);
+        out.push(match self.kind {
+            Kind::Key => 0,
+            Kind::Delta => 1,
+        });
+        out.extend((self.size.width as u16).to_be_bytes());
+        out.extend((self.size.height as u16).to_be_bytes());
+        out.extend((self.payload_len as u32).to_be_bytes());
+        out.extend(self.checksum.to_be_bytes());
+    }
+}
+
+/// Fletcher-16
+pub fn checksum(data: &[u8]) -> u16 {
+    let (mut a, mut b) = (0u16, 0u16);
+    for &byte in data {
+        a = (a + u16::from(byte)) % 255;
+        b = (b + a) % 255;
+    }
+    b << 8 | a
+}
+
+fn raw(pixel: Rgb565) -> u16 {
+    RawU16::from(pixel).into_inner()
+}
+
+fn push_words(out: &mut Vec<u8>, words: &[u16]) {
+    for word in words {
+        out.extend(word.to_be_bytes());
+    }
+}
+
+fn encode_rle(words: &[u16], out: &mut Vec<u8>) {
+    let mut i = 0;
+    while i < words.len() {
+        let run = words[i..]
+            .iter()
+            .take(MAX_RUN)
+            .take_while(|&&w| w == words[i])
+            .count();
+        if run >= MIN_RUN {
+            out.push((MAX_LITERAL + run - MIN_RUN) as u8);
+            push_words(out, &words[i..i + 1]);
+            i += run;
+            continue;
+        }
+        // Up to where the next run starts
+        let start = i;
+        while i < words.len() && i - start < MAX_LITERAL {
+            if words.get(i + 1) == Some(&words[i]) && i > start {
+                break;
+            }
+            i += 1;
+        }
+        out.push((i - start - 1) as u8);
+        push_words(out, &words[start..i]);
+    }
+}
+
+/// Fills the whole of `out`, which must come out exactly as long as the payload says
+fn decode_rle(
+    mut data: &[u8],
+    out: &mut [u16],
+    mut apply: impl FnMut(&mut u16, u16),
+) -> Result<()> {
+    let mut pos = 0;
+    let take_word = |data: &mut &[u8]| -> Result<u16> {
+        let Some((word, rest)) = data.split_first_chunk::<2>() else {
+            bail!("truncated payload");
+        };
+        *data = rest;
+        Ok(u16::from_be_bytes(*word))
+    };
+    while let Some((&control, rest)) = data.split_first() {
+        data = rest;
+        let control = usize::from(control);
+        let (len, run) = if control < MAX_LITERAL {
+            (control + 1, false)
+        } else {
+            (control - MAX_LITERAL + MIN_RUN, true)
+        };
+        let Some(target) = out.get_mut(pos..pos + len) else {
+            bail!("payload has more pixels than the frame");
+        };
+        let mut word = take_word(&mut data)?;
+        for (i, pixel) in target.iter_mut().enumerate() {
+            if !run && i > 0 {
+                word = take_word(&mut data)?;
+            }
+            apply(pixel, word);
+        }
+        pos += len;
+    }
+    if pos != out.len() {
+        bail!("payload has fewer pixels than the frame");
+    }
+    Ok(())
+}
+
+/// Turns frames into packets, as deltas of the ones before when possible
+#[derive(Default)]
+pub struct Encoder {
+    previous: Vec<u16>,
+    size: Size,
+    since_key_frame: usize,
+}
+
+impl Encoder {
+    /// Appends a packet with `pixels` to `out`
+    pub fn encode(&mut self, size: Size, pixels: &[Rgb565], out: &mut Vec<u8>) {
+        let kind = if size != self.size || self.since_key_frame >= KEY_FRAME_INTERVAL {
+            self.since_key_frame = 0;
+            self.size = size;
+            Kind::Key
+        } else {
+            self.since_key_frame += 1;
+            Kind::Delta
+        };
+        let words: Vec<u16> = match kind {
+            Kind::Key => pixels.iter().map(|&p| raw(p)).collect(),
+            Kind::Delta => pixels
+                .iter()
+                .zip(&self.previous)
+                .map(|(&p, &prev)| raw(p) ^ prev)
+                .collect(),
+        };
+        self.previous.clear();
+        self.previous.extend(pixels.iter().map(|&p| raw(p)));
+
+        let mut payload = Vec::new();
+        encode_rle(&words, &mut payload);
+        Header {
+            kind,
+            size,
+            payload_len: payload.len(),
+            checksum: checksum(&payload),
+        }
+        .write(out);
+        out.extend(payload);
+    }
+
+    /// Makes the next packet a key frame, e.g. after a reader reconnected
+    pub fn reset(&mut self) {
+        self.size = Size::zero();
+    }
+}
+
+/// Piece of what comes in over a link that carries packets along with text
+#[derive(Debug, PartialEq)]
+pub enum Chunk<'a> {
+    Text(&'a [u8]),
+    /// Whole, but the checksum is for the decoder to check
+    Packet(Header, &'a [u8]),
+}
+
+/// Splits what comes in over a link into text and packets, however it's cut into reads. Text
+/// is handed out right away, packets once they're whole. Whatever starts like a packet but
+/// isn't one is dropped up to the next `MAGIC`, so that joining halfway costs a packet at most.
+#[derive(Default)]
+pub struct Splitter {
+    /// From the first byte of a packet on
+    pending: Vec<u8>,
+    /// Of the pending packet, once it's all in
+    header: Option<Header>,
+}
+
+impl Splitter {
+    pub fn push(&mut self, bytes: &[u8], mut on_chunk: impl FnMut(Chunk<'_>)) {
+        self.feed(bytes, &mut on_chunk);
+    }
+
+    fn feed<F: FnMut(Chunk<'_>)>(&mut self, mut bytes: &[u8], on_chunk: &mut F) {
+        while !bytes.is_empty() {
+            if self.pending.is_empty() {
+                let text_len = bytes
+                    .iter()
+                    .position(|&b| b == MAGIC[0])
+                    .unwrap_or(bytes.len());
+                if text_len > 0 {
+                    on_chunk(Chunk::Text(&bytes[..text_len]));
+                    bytes = &bytes[text_len..];
+                    continue;
+                }
+            }
+            let wanted = HEADER_SIZE + self.header.map_or(0, |header| header.payload_len);
+            let (taken, rest) = bytes.split_at((wanted - self.pending.len()).min(bytes.len()));
+            self.pending.extend_from_slice(taken);
+            bytes = rest;
+            self.advance(on_chunk);
+        }
+    }
+
+    fn advance<F: FnMut(Chunk<'_>)>(&mut self, on_chunk: &mut F) {
+        if self.pending.len() >= MAGIC.len() && self.pending[..MAGIC.len()] != MAGIC {
+            return self.resync(on_chunk);
+        }
+        if self.header.is_none() {
+            let Some(header) = self.pending.first_chunk::<HEADER_SIZE>() else {
+                return;
+            };
+            match Header::parse(header) {
+                Ok(header) => self.header = Some(header),
+                Err(_) => return self.resync(on_chunk),
+            }
+        }
+        if let Some(header) = self.header {
+            if self.pending.len() == HEADER_SIZE + header.payload_len {
+                on_chunk(Chunk::Packet(header, &self.pending[HEADER_SIZE..]));
+                self.pending.clear();
+                self.header = None;
+            }
+        }
+    }
+
+    /// Drops the first byte of what looked like a packet, and looks again at the rest
+    fn resync<F: FnMut(Chunk<'_>)>(&mut self, on_chunk: &mut F) {
+        let rest = self.pending.split_off(1);
+        self.pending.clear();
+        self.header = None;
+        self.feed(&rest, on_chunk);
+    }
+}
+
+/// Rebuilds frames from packets
+#[derive(Default)]
+pub struct Decoder {
+    size: Size,
+    pixels: Vec<u16>,
+    /// Deltas are garbage without the frame they apply to
+    has_key_frame: bool,
+}
+
+impl Decoder {
+    pub fn decode(&mut self, header: &Header, payload: &[u8]) -> Result<()> {
+        if payload.len() != header.payload_len || checksum(payload) != header.checksum {
+            // Whatever the next delta applies to got lost
+            self.has_key_frame = false;
+            bail!("corrupted packet");
+        }
+        let len = (header.size.width * header.size.height) as usize;
+        let result = match header.kind {
+            Kind::Key => {
+                self.size = header.size;
+                self.pixels.resize(len, 0);
+                decode_rle(payload, &mut self.pixels, |pixel, word| *pixel = word)
+            }
+            Kind::Delta if !self.has_key_frame => bail!("waiting for a key frame"),
+            Kind::Delta if header.size != self.size => bail!("delta of a different size"),
+            Kind::Delta => decode_rle(payload, &mut self.pixels, |pixel, word| *pixel ^= word),
+        };
+        self.has_key_frame = result.is_ok();
+        result
+    }
+
+    /// The last frame decoded, None until there's a whole one
+    pub fn frame(&self) -> Option<(Size, impl Iterator<Item = Rgb565> + '_)> {
+        self.has_key_frame.then(|| {
+            (
+                self.size,
+                self.pixels.iter().map(|&word| RawU16::new(word).into()),
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixels(words: &[u16]) -> Vec<Rgb565> {
+        words.iter().map(|&w| RawU16::new(w).into()).collect()
+    }
+
+    fn round_trip(words: &[u16]) {
+        let mut payload = Vec::new();
+        encode_rle(words, &mut payload);
+        let mut out = alloc::vec![0; words.len()];
+        decode_rle(&payload, &mut out, |pixel, word| *pixel = word).unwrap();
+        assert_eq!(out, words, "payload {payload:?}");
+    }
+
+    fn split(packet: &[u8]) -> (Header, &[u8]) {
+        let (header, payload) = packet.split_first_chunk::<HEADER_SIZE>().unwrap();
+        (Header::parse(header).unwrap(), payload)
+    }
+
+    #[test]
+    fn rle_round_trips() {
+        round_trip(&[1]);
+        round_trip(&[1, 2, 3]);
+        round_trip(&[5; 300]);
+        round_trip(&[1, 1, 2, 3, 3, 3, 4]);
+        let mixed: Vec<u16> = (0..1000).map(|i| if i % 7 < 3 { 9 } else { i }).collect();
+        round_trip(&mixed);
+    }
+
+    #[test]
+    fn rle_compresses_runs() {
+        let mut payload = Vec::new();
+        encode_rle(&[7; 129], &mut payload);
+        assert_eq!(payload, [255, 0, 7]);
+        payload.clear();
+        encode_rle(&[1, 2, 2], &mut payload);
+        assert_eq!(payload, [0, 0, 1, 128, 0, 2]);
+    }
+
+    #[test]
+    fn encodes_deltas() {
+        let size = Size::new(4, 2);
+        let first = pixels(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        let second = pixels(&[1, 2, 3, 4, 5, 6, 7, 0xffff]);
+        let mut encoder = Encoder::default();
+        let mut decoder = Decoder::default();
+        assert!(decoder.frame().is_none());
+
+        for (frame, kind) in [(&first, Kind::Key), (&second, Kind::Delta)] {
+            let mut packet = Vec::new();
+            encoder.encode(size, frame, &mut packet);
+            let (header, payload) = split(&packet);
+            assert_eq!(header.kind, kind);
+            decoder.decode(&header, payload).unwrap();
+            let (decoded_size, decoded) = decoder.frame().unwrap();
+            assert_eq!(decoded_size, size);
+            assert_eq!(decoded.collect::<Vec<_>>(), *frame);
+        }
+
+        encoder.reset();
+        let mut packet = Vec::new();
+        encoder.encode(size, &second, &mut packet);
+        assert_eq!(split(&packet).0.kind, Kind::Key);
+    }
+
+    #[test]
+    fn rejects_corrupted_packets() {
+        let size = Size::new(2, 2);
+        let mut encoder = Encoder::default();
+        let mut decoder = Decoder::default();
+        let mut key = Vec::new();
+        encoder.encode(size, &pixels(&[1, 2, 3, 4]), &mut key);
+        let mut delta = Vec::new();
+        encoder.encode(size, &pixels(&[1, 2, 3, 5]), &mut delta);
+
+        // A delta before any key frame
+        let (header, payload) = split(&delta);
+        assert!(decoder.decode(&header, payload).is_err());
+
+        let mut corrupted = key.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        let (header, payload) = split(&corrupted);
+        assert!(decoder.decode(&header, payload).is_err());
+        assert!(decoder.frame().is_none());
+
+        let (header, payload) = split(&key);
+        decoder.decode(&header, payload).unwrap();
+        let (header, payload) = split(&delta);
+        decoder.decode(&header, payload).unwrap();
+    }
+
+    #[test]
+    fn splits_text_and_packets() {
+        let mut packet = Vec::new();
+        Encoder::default().encode(Size::new(2, 2), &pixels(&[1, 2, 3, 4]), &mut packet);
+        let mut stream = b"log line\n".to_vec();
+        // Joined halfway through a packet: the tail of its payload, then one that looks fine up
+        // to a header that doesn't parse
+        stream.extend_from_slice(&packet[HEADER_SIZE + 2..]);
+        stream.extend_from_slice(&packet[..8]);
+        stream.extend_from_slice(&[0xff; 5]);
+        stream.extend_from_slice(&packet);
+        stream.extend_from_slice(b"prompt> ");
+
+        for read_size in [1, 7, stream.len()] {
+            let mut splitter = Splitter::default();
+            let (mut text, mut packets) = (Vec::new(), Vec::new());
+            for read in stream.chunks(read_size) {
+                splitter.push(read, |chunk| match chunk {
+                    Chunk::Text(bytes) => text.extend_from_slice(bytes),
+                    Chunk::Packet(header, payload) => packets.push((header, payload.to_vec())),
+                });
+            }
+            let (header, payload) = split(&packet);
+            assert_eq!(packets, [(header, payload.to_vec())], "{read_size}");
+            let text = String::from_utf8_lossy(&text);
+            assert!(text.starts_with("log line\n"), "{text:?}");
+            assert!(text.ends_with("prompt> "), "{text:?}");
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_headers() {
+        let mut packet = Vec::new();
+        Encoder::default().encode(Size::new(2, 2), &pixels(&[0; 4]), &mut packet);
+        let header: [u8; HEADER_SIZE] = packet[..HEADER_SIZE].try_into().unwrap();
+        assert!(Header::parse(&header).is_ok());
+        let mut wrong_magic = header;
+        wrong_magic[1] = b'E';
+        assert!(Header::parse(&wrong_magic).is_err());
+        let mut wrong_kind = header;
+        wrong_kind[2] = 2;
+        assert!(Header::parse(&wrong_kind).is_err());
+        let mut too_big = header;
+        too_big[3..7].copy_from_slice(&[0xff; 4]);
+        assert!(Header::parse(&too_big).is_err());
+        let mut too_long = header;
+        too_long[7..11].copy_from_slice(&1000u32.to_be_bytes());
+        assert!(Header::parse(&too_long).is_err());
+    }
+}
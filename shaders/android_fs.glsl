@@ -0,0 +1,157 @@
+#version 140
+
+// https://www.shadertoy.com/view/McfcWB
+
+// Bottom-left corner and size of the drawn area, in window pixels
+uniform vec2 u_origin;
+uniform vec2 u_resolution;
+uniform vec3 u_left_eye_color;
+uniform vec3 u_right_eye_color;
+uniform sampler2D u_lcd_texture;
+uniform vec2 u_lcd_size;
+// Optional secondary display, drawn across the face instead of the eye LEDs
+uniform bool u_has_eyes_display;
+uniform vec2 u_eyes_display_size;
+uniform sampler2D u_eyes_texture;
+uniform bool u_show_overlay;
+uniform vec2 u_overlay_size;
+uniform float u_overlay_scale;
+uniform sampler2D u_overlay_texture;
+// 0 - classic, 1 - rusted, 2 - skeletal
+uniform int u_skin;
+uniform vec3 u_body_color;
+// 0..1, grows as the escalation progresses
+uniform float u_damage;
+uniform bool u_at_peak;
+// Radians, positive tilts to the android's left
+uniform float u_head_tilt;
+// Left and right arm, radians, positive is away from the body
+uniform vec2 u_arm_angles;
+uniform float u_eye_scale;
+
+out vec4 fragColor;
+
+const float PI = 3.1415926535897932384626433832795;
+
+vec2 translate(vec2 pos, vec2 delta) {
+    return pos + delta;
+}
+
+vec2 rotate(vec2 pos, float angle) {
+    return vec2(pos.x * cos(angle) - pos.y * sin(angle),
+                pos.y * cos(angle) + pos.x * sin(angle));
+}
+
+bool in_ellipse(vec2 pos, vec2 center, vec2 radii) {
+    vec2 delta = pos - center;
+    delta.y /= radii.y / radii.x;
+    return length(delta) < radii.x;
+}
+
+bool in_circle(vec2 pos, vec2 center, float radius) {
+    return distance(pos, center) < radius;
+}
+
+bool in_rect(vec2 pos,vec2 top_left, vec2 bottom_right) {
+    return !(pos.x < top_left.x || pos.x > bottom_right.x || pos.y < top_left.y || pos.y > bottom_right.y);
+}
+
+float hash(vec2 p) {
+    return fract(sin(dot(p, vec2(127.1, 311.7))) * 43758.5453);
+}
+
+void main() {
+    // Normalized pixel coordinates -200..200 on y, aspect ratio preserving on x
+    vec2 pos = gl_FragCoord.xy - u_origin - u_resolution / 2.0;
+    pos /= u_resolution.y;
+    pos *= 400.0;
+    
+    vec4 col_bg = vec4(1.0, 1.0, 1.0, 0.0);
+    vec4 col_android = vec4(u_body_color, 1.0);
+    
+    // Head rotates around the neck
+    vec2 neck = vec2(0, 41);
+    vec2 head_pos = rotate(pos - neck, u_head_tilt) + neck;
+
+    bool in_left_eye = in_circle(vec2(-head_pos.x, head_pos.y), vec2(42, 84), 8.0 * u_eye_scale);
+    bool in_right_eye = in_circle(vec2(head_pos.x, head_pos.y), vec2(42, 84), 8.0 * u_eye_scale);
+
+    float angle_rad = 29.0 * PI / 180.0;
+    bool in_android_antennas = in_rect(rotate(vec2(abs(head_pos.x), head_pos.y), angle_rad), vec2(-14, 86), vec2(-14+6, 86+66));
+    bool in_android_antenna_tips = in_circle(rotate(vec2(abs(head_pos.x), head_pos.y), angle_rad), vec2(-14+3, 86+66), 3.0);
+    bool in_android_head_base = in_ellipse(head_pos, neck, vec2(91, 84)) && head_pos.y > 41.0;
+    if (u_skin == 2 && u_at_peak && head_pos.x < 0.0) {
+        // Broken off
+        in_android_antennas = false;
+        in_android_antenna_tips = false;
+    }
+    bool in_android_head = in_android_head_base || in_android_antennas || in_android_antenna_tips;
+    
+    bool in_android_body_upper = in_rect(pos, vec2(-91, 35-142+22), vec2(-91+182, 35));
+    bool in_android_body_mid = in_rect(pos, vec2(-91+22, 35-142), vec2(-91+182-22, 35));
+    bool in_android_body_lower_corners = in_circle(vec2(abs(pos.x), pos.y), vec2(91-22, 35-142+22), 22.0);
+    bool in_android_body = in_android_body_upper || in_android_body_mid || in_android_body_lower_corners;
+    
+    // Arms swing around the shoulders, both mirrored to the right side
+    vec2 shoulder = vec2(145-24, 10);
+    float arm_angle = pos.x < 0.0 ? u_arm_angles.x : u_arm_angles.y;
+    vec2 arm_pos = rotate(vec2(abs(pos.x), pos.y) - shoulder, -arm_angle) + shoulder;
+    bool in_android_arms_upper = in_circle(arm_pos, shoulder, 24.0);
+    bool in_android_arms_mid= in_rect(arm_pos, vec2(145-48, 10-133+58), vec2(145, 10));
+    bool in_android_arms_lower = in_circle(arm_pos, vec2(145-24, 10-133+58), 24.0);
+    bool in_android_arms = in_android_arms_upper || in_android_arms_mid || in_android_arms_lower;
+    
+    bool in_android_legs_mid= in_rect(vec2(abs(pos.x), pos.y), vec2(65-48, 10-133-25), vec2(65, 10-25));
+    bool in_android_legs_lower = in_circle(vec2(abs(pos.x), pos.y), vec2(65-24, 10-133-25), 24.0);
+    bool in_android_legs = in_android_legs_mid || in_android_legs_lower;
+    
+    bool in_android = in_android_head || in_android_body || in_android_arms || in_android_legs;
+
+    if (u_skin == 1 && in_android && hash(floor(pos / 6.0)) < u_damage * 0.5) {
+        // Rust spots
+        col_android.rgb *= 0.6;
+    }
+    if (u_skin == 2 && in_android_body && abs(pos.x) < 60.0 && pos.y < 20.0
+            && mod(pos.y, 16.0) < 8.0 * u_damage) {
+        // Gaps between ribs
+        in_android = false;
+    }
+    
+    vec2 display_center = vec2(0, -35);
+    // Panels of any other size fit in the chest where a 160x128 one would be
+    vec2 display_area = vec2(160, 128) * 0.7;
+    float display_scale = min(display_area.x / u_lcd_size.x, display_area.y / u_lcd_size.y);
+    vec2 display_size = u_lcd_size * display_scale;
+    vec2 display_uv = (pos - (display_center - display_size / 2.0)) / display_size;
+    bool in_display = in_rect(pos, display_center - display_size / 2.0, display_center + display_size / 2.0);
+
+    // Tilts with the head
+    vec2 eyes_center = vec2(0, 84);
+    vec2 eyes_size = u_eyes_display_size * 0.9;
+    vec2 eyes_uv = (head_pos - (eyes_center - eyes_size / 2.0)) / eyes_size;
+    bool in_eyes_display = u_has_eyes_display
+        && in_rect(head_pos, eyes_center - eyes_size / 2.0, eyes_center + eyes_size / 2.0);
+
+    if (in_eyes_display) {
+        fragColor = texture2D(u_eyes_texture, eyes_uv);
+    } else if (in_left_eye) {
+        fragColor = vec4(u_left_eye_color, 1.0);
+    } else if (in_right_eye) {
+        fragColor = vec4(u_right_eye_color, 1.0);
+    } else if (in_display) {
+        fragColor = texture2D(u_lcd_texture, display_uv);
+    } else if (in_android) {
+        fragColor = col_android;
+    } else {
+        fragColor = col_bg;
+    }
+
+    // Debug overlay in top-left corner of the drawn area, in window pixel coordinates
+    vec2 overlay_pos = vec2(gl_FragCoord.x - u_origin.x,
+                            u_origin.y + u_resolution.y - gl_FragCoord.y) / u_overlay_scale;
+    if (u_show_overlay && overlay_pos.x < u_overlay_size.x && overlay_pos.y < u_overlay_size.y) {
+        vec2 overlay_uv = vec2(overlay_pos.x / u_overlay_size.x, 1.0 - overlay_pos.y / u_overlay_size.y);
+        vec4 overlay = texture2D(u_overlay_texture, overlay_uv);
+        fragColor = vec4(mix(fragColor.rgb, overlay.rgb, 0.8), 1.0);
+    }
+}
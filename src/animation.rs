@@ -0,0 +1,365 @@
+use std::{ops::Range, time::Duration};
+
+use anyhow::{bail, Result};
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::{Dimensions, Point, Size},
+    pixelcolor::{Rgb565, Rgb888},
+    prelude::RgbColor,
+    primitives::Rectangle,
+};
+use gif::{ColorOutput, DecodeOptions, DisposalMethod, Repeat};
+use serde::Deserialize;
+
+use crate::scene::{Canvas, Scene, SceneContext, GLITCH_LINE_PROBABILITY};
+
+/// What browsers do with frames that claim to take no time at all
+const DEFAULT_FRAME_DELAY: Duration = Duration::from_millis(100);
+
+/// `[animation]` in config.toml, for all GIFs listed in `scenes`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Seconds that GIFs looping forever play for. Others play as many times as they say, up
+    /// to that long.
+    #[serde(default = "Config::default_max_secs")]
+    max_secs: f32,
+    /// Max offset of glitched lines, in pixels. 0 leaves the animation as the artist drew it.
+    #[serde(default)]
+    glitch: usize,
+}
+
+impl Config {
+    fn default_max_secs() -> f32 {
+        10.0
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            max_secs: Self::default_max_secs(),
+            glitch: 0,
+        }
+    }
+}
+
+/// Animated GIF, decoded a frame at a time as it plays, so that long ones fit in RAM too
+pub struct Animation {
+    data: Vec<u8>,
+    size: Size,
+    /// None for looping forever
+    plays: Option<u32>,
+    config: Config,
+}
+
+/// Palette indices rather than RGBA, a quarter of the size
+fn decoder(data: &[u8]) -> Result<gif::Decoder<&[u8]>> {
+    let mut options = DecodeOptions::new();
+    options.set_color_output(ColorOutput::Indexed);
+    Ok(options.read_info(data)?)
+}
+
+/// Rows of a frame `height` tall, in the order they're stored in
+fn row_order(height: u16, interlaced: bool) -> impl Iterator<Item = u16> {
+    let passes: &[(u16, u16)] = match interlaced {
+        true => &[(0, 8), (4, 8), (2, 4), (1, 2)],
+        false => &[(0, 1)],
+    };
+    passes
+        .iter()
+        .flat_map(move |&(first, step)| (first..height).step_by(step.into()))
+}
+
+fn frame_delay(frame: &gif::Frame) -> Duration {
+    match frame.delay {
+        // Browsers treat anything this fast as a mistake, and so do we
+        0 | 1 => DEFAULT_FRAME_DELAY,
+        // In hundredths of a second
+        delay => Duration::from_millis(u64::from(delay) * 10),
+    }
+}
+
+impl Animation {
+    /// Up to `max_size`, that of the LCD, so that the frames fit in RAM
+    pub fn parse(data: &[u8], config: &Config, max_size: Size) -> Result<Self> {
+        if !(config.max_secs > 0.0 && config.max_secs.is_finite()) {
+            bail!("animation.max_secs must be positive");
+        }
+        let mut decoder = decoder(data)?;
+        let size = Size::new(decoder.width().into(), decoder.height().into());
+        if size.width > max_size.width || size.height > max_size.height {
+            bail!(
+                "too big: {}x{}, the LCD is {}x{}",
+                size.width,
+                size.height,
+                max_size.width,
+                max_size.height
+            );
+        }
+        // Every frame, so that a broken file is found now rather than halfway through playing.
+        // Skipping over the pixels doesn't keep them anywhere.
+        let mut frames = 0;
+        while decoder.next_frame_info()?.is_some() {
+            frames += 1;
+        }
+        if frames == 0 {
+            bail!("no frames");
+        }
+        let plays = match decoder.repeat() {
+            Repeat::Infinite => None,
+            // Repeats after the first time
+            Repeat::Finite(repeats) => Some(u32::from(repeats) + 1),
+        };
+        Ok(Self {
+            data: data.to_vec(),
+            size,
+            plays,
+            config: config.clone(),
+        })
+    }
+
+    pub fn start(&self) -> AnimationScene<'_> {
+        AnimationScene {
+            animation: self,
+            decoder: None,
+            screen: vec![Rgb565::BLACK; self.size.width as usize * self.size.height as usize],
+            saved: Vec::new(),
+            palette: Vec::new(),
+            row: Vec::new(),
+            dispose: None,
+            next_frame_at: Duration::ZERO,
+            plays: 0,
+        }
+    }
+}
+
+pub struct AnimationScene<'a> {
+    animation: &'a Animation,
+    /// None until the first frame, and again after the last one
+    decoder: Option<gif::Decoder<&'a [u8]>>,
+    /// Frames drawn so far, each on top of the ones before, as big as the GIF says
+    screen: Vec<Rgb565>,
+    /// What `DisposalMethod::Previous` restores
+    saved: Vec<Rgb565>,
+    /// Of the current frame, RGB
+    palette: Vec<u8>,
+    /// Palette indices, frames are decoded a row at a time
+    row: Vec<u8>,
+    /// What to do with the area of the last frame before drawing the next one
+    dispose: Option<(Rectangle, DisposalMethod)>,
+    next_frame_at: Duration,
+    /// Times played to the end
+    plays: u32,
+}
+
+/// Index ranges of `area` rows, in pixels of a screen `width` wide
+fn rows(width: u32, area: Rectangle) -> impl Iterator<Item = Range<usize>> {
+    let (width, left, top) = (
+        width as usize,
+        area.top_left.x as usize,
+        area.top_left.y as usize,
+    );
+    (top..top + area.size.height as usize)
+        .map(move |y| y * width + left..y * width + left + area.size.width as usize)
+}
+
+impl AnimationScene<'_> {
+    fn dispose_previous(&mut self) {
+        let width = self.animation.size.width;
+        match self.dispose.take() {
+            Some((area, DisposalMethod::Background)) => {
+                for row in rows(width, area) {
+                    self.screen[row].fill(Rgb565::BLACK);
+                }
+            }
+            Some((area, DisposalMethod::Previous)) => {
+                let saved = self.saved.chunks(area.size.width.max(1) as usize);
+                for (row, saved) in rows(width, area).zip(saved) {
+                    self.screen[row].copy_from_slice(saved);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Draws the next frame onto `screen`. Returns false at the end of the GIF.
+    fn next_frame(&mut self) -> Result<bool> {
+        self.dispose_previous();
+        let width = self.animation.size.width;
+        let decoder = match &mut self.decoder {
+            Some(decoder) => decoder,
+            None => self.decoder.insert(decoder(&self.animation.data)?),
+        };
+        let Some(frame) = decoder.next_frame_info()? else {
+            self.decoder = None;
+            return Ok(false);
+        };
+        let screen_area = Rectangle::new(Point::zero(), self.animation.size);
+        let area = Rectangle::new(
+            Point::new(frame.left.into(), frame.top.into()),
+            Size::new(frame.width.into(), frame.height.into()),
+        );
+        let (dispose, delay) = (frame.dispose, frame_delay(frame));
+        let (transparent, interlaced) = (frame.transparent, frame.interlaced);
+        // Nothing to decode in frames with no width
+        let height = if frame.width > 0 { frame.height } else { 0 };
+        self.palette.clear();
+        self.palette.extend_from_slice(decoder.palette()?);
+        // Frames sticking out of the screen are legal, only the part on it shows
+        let visible = area.intersection(&screen_area);
+        if dispose == DisposalMethod::Previous {
+            self.saved.clear();
+            for row in rows(width, visible) {
+                self.saved.extend_from_slice(&self.screen[row]);
+            }
+        }
+        let skip_left = (visible.top_left.x - area.top_left.x) as usize;
+        let visible_rows = visible.rows();
+        self.row.resize(area.size.width as usize, 0);
+        for y in row_order(height, interlaced) {
+            if !decoder.fill_buffer(&mut self.row)? {
+                bail!("frame truncated");
+            }
+            let y = area.top_left.y + i32::from(y);
+            if !visible_rows.contains(&y) {
+                continue;
+            }
+            let start = y as usize * width as usize + visible.top_left.x as usize;
+            let screen = &mut self.screen[start..start + visible.size.width as usize];
+            for (pixel, &index) in screen.iter_mut().zip(&self.row[skip_left..]) {
+                // Transparent pixels leave what was there before
+                if Some(index) == transparent {
+                    continue;
+                }
+                let color = usize::from(index) * 3;
+                if let Some(&[r, g, b]) = self.palette.get(color..color + 3) {
+                    *pixel = Rgb888::new(r, g, b).into();
+                }
+            }
+        }
+        self.dispose = Some((visible, dispose));
+        self.next_frame_at += delay;
+        Ok(true)
+    }
+}
+
+impl Scene for AnimationScene<'_> {
+    fn name(&self) -> &'static str {
+        "animation"
+    }
+
+    fn draw(&mut self, t: Duration, canvas: &mut Canvas, ctx: &mut SceneContext) -> Result<bool> {
+        let animation = self.animation;
+        if t.as_secs_f32() >= animation.config.max_secs {
+            return Ok(false);
+        }
+        // Catching up on frames that took too long to draw, rather than slowing down
+        while t >= self.next_frame_at {
+            if !self.next_frame()? {
+                self.plays += 1;
+                if animation.plays.is_some_and(|plays| self.plays >= plays) {
+                    return Ok(false);
+                }
+                self.dispose = None;
+                self.screen.fill(Rgb565::BLACK);
+            }
+        }
+
+        canvas.clear(Rgb565::BLACK)?;
+        let canvas_size = canvas.bounding_box().size;
+        let top_left = Point::new(
+            (canvas_size.width as i32 - animation.size.width as i32) / 2,
+            (canvas_size.height as i32 - animation.size.height as i32) / 2,
+        );
+        canvas.fill_contiguous(
+            &Rectangle::new(top_left, animation.size),
+            self.screen.iter().copied(),
+        )?;
+        crate::glitch(
+            canvas,
+            ctx.rng,
+            animation.config.glitch,
+            GLITCH_LINE_PROBABILITY,
+        );
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gif::{Encoder, Frame};
+
+    use super::*;
+
+    const LCD: Size = Size::new(160, 128);
+
+    /// 2x2, one frame per color, each `delay` hundredths of a second
+    fn gif(colors: &[[u8; 3]], delay: u16, repeat: Repeat) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut encoder = Encoder::new(&mut data, 2, 2, &[]).unwrap();
+        encoder.set_repeat(repeat).unwrap();
+        for color in colors {
+            let mut frame = Frame::from_rgb(2, 2, &color.repeat(4));
+            frame.delay = delay;
+            encoder.write_frame(&frame).unwrap();
+        }
+        drop(encoder);
+        data
+    }
+
+    #[test]
+    fn parses_loop_count() {
+        let config = Config::default();
+        let once = gif(&[[255, 0, 0]], 10, Repeat::Finite(0));
+        assert_eq!(
+            Animation::parse(&once, &config, LCD).unwrap().plays,
+            Some(1)
+        );
+        let forever = gif(&[[255, 0, 0]], 10, Repeat::Infinite);
+        assert_eq!(
+            Animation::parse(&forever, &config, LCD).unwrap().plays,
+            None
+        );
+        assert!(Animation::parse(&once[..once.len() / 2], &config, LCD).is_err());
+        assert!(Animation::parse(b"GIF89a", &config, LCD).is_err());
+        // Bigger than the LCD
+        assert!(Animation::parse(&once, &config, Size::new(1, 2)).is_err());
+    }
+
+    #[test]
+    fn decodes_interlaced_rows_in_order() {
+        let rows: Vec<u16> = row_order(10, true).collect();
+        assert_eq!(rows, [0, 8, 4, 2, 6, 1, 3, 5, 7, 9]);
+        assert!(row_order(3, false).eq([0, 1, 2]));
+    }
+
+    #[test]
+    fn follows_frame_timing() {
+        let data = gif(&[[255, 0, 0], [0, 0, 255]], 20, Repeat::Finite(1));
+        let animation = Animation::parse(&data, &Config::default(), LCD).unwrap();
+        let mut scene = animation.start();
+        let mut shown_at = |ms| {
+            while Duration::from_millis(ms) >= scene.next_frame_at {
+                if !scene.next_frame().unwrap() {
+                    return None;
+                }
+            }
+            Some(scene.screen[0])
+        };
+        assert_eq!(shown_at(0), Some(Rgb565::RED));
+        assert_eq!(shown_at(199), Some(Rgb565::RED));
+        assert_eq!(shown_at(200), Some(Rgb565::BLUE));
+        assert_eq!(shown_at(399), Some(Rgb565::BLUE));
+        assert_eq!(shown_at(400), None);
+    }
+
+    #[test]
+    fn treats_zero_delay_as_default() {
+        let data = gif(&[[255, 0, 0]], 0, Repeat::Finite(0));
+        let animation = Animation::parse(&data, &Config::default(), LCD).unwrap();
+        let mut scene = animation.start();
+        assert!(scene.next_frame().unwrap());
+        assert_eq!(scene.next_frame_at, DEFAULT_FRAME_DELAY);
+    }
+}
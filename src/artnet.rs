@@ -0,0 +1,289 @@
+use std::{
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+
+use crate::platform::Brightness;
+
+// Read at build time, so that they work on the ESP32 too
+const OUTPUT_UNIVERSE: Option<&str> = option_env!("EVIL_ANDROID_ARTNET_OUTPUT");
+const INPUT_UNIVERSE: Option<&str> = option_env!("EVIL_ANDROID_ARTNET_INPUT");
+const TARGET: Option<&str> = option_env!("EVIL_ANDROID_ARTNET_TARGET");
+const START_ADDRESS: Option<&str> = option_env!("EVIL_ANDROID_ARTNET_ADDRESS");
+
+const PORT: u16 = 6454;
+const HEADER: &[u8; 8] = b"Art-Net\0";
+const OP_DMX: u16 = 0x5000;
+const PROTOCOL_VERSION: u16 = 14;
+/// Header, sequence, physical port, universe and length
+const DMX_HEADER_SIZE: usize = 18;
+const MAX_CHANNELS: usize = 512;
+/// Left and right LED, in that order
+const CHANNELS: usize = 2;
+/// Art-Net asks for no more than 44 frames per second per universe
+const MIN_SEND_INTERVAL: Duration = Duration::from_millis(25);
+/// Sent again even if nothing changed, so that receivers don't think we're gone
+const KEEP_ALIVE: Duration = Duration::from_secs(1);
+/// After that long without a packet the android takes its LEDs back
+const INPUT_TIMEOUT: Duration = Duration::from_secs(10);
+
+struct Output {
+    socket: UdpSocket,
+    target: SocketAddr,
+    universe: u16,
+    /// 1-based DMX address of the first channel
+    address: usize,
+    /// 1..=255, 0 would tell receivers not to reorder packets
+    sequence: u8,
+    last_sent: Option<([u8; CHANNELS], Instant)>,
+}
+
+static OUTPUT: Mutex<Option<Output>> = Mutex::new(None);
+// Set from the Art-Net thread
+static INPUT: Mutex<Option<([u8; CHANNELS], Instant)>> = Mutex::new(None);
+
+fn to_level(brightness: Brightness) -> u8 {
+    (f32::from(brightness) * 255.0).round() as u8
+}
+
+fn parse_universe(name: &str, value: &str) -> Result<u16> {
+    match value.parse() {
+        Ok(universe) if universe < 0x8000 => Ok(universe),
+        _ => bail!("invalid {name}: {value}, must be 0..32767"),
+    }
+}
+
+fn parse_address(value: Option<&str>) -> Result<usize> {
+    let Some(value) = value else {
+        return Ok(1);
+    };
+    match value.parse() {
+        Ok(address) if (1..=MAX_CHANNELS + 1 - CHANNELS).contains(&address) => Ok(address),
+        _ => bail!("invalid EVIL_ANDROID_ARTNET_ADDRESS: {value}"),
+    }
+}
+
+/// An ArtDmx packet with `channels` starting at 1-based DMX `address`, everything before it
+/// zeroed
+fn dmx_packet(universe: u16, sequence: u8, address: usize, channels: &[u8]) -> Vec<u8> {
+    // At least 2 channels, and an even number of them
+    let len = (address - 1 + channels.len()).max(2).next_multiple_of(2);
+    let mut out = Vec::with_capacity(DMX_HEADER_SIZE + len);
+    out.extend(HEADER);
+    out.extend(OP_DMX.to_le_bytes());
+    out.extend(PROTOCOL_VERSION.to_be_bytes());
+    out.push(sequence);
+    // Physical input port, informational only
+    out.push(0);
+    out.extend(universe.to_le_bytes());
+    out.extend((len as u16).to_be_bytes());
+    out.resize(DMX_HEADER_SIZE + len, 0);
+    out[DMX_HEADER_SIZE + address - 1..][..channels.len()].copy_from_slice(channels);
+    out
+}
+
+/// DMX data of an ArtDmx packet for `universe`. None for other universes and other kinds of
+/// Art-Net packets, which are perfectly normal to receive.
+fn parse_dmx(packet: &[u8], universe: u16) -> Result<Option<&[u8]>> {
+    let Some(rest) = packet.strip_prefix(HEADER) else {
+        bail!("not an Art-Net packet");
+    };
+    if rest.len() < 2 || u16::from_le_bytes([rest[0], rest[1]]) != OP_DMX {
+        return Ok(None);
+    }
+    if packet.len() < DMX_HEADER_SIZE {
+        bail!("truncated ArtDmx packet");
+    }
+    if u16::from_le_bytes([packet[14], packet[15]]) != universe {
+        return Ok(None);
+    }
+    let len = usize::from(u16::from_be_bytes([packet[16], packet[17]]));
+    match packet.get(DMX_HEADER_SIZE..DMX_HEADER_SIZE + len) {
+        Some(data) if len <= MAX_CHANNELS => Ok(Some(data)),
+        _ => bail!("invalid ArtDmx length: {len}"),
+    }
+}
+
+/// Channels for our LEDs out of DMX data. Ones the sender didn't bother including are 0.
+fn channels(data: &[u8], address: usize) -> [u8; CHANNELS] {
+    let mut out = [0; CHANNELS];
+    for (i, level) in out.iter_mut().enumerate() {
+        *level = data.get(address - 1 + i).copied().unwrap_or(0);
+    }
+    out
+}
+
+/// Whether levels are worth sending, given what was sent last and when
+fn send_due(
+    levels: [u8; CHANNELS],
+    last_sent: Option<([u8; CHANNELS], Instant)>,
+    now: Instant,
+) -> bool {
+    match last_sent {
+        None => true,
+        Some((last, at)) => {
+            let since = now.saturating_duration_since(at);
+            since >= KEEP_ALIVE || (last != levels && since >= MIN_SEND_INTERVAL)
+        }
+    }
+}
+
+/// LED brightness set by a lighting desk, if there is one sending to
+/// EVIL_ANDROID_ARTNET_INPUT
+pub fn input() -> Option<[Brightness; 2]> {
+    let mut input = INPUT.lock().unwrap();
+    if input.is_some_and(|(_, at)| at.elapsed() >= INPUT_TIMEOUT) {
+        log::info!("no Art-Net input for {INPUT_TIMEOUT:?}, LEDs are back to normal");
+        *input = None;
+    }
+    input.map(|(levels, _)| levels.map(|level| Brightness::from(f32::from(level) / 255.0)))
+}
+
+/// Tells EVIL_ANDROID_ARTNET_TARGET what the LEDs show. Cheap enough to call every frame,
+/// packets go out only as often as Art-Net wants them.
+pub fn output(leds: [Brightness; 2]) {
+    let mut output = OUTPUT.lock().unwrap();
+    let Some(output) = output.as_mut() else {
+        return;
+    };
+    let levels = leds.map(to_level);
+    let now = Instant::now();
+    if !send_due(levels, output.last_sent, now) {
+        return;
+    }
+    output.sequence = output.sequence.checked_add(1).unwrap_or(1);
+    let packet = dmx_packet(output.universe, output.sequence, output.address, &levels);
+    if let Err(e) = output.socket.send_to(&packet, output.target) {
+        log::warn!("Art-Net send failed: {e}");
+    }
+    // Not retried right away even if it failed, a broken network shouldn't cost every frame
+    output.last_sent = Some((levels, now));
+}
+
+/// Starts sending and/or receiving Art-Net, depending on which of EVIL_ANDROID_ARTNET_OUTPUT
+/// and EVIL_ANDROID_ARTNET_INPUT were set at build time
+pub fn spawn() -> Result<()> {
+    let address = parse_address(START_ADDRESS)?;
+    if let Some(universe) = OUTPUT_UNIVERSE {
+        let universe = parse_universe("EVIL_ANDROID_ARTNET_OUTPUT", universe)?;
+        let target = TARGET.unwrap_or("255.255.255.255");
+        let target = if target.contains(':') {
+            target.to_owned()
+        } else {
+            format!("{target}:{PORT}")
+        };
+        let target = target
+            .to_socket_addrs()
+            .with_context(|| format!("cannot resolve EVIL_ANDROID_ARTNET_TARGET: {target}"))?
+            .next()
+            .with_context(|| format!("no address for EVIL_ANDROID_ARTNET_TARGET: {target}"))?;
+        let socket = UdpSocket::bind("0.0.0.0:0").context("UdpSocket::bind failed")?;
+        socket.set_broadcast(true)?;
+        // Never worth stalling a frame for
+        socket.set_nonblocking(true)?;
+        log::info!("sending LEDs as Art-Net universe {universe} to {target}");
+        *OUTPUT.lock().unwrap() = Some(Output {
+            socket,
+            target,
+            universe,
+            address,
+            sequence: 0,
+            last_sent: None,
+        });
+    }
+
+    let Some(universe) = INPUT_UNIVERSE else {
+        return Ok(());
+    };
+    let universe = parse_universe("EVIL_ANDROID_ARTNET_INPUT", universe)?;
+    let socket = UdpSocket::bind(("0.0.0.0", PORT)).context("UdpSocket::bind failed")?;
+    log::info!("listening for Art-Net universe {universe} on UDP port {PORT}");
+    std::thread::Builder::new()
+        .name("artnet".to_owned())
+        .stack_size(8192)
+        .spawn(move || {
+            let mut packet = vec![0; DMX_HEADER_SIZE + MAX_CHANNELS];
+            loop {
+                let len = match socket.recv_from(&mut packet) {
+                    Ok((len, _)) => len,
+                    Err(e) => {
+                        log::error!("Art-Net receive failed: {e:?}");
+                        return;
+                    }
+                };
+                match parse_dmx(&packet[..len], universe) {
+                    Ok(Some(data)) => {
+                        *INPUT.lock().unwrap() = Some((channels(data, address), Instant::now()))
+                    }
+                    Ok(None) => {}
+                    Err(e) => log::debug!("Art-Net: {e:#}"),
+                }
+            }
+        })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_dmx() {
+        let packet = dmx_packet(0x1234, 7, 1, &[255, 128]);
+        assert_eq!(packet.len(), DMX_HEADER_SIZE + 2);
+        assert_eq!(&packet[8..18], [0x00, 0x50, 0, 14, 7, 0, 0x34, 0x12, 0, 2]);
+        assert_eq!(parse_dmx(&packet, 0x1234).unwrap(), Some(&[255, 128][..]));
+        assert_eq!(parse_dmx(&packet, 0x1235).unwrap(), None);
+
+        // Padded to an even length, with the channels before ours zeroed
+        let packet = dmx_packet(0, 1, 4, &[10, 20]);
+        let data = parse_dmx(&packet, 0).unwrap().unwrap();
+        assert_eq!(data, [0, 0, 0, 10, 20, 0]);
+        assert_eq!(channels(data, 4), [10, 20]);
+        // Short packets leave the rest off
+        assert_eq!(channels(data, 6), [0, 0]);
+    }
+
+    #[test]
+    fn rejects_malformed_packets() {
+        let packet = dmx_packet(1, 1, 1, &[1, 2]);
+        assert!(parse_dmx(b"Art-Nyet\0\0\0\0", 1).is_err());
+        assert!(parse_dmx(&packet[..DMX_HEADER_SIZE - 1], 1).is_err());
+        assert!(parse_dmx(&packet[..packet.len() - 1], 1).is_err());
+        // ArtPoll, not for us
+        assert_eq!(parse_dmx(b"Art-Net\0\x00\x20\0\x0e\0\0", 1).unwrap(), None);
+    }
+
+    #[test]
+    fn parses_config() {
+        assert_eq!(parse_universe("X", "32767").unwrap(), 32767);
+        assert!(parse_universe("X", "32768").is_err());
+        assert_eq!(parse_address(None).unwrap(), 1);
+        assert_eq!(parse_address(Some("511")).unwrap(), 511);
+        assert!(parse_address(Some("512")).is_err());
+        assert!(parse_address(Some("0")).is_err());
+    }
+
+    #[test]
+    fn limits_send_rate() {
+        let now = Instant::now();
+        let levels = [1, 2];
+        let sent = |ago| Some((levels, now - Duration::from_millis(ago)));
+        assert!(send_due(levels, None, now));
+        assert!(!send_due(levels, sent(10), now));
+        assert!(!send_due([3, 4], sent(10), now));
+        assert!(send_due([3, 4], sent(25), now));
+        assert!(!send_due(levels, sent(500), now));
+        assert!(send_due(levels, sent(1000), now));
+    }
+
+    #[test]
+    fn converts_levels() {
+        assert_eq!(to_level(0.0.into()), 0);
+        assert_eq!(to_level(0.5.into()), 128);
+        assert_eq!(to_level(2.0.into()), 255);
+    }
+}
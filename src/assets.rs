@@ -0,0 +1,341 @@
+use anyhow::{bail, Context, Result};
+use embedded_graphics::{
+    geometry::{Dimensions, Point, Size},
+    image::ImageRaw,
+    pixelcolor::{BinaryColor, Rgb565},
+    prelude::IntoStorage,
+    Drawable,
+};
+use serde::Deserialize;
+
+use crate::{
+    animation, badge, brightness_cap,
+    calendar::Calendar,
+    calibration,
+    clock::Clock,
+    countdown::Countdown,
+    message_layout,
+    platform::{Platform, Storage},
+    region::Mask,
+    scene::SceneSource,
+    sprites, theme,
+    transition::Transition,
+    tweaks::Tweaks,
+    MaskedImage,
+};
+
+const DUMPSTER_FIRE_PATH: &str = "dumpster-fire.png";
+const GLITCH_MASK_PATH: &str = "glitch-mask.png";
+const MESSAGES_PATH: &str = "messages.txt";
+const CONFIG_PATH: &str = "config.toml";
+/// Scenes built into the binary. They can be listed in `scenes` like the ones in storage, which
+/// take precedence.
+const EMBEDDED_SCENES: &[(&str, &[u8])] = &[(
+    "scenes/release.toml",
+    include_bytes!("../data/scenes/release.toml"),
+)];
+// Unless there's a message pack on the SD card
+const DEFAULT_MESSAGE: &str = "Analyzing Android.bp...";
+// Anything bigger wouldn't fit on any of the displays anyway, and RAM is scarce
+const MAX_IMAGE_PIXELS: usize = 320 * 240;
+
+/// Image with a transparency mask, in the same format the embedded ones are
+pub struct Image {
+    size: Size,
+    /// Big endian RGB565
+    color: Vec<u8>,
+    /// 1 bit per pixel, rows padded to whole bytes
+    mask: Vec<u8>,
+}
+
+impl Image {
+    /// Fully transparent pixels are left out, like in the embedded images
+    pub fn from_png(data: &[u8]) -> Result<Self> {
+        let mut decoder = png::Decoder::new(data);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info().context("reading PNG header failed")?;
+        let info = reader.info();
+        if info.width as usize * info.height as usize > MAX_IMAGE_PIXELS {
+            bail!("image too big: {}x{}", info.width, info.height);
+        }
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let frame = reader.next_frame(&mut buf).context("decoding PNG failed")?;
+        let rgba: Vec<[u8; 4]> = match frame.color_type {
+            png::ColorType::Rgba => buf
+                .chunks_exact(4)
+                .map(|p| [p[0], p[1], p[2], p[3]])
+                .collect(),
+            png::ColorType::Rgb => buf
+                .chunks_exact(3)
+                .map(|p| [p[0], p[1], p[2], 255])
+                .collect(),
+            png::ColorType::GrayscaleAlpha => buf
+                .chunks_exact(2)
+                .map(|p| [p[0], p[0], p[0], p[1]])
+                .collect(),
+            png::ColorType::Grayscale => buf.iter().map(|&v| [v, v, v, 255]).collect(),
+            png::ColorType::Indexed => bail!("indexed PNG not expanded"),
+        };
+
+        let (width, height) = (frame.width as usize, frame.height as usize);
+        let mask_stride = width.div_ceil(8);
+        let mut color = Vec::with_capacity(width * height * 2);
+        let mut mask = vec![0u8; mask_stride * height];
+        for (i, [r, g, b, a]) in rgba.into_iter().take(width * height).enumerate() {
+            let pixel = Rgb565::new(r >> 3, g >> 2, b >> 3);
+            color.extend_from_slice(&pixel.into_storage().to_be_bytes());
+            if a != 0 {
+                let (y, x) = (i / width, i % width);
+                mask[y * mask_stride + x / 8] |= 0x80 >> (x % 8);
+            }
+        }
+        Ok(Self {
+            size: Size::new(frame.width, frame.height),
+            color,
+            mask,
+        })
+    }
+
+    pub fn size(&self) -> Size {
+        self.size
+    }
+
+    pub fn is_opaque(&self, x: usize, y: usize) -> bool {
+        let stride = (self.size.width as usize).div_ceil(8);
+        self.mask[y * stride + x / 8] & (0x80 >> (x % 8)) != 0
+    }
+
+    pub fn at(&self, pos: Point) -> Result<impl Drawable<Color = Rgb565> + '_> {
+        MaskedImage::new(
+            ImageRaw::<Rgb565>::new(&self.color, self.size.width),
+            ImageRaw::<BinaryColor>::new(&self.mask, self.size.width),
+            pos,
+        )
+    }
+}
+
+/// Replacements for the built-in content, read from storage at startup. Anything
+/// missing or broken falls back to what's embedded in the binary.
+#[derive(Default)]
+pub struct Assets {
+    pub dumpster_fire: Option<Image>,
+    /// Limits glitches in the escalation to its opaque pixels
+    pub glitch_mask: Option<Mask>,
+    /// Shown under the build timer, one per escalation. Empty means the default one.
+    pub messages: Vec<String>,
+    /// Lines of text over the escalation. Empty means the default ones.
+    pub text: Vec<message_layout::Line>,
+    pub tweaks: Option<Tweaks>,
+    /// Played after escalations, one at a time, in order
+    pub scenes: Vec<SceneSource>,
+    /// All of EMBEDDED_SCENES, for demo mode
+    pub builtin_scenes: Vec<SceneSource>,
+    /// None means the default ones
+    pub transitions: Option<Vec<Transition>>,
+    /// Changes to the escalation's display list, by layer name
+    pub layers: sprites::Overrides,
+    pub countdown: Option<Countdown>,
+    pub clock: Option<Clock>,
+    pub badge: Option<badge::Config>,
+    /// None keeps the classic one
+    pub theme: Option<theme::Config>,
+    /// None leaves the colors as they are, unless a profile was picked on the console
+    pub calibration: Option<calibration::Config>,
+    /// None lets the LEDs shine at full brightness, day and night
+    pub brightness: Option<brightness_cap::Config>,
+    pub calendar: Option<Calendar>,
+    /// For everything that needs local time
+    pub utc_offset_minutes: i32,
+}
+
+impl Assets {
+    /// Shown under the build timer during the `escalation`th escalation
+    pub fn message(&self, escalation: usize) -> &str {
+        match self.messages.len() {
+            0 => DEFAULT_MESSAGE,
+            n => &self.messages[escalation % n],
+        }
+    }
+
+    /// Lines of text over the escalation, top to bottom
+    pub fn text_lines(&self) -> &[message_layout::Line] {
+        match self.text.is_empty() {
+            true => message_layout::DEFAULT,
+            false => &self.text,
+        }
+    }
+
+    pub fn load(platform: &mut impl Platform) -> Self {
+        let config = load(platform, CONFIG_PATH, parse_config).unwrap_or_default();
+        let animation = config.animation.clone().unwrap_or_default();
+        let lcd_size = platform.lcd().bounding_box().size;
+        let assets = Self {
+            dumpster_fire: load(platform, DUMPSTER_FIRE_PATH, Image::from_png),
+            glitch_mask: load(platform, GLITCH_MASK_PATH, |data| {
+                Image::from_png(data)
+                    .map(|image| Mask::from_fn(image.size(), |x, y| image.is_opaque(x, y)))
+            }),
+            messages: load(platform, MESSAGES_PATH, parse_messages).unwrap_or_default(),
+            tweaks: config.tweaks.filter(|tweaks| {
+                tweaks
+                    .validate()
+                    .inspect_err(|e| log::error!("invalid tweaks config: {e:?}"))
+                    .is_ok()
+            }),
+            text: Some(config.text)
+                .filter(|lines| {
+                    message_layout::validate(lines)
+                        .inspect_err(|e| log::error!("invalid text config: {e:?}"))
+                        .is_ok()
+                })
+                .unwrap_or_default(),
+            transitions: config.transitions,
+            layers: Some(config.layers)
+                .filter(|layers| {
+                    sprites::validate(layers)
+                        .inspect_err(|e| log::error!("invalid layers config: {e:?}"))
+                        .is_ok()
+                })
+                .unwrap_or_default(),
+            countdown: config.countdown.filter(|countdown| {
+                countdown
+                    .deadline()
+                    .inspect_err(|e| log::error!("invalid countdown deadline: {e:?}"))
+                    .is_ok()
+            }),
+            badge: config.badge,
+            theme: config.theme,
+            calibration: config.calibration.filter(|calibration| {
+                calibration
+                    .validate()
+                    .inspect_err(|e| log::error!("invalid calibration config: {e:?}"))
+                    .is_ok()
+            }),
+            brightness: config.brightness,
+            calendar: config.calendar.filter(|calendar| {
+                calendar
+                    .validate()
+                    .inspect_err(|e| log::error!("invalid calendar config: {e:?}"))
+                    .is_ok()
+            }),
+            utc_offset_minutes: config
+                .utc_offset_minutes
+                .or_else(|| {
+                    let minutes = config.clock.as_ref()?.legacy_utc_offset_minutes()?;
+                    log::warn!("utc_offset_minutes belongs at the top of config.toml now");
+                    Some(minutes)
+                })
+                .unwrap_or_default(),
+            clock: config.clock.filter(|clock| {
+                clock
+                    .validate()
+                    .inspect_err(|e| log::error!("invalid clock config: {e:?}"))
+                    .is_ok()
+            }),
+            builtin_scenes: EMBEDDED_SCENES
+                .iter()
+                .filter_map(|(path, data)| {
+                    SceneSource::parse(path, data, &animation, lcd_size)
+                        .inspect_err(|e| log::error!("invalid embedded {path}: {e:?}"))
+                        .ok()
+                })
+                .collect(),
+            scenes: config
+                .scenes
+                .iter()
+                .filter_map(|path| load_scene(platform, path, &animation, lcd_size))
+                .collect(),
+        };
+        log::info!(
+            "assets: custom dumpster fire: {}, glitch mask: {:?}, {} messages, custom tweaks: {}, \
+             {}/{} scenes",
+            assets.dumpster_fire.is_some(),
+            assets.glitch_mask.as_ref().map(Mask::size),
+            assets.messages.len(),
+            assets.tweaks.is_some(),
+            assets.scenes.len(),
+            config.scenes.len(),
+        );
+        assets
+    }
+}
+
+fn load<T>(
+    platform: &mut impl Platform,
+    name: &str,
+    parse: impl FnOnce(&[u8]) -> Result<T>,
+) -> Option<T> {
+    let data = platform
+        .storage()
+        .read(name)
+        .inspect_err(|e| log::error!("reading {name} failed: {e:?}"))
+        .ok()??;
+    parse(&data)
+        .inspect_err(|e| log::error!("invalid {name}, not using it: {e:?}"))
+        .ok()
+}
+
+fn load_scene(
+    platform: &mut impl Platform,
+    path: &str,
+    animation: &animation::Config,
+    lcd_size: Size,
+) -> Option<SceneSource> {
+    let parse = |data: &[u8]| SceneSource::parse(path, data, animation, lcd_size);
+    load(platform, path, parse).or_else(|| {
+        let (_, data) = EMBEDDED_SCENES.iter().find(|(name, _)| *name == path)?;
+        parse(data)
+            .inspect_err(|e| log::error!("invalid embedded {path}: {e:?}"))
+            .ok()
+    })
+}
+
+/// One message per line. Lines starting with `#` are comments, `\n` breaks lines.
+fn parse_messages(data: &[u8]) -> Result<Vec<String>> {
+    let text = std::str::from_utf8(data).context("not valid UTF-8")?;
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.replace("\\n", "\n"))
+        .collect())
+}
+
+/// Contents of config.toml. Every section is optional, and so is everything inside.
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    /// Local time zone, `[clock]` has it in older configs
+    utc_offset_minutes: Option<i32>,
+    tweaks: Option<Tweaks>,
+    /// Paths of scene scripts, timelines and GIFs
+    #[serde(default)]
+    scenes: Vec<String>,
+    /// For GIFs listed in `scenes`
+    animation: Option<animation::Config>,
+    /// First one matching the scene switch is used
+    transitions: Option<Vec<Transition>>,
+    /// Z-order, visibility and effects of the escalation layers
+    #[serde(default)]
+    layers: sprites::Overrides,
+    /// Font, color, shake and entrance of each line of text in the escalation
+    #[serde(default)]
+    text: Vec<message_layout::Line>,
+    countdown: Option<Countdown>,
+    /// Outside office hours
+    clock: Option<Clock>,
+    badge: Option<badge::Config>,
+    /// Name of a built-in theme, or custom colors
+    theme: Option<theme::Config>,
+    /// Color correction for the panel
+    calibration: Option<calibration::Config>,
+    /// Ceiling of the LEDs, lower at night
+    brightness: Option<brightness_cap::Config>,
+    /// Date-based surprises
+    calendar: Option<Calendar>,
+}
+
+fn parse_config(data: &[u8]) -> Result<Config> {
+    let text = std::str::from_utf8(data).context("not valid UTF-8")?;
+    Ok(toml::from_str(text)?)
+}
@@ -0,0 +1,101 @@
+use std::{f32::consts::TAU, time::Duration};
+
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::platform::Brightness;
+
+/// `[tweaks.backlight]` in config.toml. Amounts are fractions of full brightness, 0 turns that
+/// part off.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// How far a full strength glitch burst drags the backlight down, like a sagging supply
+    pub burst_dip: f32,
+    /// How far it dims at the bottom of each breath, while nothing is glitching yet
+    pub breathing: f32,
+    pub breathing_secs: f32,
+    /// Escalation progress, 0..1, from which it blacks out for a frame now and then
+    pub climax: f32,
+    /// Chance of a blackout each frame, reached at the very end and kept through the noise
+    pub blackout: f32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            burst_dip: 0.7,
+            breathing: 0.3,
+            breathing_secs: 4.0,
+            climax: 0.9,
+            blackout: 0.08,
+        }
+    }
+}
+
+/// What the screen is showing, for the backlight to go along with
+pub struct Drive {
+    /// Into the escalation
+    pub elapsed: Duration,
+    /// 0..1
+    pub progress: f32,
+    /// 0 until glitching starts
+    pub glitchiness: usize,
+    /// Strength of the glitch burst going on, 0..1
+    pub burst: f32,
+}
+
+/// Backlight level for a frame of the escalation or the noise after it
+pub fn level(config: &Config, drive: &Drive, rng: &mut impl Rng) -> Brightness {
+    let mut level = 1.0;
+    if drive.glitchiness == 0 && config.breathing_secs > 0.0 {
+        let phase = drive.elapsed.as_secs_f32() / config.breathing_secs * TAU;
+        level -= config.breathing * (0.5 - 0.5 * phase.cos());
+    }
+    // A sagging supply flickers rather than dims evenly
+    if drive.burst > 0.0 {
+        level -= config.burst_dip * drive.burst * rng.gen_range(0.5..=1.0);
+    }
+    if drive.progress >= config.climax {
+        let into_climax =
+            (drive.progress - config.climax) / (1.0 - config.climax).max(f32::EPSILON);
+        if rng.gen::<f32>() < config.blackout * into_climax.min(1.0) {
+            level = 0.0;
+        }
+    }
+    level.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn follows_the_escalation() {
+        let config = Config::default();
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut at = |elapsed_secs: f32, progress, glitchiness, burst| {
+            let drive = Drive {
+                elapsed: Duration::from_secs_f32(elapsed_secs),
+                progress,
+                glitchiness,
+                burst,
+            };
+            f32::from(level(&config, &drive, &mut rng))
+        };
+        // Breathing while calm, steady once glitching starts
+        assert_eq!(at(0.0, 0.1, 0, 0.0), 1.0);
+        assert!((at(2.0, 0.1, 0, 0.0) - 0.7).abs() < 1e-4);
+        assert_eq!(at(2.0, 0.5, 1, 0.0), 1.0);
+        // Dips with bursts, the stronger the deeper
+        let dip = at(2.0, 0.5, 1, 1.0);
+        assert!((0.3..=0.65).contains(&dip), "{dip}");
+        // Blacks out at the very end, but not all the time
+        let levels: Vec<f32> = (0..1000).map(|_| at(0.0, 1.0, 10, 0.0)).collect();
+        let blackouts = levels.iter().filter(|&&level| level == 0.0).count();
+        assert!((40..=120).contains(&blackouts), "{blackouts}");
+        assert!((0..1000).all(|_| at(0.0, 0.8, 10, 0.0) == 1.0));
+    }
+}
@@ -0,0 +1,186 @@
+use std::{sync::Mutex, time::Duration};
+
+use anyhow::Result;
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::{Dimensions, Point},
+    mono_font::{
+        iso_8859_1::{FONT_10X20, FONT_6X10},
+        MonoTextStyle,
+    },
+    pixelcolor::Rgb565,
+    prelude::RgbColor,
+    text::{Alignment, Baseline, Text, TextStyleBuilder},
+    Drawable,
+};
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::{
+    scene::{Canvas, Scaled, Scene, SceneContext},
+    theme,
+};
+
+/// Pixels per second
+const TAGLINE_SCROLL_SPEED: f32 = 30.0;
+/// Just enough to keep it recognizably evil while staying readable
+const GLITCH_MAX_OFFSET: usize = 3;
+const GLITCH_LINE_PROBABILITY: f32 = 0.03;
+/// Every now and then things get worse for a moment
+const BURST_INTERVAL: Duration = Duration::from_secs(4);
+const BURST_DURATION: Duration = Duration::from_millis(200);
+const BURST_MAX_OFFSET: usize = 12;
+const BURST_LINE_PROBABILITY: f32 = 0.3;
+const LED_BRIGHTNESS: f32 = 0.2;
+
+/// Initial badge contents, from `[badge]` in config.toml
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    tagline: String,
+    /// Start in badge mode instead of the escalation
+    #[serde(default)]
+    on_boot: bool,
+}
+
+struct State {
+    enabled: bool,
+    name: String,
+    tagline: String,
+}
+
+// Set from the console and HTTP threads
+static STATE: Mutex<State> = Mutex::new(State {
+    enabled: false,
+    name: String::new(),
+    tagline: String::new(),
+});
+
+/// Device name is the default name
+pub fn init(config: Option<&Config>) {
+    let config = config.cloned().unwrap_or_default();
+    let mut state = STATE.lock().unwrap();
+    state.name = config.name.unwrap_or_else(crate::device_name::get);
+    state.tagline = config.tagline;
+    state.enabled = config.on_boot;
+}
+
+pub fn is_enabled() -> bool {
+    STATE.lock().unwrap().enabled
+}
+
+pub fn toggle() {
+    let mut state = STATE.lock().unwrap();
+    state.enabled = !state.enabled;
+    log::info!("badge mode: {}", state.enabled);
+}
+
+/// Also turns the badge on
+pub fn set_name(name: &str) {
+    let mut state = STATE.lock().unwrap();
+    state.name = name.trim().to_owned();
+    state.enabled = true;
+}
+
+/// Also turns the badge on
+pub fn set_tagline(tagline: &str) {
+    let mut state = STATE.lock().unwrap();
+    state.tagline = tagline.trim().to_owned();
+    state.enabled = true;
+}
+
+/// Name in the first line, tagline in the rest. Empty text turns the badge off.
+#[cfg(target_os = "espidf")]
+pub fn set_text(text: &str) {
+    let text = text.trim();
+    if text.is_empty() {
+        STATE.lock().unwrap().enabled = false;
+        return;
+    }
+    let (name, tagline) = text.split_once('\n').unwrap_or((text, ""));
+    set_name(name);
+    set_tagline(&tagline.replace('\n', " "));
+}
+
+pub struct BadgeScene;
+
+impl Scene for BadgeScene {
+    fn name(&self) -> &'static str {
+        "badge"
+    }
+
+    fn draw(&mut self, t: Duration, canvas: &mut Canvas, ctx: &mut SceneContext) -> Result<bool> {
+        let (name, tagline) = {
+            let state = STATE.lock().unwrap();
+            if !state.enabled {
+                return Ok(false);
+            }
+            (state.name.clone(), state.tagline.clone())
+        };
+
+        let size = canvas.bounding_box().size;
+        let name_font = FONT_10X20.character_size;
+        let tagline_height = if tagline.is_empty() {
+            0
+        } else {
+            FONT_6X10.character_size.height + 2
+        };
+        let name_width = name_font.width * name.chars().count().max(1) as u32;
+        // As big as fits. Long names get cut off, rather than rendered unreadably small.
+        let scale = (size.width / name_width)
+            .min(size.height.saturating_sub(tagline_height) / name_font.height)
+            .max(1);
+        let name_center = Point::new(
+            size.width as i32 / 2,
+            size.height.saturating_sub(tagline_height) as i32 / 2,
+        );
+
+        let [name_color, tagline_color] = theme::current().badge();
+        canvas.clear(Rgb565::BLACK)?;
+        Text::with_text_style(
+            &name,
+            name_center / scale as i32,
+            MonoTextStyle::new(&FONT_10X20, name_color),
+            TextStyleBuilder::new()
+                .alignment(Alignment::Center)
+                .baseline(Baseline::Middle)
+                .build(),
+        )
+        .draw(&mut Scaled {
+            target: &mut *canvas,
+            scale,
+        })?;
+
+        if !tagline.is_empty() {
+            // Enters from the right, leaves on the left, starts over
+            let tagline_width = FONT_6X10.character_size.width * tagline.chars().count() as u32;
+            let period = (size.width + tagline_width) as f32;
+            let scrolled = (t.as_secs_f32() * TAGLINE_SCROLL_SPEED) % period;
+            Text::with_baseline(
+                &tagline,
+                Point::new(
+                    size.width as i32 - scrolled as i32,
+                    (size.height - tagline_height) as i32,
+                ),
+                MonoTextStyle::new(&FONT_6X10, tagline_color),
+                Baseline::Top,
+            )
+            .draw(canvas)?;
+        }
+
+        let since_burst =
+            Duration::from_millis((t.as_millis() % BURST_INTERVAL.as_millis()) as u64);
+        if since_burst < BURST_DURATION {
+            crate::glitch(canvas, ctx.rng, BURST_MAX_OFFSET, BURST_LINE_PROBABILITY);
+        } else {
+            crate::glitch(canvas, ctx.rng, GLITCH_MAX_OFFSET, GLITCH_LINE_PROBABILITY);
+        }
+        // Flickering, like something's not quite right
+        let flicker = if ctx.rng.gen_bool(0.05) { 0.0 } else { 1.0 };
+        ctx.leds = [LED_BRIGHTNESS * flicker; 2];
+        Ok(true)
+    }
+}
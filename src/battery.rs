@@ -0,0 +1,213 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::{Dimensions, Point, Size},
+    mono_font::{ascii::FONT_6X10, MonoTextStyle},
+    pixelcolor::Rgb565,
+    prelude::{Primitive, RgbColor},
+    primitives::{PrimitiveStyle, Rectangle},
+    text::{Alignment, Text},
+    Drawable,
+};
+
+use crate::{
+    artnet, layout,
+    platform::{BatteryStatus, Platform, LED},
+    theme,
+};
+
+/// Below this LEDs get dimmed to save power
+pub const LOW_CHARGE: f32 = 0.15;
+/// Below this the android gives up and goes to sleep
+pub const CRITICAL_CHARGE: f32 = 0.03;
+const LOW_CHARGE_LED_SCALE: f32 = 0.3;
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+// ADC readings are noisy and sag under load, each reading only moves the estimate this much
+const SMOOTHING: f32 = 0.2;
+const DEAD_BATTERY_SCREEN_TIME: Duration = Duration::from_secs(5);
+
+/// Rough single-cell LiPo discharge curve under light load: (volts, charge)
+const LIPO_CURVE: &[(f32, f32)] = &[
+    (3.3, 0.0),
+    (3.5, 0.05),
+    (3.6, 0.1),
+    (3.7, 0.3),
+    (3.8, 0.5),
+    (3.9, 0.65),
+    (4.0, 0.8),
+    (4.1, 0.9),
+    (4.2, 1.0),
+];
+
+/// Estimates charge of a single-cell LiPo from its voltage
+pub fn lipo_charge(voltage: f32) -> f32 {
+    let upper = LIPO_CURVE
+        .iter()
+        .position(|&(v, _)| v >= voltage)
+        .unwrap_or(LIPO_CURVE.len() - 1);
+    if upper == 0 {
+        return 0.0;
+    }
+    let (v0, c0) = LIPO_CURVE[upper - 1];
+    let (v1, c1) = LIPO_CURVE[upper];
+    (c0 + (voltage - v0) / (v1 - v0) * (c1 - c0)).clamp(0.0, 1.0)
+}
+
+/// Battery whose voltage drops linearly over a given time, for trying out low battery behavior on
+/// Linux. Enabled with EVIL_ANDROID_BATTERY_MINUTES.
+pub struct SimulatedBattery {
+    start: Instant,
+    lifetime: Duration,
+}
+
+impl SimulatedBattery {
+    pub fn from_env() -> Option<Self> {
+        let minutes: f32 = std::env::var("EVIL_ANDROID_BATTERY_MINUTES")
+            .ok()?
+            .parse()
+            .inspect_err(|e| log::warn!("invalid EVIL_ANDROID_BATTERY_MINUTES: {e}"))
+            .ok()?;
+        Some(Self {
+            start: Instant::now(),
+            lifetime: Duration::from_secs_f32(minutes * 60.0),
+        })
+    }
+
+    pub fn status(&self) -> BatteryStatus {
+        let left = 1.0 - self.start.elapsed().as_secs_f32() / self.lifetime.as_secs_f32();
+        let (v0, _) = LIPO_CURVE[0];
+        let (v1, _) = LIPO_CURVE[LIPO_CURVE.len() - 1];
+        let voltage = v0 + left.max(0.0) * (v1 - v0);
+        BatteryStatus {
+            voltage,
+            charge: lipo_charge(voltage),
+        }
+    }
+}
+
+/// Keeps a smoothed estimate of the battery charge, checking the platform every now and then
+#[derive(Default)]
+pub struct Monitor {
+    last_check: Option<Instant>,
+    charge: Option<f32>,
+}
+
+impl Monitor {
+    pub fn poll(&mut self, platform: &mut impl Platform) {
+        if self
+            .last_check
+            .is_some_and(|t| t.elapsed() < CHECK_INTERVAL)
+        {
+            return;
+        }
+        self.last_check = Some(Instant::now());
+
+        let Some(status) = platform.battery() else {
+            self.charge = None;
+            return;
+        };
+        let charge = match self.charge {
+            Some(charge) => charge + (status.charge - charge) * SMOOTHING,
+            None => status.charge,
+        };
+        if self.charge.is_some_and(|c| c >= LOW_CHARGE) && charge < LOW_CHARGE {
+            log::warn!("battery low: {:.2}V", status.voltage);
+        }
+        self.charge = Some(charge);
+    }
+
+    /// None if the platform is not battery powered
+    pub fn charge(&self) -> Option<f32> {
+        self.charge
+    }
+
+    pub fn is_critical(&self) -> bool {
+        self.charge.is_some_and(|c| c < CRITICAL_CHARGE)
+    }
+
+    /// Multiplier for LED brightness
+    pub fn led_scale(&self) -> f32 {
+        if self.charge.is_some_and(|c| c < LOW_CHARGE) {
+            LOW_CHARGE_LED_SCALE
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Small battery icon in the top right corner, red when low
+pub fn draw_indicator<D: DrawTarget<Color = Rgb565>>(
+    target: &mut D,
+    charge: f32,
+) -> Result<(), D::Error> {
+    const BODY_SIZE: Size = Size::new(12, 6);
+    const MARGIN: i32 = 2;
+
+    let bb = target.bounding_box();
+    let top_left = Point::new(
+        bb.top_left.x + bb.size.width as i32 - BODY_SIZE.width as i32 - 1 - MARGIN,
+        bb.top_left.y + MARGIN,
+    );
+    let [normal, low] = theme::current().battery();
+    let color = if charge < LOW_CHARGE { low } else { normal };
+
+    let body = Rectangle::new(top_left, BODY_SIZE);
+    body.into_styled(PrimitiveStyle::with_fill(Rgb565::BLACK))
+        .draw(target)?;
+    body.into_styled(PrimitiveStyle::with_stroke(color, 1))
+        .draw(target)?;
+    Rectangle::new(
+        top_left + Point::new(BODY_SIZE.width as i32, 2),
+        Size::new(1, 2),
+    )
+    .into_styled(PrimitiveStyle::with_fill(color))
+    .draw(target)?;
+    let fill_width = ((BODY_SIZE.width - 2) as f32 * charge.clamp(0.0, 1.0)).round() as u32;
+    Rectangle::new(
+        top_left + Point::new(1, 1),
+        Size::new(fill_width, BODY_SIZE.height - 2),
+    )
+    .into_styled(PrimitiveStyle::with_fill(color))
+    .draw(target)
+}
+
+/// Shows the final message for a while, then puts the platform to sleep
+pub fn shut_down(platform: &mut impl Platform) -> Result<()> {
+    log::warn!("battery critical, going to sleep");
+    platform.led0().set_brightness(0f32.into())?;
+    platform.led1().set_brightness(0f32.into())?;
+    artnet::output([0f32.into(); 2]);
+
+    let lcd = platform.lcd();
+    let center = lcd.bounding_box().center();
+    let text = "THE BUILD OUTLIVED\nTHE BATTERY";
+    let [_, low] = theme::current().battery();
+    let style = MonoTextStyle::new(&FONT_6X10, low);
+    lcd.clear(Rgb565::BLACK)
+        .and_then(|()| {
+            Text::with_alignment(
+                text,
+                layout::text_block(center, text.lines().count(), &style),
+                style,
+                Alignment::Center,
+            )
+            .draw(lcd)
+        })
+        .map_err(|e| platform.display_error(e))
+        .context("cannot show the dead battery screen")?;
+
+    let start = Instant::now();
+    while start.elapsed() < DEAD_BATTERY_SCREEN_TIME && !platform.exit_requested() {
+        platform.feed_watchdog()?;
+        platform.report_frame_stats(&crate::platform::FrameStats {
+            scene: "dead battery",
+            ..Default::default()
+        });
+        platform.sleep(Duration::from_millis(500));
+    }
+
+    platform.deep_sleep(None, None);
+    Ok(())
+}
@@ -0,0 +1,131 @@
+use std::{net::IpAddr, time::Duration};
+
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::{Point, Size},
+    mono_font::{iso_8859_1::FONT_6X10, MonoTextStyle},
+    pixelcolor::{Rgb565, Rgb888},
+    prelude::RgbColor,
+    primitives::{Primitive, PrimitiveStyle, Rectangle},
+    text::{Alignment, Text},
+    Drawable,
+};
+
+use crate::{layout::Layout, platform::Brightness, version};
+
+/// Long enough to check the test pattern and read the text, short enough not to get in the way
+pub const DURATION: Duration = Duration::from_secs(3);
+/// Each LED fades in and out once in this long
+const LED_SWEEP: Duration = Duration::from_millis(1500);
+
+/// Left to right, like on a TV test card. Swapped channels or a mirrored panel show right away.
+const BARS: [Rgb565; 8] = [
+    Rgb565::WHITE,
+    Rgb565::YELLOW,
+    Rgb565::CYAN,
+    Rgb565::GREEN,
+    Rgb565::MAGENTA,
+    Rgb565::RED,
+    Rgb565::BLUE,
+    Rgb565::BLACK,
+];
+
+/// What the splash says about the device
+pub struct Info<'a> {
+    pub name: &'a str,
+    pub platform: &'static str,
+    /// None when not networked
+    pub ip: Option<IpAddr>,
+}
+
+/// Color bars and a gray ramp at the top, the device info below, all inside an outline that
+/// shows whether the edges of the panel are where they should be
+pub fn draw<D: DrawTarget<Color = Rgb565>>(target: &mut D, info: &Info) -> Result<(), D::Error> {
+    target.clear(Rgb565::BLACK)?;
+    let layout = Layout::of(target);
+    let Size { width, height } = target.bounding_box().size;
+    let bars_height = height / 4;
+    for (i, &color) in BARS.iter().enumerate() {
+        let left = width * i as u32 / BARS.len() as u32;
+        let right = width * (i as u32 + 1) / BARS.len() as u32;
+        let bar = Rectangle::new(
+            Point::new(left as i32, 0),
+            Size::new(right - left, bars_height),
+        );
+        target.fill_solid(&bar, color)?;
+    }
+    // Every level of the panel, banding or missing bits show up as steps
+    let ramp_height = (height / 10).max(1);
+    for x in 0..width {
+        let level = x * 255 / width.saturating_sub(1).max(1);
+        let column = Rectangle::new(
+            Point::new(x as i32, bars_height as i32),
+            Size::new(1, ramp_height),
+        );
+        target.fill_solid(&column, gray(level as u8))?;
+    }
+    Rectangle::new(Point::zero(), Size::new(width, height))
+        .into_styled(PrimitiveStyle::with_stroke(Rgb565::WHITE, 1))
+        .draw(target)?;
+
+    let mut text = format!(
+        "{}\nv{}\n{} {}",
+        info.name,
+        version::short(),
+        info.platform,
+        version::BUILD_DATE
+    );
+    if let Some(ip) = info.ip {
+        text += &format!("\n{ip}");
+    }
+    let style = MonoTextStyle::new(&FONT_6X10, Rgb565::WHITE);
+    let top = (bars_height + ramp_height) as i32;
+    let center = Point::new(layout.center().x, top + (height as i32 - top) / 2);
+    Text::with_alignment(
+        &text,
+        crate::layout::text_block(center, text.lines().count(), &style),
+        style,
+        Alignment::Center,
+    )
+    .draw(target)?;
+    Ok(())
+}
+
+fn gray(level: u8) -> Rgb565 {
+    Rgb565::new(level >> 3, level >> 2, level >> 3)
+}
+
+/// Colors of both LEDs `t` into the splash. White, so that RGB ones light up every channel.
+pub fn led_colors(t: Duration) -> [Rgb888; 2] {
+    led_sweep(t).map(|brightness| {
+        let level = (f32::from(brightness) * 255.0) as u8;
+        Rgb888::new(level, level, level)
+    })
+}
+
+/// Brightness of both LEDs `t` into the splash: one fades in and out, then the other
+fn led_sweep(t: Duration) -> [Brightness; 2] {
+    let phase = (t.as_secs_f32() / LED_SWEEP.as_secs_f32()).fract() * 2.0;
+    let fade = |x: f32| (1.0 - (x * 2.0 - 1.0).abs()).into();
+    match phase < 1.0 {
+        true => [fade(phase), 0.0.into()],
+        false => [0.0.into(), fade(phase - 1.0)],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sweeps_one_led_at_a_time() {
+        let at = |ms| led_sweep(Duration::from_millis(ms)).map(f32::from);
+        assert_eq!(at(0), [0.0, 0.0]);
+        assert_eq!(at(375), [1.0, 0.0]);
+        assert_eq!(at(1125), [0.0, 1.0]);
+        for ms in (0..3000).step_by(10) {
+            let [led0, led1] = at(ms);
+            assert!(led0 == 0.0 || led1 == 0.0, "{ms}");
+        }
+    }
+}
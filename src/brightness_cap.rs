@@ -0,0 +1,126 @@
+use std::sync::Mutex;
+
+use anyhow::{bail, Context, Result};
+use embedded_graphics::pixelcolor::{Rgb888, RgbColor};
+use serde::Deserialize;
+use toml::value::Datetime;
+
+use crate::{clock, platform::Brightness, wall_clock};
+
+/// `[brightness]` in config.toml. The bare LEDs at full duty are blinding across a dark room.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Nothing lights up brighter than that, 0..1
+    pub max: f32,
+    /// Ceiling during quiet hours
+    pub night_max: f32,
+    /// Local time of day when quiet hours start, e.g. `22:00:00`. None means no quiet hours.
+    pub quiet_start: Option<Datetime>,
+    /// Local time of day when they end
+    pub quiet_end: Option<Datetime>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            max: 1.0,
+            night_max: 0.1,
+            quiet_start: None,
+            quiet_end: None,
+        }
+    }
+}
+
+struct State {
+    max: f32,
+    night_max: f32,
+    /// Start and end, in minutes of the local day
+    quiet_hours: Option<(u32, u32)>,
+}
+
+impl State {
+    /// Ceiling at `minute` of the local day, None if the wall clock isn't set
+    fn ceiling(&self, minute: Option<u32>) -> f32 {
+        let quiet = match (minute, self.quiet_hours) {
+            (Some(minute), Some((start, end))) => clock::is_between(minute, start, end),
+            _ => false,
+        };
+        match quiet {
+            true => self.max.min(self.night_max),
+            false => self.max,
+        }
+    }
+}
+
+fn parse(config: &Config) -> Result<State> {
+    let quiet_hours = match (&config.quiet_start, &config.quiet_end) {
+        (None, None) => None,
+        (Some(start), Some(end)) => Some((
+            clock::minute_of_day(start).context("invalid quiet_start")?,
+            clock::minute_of_day(end).context("invalid quiet_end")?,
+        )),
+        _ => bail!("quiet_start and quiet_end need to be set together"),
+    };
+    Ok(State {
+        max: config.max.clamp(0.0, 1.0),
+        night_max: config.night_max.clamp(0.0, 1.0),
+        quiet_hours,
+    })
+}
+
+// Read on every LED update, from whichever thread does it
+static STATE: Mutex<State> = Mutex::new(State {
+    max: 1.0,
+    night_max: 1.0,
+    quiet_hours: None,
+});
+
+pub fn init(config: Option<&Config>) {
+    let Some(config) = config else {
+        return;
+    };
+    match parse(config) {
+        Ok(state) => *STATE.lock().unwrap() = state,
+        Err(e) => log::error!("invalid brightness config: {e:#}"),
+    }
+}
+
+/// The highest brightness allowed right now. Quiet hours need the wall clock, until it's set
+/// only `max` applies.
+pub fn ceiling() -> Brightness {
+    let minute = wall_clock::local_now().map(|now| now.minute_of_day());
+    STATE.lock().unwrap().ceiling(minute).into()
+}
+
+pub fn limit(brightness: Brightness) -> Brightness {
+    f32::from(brightness).min(ceiling().into()).into()
+}
+
+/// Each channel capped, so that no part of an RGB LED shines brighter than a plain one could
+pub fn limit_color(color: Rgb888) -> Rgb888 {
+    let max = (f32::from(ceiling()) * 255.0) as u8;
+    Rgb888::new(color.r().min(max), color.g().min(max), color.b().min(max))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dims_during_quiet_hours() {
+        let parse_toml = |text| parse(&toml::from_str(text).unwrap());
+        let state = parse_toml("max = 0.8\nquiet_start = 22:00:00\nquiet_end = 07:00:00").unwrap();
+        assert_eq!(state.ceiling(Some(12 * 60)), 0.8);
+        assert_eq!(state.ceiling(Some(23 * 60)), 0.1);
+        assert_eq!(state.ceiling(Some(6 * 60 + 59)), 0.1);
+        assert_eq!(state.ceiling(Some(7 * 60)), 0.8);
+        // Clock not set yet
+        assert_eq!(state.ceiling(None), 0.8);
+        // Never brighter than `max`, quiet or not
+        let state = parse_toml("max = 0.05\nquiet_start = 00:00:00\nquiet_end = 23:59:00").unwrap();
+        assert_eq!(state.ceiling(Some(60)), 0.05);
+        assert!(parse_toml("quiet_start = 22:00:00").is_err());
+        assert!(parse_toml("quiet_start = 2024-01-01\nquiet_end = 07:00:00").is_err());
+    }
+}
@@ -0,0 +1,118 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::{bail, Result};
+use serde::Deserialize;
+use toml::value::Datetime;
+
+use crate::wall_clock::{self, days_from_civil};
+
+const FRIDAY: u32 = 4;
+/// Local time of the Friday success, in minutes since midnight
+const FRIDAY_SUCCESS_MINUTE: u32 = 17 * 60;
+/// Still happens if the android wakes up or finishes something else a bit late
+const FRIDAY_SUCCESS_WINDOW_MINUTES: u32 = 10;
+
+// Read by show_frame, which is called from all over the place
+static UPSIDE_DOWN: AtomicBool = AtomicBool::new(false);
+
+/// Special behaviors on some dates, in local time. Configured with `[calendar]` in
+/// config.toml, everything is on by default.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Calendar {
+    /// Everything upside down on April 1st
+    april_fools: bool,
+    /// The build succeeds on Fridays at 17:00. For a few seconds.
+    friday_success: bool,
+    /// Dumpster fire from the first frame on that day, e.g. `2025-06-30`
+    release_day: Option<Datetime>,
+}
+
+impl Default for Calendar {
+    fn default() -> Self {
+        Self {
+            april_fools: true,
+            friday_success: true,
+            release_day: None,
+        }
+    }
+}
+
+impl Calendar {
+    /// Fails for a release day with a time or without a date
+    pub fn validate(&self) -> Result<()> {
+        self.release_day()?;
+        Ok(())
+    }
+
+    /// Days since 1970-01-01
+    fn release_day(&self) -> Result<Option<i64>> {
+        match &self.release_day {
+            Some(Datetime {
+                date: Some(date),
+                time: None,
+                offset: None,
+            }) => Ok(Some(days_from_civil(
+                date.year.into(),
+                date.month.into(),
+                date.day.into(),
+            ))),
+            Some(other) => bail!("release_day should be just a date, like 2025-06-30, got {other}"),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Whether frames should be flipped before showing them
+pub fn is_upside_down() -> bool {
+    UPSIDE_DOWN.load(Ordering::Relaxed)
+}
+
+/// Checks the date, remembering what already happened today. Does nothing until the clock is
+/// set.
+pub struct Triggers {
+    calendar: Calendar,
+    /// Day of the last Friday success, not to repeat it within the window
+    friday_success_day: Option<i64>,
+}
+
+impl Triggers {
+    pub fn new(calendar: Calendar) -> Self {
+        Self {
+            calendar,
+            friday_success_day: None,
+        }
+    }
+
+    /// Returns true on the release day, meaning the escalation should skip to the fire
+    pub fn on_escalation_start(&mut self) -> bool {
+        let Some(now) = wall_clock::local_now() else {
+            return false;
+        };
+        let april_fools = self.calendar.april_fools && now.month == 4 && now.day == 1;
+        if UPSIDE_DOWN.swap(april_fools, Ordering::Relaxed) != april_fools {
+            log::info!("upside down: {april_fools}");
+        }
+        matches!(self.calendar.release_day(), Ok(Some(day)) if day == now.days_since_epoch())
+    }
+
+    /// Returns true once per Friday, at 17:00
+    pub fn friday_success_due(&mut self) -> bool {
+        if !self.calendar.friday_success {
+            return false;
+        }
+        let Some(now) = wall_clock::local_now() else {
+            return false;
+        };
+        let today = now.days_since_epoch();
+        let due = now.weekday == FRIDAY
+            && (FRIDAY_SUCCESS_MINUTE..FRIDAY_SUCCESS_MINUTE + FRIDAY_SUCCESS_WINDOW_MINUTES)
+                .contains(&now.minute_of_day())
+            && self.friday_success_day != Some(today);
+        if due {
+            self.friday_success_day = Some(today);
+            log::info!("it's Friday 17:00, the build succeeds");
+        }
+        due
+    }
+}
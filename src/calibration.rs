@@ -0,0 +1,502 @@
+//! Color correction for the panel. Cheap ST7735 clones are all off in their own way, a profile
+//! picked for the one at hand is applied to every frame on the way to it.
+
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use anyhow::{bail, Result};
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::{Point, Size},
+    mono_font::{iso_8859_1::FONT_6X10, MonoTextStyle},
+    pixelcolor::Rgb565,
+    prelude::RgbColor,
+    primitives::Rectangle,
+    text::{Alignment, Baseline, Text, TextStyleBuilder},
+    Drawable,
+};
+use serde::Deserialize;
+
+use crate::{
+    color565,
+    platform::Platform,
+    scene::{Canvas, Scene, SceneContext},
+};
+
+/// Of the profile picked on the console, which takes over from config.toml
+const STORAGE_KEY: &str = "calibration";
+/// Channels are mixed with this many bits of precision, gamma gets applied to that
+const SIGNAL_BITS: u32 = 10;
+const SIGNAL_MAX: i32 = (1 << SIGNAL_BITS) - 1;
+/// Of the color matrix in fixed point
+const MATRIX_ONE: f32 = (1 << SIGNAL_BITS) as f32;
+/// Highest level of red, green and blue
+const CHANNEL_MAX: [u8; 3] = [31, 63, 31];
+/// Steps of each ramp of the test pattern
+const RAMP_STEPS: u32 = 16;
+/// Of each entry of the color matrix, either way, so that mixing can't overflow
+const MATRIX_MAX: f32 = 16.0;
+
+const IDENTITY: [[f32; 3]; 3] = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+/// How to correct a panel. Each pixel gets mixed by `matrix`, then every channel raised to the
+/// power of its `gamma`, both on levels from 0 to 1.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    /// Red, green and blue. Above 1 darkens the midtones, below 1 lifts them.
+    pub gamma: [f32; 3],
+    /// Rows make red, green and blue out of the red, green and blue of the frame
+    pub matrix: [[f32; 3]; 3],
+}
+
+const NONE: Profile = Profile {
+    gamma: [1.0; 3],
+    matrix: IDENTITY,
+};
+
+impl Default for Profile {
+    fn default() -> Self {
+        NONE
+    }
+}
+
+/// Name and profile, in the order `panel` on the console lists them
+const BUILTIN: &[(&str, Profile)] = &[
+    ("none", NONE),
+    // Panels with red and blue the other way around than the build settings say
+    (
+        "swap-rb",
+        Profile {
+            gamma: [1.0; 3],
+            matrix: [[0.0, 0.0, 1.0], [0.0, 1.0, 0.0], [1.0, 0.0, 0.0]],
+        },
+    ),
+    // The most common kind of clone, where anything light turns minty
+    (
+        "green-cast",
+        Profile {
+            gamma: [1.0, 1.15, 1.0],
+            matrix: [[1.0, 0.0, 0.0], [0.0, 0.85, 0.0], [0.0, 0.0, 1.0]],
+        },
+    ),
+    // Midtones that look faded, blacks that look gray
+    (
+        "washed-out",
+        Profile {
+            gamma: [1.4; 3],
+            matrix: IDENTITY,
+        },
+    ),
+];
+
+/// `[calibration]` in config.toml
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Name of a built-in profile or one from `profiles`. One picked on the console wins.
+    pub profile: Option<String>,
+    /// Made to measure for particular panels, by name
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+impl Config {
+    pub fn validate(&self) -> Result<()> {
+        for (name, profile) in &self.profiles {
+            if !profile
+                .gamma
+                .iter()
+                .all(|gamma| *gamma > 0.0 && gamma.is_finite())
+            {
+                bail!("{name}: gamma must be positive");
+            }
+            if !profile
+                .matrix
+                .iter()
+                .flatten()
+                .all(|x| x.abs() <= MATRIX_MAX)
+            {
+                bail!("{name}: matrix entries must be between -{MATRIX_MAX} and {MATRIX_MAX}");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Lookup tables of a profile, to go through a whole frame quickly
+pub struct Table {
+    /// Level of each channel scaled to the signal range
+    decode: [[u16; 64]; 3],
+    /// In fixed point, None if it doesn't change anything
+    matrix: Option<[[i32; 3]; 3]>,
+    /// Signal of each channel back to its level, gamma applied
+    encode: [[u8; SIGNAL_MAX as usize + 1]; 3],
+}
+
+impl Table {
+    /// None if the profile leaves colors as they are
+    fn new(profile: &Profile) -> Option<Self> {
+        if *profile == NONE {
+            return None;
+        }
+        let mut decode = [[0; 64]; 3];
+        let mut encode = [[0; SIGNAL_MAX as usize + 1]; 3];
+        for channel in 0..3 {
+            let max = u32::from(CHANNEL_MAX[channel]);
+            for level in 0..=max {
+                decode[channel][level as usize] = (level * SIGNAL_MAX as u32 / max) as u16;
+            }
+            let gamma = profile.gamma[channel].max(0.01);
+            for (signal, level) in encode[channel].iter_mut().enumerate() {
+                let corrected = (signal as f32 / SIGNAL_MAX as f32).powf(gamma);
+                *level = (corrected * max as f32).round() as u8;
+            }
+        }
+        let matrix = (profile.matrix != IDENTITY).then(|| {
+            profile
+                .matrix
+                .map(|row| row.map(|x| (x * MATRIX_ONE).round() as i32))
+        });
+        Some(Self {
+            decode,
+            matrix,
+            encode,
+        })
+    }
+
+    pub fn apply(&self, color: Rgb565) -> Rgb565 {
+        let levels = color565::split(color);
+        let signal: [i32; 3] =
+            std::array::from_fn(|i| i32::from(self.decode[i][levels[i] as usize]));
+        let mixed = match &self.matrix {
+            Some(matrix) => matrix.map(|row| {
+                let sum: i32 = row.iter().zip(&signal).map(|(m, s)| m * s).sum();
+                (sum >> SIGNAL_BITS).clamp(0, SIGNAL_MAX)
+            }),
+            None => signal,
+        };
+        color565::merge(std::array::from_fn(|i| self.encode[i][mixed[i] as usize]))
+    }
+
+    /// Corrects `area` of a frame `width` pixels wide into the same place in `out`, which ends
+    /// up as big as the frame. The rest of `out` is left as it was.
+    pub fn apply_area(
+        &self,
+        pixels: &[Rgb565],
+        width: usize,
+        area: Rectangle,
+        out: &mut Vec<Rgb565>,
+    ) {
+        out.resize(pixels.len(), Rgb565::BLACK);
+        let (left, area_width) = (area.top_left.x as usize, area.size.width as usize);
+        for y in area.rows() {
+            let row = y as usize * width + left..;
+            let corrected = out[row.clone()][..area_width].iter_mut();
+            for (out, &color) in corrected.zip(&pixels[row][..area_width]) {
+                *out = self.apply(color);
+            }
+        }
+    }
+}
+
+struct State {
+    /// From config.toml
+    profiles: BTreeMap<String, Profile>,
+    /// From config.toml, used unless another one was picked
+    configured: Option<String>,
+    /// On the console, now or before a reboot
+    picked: Option<String>,
+    name: String,
+    /// None for profiles that change nothing
+    table: Option<Arc<Table>>,
+    /// Whether the test pattern is on
+    testing: bool,
+}
+
+impl State {
+    fn find(&self, name: &str) -> Option<Profile> {
+        let builtin = BUILTIN.iter().find(|(builtin, _)| *builtin == name);
+        builtin
+            .map(|(_, profile)| *profile)
+            .or_else(|| self.profiles.get(name).copied())
+    }
+
+    /// Applies the picked profile, or the configured one. Unknown names fall back to none.
+    fn resolve(&mut self) {
+        let name = self
+            .picked
+            .clone()
+            .or_else(|| self.configured.clone())
+            .unwrap_or_else(|| "none".to_owned());
+        let profile = self.find(&name).unwrap_or_else(|| {
+            log::error!("no calibration profile named {name}, not correcting colors");
+            NONE
+        });
+        log::info!("calibration: {name}");
+        self.table = Table::new(&profile).map(Arc::new);
+        self.name = name;
+    }
+
+    fn names(&self) -> Vec<String> {
+        BUILTIN
+            .iter()
+            .map(|(name, _)| name.to_string())
+            .chain(self.profiles.keys().cloned())
+            .collect()
+    }
+}
+
+/// Picked, but not saved yet. Checked on every flush, without locking `STATE`.
+static UNSAVED: AtomicBool = AtomicBool::new(false);
+
+// Set from the console thread, read on every flush
+static STATE: Mutex<State> = Mutex::new(State {
+    profiles: BTreeMap::new(),
+    configured: None,
+    picked: None,
+    name: String::new(),
+    table: None,
+    testing: false,
+});
+
+/// Also called when config.toml changes, a profile picked on the console stays
+pub fn init(config: Option<&Config>) {
+    let config = config.cloned().unwrap_or_default();
+    let mut state = STATE.lock().unwrap();
+    state.profiles = config.profiles;
+    state.configured = config.profile;
+    state.resolve();
+}
+
+/// Brings back the profile picked before the last reboot, if any
+pub fn restore(platform: &mut impl Platform) {
+    let name = match platform.load(STORAGE_KEY) {
+        Ok(Some(name)) => String::from_utf8_lossy(&name).into_owned(),
+        Ok(None) => return,
+        Err(e) => {
+            log::error!("loading calibration profile failed: {e:?}");
+            return;
+        }
+    };
+    let mut state = STATE.lock().unwrap();
+    state.picked = Some(name);
+    state.resolve();
+}
+
+/// Saves the profile picked on the console since the last call, if any
+pub fn save_picked(platform: &mut impl Platform) {
+    if !UNSAVED.swap(false, Ordering::Relaxed) {
+        return;
+    }
+    // Set while `set` held the lock, so this sees what it picked
+    let picked = STATE.lock().unwrap().picked.clone();
+    if let Some(name) = picked {
+        if let Err(e) = platform.store(STORAGE_KEY, name.as_bytes()) {
+            log::error!("saving calibration profile failed: {e:?}");
+        }
+    }
+}
+
+/// Switches to a profile, built-in or from config.toml, and remembers it across reboots
+pub fn set(name: &str) -> Result<()> {
+    let mut state = STATE.lock().unwrap();
+    if state.find(name).is_none() {
+        bail!(
+            "no calibration profile named {name}, try one of: {}",
+            state.names().join(", ")
+        );
+    }
+    state.picked = Some(name.to_owned());
+    state.resolve();
+    UNSAVED.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Name of the profile in use
+pub fn name() -> String {
+    STATE.lock().unwrap().name.clone()
+}
+
+/// Of all profiles that can be switched to
+pub fn names() -> Vec<String> {
+    STATE.lock().unwrap().names()
+}
+
+/// None if colors go to the panel as they are
+pub fn current() -> Option<Arc<Table>> {
+    STATE.lock().unwrap().table.clone()
+}
+
+pub fn is_testing() -> bool {
+    STATE.lock().unwrap().testing
+}
+
+pub fn toggle_test_pattern() {
+    let mut state = STATE.lock().unwrap();
+    state.testing = !state.testing;
+    log::info!("calibration test pattern: {}", state.testing);
+}
+
+/// Ramps of each channel and of gray, for checking the colors and the steps between levels,
+/// and a patch of lines next to a gray that looks as bright on a panel with a gamma of 2.2.
+/// Takes over until turned off again.
+pub struct TestPattern;
+
+impl Scene for TestPattern {
+    fn name(&self) -> &'static str {
+        "calibration"
+    }
+
+    fn draw(&mut self, _t: Duration, canvas: &mut Canvas, ctx: &mut SceneContext) -> Result<bool> {
+        if !is_testing() {
+            return Ok(false);
+        }
+        draw_pattern(canvas, &name())?;
+        ctx.leds = [0.0; 2];
+        Ok(true)
+    }
+}
+
+fn draw_pattern<D: DrawTarget<Color = Rgb565>>(target: &mut D, name: &str) -> Result<(), D::Error> {
+    target.clear(Rgb565::BLACK)?;
+    let Size { width, height } = target.bounding_box().size;
+    let ramp_height = height / 6;
+    let ramps: [fn(u8) -> Rgb565; 4] = [
+        |level| Rgb565::new(level >> 3, 0, 0),
+        |level| Rgb565::new(0, level >> 2, 0),
+        |level| Rgb565::new(0, 0, level >> 3),
+        |level| Rgb565::new(level >> 3, level >> 2, level >> 3),
+    ];
+    for (row, ramp) in ramps.iter().enumerate() {
+        for step in 0..RAMP_STEPS {
+            let left = width * step / RAMP_STEPS;
+            let right = width * (step + 1) / RAMP_STEPS;
+            let level = step * 255 / (RAMP_STEPS - 1);
+            let block = Rectangle::new(
+                Point::new(left as i32, (row as u32 * ramp_height) as i32),
+                Size::new(right - left, ramp_height),
+            );
+            target.fill_solid(&block, ramp(level as u8))?;
+        }
+    }
+
+    // Half the lines lit is half the light. Gray that bright is at 73% of the range at a gamma
+    // of 2.2, the two halves look the same when the panel gets it right.
+    let top = 4 * ramp_height;
+    let patch_height = height - top - FONT_6X10.character_size.height - 2;
+    for y in (top..top + patch_height).step_by(2) {
+        let line = Rectangle::new(Point::new(0, y as i32), Size::new(width / 2, 1));
+        target.fill_solid(&line, Rgb565::WHITE)?;
+    }
+    let gray = Rectangle::new(
+        Point::new((width / 2) as i32, top as i32),
+        Size::new(width - width / 2, patch_height),
+    );
+    target.fill_solid(&gray, Rgb565::new(23, 46, 23))?;
+
+    Text::with_text_style(
+        name,
+        Point::new(width as i32 / 2, height as i32 - 1),
+        MonoTextStyle::new(&FONT_6X10, Rgb565::WHITE),
+        TextStyleBuilder::new()
+            .alignment(Alignment::Center)
+            .baseline(Baseline::Bottom)
+            .build(),
+    )
+    .draw(target)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(name: &str) -> Table {
+        let (_, profile) = BUILTIN
+            .iter()
+            .find(|(builtin, _)| *builtin == name)
+            .unwrap();
+        Table::new(profile).unwrap()
+    }
+
+    #[test]
+    fn corrects_colors() {
+        assert!(Table::new(&NONE).is_none());
+        let swap = table("swap-rb");
+        assert_eq!(swap.apply(Rgb565::RED), Rgb565::BLUE);
+        assert_eq!(swap.apply(Rgb565::new(3, 40, 17)), Rgb565::new(17, 40, 3));
+        // Ends of the range stay where they are, the middle gets darker
+        let washed_out = table("washed-out");
+        assert_eq!(washed_out.apply(Rgb565::WHITE), Rgb565::WHITE);
+        assert_eq!(washed_out.apply(Rgb565::BLACK), Rgb565::BLACK);
+        let [r, g, b] = color565::split(washed_out.apply(Rgb565::new(16, 32, 16)));
+        assert!(r < 16 && g < 32 && b < 16, "{r} {g} {b}");
+        // Gamma of 1 and a matrix that does nothing
+        let profile = Profile {
+            gamma: [1.0; 3],
+            matrix: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 0.999]],
+        };
+        let nearly_none = Table::new(&profile).unwrap();
+        for level in 0..32 {
+            let color = Rgb565::new(level, level * 2 + 1, 31 - level);
+            assert_eq!(
+                color565::split(nearly_none.apply(color))[..2],
+                [level, level * 2 + 1]
+            );
+        }
+    }
+
+    #[test]
+    fn corrects_only_the_area() {
+        let swap = table("swap-rb");
+        let pixels = vec![Rgb565::RED; 4 * 3];
+        let mut out = vec![Rgb565::GREEN; 4 * 3];
+        swap.apply_area(
+            &pixels,
+            4,
+            Rectangle::new(Point::new(1, 1), Size::new(2, 2)),
+            &mut out,
+        );
+        let blue = |x: usize, y: usize| out[y * 4 + x] == Rgb565::BLUE;
+        for y in 0..3 {
+            for x in 0..4 {
+                assert_eq!(
+                    blue(x, y),
+                    (1..3).contains(&x) && (1..3).contains(&y),
+                    "{x} {y}"
+                );
+            }
+        }
+        // Grows to the size of the frame
+        let mut out = Vec::new();
+        swap.apply_area(
+            &pixels,
+            4,
+            Rectangle::new(Point::zero(), Size::new(4, 3)),
+            &mut out,
+        );
+        assert_eq!(out, vec![Rgb565::BLUE; 4 * 3]);
+    }
+
+    #[test]
+    fn parses_config() {
+        let config: Config =
+            toml::from_str("profile = \"mine\"\n[profiles.mine]\ngamma = [1.0, 1.2, 1.0]").unwrap();
+        assert_eq!(config.profile.as_deref(), Some("mine"));
+        assert_eq!(config.profiles["mine"].gamma, [1.0, 1.2, 1.0]);
+        assert_eq!(config.profiles["mine"].matrix, IDENTITY);
+        assert!(config.validate().is_ok());
+        assert!(toml::from_str::<Config>("[profiles.mine]\ngama = [1.0, 1.0, 1.0]").is_err());
+        let mixing = |row| format!("[profiles.mine]\nmatrix = [{row}, [0, 1, 0], [0, 0, 1]]");
+        for row in ["[1e9, 0, 0]", "[0, -17, 0]", "[nan, 0, 0]"] {
+            let config: Config = toml::from_str(&mixing(row)).unwrap();
+            assert!(config.validate().is_err(), "{row}");
+        }
+    }
+}
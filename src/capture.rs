@@ -0,0 +1,179 @@
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+use embedded_graphics::{
+    geometry::{Point, Size},
+    primitives::Rectangle,
+};
+
+use crate::mirror;
+
+const DEFAULT_FPS: u32 = 10;
+const DEFAULT_SIZE: Size = Size::new(160, 128);
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+/// What to capture and where to send it
+struct Options {
+    region: Rectangle,
+    to: String,
+    fps: u32,
+    /// Of the LCD on the other end
+    size: Size,
+}
+
+/// `<w>x<h>`
+fn parse_size(s: &str) -> Option<Size> {
+    let (w, h) = s.split_once('x')?;
+    Some(Size::new(w.parse().ok()?, h.parse().ok()?))
+}
+
+/// X11 geometry, `<w>x<h>+<x>+<y>`
+fn parse_region(s: &str) -> Result<Rectangle> {
+    let parsed = s.split_once('+').and_then(|(size, offset)| {
+        let (x, y) = offset.split_once('+')?;
+        Some(Rectangle::new(
+            Point::new(x.parse().ok()?, y.parse().ok()?),
+            parse_size(size)?,
+        ))
+    });
+    match parsed {
+        Some(region) if region.size.width > 0 && region.size.height > 0 => Ok(region),
+        _ => bail!("invalid region: {s}, expected <w>x<h>+<x>+<y>"),
+    }
+}
+
+impl Options {
+    /// Taken from `--mirror <w>x<h>+<x>+<y> --to <host:port> [--fps <n>] [--size <w>x<h>]`
+    fn from_args() -> Result<Self> {
+        let mut region = None;
+        let mut to = None;
+        let mut fps = DEFAULT_FPS;
+        let mut size = DEFAULT_SIZE;
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().with_context(|| format!("{arg} needs a value"));
+            match arg.as_str() {
+                "--mirror" => region = Some(parse_region(&value()?)?),
+                "--to" => to = Some(value()?),
+                "--fps" => fps = value()?.parse().context("invalid --fps")?,
+                "--size" => {
+                    let value = value()?;
+                    size = parse_size(&value)
+                        .filter(|s| s.width > 0 && s.height > 0)
+                        .with_context(|| format!("invalid --size: {value}"))?;
+                }
+                _ => {}
+            }
+        }
+        let (Some(region), Some(to)) = (region, to) else {
+            bail!("--mirror needs a region and --to");
+        };
+        if fps == 0 {
+            bail!("--fps must be positive");
+        }
+        Ok(Self {
+            region,
+            to,
+            fps,
+            size,
+        })
+    }
+}
+
+pub fn requested() -> bool {
+    std::env::args().any(|arg| arg == "--mirror")
+}
+
+/// Captures a region of the host screen with ffmpeg and streams it to an android's
+/// EVIL_ANDROID_MIRROR_PORT. Runs until ffmpeg stops, reconnecting whenever the android goes
+/// away.
+pub fn run() -> Result<()> {
+    let options = Options::from_args()?;
+    let Options { region, size, .. } = options;
+    let display = std::env::var("DISPLAY").unwrap_or_else(|_| ":0".to_owned());
+    let mut ffmpeg = Command::new("ffmpeg")
+        .args(["-loglevel", "error"])
+        .args(["-f", "x11grab", "-draw_mouse", "0"])
+        .args(["-framerate", &options.fps.to_string()])
+        .args([
+            "-video_size",
+            &format!("{}x{}", region.size.width, region.size.height),
+        ])
+        .args([
+            "-i",
+            &format!("{display}+{},{}", region.top_left.x, region.top_left.y),
+        ])
+        // Area averaging keeps small text legible-ish, black bars keep the aspect ratio
+        .args([
+            "-vf",
+            &format!(
+                "scale={w}:{h}:force_original_aspect_ratio=decrease:flags=area,\
+                 pad={w}:{h}:(ow-iw)/2:(oh-ih)/2",
+                w = size.width,
+                h = size.height
+            ),
+        ])
+        .args(["-pix_fmt", "rgb565be", "-f", "rawvideo", "-"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("cannot run ffmpeg, is it installed?")?;
+    let mut frames = ffmpeg.stdout.take().context("no ffmpeg stdout")?;
+    log::info!(
+        "mirroring {}x{}+{}+{} to {}",
+        region.size.width,
+        region.size.height,
+        region.top_left.x,
+        region.top_left.y,
+        options.to
+    );
+
+    let header = mirror::header(size);
+    let mut frame = vec![0; (size.width * size.height * 2) as usize];
+    loop {
+        let mut stream = match TcpStream::connect(&options.to) {
+            Ok(stream) => stream,
+            Err(e) => {
+                log::warn!("cannot connect to {}: {e}", options.to);
+                std::thread::sleep(RECONNECT_DELAY);
+                continue;
+            }
+        };
+        stream.set_nodelay(true)?;
+        log::info!("connected to {}", options.to);
+        loop {
+            frames
+                .read_exact(&mut frame)
+                .context("ffmpeg stopped capturing")?;
+            if let Err(e) = stream
+                .write_all(&header)
+                .and_then(|()| stream.write_all(&frame))
+            {
+                log::warn!("lost {}: {e}", options.to);
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_regions() {
+        assert_eq!(
+            parse_region("320x240+10+20").unwrap(),
+            Rectangle::new(Point::new(10, 20), Size::new(320, 240))
+        );
+        assert!(parse_region("320x240").is_err());
+        assert!(parse_region("0x240+0+0").is_err());
+        assert!(parse_region("320x240+a+0").is_err());
+        assert_eq!(parse_size("160x128"), Some(DEFAULT_SIZE));
+        assert_eq!(parse_size("160"), None);
+    }
+}
@@ -0,0 +1,296 @@
+use std::{
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+use crate::osc::{self, Arg};
+
+/// How often the server checks whether anything is due
+const TICK: Duration = Duration::from_millis(10);
+
+/// Coordinates a shelf of androids over OSC, see `Config`
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    /// `host:port` of each android's OSC listener, in the order waves go through them
+    devices: Vec<String>,
+    /// Fraction of the escalation each device is ahead of the previous one
+    #[serde(default)]
+    phase_step: f32,
+    /// Seconds between sending phases again, as devices drift apart with differing frame
+    /// rates. 0 sends them once, at the start.
+    #[serde(default)]
+    resync_secs: f32,
+    /// Seconds between switching every device to the next scene at once. 0 never does.
+    #[serde(default)]
+    next_scene_secs: f32,
+    burst: Option<Burst>,
+}
+
+/// Glitch bursts rolling through the devices one after another
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Burst {
+    /// Seconds between waves
+    every_secs: f32,
+    /// 0..1, as in `/evil/burst`
+    #[serde(default = "Burst::default_strength")]
+    strength: f32,
+    /// How long each device glitches
+    #[serde(default = "Burst::default_duration_ms")]
+    duration_ms: u32,
+    /// Delay between neighbors, the speed of the wave
+    #[serde(default = "Burst::default_step_ms")]
+    step_ms: u32,
+}
+
+impl Burst {
+    fn default_strength() -> f32 {
+        1.0
+    }
+
+    fn default_duration_ms() -> u32 {
+        300
+    }
+
+    fn default_step_ms() -> u32 {
+        150
+    }
+}
+
+impl Config {
+    fn parse(text: &str) -> Result<Self> {
+        let config: Self = toml::from_str(text)?;
+        if config.devices.is_empty() {
+            bail!("no devices");
+        }
+        let mut intervals = [config.resync_secs, config.next_scene_secs]
+            .into_iter()
+            .chain(config.burst.as_ref().map(|b| b.every_secs));
+        if intervals.any(|s| !(s >= 0.0 && s.is_finite())) {
+            bail!("intervals must not be negative");
+        }
+        if config.burst.as_ref().is_some_and(|b| b.every_secs == 0.0) {
+            bail!("burst.every_secs must be positive");
+        }
+        Ok(config)
+    }
+}
+
+/// None for 0, which means never
+fn interval(secs: f32) -> Option<Duration> {
+    (secs > 0.0).then(|| Duration::from_secs_f32(secs))
+}
+
+/// What should go out to which device
+#[derive(Debug, PartialEq)]
+struct Outgoing {
+    device: usize,
+    address: &'static str,
+    args: Vec<Arg>,
+}
+
+/// Decides what to send when, without sending anything
+struct Schedule {
+    config: Config,
+    next_sync: Option<Instant>,
+    next_scene: Option<Instant>,
+    next_burst: Option<Instant>,
+    /// Devices the current wave didn't reach yet
+    pending_bursts: Vec<(Instant, usize)>,
+}
+
+impl Schedule {
+    fn new(config: Config, start: Instant) -> Self {
+        Self {
+            next_sync: Some(start),
+            next_scene: interval(config.next_scene_secs).map(|i| start + i),
+            next_burst: config
+                .burst
+                .as_ref()
+                .and_then(|b| interval(b.every_secs))
+                .map(|i| start + i),
+            pending_bursts: Vec::new(),
+            config,
+        }
+    }
+
+    /// Position in the escalation that device `index` should be at
+    fn phase(&self, index: usize) -> f32 {
+        (index as f32 * self.config.phase_step).rem_euclid(1.0)
+    }
+
+    /// Everything due by `now`
+    fn due(&mut self, now: Instant) -> Vec<Outgoing> {
+        let devices = 0..self.config.devices.len();
+        let mut out = Vec::new();
+        if self.next_sync.is_some_and(|at| at <= now) {
+            out.extend(devices.clone().map(|device| Outgoing {
+                device,
+                address: "/evil/seek",
+                args: vec![Arg::Float(self.phase(device))],
+            }));
+            self.next_sync = interval(self.config.resync_secs).map(|i| now + i);
+        }
+        if self.next_scene.is_some_and(|at| at <= now) {
+            out.extend(devices.clone().map(|device| Outgoing {
+                device,
+                address: "/evil/input",
+                args: vec![Arg::String("next".to_owned())],
+            }));
+            self.next_scene = interval(self.config.next_scene_secs).map(|i| now + i);
+        }
+        if let Some(burst) = &self.config.burst {
+            if self.next_burst.is_some_and(|at| at <= now) {
+                let step = Duration::from_millis(burst.step_ms.into());
+                self.pending_bursts
+                    .extend(devices.map(|device| (now + step * device as u32, device)));
+                self.next_burst = interval(burst.every_secs).map(|i| now + i);
+            }
+            self.pending_bursts.retain(|&(at, device)| {
+                if at > now {
+                    return true;
+                }
+                out.push(Outgoing {
+                    device,
+                    address: "/evil/burst",
+                    args: vec![
+                        Arg::Float(burst.strength),
+                        Arg::Int(burst.duration_ms as i32),
+                    ],
+                });
+                false
+            });
+        }
+        out
+    }
+}
+
+/// Taken from `--choreograph <config.toml>`
+pub fn requested() -> Option<PathBuf> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--choreograph" {
+            return args.next().map(PathBuf::from);
+        }
+    }
+    None
+}
+
+fn resolve(device: &str) -> Result<SocketAddr> {
+    device
+        .to_socket_addrs()
+        .with_context(|| format!("cannot resolve {device}"))?
+        .next()
+        .with_context(|| format!("no address for {device}"))
+}
+
+/// Runs the server until killed
+pub fn run(path: &Path) -> Result<()> {
+    let text =
+        std::fs::read_to_string(path).with_context(|| format!("cannot read {}", path.display()))?;
+    let config =
+        Config::parse(&text).with_context(|| format!("invalid config: {}", path.display()))?;
+    let addresses = config
+        .devices
+        .iter()
+        .map(|device| resolve(device))
+        .collect::<Result<Vec<_>>>()?;
+    let socket = UdpSocket::bind("0.0.0.0:0").context("UdpSocket::bind failed")?;
+    // So that a single broadcast address can stand for a whole shelf
+    socket.set_broadcast(true)?;
+    log::info!("choreographing {} devices", addresses.len());
+
+    let mut schedule = Schedule::new(config, Instant::now());
+    loop {
+        for outgoing in schedule.due(Instant::now()) {
+            let to = addresses[outgoing.device];
+            log::debug!("{to}: {} {:?}", outgoing.address, outgoing.args);
+            // An android that's off or asleep shouldn't stop the others
+            if let Err(e) = socket.send_to(&osc::message(outgoing.address, &outgoing.args), to) {
+                log::warn!("cannot send to {to}: {e}");
+            }
+        }
+        std::thread::sleep(TICK);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(text: &str) -> (Schedule, Instant) {
+        let start = Instant::now();
+        (Schedule::new(Config::parse(text).unwrap(), start), start)
+    }
+
+    fn summary(sends: &[Outgoing]) -> Vec<(usize, &str)> {
+        sends.iter().map(|s| (s.device, s.address)).collect()
+    }
+
+    #[test]
+    fn rejects_invalid_configs() {
+        assert!(Config::parse("devices = []").is_err());
+        assert!(Config::parse("devices = ['a:1']\nresync_secs = -1").is_err());
+        assert!(Config::parse("devices = ['a:1']\n[burst]\nevery_secs = 0").is_err());
+        assert!(Config::parse("devices = ['a:1']\nphase = 0.5").is_err());
+    }
+
+    #[test]
+    fn sends_phases_once() {
+        let (mut schedule, start) = schedule("devices = ['a:1', 'b:1', 'c:1']\nphase_step = 0.4");
+        let phases: Vec<Vec<Arg>> = schedule.due(start).into_iter().map(|s| s.args).collect();
+        assert_eq!(
+            phases,
+            [
+                vec![Arg::Float(0.0)],
+                vec![Arg::Float(0.4)],
+                vec![Arg::Float(0.8)],
+            ]
+        );
+        assert!((schedule.phase(3) - 0.2).abs() < 1e-6);
+        assert!(schedule.due(start + Duration::from_secs(3600)).is_empty());
+    }
+
+    #[test]
+    fn resyncs_and_switches_scenes() {
+        let (mut schedule, start) =
+            schedule("devices = ['a:1', 'b:1']\nresync_secs = 10\nnext_scene_secs = 15");
+        assert_eq!(schedule.due(start).len(), 2);
+        assert!(schedule.due(start + Duration::from_secs(9)).is_empty());
+        assert_eq!(
+            summary(&schedule.due(start + Duration::from_secs(10))),
+            [(0, "/evil/seek"), (1, "/evil/seek")]
+        );
+        assert_eq!(
+            summary(&schedule.due(start + Duration::from_secs(15))),
+            [(0, "/evil/input"), (1, "/evil/input")]
+        );
+    }
+
+    #[test]
+    fn bursts_roll_through_devices() {
+        let (mut schedule, start) = schedule(
+            "devices = ['a:1', 'b:1', 'c:1']\n[burst]\nevery_secs = 5\nstep_ms = 100\nstrength = 0.5",
+        );
+        schedule.due(start);
+        let at = |ms| start + Duration::from_millis(ms);
+        let bursts = schedule.due(at(5000));
+        assert_eq!(
+            bursts,
+            [Outgoing {
+                device: 0,
+                address: "/evil/burst",
+                args: vec![Arg::Float(0.5), Arg::Int(300)],
+            }]
+        );
+        assert!(schedule.due(at(5050)).is_empty());
+        assert_eq!(summary(&schedule.due(at(5100))), [(1, "/evil/burst")]);
+        assert_eq!(summary(&schedule.due(at(5200))), [(2, "/evil/burst")]);
+        assert_eq!(summary(&schedule.due(at(10000))), [(0, "/evil/burst")]);
+    }
+}
@@ -0,0 +1,302 @@
+use std::{
+    sync::{Arc, Mutex, Weak},
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::{Dimensions, Point},
+    mono_font::{
+        iso_8859_1::{FONT_10X20, FONT_6X10},
+        MonoTextStyle,
+    },
+    pixelcolor::Rgb565,
+    prelude::RgbColor,
+    text::{Alignment, Baseline, Text, TextStyleBuilder},
+    Drawable,
+};
+use serde::Deserialize;
+use toml::value::{Datetime, Time};
+
+use crate::{
+    scene::{Canvas, Scaled, Scene, SceneContext},
+    theme, wall_clock,
+};
+
+/// Only the first line of the response is shown, cut off at that many characters
+const MAX_WEATHER_CHARS: usize = 26;
+const MAX_WEATHER_RESPONSE_SIZE: usize = 256;
+/// Retried that often until the first successful fetch, e.g. while WiFi is connecting
+const WEATHER_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+// TLS handshakes need a lot of stack
+const WEATHER_STACK_SIZE: usize = 12 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Weekday {
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+    Sun,
+}
+
+/// Turns the android into a desk clock outside office hours. Configured with `[clock]` in
+/// config.toml.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Clock {
+    /// Local time of day when the evil mode starts, e.g. `09:00:00`
+    #[serde(default = "Clock::default_office_start")]
+    office_start: Datetime,
+    /// Local time of day when the clock takes over
+    #[serde(default = "Clock::default_office_end")]
+    office_end: Datetime,
+    /// Other days are all clock
+    #[serde(default = "Clock::default_office_days")]
+    office_days: Vec<Weekday>,
+    /// Plain text shown under the date, e.g. `https://wttr.in/Berlin?format=%t+%C`
+    #[serde(default)]
+    weather_url: Option<String>,
+    #[serde(default = "Clock::default_weather_refresh_secs")]
+    weather_refresh_secs: u64,
+    /// Where the time zone used to be set, before it moved to the top of config.toml. Still
+    /// used if it isn't set there.
+    #[serde(default)]
+    utc_offset_minutes: Option<i32>,
+}
+
+impl Clock {
+    fn default_office_start() -> Datetime {
+        time_of_day(9, 0)
+    }
+
+    fn default_office_end() -> Datetime {
+        time_of_day(18, 0)
+    }
+
+    fn default_office_days() -> Vec<Weekday> {
+        vec![
+            Weekday::Mon,
+            Weekday::Tue,
+            Weekday::Wed,
+            Weekday::Thu,
+            Weekday::Fri,
+        ]
+    }
+
+    fn default_weather_refresh_secs() -> u64 {
+        15 * 60
+    }
+
+    /// Time zone set the old way, in `[clock]`
+    pub fn legacy_utc_offset_minutes(&self) -> Option<i32> {
+        self.utc_offset_minutes
+    }
+
+    /// Fails for office hours that are not plain times of day
+    pub fn validate(&self) -> Result<()> {
+        minute_of_day(&self.office_start).context("invalid office_start")?;
+        minute_of_day(&self.office_end).context("invalid office_end")?;
+        Ok(())
+    }
+
+    /// Whether it's time to be evil. Always true when the clock isn't set, nobody would want
+    /// to look at 00:00 for hours.
+    pub fn is_office_hours(&self) -> bool {
+        let Some(now) = wall_clock::local_now() else {
+            return true;
+        };
+        let (Ok(start), Ok(end)) = (
+            minute_of_day(&self.office_start),
+            minute_of_day(&self.office_end),
+        ) else {
+            return true;
+        };
+        let weekday = self.office_days.iter().any(|&d| d as u32 == now.weekday);
+        weekday && is_between(now.minute_of_day(), start, end)
+    }
+
+    /// Starts fetching the weather in the background, if there's a URL for it. Fetching stops
+    /// once the returned value is dropped.
+    pub fn start_weather(&self) -> Option<Weather> {
+        let url = self.weather_url.clone()?;
+        if !cfg!(feature = "net") {
+            log::warn!("weather_url set, but built without the net feature");
+            return None;
+        }
+        let refresh = Duration::from_secs(self.weather_refresh_secs.max(60));
+        let current = Arc::new(Mutex::new(None));
+        let weak = Arc::downgrade(&current);
+        std::thread::Builder::new()
+            .name("weather".to_owned())
+            .stack_size(WEATHER_STACK_SIZE)
+            .spawn(move || fetch_weather(&url, refresh, weak))
+            .inspect_err(|e| log::error!("weather thread failed to start: {e:?}"))
+            .ok()?;
+        Some(Weather(current))
+    }
+
+    pub fn start<'a>(&'a self, weather: Option<&'a Weather>) -> ClockScene<'a> {
+        ClockScene {
+            clock: self,
+            weather,
+        }
+    }
+}
+
+fn time_of_day(hour: u8, minute: u8) -> Datetime {
+    Datetime {
+        date: None,
+        time: Some(Time {
+            hour,
+            minute,
+            second: 0,
+            nanosecond: 0,
+        }),
+        offset: None,
+    }
+}
+
+/// Minutes since midnight of a plain time of day, like `09:00:00`
+pub fn minute_of_day(datetime: &Datetime) -> Result<u32> {
+    match datetime {
+        Datetime {
+            date: None,
+            time: Some(time),
+            offset: None,
+        } => Ok(u32::from(time.hour) * 60 + u32::from(time.minute)),
+        _ => bail!("expected a time of day, like 09:00:00, got {datetime}"),
+    }
+}
+
+/// Whether `minute` of the day is from `start` up to `end`, past midnight if `end` is earlier
+pub fn is_between(minute: u32, start: u32, end: u32) -> bool {
+    if start <= end {
+        (start..end).contains(&minute)
+    } else {
+        // Night shift
+        minute >= start || minute < end
+    }
+}
+
+/// Latest weather report, kept up to date by a background thread
+pub struct Weather(Arc<Mutex<Option<String>>>);
+
+impl Weather {
+    fn current(&self) -> Option<String> {
+        self.0.lock().ok()?.clone()
+    }
+}
+
+fn fetch_weather(url: &str, refresh: Duration, current: Weak<Mutex<Option<String>>>) {
+    loop {
+        let report = crate::platform::http_get(url, MAX_WEATHER_RESPONSE_SIZE).map(|body| {
+            String::from_utf8_lossy(&body)
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .chars()
+                .take(MAX_WEATHER_CHARS)
+                .collect::<String>()
+        });
+        let Some(current) = current.upgrade() else {
+            return;
+        };
+        let retry_after = match report {
+            Ok(report) => {
+                log::info!("weather: {report}");
+                *current.lock().unwrap() = Some(report);
+                refresh
+            }
+            Err(e) => {
+                log::warn!("fetching weather failed: {e:?}");
+                match *current.lock().unwrap() {
+                    Some(_) => refresh,
+                    None => WEATHER_RETRY_INTERVAL,
+                }
+            }
+        };
+        drop(current);
+        std::thread::sleep(retry_after);
+    }
+}
+
+pub struct ClockScene<'a> {
+    clock: &'a Clock,
+    weather: Option<&'a Weather>,
+}
+
+impl Scene for ClockScene<'_> {
+    fn name(&self) -> &'static str {
+        "clock"
+    }
+
+    fn draw(&mut self, _t: Duration, canvas: &mut Canvas, ctx: &mut SceneContext) -> Result<bool> {
+        if self.clock.is_office_hours() {
+            return Ok(false);
+        }
+        let Some(now) = wall_clock::local_now() else {
+            return Ok(false);
+        };
+        let weather = self.weather.and_then(Weather::current);
+
+        let size = canvas.bounding_box().size;
+        let digits_size = FONT_10X20.character_size;
+        let details_height = FONT_6X10.character_size.height + 1;
+        let details_lines = if weather.is_some() { 2 } else { 1 };
+        // As big as fits, with some margin
+        let scale = (size.width.saturating_sub(4) / (digits_size.width * 5))
+            .min(size.height.saturating_sub(details_height * details_lines) / digits_size.height)
+            .max(1);
+        let total_height = digits_size.height * scale + details_height * details_lines;
+        let top = size.height.saturating_sub(total_height) as i32 / 2;
+        let top_center = TextStyleBuilder::new()
+            .alignment(Alignment::Center)
+            .baseline(Baseline::Top)
+            .build();
+
+        let [digits_color, details_color] = theme::current().clock();
+        canvas.clear(Rgb565::BLACK)?;
+        // Blinking colon, like proper desk clocks have
+        let separator = if now.second & 1 == 0 { ':' } else { ' ' };
+        Text::with_text_style(
+            &format!("{:02}{separator}{:02}", now.hour, now.minute),
+            Point::new(size.width as i32 / 2, top) / scale as i32,
+            MonoTextStyle::new(&FONT_10X20, digits_color),
+            top_center,
+        )
+        .draw(&mut Scaled {
+            target: &mut *canvas,
+            scale,
+        })?;
+
+        let mut details = format!(
+            "{} {}-{:02}-{:02}",
+            now.weekday_name(),
+            now.year,
+            now.month,
+            now.day
+        );
+        if let Some(weather) = weather {
+            details.push('\n');
+            details.push_str(&weather);
+        }
+        Text::with_text_style(
+            &details,
+            Point::new(
+                size.width as i32 / 2,
+                top + (digits_size.height * scale) as i32 + 1,
+            ),
+            MonoTextStyle::new(&FONT_6X10, details_color),
+            top_center,
+        )
+        .draw(canvas)?;
+        ctx.leds = [0.0; 2];
+        Ok(true)
+    }
+}
@@ -0,0 +1,89 @@
+use embedded_graphics::pixelcolor::{
+    raw::{RawData, RawU16},
+    Rgb565,
+};
+
+use crate::intensity::Intensity;
+
+/// Fractions taken by `scale` and `lerp` are in 1/32ths, so that a 5 bit fraction times a
+/// 6 bit channel still fits in the gaps between channels of a spread out pixel
+pub const FRACTION_ONE: u32 = 32;
+
+/// Spreads channels apart so they have room to get multiplied: green goes to the top half, red
+/// and blue stay in the bottom one
+const SPREAD_MASK: u32 = 0b00000111_11100000_11111000_00011111;
+
+pub fn fraction(intensity: Intensity) -> u32 {
+    (intensity.get() * FRACTION_ONE as f32) as u32
+}
+
+fn raw(c: Rgb565) -> u16 {
+    RawU16::from(c).into_inner()
+}
+
+fn from_raw(raw: u16) -> Rgb565 {
+    RawU16::new(raw).into()
+}
+
+fn spread(c: Rgb565) -> u32 {
+    let raw = u32::from(raw(c));
+    (raw | raw << 16) & SPREAD_MASK
+}
+
+fn unspread(spread: u32) -> Rgb565 {
+    let spread = spread & SPREAD_MASK;
+    from_raw((spread | spread >> 16) as u16)
+}
+
+/// Red (0..32), green (0..64) and blue (0..32)
+pub fn split(c: Rgb565) -> [u8; 3] {
+    let raw = raw(c);
+    [
+        (raw >> 11) as u8,
+        ((raw >> 5) & 0x3f) as u8,
+        (raw & 0x1f) as u8,
+    ]
+}
+
+/// Inverse of `split`. Out of range channels are clamped.
+pub fn merge([r, g, b]: [u8; 3]) -> Rgb565 {
+    let (r, g, b) = (
+        u16::from(r.min(31)),
+        u16::from(g.min(63)),
+        u16::from(b.min(31)),
+    );
+    from_raw((r << 11) | (g << 5) | b)
+}
+
+pub fn saturating_add(a: Rgb565, b: Rgb565) -> Rgb565 {
+    let ([ar, ag, ab], [br, bg, bb]) = (split(a), split(b));
+    merge([ar + br, ag + bg, ab + bb])
+}
+
+pub fn saturating_sub(a: Rgb565, b: Rgb565) -> Rgb565 {
+    let ([ar, ag, ab], [br, bg, bb]) = (split(a), split(b));
+    merge([
+        ar.saturating_sub(br),
+        ag.saturating_sub(bg),
+        ab.saturating_sub(bb),
+    ])
+}
+
+/// All channels multiplied by `fraction`/32. Works on the packed value all at once.
+pub fn scale(c: Rgb565, fraction: u32) -> Rgb565 {
+    unspread((spread(c) * fraction.min(FRACTION_ONE)) >> 5)
+}
+
+/// Color between `dark` and `bright`, as far along as `c` is bright
+pub fn remap(c: Rgb565, dark: Rgb565, bright: Rgb565) -> Rgb565 {
+    let [r, g, b] = split(c);
+    // Green has twice the levels, and counts about as much as red and blue put together
+    let luma = u32::from(r) * 2 + u32::from(g) + u32::from(b) * 2;
+    lerp(dark, bright, luma * FRACTION_ONE / (31 * 2 + 63 + 31 * 2))
+}
+
+/// `a` at 0, `b` at 32. Works on the packed values all at once.
+pub fn lerp(a: Rgb565, b: Rgb565, fraction: u32) -> Rgb565 {
+    let fraction = fraction.min(FRACTION_ONE);
+    unspread((spread(a) * (FRACTION_ONE - fraction) + spread(b) * fraction) >> 5)
+}
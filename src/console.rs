@@ -0,0 +1,236 @@
+use std::{
+    io::{BufRead, Write},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Mutex,
+    },
+};
+
+use anyhow::{bail, Context, Result};
+
+use crate::{
+    badge, calibration,
+    frame_codec::{self, Decoder},
+    log_buffer, osc,
+    platform::{Input, InputEvent},
+    serial_frames, theme,
+};
+
+const HELP: &str = "\
+commands:
+  pause    pause/resume
+  next     skip to next scene
+  back     scrub timeline back
+  fwd      scrub timeline forward
+  faster   speed up
+  slower   slow down
+  brighter increase LED brightness
+  dimmer   decrease LED brightness
+  shake    shake the android
+  pet      pet the android
+  poke     poke the android
+  log      toggle log overlay on the LCD
+  mem      toggle heap and stack usage overlay on the LCD
+  stats    show/hide statistics
+  sleep    go to sleep until woken up
+  frame    save next frame to --dump-frame path
+  demo     toggle demo mode
+  badge    show/hide the name badge, 'badge name ...' and
+           'badge tagline ...' set what's on it
+  theme    print the current theme and all there are,
+           'theme <name>' switches to one
+  panel    print the panel calibration profile and all there are,
+           'panel <name>' switches to one and remembers it,
+           'panel test' shows/hides the test pattern
+  message  'message <text>' replaces the one under the timer,
+           'message' alone brings it back
+  dump     print buffered log lines
+  stream   toggle streaming frames to the serial port,
+           'stream on' and 'stream off' set it
+  help     print this message";
+
+pub fn parse_command(command: &str) -> Option<InputEvent> {
+    match command {
+        "pause" => Some(InputEvent::TogglePause),
+        "next" => Some(InputEvent::NextScene),
+        "back" => Some(InputEvent::Scrub(-1)),
+        "fwd" => Some(InputEvent::Scrub(1)),
+        "faster" => Some(InputEvent::Speed(1)),
+        "slower" => Some(InputEvent::Speed(-1)),
+        "brighter" => Some(InputEvent::Brightness(1)),
+        "dimmer" => Some(InputEvent::Brightness(-1)),
+        "shake" => Some(InputEvent::Shake(1.0)),
+        "pet" => Some(InputEvent::Pet),
+        "poke" => Some(InputEvent::Poke),
+        "log" => Some(InputEvent::ToggleLogOverlay),
+        "mem" => Some(InputEvent::ToggleDiagnostics),
+        "stats" => Some(InputEvent::ToggleStats),
+        "sleep" => Some(InputEvent::Sleep),
+        "frame" => Some(InputEvent::DumpFrame),
+        "demo" => Some(InputEvent::ToggleDemo),
+        "badge" => Some(InputEvent::ToggleBadge),
+        // Not in the help, on purpose
+        "lunch" => Some(InputEvent::Lunch),
+        _ => None,
+    }
+}
+
+// Where events of commands go, whichever thread they come from, e.g. HTTP
+static EVENTS: Mutex<Option<Sender<InputEvent>>> = Mutex::new(None);
+
+/// Runs `command` as if it was typed in, with what it prints going to `out`. Fails on unknown
+/// commands, and on ones meant for the escalation when no Console is running.
+pub fn execute(command: &str, out: &mut impl Write) -> Result<()> {
+    match command.trim() {
+        "" => {}
+        "help" => writeln!(out, "{HELP}")?,
+        "dump" => {
+            for (level, line) in log_buffer::lines() {
+                writeln!(out, "{level:5} {line}")?;
+            }
+        }
+        "stream" => serial_frames::set_streaming(!serial_frames::is_streaming())?,
+        "stream on" => serial_frames::set_streaming(true)?,
+        "stream off" => serial_frames::set_streaming(false)?,
+        command if command.starts_with("badge ") => {
+            match command["badge ".len()..].trim().split_once(' ') {
+                Some(("name", name)) => badge::set_name(name),
+                Some(("tagline", tagline)) => badge::set_tagline(tagline),
+                _ => bail!("usage: badge [name|tagline] <text>"),
+            }
+        }
+        "theme" => writeln!(
+            out,
+            "theme: {}, available: {}",
+            theme::name(),
+            theme::names().join(", ")
+        )?,
+        command if command.starts_with("theme ") => theme::set(command["theme ".len()..].trim())?,
+        "panel" => writeln!(
+            out,
+            "panel: {}, available: {}",
+            calibration::name(),
+            calibration::names().join(", ")
+        )?,
+        "panel test" => calibration::toggle_test_pattern(),
+        command if command.starts_with("panel ") => {
+            calibration::set(command["panel ".len()..].trim())?
+        }
+        "message" => osc::set_message(None),
+        command if command.starts_with("message ") => {
+            osc::set_message(Some(command["message ".len()..].trim()))
+        }
+        command => {
+            let event = parse_command(command)
+                .with_context(|| format!("unknown command: {command}, try 'help'"))?;
+            let events = EVENTS.lock().unwrap();
+            let sent = events
+                .as_ref()
+                .is_some_and(|events| events.send(event).is_ok());
+            if !sent {
+                bail!("nothing to send {command} to");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reads up to the end of a line, whichever of CR or LF the terminal ends it with. Stdin is
+/// read as is, no line ending conversion, so that pushed frames come through intact.
+fn read_line(input: &mut impl BufRead, line: &mut Vec<u8>) -> std::io::Result<()> {
+    loop {
+        let available = input.fill_buf()?;
+        let Some(end) = available.iter().position(|&b| b == b'\n' || b == b'\r') else {
+            let len = available.len();
+            line.extend_from_slice(available);
+            input.consume(len);
+            if len == 0 {
+                return Ok(());
+            }
+            continue;
+        };
+        line.extend_from_slice(&available[..end]);
+        input.consume(end + 1);
+        return Ok(());
+    }
+}
+
+/// Line-based commands read from stdin, which is the serial port on ESP32. Frames pushed with
+/// `frame_codec` packets can come in between lines.
+pub struct Console(Receiver<InputEvent>);
+
+impl Console {
+    pub fn spawn() -> Result<Self> {
+        let (sender, receiver) = mpsc::channel();
+        *EVENTS.lock().unwrap() = Some(sender);
+        std::thread::Builder::new()
+            .name("console".to_owned())
+            .stack_size(8192)
+            .spawn(move || {
+                let mut stdin = std::io::stdin().lock();
+                let mut decoder = Decoder::default();
+                let mut line = Vec::new();
+                loop {
+                    match stdin.fill_buf() {
+                        // End of input
+                        Ok([]) => return,
+                        Ok([first, ..]) if *first == frame_codec::MAGIC[0] => {
+                            if let Err(e) = serial_frames::receive(&mut stdin, &mut decoder) {
+                                log::warn!("pushed frame: {e:#}");
+                            }
+                            continue;
+                        }
+                        Ok(_) => {}
+                        Err(e) => {
+                            log::error!("failed to read from stdin: {e:?}");
+                            return;
+                        }
+                    }
+                    line.clear();
+                    if let Err(e) = read_line(&mut stdin, &mut line) {
+                        log::error!("failed to read from stdin: {e:?}");
+                        return;
+                    }
+                    let command = String::from_utf8_lossy(&line);
+                    if let Err(e) = execute(&command, &mut std::io::stdout()) {
+                        println!("{e:#}");
+                    }
+                }
+            })?;
+        Ok(Self(receiver))
+    }
+}
+
+impl Input for Console {
+    fn poll(&mut self) -> Result<Option<InputEvent>> {
+        Ok(self.0.try_recv().ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn executes_commands() {
+        let mut out = Vec::new();
+        execute("help\n", &mut out).unwrap();
+        assert!(String::from_utf8(out).unwrap().contains("'message' alone"));
+        let mut out = Vec::new();
+        assert!(execute("frobnicate", &mut out).is_err());
+        assert!(execute("badge title boss", &mut out).is_err());
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn reads_lines_ending_in_cr_or_lf() {
+        let mut input = &b"help\rtheme\r\nstream"[..];
+        let mut lines = Vec::new();
+        while !input.is_empty() {
+            let mut line = Vec::new();
+            read_line(&mut input, &mut line).unwrap();
+            lines.push(String::from_utf8(line).unwrap());
+        }
+        assert_eq!(lines, ["help", "theme", "", "stream"]);
+    }
+}
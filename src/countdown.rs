@@ -0,0 +1,201 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::Dimensions,
+    mono_font::{iso_8859_1::FONT_6X10, MonoTextStyle},
+    text::{Alignment, Text},
+    Drawable,
+};
+use serde::Deserialize;
+use toml::value::{Datetime, Offset};
+
+use crate::{
+    color565,
+    duration_fmt::{self, Style},
+    intensity::Intensity,
+    layout,
+    scene::{Canvas, Scene, SceneContext, GLITCH_LINE_PROBABILITY},
+    theme,
+    wall_clock::{self, days_from_civil},
+};
+
+const MAX_GLITCH: f32 = 48.0;
+const MAX_SHAKE: f32 = 3.0;
+/// LEDs blink for the last this many seconds
+const LED_BLINK_SECS: u64 = 10;
+
+/// Counts down to a deadline, getting more and more nervous. Configured with `[countdown]` in
+/// config.toml.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Countdown {
+    /// When it hits zero. UTC unless an offset is given.
+    deadline: Datetime,
+    /// Shown above the time left
+    #[serde(default = "Countdown::default_label")]
+    label: String,
+    /// Glitches start that long before the deadline
+    #[serde(default = "Countdown::default_glitch_from_secs")]
+    glitch_from_secs: u64,
+    /// Shown after T-0
+    #[serde(default = "Countdown::default_takeover_message")]
+    takeover_message: String,
+    /// How long the T-0 takeover lasts, before going back to the usual escalation
+    #[serde(default = "Countdown::default_takeover_secs")]
+    takeover_secs: u64,
+}
+
+impl Countdown {
+    fn default_label() -> String {
+        "Release in".to_owned()
+    }
+
+    fn default_glitch_from_secs() -> u64 {
+        60 * 60
+    }
+
+    fn default_takeover_message() -> String {
+        "IT'S HAPPENING".to_owned()
+    }
+
+    fn default_takeover_secs() -> u64 {
+        30
+    }
+
+    /// Fails for datetimes without a date
+    pub fn deadline(&self) -> Result<SystemTime> {
+        let date = self.deadline.date.context("deadline needs a date")?;
+        let days = days_from_civil(date.year.into(), date.month.into(), date.day.into());
+        let mut secs = days * 24 * 60 * 60;
+        if let Some(time) = self.deadline.time {
+            secs += i64::from(time.hour) * 60 * 60
+                + i64::from(time.minute) * 60
+                + i64::from(time.second);
+        }
+        if let Some(Offset::Custom { minutes }) = self.deadline.offset {
+            secs -= i64::from(minutes) * 60;
+        }
+        let secs = u64::try_from(secs).context("deadline before 1970")?;
+        Ok(UNIX_EPOCH + Duration::from_secs(secs))
+    }
+
+    /// Whether there's anything left to show. False if the clock isn't set yet.
+    pub fn is_pending(&self) -> bool {
+        let Ok(deadline) = self.deadline() else {
+            return false;
+        };
+        let Some(now) = wall_clock::now() else {
+            log::info!("clock not set, skipping the countdown");
+            return false;
+        };
+        now < deadline + Duration::from_secs(self.takeover_secs)
+    }
+
+    pub fn start(&self) -> Result<CountdownScene<'_>> {
+        Ok(CountdownScene {
+            countdown: self,
+            deadline: self.deadline()?,
+        })
+    }
+}
+
+pub struct CountdownScene<'a> {
+    countdown: &'a Countdown,
+    deadline: SystemTime,
+}
+
+impl Scene for CountdownScene<'_> {
+    fn name(&self) -> &'static str {
+        "countdown"
+    }
+
+    fn draw(&mut self, t: Duration, canvas: &mut Canvas, ctx: &mut SceneContext) -> Result<bool> {
+        let Some(now) = wall_clock::now() else {
+            return Ok(false);
+        };
+        let center = canvas.bounding_box().center();
+        let [text_color, alarm_color] = theme::current().countdown();
+
+        if let Ok(left) = self.deadline.duration_since(now) {
+            let glitch_from = self.countdown.glitch_from_secs as f32;
+            // 0 until glitch_from_secs before the deadline, 1 at T-0
+            let nervousness = if glitch_from > 0.0 {
+                (1.0 - left.as_secs_f32() / glitch_from).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            let nervousness = nervousness * nervousness;
+
+            // Half as bright as the alarm color at most, to keep the text readable
+            canvas.clear(color565::scale(
+                alarm_color,
+                (nervousness * color565::FRACTION_ONE as f32 / 2.0) as u32,
+            ))?;
+            let text = format!(
+                "{}\nT-{}",
+                self.countdown.label,
+                // Rounded up, so that T-00:00 only shows up at the deadline
+                duration_fmt::format(left + Duration::from_millis(999), Style::Compact)
+            );
+            let style = MonoTextStyle::new(&FONT_6X10, text_color);
+            Text::with_alignment(
+                &text,
+                crate::intensify(
+                    ctx.rng,
+                    layout::text_block(center, text.lines().count(), &style),
+                    (nervousness * MAX_SHAKE) as i32,
+                ),
+                style,
+                Alignment::Center,
+            )
+            .draw(canvas)?;
+            crate::glitch(
+                canvas,
+                ctx.rng,
+                (nervousness * MAX_GLITCH) as usize,
+                GLITCH_LINE_PROBABILITY,
+            );
+            ctx.leds = if left.as_secs() < LED_BLINK_SECS {
+                [if (t.as_millis() / 250) & 1 == 0 {
+                    1.0
+                } else {
+                    0.0
+                }; 2]
+            } else {
+                [nervousness; 2]
+            };
+            return Ok(true);
+        }
+
+        let since = now.duration_since(self.deadline).unwrap_or_default();
+        if since.as_secs() >= self.countdown.takeover_secs {
+            return Ok(false);
+        }
+        // Flashing, as obnoxious as it gets
+        let flash = (since.as_millis() / 250) & 1 == 0;
+        let (background, foreground) = if flash {
+            (alarm_color, text_color)
+        } else {
+            (text_color, alarm_color)
+        };
+        canvas.clear(background)?;
+        Text::with_alignment(
+            &format!("T-0\n{}", self.countdown.takeover_message),
+            crate::intensify(ctx.rng, center, MAX_SHAKE as i32),
+            MonoTextStyle::new(&FONT_6X10, foreground),
+            Alignment::Center,
+        )
+        .draw(canvas)?;
+        crate::glitch(
+            canvas,
+            ctx.rng,
+            MAX_GLITCH as usize,
+            GLITCH_LINE_PROBABILITY,
+        );
+        crate::add_noise(canvas, ctx.rng, Intensity::new(0.1));
+        ctx.leds = if flash { [1.0, 0.0] } else { [0.0, 1.0] };
+        Ok(true)
+    }
+}
@@ -0,0 +1,11 @@
+use std::time::Duration;
+
+/// How long the whole escalation takes in demo mode, regardless of tweaks
+pub const ESCALATION_DURATION: Duration = Duration::from_secs(30);
+/// Statistics are shown at the start of every escalation, briefly
+pub const STATS_DURATION: Duration = Duration::from_secs(3);
+
+/// Taken from `--demo`
+pub fn requested() -> bool {
+    std::env::args().skip(1).any(|arg| arg == "--demo")
+}
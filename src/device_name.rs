@@ -0,0 +1,11 @@
+// Read at build time, so that it works on the ESP32 too
+const NAME: Option<&str> = option_env!("EVIL_ANDROID_DEVICE_NAME");
+
+/// `evil-android-<id>`, unless configured otherwise. Also the mDNS host name on the ESP32, so
+/// it should only contain letters, digits and dashes.
+pub fn get() -> String {
+    match NAME {
+        Some(name) => name.to_owned(),
+        None => format!("evil-android-{}", crate::platform::device_id()),
+    }
+}
@@ -0,0 +1,107 @@
+use std::time::{Duration, Instant};
+
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::Point,
+    mono_font::{ascii::FONT_4X6, MonoFont, MonoTextStyleBuilder},
+    pixelcolor::Rgb565,
+    prelude::RgbColor,
+    text::{Baseline, Text},
+    Drawable,
+};
+
+use crate::platform::{FrameStats, MemoryStats, Platform};
+
+const OVERLAY_FONT: MonoFont = FONT_4X6;
+/// Reading memory stats isn't free, walking the task list on ESP32 in particular
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Memory usage and frame timing on top of the LCD, for tracking down leaks
+#[derive(Default)]
+pub struct Overlay {
+    shown: bool,
+    memory: Option<MemoryStats>,
+    updated: Option<Instant>,
+}
+
+impl Overlay {
+    pub fn is_shown(&self) -> bool {
+        self.shown
+    }
+
+    pub fn toggle(&mut self) {
+        self.shown = !self.shown;
+        self.updated = None;
+    }
+
+    /// Reads memory stats if it's time to. Does nothing while hidden.
+    pub fn update(&mut self, platform: &mut impl Platform) {
+        if !self.shown || self.updated.is_some_and(|t| t.elapsed() < REFRESH_INTERVAL) {
+            return;
+        }
+        self.memory = platform.memory_stats();
+        self.updated = Some(Instant::now());
+        if let Some(memory) = &self.memory {
+            log::debug!("{memory:?}");
+        }
+    }
+
+    pub fn draw<D: DrawTarget<Color = Rgb565>>(
+        &self,
+        target: &mut D,
+        stats: &FrameStats,
+    ) -> Result<(), D::Error> {
+        if !self.shown {
+            return Ok(());
+        }
+        let mut lines = vec![format!(
+            "frame {} ms, {} skipped",
+            stats.frame_time.as_millis(),
+            stats.skipped_frames
+        )];
+        match &self.memory {
+            Some(memory) => lines.extend(describe(memory)),
+            None => lines.push("no memory stats".to_owned()),
+        }
+
+        let style = MonoTextStyleBuilder::new()
+            .font(&OVERLAY_FONT)
+            .text_color(Rgb565::GREEN)
+            .background_color(Rgb565::BLACK)
+            .build();
+        let top_left = target.bounding_box().top_left;
+        for (idx, line) in lines.iter().enumerate() {
+            let y = idx as u32 * OVERLAY_FONT.character_size.height;
+            Text::with_baseline(
+                line,
+                top_left + Point::new(0, y as i32),
+                style,
+                Baseline::Top,
+            )
+            .draw(target)?;
+        }
+        Ok(())
+    }
+}
+
+fn describe(memory: &MemoryStats) -> Vec<String> {
+    let kib = |bytes: usize| format!("{}k", bytes / 1024);
+    let mut lines = Vec::new();
+    if let Some(free) = memory.heap_free {
+        let min = memory
+            .heap_free_min
+            .map(|min| format!(", min {}", kib(min)))
+            .unwrap_or_default();
+        lines.push(format!("heap {} free{min}", kib(free)));
+    }
+    if let Some(largest) = memory.largest_free_block {
+        lines.push(format!("largest block {}", kib(largest)));
+    }
+    if let Some(resident) = memory.resident {
+        lines.push(format!("resident {}", kib(resident)));
+    }
+    for (task, headroom) in &memory.stacks {
+        lines.push(format!("stack {task}: {headroom} B left"));
+    }
+    lines
+}
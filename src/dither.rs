@@ -0,0 +1,77 @@
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::{Dimensions, Point},
+    pixelcolor::{BinaryColor, Rgb565, Rgb888},
+    prelude::{PointsIter, RgbColor},
+    primitives::Rectangle,
+    Pixel,
+};
+
+// 4x4 Bayer matrix, values 0..16
+const BAYER_4X4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+fn luma(color: Rgb565) -> u8 {
+    let c = Rgb888::from(color);
+    ((c.r() as u32 * 77 + c.g() as u32 * 150 + c.b() as u32 * 29) >> 8) as u8
+}
+
+fn dither(point: Point, color: Rgb565) -> BinaryColor {
+    let row = point.y.rem_euclid(4) as usize;
+    let col = point.x.rem_euclid(4) as usize;
+    let threshold = BAYER_4X4[row][col] * 16 + 8;
+    if luma(color) > threshold {
+        BinaryColor::On
+    } else {
+        BinaryColor::Off
+    }
+}
+
+/// Presents a monochrome display as an Rgb565 one, using ordered dithering
+pub struct Dithered<D> {
+    pub inner: D,
+}
+
+impl<D> Dithered<D> {
+    pub fn new(inner: D) -> Self {
+        Self { inner }
+    }
+}
+
+impl<D: DrawTarget<Color = BinaryColor>> Dimensions for Dithered<D> {
+    fn bounding_box(&self) -> Rectangle {
+        self.inner.bounding_box()
+    }
+}
+
+impl<D: DrawTarget<Color = BinaryColor>> DrawTarget for Dithered<D> {
+    type Color = Rgb565;
+    type Error = D::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        self.inner.draw_iter(
+            pixels
+                .into_iter()
+                .map(|Pixel(point, color)| Pixel(point, dither(point, color))),
+        )
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        let colors = area
+            .points()
+            .zip(colors)
+            .map(|(point, color)| dither(point, color));
+        self.inner.fill_contiguous(area, colors)
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        // Solid colors still need dithering, unless they're pure black or white
+        let bb = self.bounding_box();
+        self.fill_contiguous(&bb, core::iter::repeat(color))
+    }
+}
@@ -0,0 +1,217 @@
+use alloc::string::String;
+use core::{fmt::Write, time::Duration};
+
+const MINUTE: u64 = 60;
+const HOUR: u64 = 60 * MINUTE;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+/// Average Gregorian year, 365.2425 days, so that months come out even
+const YEAR: u64 = 31_556_952;
+const MONTH: u64 = YEAR / 12;
+
+/// Anything longer is shown as "more than that", rather than a wall of digits
+pub const MAX_YEARS: u64 = 99_999_999;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Style {
+    /// `2y 3mo 1w 4d 5:06:07`, like a stopwatch that's been running for way too long
+    Compact,
+    /// `2 years 3 months 1 week 4 days 5 hours 6 minutes 7 seconds`, zeros left out
+    Verbose,
+}
+
+/// Whole units, largest first
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Parts {
+    years: u64,
+    months: u64,
+    weeks: u64,
+    days: u64,
+    hours: u64,
+    minutes: u64,
+    seconds: u64,
+}
+
+impl Parts {
+    fn new(d: Duration) -> Self {
+        let secs = d.as_secs();
+        let (years, secs) = (secs / YEAR, secs % YEAR);
+        let (months, secs) = (secs / MONTH, secs % MONTH);
+        let (weeks, secs) = (secs / WEEK, secs % WEEK);
+        let (days, secs) = (secs / DAY, secs % DAY);
+        let (hours, secs) = (secs / HOUR, secs % HOUR);
+        let (minutes, seconds) = (secs / MINUTE, secs % MINUTE);
+        Self {
+            years,
+            months,
+            weeks,
+            days,
+            hours,
+            minutes,
+            seconds,
+        }
+    }
+}
+
+/// Fractions of a second are dropped
+pub fn format(d: Duration, style: Style) -> String {
+    let parts = Parts::new(d);
+    let saturated = parts.years > MAX_YEARS;
+    // Writing to a String never fails
+    let mut s = String::new();
+    match (style, saturated) {
+        (Style::Compact, true) => {
+            let _ = write!(s, "{MAX_YEARS}y+");
+        }
+        (Style::Verbose, true) => {
+            let _ = write!(s, "more than {MAX_YEARS} years");
+        }
+        (Style::Compact, false) => {
+            for (value, suffix) in [
+                (parts.years, "y"),
+                (parts.months, "mo"),
+                (parts.weeks, "w"),
+                (parts.days, "d"),
+            ] {
+                if value > 0 {
+                    let _ = write!(s, "{value}{suffix} ");
+                }
+            }
+            if parts.hours > 0 {
+                let _ = write!(s, "{}:", parts.hours);
+            }
+            let _ = write!(s, "{:02}:{:02}", parts.minutes, parts.seconds);
+        }
+        (Style::Verbose, false) => {
+            for (value, unit) in [
+                (parts.years, "year"),
+                (parts.months, "month"),
+                (parts.weeks, "week"),
+                (parts.days, "day"),
+                (parts.hours, "hour"),
+                (parts.minutes, "minute"),
+                (parts.seconds, "second"),
+            ] {
+                if value > 0 {
+                    let separator = if s.is_empty() { "" } else { " " };
+                    let plural = if value == 1 { "" } else { "s" };
+                    let _ = write!(s, "{separator}{value} {unit}{plural}");
+                }
+            }
+            if s.is_empty() {
+                s.push_str("0 seconds");
+            }
+        }
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    /// Inverse of the compact style, for round trips
+    fn parse_compact(s: &str) -> u64 {
+        let mut total = 0;
+        for token in s.split(' ') {
+            let number = token.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+            let unit = match &token[number.len()..] {
+                "y" => YEAR,
+                "mo" => MONTH,
+                "w" => WEEK,
+                "d" => DAY,
+                // h:mm:ss or mm:ss
+                _ => {
+                    total += token
+                        .split(':')
+                        .fold(0, |acc, field| acc * 60 + field.parse::<u64>().unwrap());
+                    continue;
+                }
+            };
+            total += number.parse::<u64>().unwrap() * unit;
+        }
+        total
+    }
+
+    #[test]
+    fn compact_matches_the_old_stopwatch_format() {
+        assert_eq!(format(Duration::ZERO, Style::Compact), "00:00");
+        assert_eq!(format(Duration::from_millis(999), Style::Compact), "00:00");
+        assert_eq!(format(secs(59), Style::Compact), "00:59");
+        assert_eq!(format(secs(MINUTE), Style::Compact), "01:00");
+        assert_eq!(format(secs(HOUR - 1), Style::Compact), "59:59");
+        assert_eq!(format(secs(HOUR), Style::Compact), "1:00:00");
+        assert_eq!(format(secs(DAY - 1), Style::Compact), "23:59:59");
+        assert_eq!(format(secs(DAY), Style::Compact), "1d 00:00");
+        assert_eq!(format(secs(DAY + HOUR + 5), Style::Compact), "1d 1:00:05");
+    }
+
+    #[test]
+    fn compact_uses_every_unit() {
+        assert_eq!(format(secs(WEEK), Style::Compact), "1w 00:00");
+        assert_eq!(format(secs(MONTH), Style::Compact), "1mo 00:00");
+        assert_eq!(format(secs(YEAR), Style::Compact), "1y 00:00");
+        assert_eq!(
+            format(
+                secs(2 * YEAR + 3 * MONTH + WEEK + 4 * DAY + 5 * HOUR + 6 * MINUTE + 7),
+                Style::Compact
+            ),
+            "2y 3mo 1w 4d 5:06:07"
+        );
+        assert_eq!(
+            format(secs(2 * YEAR + 3 * DAY), Style::Compact),
+            "2y 3d 00:00"
+        );
+    }
+
+    #[test]
+    fn verbose_leaves_out_zeros_and_pluralizes() {
+        assert_eq!(format(Duration::ZERO, Style::Verbose), "0 seconds");
+        assert_eq!(format(secs(1), Style::Verbose), "1 second");
+        assert_eq!(format(secs(2), Style::Verbose), "2 seconds");
+        assert_eq!(format(secs(HOUR + 1), Style::Verbose), "1 hour 1 second");
+        assert_eq!(
+            format(secs(2 * YEAR + 3 * DAY), Style::Verbose),
+            "2 years 3 days"
+        );
+        assert_eq!(
+            format(
+                secs(YEAR + MONTH + WEEK + DAY + HOUR + MINUTE + 1),
+                Style::Verbose
+            ),
+            "1 year 1 month 1 week 1 day 1 hour 1 minute 1 second"
+        );
+    }
+
+    #[test]
+    fn absurd_durations_saturate() {
+        let limit = (MAX_YEARS + 1) * YEAR;
+        assert!(!format(secs(limit - 1), Style::Compact).ends_with('+'));
+        for d in [secs(limit), secs(u64::MAX), Duration::MAX] {
+            assert_eq!(format(d, Style::Compact), "99999999y+");
+            assert_eq!(format(d, Style::Verbose), "more than 99999999 years");
+        }
+    }
+
+    #[test]
+    fn compact_round_trips() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let limit = (MAX_YEARS + 1) * YEAR;
+        // Boundaries of every unit, and random ones of all magnitudes
+        let mut cases = vec![0, limit - 1];
+        for unit in [MINUTE, HOUR, DAY, WEEK, MONTH, YEAR] {
+            cases.extend([unit - 1, unit, unit + 1]);
+        }
+        cases.extend((0..1000).map(|_| rng.gen_range(0..limit) >> rng.gen_range(0..50)));
+        for secs in cases {
+            let formatted = format(Duration::from_secs(secs), Style::Compact);
+            assert_eq!(parse_compact(&formatted), secs, "{formatted}");
+        }
+    }
+}
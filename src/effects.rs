@@ -0,0 +1,414 @@
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use anyhow::{bail, Result};
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::{Dimensions, OriginDimensions, Point},
+    image::GetPixel,
+    pixelcolor::{BinaryColor, PixelColor, Rgb565},
+    Drawable, Pixel,
+};
+use embedded_graphics_framebuf::{backends::FrameBufferBackend, FrameBuf};
+use itertools::Itertools;
+use rand::Rng;
+
+use crate::{
+    intensity::Intensity,
+    noise::{Flavor, NoiseEffect},
+    region::Region,
+};
+
+pub struct MaskedImage<ColorImage, MaskImage>
+where
+    ColorImage: OriginDimensions + GetPixel<Color = Rgb565>,
+    MaskImage: OriginDimensions + GetPixel<Color = BinaryColor>,
+{
+    color_image: ColorImage,
+    mask_image: MaskImage,
+    pos: Point,
+}
+
+impl<ColorImage, MaskImage> MaskedImage<ColorImage, MaskImage>
+where
+    ColorImage: OriginDimensions + GetPixel<Color = Rgb565>,
+    MaskImage: OriginDimensions + GetPixel<Color = BinaryColor>,
+{
+    pub fn new(color_image: ColorImage, mask_image: MaskImage, pos: Point) -> Result<Self> {
+        if color_image.bounding_box() != mask_image.bounding_box() {
+            bail!(
+                "inconsistent dimensions of color vs mask\ncolor: {cbb:?}\n mask: {mbb:?}",
+                cbb = color_image.bounding_box(),
+                mbb = mask_image.bounding_box()
+            );
+        }
+        Ok(Self {
+            color_image,
+            mask_image,
+            pos,
+        })
+    }
+}
+
+impl<ColorImage, MaskImage> Drawable for MaskedImage<ColorImage, MaskImage>
+where
+    ColorImage: OriginDimensions + GetPixel<Color = Rgb565>,
+    MaskImage: OriginDimensions + GetPixel<Color = BinaryColor>,
+{
+    type Color = Rgb565;
+    type Output = ();
+
+    fn draw<D>(&self, target: &mut D) -> core::result::Result<Self::Output, D::Error>
+    where
+        D: DrawTarget<Color = Self::Color>,
+    {
+        let bb = self.color_image.bounding_box();
+        let x_range = bb.top_left.x..bb.bottom_right().unwrap().x;
+        let y_range = bb.top_left.y..=bb.bottom_right().unwrap().y;
+        let points = y_range
+            .cartesian_product(x_range)
+            .map(|(y, x)| Point::new(x, y));
+        let pixels = points.filter_map(|p| {
+            if self.mask_image.pixel(p).unwrap().is_on() {
+                Some(Pixel(p + self.pos, self.color_image.pixel(p).unwrap()))
+            } else {
+                None
+            }
+        });
+        target.draw_iter(pixels)
+    }
+}
+
+/// `point` moved by up to `amplitude` pixels each way, left alone if `amplitude` isn't positive
+pub fn intensify(rng: &mut impl Rng, point: Point, amplitude: i32) -> Point {
+    if amplitude <= 0 {
+        point
+    } else {
+        Point::new(
+            point.x + rng.gen_range(-amplitude..amplitude),
+            point.y + rng.gen_range(-amplitude..amplitude),
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct RowOffset {
+    offset: usize,
+    row_width: usize,
+}
+
+impl RowOffset {
+    fn new(offset: usize, row_width: usize) -> Self {
+        assert!(row_width > 0);
+        let offset = offset.min(row_width);
+        Self { offset, row_width }
+    }
+
+    fn range_to(self, other: usize) -> RowRange {
+        RowRange {
+            start: self.offset.min(other).min(self.row_width),
+            end: self.offset.max(other).min(self.row_width),
+            row_width: self.row_width,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct RowRange {
+    start: usize,
+    end: usize,
+    row_width: usize,
+}
+
+impl RowRange {
+    /// Clipped to the row, so it may end up shorter or even empty
+    fn offset(self, rhs: isize) -> RowRange {
+        let start = ((self.start as isize).saturating_add(rhs).max(0) as usize).min(self.row_width);
+        let end = ((self.end as isize).saturating_add(rhs).max(0) as usize).min(self.row_width);
+        Self { start, end, ..self }
+    }
+
+    /// Columns these ones end up at after shifting by `rhs`, with pixels pushed off one edge
+    /// coming back on the other
+    fn wrapping_offset(self, rhs: isize) -> impl Iterator<Item = usize> {
+        let row_width = self.row_width as isize;
+        let rhs = rhs.rem_euclid(row_width);
+        self.to_range()
+            .map(move |column| (column as isize + rhs).rem_euclid(row_width) as usize)
+    }
+
+    fn to_range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+}
+
+pub fn add_noise<B: FrameBufferBackend<Color = Rgb565>>(
+    fb: &mut FrameBuf<Rgb565, B>,
+    rng: &mut impl Rng,
+    intensity: Intensity,
+) {
+    NoiseEffect::new(Flavor::Color, intensity).apply(fb, rng, &Region::Full)
+}
+
+/// Shifts random parts of random lines left or right. Whatever gets shifted past the edge is
+/// lost.
+pub fn glitch<C: PixelColor, B: FrameBufferBackend<Color = C>>(
+    fb: &mut FrameBuf<C, B>,
+    rng: &mut impl Rng,
+    max_offset: usize,
+    line_probability: f32,
+) {
+    glitch_rows(fb, rng, max_offset, line_probability, false, &Region::Full)
+}
+
+/// Like `glitch`, but only within `region`, as if the parts of rows in it were all there is.
+/// With `wrap`, pixels shifted past one edge come back on the other.
+pub fn glitch_rows<C: PixelColor, B: FrameBufferBackend<Color = C>>(
+    fb: &mut FrameBuf<C, B>,
+    rng: &mut impl Rng,
+    max_offset: usize,
+    line_probability: f32,
+    wrap: bool,
+    region: &Region,
+) {
+    if max_offset == 0 {
+        return;
+    }
+
+    let fb_width = fb.width();
+    region.for_each_span(fb_width, fb.height(), |line, columns| {
+        let should_glitch = rng.gen::<f32>() < line_probability;
+        if should_glitch {
+            let row_width = columns.len();
+            let mut rand_idx = || rng.next_u32() as usize % row_width;
+            let offset = (rand_idx() % max_offset) as isize - (max_offset / 2) as isize;
+            let src = RowOffset::new(rand_idx(), row_width).range_to(rand_idx());
+            let row_index = line * fb_width + columns.start;
+
+            if wrap {
+                // Source and destination may overlap from both ends
+                let pixels: Vec<C> = src
+                    .to_range()
+                    .map(|column| fb.data.get(row_index + column))
+                    .collect();
+                for (dst, pixel) in src.wrapping_offset(offset).zip(pixels) {
+                    fb.data.set(row_index + dst, pixel);
+                }
+                return;
+            }
+
+            // Only the part that stays within the row, so that each pixel moves by exactly
+            // `offset`, even near the edges
+            let dst = src.offset(offset);
+            let src = dst.offset(-offset);
+            if offset < 0 {
+                for (src, dst) in src.to_range().zip(dst.to_range()) {
+                    fb.data.set(row_index + dst, fb.data.get(row_index + src));
+                }
+            } else {
+                for (src, dst) in src.to_range().zip(dst.to_range()).rev() {
+                    fb.data.set(row_index + dst, fb.data.get(row_index + src));
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_graphics::geometry::Size;
+    use embedded_graphics::pixelcolor::{raw::RawU16, RgbColor};
+    use proptest::prelude::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::framebuffer::{ArrayFrameBufferBackend, SliceFrameBufferBackend};
+
+    const CASES: usize = 1000;
+
+    /// Row widths and offsets, random ones mixed with the nastiest there are
+    fn extreme_offsets(rng: &mut StdRng, row_width: usize) -> Vec<isize> {
+        let width = row_width as isize;
+        vec![
+            0,
+            1,
+            -1,
+            width - 1,
+            width,
+            width + 1,
+            -width,
+            -width - 1,
+            width * 10,
+            -width * 10,
+            isize::MAX,
+            isize::MIN,
+            rng.gen_range(-4 * width..=4 * width),
+        ]
+    }
+
+    fn random_range(rng: &mut StdRng, row_width: usize) -> RowRange {
+        // Past the end too, RowOffset is supposed to clamp
+        RowOffset::new(rng.gen_range(0..=row_width * 2), row_width)
+            .range_to(rng.gen_range(0..=row_width * 2))
+    }
+
+    /// Mostly realistic ones. Anything past isize::MAX couldn't be offset by an isize anyway.
+    fn row_width() -> impl Strategy<Value = usize> {
+        prop_oneof![1..=4096usize, 1..=isize::MAX as usize]
+    }
+
+    /// Any range RowOffset can produce
+    fn row_range() -> impl Strategy<Value = RowRange> {
+        (row_width(), any::<usize>(), any::<usize>())
+            .prop_map(|(row_width, from, to)| RowOffset::new(from, row_width).range_to(to))
+    }
+
+    proptest! {
+        #[test]
+        fn row_offset_is_clamped_to_row(
+            row_width in row_width(),
+            from in any::<usize>(),
+            to in any::<usize>(),
+        ) {
+            let range = RowOffset::new(from, row_width).range_to(to);
+            prop_assert!(range.start <= range.end && range.end <= row_width, "{range:?}");
+            // Nothing gets lost within the row
+            prop_assert_eq!(range.start, from.min(to).min(row_width));
+            prop_assert_eq!(range.end, from.max(to).min(row_width));
+        }
+
+        #[test]
+        fn row_range_offset_is_clamped_to_row(range in row_range(), offset in any::<isize>()) {
+            let shifted = range.offset(offset);
+            prop_assert!(
+                shifted.start <= shifted.end && shifted.end <= range.row_width,
+                "{range:?} offset by {offset}: {shifted:?}"
+            );
+            prop_assert!(shifted.to_range().len() <= range.to_range().len());
+        }
+
+        #[test]
+        fn row_range_offset_within_row_keeps_length(
+            (range, offset) in row_range().prop_flat_map(|range| {
+                let room = -(range.start as isize)..=(range.row_width - range.end) as isize;
+                (Just(range), room)
+            }),
+        ) {
+            let shifted = range.offset(offset);
+            prop_assert_eq!(shifted.to_range().len(), range.to_range().len());
+            prop_assert_eq!(shifted.start as isize, range.start as isize + offset);
+        }
+    }
+
+    #[test]
+    fn intensify_leaves_point_alone_without_amplitude() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let point = Point::new(3, 4);
+        assert_eq!(intensify(&mut rng, point, 0), point);
+        assert_eq!(intensify(&mut rng, point, -5), point);
+        let moved = intensify(&mut rng, point, 2) - point;
+        assert!(moved.x.abs() <= 2 && moved.y.abs() <= 2, "{moved:?}");
+    }
+
+    #[test]
+    fn clipped_shift_moves_every_pixel_by_offset() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..CASES {
+            let row_width = rng.gen_range(1..=512);
+            let range = random_range(&mut rng, row_width);
+            // Glitch never produces isize::MIN, negating it would overflow
+            for offset in extreme_offsets(&mut rng, row_width)
+                .into_iter()
+                .filter(|&o| o != isize::MIN)
+            {
+                let dst = range.offset(offset);
+                let src = dst.offset(-offset);
+                assert_eq!(src.to_range().len(), dst.to_range().len());
+                assert!(range.start <= src.start && src.end <= range.end || src.start == src.end);
+                for (src, dst) in src.to_range().zip(dst.to_range()) {
+                    assert_eq!(dst as isize - src as isize, offset);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn wrapping_offset_is_a_permutation_within_row() {
+        let mut rng = StdRng::seed_from_u64(2);
+        for _ in 0..CASES {
+            let row_width = rng.gen_range(1..=512);
+            let range = random_range(&mut rng, row_width);
+            for offset in extreme_offsets(&mut rng, row_width) {
+                let columns: Vec<usize> = range.wrapping_offset(offset).collect();
+                assert_eq!(columns.len(), range.to_range().len());
+                assert!(columns.iter().all(|&c| c < row_width), "{columns:?}");
+                let mut unique = columns.clone();
+                unique.sort_unstable();
+                unique.dedup();
+                assert_eq!(unique.len(), columns.len(), "{range:?} offset by {offset}");
+            }
+        }
+    }
+
+    #[test]
+    fn glitch_survives_extreme_max_offsets() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for (width, height) in [(1, 1), (2, 3), (7, 5), (160, 80)] {
+            for max_offset in [1, width - 1, width, width + 1, width * 10, usize::MAX] {
+                for wrap in [false, true] {
+                    let mut backend = SliceFrameBufferBackend::new(
+                        Size::new(width as u32, height),
+                        Rgb565::BLACK,
+                    );
+                    // All different, to tell where each one came from
+                    for (i, pixel) in backend.pixels.iter_mut().enumerate() {
+                        *pixel = RawU16::new(i as u16).into();
+                    }
+                    let before = backend.pixels.to_vec();
+                    let mut fb = FrameBuf::new(&mut backend, width, height as usize);
+                    // Every line, to hit as many ranges as possible
+                    glitch_rows(&mut fb, &mut rng, max_offset, 1.0, wrap, &Region::Full);
+                    // Pixels only ever move within their row
+                    for (before, after) in before.chunks(width).zip(backend.pixels.chunks(width)) {
+                        assert!(after.iter().all(|pixel| before.contains(pixel)));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn array_backend_glitches_like_slice_backend() {
+        const WIDTH: usize = 16;
+        const HEIGHT: usize = 8;
+        let mut array = ArrayFrameBufferBackend::<Rgb565, WIDTH, HEIGHT>::new(Rgb565::BLACK);
+        let mut vec =
+            SliceFrameBufferBackend::new(Size::new(WIDTH as u32, HEIGHT as u32), Rgb565::BLACK);
+        for (i, pixel) in vec.pixels.iter_mut().enumerate() {
+            *pixel = RawU16::new(i as u16).into();
+            array.pixels[i / WIDTH][i % WIDTH] = *pixel;
+        }
+        assert_eq!(array.as_slice(), &vec.pixels[..]);
+        for wrap in [false, true] {
+            let mut fb = FrameBuf::new(&mut array, WIDTH, HEIGHT);
+            glitch_rows(
+                &mut fb,
+                &mut StdRng::seed_from_u64(4),
+                WIDTH,
+                1.0,
+                wrap,
+                &Region::Full,
+            );
+            let mut fb = FrameBuf::new(&mut vec, WIDTH, HEIGHT);
+            glitch_rows(
+                &mut fb,
+                &mut StdRng::seed_from_u64(4),
+                WIDTH,
+                1.0,
+                wrap,
+                &Region::Full,
+            );
+            assert_eq!(array.as_slice(), &vec.pixels[..]);
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use crate::tweaks::Tweaks;
+
+/// Exaggeration values above this are just shown as a bunch of 9s
+pub const MAX: f32 = 1e15;
+
+/// Seconds added to the real elapsed time at each frame of the escalation. Computed once per
+/// tweak change in single precision, the ESP32 has no double precision FPU and this used to
+/// take two `f64::powf` per frame.
+pub struct Table {
+    /// One per frame, up to the first one that hits MAX
+    seconds: Vec<f32>,
+}
+
+impl Table {
+    pub fn new(tweaks: &Tweaks, total_frames: usize) -> Self {
+        let unexaggerated_frames = tweaks.unexaggerated_shades * tweaks.frames_per_shade;
+        // base^x = 2^(x * log2(base)). Taking the logarithm in double precision keeps the
+        // error of huge exponents down, and it's only done once.
+        let log2_base = tweaks.exaggeration_base.log2() as f32;
+        let factor = tweaks.exaggeration_factor as f32;
+        let seconds = (0..total_frames)
+            .map(|frame| match frame.checked_sub(unexaggerated_frames) {
+                None => 0.0,
+                Some(v) => ((v as f32).powf(factor) * log2_base).exp2(),
+            })
+            .take_while(|&seconds| seconds < MAX)
+            .collect();
+        Self { seconds }
+    }
+
+    /// None once it's off the charts
+    pub fn at(&self, frame: usize) -> Option<f32> {
+        self.seconds.get(frame).copied()
+    }
+
+    /// First frame at which exaggeration hits MAX, or `total_frames` if never
+    pub fn glitch_start_frame(&self) -> usize {
+        self.seconds.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What the table replaces
+    fn reference(tweaks: &Tweaks, frame: usize) -> f64 {
+        let unexaggerated_frames = tweaks.unexaggerated_shades * tweaks.frames_per_shade;
+        if frame < unexaggerated_frames {
+            0f64
+        } else {
+            let v = (frame - unexaggerated_frames) as f64;
+            tweaks
+                .exaggeration_base
+                .powf(v.powf(tweaks.exaggeration_factor))
+        }
+    }
+
+    #[test]
+    fn table_matches_double_precision_reference() {
+        for (base, factor) in [
+            (1.01, 1.4),
+            (1.0, 1.0),
+            (1.1, 2.0),
+            (1.05, 1.2),
+            (1.001, 1.9),
+        ] {
+            let tweaks = Tweaks {
+                exaggeration_base: base,
+                exaggeration_factor: factor,
+                ..Default::default()
+            };
+            let total_frames = tweaks.frames_per_shade * 32;
+            let table = Table::new(&tweaks, total_frames);
+            let expected_start = (0..total_frames)
+                .find(|&frame| reference(&tweaks, frame) >= f64::from(MAX))
+                .unwrap_or(total_frames);
+            // Rounding may move the cutoff by a frame, when it's right at the edge
+            assert!(
+                table.glitch_start_frame().abs_diff(expected_start) <= 1,
+                "base {base}, factor {factor}: {} vs {expected_start}",
+                table.glitch_start_frame()
+            );
+            for frame in 0..table.glitch_start_frame().min(expected_start) {
+                let expected = reference(&tweaks, frame);
+                let actual = f64::from(table.at(frame).unwrap());
+                assert!(
+                    (actual - expected).abs() <= expected * 1e-4,
+                    "base {base}, factor {factor}, frame {frame}: {actual} vs {expected}"
+                );
+            }
+            assert_eq!(table.at(table.glitch_start_frame()), None);
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::Point,
+    pixelcolor::Rgb565,
+    prelude::{Primitive, RgbColor},
+    primitives::{Circle, PrimitiveStyle},
+    Drawable,
+};
+use rand::Rng;
+
+use crate::platform::Brightness;
+
+// Eyes wander at most this far from their place when glitching
+const MAX_JITTER: i32 = 4;
+
+/// Draws a pair of eyes on a secondary display, left then right lit the same as the eye LEDs
+pub fn draw<D: DrawTarget<Color = Rgb565>>(
+    target: &mut D,
+    brightness: [Brightness; 2],
+    glitchiness: usize,
+    rng: &mut impl Rng,
+) -> Result<(), D::Error> {
+    let bb = target.bounding_box();
+    let diameter = bb.size.height * 3 / 4;
+    let jitter = (glitchiness as i32 / 8).min(MAX_JITTER);
+
+    target.clear(Rgb565::BLACK)?;
+    for (x, brightness) in [bb.size.width / 4, bb.size.width * 3 / 4]
+        .into_iter()
+        .zip(brightness)
+    {
+        let red = (f32::from(brightness) * Rgb565::MAX_R as f32) as u8;
+        let style = PrimitiveStyle::with_fill(Rgb565::new(red, 0, 0));
+        let mut center = bb.top_left + Point::new(x as i32, bb.size.height as i32 / 2);
+        if jitter > 0 {
+            center += Point::new(
+                rng.gen_range(-jitter..=jitter),
+                rng.gen_range(-jitter..=jitter),
+            );
+        }
+        Circle::with_center(center, diameter)
+            .into_styled(style)
+            .draw(target)?;
+    }
+    Ok(())
+}
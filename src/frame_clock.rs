@@ -0,0 +1,38 @@
+use std::{
+    cell::Cell,
+    time::{Duration, Instant},
+};
+
+thread_local! {
+    /// When it started and how far it got, while time is virtual. Per thread, so that tests
+    /// running side by side each get their own.
+    static VIRTUAL: Cell<Option<(Instant, Duration)>> = const { Cell::new(None) };
+}
+
+/// Time that animations and transitions follow. Real time, unless something rendering frames
+/// offscreen took control of it with `make_virtual`.
+pub fn now() -> Instant {
+    match VIRTUAL.get() {
+        Some((start, elapsed)) => start + elapsed,
+        None => Instant::now(),
+    }
+}
+
+/// Time since `earlier`, as `now` sees it
+pub fn elapsed(earlier: Instant) -> Duration {
+    now().saturating_duration_since(earlier)
+}
+
+/// Stops the clock of the calling thread. From then on it only moves with `advance_to`.
+pub fn make_virtual() {
+    if VIRTUAL.get().is_none() {
+        VIRTUAL.set(Some((Instant::now(), Duration::ZERO)));
+    }
+}
+
+/// Moves virtual time to `elapsed` after `make_virtual` was called. Does nothing to real time.
+pub fn advance_to(elapsed: Duration) {
+    if let Some((start, _)) = VIRTUAL.get() {
+        VIRTUAL.set(Some((start, elapsed)));
+    }
+}
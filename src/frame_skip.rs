@@ -0,0 +1,97 @@
+use std::time::Duration;
+
+/// Caps how far behind rendering can fall, so that a single stall (loading assets, a slow
+/// sensor read) doesn't turn into a long streak of skipped frames
+const MAX_FRAMES_BEHIND: u32 = 4;
+/// Even when hopelessly behind, some frames should make it to the screen
+const MAX_SKIPPED_IN_A_ROW: u32 = 2;
+
+/// Decides which frames get rendered but not shown. Animations that advance by frame rather
+/// than by time would slow down along with the frame rate otherwise. Skipped frames don't wait
+/// for the LCD, so the ones after them catch up.
+#[derive(Default)]
+pub struct FrameSkipper {
+    /// Time over budget, summed over frames
+    behind: Duration,
+    skipped_in_a_row: u32,
+    /// Since startup
+    skipped: u32,
+}
+
+impl FrameSkipper {
+    /// Takes the time the previous frame took, without any pause after it, returns true if this
+    /// one shouldn't be shown. A zero budget never skips.
+    pub fn skip(&mut self, frame_time: Duration, budget: Duration) -> bool {
+        if budget.is_zero() {
+            self.behind = Duration::ZERO;
+            return false;
+        }
+        self.behind = (self.behind + frame_time)
+            .saturating_sub(budget)
+            .min(budget * MAX_FRAMES_BEHIND);
+        if self.behind >= budget && self.skipped_in_a_row < MAX_SKIPPED_IN_A_ROW {
+            self.skipped_in_a_row += 1;
+            self.skipped += 1;
+            true
+        } else {
+            self.skipped_in_a_row = 0;
+            false
+        }
+    }
+
+    pub fn skipped(&self) -> u32 {
+        self.skipped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUDGET: Duration = Duration::from_millis(33);
+
+    #[test]
+    fn frames_within_budget_are_all_shown() {
+        let mut skipper = FrameSkipper::default();
+        for ms in [10, 33, 20, 33, 5] {
+            assert!(!skipper.skip(Duration::from_millis(ms), BUDGET));
+        }
+        assert_eq!(skipper.skipped(), 0);
+    }
+
+    #[test]
+    fn slow_frames_get_skipped_until_caught_up() {
+        let mut skipper = FrameSkipper::default();
+        let mut skip = false;
+        let mut skipped = 0;
+        for _ in 0..150 {
+            // 7 ms over budget when shown, 8 ms under when not
+            let ms = if skip { 25 } else { 40 };
+            skip = skipper.skip(Duration::from_millis(ms), BUDGET);
+            skipped += usize::from(skip);
+        }
+        // Roughly every other frame, anything more would leave the animation behind
+        assert!((60..=80).contains(&skipped), "{skipped}");
+        assert_eq!(skipper.skipped() as usize, skipped);
+    }
+
+    #[test]
+    fn stalls_are_caught_up_within_a_few_frames() {
+        let mut skipper = FrameSkipper::default();
+        let shown: Vec<bool> = [5000]
+            .into_iter()
+            .chain([20; 12])
+            .map(|ms| !skipper.skip(Duration::from_millis(ms), BUDGET))
+            .collect();
+        assert!(!shown[0]);
+        // Never more than two in a row
+        assert!(shown.windows(3).all(|w| w.contains(&true)), "{shown:?}");
+        assert!(shown[9..].iter().all(|&shown| shown), "{shown:?}");
+    }
+
+    #[test]
+    fn zero_budget_never_skips() {
+        let mut skipper = FrameSkipper::default();
+        assert!(!skipper.skip(Duration::from_secs(1), Duration::ZERO));
+    }
+}
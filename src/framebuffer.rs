@@ -0,0 +1,190 @@
+use alloc::{vec, vec::Vec};
+use core::{
+    cell::UnsafeCell,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use embedded_graphics::{geometry::Size, pixelcolor::PixelColor};
+use embedded_graphics_framebuf::backends::FrameBufferBackend;
+
+/// Where the pixels of a `SliceFrameBufferBackend` live
+pub enum Pixels<Color: 'static> {
+    Heap(Vec<Color>),
+    /// Taken from a `StaticFrameBufferBackend`, never freed
+    Static(&'static mut [Color]),
+}
+
+impl<Color> Deref for Pixels<Color> {
+    type Target = [Color];
+
+    fn deref(&self) -> &[Color] {
+        match self {
+            Pixels::Heap(pixels) => pixels,
+            Pixels::Static(pixels) => pixels,
+        }
+    }
+}
+
+impl<Color> DerefMut for Pixels<Color> {
+    fn deref_mut(&mut self) -> &mut [Color] {
+        match self {
+            Pixels::Heap(pixels) => pixels,
+            Pixels::Static(pixels) => pixels,
+        }
+    }
+}
+
+/// Frame buffer of any size, on the heap or in a static
+pub struct SliceFrameBufferBackend<Color: PixelColor + 'static> {
+    pub pixels: Pixels<Color>,
+    pub size: Size,
+}
+
+impl<Color: PixelColor> SliceFrameBufferBackend<Color> {
+    /// Allocated on the heap
+    pub fn new(size: Size, fill_color: Color) -> Self {
+        let pixels = vec![fill_color; pixel_count(size)];
+        Self {
+            pixels: Pixels::Heap(pixels),
+            size,
+        }
+    }
+
+    /// Uses `pixels` instead of allocating, None if there's not enough of them. Contents are
+    /// left as they are.
+    pub fn from_static(pixels: &'static mut [Color], size: Size) -> Option<Self> {
+        let pixels = pixels.get_mut(..pixel_count(size))?;
+        Some(Self {
+            pixels: Pixels::Static(pixels),
+            size,
+        })
+    }
+}
+
+fn pixel_count(size: Size) -> usize {
+    usize::try_from(size.width).unwrap() * usize::try_from(size.height).unwrap()
+}
+
+impl<Color: PixelColor> FrameBufferBackend for &mut SliceFrameBufferBackend<Color> {
+    type Color = Color;
+
+    fn set(&mut self, index: usize, color: Self::Color) {
+        self.pixels[index] = color;
+    }
+
+    fn get(&self, index: usize) -> Self::Color {
+        self.pixels[index]
+    }
+
+    fn nr_elements(&self) -> usize {
+        pixel_count(self.size)
+    }
+}
+
+/// Frame buffer of a size known at compile time, that needs no heap. Can be a `static`.
+pub struct ArrayFrameBufferBackend<Color: PixelColor, const WIDTH: usize, const HEIGHT: usize> {
+    pub pixels: [[Color; WIDTH]; HEIGHT],
+}
+
+impl<Color: PixelColor, const WIDTH: usize, const HEIGHT: usize>
+    ArrayFrameBufferBackend<Color, WIDTH, HEIGHT>
+{
+    pub const fn new(fill_color: Color) -> Self {
+        Self {
+            pixels: [[fill_color; WIDTH]; HEIGHT],
+        }
+    }
+
+    pub const fn size(&self) -> Size {
+        Size::new(WIDTH as u32, HEIGHT as u32)
+    }
+
+    /// All rows one after another, like `SliceFrameBufferBackend::pixels`
+    pub fn as_slice(&self) -> &[Color] {
+        // SAFETY: arrays have no padding, so rows of an array of arrays are contiguous
+        unsafe { core::slice::from_raw_parts(self.pixels.as_ptr().cast(), WIDTH * HEIGHT) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [Color] {
+        // SAFETY: see as_slice
+        unsafe { core::slice::from_raw_parts_mut(self.pixels.as_mut_ptr().cast(), WIDTH * HEIGHT) }
+    }
+}
+
+impl<Color: PixelColor, const WIDTH: usize, const HEIGHT: usize> FrameBufferBackend
+    for &mut ArrayFrameBufferBackend<Color, WIDTH, HEIGHT>
+{
+    type Color = Color;
+
+    fn set(&mut self, index: usize, color: Self::Color) {
+        self.pixels[index / WIDTH][index % WIDTH] = color;
+    }
+
+    fn get(&self, index: usize) -> Self::Color {
+        self.pixels[index / WIDTH][index % WIDTH]
+    }
+
+    fn nr_elements(&self) -> usize {
+        WIDTH * HEIGHT
+    }
+}
+
+/// Frame buffer in a `static`, so that it's never allocated on the heap and can't fragment it
+/// on devices that run for weeks. Can be handed out once.
+pub struct StaticFrameBufferBackend<Color: PixelColor, const WIDTH: usize, const HEIGHT: usize> {
+    buffer: UnsafeCell<ArrayFrameBufferBackend<Color, WIDTH, HEIGHT>>,
+    taken: AtomicBool,
+}
+
+// SAFETY: the buffer is only accessible through `take`, which hands it out once
+unsafe impl<Color: PixelColor + Send, const WIDTH: usize, const HEIGHT: usize> Sync
+    for StaticFrameBufferBackend<Color, WIDTH, HEIGHT>
+{
+}
+
+impl<Color: PixelColor, const WIDTH: usize, const HEIGHT: usize>
+    StaticFrameBufferBackend<Color, WIDTH, HEIGHT>
+{
+    pub const fn new(fill_color: Color) -> Self {
+        Self {
+            buffer: UnsafeCell::new(ArrayFrameBufferBackend::new(fill_color)),
+            taken: AtomicBool::new(false),
+        }
+    }
+
+    /// All the pixels, for `SliceFrameBufferBackend::from_static`. None if already taken.
+    #[allow(clippy::mut_from_ref)]
+    pub fn take(&'static self) -> Option<&'static mut [Color]> {
+        if self.taken.swap(true, Ordering::AcqRel) {
+            return None;
+        }
+        // SAFETY: `taken` makes sure this is the only reference, ever
+        Some(unsafe { &mut *self.buffer.get() }.as_mut_slice())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_graphics::pixelcolor::{Rgb565, RgbColor};
+
+    use super::*;
+
+    #[test]
+    fn static_buffer_is_taken_once() {
+        static BUFFER: StaticFrameBufferBackend<Rgb565, 4, 3> =
+            StaticFrameBufferBackend::new(Rgb565::RED);
+        let pixels = BUFFER.take().unwrap();
+        assert_eq!(pixels.len(), 12);
+        assert!(BUFFER.take().is_none());
+
+        // Larger than needed is fine, the rest is left unused
+        let buffer = SliceFrameBufferBackend::from_static(pixels, Size::new(2, 5)).unwrap();
+        assert_eq!(buffer.pixels.len(), 10);
+        assert!(buffer.pixels.iter().all(|&pixel| pixel == Rgb565::RED));
+        let Pixels::Static(pixels) = buffer.pixels else {
+            panic!("static buffer ended up on the heap");
+        };
+        assert!(SliceFrameBufferBackend::from_static(pixels, Size::new(4, 3)).is_none());
+    }
+}
@@ -0,0 +1,35 @@
+use alloc::vec::Vec;
+
+use embedded_graphics::pixelcolor::Rgb565;
+
+use crate::{color565, intensity::Intensity};
+
+/// Persistence of vision, like on a dying panel: each frame keeps a fading copy of the ones
+/// before it. Also smooths out noise.
+#[derive(Default)]
+pub struct Ghosting {
+    /// What was shown last, ghosts included. Empty until the first frame.
+    previous: Vec<Rgb565>,
+}
+
+impl Ghosting {
+    /// Forgets previous frames, for when they're not related to the next one
+    pub fn reset(&mut self) {
+        self.previous.clear();
+    }
+
+    /// Blends `pixels` with the previous frames. Persistence is how much of them is left in
+    /// each frame, 0 means no ghosting at all.
+    pub fn apply(&mut self, pixels: &mut [Rgb565], persistence: Intensity) {
+        if persistence == Intensity::ZERO || self.previous.len() != pixels.len() {
+            self.previous.clear();
+            self.previous.extend_from_slice(pixels);
+            return;
+        }
+        let persistence = color565::fraction(persistence);
+        for (pixel, previous) in pixels.iter_mut().zip(&mut self.previous) {
+            *pixel = color565::lerp(*pixel, *previous, persistence);
+            *previous = *pixel;
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use alloc::{vec, vec::Vec};
+
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::{OriginDimensions, Size},
+    pixelcolor::{Rgb565, RgbColor},
+    primitives::Rectangle,
+    Pixel,
+};
+use embedded_graphics_framebuf::FrameBuf;
+use rand::Rng;
+
+use crate::{
+    effects::glitch_rows,
+    framebuffer::SliceFrameBufferBackend,
+    ghosting::Ghosting,
+    intensity::Intensity,
+    noise::{Flavor, NoiseEffect},
+    region::Region,
+};
+
+/// One step of the chain `GlitchedDrawTarget` applies on flush
+#[derive(Clone, Copy, Debug)]
+pub enum Effect {
+    /// Random parts of random rows shifted sideways, see `effects::glitch_rows`
+    Glitch {
+        max_offset: usize,
+        line_probability: f32,
+        wrap: bool,
+    },
+    Noise(NoiseEffect),
+    /// How much of the previous frames is left in each one, see `Ghosting`
+    Ghosting(Intensity),
+}
+
+impl Effect {
+    /// Roughly what the escalation looks like halfway through
+    pub fn defaults() -> Vec<Effect> {
+        vec![
+            Effect::Glitch {
+                max_offset: 16,
+                line_probability: 0.25,
+                wrap: false,
+            },
+            Effect::Noise(NoiseEffect::new(Flavor::Color, Intensity::new(0.02))),
+        ]
+    }
+}
+
+/// Wraps a display so that everything drawn on it gets corrupted by a chain of effects. Drawing
+/// goes to an off-screen buffer, which is sent to the display by `flush`:
+///
+/// ```
+/// # use embedded_graphics::{mock_display::MockDisplay, pixelcolor::Rgb565};
+/// # use evil_android::glitched::GlitchedDrawTarget;
+/// # use rand::{rngs::StdRng, SeedableRng};
+/// # let display = MockDisplay::<Rgb565>::new();
+/// let mut display = GlitchedDrawTarget::new(display, StdRng::from_entropy());
+/// // ...draw as usual, then once per frame:
+/// display.flush()?;
+/// # Ok::<(), core::convert::Infallible>(())
+/// ```
+///
+/// Effects are applied to the buffer in place, so it's best to redraw everything every frame.
+/// Coordinates start at 0, 0 even if the display's don't.
+pub struct GlitchedDrawTarget<D, R> {
+    target: D,
+    buffer: SliceFrameBufferBackend<Rgb565>,
+    ghosting: Ghosting,
+    rng: R,
+    /// Applied in order, `Effect::defaults()` unless changed
+    pub effects: Vec<Effect>,
+}
+
+impl<D: DrawTarget<Color = Rgb565>, R: Rng> GlitchedDrawTarget<D, R> {
+    pub fn new(target: D, rng: R) -> Self {
+        let size = target.bounding_box().size;
+        Self {
+            target,
+            buffer: SliceFrameBufferBackend::new(size, Rgb565::BLACK),
+            ghosting: Ghosting::default(),
+            rng,
+            effects: Effect::defaults(),
+        }
+    }
+
+    /// Applies the effects and sends the result to the display
+    pub fn flush(&mut self) -> Result<(), D::Error> {
+        let size = self.buffer.size;
+        let mut fb = FrameBuf::new(&mut self.buffer, size.width as usize, size.height as usize);
+        for effect in &self.effects {
+            match *effect {
+                Effect::Glitch {
+                    max_offset,
+                    line_probability,
+                    wrap,
+                } => glitch_rows(
+                    &mut fb,
+                    &mut self.rng,
+                    max_offset,
+                    line_probability,
+                    wrap,
+                    &Region::Full,
+                ),
+                Effect::Noise(noise) => noise.apply(&mut fb, &mut self.rng, &Region::Full),
+                Effect::Ghosting(persistence) => {
+                    self.ghosting.apply(&mut fb.data.pixels, persistence)
+                }
+            }
+        }
+        let area = Rectangle::new(self.target.bounding_box().top_left, size);
+        self.target
+            .fill_contiguous(&area, self.buffer.pixels.iter().copied())
+    }
+
+    pub fn inner_mut(&mut self) -> &mut D {
+        &mut self.target
+    }
+
+    pub fn into_inner(self) -> D {
+        self.target
+    }
+
+    fn canvas(&mut self) -> FrameBuf<Rgb565, &mut SliceFrameBufferBackend<Rgb565>> {
+        let size = self.buffer.size;
+        FrameBuf::new(&mut self.buffer, size.width as usize, size.height as usize)
+    }
+}
+
+impl<D, R> OriginDimensions for GlitchedDrawTarget<D, R> {
+    fn size(&self) -> Size {
+        self.buffer.size
+    }
+}
+
+impl<D: DrawTarget<Color = Rgb565>, R: Rng> DrawTarget for GlitchedDrawTarget<D, R> {
+    type Color = Rgb565;
+    type Error = D::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        // Drawing on a buffer never fails
+        self.canvas()
+            .draw_iter(pixels)
+            .unwrap_or_else(|e| match e {});
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.buffer.pixels.fill(color);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_graphics::{
+        mock_display::MockDisplay,
+        prelude::{Point, Primitive},
+        primitives::PrimitiveStyle,
+        Drawable,
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    fn draw_square(target: &mut impl DrawTarget<Color = Rgb565>) {
+        target.clear(Rgb565::BLACK).ok();
+        Rectangle::new(Point::new(2, 2), Size::new(4, 4))
+            .into_styled(PrimitiveStyle::with_fill(Rgb565::RED))
+            .draw(target)
+            .ok();
+    }
+
+    #[test]
+    fn without_effects_flush_shows_what_was_drawn() {
+        let mut expected = MockDisplay::new();
+        expected.set_allow_overdraw(true);
+        draw_square(&mut expected);
+
+        let mut display = GlitchedDrawTarget::new(MockDisplay::new(), StdRng::seed_from_u64(0));
+        display.effects.clear();
+        draw_square(&mut display);
+        display.flush().unwrap();
+        display.into_inner().assert_eq(&expected);
+    }
+
+    #[test]
+    fn glitches_only_move_pixels_within_rows() {
+        let mut display = GlitchedDrawTarget::new(MockDisplay::new(), StdRng::seed_from_u64(0));
+        display.effects = vec![Effect::Glitch {
+            max_offset: 8,
+            line_probability: 1.0,
+            wrap: true,
+        }];
+        draw_square(&mut display);
+        display.flush().unwrap();
+        let display = display.into_inner();
+        for y in 0..64 {
+            let red = (0..64)
+                .filter(|&x| display.get_pixel(Point::new(x, y)) == Some(Rgb565::RED))
+                .count();
+            assert_eq!(red, if (2..6).contains(&y) { 4 } else { 0 }, "row {y}");
+        }
+    }
+}
@@ -0,0 +1,60 @@
+/// How strong an effect is, from 0 (none at all) to 1 (as bad as it gets). Effects scale it to
+/// whatever units they use.
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+pub struct Intensity(f32);
+
+impl Intensity {
+    pub const ZERO: Intensity = Intensity(0.0);
+
+    /// Clamped to 0..1, NaN counts as 0
+    pub fn new(value: f32) -> Self {
+        if value.is_nan() {
+            Self::ZERO
+        } else {
+            Self(value.clamp(0.0, 1.0))
+        }
+    }
+
+    pub fn get(self) -> f32 {
+        self.0
+    }
+
+    /// 0..max
+    pub fn scale(self, max: f32) -> f32 {
+        self.0 * max
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn always_within_0_and_1(value in any::<f32>()) {
+            let intensity = Intensity::new(value).get();
+            prop_assert!((0.0..=1.0).contains(&intensity), "{value} -> {intensity}");
+        }
+
+        #[test]
+        fn values_within_0_and_1_are_kept(value in 0.0f32..=1.0) {
+            prop_assert_eq!(Intensity::new(value).get(), value);
+        }
+
+        #[test]
+        fn out_of_range_values_are_clamped(value in 1.0f32..=f32::MAX) {
+            prop_assert_eq!(Intensity::new(value), Intensity::new(1.0));
+            prop_assert_eq!(Intensity::new(-value), Intensity::ZERO);
+        }
+    }
+
+    #[test]
+    fn special_values() {
+        assert_eq!(Intensity::new(f32::NAN), Intensity::ZERO);
+        assert_eq!(Intensity::new(f32::INFINITY).get(), 1.0);
+        assert_eq!(Intensity::new(f32::NEG_INFINITY), Intensity::ZERO);
+        assert_eq!(Intensity::new(-0.0).get(), 0.0);
+    }
+}
@@ -0,0 +1,91 @@
+//! Positions relative to the screen, so that the same code lays things out right on 128x64,
+//! 160x128 and 320x240 panels alike
+
+use embedded_graphics::{
+    geometry::{Dimensions, Point, Size},
+    mono_font::MonoTextStyle,
+    primitives::Rectangle,
+};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Layout {
+    bounds: Rectangle,
+}
+
+impl Layout {
+    pub fn new(bounds: Rectangle) -> Self {
+        Self { bounds }
+    }
+
+    pub fn of(target: &impl Dimensions) -> Self {
+        Self::new(target.bounding_box())
+    }
+
+    pub fn center(&self) -> Point {
+        self.bounds.center()
+    }
+
+    /// Top left corner of something `size` big, in the middle
+    pub fn centered(&self, size: Size) -> Point {
+        self.center() - Rectangle::new(Point::zero(), size).center()
+    }
+
+    /// Space to leave around the edges: 2% of the shorter side, at least a pixel
+    fn margin(&self) -> u32 {
+        (self.bounds.size.width.min(self.bounds.size.height) / 50).max(1)
+    }
+
+    /// Whether the longest line of `text` fits between the margins
+    pub fn fits<C>(&self, text: &str, style: &MonoTextStyle<'_, C>) -> bool {
+        let longest = text.lines().map(|line| line.chars().count()).max();
+        let width = longest.unwrap_or(0) as u32 * style.font.character_size.width;
+        width + 2 * self.margin() <= self.bounds.size.width
+    }
+}
+
+/// Where the baseline of the first of `lines` lines goes, for all of them to be vertically
+/// centered on `center`. With `Alignment::Center`, the whole block is centered then.
+pub fn text_block<C>(center: Point, lines: usize, style: &MonoTextStyle<'_, C>) -> Point {
+    let line_height = style.font.character_size.height as i32;
+    let block_height = line_height * lines.max(1) as i32;
+    center + Point::new(0, style.font.baseline as i32 - block_height / 2)
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_graphics::{
+        mono_font::ascii::FONT_6X10,
+        pixelcolor::BinaryColor,
+        text::{Alignment, Text},
+    };
+
+    use super::*;
+
+    #[test]
+    fn lays_out_relative_to_the_screen() {
+        for size in [Size::new(128, 64), Size::new(160, 128), Size::new(320, 240)] {
+            let layout = Layout::new(Rectangle::new(Point::zero(), size));
+            assert_eq!(layout.centered(size), Point::zero());
+        }
+        let small = Layout::new(Rectangle::new(Point::zero(), Size::new(128, 64)));
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        assert!(small.fits("SOC temp: 45°C", &style));
+        assert!(!small.fits("short\nSOC temp: 45°C (simulated)", &style));
+    }
+
+    #[test]
+    fn centers_text_blocks() {
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        let center = Point::new(80, 64);
+        for lines in ["one", "one\ntwo", "one\ntwo\nthree"] {
+            let position = text_block(center, lines.lines().count(), &style);
+            let text = Text::with_alignment(lines, position, style, Alignment::Center);
+            let bounds = text.bounding_box();
+            // Within a pixel, of rounding
+            assert!(
+                (bounds.center().y - center.y).abs() <= 1,
+                "{lines}: {bounds:?}"
+            );
+        }
+    }
+}
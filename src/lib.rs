@@ -0,0 +1,26 @@
+//! Rendering core of the evil android: frame buffers, glitches, noise and other effects for
+//! embedded-graphics displays. `no_std`, but needs an allocator.
+//!
+//! Other projects can get the same look by wrapping their display in
+//! [`glitched::GlitchedDrawTarget`].
+#![cfg_attr(not(test), no_std)]
+#![deny(clippy::std_instead_of_core, clippy::std_instead_of_alloc)]
+
+extern crate alloc;
+
+pub mod color565;
+pub mod duration_fmt;
+pub mod effects;
+pub mod framebuffer;
+pub mod ghosting;
+pub mod glitched;
+pub mod intensity;
+pub mod layout;
+pub mod noise;
+pub mod orientation;
+pub mod region;
+pub mod testing;
+pub mod text_sprite;
+pub mod viewport;
+
+pub use evil_android_frame_codec as frame_codec;
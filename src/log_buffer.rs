@@ -0,0 +1,126 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use anyhow::Result;
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::Point,
+    mono_font::{ascii::FONT_4X6, MonoFont, MonoTextStyleBuilder},
+    pixelcolor::Rgb565,
+    prelude::RgbColor,
+    text::{Baseline, Text},
+    Drawable,
+};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+use crate::theme;
+
+const MAX_LINES: usize = 64;
+// Long lines get truncated, to put a bound on memory usage
+const MAX_LINE_LEN: usize = 128;
+const OVERLAY_FONT: MonoFont = FONT_4X6;
+
+static LINES: Mutex<VecDeque<(Level, String)>> = Mutex::new(VecDeque::new());
+static ERRORS: AtomicUsize = AtomicUsize::new(0);
+
+/// Passes everything to another logger, keeping a copy of last MAX_LINES lines
+struct BufferingLogger<L: Log> {
+    inner: L,
+}
+
+impl<L: Log> Log for BufferingLogger<L> {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        self.inner.log(record);
+
+        if record.level() == Level::Error {
+            ERRORS.fetch_add(1, Ordering::Relaxed);
+        }
+        if record.level() > log::max_level() {
+            return;
+        }
+        let mut line = format!("{}", record.args());
+        if line.len() > MAX_LINE_LEN {
+            let mut end = MAX_LINE_LEN;
+            while !line.is_char_boundary(end) {
+                end -= 1;
+            }
+            line.truncate(end);
+        }
+
+        // Not panicking on a poisoned lock, logging is done from panic hooks too
+        if let Ok(mut lines) = LINES.lock() {
+            if lines.len() == MAX_LINES {
+                lines.pop_front();
+            }
+            lines.push_back((record.level(), line));
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Installs `logger` as the global logger, keeping a copy of recent lines
+pub fn init(logger: impl Log + 'static, level: LevelFilter) -> Result<()> {
+    let logger = Box::leak(Box::new(BufferingLogger { inner: logger }));
+    log::set_logger(logger).map_err(|e| anyhow::Error::msg(format!("{e}")))?;
+    log::set_max_level(level);
+    Ok(())
+}
+
+/// Errors logged so far, for noticing new ones
+pub fn errors() -> usize {
+    ERRORS.load(Ordering::Relaxed)
+}
+
+/// Returns a copy of the buffered lines, oldest first
+pub fn lines() -> Vec<(Level, String)> {
+    match LINES.lock() {
+        Ok(lines) => lines.iter().cloned().collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Draws as many recent log lines as fit on the screen, newest at the bottom
+pub fn draw_overlay<D: DrawTarget<Color = Rgb565>>(target: &mut D) -> Result<(), D::Error> {
+    let bb = target.bounding_box();
+    let chars_per_line = (bb.size.width / OVERLAY_FONT.character_size.width) as usize;
+    let max_lines = (bb.size.height / OVERLAY_FONT.character_size.height) as usize;
+
+    let [info, warn, error] = theme::current().log();
+    let lines = lines();
+    let visible = &lines[lines.len().saturating_sub(max_lines)..];
+    let first_line_y = bb.size.height - visible.len() as u32 * OVERLAY_FONT.character_size.height;
+    for (idx, (level, line)) in visible.iter().enumerate() {
+        let color = match level {
+            Level::Error => error,
+            Level::Warn => warn,
+            _ => info,
+        };
+        let style = MonoTextStyleBuilder::new()
+            .font(&OVERLAY_FONT)
+            .text_color(color)
+            .background_color(Rgb565::BLACK)
+            .build();
+        let line = line.chars().take(chars_per_line).collect::<String>();
+        let y = first_line_y + idx as u32 * OVERLAY_FONT.character_size.height;
+        Text::with_baseline(
+            &line,
+            bb.top_left + Point::new(0, y as i32),
+            style,
+            Baseline::Top,
+        )
+        .draw(target)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,161 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::{Dimensions, Point, Size},
+    mono_font::{iso_8859_1::FONT_6X10, MonoTextStyle},
+    pixelcolor::Rgb565,
+    prelude::RgbColor,
+    primitives::Rectangle,
+    text::{Baseline, Text},
+    Drawable,
+};
+
+use crate::{
+    platform::InputEvent,
+    scene::{Canvas, Scene, SceneContext},
+    theme,
+};
+
+/// Pressing pause that many times in a row, quickly enough, sends you to lunch
+const TRIGGER_PRESSES: usize = 5;
+const TRIGGER_WINDOW: Duration = Duration::from_secs(3);
+
+/// From sensible to what this device is actually about
+const TARGETS: &[&str] = &[
+    "aosp_arm-eng",
+    "aosp_arm64-userdebug",
+    "aosp_x86_64-eng",
+    "aosp_cf_x86_64_phone-userdebug",
+    "sdk_phone64_arm64-eng",
+    "aosp_toaster-userdebug",
+    "aosp_fridge_x86-eng",
+    "aosp_smartwatch_for_cats-user",
+    "aosp_potato-eng",
+    "aosp_tamagotchi_arm5-userdebug",
+    "aosp_abacus-eng",
+    "aosp_fax_machine-user",
+    "aosp_toaster_cluster-eng",
+    "aosp_heat_death-userdebug",
+    "aosp_androidbp_forever-eng",
+    "aosp_dumpster_fire-eng",
+];
+const CHOSEN_TARGET: &str = "evil_android-eng";
+
+const TITLE: &str = "Lunch menu .. Here are\nthe common combinations:";
+/// Scrolling speeds up from a crawl to a blur
+const SCROLL_DURATION: Duration = Duration::from_secs(6);
+const TYPING_DURATION: Duration = Duration::from_millis(1500);
+const ENV_DURATION: Duration = Duration::from_secs(3);
+
+/// Watches input for the secret sequence
+#[derive(Default)]
+pub struct Trigger {
+    presses: Vec<Instant>,
+}
+
+impl Trigger {
+    /// Returns true once the sequence is complete
+    pub fn on_input(&mut self, event: InputEvent) -> bool {
+        if event != InputEvent::TogglePause {
+            self.presses.clear();
+            return false;
+        }
+        let now = Instant::now();
+        self.presses.retain(|t| now - *t < TRIGGER_WINDOW);
+        self.presses.push(now);
+        if self.presses.len() < TRIGGER_PRESSES {
+            return false;
+        }
+        self.presses.clear();
+        log::info!("going for lunch");
+        true
+    }
+}
+
+/// Fake `lunch` target chooser, that chooses the worst target there is
+pub struct LunchScene;
+
+impl Scene for LunchScene {
+    fn name(&self) -> &'static str {
+        "lunch"
+    }
+
+    fn draw(&mut self, t: Duration, canvas: &mut Canvas, _ctx: &mut SceneContext) -> Result<bool> {
+        let line_height = FONT_6X10.character_size.height as i32;
+        let [text_color, highlight_color] = theme::current().lunch();
+        let style = MonoTextStyle::new(&FONT_6X10, text_color);
+        let highlight = MonoTextStyle::new(&FONT_6X10, highlight_color);
+        let size = canvas.bounding_box().size;
+        let height = size.height as i32;
+        let list_top = line_height * 2 + 2;
+        let visible_lines = ((height - list_top) / line_height).max(1) as usize;
+
+        canvas.clear(Rgb565::BLACK)?;
+        if t < SCROLL_DURATION {
+            // Accelerating, so that the last few pass by way too fast to read
+            let progress = t.as_secs_f32() / SCROLL_DURATION.as_secs_f32();
+            let max_scroll = TARGETS.len().saturating_sub(visible_lines) as f32;
+            let scrolled = progress * progress * max_scroll;
+            let offset = (scrolled.fract() * line_height as f32) as i32;
+            for (i, target) in TARGETS.iter().enumerate().skip(scrolled as usize) {
+                let y = list_top + (i - scrolled as usize) as i32 * line_height - offset;
+                if y >= height {
+                    break;
+                }
+                Text::with_baseline(
+                    &format!("{:>3}. {target}", i + 1),
+                    Point::new(0, y),
+                    style,
+                    Baseline::Top,
+                )
+                .draw(canvas)?;
+            }
+            // Over whatever scrolled under it
+            canvas.fill_solid(
+                &Rectangle::new(Point::zero(), Size::new(size.width, list_top as u32)),
+                Rgb565::BLACK,
+            )?;
+            Text::with_baseline(TITLE, Point::zero(), style, Baseline::Top).draw(canvas)?;
+            return Ok(true);
+        }
+
+        let t = t - SCROLL_DURATION;
+        if t < TYPING_DURATION {
+            let typed =
+                CHOSEN_TARGET.len() * t.as_millis() as usize / TYPING_DURATION.as_millis() as usize;
+            Text::with_baseline(TITLE, Point::zero(), style, Baseline::Top).draw(canvas)?;
+            Text::with_baseline(
+                "Which would you like?",
+                Point::new(0, list_top),
+                style,
+                Baseline::Top,
+            )
+            .draw(canvas)?;
+            Text::with_baseline(
+                &format!("> {}_", &CHOSEN_TARGET[..typed]),
+                Point::new(0, list_top + line_height),
+                highlight,
+                Baseline::Top,
+            )
+            .draw(canvas)?;
+            return Ok(true);
+        }
+
+        let t = t - TYPING_DURATION;
+        if t < ENV_DURATION {
+            let text = format!(
+                "====================\n\
+                 TARGET_PRODUCT=\n  {}\n\
+                 TARGET_BUILD_VARIANT=eng\n\
+                 ====================\n\
+                 Starting the build...",
+                CHOSEN_TARGET.trim_end_matches("-eng")
+            );
+            Text::with_baseline(&text, Point::zero(), highlight, Baseline::Top).draw(canvas)?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+}
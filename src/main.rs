@@ -1,102 +1,141 @@
+extern crate alloc;
+
 use std::{
-    ops::{Div, Range, Rem}, time::{Duration, Instant}
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
 };
 
-use anyhow::{bail, Context, Result};
+use anyhow::{Context, Result};
+use duration_fmt::Style;
+use effects::{add_noise, glitch, glitch_rows, intensify, MaskedImage};
 use embedded_graphics::{
     draw_target::DrawTarget,
-    geometry::{Dimensions, OriginDimensions, Point, Size},
-    image::GetPixel,
-    mono_font::{ascii::FONT_6X10, MonoTextStyle},
-    pixelcolor::{BinaryColor, PixelColor, Rgb565},
+    geometry::{Dimensions, Point, Size},
+    mono_font::{MonoFont, MonoTextStyle},
+    pixelcolor::Rgb565,
     prelude::RgbColor,
     primitives::Rectangle,
-    text::{Alignment, Text},
-    Drawable, Pixel,
+    text::Alignment,
+};
+use embedded_graphics_framebuf::FrameBuf;
+// Rendering core lives in the library, imported here so that `crate::` paths to it work too
+use evil_android::{
+    color565, duration_fmt, effects, frame_codec, framebuffer, ghosting, intensity, layout, noise,
+    orientation, region, text_sprite, viewport,
 };
-use embedded_graphics_framebuf::{backends::FrameBufferBackend, FrameBuf};
-use itertools::Itertools;
-use platform::{Brightness, Platform, LED};
-use rand::Rng;
+use framebuffer::SliceFrameBufferBackend;
+use intensity::Intensity;
+use layout::Layout;
+use noise::{Flavor, NoiseEffect};
+use orientation::{Orientation, Rotation};
+use platform::{
+    Brightness, DisplayId, FrameStats, Input, InputEvent, Platform, PlatformError, ResumeState, LED,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use region::Region;
+use scene::{Canvas, Scene, SceneContext};
+use sprites::{DisplayList, Sprite};
+use transition::SceneManager;
+use tweaks::{led_levels, LedChannels};
 
+mod animation;
+#[cfg_attr(not(feature = "net"), allow(dead_code))]
+mod artnet;
+mod assets;
+mod backlight;
+mod badge;
+mod battery;
+mod boot_splash;
+mod brightness_cap;
+mod calendar;
+mod calibration;
+#[cfg(target_os = "linux")]
+mod capture;
+#[cfg(target_os = "linux")]
+mod choreography;
+mod clock;
+mod console;
+mod countdown;
+mod demo;
+mod device_name;
+mod diagnostics;
+#[cfg_attr(not(any(feature = "ssd1306", feature = "epaper")), allow(dead_code))]
+mod dither;
+mod exaggeration;
+mod eyes;
+mod frame_clock;
+mod frame_skip;
+mod log_buffer;
+mod lunch;
+mod message_layout;
+#[cfg_attr(not(feature = "net"), allow(dead_code))]
+mod mirror;
+#[cfg_attr(not(feature = "net"), allow(dead_code))]
+mod osc;
+mod panic_screen;
 mod platform;
-
-struct MaskedImage<ColorImage, MaskImage>
-where
-    ColorImage: OriginDimensions + GetPixel<Color = Rgb565>,
-    MaskImage: OriginDimensions + GetPixel<Color = BinaryColor>,
-{
-    color_image: ColorImage,
-    mask_image: MaskImage,
-    pos: Point,
-}
-
-impl<ColorImage, MaskImage> MaskedImage<ColorImage, MaskImage>
-where
-    ColorImage: OriginDimensions + GetPixel<Color = Rgb565>,
-    MaskImage: OriginDimensions + GetPixel<Color = BinaryColor>,
-{
-    fn new(color_image: ColorImage, mask_image: MaskImage, pos: Point) -> Result<Self> {
-        if color_image.bounding_box() != mask_image.bounding_box() {
-            bail!(
-                "inconsistent dimensions of color vs mask\ncolor: {cbb:?}\n mask: {mbb:?}",
-                cbb = color_image.bounding_box(),
-                mbb = mask_image.bounding_box()
-            );
-        }
-        Ok(Self {
-            color_image,
-            mask_image,
-            pos,
-        })
-    }
-}
-
-impl<ColorImage, MaskImage> Drawable for MaskedImage<ColorImage, MaskImage>
-where
-    ColorImage: OriginDimensions + GetPixel<Color = Rgb565>,
-    MaskImage: OriginDimensions + GetPixel<Color = BinaryColor>,
-{
-    type Color = Rgb565;
-    type Output = ();
-
-    fn draw<D>(&self, target: &mut D) -> std::result::Result<Self::Output, D::Error>
-    where
-        D: DrawTarget<Color = Self::Color>,
-    {
-        let bb = self.color_image.bounding_box();
-        let x_range = bb.top_left.x..bb.bottom_right().unwrap().x;
-        let y_range = bb.top_left.y..=bb.bottom_right().unwrap().y;
-        let points = y_range
-            .cartesian_product(x_range)
-            .map(|(y, x)| Point::new(x, y));
-        let pixels = points.filter_map(|p| {
-            if self.mask_image.pixel(p).unwrap().is_on() {
-                Some(Pixel(p + self.pos, self.color_image.pixel(p).unwrap()))
-            } else {
-                None
-            }
-        });
-        target.draw_iter(pixels)
-    }
-}
+mod power;
+// Stays no_std + alloc clean like the library, scenes could move there one day
+#[deny(clippy::std_instead_of_core, clippy::std_instead_of_alloc)]
+mod scene;
+mod screen_shake;
+mod screenshot;
+#[cfg(feature = "scripting")]
+mod script;
+mod serial_frames;
+#[cfg(target_os = "linux")]
+mod serial_host;
+mod sprites;
+mod stats;
+mod success;
+mod temperature;
+// Not testing.rs, that one is the library's
+#[cfg(any(test, feature = "testing"))]
+#[cfg_attr(not(test), allow(dead_code))]
+#[path = "mock_platform.rs"]
+pub mod testing;
+mod theme;
+mod timeline;
+mod transition;
+mod tweaks;
+mod typewriter;
+mod version;
+mod wall_clock;
+mod webhooks;
 
 // no const fn for this in std yet :(
+/// Panics on anything but digits that fit in a usize. Used on env vars set by build.rs, where
+/// that means a compile error.
+#[cfg_attr(not(feature = "fire"), allow(dead_code))]
 const fn parse_usize(s: &str) -> usize {
-    let mut val = 0;
     let bytes = s.as_bytes();
+    if bytes.is_empty() {
+        panic!("failed to parse int: empty string");
+    }
+    let mut val: usize = 0;
     let mut i = 0;
     while i < bytes.len() {
-        val *= 10;
-        match bytes[i] {
-            b'0'..=b'9' => val += (bytes[i] - b'0') as usize,
+        let digit = match bytes[i] {
+            b'0'..=b'9' => (bytes[i] - b'0') as usize,
             _ => panic!("failed to parse int"),
-        }
+        };
+        val = match val.checked_mul(10) {
+            Some(val) => val,
+            None => panic!("failed to parse int: too large"),
+        };
+        val = match val.checked_add(digit) {
+            Some(val) => val,
+            None => panic!("failed to parse int: too large"),
+        };
         i += 1;
     }
     val
 }
 
+#[cfg(feature = "fire")]
 mod dumpster_fire {
     use anyhow::Result;
     use embedded_graphics::{
@@ -125,288 +164,1417 @@ mod dumpster_fire {
     }
 }
 
-fn intensify(rng: &mut impl Rng, point: Point, amplitude: i32) -> Point {
-    if amplitude == 0 {
-        point
+/// Sends the `area` part of a full frame to the LCD. Rows as wide as the frame are already next
+/// to each other and go in one write, narrower ones one row at a time.
+fn flush_lcd(platform: &mut impl Platform, pixels: &[Rgb565], area: Rectangle) -> Result<()> {
+    let width = platform.lcd().bounding_box().size.width as usize;
+    let (left, area_width) = (area.top_left.x as usize, area.size.width as usize);
+    let result = if area_width == width {
+        let top = area.top_left.y as usize * width;
+        platform.write_window(&area, &pixels[top..][..width * area.size.height as usize])
     } else {
-        Point::new(
-            point.x + rng.gen_range(-amplitude..amplitude),
-            point.y + rng.gen_range(-amplitude..amplitude),
-        )
-    }
-}
-
-#[derive(Clone)]
-struct VecFrameBufferBackend<Color: PixelColor> {
-    pixels: Vec<Color>,
-    size: Size,
-}
-
-impl<Color: PixelColor> VecFrameBufferBackend<Color> {
-    fn new(size: Size, fill_color: Color) -> Self {
-        let width = usize::try_from(size.width).unwrap();
-        let height = usize::try_from(size.height).unwrap();
-        let pixels = vec![fill_color; width * height];
-        Self { pixels, size }
-    }
+        area.rows().try_for_each(|y| {
+            let row = Rectangle::new(
+                Point::new(area.top_left.x, y),
+                Size::new(area.size.width, 1),
+            );
+            platform.write_window(&row, &pixels[y as usize * width + left..][..area_width])
+        })
+    };
+    result.map_err(|e| platform.display_error(e).into())
 }
 
-fn div_rem<T: Div<Output = T> + Rem<Output = T> + Copy>(a: T, b: T) -> (T, T) {
-    (a / b, a % b)
+/// Sets both LEDs, unless a lighting desk drives them over Art-Net, and sends what they show
+/// to Art-Net
+fn set_leds(platform: &mut impl Platform, leds: [Brightness; 2]) -> Result<()> {
+    let leds = artnet::input().unwrap_or(leds);
+    platform.led0().set_brightness(leds[0])?;
+    platform.led1().set_brightness(leds[1])?;
+    artnet::output(leds);
+    Ok(())
 }
 
-fn format_duration(d: Duration) -> String {
-    let secs = d.as_secs();
-    let (mins, secs) = div_rem(secs, 60);
-    let (hrs, mins) = div_rem(mins, 60);
-    let (days, hrs) = div_rem(hrs, 24);
-    let (years, days) = div_rem(days, 365);
-
-    let mut s = String::new();
-    if years > 0 {
-        s = format!("{s}{years}y ");
-    }
-    if days > 0 {
-        s = format!("{s}{days}d ");
-    }
-    if hrs > 0 {
-        s = format!("{s}{hrs}:");
-    }
-    format!("{s}{mins:02}:{secs:02}")
+/// Sends a full frame to the LCD and feeds the watchdog. If the LCD keeps failing, attempts
+/// resetting it before giving up.
+fn show_frame(platform: &mut impl Platform, pixels: &[Rgb565]) -> Result<()> {
+    show_frame_area(platform, pixels, None)
 }
 
-impl<Color: PixelColor> FrameBufferBackend for &mut VecFrameBufferBackend<Color> {
-    type Color = Color;
+/// Like `show_frame`, but sends only `area` of the frame to the LCD, if given. The rest of the
+/// frame has to be the same as the one shown before.
+fn show_frame_area(
+    platform: &mut impl Platform,
+    pixels: &[Rgb565],
+    area: Option<Rectangle>,
+) -> Result<()> {
+    const MAX_FLUSH_ATTEMPTS: usize = 3;
+    // Of the last frame shown
+    static FLIPPED: AtomicBool = AtomicBool::new(false);
 
-    fn set(&mut self, index: usize, color: Self::Color) {
-        self.pixels[index] = color;
+    let bb = platform.lcd().bounding_box();
+    let upside_down = calendar::is_upside_down();
+    // Turning the screen over moves every pixel
+    let area = match FLIPPED.swap(upside_down, Ordering::Relaxed) == upside_down {
+        true => area.unwrap_or(bb),
+        false => bb,
+    };
+    // Into the same buffer every frame
+    static FLIPPED_PIXELS: Mutex<Vec<Rgb565>> = Mutex::new(Vec::new());
+    let mut flipped = FLIPPED_PIXELS.lock().unwrap();
+    let (pixels, area) = if upside_down {
+        let half_turn = Orientation {
+            rotation: Rotation::Deg180,
+            mirror: false,
+        };
+        orientation::turn(
+            half_turn,
+            bb.size,
+            &bb,
+            pixels.iter().copied(),
+            &mut flipped,
+        );
+        (&flipped[..], half_turn.area_to_panel(area, bb.size))
+    } else {
+        (pixels, area)
+    };
+    platform
+        .feed_watchdog()
+        .context("Platform::feed_watchdog failed")?;
+    screenshot::offer(bb.size, pixels);
+    serial_frames::offer(bb.size, pixels);
+    // Nothing changed
+    if area.is_zero_sized() {
+        return Ok(());
     }
+    // Only what goes to the panel is corrected, screenshots and streams show the frame as drawn.
+    // Into the same buffer every frame, and only `area` of it, the part `flush_lcd` reads.
+    static CALIBRATED: Mutex<Vec<Rgb565>> = Mutex::new(Vec::new());
+    calibration::save_picked(platform);
+    let mut calibrated = CALIBRATED.lock().unwrap();
+    let pixels = match calibration::current() {
+        Some(table) => {
+            table.apply_area(pixels, bb.size.width as usize, area, &mut calibrated);
+            &calibrated[..]
+        }
+        None => pixels,
+    };
 
-    fn get(&self, index: usize) -> Self::Color {
-        self.pixels[index]
+    for attempt in 1..=MAX_FLUSH_ATTEMPTS {
+        match flush_lcd(platform, pixels, area) {
+            Ok(()) => return Ok(()),
+            Err(e) => {
+                log::warn!("LCD flush attempt {attempt}/{MAX_FLUSH_ATTEMPTS}: {e:?}");
+                // Straight to the reset, trying again won't help
+                if !PlatformError::is_transient_error(&e) {
+                    break;
+                }
+            }
+        }
     }
 
-    fn nr_elements(&self) -> usize {
-        usize::try_from(self.size.width).unwrap() * usize::try_from(self.size.height).unwrap()
-    }
+    log::error!("LCD unresponsive, resetting");
+    platform.reset_lcd().context("Platform::reset_lcd failed")?;
+    flush_lcd(platform, pixels, bb).context("LCD still unresponsive after reset")
 }
 
-#[derive(Clone, Copy, Debug)]
-struct RowOffset {
-    offset: usize,
-    row_width: usize,
+/// Platform input first, then whatever came over OSC
+fn poll_input(platform: &mut impl Platform) -> Result<Option<InputEvent>> {
+    Ok(platform.input().poll()?.or_else(osc::poll_event))
 }
 
-impl RowOffset {
-    fn new<C: PixelColor, B: FrameBufferBackend<Color = C>>(
-        offset: usize,
-        fb: &FrameBuf<C, B>,
-    ) -> Self {
-        assert!(fb.width() > 0);
-        let row_width = fb.width();
-        let offset = offset.min(row_width);
-        Self { offset, row_width }
+/// Test pattern, LED sweep and what's running where, for bringing up new hardware. Also tells
+/// multiple androids on one network apart.
+fn show_boot_splash(
+    platform: &mut impl Platform,
+    buffer: &mut SliceFrameBufferBackend<Rgb565>,
+    assets: &assets::Assets,
+) -> Result<()> {
+    let name = device_name::get();
+    log::info!("{name}: v{} on {}", version::full(), platform.name());
+    let mut scene_manager = SceneManager::new(
+        assets
+            .transitions
+            .clone()
+            .unwrap_or_else(transition::defaults),
+        buffer.size,
+    );
+    let mut rng = StdRng::from_entropy();
+    scene_manager.switch_to("boot-splash", &buffer.pixels, &mut rng);
+    let shown_since = frame_clock::now();
+    while frame_clock::elapsed(shown_since) < boot_splash::DURATION && !platform.exit_requested() {
+        // WiFi may connect in the meantime
+        let info = boot_splash::Info {
+            name: &name,
+            platform: platform.name(),
+            ip: platform.ip_address(),
+        };
+        let size = buffer.size;
+        let mut framebuffer = FrameBuf::new(
+            &mut *buffer,
+            size.width.try_into()?,
+            size.height.try_into()?,
+        );
+        boot_splash::draw(&mut framebuffer, &info).context("boot_splash::draw failed")?;
+        let [led0, led1] = boot_splash::led_colors(frame_clock::elapsed(shown_since));
+        platform.led0().set_color(led0)?;
+        platform.led1().set_color(led1)?;
+        show_frame(platform, scene_manager.blend(&buffer.pixels, &mut rng))?;
+        platform.report_frame_stats(&FrameStats {
+            scene: "boot-splash",
+            ..Default::default()
+        });
+        platform.feed_watchdog()?;
+        platform.sleep(Duration::from_millis(50));
     }
+    platform.led0().set_brightness(0.0.into())?;
+    platform.led1().set_brightness(0.0.into())?;
+    Ok(())
+}
 
-    fn range_to(self, other: usize) -> RowRange {
-        RowRange {
-            start: self.offset.min(other).min(self.row_width),
-            end: self.offset.max(other).min(self.row_width),
-            row_width: self.row_width,
-        }
+/// Battery indicator, log and diagnostics, in front of everything else by default
+fn push_overlays<'a>(
+    layers: &mut DisplayList<'a>,
+    battery_charge: Option<f32>,
+    show_log: bool,
+    diagnostics: &'a diagnostics::Overlay,
+    stats: &'a FrameStats,
+) {
+    if let Some(charge) = battery_charge {
+        layers.push(
+            Sprite::new(sprites::BATTERY, move |canvas, _ctx| {
+                battery::draw_indicator(canvas, charge)?;
+                Ok(canvas.bounding_box())
+            })
+            .key(sprites::key(charge.to_bits())),
+        );
     }
+    layers.push(
+        Sprite::new(sprites::LOG, |canvas, _ctx| {
+            log_buffer::draw_overlay(canvas)?;
+            Ok(canvas.bounding_box())
+        })
+        .visible(show_log),
+    );
+    layers.push(
+        Sprite::new(sprites::DIAGNOSTICS, |canvas, _ctx| {
+            diagnostics.draw(canvas, stats)?;
+            Ok(canvas.bounding_box())
+        })
+        .visible(diagnostics.is_shown()),
+    );
 }
 
-#[derive(Clone, Copy, Debug)]
-struct RowRange {
-    start: usize,
-    end: usize,
-    row_width: usize,
+/// Line of text on the text layer, centered at `position` in half pixels. Blended into
+/// `background` when between pixels, with `edge` around it.
+fn text_line<'a>(
+    sprite: &'a mut text_sprite::TextSprite,
+    text: &'a str,
+    position: Point,
+    style: MonoTextStyle<'static, Rgb565>,
+    edge: text_sprite::Edge,
+    background: Rgb565,
+) -> Sprite<'a> {
+    // Fonts by address, like TextSprite compares them
+    let font: *const MonoFont = style.font;
+    Sprite::new(sprites::TEXT, move |canvas, ctx| {
+        sprite.set_edge(edge);
+        sprite
+            .draw_supersampled(
+                text,
+                position + ctx.offset * 2,
+                style,
+                Alignment::Center,
+                background,
+                canvas,
+            )
+            .context("TextSprite::draw_supersampled failed")
+    })
+    .key(sprites::key((
+        text,
+        position,
+        font,
+        style.text_color,
+        edge,
+        background,
+    )))
 }
 
-impl RowRange {
-    fn offset(self, rhs: isize) -> RowRange {
-        let start = ((self.start as isize).saturating_add(rhs).max(0) as usize).min(self.row_width);
-        let end = ((self.end as isize).saturating_add(rhs).max(0) as usize).min(self.row_width);
-        Self { start, end, ..self }
+/// Kicks the screen shake on glitch bursts and errors logged since the last frame, and lets it
+/// calm down by `frame_time`
+fn update_screen_shake(
+    shake: &mut screen_shake::ScreenShake,
+    config: &screen_shake::Config,
+    burst: f32,
+    errors_seen: &mut usize,
+    frame_time: Duration,
+) {
+    shake.settle(frame_time, config.decay_secs);
+    shake.kick(burst * config.burst);
+    let errors = log_buffer::errors();
+    if errors != *errors_seen {
+        *errors_seen = errors;
+        shake.kick(config.error);
     }
+}
+
+/// Moves the whole frame around, while it's shaking
+fn screen_shake_sprite(shake: &mut screen_shake::ScreenShake) -> Sprite<'_> {
+    let visible = shake.is_shaking();
+    Sprite::new(sprites::SHAKE, move |canvas, ctx| {
+        let width = canvas.width();
+        Ok(match shake.apply(&mut canvas.data.pixels, width, ctx.rng) {
+            true => canvas.bounding_box(),
+            false => Rectangle::zero(),
+        })
+    })
+    .visible(visible)
+}
 
-    fn to_range(&self) -> Range<usize> {
-        self.start..self.end
+/// Ghosting changes the whole screen, unless it's off
+fn ghosted_area(canvas: &Canvas, persistence: Intensity) -> Rectangle {
+    match persistence == Intensity::ZERO {
+        true => Rectangle::zero(),
+        false => canvas.bounding_box(),
     }
 }
 
-struct Intensity(usize);
+/// Plays the scene until it's over, or skipped
+fn play_scene(
+    platform: &mut impl Platform,
+    buffer: &mut SliceFrameBufferBackend<Rgb565>,
+    mut virtual_canvas: Option<&mut SliceFrameBufferBackend<Rgb565>>,
+    scene: &mut dyn Scene,
+    scene_manager: &mut SceneManager,
+    rng: &mut StdRng,
+    led_scale: f32,
+) -> Result<()> {
+    let started = frame_clock::now();
+    let mut last_frame_time = started;
+    let mut leds = [0.0; 2];
+    // Whatever the escalation left it at
+    platform.set_backlight(Brightness::from(1.0))?;
+    loop {
+        if platform.exit_requested() {
+            return Ok(());
+        }
+        while let Some(event) = poll_input(platform)? {
+            match event {
+                InputEvent::NextScene => return Ok(()),
+                InputEvent::DumpFrame => screenshot::request_dump(),
+                // Either the badge is over, or it should show up right away
+                InputEvent::ToggleBadge => {
+                    badge::toggle();
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
 
-impl Intensity {
-    const MAX: Intensity = Intensity(128);
-}
+        let now = frame_clock::now();
+        let mut stats = FrameStats {
+            scene: scene.name(),
+            frame_time: now - last_frame_time,
+            ..Default::default()
+        };
+        last_frame_time = now;
 
-impl From<usize> for Intensity {
-    fn from(value: usize) -> Self {
-        Self(value.min(Self::MAX.0))
+        scene_manager.switch_to(scene.name(), &buffer.pixels, rng);
+        let size = buffer.size;
+        let mut canvas = FrameBuf::new(
+            &mut *buffer,
+            size.width.try_into()?,
+            size.height.try_into()?,
+        );
+        let mut ctx = SceneContext {
+            storage: platform.storage(),
+            rng: &mut *rng,
+            leds,
+            virtual_canvas: virtual_canvas.as_deref_mut(),
+        };
+        let t = Instant::now();
+        match scene.draw(frame_clock::elapsed(started), &mut canvas, &mut ctx) {
+            Ok(true) => {}
+            Ok(false) => return Ok(()),
+            // Broken scenes are not worth restarting everything over
+            Err(e) => {
+                log::error!("{} scene failed: {e:?}", scene.name());
+                return Ok(());
+            }
+        }
+        leds = ctx.leds;
+        stats.text = t.elapsed();
+
+        let t = Instant::now();
+        show_frame(platform, scene_manager.blend(&buffer.pixels, rng))?;
+        stats.flush = t.elapsed();
+        set_leds(platform, leds.map(|led| Brightness::from(led * led_scale)))?;
+        platform.report_frame_stats(&stats);
+
+        platform.sleep(Duration::from_millis(10));
     }
 }
 
-fn add_noise<B: FrameBufferBackend<Color = Rgb565>>(
-    fb: &mut FrameBuf<Rgb565, B>,
-    rng: &mut impl Rng,
-    intensity: Intensity,
-) {
-    for index in 0..fb.data.nr_elements() {
-        let apply_noise = rng.next_u32() as usize % Intensity::MAX.0 < intensity.0;
-        if apply_noise {
-            let random_color = Rgb565::new(
-                (rng.next_u32() % 32) as u8,
-                (rng.next_u32() % 64) as u8,
-                (rng.next_u32() % 32) as u8,
-            );
-            fb.data.set(index, random_color);
+/// For the main display, once for all the runs of `draw_loop`, as the platform hands out its
+/// memory only once
+fn frame_buffer(platform: &mut impl Platform) -> SliceFrameBufferBackend<Rgb565> {
+    log::info!("allocating the frame buffer");
+    let lcd_size = platform.lcd().bounding_box().size;
+    match platform.frame_buffer() {
+        Some(pixels) => {
+            SliceFrameBufferBackend::from_static(pixels, lcd_size).unwrap_or_else(|| {
+                log::warn!("static frame buffer too small for {lcd_size}, using the heap");
+                SliceFrameBufferBackend::new(lcd_size, Rgb565::BLACK)
+            })
         }
+        None => SliceFrameBufferBackend::new(lcd_size, Rgb565::BLACK),
     }
 }
 
-fn glitch<C: PixelColor, B: FrameBufferBackend<Color = C>>(
-    fb: &mut FrameBuf<C, B>,
-    rng: &mut impl Rng,
-    max_offset: usize,
-) {
-    if max_offset == 0 {
-        return;
-    }
+fn draw_loop(
+    platform: &mut impl Platform,
+    buffer: &mut SliceFrameBufferBackend<Rgb565>,
+    mut resume: Option<ResumeState>,
+    stats_tracker: &mut stats::Tracker,
+    assets: &mut assets::Assets,
+) -> Result<()> {
+    let mut tweaks = assets.tweaks.clone().unwrap_or_default();
+    platform.adjust_tweaks(&mut tweaks);
+    let mut rng = match tweaks.rng_seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    log::info!("allocating buffers");
+    let mut virtual_canvas = platform.virtual_canvas_size().map(|size| {
+        log::info!("virtual canvas: {size}");
+        SliceFrameBufferBackend::new(size, Rgb565::BLACK)
+    });
+    let mut scene_manager = SceneManager::new(
+        assets
+            .transitions
+            .clone()
+            .unwrap_or_else(transition::defaults),
+        buffer.size,
+    );
+
+    // Of the background, from the first color of the theme to the second
+    const SHADES: usize = 32;
+    // Shaking the device adds rage that decays exponentially with this time constant
+    const RAGE_DECAY_SECS: f32 = 1.5;
+    const RAGE_MAX_GLITCHINESS: f32 = 48.0;
+    const POKE_RAGE: f32 = 0.5;
+    // Playback speed is 2^(level/2), so that 2 steps double it
+    const MAX_SPEED_LEVEL: i32 = 4;
+    const LED_BRIGHTNESS_STEP: f32 = 0.1;
+    let mut total_frames = tweaks.frames_per_shade * SHADES;
+    let mut exaggeration = exaggeration::Table::new(&tweaks, total_frames);
+    // Glitchiness grows by one with every frame past this point. Derived from the frame index
+    // instead of accumulated, so that scrubbing back actually calms things down.
+    let mut glitch_start_frame = exaggeration.glitch_start_frame();
+
+    let mut rage = 0f32;
+    let mut speed_level = 0;
+    let mut led_brightness_scale = 1f32;
+    let mut show_log_overlay = false;
+    let mut diagnostics = diagnostics::Overlay::default();
+    let mut frame_skipper = frame_skip::FrameSkipper::default();
+    let mut stats_shown_since: Option<Instant> = None;
+    let mut battery_monitor = battery::Monitor::default();
+    let mut temperature_monitor = temperature::Monitor::default();
+    let mut power_manager =
+        power::PowerManager::new(power::Config::from_build_env(platform.can_deep_sleep()));
+    let mut notifier =
+        webhooks::Notifier::from_build_env().context("Notifier::from_build_env failed")?;
+    let mut escalations = 0usize;
+    let mut demo = demo::requested();
+    let weather = assets.clock.as_ref().and_then(clock::Clock::start_weather);
+    let mut calendar = calendar::Triggers::new(assets.calendar.clone().unwrap_or_default());
+    let mut ghosting = ghosting::Ghosting::default();
+    let mut screen_shake = screen_shake::ScreenShake::default();
+    let mut errors_seen = log_buffer::errors();
+    // One for each line of text, so that lines that stay the same aren't rasterized every frame
+    let mut text_sprites: Vec<text_sprite::TextSprite> = Vec::new();
+    let mut damage = sprites::Damage::default();
+
+    'escalations: loop {
+        // These take over until turned off, the deadline passes, or office hours start.
+        // Skipping them lets one escalation through.
+        if resume.is_none() {
+            if calibration::is_testing() {
+                play_scene(
+                    platform,
+                    buffer,
+                    virtual_canvas.as_mut(),
+                    &mut calibration::TestPattern,
+                    &mut scene_manager,
+                    &mut rng,
+                    led_brightness_scale * battery_monitor.led_scale(),
+                )?;
+            }
+            if badge::is_enabled() {
+                play_scene(
+                    platform,
+                    buffer,
+                    virtual_canvas.as_mut(),
+                    &mut badge::BadgeScene,
+                    &mut scene_manager,
+                    &mut rng,
+                    led_brightness_scale * battery_monitor.led_scale(),
+                )?;
+            }
+            if mirror::is_live() {
+                play_scene(
+                    platform,
+                    buffer,
+                    virtual_canvas.as_mut(),
+                    &mut mirror::MirrorScene::new()?,
+                    &mut scene_manager,
+                    &mut rng,
+                    led_brightness_scale * battery_monitor.led_scale(),
+                )?;
+            }
+            if let Some(countdown) = assets.countdown.as_ref().filter(|c| c.is_pending()) {
+                play_scene(
+                    platform,
+                    buffer,
+                    virtual_canvas.as_mut(),
+                    &mut countdown.start()?,
+                    &mut scene_manager,
+                    &mut rng,
+                    led_brightness_scale * battery_monitor.led_scale(),
+                )?;
+            }
+            if let Some(clock) = assets
+                .clock
+                .as_ref()
+                .filter(|c| !demo && !c.is_office_hours())
+            {
+                play_scene(
+                    platform,
+                    buffer,
+                    virtual_canvas.as_mut(),
+                    &mut clock.start(weather.as_ref()),
+                    &mut scene_manager,
+                    &mut rng,
+                    led_brightness_scale * battery_monitor.led_scale(),
+                )?;
+            }
+        }
+
+        let ResumeState {
+            mut elapsed,
+            // Fractional, to allow playback speeds below 1 frame per frame
+            mut timeline_pos,
+        } = resume.take().unwrap_or(ResumeState {
+            elapsed: Duration::ZERO,
+            timeline_pos: 0.0,
+        });
+        let mut last_frame_time = frame_clock::now();
+        // Slept after the last frame on purpose, which the frame skipper has nothing to catch
+        // up on
+        let mut frame_delay = Duration::ZERO;
+        let mut paused = false;
+        // Gets enraged once halfway through, to show that off too
+        let mut demo_poked = false;
+        let mut badge_was_enabled = badge::is_enabled();
+        let mut lunch_trigger = lunch::Trigger::default();
+        // The fire starts right away
+        let release_day = calendar.on_escalation_start();
+        // Last one that wasn't off the charts, for bragging about in the success ending
+        let mut took = Duration::ZERO;
+        // Whatever was shown before has nothing to do with this escalation
+        ghosting.reset();
+        damage.invalidate();
+        if demo {
+            stats_shown_since = Some(frame_clock::now());
+        }
 
-    for line in 0..fb.height() {
-        let should_glitch = rng.next_u32() % 128 < 32;
-        if should_glitch {
-            let mut rand_idx = || rng.next_u32() as usize % fb.width();
-            let offset = (rand_idx() % max_offset) as i32 - (max_offset as i32 / 2);
-            let src = RowOffset::new(rand_idx(), fb).range_to(rand_idx());
-            let dst = src.offset(offset as _);
-
-            let row_index = line * fb.width();
-            if offset < 0 {
-                for (src, dst) in src.to_range().zip(dst.to_range()) {
-                    fb.data.set(row_index + dst, fb.data.get(row_index + src));
+        while (timeline_pos as usize) < total_frames {
+            if platform.exit_requested() {
+                return Ok(());
+            }
+            let mut new_tweaks = platform.poll_tweaks();
+            if platform.assets_changed() {
+                log::info!("storage changed, reloading assets");
+                *assets = assets::Assets::load(platform);
+                apply_config(assets);
+                // Saving config.toml takes over from whatever the tweak panel had
+                let mut reloaded = assets.tweaks.clone().unwrap_or_default();
+                platform.adjust_tweaks(&mut reloaded);
+                new_tweaks = Some(reloaded);
+            }
+            if let Some(new_tweaks) = new_tweaks {
+                if new_tweaks.rng_seed != tweaks.rng_seed {
+                    rng = match new_tweaks.rng_seed {
+                        Some(seed) => StdRng::seed_from_u64(seed),
+                        None => StdRng::from_entropy(),
+                    };
                 }
-            } else {
-                for (src, dst) in src.to_range().zip(dst.to_range()).rev() {
-                    fb.data.set(row_index + dst, fb.data.get(row_index + src));
+                let new_total_frames = new_tweaks.frames_per_shade * SHADES;
+                // Stay at the same point of the escalation
+                timeline_pos *= new_total_frames as f32 / total_frames as f32;
+                total_frames = new_total_frames;
+                exaggeration = exaggeration::Table::new(&new_tweaks, total_frames);
+                glitch_start_frame = exaggeration.glitch_start_frame();
+                tweaks = new_tweaks;
+            }
+            battery_monitor.poll(platform);
+            if battery_monitor.is_critical() {
+                stats_tracker.save(platform);
+                return battery::shut_down(platform);
+            }
+            temperature_monitor.poll(platform, tweaks.throttle_celsius);
+            // How far a single encoder detent / key press moves the timeline
+            let scrub_step_frames = tweaks.frames_per_shade as f32;
+
+            let mut go_to_lunch = false;
+            while let Some(event) = poll_input(platform)? {
+                power_manager.on_input(event);
+                go_to_lunch |= lunch_trigger.on_input(event);
+                match event {
+                    InputEvent::Scrub(steps) => {
+                        timeline_pos = (timeline_pos + steps as f32 * scrub_step_frames)
+                            .clamp(0.0, (total_frames - 1) as f32);
+                    }
+                    InputEvent::Seek(position) => {
+                        timeline_pos =
+                            (position * total_frames as f32).clamp(0.0, (total_frames - 1) as f32);
+                    }
+                    InputEvent::TogglePause => paused = !paused,
+                    InputEvent::Shake(strength) => rage = (rage + strength).min(1.0),
+                    InputEvent::Poke => rage = (rage + POKE_RAGE).min(1.0),
+                    InputEvent::Pet => {
+                        rage = 0.0;
+                        timeline_pos = (timeline_pos - scrub_step_frames).max(0.0);
+                    }
+                    // Escalation is the only scene there is, skip straight to the noise ending
+                    InputEvent::NextScene => timeline_pos = total_frames as f32,
+                    InputEvent::Speed(delta) => {
+                        speed_level =
+                            (speed_level + delta).clamp(-MAX_SPEED_LEVEL, MAX_SPEED_LEVEL);
+                        log::info!("speed: {}x", 2f32.powf(speed_level as f32 / 2.0));
+                    }
+                    InputEvent::Brightness(delta) => {
+                        led_brightness_scale = (led_brightness_scale
+                            + delta as f32 * LED_BRIGHTNESS_STEP)
+                            .clamp(LED_BRIGHTNESS_STEP, 1.0);
+                        log::info!("LED brightness: {led_brightness_scale}");
+                    }
+                    InputEvent::ToggleLogOverlay => show_log_overlay = !show_log_overlay,
+                    InputEvent::ToggleDiagnostics => diagnostics.toggle(),
+                    InputEvent::ToggleStats => {
+                        stats_shown_since = match stats_shown_since {
+                            Some(_) => None,
+                            None => Some(frame_clock::now()),
+                        }
+                    }
+                    // Handled by the power manager
+                    InputEvent::Sleep => {}
+                    InputEvent::DumpFrame => screenshot::request_dump(),
+                    InputEvent::ToggleDemo => {
+                        demo = !demo;
+                        log::info!("demo mode: {demo}");
+                    }
+                    InputEvent::ToggleBadge => badge::toggle(),
+                    InputEvent::Lunch => go_to_lunch = true,
                 }
             }
-        }
-    }
-}
+            if go_to_lunch {
+                play_scene(
+                    platform,
+                    buffer,
+                    virtual_canvas.as_mut(),
+                    &mut lunch::LunchScene,
+                    &mut scene_manager,
+                    &mut rng,
+                    led_brightness_scale * battery_monitor.led_scale(),
+                )?;
+                // Fresh build with the new target
+                continue 'escalations;
+            }
+            if calendar.friday_success_due() {
+                play_scene(
+                    platform,
+                    buffer,
+                    virtual_canvas.as_mut(),
+                    &mut success::SuccessScene::fake(elapsed),
+                    &mut scene_manager,
+                    &mut rng,
+                    led_brightness_scale * battery_monitor.led_scale(),
+                )?;
+                continue 'escalations;
+            }
+            // Turned on just now, from input or from the console/HTTP
+            let badge_enabled = badge::is_enabled();
+            if badge_enabled && !badge_was_enabled {
+                continue 'escalations;
+            }
+            badge_was_enabled = badge_enabled;
+            if calibration::is_testing() {
+                continue 'escalations;
+            }
+            if timeline_pos as usize >= total_frames {
+                break;
+            }
+            // Nobody would wake it up at a booth
+            if !demo && power_manager.should_sleep() {
+                stats_tracker.save(platform);
+                let state = ResumeState {
+                    elapsed,
+                    timeline_pos,
+                };
+                power_manager.sleep(platform, state)?;
+                // Time spent asleep doesn't count
+                last_frame_time = frame_clock::now();
+                damage.invalidate();
+                continue;
+            }
+            let stats_duration = if demo {
+                demo::STATS_DURATION
+            } else {
+                stats::SCENE_DURATION
+            };
+            if stats_shown_since.is_some_and(|t| frame_clock::elapsed(t) >= stats_duration) {
+                stats_shown_since = None;
+            }
+            if stats_shown_since.is_some() {
+                scene_manager.switch_to("stats", &buffer.pixels, &mut rng);
+                let size = buffer.size;
+                let mut framebuffer = FrameBuf::new(
+                    &mut *buffer,
+                    size.width.try_into()?,
+                    size.height.try_into()?,
+                );
+                stats::draw(
+                    &mut framebuffer,
+                    &stats_tracker.current(),
+                    temperature_monitor.is_throttled(),
+                )
+                .context("stats::draw failed")?;
+                platform.set_backlight(Brightness::from(1.0))?;
+                show_frame(platform, scene_manager.blend(&buffer.pixels, &mut rng))?;
+                platform.report_frame_stats(&FrameStats {
+                    scene: "stats",
+                    ..Default::default()
+                });
+                platform.sleep(Duration::from_millis(100));
+                // The escalation stays frozen in the meantime
+                last_frame_time = frame_clock::now();
+                damage.invalidate();
+                continue;
+            }
 
-fn draw_loop(platform: &mut impl Platform) -> Result<()> {
-    let mut rng = rand::thread_rng();
-    log::info!("allocating buffers");
-    let mut buffer = VecFrameBufferBackend::new(platform.lcd().bounding_box().size, Rgb565::BLACK);
+            let speed = 2f32.powf(speed_level as f32 / 2.0);
+            let now = frame_clock::now();
+            let frame_time = now - last_frame_time;
+            // Escalation advances by frame, so slow frames would slow it down
+            let skip_frame = frame_skipper.skip(
+                frame_time.saturating_sub(frame_delay),
+                tweaks.frame_budget(),
+            );
+            let mut stats = FrameStats {
+                scene: "escalation",
+                frame_time,
+                skipped_frames: frame_skipper.skipped(),
+                ..Default::default()
+            };
+            if !paused {
+                elapsed += frame_time.mul_f32(speed);
+            }
+            last_frame_time = now;
+            rage *= (-frame_time.as_secs_f32() / RAGE_DECAY_SECS).exp();
+            if demo && !demo_poked && timeline_pos >= total_frames as f32 / 2.0 {
+                demo_poked = true;
+                rage = 1.0;
+            }
 
-    let shades_of_red: Vec<Rgb565> = (0..32).map(|v| Rgb565::new(v, 0, 0)).collect();
-    const MAX_INTENSITY: i32 = 3;
-    const FRAMES_PER_SHADE: usize = 16;
-    const UNEXAGGERATED_TIME_FRAMES: usize = FRAMES_PER_SHADE * 8;
-    const EXAGGERATION_BASE: f64 = 1.01f64;
-    const EXAGGERATION_FACTOR: f64 = 1.4f64;
-    let total_frames: usize = FRAMES_PER_SHADE * shades_of_red.len();
+            let curr_frame = timeline_pos as usize;
 
-    loop {
-        let start_time = Instant::now();
-        let mut glitchiness = 0;
+            let idx = curr_frame / tweaks.frames_per_shade;
+            let frame = curr_frame % tweaks.frames_per_shade;
+            // Switched from the console at any time
+            let theme = theme::current();
+            let bgcolor = theme.background(idx, SHADES);
+            let progress = Intensity::new(timeline_pos / total_frames as f32);
+            // Whatever is puppeted over OSC wins
+            let overrides = osc::overrides();
+            let max_text_shake = tweaks.max_text_shake as f32;
+            // In pixels
+            let shake = match overrides.shake {
+                Some(shake) => shake * max_text_shake,
+                None => {
+                    tweaks.shake_curve.apply(progress).scale(max_text_shake) + rage * max_text_shake
+                }
+            };
+            let glitch_start = if release_day { 0 } else { glitch_start_frame };
+            // Frames from when glitching starts to the end, which a linear curve adds one
+            // glitchiness each
+            let glitch_frames = total_frames.saturating_sub(glitch_start).max(1);
+            let glitch_progress = Intensity::new(
+                (curr_frame + 1).saturating_sub(glitch_start) as f32 / glitch_frames as f32,
+            );
+            let glitchiness = match overrides.glitch {
+                Some(glitch) => (glitch * RAGE_MAX_GLITCHINESS) as usize,
+                None => {
+                    ((tweaks
+                        .glitch_curve
+                        .apply(glitch_progress)
+                        .scale(glitch_frames as f32)
+                        + rage * RAGE_MAX_GLITCHINESS)
+                        * temperature_monitor.glitch_scale()) as usize
+                }
+            };
+            let noise = match overrides.noise {
+                Some(noise) => Intensity::new(noise),
+                None => Intensity::new(tweaks.noise_curve.apply(progress).scale(tweaks.max_noise)),
+            };
+            // Bursts only ever make it worse
+            let burst = overrides.burst.unwrap_or(0.0);
+            let glitchiness = glitchiness.max((burst * RAGE_MAX_GLITCHINESS) as usize);
+            update_screen_shake(
+                &mut screen_shake,
+                &tweaks.screen_shake,
+                burst,
+                &mut errors_seen,
+                frame_time,
+            );
+            stats.glitchiness = glitchiness;
+            stats.progress = progress.get();
+            stats.rage = rage;
 
-        for (idx, &bgcolor) in shades_of_red.iter().enumerate() {
-            let curr_time = Instant::now();
-            let intensity = idx as i32 / (shades_of_red.len() as i32 / MAX_INTENSITY);
+            let shown_time = match exaggeration.at(curr_frame) {
+                Some(exaggeration) => {
+                    let shown_time = elapsed + Duration::from_secs_f32(exaggeration);
+                    stats.shown_time = Some(shown_time);
+                    took = shown_time;
+                    shown_time
+                }
+                // Off the charts
+                None => Duration::MAX,
+            };
+            let exaggerated_str = duration_fmt::format(shown_time, Style::Compact);
 
-            for frame in 0..FRAMES_PER_SHADE {
-                let curr_frame = idx * FRAMES_PER_SHADE + frame;
-                let exaggeration = if curr_frame < UNEXAGGERATED_TIME_FRAMES {
-                    0f64
-                } else {
-                    let v = curr_frame.saturating_sub(UNEXAGGERATED_TIME_FRAMES) as f64;
-                    EXAGGERATION_BASE.powf(v.powf(EXAGGERATION_FACTOR))
-                };
-                let exaggerated_str = if exaggeration < 1e15 {
-                    let exaggerated_time =
-                        (curr_time - start_time) + Duration::from_secs_f64(exaggeration);
-                    format_duration(exaggerated_time)
-                } else {
-                    glitchiness += 1;
-                    "9999999999999999999999999999".to_owned()
-                };
+            let channels = LedChannels {
+                // Brightness of real TFT LEDs is *very* non-linear. Event a tiny amount of
+                // PWM duty (that we map this brightness to) makes them shine relatively
+                // bright, and increasing that value has somewhat less noticeable effect.
+                progress: match rng.gen::<f32>() < rage {
+                    // Flicker wildly while enraged
+                    true => 1.0,
+                    false => tweaks.led_curve.apply(progress).get(),
+                },
+                // Fully glitched by the end of the escalation
+                glitchiness: (glitchiness as f32
+                    / total_frames.saturating_sub(glitch_start).max(1) as f32)
+                    .min(1.0),
+                rage,
+            };
+            let led_scale = led_brightness_scale * battery_monitor.led_scale();
+            let levels = led_levels(tweaks.led_roles, &channels);
+            let leds: [Brightness; 2] = std::array::from_fn(|i| {
+                overrides.leds[i].map_or(Brightness::from(levels[i] * led_scale), |led| {
+                    Brightness::from(led * led_brightness_scale)
+                })
+            });
+            set_leds(platform, leds)?;
+            let drive = backlight::Drive {
+                elapsed,
+                progress: progress.get(),
+                glitchiness,
+                burst,
+            };
+            platform.set_backlight(backlight::level(&tweaks.backlight, &drive, &mut rng))?;
 
-                let brightness = Brightness::from({
-                    let linear: f32 = curr_frame as f32 / total_frames as f32;
-                    // Brightness of real TFT LEDs is *very* non-linear. Event a tiny amount of
-                    // PWM duty (that we map this brightness to) makes them shine relatively
-                    // bright, and increasing that value has somewhat less noticeable effect.
-                    linear.powf(3.0)
-                });
-                platform.led0().set_brightness(brightness)?;
-                platform.led1().set_brightness(brightness)?;
-
-                let size = buffer.size.clone();
-                let mut framebuffer =
-                    FrameBuf::new(&mut buffer, size.width.try_into()?, size.height.try_into()?);
-
-                let lcd_center = platform.lcd().bounding_box().center();
-                framebuffer
-                    .clear(bgcolor)
-                    .context("DrawTarget::clear failed")?;
-                Text::with_alignment(
-                    &format!("{}\nAnalyzing Android.bp...", exaggerated_str),
-                    intensify(&mut rng, lcd_center, intensity),
-                    MonoTextStyle::new(&FONT_6X10, Rgb565::WHITE),
-                    Alignment::Center,
-                )
-                .draw(&mut framebuffer)
-                .context("Drawable::draw failed")?;
+            scene_manager.switch_to("escalation", &buffer.pixels, &mut rng);
+            let size = buffer.size.clone();
+            let mut framebuffer = FrameBuf::new(
+                &mut *buffer,
+                size.width.try_into()?,
+                size.height.try_into()?,
+            );
 
-                if glitchiness > 0 && frame / 4 % 2 == 0 {
-                    let pos =
-                        lcd_center - Rectangle::new(Point::zero(), dumpster_fire::size()).center();
-                    dumpster_fire::image_at(pos)?.draw(&mut framebuffer)?;
+            let layout = Layout::of(platform.lcd());
+            let lcd_center = layout.center();
+            diagnostics.update(platform);
+            let message = overrides
+                .message
+                .as_deref()
+                .unwrap_or(assets.message(escalations));
+            let soc_temp = temperature_monitor.simulated(stats.progress, rage);
+            let temperature = format!("SOC temp: {soc_temp:.0}°C (simulated)");
+            let values = message_layout::Values {
+                timer: &exaggerated_str,
+                message,
+                temperature: &temperature,
+            };
+            // Narrow panels get the gist
+            let gist = format!("SOC {soc_temp:.0}°C");
+            let text_lines = assets.text_lines();
+            let texts: Vec<(String, MonoTextStyle<'static, Rgb565>)> = text_lines
+                .iter()
+                .map(|line| {
+                    let color = line.color.map_or(theme.text(), scene::color_from_hex);
+                    let style = MonoTextStyle::new(line.font.mono(), color);
+                    let text = line.text(&values);
+                    let text = match layout.fits(&text, &style) {
+                        true => text,
+                        false => line.text(&message_layout::Values {
+                            temperature: &gist,
+                            ..values
+                        }),
+                    };
+                    (text, style)
+                })
+                .collect();
+            let mut layers = DisplayList::new(&assets.layers);
+            layers.push(
+                Sprite::new(sprites::BACKGROUND, |canvas, _ctx| {
+                    canvas.clear(bgcolor).context("DrawTarget::clear failed")?;
+                    Ok(canvas.bounding_box())
+                })
+                .key(sprites::key(bgcolor)),
+            );
+            // Separate sprites, so that a new timer value doesn't flush the other lines. They
+            // shake together, each as much as its config says.
+            // Positions in half pixels
+            let rows: Vec<_> = texts
+                .iter()
+                .map(|(text, style)| (text.split('\n').count(), style.font))
+                .collect();
+            let jitter = match tweaks.supersampled_text {
+                true => intensify(&mut rng, Point::zero(), (shake * 2.0) as i32),
+                false => intensify(&mut rng, Point::zero(), shake as i32),
+            };
+            text_sprites.resize_with(texts.len(), Default::default);
+            let positions = message_layout::baselines(lcd_center, &rows);
+            for (((line, (text, style)), position), sprite) in text_lines
+                .iter()
+                .zip(&texts)
+                .zip(positions)
+                .zip(&mut text_sprites)
+            {
+                if line.is_revealed(progress.get()) {
+                    let jitter = match tweaks.supersampled_text {
+                        true => line.jitter(jitter),
+                        false => line.jitter(jitter) * 2,
+                    };
+                    let position = position * 2 + jitter;
+                    let edge = theme.text_edge();
+                    layers.push(text_line(sprite, text, position, *style, edge, bgcolor));
                 }
+            }
+            layers.push(
+                Sprite::new(sprites::FIRE, |canvas, _ctx| match &assets.dumpster_fire {
+                    Some(image) => {
+                        let pos = layout.centered(image.size());
+                        theme::draw(&image.at(pos)?, canvas, theme.fire())?;
+                        Ok(Rectangle::new(pos, image.size()))
+                    }
+                    #[cfg(feature = "fire")]
+                    None => {
+                        let pos = layout.centered(dumpster_fire::size());
+                        theme::draw(&dumpster_fire::image_at(pos)?, canvas, theme.fire())?;
+                        Ok(Rectangle::new(pos, dumpster_fire::size()))
+                    }
+                    // Built without one, only a custom image from storage shows up
+                    #[cfg(not(feature = "fire"))]
+                    None => Ok(Rectangle::zero()),
+                })
+                .visible(glitchiness > 0 && frame / 4 % 2 == 0)
+                .key(sprites::key(theme.fire)),
+            );
+            layers.push(
+                Sprite::new(sprites::GLITCH, |canvas, ctx| {
+                    let glitch_region = match &assets.glitch_mask {
+                        Some(mask) => Region::Mask(mask),
+                        None if tweaks.glitch_spread => {
+                            // From the middle of the screen if the text is hidden
+                            let text_box = ctx
+                                .area(sprites::TEXT)
+                                .unwrap_or(Rectangle::new(lcd_center, Size::zero()));
+                            let spread = Intensity::new(
+                                curr_frame.saturating_sub(glitch_start) as f32
+                                    / total_frames.saturating_sub(glitch_start).max(1) as f32,
+                            );
+                            Region::Rect(region::grow(text_box, canvas.bounding_box(), spread))
+                        }
+                        None => Region::Full,
+                    };
+                    glitch_rows(
+                        canvas,
+                        ctx.rng,
+                        glitchiness,
+                        tweaks.glitch_probability,
+                        tweaks.glitch_wrap,
+                        &glitch_region,
+                    );
+                    Ok(canvas.bounding_box())
+                })
+                .visible(glitchiness > 0),
+            );
+            if noise > Intensity::ZERO {
+                layers.push(Sprite::new(sprites::NOISE, move |canvas, ctx| {
+                    NoiseEffect::new(Flavor::Color, noise)
+                        .with_palette(theme.noise())
+                        .apply(canvas, ctx.rng, &Region::Full);
+                    Ok(canvas.bounding_box())
+                }));
+            }
+            // Too expensive when throttled, and stale by the time it cools down
+            let throttled = temperature_monitor.is_throttled();
+            if throttled {
+                ghosting.reset();
+            }
+            // Builds up along with everything else
+            layers.push(
+                Sprite::new(sprites::GHOSTING, |canvas, _ctx| {
+                    let persistence = Intensity::new(tweaks.ghosting * progress.get());
+                    ghosting.apply(&mut canvas.data.pixels, persistence);
+                    Ok(ghosted_area(canvas, persistence))
+                })
+                .visible(!throttled),
+            );
+            layers.push(screen_shake_sprite(&mut screen_shake));
+            push_overlays(
+                &mut layers,
+                battery_monitor.charge(),
+                show_log_overlay,
+                &diagnostics,
+                &stats,
+            );
+            let took = layers.draw(&mut framebuffer, &mut rng, &mut damage)?;
+            stats.clear = took.of(&[sprites::BACKGROUND]);
+            stats.text = took.of(&[sprites::TEXT, sprites::FIRE]);
+            stats.effects = took.of(&[
+                sprites::GLITCH,
+                sprites::NOISE,
+                sprites::GHOSTING,
+                sprites::SHAKE,
+                sprites::BATTERY,
+                sprites::LOG,
+                sprites::DIAGNOSTICS,
+            ]);
 
-                glitch(&mut framebuffer, &mut rng, glitchiness as usize);
+            let t = Instant::now();
+            if !skip_frame {
+                // Blended frames change all over
+                if scene_manager.is_blending() {
+                    damage.invalidate();
+                }
+                let dirty = damage.take(Rectangle::new(Point::zero(), buffer.size));
+                show_frame_area(
+                    platform,
+                    scene_manager.blend(&buffer.pixels, &mut rng),
+                    Some(dirty),
+                )?;
+                if let Some(eyes_display) = platform.display(DisplayId::EYES) {
+                    eyes::draw(eyes_display, leds, glitchiness, &mut rng)
+                        .map_err(|e| platform.display_error(e))
+                        .context("drawing eyes failed")?;
+                }
+            }
+            stats.flush = t.elapsed();
+            platform.report_frame_stats(&stats);
+            notifier.on_frame(&stats);
+            stats_tracker.on_frame(glitchiness);
+            stats_tracker.maybe_save(platform);
 
-                let bb = platform.lcd().bounding_box();
-                platform
-                    .lcd()
-                    .fill_contiguous(&bb, buffer.pixels.iter().copied())
-                    .map_err(|_| anyhow::Error::msg("DrawTarget::fill_contiguous failed"))?;
+            frame_delay = temperature_monitor.frame_delay();
+            platform.sleep(frame_delay);
 
-                platform.sleep(Duration::from_millis(10));
+            if !paused {
+                timeline_pos += if demo {
+                    total_frames as f32 * frame_time.as_secs_f32()
+                        / demo::ESCALATION_DURATION.as_secs_f32()
+                } else {
+                    speed
+                };
             }
         }
 
-        for _ in 0..FRAMES_PER_SHADE {
+        stats_tracker.on_escalation_done();
+        notifier.on_escalation_done();
+        let scene_index = escalations;
+        escalations += 1;
+        // False hope, every once in a blue moon
+        let succeeded = rng.gen::<f32>() < tweaks.success_probability;
+        if succeeded {
+            log::info!(
+                "the build succeeded! took {}",
+                duration_fmt::format(took, Style::Verbose)
+            );
+            play_scene(
+                platform,
+                buffer,
+                virtual_canvas.as_mut(),
+                &mut success::SuccessScene::real(took),
+                &mut scene_manager,
+                &mut rng,
+                led_brightness_scale * battery_monitor.led_scale(),
+            )?;
+        }
+        let noise = tweaks
+            .noise
+            .choose(&mut rng)
+            .map(|noise| noise.with_palette(theme::current().noise()));
+        let noise_frames = if succeeded {
+            0
+        } else {
+            tweaks.frames_per_shade
+        };
+        for _ in 0..noise_frames {
+            if platform.exit_requested() {
+                return Ok(());
+            }
+            let now = frame_clock::now();
+            let frame_time = now - last_frame_time;
+            // Noise lasts a number of frames too
+            let skip_frame = frame_skipper.skip(
+                frame_time.saturating_sub(frame_delay),
+                tweaks.frame_budget(),
+            );
+            let mut stats = FrameStats {
+                scene: "noise",
+                progress: 1.0,
+                frame_time,
+                skipped_frames: frame_skipper.skipped(),
+                ..Default::default()
+            };
+            last_frame_time = now;
+            let burst = osc::overrides().burst.unwrap_or(0.0);
+            update_screen_shake(
+                &mut screen_shake,
+                &tweaks.screen_shake,
+                burst,
+                &mut errors_seen,
+                frame_time,
+            );
+            // Still the climax
+            let drive = backlight::Drive {
+                elapsed,
+                progress: 1.0,
+                glitchiness: 1,
+                burst,
+            };
+            platform.set_backlight(backlight::level(&tweaks.backlight, &drive, &mut rng))?;
+
+            scene_manager.switch_to("noise", &buffer.pixels, &mut rng);
             let size = buffer.size.clone();
-            let mut framebuffer =
-                FrameBuf::new(&mut buffer, size.width.try_into()?, size.height.try_into()?);
-            add_noise(&mut framebuffer, &mut rng, Intensity::MAX);
+            let mut framebuffer = FrameBuf::new(
+                &mut *buffer,
+                size.width.try_into()?,
+                size.height.try_into()?,
+            );
+            diagnostics.update(platform);
+            let mut layers = DisplayList::new(&assets.layers);
+            if let Some(noise) = &noise {
+                layers.push(Sprite::new(sprites::NOISE, |canvas, ctx| {
+                    noise.apply(canvas, ctx.rng, &Region::Full);
+                    Ok(canvas.bounding_box())
+                }));
+            }
+            let throttled = temperature_monitor.is_throttled();
+            if throttled {
+                ghosting.reset();
+            }
+            layers.push(
+                Sprite::new(sprites::GHOSTING, |canvas, _ctx| {
+                    let persistence = Intensity::new(tweaks.ghosting);
+                    ghosting.apply(&mut canvas.data.pixels, persistence);
+                    Ok(ghosted_area(canvas, persistence))
+                })
+                .visible(!throttled),
+            );
+            layers.push(screen_shake_sprite(&mut screen_shake));
+            push_overlays(
+                &mut layers,
+                battery_monitor.charge(),
+                show_log_overlay,
+                &diagnostics,
+                &stats,
+            );
+            stats.effects = layers
+                .draw(&mut framebuffer, &mut rng, &mut damage)?
+                .of(sprites::ALL);
 
-            let bb = platform.lcd().bounding_box();
-            platform
-                .lcd()
-                .fill_contiguous(&bb, buffer.pixels.iter().copied())
-                .map_err(|_| anyhow::Error::msg("DrawTarget::fill_contiguous failed"))?;
+            let t = Instant::now();
+            if !skip_frame {
+                // Blended frames change all over
+                if scene_manager.is_blending() {
+                    damage.invalidate();
+                }
+                let dirty = damage.take(Rectangle::new(Point::zero(), buffer.size));
+                show_frame_area(
+                    platform,
+                    scene_manager.blend(&buffer.pixels, &mut rng),
+                    Some(dirty),
+                )?;
+            }
+            stats.flush = t.elapsed();
+            platform.report_frame_stats(&stats);
+            stats_tracker.on_frame(0);
+            stats_tracker.maybe_save(platform);
 
-            platform.sleep(Duration::from_millis(10));
+            frame_delay = temperature_monitor.frame_delay();
+            platform.sleep(frame_delay);
         }
+
+        // All of them in demo mode, even the built-in ones if nothing else is configured
+        let scenes = match (demo, assets.scenes.is_empty()) {
+            (true, true) => &assets.builtin_scenes[..],
+            (true, false) => &assets.scenes[..],
+            (false, true) => &[],
+            (false, false) => {
+                std::slice::from_ref(&assets.scenes[scene_index % assets.scenes.len()])
+            }
+        };
+        for source in scenes {
+            play_scene(
+                platform,
+                buffer,
+                virtual_canvas.as_mut(),
+                &mut *source.start(),
+                &mut scene_manager,
+                &mut rng,
+                led_brightness_scale * battery_monitor.led_scale(),
+            )?;
+        }
+        platform.cycle_done();
     }
 }
 
-fn main() {
-    #[cfg(target_arch = "xtensa")]
-    let mut platform = platform::new_esp32().expect("platform::new_esp32 failed");
-    #[cfg(target_os = "linux")]
-    let mut platform = platform::new_pc().expect("platform::new_pc failed");
+/// Hands the config sections that modules keep for themselves over to them
+fn apply_config(assets: &assets::Assets) {
+    badge::init(assets.badge.as_ref());
+    theme::init(assets.theme.as_ref());
+    calibration::init(assets.calibration.as_ref());
+    brightness_cap::init(assets.brightness.as_ref());
+    wall_clock::set_utc_offset_minutes(assets.utc_offset_minutes);
+}
 
-    loop {
-        match draw_loop(&mut platform) {
+/// OSC, Art-Net and mirroring, each on its own thread
+#[cfg(feature = "net")]
+fn spawn_listeners(lcd_size: Size) {
+    if let Err(e) = osc::spawn() {
+        log::error!("osc::spawn failed: {e:?}");
+    }
+    if let Err(e) = artnet::spawn() {
+        log::error!("artnet::spawn failed: {e:?}");
+    }
+    if let Err(e) = mirror::spawn(lcd_size) {
+        log::error!("mirror::spawn failed: {e:?}");
+    }
+}
+
+fn run(mut platform: impl Platform) {
+    // SAFETY: platform is never moved, and gets dropped only when this function returns.
+    // Platforms that keep a pointer to it never request an exit.
+    unsafe { platform.install_panic_screen() };
+
+    let mut stats_tracker = stats::Tracker::load(&mut platform);
+    let mut assets = assets::Assets::load(&mut platform);
+    apply_config(&assets);
+    calibration::restore(&mut platform);
+    #[cfg(feature = "net")]
+    spawn_listeners(platform.lcd().bounding_box().size);
+    let mut buffer = frame_buffer(&mut platform);
+    // Taken once, so that restarting draw_loop after an error is neither a wake-up nor a boot
+    let mut resume = platform.resume_state();
+    // Waking up from deep sleep is not a boot, as far as anyone watching can tell
+    if resume.is_none() {
+        if let Err(e) = show_boot_splash(&mut platform, &mut buffer, &assets) {
+            log::error!("show_boot_splash failed: {e:?}");
+        }
+    }
+    while !platform.exit_requested() {
+        let resume = resume.take();
+        match draw_loop(
+            &mut platform,
+            &mut buffer,
+            resume,
+            &mut stats_tracker,
+            &mut assets,
+        ) {
+            Ok(_) if platform.exit_requested() => {}
             Ok(_) => log::warn!("draw_loop exited with success (?)"),
             Err(e) => log::error!("draw_loop exited with error: {:?}", e),
         }
     }
+    stats_tracker.save(&mut platform);
+    log::info!("exiting");
+}
+
+/// Runs one of the tools for the host instead of the android
+#[cfg(target_os = "linux")]
+fn run_companion(tool: impl FnOnce() -> Result<()>) {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    if let Err(e) = tool() {
+        log::error!("{e:#}");
+        std::process::exit(1);
+    }
+}
+
+fn main() {
+    #[cfg(target_os = "espidf")]
+    run(platform::new_esp32().expect("platform::new_esp32 failed"));
+
+    #[cfg(target_os = "linux")]
+    {
+        use platform::LinuxBackend;
+
+        if let Some(path) = choreography::requested() {
+            return run_companion(|| choreography::run(&path));
+        }
+        if capture::requested() {
+            return run_companion(capture::run);
+        }
+        if serial_host::requested() {
+            return run_companion(serial_host::run);
+        }
+
+        match LinuxBackend::select().expect("LinuxBackend::select failed") {
+            LinuxBackend::Window => run(platform::new_pc().expect("platform::new_pc failed")),
+            LinuxBackend::Term => run(platform::new_term().expect("platform::new_term failed")),
+            LinuxBackend::Fbdev => run(platform::new_fbdev().expect("platform::new_fbdev failed")),
+            LinuxBackend::Spi => {
+                run(platform::new_linux_spi().expect("platform::new_linux_spi failed"))
+            }
+            LinuxBackend::Video => run(platform::new_video().expect("platform::new_video failed")),
+            #[cfg(feature = "eg-simulator")]
+            LinuxBackend::EgSimulator => {
+                run(platform::new_eg_simulator().expect("platform::new_eg_simulator failed"))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::testing::MockPlatform;
+
+    /// Until the platform asks to exit, which a fresh one does after the first escalation
+    fn run_draw_loop(platform: &mut MockPlatform) {
+        let mut stats_tracker = stats::Tracker::load(platform);
+        let mut assets = assets::Assets::load(platform);
+        let mut buffer = frame_buffer(platform);
+        show_boot_splash(platform, &mut buffer, &assets).unwrap();
+        draw_loop(platform, &mut buffer, None, &mut stats_tracker, &mut assets).unwrap();
+    }
+
+    #[test]
+    fn boots_into_the_splash() {
+        let mut platform = MockPlatform::new(Size::new(160, 128));
+        platform.run_for(Duration::from_secs(1));
+        run_draw_loop(&mut platform);
+        // Color bars, 20 pixels wide
+        assert_eq!(platform.lcd.pixel(Point::new(10, 10)), Rgb565::WHITE);
+        assert_eq!(platform.lcd.pixel(Point::new(110, 10)), Rgb565::RED);
+    }
+
+    #[test]
+    fn leds_brighten_along_with_the_escalation() {
+        let mut platform = MockPlatform::new(Size::new(160, 128));
+        platform.tweaks.frames_per_shade = 2;
+        run_draw_loop(&mut platform);
+        // The boot splash sweeps them on its own
+        let levels: Vec<f32> = platform.led0.calls_since(boot_splash::DURATION).collect();
+        // 2 frames for each of the 32 shades
+        assert!(levels.len() >= 64);
+        assert!(
+            levels.windows(2).all(|pair| pair[0] <= pair[1]),
+            "{levels:?}"
+        );
+        assert!(platform.led0.brightness() > 0.9);
+        let right: Vec<f32> = platform.led1.calls_since(boot_splash::DURATION).collect();
+        assert_eq!(levels, right);
+    }
+
+    #[test]
+    fn glitches_start_once_the_timer_is_off_the_charts() {
+        let mut platform = MockPlatform::new(Size::new(160, 128));
+        platform.tweaks.frames_per_shade = 2;
+        // Off the charts a few frames after the unexaggerated shades
+        platform.tweaks.exaggeration_base = 10.0;
+        run_draw_loop(&mut platform);
+        let frames = &platform.escalation;
+        assert!(frames.len() >= 64);
+        for pair in frames.windows(2) {
+            assert!(pair[0].progress <= pair[1].progress, "{pair:?}");
+            assert!(pair[0].glitchiness <= pair[1].glitchiness, "{pair:?}");
+            // Never back on them
+            assert!(pair[0].shown_time.is_some() || pair[1].shown_time.is_none());
+        }
+        let on_the_charts = frames.iter().take_while(|f| f.shown_time.is_some());
+        assert!(on_the_charts.clone().count() >= 16);
+        assert!(on_the_charts.clone().all(|f| f.glitchiness == 0));
+        let last = frames.last().unwrap();
+        assert!(last.shown_time.is_none() && last.glitchiness > 0);
+        assert!(last.progress > 0.9);
+    }
+
+    #[test]
+    fn timer_follows_virtual_time() {
+        let mut platform = MockPlatform::new(Size::new(160, 128));
+        // Nowhere near the end after 5 minutes
+        platform.tweaks.frames_per_shade = 1000;
+        platform.frame_time = Duration::from_secs(1);
+        platform.run_for(boot_splash::DURATION + Duration::from_secs(5 * 60));
+        run_draw_loop(&mut platform);
+        let last = platform.escalation.last().unwrap();
+        // Exaggerated, but never less than the time that went by
+        let shown = last.shown_time.unwrap();
+        assert!(shown >= Duration::from_secs(4 * 60), "{shown:?}");
+        assert!(last.progress < 0.1, "{last:?}");
+        // The background is still dark, and the screen nowhere near the boot splash
+        let background = platform.lcd.pixel(Point::zero());
+        assert!(background.r() < 8, "{background:?}");
+    }
+
+    #[test]
+    fn pokes_make_the_leds_flicker() {
+        let mut platform = MockPlatform::new(Size::new(160, 128));
+        // Early on, while they're still dark
+        platform.run_for(boot_splash::DURATION + Duration::from_millis(500));
+        for _ in 0..2 {
+            platform.input(InputEvent::Poke);
+        }
+        run_draw_loop(&mut platform);
+        let levels: Vec<f32> = platform.led0.calls_since(boot_splash::DURATION).collect();
+        assert!(levels.contains(&1.0), "{levels:?}");
+    }
+
+    fn parse_panics(s: &str) -> bool {
+        std::panic::catch_unwind(|| parse_usize(s)).is_err()
+    }
+
+    proptest! {
+        #[test]
+        fn parse_usize_round_trips(value in any::<usize>()) {
+            prop_assert_eq!(parse_usize(&value.to_string()), value);
+        }
+
+        #[test]
+        fn parse_usize_accepts_leading_zeros(value in any::<u32>(), zeros in 1..8usize) {
+            let s = format!("{}{value}", "0".repeat(zeros));
+            prop_assert_eq!(parse_usize(&s), value as usize);
+        }
+
+        #[test]
+        fn parse_usize_rejects_overflow(value in (usize::MAX as u128 + 1)..=u128::MAX) {
+            prop_assert!(parse_panics(&value.to_string()));
+        }
+
+        #[test]
+        fn parse_usize_rejects_anything_but_digits(s in "[0-9]*[^0-9][0-9]*") {
+            prop_assert!(parse_panics(&s));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "empty string")]
+    fn parse_usize_rejects_empty_string() {
+        parse_usize("");
+    }
 }
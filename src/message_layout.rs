@@ -0,0 +1,195 @@
+//! Lines of text over the escalation, each with its own font, color, shake and entrance
+
+use std::borrow::Cow;
+
+use anyhow::{bail, Result};
+use embedded_graphics::{
+    geometry::Point,
+    // Latin-1 rather than ASCII, for the degree sign
+    mono_font::{
+        iso_8859_1::{FONT_10X20, FONT_4X6, FONT_6X10},
+        MonoFont,
+    },
+};
+use serde::Deserialize;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize)]
+pub enum Font {
+    #[serde(rename = "4x6")]
+    Small,
+    #[default]
+    #[serde(rename = "6x10")]
+    Normal,
+    #[serde(rename = "10x20")]
+    Large,
+}
+
+impl Font {
+    pub fn mono(self) -> &'static MonoFont<'static> {
+        match self {
+            Font::Small => &FONT_4X6,
+            Font::Normal => &FONT_6X10,
+            Font::Large => &FONT_10X20,
+        }
+    }
+}
+
+/// `[[text]]` in config.toml, one for each line, top to bottom
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Line {
+    /// `{timer}`, `{message}` and `{temperature}` get replaced with what they say. The message
+    /// may take more than one row.
+    pub text: Cow<'static, str>,
+    pub font: Font,
+    /// 0xRRGGBB, None takes the text color of the theme
+    pub color: Option<u32>,
+    /// Fraction of the text shake the line gets, 0 keeps it steady
+    pub shake: f32,
+    /// Escalation progress, 0..1, at which the line shows up. Its space is kept until then.
+    pub reveal: f32,
+}
+
+impl Default for Line {
+    fn default() -> Self {
+        Self::plain("")
+    }
+}
+
+/// Timer, message and temperature, all alike
+pub const DEFAULT: &[Line] = &[
+    Line::plain("{timer}"),
+    Line::plain("{message}"),
+    Line::plain("{temperature}"),
+];
+
+/// What the placeholders stand for in this frame
+#[derive(Clone, Copy)]
+pub struct Values<'a> {
+    pub timer: &'a str,
+    pub message: &'a str,
+    pub temperature: &'a str,
+}
+
+impl Line {
+    const fn plain(text: &'static str) -> Self {
+        Self {
+            text: Cow::Borrowed(text),
+            font: Font::Normal,
+            color: None,
+            shake: 1.0,
+            reveal: 0.0,
+        }
+    }
+
+    pub fn text(&self, values: &Values) -> String {
+        self.text
+            .replace("{timer}", values.timer)
+            .replace("{message}", values.message)
+            .replace("{temperature}", values.temperature)
+    }
+
+    pub fn is_revealed(&self, progress: f32) -> bool {
+        progress >= self.reveal
+    }
+
+    /// `offset` of the whole text, scaled down for this line
+    pub fn jitter(&self, offset: Point) -> Point {
+        let scale = |v: i32| (v as f32 * self.shake).round() as i32;
+        Point::new(scale(offset.x), scale(offset.y))
+    }
+}
+
+pub fn validate(lines: &[Line]) -> Result<()> {
+    for line in lines {
+        if !(0.0..=1.0).contains(&line.reveal) {
+            bail!("{:?}: reveal must be between 0 and 1", line.text);
+        }
+        if line.shake < 0.0 {
+            bail!("{:?}: shake can't be negative", line.text);
+        }
+    }
+    Ok(())
+}
+
+/// Where the baseline of each of `lines` goes, `(rows, font)` each, for all of them to be
+/// vertically centered on `center`. Like `layout::text_block`, but with a font per line.
+pub fn baselines(center: Point, lines: &[(usize, &MonoFont)]) -> Vec<Point> {
+    let heights = lines
+        .iter()
+        .map(|(rows, font)| font.character_size.height as i32 * (*rows).max(1) as i32);
+    let mut top = center.y - heights.clone().sum::<i32>() / 2;
+    lines
+        .iter()
+        .zip(heights)
+        .map(|((_, font), height)| {
+            let baseline = Point::new(center.x, top + font.baseline as i32);
+            top += height;
+            baseline
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_graphics::{
+        geometry::Dimensions,
+        mono_font::MonoTextStyle,
+        pixelcolor::BinaryColor,
+        text::{Alignment, Text},
+    };
+
+    use super::*;
+    use crate::layout;
+
+    #[test]
+    fn lays_out_lines_in_their_own_fonts() {
+        let values = Values {
+            timer: "1m 2s",
+            message: "Analyzing\nAndroid.bp",
+            temperature: "SOC 45°C",
+        };
+        let texts: Vec<String> = DEFAULT.iter().map(|line| line.text(&values)).collect();
+        assert_eq!(texts, ["1m 2s", "Analyzing\nAndroid.bp", "SOC 45°C"]);
+
+        // Same as one block when all fonts are the same
+        let center = Point::new(80, 64);
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        let rows = [(1, &FONT_6X10), (2, &FONT_6X10), (1, &FONT_6X10)];
+        assert_eq!(
+            baselines(center, &rows)[0],
+            layout::text_block(center, 4, &style)
+        );
+
+        // Mixed ones stack without overlapping, centered together
+        let rows = [(1, &FONT_10X20), (2, &FONT_6X10), (1, &FONT_4X6)];
+        let boxes: Vec<_> = ["12:34", "two\nrows", "tiny"]
+            .iter()
+            .zip(baselines(center, &rows))
+            .zip(rows)
+            .map(|((text, position), (_, font))| {
+                let style = MonoTextStyle::new(font, BinaryColor::On);
+                Text::with_alignment(text, position, style, Alignment::Center).bounding_box()
+            })
+            .collect();
+        for pair in boxes.windows(2) {
+            assert!(
+                pair[0].bottom_right().unwrap().y < pair[1].top_left.y,
+                "{boxes:?}"
+            );
+        }
+        let (top, bottom) = (boxes[0].top_left.y, boxes[2].bottom_right().unwrap().y);
+        assert!(((top + bottom) / 2 - center.y).abs() <= 1, "{boxes:?}");
+
+        let steady = Line {
+            shake: 0.0,
+            ..Line::default()
+        };
+        assert_eq!(steady.jitter(Point::new(5, -3)), Point::zero());
+        assert!(validate(&[Line {
+            reveal: 1.5,
+            ..Line::default()
+        }])
+        .is_err());
+    }
+}
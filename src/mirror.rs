@@ -0,0 +1,225 @@
+use std::{
+    io::Read,
+    net::TcpListener,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::{Dimensions, Point, Size},
+    image::{Image, ImageRaw},
+    pixelcolor::Rgb565,
+    prelude::RgbColor,
+    Drawable,
+};
+
+use crate::scene::{Canvas, Scene, SceneContext};
+
+// Read at build time, so that they work on the ESP32 too
+const PORT: Option<&str> = option_env!("EVIL_ANDROID_MIRROR_PORT");
+const SHOW_SECS: Option<&str> = option_env!("EVIL_ANDROID_MIRROR_SECS");
+const DEFAULT_SHOW_SECS: u64 = 20;
+
+pub const MAGIC: &[u8; 4] = b"EVMF";
+/// Magic, then width and height as big-endian u16
+pub const HEADER_SIZE: usize = 8;
+/// A stream that went quiet for that long is over
+const STALE_AFTER: Duration = Duration::from_secs(3);
+
+struct Frame {
+    size: Size,
+    /// Big-endian Rgb565
+    pixels: Vec<u8>,
+    received: Instant,
+}
+
+// Set from the mirror thread
+static FRAME: Mutex<Option<Frame>> = Mutex::new(None);
+
+/// Starts every frame of a mirror stream, followed by big-endian Rgb565 pixels
+pub fn header(size: Size) -> [u8; HEADER_SIZE] {
+    let mut out = [0; HEADER_SIZE];
+    out[..4].copy_from_slice(MAGIC);
+    out[4..6].copy_from_slice(&(size.width as u16).to_be_bytes());
+    out[6..].copy_from_slice(&(size.height as u16).to_be_bytes());
+    out
+}
+
+/// Reads the next frame of a stream into `pixels`, returns its size. Frames bigger than
+/// `max_size`, that of the LCD, are rejected rather than allocated for.
+fn read_frame(stream: &mut impl Read, pixels: &mut Vec<u8>, max_size: Size) -> Result<Size> {
+    let mut header = [0; HEADER_SIZE];
+    stream.read_exact(&mut header)?;
+    if !header.starts_with(MAGIC) {
+        bail!("not a mirror stream");
+    }
+    let width = u16::from_be_bytes([header[4], header[5]]);
+    let height = u16::from_be_bytes([header[6], header[7]]);
+    let len = usize::from(width) * usize::from(height);
+    if len == 0 || u32::from(width) > max_size.width || u32::from(height) > max_size.height {
+        bail!("invalid frame size: {width}x{height}");
+    }
+    pixels.resize(len * 2, 0);
+    stream.read_exact(pixels)?;
+    Ok(Size::new(width.into(), height.into()))
+}
+
+/// Shows `pixels`, big-endian Rgb565, from now on. Leaves the previously shown ones in
+/// `pixels`, so that the next frame can reuse them.
+pub fn set_frame(size: Size, pixels: &mut Vec<u8>) {
+    let mut frame = FRAME.lock().unwrap();
+    let frame = frame.get_or_insert_with(|| Frame {
+        size,
+        pixels: Vec::new(),
+        received: Instant::now(),
+    });
+    frame.size = size;
+    frame.received = Instant::now();
+    std::mem::swap(&mut frame.pixels, pixels);
+}
+
+/// Whether someone is streaming their screen right now
+pub fn is_live() -> bool {
+    FRAME
+        .lock()
+        .unwrap()
+        .as_ref()
+        .is_some_and(|frame| frame.received.elapsed() < STALE_AFTER)
+}
+
+/// Accepts screen mirror streams on EVIL_ANDROID_MIRROR_PORT, if set at build time. One
+/// stream at a time, the next one waits until it's over. Frames up to `lcd_size`.
+pub fn spawn(lcd_size: Size) -> Result<()> {
+    let Some(port) = PORT else {
+        return Ok(());
+    };
+    let port: u16 = port
+        .parse()
+        .with_context(|| format!("invalid EVIL_ANDROID_MIRROR_PORT: {port}"))?;
+    let listener = TcpListener::bind(("0.0.0.0", port)).context("TcpListener::bind failed")?;
+    log::info!("accepting screen mirrors on TCP port {port}");
+    std::thread::Builder::new()
+        .name("mirror".to_owned())
+        .stack_size(8192)
+        .spawn(move || {
+            // Swapped with the shown frame by `set_frame`
+            let mut pixels = Vec::new();
+            for stream in listener.incoming() {
+                let mut stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        log::warn!("mirror: accept failed: {e}");
+                        continue;
+                    }
+                };
+                log::info!("mirror: streaming from {:?}", stream.peer_addr());
+                // Stale frames are never shown, a stuck sender shouldn't block others forever
+                if let Err(e) = stream.set_read_timeout(Some(STALE_AFTER)) {
+                    log::warn!("mirror: {e}");
+                }
+                loop {
+                    match read_frame(&mut stream, &mut pixels, lcd_size) {
+                        Ok(size) => set_frame(size, &mut pixels),
+                        Err(e) => {
+                            log::info!("mirror: stream over: {e:#}");
+                            break;
+                        }
+                    }
+                }
+            }
+        })?;
+    Ok(())
+}
+
+/// Whatever is being streamed, centered, for EVIL_ANDROID_MIRROR_SECS or until the stream
+/// stops
+pub struct MirrorScene {
+    show_for: Duration,
+}
+
+impl MirrorScene {
+    pub fn new() -> Result<Self> {
+        let secs = match SHOW_SECS {
+            None => DEFAULT_SHOW_SECS,
+            Some(secs) => secs
+                .parse()
+                .with_context(|| format!("invalid EVIL_ANDROID_MIRROR_SECS: {secs}"))?,
+        };
+        Ok(Self {
+            show_for: Duration::from_secs(secs),
+        })
+    }
+}
+
+impl Scene for MirrorScene {
+    fn name(&self) -> &'static str {
+        "mirror"
+    }
+
+    fn draw(&mut self, t: Duration, canvas: &mut Canvas, _ctx: &mut SceneContext) -> Result<bool> {
+        let frame = FRAME.lock().unwrap();
+        let Some(frame) = frame
+            .as_ref()
+            .filter(|frame| t < self.show_for && frame.received.elapsed() < STALE_AFTER)
+        else {
+            return Ok(false);
+        };
+        let canvas_size = canvas.bounding_box().size;
+        let top_left = Point::new(
+            (canvas_size.width as i32 - frame.size.width as i32) / 2,
+            (canvas_size.height as i32 - frame.size.height as i32) / 2,
+        );
+        canvas.clear(Rgb565::BLACK)?;
+        Image::new(
+            &ImageRaw::<Rgb565>::new(&frame.pixels, frame.size.width),
+            top_left,
+        )
+        .draw(canvas)?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LCD: Size = Size::new(160, 128);
+
+    #[test]
+    fn reads_frames() {
+        let mut stream = header(Size::new(2, 1)).to_vec();
+        stream.extend([0xf8, 0x00, 0x00, 0x1f]);
+        stream.extend(header(Size::new(1, 1)));
+        stream.extend([0x07, 0xe0]);
+        let mut stream = &stream[..];
+        let mut pixels = Vec::new();
+        assert_eq!(
+            read_frame(&mut stream, &mut pixels, LCD).unwrap(),
+            Size::new(2, 1)
+        );
+        assert_eq!(pixels, [0xf8, 0x00, 0x00, 0x1f]);
+        assert_eq!(
+            read_frame(&mut stream, &mut pixels, LCD).unwrap(),
+            Size::new(1, 1)
+        );
+        assert_eq!(pixels, [0x07, 0xe0]);
+        assert!(read_frame(&mut stream, &mut pixels, LCD).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_frames() {
+        let mut pixels = Vec::new();
+        let mut wrong_magic = &b"EVMX\0\x01\0\x01\0\0"[..];
+        assert!(read_frame(&mut wrong_magic, &mut pixels, LCD).is_err());
+        let empty = header(Size::new(0, 128));
+        assert!(read_frame(&mut &empty[..], &mut pixels, LCD).is_err());
+        // Wider than the LCD, even if it's few pixels
+        let wide = header(Size::new(161, 1));
+        assert!(read_frame(&mut &wide[..], &mut pixels, LCD).is_err());
+        let mut truncated = header(Size::new(2, 2)).to_vec();
+        truncated.extend([0; 7]);
+        assert!(read_frame(&mut &truncated[..], &mut pixels, LCD).is_err());
+    }
+}
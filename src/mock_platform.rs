@@ -0,0 +1,211 @@
+//! A platform for unit tests: in-memory LCD, recorded LED calls and virtual time, so that
+//! scenes and the escalation render the same way on every run. The LCD is the library's
+//! `testing::MockLcd`, which works for anything drawing on embedded-graphics targets.
+
+use std::{
+    collections::VecDeque,
+    convert::Infallible,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use embedded_graphics::{draw_target::DrawTarget, geometry::Size, pixelcolor::Rgb565};
+
+pub use evil_android::testing::MockLcd;
+
+use crate::{
+    frame_clock,
+    platform::{
+        BatteryStatus, Brightness, DisplayId, FrameStats, Input, InputEvent, Platform, ResumeState,
+        LED,
+    },
+    tweaks::Tweaks,
+};
+
+/// Remembers everything it was set to, and when
+pub struct MockLed {
+    started: Instant,
+    pub calls: Vec<(Duration, f32)>,
+}
+
+impl MockLed {
+    /// Brightness set last, 0 before anything was
+    pub fn brightness(&self) -> f32 {
+        self.calls.last().map_or(0.0, |&(_, brightness)| brightness)
+    }
+
+    /// Calls made from `since` on
+    pub fn calls_since(&self, since: Duration) -> impl Iterator<Item = f32> + '_ {
+        self.calls
+            .iter()
+            .filter(move |(t, _)| *t >= since)
+            .map(|&(_, brightness)| brightness)
+    }
+}
+
+impl LED for MockLed {
+    fn write_brightness(&mut self, brightness: Brightness) -> Result<()> {
+        self.calls
+            .push((frame_clock::elapsed(self.started), brightness.into()));
+        Ok(())
+    }
+}
+
+/// Events queued up with `MockPlatform::input`, handed out one per poll
+#[derive(Default)]
+pub struct MockInput(VecDeque<InputEvent>);
+
+impl Input for MockInput {
+    fn poll(&mut self) -> Result<Option<InputEvent>> {
+        Ok(self.0.pop_front())
+    }
+}
+
+/// Time only moves when something sleeps on it or calls `advance`. Asks to exit once the
+/// deadline from `run_for` passes, or after the first escalation cycle is over.
+pub struct MockPlatform {
+    pub lcd: MockLcd,
+    pub led0: MockLed,
+    pub led1: MockLed,
+    input: MockInput,
+    /// Nothing to load, everything stays at its defaults
+    storage: (),
+    /// Given to everything that reads the tweaks from config.toml
+    pub tweaks: Tweaks,
+    started: Instant,
+    deadline: Option<Duration>,
+    cycle_done: bool,
+    pub frames: usize,
+    /// Virtual time each frame takes to render, on top of any it sleeps for
+    pub frame_time: Duration,
+    /// Of every escalation frame, in order
+    pub escalation: Vec<FrameStats>,
+}
+
+impl MockPlatform {
+    /// Makes time virtual for the calling thread, starting from now
+    pub fn new(size: Size) -> Self {
+        frame_clock::make_virtual();
+        frame_clock::advance_to(Duration::ZERO);
+        let started = frame_clock::now();
+        let led = || MockLed {
+            started,
+            calls: Vec::new(),
+        };
+        Self {
+            lcd: MockLcd::new(size),
+            led0: led(),
+            led1: led(),
+            input: MockInput::default(),
+            storage: (),
+            // Same escalation every time
+            tweaks: Tweaks {
+                rng_seed: Some(0),
+                frame_budget_ms: 0,
+                ..Tweaks::default()
+            },
+            started,
+            deadline: None,
+            cycle_done: false,
+            frames: 0,
+            frame_time: Duration::ZERO,
+            escalation: Vec::new(),
+        }
+    }
+
+    /// Virtual time since `new`
+    pub fn elapsed(&self) -> Duration {
+        frame_clock::elapsed(self.started)
+    }
+
+    pub fn advance(&mut self, by: Duration) {
+        frame_clock::advance_to(self.elapsed() + by);
+    }
+
+    /// Asks to exit `duration` of virtual time from now
+    pub fn run_for(&mut self, duration: Duration) {
+        self.deadline = Some(self.elapsed() + duration);
+    }
+
+    /// Queues `event` up for the next poll
+    pub fn input(&mut self, event: InputEvent) {
+        self.input.0.push_back(event);
+    }
+}
+
+impl Platform for MockPlatform {
+    type DisplayError = Infallible;
+
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        self.advance(duration);
+    }
+
+    fn display(
+        &mut self,
+        id: DisplayId,
+    ) -> Option<&mut impl DrawTarget<Color = Rgb565, Error = Self::DisplayError>> {
+        (id == DisplayId::MAIN).then_some(&mut self.lcd)
+    }
+
+    fn led0(&mut self) -> &mut impl LED {
+        &mut self.led0
+    }
+
+    fn led1(&mut self) -> &mut impl LED {
+        &mut self.led1
+    }
+
+    fn input(&mut self) -> &mut impl Input {
+        &mut self.input
+    }
+
+    fn storage(&mut self) -> &mut impl crate::platform::Storage {
+        &mut self.storage
+    }
+
+    fn feed_watchdog(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn reset_lcd(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn report_frame_stats(&mut self, stats: &FrameStats) {
+        self.frames += 1;
+        if stats.scene == "escalation" {
+            self.escalation.push(stats.clone());
+        }
+        self.advance(self.frame_time);
+    }
+
+    fn exit_requested(&self) -> bool {
+        self.cycle_done || self.deadline.is_some_and(|t| self.elapsed() >= t)
+    }
+
+    fn poll_tweaks(&mut self) -> Option<Tweaks> {
+        None
+    }
+
+    fn adjust_tweaks(&self, tweaks: &mut Tweaks) {
+        *tweaks = self.tweaks.clone();
+    }
+
+    fn cycle_done(&mut self) {
+        self.cycle_done = true;
+    }
+
+    fn battery(&mut self) -> Option<BatteryStatus> {
+        None
+    }
+
+    fn deep_sleep(&mut self, _wake_after: Option<Duration>, _resume: Option<ResumeState>) {
+        self.cycle_done = true;
+    }
+
+    unsafe fn install_panic_screen(&mut self) {}
+}
@@ -0,0 +1,210 @@
+use alloc::vec::Vec;
+
+use embedded_graphics::pixelcolor::{raw::RawU16, Rgb565, RgbColor};
+use embedded_graphics_framebuf::{backends::FrameBufferBackend, FrameBuf};
+use rand::{seq::SliceRandom, Rng};
+use serde::Deserialize;
+
+use crate::{color565, intensity::Intensity, region::Region};
+
+/// Tallest band of banding noise, in rows
+const MAX_BAND_HEIGHT: usize = 4;
+/// How far sensor noise pushes each channel, in 0..1 of the channel range
+const SENSOR_NOISE_AMPLITUDE: f32 = 0.25;
+/// Green is the most sensitive channel, red and blue get half as much
+const SENSOR_TINT: Rgb565 = Rgb565::new(15, 63, 15);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Flavor {
+    /// Pixels replaced with random colors
+    Color,
+    /// Pixels replaced with random shades of gray, like an untuned TV
+    Monochrome,
+    /// Pixels get brighter or darker, mostly in green, like a camera sensor in the dark
+    Sensor,
+    /// Pixels replaced with pure black or white
+    SaltAndPepper,
+    /// Whole bands of rows replaced with a flat gray
+    Banding,
+}
+
+/// Noise of one flavor. Intensity is the fraction of pixels affected, or rows for banding.
+#[derive(Clone, Copy, Debug)]
+pub struct NoiseEffect {
+    pub flavor: Flavor,
+    pub intensity: Intensity,
+    /// Darkest and brightest color of the noise, None for its own colors
+    pub palette: Option<[Rgb565; 2]>,
+}
+
+impl NoiseEffect {
+    pub fn new(flavor: Flavor, intensity: Intensity) -> Self {
+        Self {
+            flavor,
+            intensity,
+            palette: None,
+        }
+    }
+
+    pub fn with_palette(self, palette: Option<[Rgb565; 2]>) -> Self {
+        Self { palette, ..self }
+    }
+
+    fn recolor(&self, color: Rgb565) -> Rgb565 {
+        match self.palette {
+            Some([dark, bright]) => color565::remap(color, dark, bright),
+            None => color,
+        }
+    }
+
+    pub fn apply<B: FrameBufferBackend<Color = Rgb565>>(
+        &self,
+        fb: &mut FrameBuf<Rgb565, B>,
+        rng: &mut impl Rng,
+        region: &Region,
+    ) {
+        let intensity = self.intensity.get();
+        if self.flavor == Flavor::Banding {
+            add_bands(fb, rng, intensity, region, |color| self.recolor(color));
+            return;
+        }
+        // Chance of each pixel being hit, out of 2^32
+        let threshold = (f64::from(intensity) * (1u64 << 32) as f64) as u64;
+        let width = fb.width();
+        region.for_each_span(width, fb.height(), |row, columns| {
+            // Asking the RNG for every decision costs more than the noise itself. It only seeds
+            // a much cheaper generator for each row.
+            let mut words = Xorshift(rng.next_u64() | 1);
+            for column in columns {
+                let word = words.next_word();
+                // Low half decides, high half picks the color
+                if u64::from(word as u32) < threshold {
+                    self.apply_to_pixel(fb, row * width + column, (word >> 32) as u32);
+                }
+            }
+        });
+    }
+
+    fn apply_to_pixel<B: FrameBufferBackend<Color = Rgb565>>(
+        &self,
+        fb: &mut FrameBuf<Rgb565, B>,
+        index: usize,
+        bits: u32,
+    ) {
+        let color = match self.flavor {
+            Flavor::Color => RawU16::new(bits as u16).into(),
+            Flavor::Monochrome => gray(bits as u8),
+            Flavor::Sensor => {
+                let pixel = fb.data.get(index);
+                let amount =
+                    Intensity::new((bits & 0xffff) as f32 / 65536.0 * SENSOR_NOISE_AMPLITUDE);
+                let tint = self.palette.map_or(SENSOR_TINT, |[_, bright]| bright);
+                let delta = color565::scale(tint, color565::fraction(amount));
+                if bits & 0x1_0000 != 0 {
+                    color565::saturating_add(pixel, delta)
+                } else {
+                    color565::saturating_sub(pixel, delta)
+                }
+            }
+            Flavor::SaltAndPepper => {
+                if bits & 1 != 0 {
+                    Rgb565::WHITE
+                } else {
+                    Rgb565::BLACK
+                }
+            }
+            Flavor::Banding => unreachable!(),
+        };
+        // Sensor noise is tinted already
+        let color = match self.flavor {
+            Flavor::Sensor => color,
+            _ => self.recolor(color),
+        };
+        fb.data.set(index, color);
+    }
+}
+
+/// xorshift64*, good enough for noise. Never seeded with 0, which it would get stuck at.
+struct Xorshift(u64);
+
+impl Xorshift {
+    fn next_word(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+}
+
+fn gray(level: u8) -> Rgb565 {
+    Rgb565::new(level >> 3, level >> 2, level >> 3)
+}
+
+fn add_bands<B: FrameBufferBackend<Color = Rgb565>>(
+    fb: &mut FrameBuf<Rgb565, B>,
+    rng: &mut impl Rng,
+    row_probability: f32,
+    region: &Region,
+    recolor: impl Fn(Rgb565) -> Rgb565,
+) {
+    let width = fb.width();
+    // Band color and the row it ends at
+    let mut band: Option<(Rgb565, usize)> = None;
+    region.for_each_span(width, fb.height(), |row, columns| {
+        let color = match band {
+            Some((color, end)) if row < end => color,
+            _ if rng.gen::<f32>() < row_probability => {
+                let color = recolor(gray(rng.gen()));
+                band = Some((color, row + rng.gen_range(1..=MAX_BAND_HEIGHT)));
+                color
+            }
+            _ => return,
+        };
+        for column in columns {
+            fb.data.set(row * width + column, color);
+        }
+    });
+}
+
+/// Intensity of each noise flavor in the ending. Configured with `[tweaks.noise]` in
+/// config.toml, zero disables a flavor.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NoiseMix {
+    pub color: f32,
+    pub monochrome: f32,
+    pub sensor: f32,
+    pub salt_and_pepper: f32,
+    pub banding: f32,
+}
+
+impl Default for NoiseMix {
+    fn default() -> Self {
+        Self {
+            color: 1.0,
+            monochrome: 1.0,
+            sensor: 0.5,
+            salt_and_pepper: 0.3,
+            banding: 0.5,
+        }
+    }
+}
+
+impl NoiseMix {
+    /// One of the enabled flavors, for variety from one ending to the next. None if all of them
+    /// are disabled.
+    pub fn choose(&self, rng: &mut impl Rng) -> Option<NoiseEffect> {
+        let enabled: Vec<NoiseEffect> = [
+            (Flavor::Color, self.color),
+            (Flavor::Monochrome, self.monochrome),
+            (Flavor::Sensor, self.sensor),
+            (Flavor::SaltAndPepper, self.salt_and_pepper),
+            (Flavor::Banding, self.banding),
+        ]
+        .into_iter()
+        .map(|(flavor, intensity)| NoiseEffect::new(flavor, Intensity::new(intensity)))
+        .filter(|effect| effect.intensity > Intensity::ZERO)
+        .collect();
+        enabled.choose(rng).copied()
+    }
+}
@@ -0,0 +1,350 @@
+//! Panels mounted sideways, upside down or seen through a mirror. [`Oriented`] turns whatever
+//! gets drawn on it before it reaches the panel, so that everything above it only ever sees the
+//! right way up.
+
+use alloc::vec::Vec;
+
+use anyhow::{bail, Result};
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::{Dimensions, Point, Size},
+    primitives::{PointsIter, Rectangle},
+    Pixel,
+};
+
+/// Clockwise
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Rotation {
+    #[default]
+    Deg0,
+    Deg90,
+    Deg180,
+    Deg270,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Orientation {
+    pub rotation: Rotation,
+    /// Left to right, before rotating
+    pub mirror: bool,
+}
+
+impl Orientation {
+    /// From degrees, "0", "90", "180" or "270", and "true" or "false" for mirroring. None means
+    /// the default, landscape and not mirrored.
+    pub fn parse(rotation: Option<&str>, mirror: Option<&str>) -> Result<Self> {
+        let rotation = match rotation {
+            None | Some("0") => Rotation::Deg0,
+            Some("90") => Rotation::Deg90,
+            Some("180") => Rotation::Deg180,
+            Some("270") => Rotation::Deg270,
+            Some(other) => bail!("invalid rotation: {other}, expected 0, 90, 180 or 270"),
+        };
+        let mirror = match mirror {
+            None | Some("false") | Some("0") => false,
+            Some("true") | Some("1") => true,
+            Some(other) => bail!("invalid mirror: {other}, expected true or false"),
+        };
+        Ok(Self { rotation, mirror })
+    }
+
+    /// Size of the frames drawn on a `panel` this big
+    pub fn size(self, panel: Size) -> Size {
+        match self.rotation {
+            Rotation::Deg0 | Rotation::Deg180 => panel,
+            Rotation::Deg90 | Rotation::Deg270 => Size::new(panel.height, panel.width),
+        }
+    }
+
+    /// Where `point` of a frame `size` big ends up on the panel
+    fn to_panel(self, point: Point, size: Size) -> Point {
+        let (width, height) = (size.width as i32, size.height as i32);
+        let Point { x, y } = point;
+        let x = if self.mirror { width - 1 - x } else { x };
+        match self.rotation {
+            Rotation::Deg0 => Point::new(x, y),
+            Rotation::Deg90 => Point::new(height - 1 - y, x),
+            Rotation::Deg180 => Point::new(width - 1 - x, height - 1 - y),
+            Rotation::Deg270 => Point::new(y, width - 1 - x),
+        }
+    }
+
+    /// Rectangles stay rectangles, just with other corners
+    pub fn area_to_panel(self, area: Rectangle, size: Size) -> Rectangle {
+        let Some(bottom_right) = area.bottom_right() else {
+            return Rectangle::new(self.to_panel(area.top_left, size), Size::zero());
+        };
+        let [a, b] = [area.top_left, bottom_right].map(|corner| self.to_panel(corner, size));
+        Rectangle::with_corners(a, b)
+    }
+}
+
+/// Draws on `target` turned by `orientation`. Reports the turned size, so that frames of that
+/// size fill the whole panel.
+pub struct Oriented<D: DrawTarget> {
+    target: D,
+    orientation: Orientation,
+    /// Of what's drawn on this
+    size: Size,
+    /// Reused, so that turning a frame doesn't allocate
+    turned: Vec<D::Color>,
+}
+
+impl<D: DrawTarget> Oriented<D> {
+    pub fn new(target: D, orientation: Orientation) -> Self {
+        let size = orientation.size(target.bounding_box().size);
+        Self {
+            target,
+            orientation,
+            size,
+            turned: Vec::new(),
+        }
+    }
+
+    pub fn inner_mut(&mut self) -> &mut D {
+        &mut self.target
+    }
+
+    fn is_identity(&self) -> bool {
+        self.orientation == Orientation::default()
+    }
+
+    /// Hands `pixels` of `area` to `write`, turned into rows of the panel, along with the area
+    /// of the panel they cover. Lets drivers with a faster way than `fill_contiguous` use it
+    /// on turned frames too. Areas sticking out of the frame go through `fill_contiguous`.
+    pub fn write_turned(
+        &mut self,
+        area: &Rectangle,
+        pixels: &[D::Color],
+        write: impl FnOnce(&mut D, &Rectangle, &[D::Color]) -> Result<(), D::Error>,
+    ) -> Result<(), D::Error> {
+        let len = area.size.width as usize * area.size.height as usize;
+        let fits = self.bounding_box().intersection(area) == *area;
+        if !fits || pixels.len() < len {
+            return self.fill_contiguous(area, pixels.iter().copied());
+        }
+        if self.is_identity() {
+            return write(&mut self.target, area, &pixels[..len]);
+        }
+        let (panel_area, _) = turn(
+            self.orientation,
+            self.size,
+            area,
+            pixels[..len].iter().copied(),
+            &mut self.turned,
+        );
+        write(&mut self.target, &panel_area, &self.turned)
+    }
+}
+
+/// Reorders `colors` of `area` of a frame `size` big into rows of the panel, in `turned`.
+/// Returns the area of the panel they cover, and how many colors there were, which may be
+/// fewer than `area` holds.
+pub fn turn<C: Copy>(
+    orientation: Orientation,
+    size: Size,
+    area: &Rectangle,
+    colors: impl IntoIterator<Item = C>,
+    turned: &mut Vec<C>,
+) -> (Rectangle, usize) {
+    let panel_area = orientation.area_to_panel(*area, size);
+    let panel_width = panel_area.size.width as usize;
+    let len = area.size.width as usize * area.size.height as usize;
+    let mut colors = colors.into_iter();
+    turned.clear();
+    let Some(first) = colors.next() else {
+        return (panel_area, 0);
+    };
+    turned.resize(len, first);
+    let mut count = 0;
+    for (point, color) in area.points().zip(core::iter::once(first).chain(colors)) {
+        let Point { x, y } = orientation.to_panel(point, size) - panel_area.top_left;
+        turned[y as usize * panel_width + x as usize] = color;
+        count += 1;
+    }
+    (panel_area, count)
+}
+
+impl<D: DrawTarget> Dimensions for Oriented<D> {
+    fn bounding_box(&self) -> Rectangle {
+        Rectangle::new(Point::zero(), self.size)
+    }
+}
+
+impl<D: DrawTarget> DrawTarget for Oriented<D> {
+    type Color = D::Color;
+    type Error = D::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let (orientation, size) = (self.orientation, self.size);
+        // Points off the frame stay off the panel, the target clips them
+        self.target.draw_iter(
+            pixels
+                .into_iter()
+                .map(|Pixel(point, color)| Pixel(orientation.to_panel(point, size), color)),
+        )
+    }
+
+    /// Reorders the pixels into rows of the panel, so that the target still gets them all in
+    /// one go
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        if self.is_identity() {
+            return self.target.fill_contiguous(area, colors);
+        }
+        let (orientation, size) = (self.orientation, self.size);
+        let fits = self.bounding_box().intersection(area) == *area;
+        if !fits {
+            return self.target.draw_iter(
+                area.points()
+                    .zip(colors)
+                    .map(|(point, color)| Pixel(orientation.to_panel(point, size), color)),
+            );
+        }
+        let (panel_area, count) = turn(orientation, size, area, colors, &mut self.turned);
+        let len = area.size.width as usize * area.size.height as usize;
+        if count < len {
+            // Ran out of colors, the rest of the area stays as it was
+            let (panel_width, turned) = (panel_area.size.width as usize, &self.turned);
+            return self
+                .target
+                .draw_iter(area.points().take(count).map(|point| {
+                    let point = orientation.to_panel(point, size);
+                    let Point { x, y } = point - panel_area.top_left;
+                    Pixel(point, turned[y as usize * panel_width + x as usize])
+                }));
+        }
+        self.target
+            .fill_contiguous(&panel_area, self.turned.iter().copied())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        let area = area.intersection(&self.bounding_box());
+        if area.is_zero_sized() {
+            return Ok(());
+        }
+        let panel_area = self.orientation.area_to_panel(area, self.size);
+        self.target.fill_solid(&panel_area, color)
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.target.clear(color)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_graphics::{mock_display::MockDisplay, pixelcolor::BinaryColor, Drawable};
+
+    use super::*;
+
+    /// MockDisplay is always 64x64, the panels in these tests are smaller
+    struct Panel(MockDisplay<BinaryColor>, Size);
+
+    impl Dimensions for Panel {
+        fn bounding_box(&self) -> Rectangle {
+            Rectangle::new(Point::zero(), self.1)
+        }
+    }
+
+    impl DrawTarget for Panel {
+        type Color = BinaryColor;
+        type Error = core::convert::Infallible;
+
+        fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+        where
+            I: IntoIterator<Item = Pixel<Self::Color>>,
+        {
+            self.0.draw_iter(pixels)
+        }
+    }
+
+    fn oriented(rotation: Rotation, mirror: bool) -> Oriented<Panel> {
+        let panel = Panel(MockDisplay::new(), Size::new(3, 2));
+        Oriented::new(panel, Orientation { rotation, mirror })
+    }
+
+    /// Draws a frame with `rows` of `#` and `.` and returns what ends up on the panel
+    fn turned(rotation: Rotation, mirror: bool, rows: &[&str]) -> MockDisplay<BinaryColor> {
+        let mut oriented = oriented(rotation, mirror);
+        let colors = rows
+            .iter()
+            .flat_map(|row| row.chars())
+            .map(|c| BinaryColor::from(c == '#'));
+        let pixels: Vec<_> = colors.collect();
+        let area = oriented.bounding_box();
+        oriented
+            .fill_contiguous(&area, pixels.iter().copied())
+            .unwrap();
+        // The same pixels in the same places, whichever way they get there
+        let mut written = self::oriented(rotation, mirror);
+        written
+            .write_turned(&area, &pixels, |target, area, pixels| {
+                target.fill_contiguous(area, pixels.iter().copied())
+            })
+            .unwrap();
+        written.target.0.assert_eq(&oriented.target.0);
+        oriented.target.0
+    }
+
+    #[test]
+    fn parses_build_env() {
+        assert_eq!(
+            Orientation::parse(None, None).unwrap(),
+            Orientation::default()
+        );
+        let orientation = Orientation::parse(Some("90"), Some("true")).unwrap();
+        assert_eq!(orientation.rotation, Rotation::Deg90);
+        assert!(orientation.mirror);
+        assert!(Orientation::parse(Some("45"), None).is_err());
+        assert!(Orientation::parse(None, Some("yes")).is_err());
+    }
+
+    #[test]
+    fn turns_frames() {
+        let frame = ["#..", "..."];
+        turned(Rotation::Deg0, false, &frame).assert_pattern(&["#..", "..."]);
+        turned(Rotation::Deg180, false, &frame).assert_pattern(&["...", "..#"]);
+        turned(Rotation::Deg0, true, &frame).assert_pattern(&["..#", "..."]);
+        turned(Rotation::Deg180, true, &frame).assert_pattern(&["...", "#.."]);
+        // Sideways, the frame is 2x3
+        let frame = ["#.", "..", ".."];
+        assert_eq!(
+            oriented(Rotation::Deg90, false).bounding_box().size,
+            Size::new(2, 3)
+        );
+        turned(Rotation::Deg90, false, &frame).assert_pattern(&["..#", "..."]);
+        turned(Rotation::Deg270, false, &frame).assert_pattern(&["...", "#.."]);
+        turned(Rotation::Deg90, true, &frame).assert_pattern(&["...", "..#"]);
+        turned(Rotation::Deg270, true, &frame).assert_pattern(&["#..", "..."]);
+    }
+
+    #[test]
+    fn turns_pixels_and_areas() {
+        let mut oriented = oriented(Rotation::Deg90, false);
+        Pixel(Point::new(1, 0), BinaryColor::On)
+            .draw(&mut oriented)
+            .unwrap();
+        // Sticks out of the frame, gets clipped
+        oriented
+            .fill_solid(
+                &Rectangle::new(Point::new(0, 2), Size::new(3, 1)),
+                BinaryColor::Off,
+            )
+            .unwrap();
+        oriented.target.0.assert_pattern(&[".  ", ". #"]);
+
+        // Only as many as there are colors
+        let mut oriented = self::oriented(Rotation::Deg180, false);
+        let area = oriented.bounding_box();
+        oriented
+            .fill_contiguous(&area, [BinaryColor::On, BinaryColor::Off])
+            .unwrap();
+        oriented.target.0.assert_pattern(&["   ", " .#"]);
+    }
+}
@@ -0,0 +1,446 @@
+use std::{
+    collections::VecDeque,
+    net::UdpSocket,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+
+use crate::platform::InputEvent;
+
+// Read at build time, so that it works on the ESP32 too
+const PORT: Option<&str> = option_env!("EVIL_ANDROID_OSC_PORT");
+/// Larger than anything a lighting desk sends in one go
+const MAX_PACKET_SIZE: usize = 1536;
+/// Bundles in bundles in bundles... are legal, but nobody needs more than that
+const MAX_BUNDLE_DEPTH: usize = 4;
+/// Events nobody picked up, e.g. while asleep, don't pile up forever
+const MAX_PENDING_EVENTS: usize = 16;
+/// Longer bursts are cut short, a glitch storm that never ends is what `/evil/glitch` is for
+const MAX_BURST: Duration = Duration::from_secs(60);
+
+/// What the escalation shows instead of its own idea, while set
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Overrides {
+    /// 0..1, text shake as a fraction of `max_text_shake`
+    pub shake: Option<f32>,
+    /// 0..1, glitchiness as a fraction of what a full rage brings
+    pub glitch: Option<f32>,
+    /// 0..1, noise over the whole screen
+    pub noise: Option<f32>,
+    pub message: Option<String>,
+    /// 0..1, brightness of each LED
+    pub leds: [Option<f32>; 2],
+    /// 0..1, glitchiness of a burst going on right now, on top of the usual
+    pub burst: Option<f32>,
+}
+
+struct State {
+    overrides: Overrides,
+    burst_until: Option<Instant>,
+    events: VecDeque<InputEvent>,
+}
+
+// Set from the OSC thread
+static STATE: Mutex<State> = Mutex::new(State {
+    overrides: Overrides {
+        shake: None,
+        glitch: None,
+        noise: None,
+        message: None,
+        leds: [None; 2],
+        burst: None,
+    },
+    burst_until: None,
+    events: VecDeque::new(),
+});
+
+pub fn overrides() -> Overrides {
+    let mut state = STATE.lock().unwrap();
+    if state
+        .burst_until
+        .is_some_and(|until| Instant::now() >= until)
+    {
+        state.burst_until = None;
+        state.overrides.burst = None;
+    }
+    state.overrides.clone()
+}
+
+/// Same as `/evil/message`, for the console. None or empty gives the message back.
+pub fn set_message(message: Option<&str>) {
+    STATE.lock().unwrap().overrides.message = message
+        .filter(|message| !message.is_empty())
+        .map(str::to_owned);
+}
+
+/// Returns the next event sent with `/evil/input` or `/evil/seek`, if any
+pub fn poll_event() -> Option<InputEvent> {
+    STATE.lock().unwrap().events.pop_front()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Arg {
+    Int(i32),
+    Float(f32),
+    String(String),
+}
+
+impl Arg {
+    fn as_f32(&self) -> Option<f32> {
+        match self {
+            Arg::Int(i) => Some(*i as f32),
+            Arg::Float(f) => Some(*f),
+            Arg::String(_) => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Message {
+    address: String,
+    args: Vec<Arg>,
+}
+
+/// Splits off a 4-byte aligned chunk
+fn take<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    let padded = len.next_multiple_of(4);
+    if data.len() < padded {
+        bail!("truncated packet");
+    }
+    let (chunk, rest) = data.split_at(padded);
+    *data = rest;
+    Ok(&chunk[..len])
+}
+
+fn take_u32(data: &mut &[u8]) -> Result<u32> {
+    let bytes = take(data, 4)?;
+    Ok(u32::from_be_bytes(bytes.try_into()?))
+}
+
+/// Null-terminated, padded with more nulls to a multiple of 4 bytes
+fn take_string(data: &mut &[u8]) -> Result<String> {
+    let len = data
+        .iter()
+        .position(|&b| b == 0)
+        .context("unterminated string")?;
+    let bytes = take(data, len + 1)?;
+    Ok(std::str::from_utf8(&bytes[..len])?.to_owned())
+}
+
+/// Appends messages from an OSC 1.0 packet, either a message or a bundle of them. Bundle time
+/// tags are ignored, everything applies right away.
+fn parse_packet(mut data: &[u8], depth: usize, out: &mut Vec<Message>) -> Result<()> {
+    if data.starts_with(b"#bundle\0") {
+        if depth >= MAX_BUNDLE_DEPTH {
+            bail!("bundles nested too deep");
+        }
+        // Tag and time tag
+        take(&mut data, 16)?;
+        while !data.is_empty() {
+            let len = take_u32(&mut data)? as usize;
+            parse_packet(take(&mut data, len)?, depth + 1, out)?;
+        }
+        return Ok(());
+    }
+
+    let address = take_string(&mut data)?;
+    // Very old senders leave out type tags, there would be no telling what the arguments are
+    let tags = take_string(&mut data)?;
+    let Some(tags) = tags.strip_prefix(',') else {
+        bail!("no type tags for {address}");
+    };
+    let mut args = Vec::new();
+    for tag in tags.chars() {
+        args.push(match tag {
+            'i' => Arg::Int(take_u32(&mut data)? as i32),
+            'f' => Arg::Float(f32::from_bits(take_u32(&mut data)?)),
+            's' => Arg::String(take_string(&mut data)?),
+            other => bail!("unsupported type tag '{other}' for {address}"),
+        });
+    }
+    out.push(Message { address, args });
+    Ok(())
+}
+
+/// Without any arguments, the override is released
+fn fraction(message: &Message) -> Result<Option<f32>> {
+    match message.args.first() {
+        None => Ok(None),
+        Some(arg) => match arg.as_f32() {
+            Some(value) => Ok(Some(value.clamp(0.0, 1.0))),
+            None => bail!("{} needs a number", message.address),
+        },
+    }
+}
+
+fn apply(state: &mut State, message: &Message) -> Result<()> {
+    let overrides = &mut state.overrides;
+    match message.address.as_str() {
+        "/evil/shake" => overrides.shake = fraction(message)?,
+        "/evil/glitch" => overrides.glitch = fraction(message)?,
+        "/evil/noise" => overrides.noise = fraction(message)?,
+        "/evil/message" => {
+            overrides.message = match message.args.first() {
+                None => None,
+                Some(Arg::String(s)) if s.is_empty() => None,
+                Some(Arg::String(s)) => Some(s.clone()),
+                Some(_) => bail!("/evil/message needs a string"),
+            }
+        }
+        "/evil/led/0" => overrides.leds[0] = fraction(message)?,
+        "/evil/led/1" => overrides.leds[1] = fraction(message)?,
+        "/evil/leds" => overrides.leds = [fraction(message)?; 2],
+        "/evil/release" => *overrides = Overrides::default(),
+        "/evil/burst" => {
+            let (Some(strength), Some(ms)) = (
+                fraction(message)?,
+                message.args.get(1).and_then(Arg::as_f32),
+            ) else {
+                bail!("/evil/burst needs strength and milliseconds");
+            };
+            // NaN ends up as no burst at all, infinity as the longest one
+            let secs = (ms / 1000.0).clamp(0.0, MAX_BURST.as_secs_f32());
+            let duration = Duration::try_from_secs_f32(secs).unwrap_or(Duration::ZERO);
+            overrides.burst = Some(strength);
+            state.burst_until = Instant::now().checked_add(duration);
+        }
+        "/evil/seek" => match fraction(message)? {
+            Some(position) => push_event(state, InputEvent::Seek(position)),
+            None => bail!("/evil/seek needs a number"),
+        },
+        "/evil/input" => match message.args.first() {
+            Some(Arg::String(command)) => match crate::console::parse_command(command) {
+                Some(event) => push_event(state, event),
+                None => bail!("unknown command: {command}"),
+            },
+            _ => bail!("/evil/input needs a console command"),
+        },
+        other => bail!("unknown address: {other}"),
+    }
+    Ok(())
+}
+
+fn push_event(state: &mut State, event: InputEvent) {
+    if state.events.len() >= MAX_PENDING_EVENTS {
+        state.events.pop_front();
+    }
+    state.events.push_back(event);
+}
+
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn push_string(out: &mut Vec<u8>, s: &str) {
+    out.extend(s.as_bytes());
+    out.resize((out.len() + 1).next_multiple_of(4), 0);
+}
+
+/// Encodes an OSC 1.0 message
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub fn message(address: &str, args: &[Arg]) -> Vec<u8> {
+    let mut out = Vec::new();
+    push_string(&mut out, address);
+    let tags: String = std::iter::once(',')
+        .chain(args.iter().map(|arg| match arg {
+            Arg::Int(_) => 'i',
+            Arg::Float(_) => 'f',
+            Arg::String(_) => 's',
+        }))
+        .collect();
+    push_string(&mut out, &tags);
+    for arg in args {
+        match arg {
+            Arg::Int(i) => out.extend(i.to_be_bytes()),
+            Arg::Float(f) => out.extend(f.to_be_bytes()),
+            Arg::String(s) => push_string(&mut out, s),
+        }
+    }
+    out
+}
+
+/// Listens for OSC messages on EVIL_ANDROID_OSC_PORT, if set at build time
+pub fn spawn() -> Result<()> {
+    let Some(port) = PORT else {
+        return Ok(());
+    };
+    let port: u16 = port
+        .parse()
+        .with_context(|| format!("invalid EVIL_ANDROID_OSC_PORT: {port}"))?;
+    let socket = UdpSocket::bind(("0.0.0.0", port)).context("UdpSocket::bind failed")?;
+    log::info!("listening for OSC on UDP port {port}");
+    std::thread::Builder::new()
+        .name("osc".to_owned())
+        .stack_size(8192)
+        .spawn(move || {
+            let mut packet = vec![0; MAX_PACKET_SIZE];
+            let mut messages = Vec::new();
+            loop {
+                let len = match socket.recv_from(&mut packet) {
+                    Ok((len, _)) => len,
+                    Err(e) => {
+                        log::error!("OSC receive failed: {e:?}");
+                        return;
+                    }
+                };
+                messages.clear();
+                // Whatever parsed before an error still applies
+                let parsed = parse_packet(&packet[..len], 0, &mut messages);
+                let mut state = STATE.lock().unwrap();
+                for message in &messages {
+                    if let Err(e) = apply(&mut state, message) {
+                        log::warn!("OSC: {e:#}");
+                    }
+                }
+                if let Err(e) = parsed {
+                    log::warn!("OSC: {e:#}");
+                }
+            }
+        })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(packet: &[u8]) -> Result<Vec<Message>> {
+        let mut messages = Vec::new();
+        parse_packet(packet, 0, &mut messages)?;
+        Ok(messages)
+    }
+
+    fn new_state() -> State {
+        State {
+            overrides: Overrides::default(),
+            burst_until: None,
+            events: VecDeque::new(),
+        }
+    }
+
+    fn send(state: &mut State, address: &str, args: &[Arg]) -> Result<()> {
+        for message in parse(&message(address, args)).unwrap() {
+            apply(state, &message)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn parses_messages() {
+        let args = vec![
+            Arg::Float(0.5),
+            Arg::Int(-3),
+            Arg::String("Analyzing".to_owned()),
+            Arg::String("abc".to_owned()),
+        ];
+        assert_eq!(
+            parse(&message("/evil/x", &args)).unwrap(),
+            [Message {
+                address: "/evil/x".to_owned(),
+                args
+            }]
+        );
+        assert_eq!(parse(&message("/evil/release", &[])).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn parses_bundles() {
+        let first = message("/evil/shake", &[Arg::Float(1.0)]);
+        let second = message("/evil/noise", &[Arg::Int(0)]);
+        let mut packet = b"#bundle\0".to_vec();
+        packet.extend([0, 0, 0, 0, 0, 0, 0, 1]);
+        for message in [&first, &second] {
+            packet.extend((message.len() as u32).to_be_bytes());
+            packet.extend(message);
+        }
+        let addresses: Vec<String> = parse(&packet)
+            .unwrap()
+            .into_iter()
+            .map(|m| m.address)
+            .collect();
+        assert_eq!(addresses, ["/evil/shake", "/evil/noise"]);
+    }
+
+    #[test]
+    fn rejects_malformed_packets() {
+        let shake = message("/evil/shake", &[Arg::Float(1.0)]);
+        assert!(parse(&shake[..shake.len() - 1]).is_err());
+        assert!(parse(b"/evil/shake\0").is_err());
+        assert!(parse(b"/evil/shake\0\0\0\0\0").is_err());
+        assert!(parse(b"/evil/shake\0\0\0\0,T\0\0").is_err());
+    }
+
+    #[test]
+    fn applies_overrides() {
+        let mut state = new_state();
+        send(&mut state, "/evil/shake", &[Arg::Float(0.25)]).unwrap();
+        send(&mut state, "/evil/glitch", &[Arg::Int(2)]).unwrap();
+        let on_air = Arg::String("on air".to_owned());
+        send(&mut state, "/evil/message", &[on_air]).unwrap();
+        send(&mut state, "/evil/leds", &[Arg::Float(0.5)]).unwrap();
+        send(&mut state, "/evil/led/1", &[Arg::Float(-1.0)]).unwrap();
+        assert_eq!(
+            state.overrides,
+            Overrides {
+                shake: Some(0.25),
+                glitch: Some(1.0),
+                noise: None,
+                message: Some("on air".to_owned()),
+                leds: [Some(0.5), Some(0.0)],
+                burst: None,
+            }
+        );
+
+        send(&mut state, "/evil/shake", &[]).unwrap();
+        send(&mut state, "/evil/message", &[Arg::String(String::new())]).unwrap();
+        assert_eq!(state.overrides.shake, None);
+        assert_eq!(state.overrides.message, None);
+        send(&mut state, "/evil/release", &[]).unwrap();
+        assert_eq!(state.overrides, Overrides::default());
+    }
+
+    #[test]
+    fn queues_events() {
+        let mut state = new_state();
+        send(&mut state, "/evil/seek", &[Arg::Float(0.5)]).unwrap();
+        send(&mut state, "/evil/input", &[Arg::String("next".to_owned())]).unwrap();
+        assert_eq!(state.events, [InputEvent::Seek(0.5), InputEvent::NextScene]);
+        for _ in 0..MAX_PENDING_EVENTS {
+            send(&mut state, "/evil/input", &[Arg::String("pet".to_owned())]).unwrap();
+        }
+        assert_eq!(state.events.len(), MAX_PENDING_EVENTS);
+        assert!(state.events.iter().all(|&e| e == InputEvent::Pet));
+    }
+
+    #[test]
+    fn bursts_expire() {
+        let mut state = new_state();
+        send(&mut state, "/evil/burst", &[Arg::Float(0.5), Arg::Int(300)]).unwrap();
+        assert_eq!(state.overrides.burst, Some(0.5));
+        let until = state.burst_until.unwrap();
+        assert!(until > Instant::now() + Duration::from_millis(200));
+        assert!(until <= Instant::now() + Duration::from_millis(300));
+        // Nothing anyone sends makes it panic
+        for ms in [f32::INFINITY, f32::MAX, f32::NAN, -1.0] {
+            send(
+                &mut state,
+                "/evil/burst",
+                &[Arg::Float(1.0), Arg::Float(ms)],
+            )
+            .unwrap();
+            let until = state.burst_until.unwrap();
+            assert!(until <= Instant::now() + MAX_BURST, "{ms}");
+        }
+    }
+
+    #[test]
+    fn rejects_wrong_arguments() {
+        let mut state = new_state();
+        let text = || Arg::String("x".to_owned());
+        assert!(send(&mut state, "/evil/shake", &[text()]).is_err());
+        assert!(send(&mut state, "/evil/message", &[Arg::Int(1)]).is_err());
+        assert!(send(&mut state, "/evil/burst", &[Arg::Float(1.0)]).is_err());
+        assert!(send(&mut state, "/evil/seek", &[]).is_err());
+        assert!(send(&mut state, "/evil/input", &[text()]).is_err());
+        assert!(send(&mut state, "/evil/unknown", &[]).is_err());
+    }
+}
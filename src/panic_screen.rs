@@ -0,0 +1,75 @@
+use std::{sync::Mutex, time::Duration};
+
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::Point,
+    mono_font::{ascii::FONT_6X10, MonoTextStyle},
+    pixelcolor::Rgb565,
+    text::{Baseline, Text},
+    Drawable,
+};
+
+const FONT: embedded_graphics::mono_font::MonoFont = FONT_6X10;
+
+fn draw(lcd: &mut impl DrawTarget<Color = Rgb565>, location: &str, payload: &str) {
+    let bb = lcd.bounding_box();
+    let chars_per_line = (bb.size.width / FONT.character_size.width).max(1) as usize;
+    let max_lines = (bb.size.height / FONT.character_size.height) as usize;
+
+    let header = ["*** PANIC ***", location];
+    let footer = ["rebooting..."];
+    let payload_lines = max_lines.saturating_sub(header.len() + footer.len());
+    let payload = payload.chars().collect::<Vec<_>>();
+    let payload = payload
+        .chunks(chars_per_line)
+        .map(|chunk| chunk.iter().collect::<String>())
+        .take(payload_lines)
+        .collect::<Vec<_>>();
+
+    // Errors are ignored, there's nothing sensible to do about them while panicking
+    // Whatever panicked may be holding the theme
+    let [background, text] = crate::theme::try_current().unwrap_or_default().panic();
+    let _ = lcd.clear(background);
+    let style = MonoTextStyle::new(&FONT, text);
+    let lines = header
+        .iter()
+        .copied()
+        .chain(payload.iter().map(String::as_str))
+        .chain(footer);
+    for (idx, line) in lines.enumerate() {
+        let pos = bb.top_left + Point::new(0, (idx as u32 * FONT.character_size.height) as i32);
+        let _ = Text::with_baseline(line, pos, style, Baseline::Top).draw(lcd);
+    }
+}
+
+/// Installs a panic hook that shows the panic message on `lcd` and keeps it there for `hold`
+/// before letting the panic continue. The default hook still runs first, so the serial log
+/// gets the message as well.
+pub fn install<D>(lcd: D, hold: Duration)
+where
+    D: DrawTarget<Color = Rgb565> + Send + 'static,
+{
+    let lcd = Mutex::new(lcd);
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+
+        let location = info
+            .location()
+            .map(|l| format!("{}:{}", l.file(), l.line()))
+            .unwrap_or_else(|| "unknown location".to_owned());
+        let payload = if let Some(s) = info.payload().downcast_ref::<&str>() {
+            s
+        } else if let Some(s) = info.payload().downcast_ref::<String>() {
+            s.as_str()
+        } else {
+            "(no message)"
+        };
+
+        // A panic while drawing the panic screen would deadlock otherwise
+        if let Ok(mut lcd) = lcd.try_lock() {
+            draw(&mut *lcd, &location, payload);
+            std::thread::sleep(hold);
+        }
+    }));
+}
@@ -1,7 +1,18 @@
-use std::time::Duration;
+use std::{
+    fmt,
+    net::IpAddr,
+    time::{Duration, Instant},
+};
 
 use anyhow::Result;
-use embedded_graphics::{draw_target::DrawTarget, pixelcolor::Rgb565};
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::Size,
+    pixelcolor::{Rgb565, Rgb888, RgbColor},
+    primitives::Rectangle,
+};
+
+use crate::brightness_cap;
 
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
 pub struct Brightness(f32);
@@ -18,23 +29,461 @@ impl From<Brightness> for f32 {
     }
 }
 
+/// Where the time goes, for debugging purposes
+#[derive(Clone, Debug, Default)]
+pub struct FrameStats {
+    pub scene: &'static str,
+    pub glitchiness: usize,
+    /// How far into the current scene, 0..1
+    pub progress: f32,
+    /// How angry the android is right now, 0..1
+    pub rage: f32,
+    /// What the build timer shows, None if it's off the charts
+    pub shown_time: Option<Duration>,
+    /// Time between starts of this and the previous frame
+    pub frame_time: Duration,
+    pub clear: Duration,
+    /// Text and images
+    pub text: Duration,
+    /// Glitch, noise and overlays
+    pub effects: Duration,
+    pub flush: Duration,
+    /// Frames rendered but not shown since startup, to keep up with the frame budget
+    pub skipped_frames: u32,
+}
+
 pub trait LED {
-    fn set_brightness(&mut self, brightness: Brightness) -> Result<()>;
+    /// Shows `brightness` as it is, regardless of the ceiling. RGB LEDs light up red, like the
+    /// single color ones in the eyes of the android.
+    fn write_brightness(&mut self, brightness: Brightness) -> Result<()>;
+
+    /// RGB LEDs show `color`, single color ones shine as bright as its brightest channel
+    fn write_color(&mut self, color: Rgb888) -> Result<()> {
+        let brightest = color.r().max(color.g()).max(color.b());
+        self.write_brightness((f32::from(brightest) / 255.0).into())
+    }
+
+    /// Never brighter than `[brightness]` in config.toml allows
+    fn set_brightness(&mut self, brightness: Brightness) -> Result<()> {
+        self.write_brightness(brightness_cap::limit(brightness))
+    }
+
+    fn set_color(&mut self, color: Rgb888) -> Result<()> {
+        self.write_color(brightness_cap::limit_color(color))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InputEvent {
+    /// Move along the escalation timeline by given number of steps. Negative values go back.
+    Scrub(i32),
+    /// Jump to that fraction of the escalation timeline, 0..1
+    Seek(f32),
+    TogglePause,
+    /// Device got shaken. Strength is roughly 0..1, but may exceed 1 for violent shakes.
+    Shake(f32),
+    /// Someone is being nice to the android
+    Pet,
+    /// Someone is being mean to the android
+    Poke,
+    NextScene,
+    /// Change playback speed by given number of steps. Negative values slow down.
+    Speed(i32),
+    /// Change LED brightness by given number of steps. Negative values dim.
+    Brightness(i32),
+    ToggleLogOverlay,
+    /// Show or hide the statistics screen
+    ToggleStats,
+    /// Show or hide heap and stack usage on top of the LCD
+    ToggleDiagnostics,
+    /// Go to sleep right away, instead of waiting for the idle timeout
+    Sleep,
+    /// Save the next frame to the `--dump-frame` path
+    DumpFrame,
+    /// Whole escalation in half a minute, followed by every scene there is
+    ToggleDemo,
+    /// Show or hide the name badge
+    ToggleBadge,
+    /// Easter egg, also triggered by a secret sequence of other events
+    Lunch,
+}
+
+/// Read-only files, shipped separately from the program
+pub trait Storage {
+    /// None if there's no such file. Paths are relative, with `/` as the separator.
+    fn read(&mut self, path: &str) -> Result<Option<Vec<u8>>>;
+}
+
+/// For builds without any storage
+impl Storage for () {
+    fn read(&mut self, _path: &str) -> Result<Option<Vec<u8>>> {
+        Ok(None)
+    }
+}
+
+pub trait Input {
+    /// Returns the next pending event, if any. Must not block.
+    fn poll(&mut self) -> Result<Option<InputEvent>>;
+}
+
+/// For input devices some builds don't have
+impl Input for () {
+    fn poll(&mut self) -> Result<Option<InputEvent>> {
+        Ok(None)
+    }
+}
+
+/// For optional input devices that may not be connected
+impl<T: Input> Input for Option<T> {
+    fn poll(&mut self) -> Result<Option<InputEvent>> {
+        match self {
+            Some(input) => input.poll(),
+            None => Ok(None),
+        }
+    }
+}
+
+/// For platforms with multiple input devices. Earlier ones take priority.
+impl<A: Input, B: Input> Input for (A, B) {
+    fn poll(&mut self) -> Result<Option<InputEvent>> {
+        match self.0.poll()? {
+            Some(event) => Ok(Some(event)),
+            None => self.1.poll(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct BatteryStatus {
+    pub voltage: f32,
+    /// Estimated, 0..1
+    pub charge: f32,
+}
+
+/// Environment measurements, fields are None for sensors the platform doesn't have
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SensorReadings {
+    /// In °C
+    pub temperature: Option<f32>,
+}
+
+/// Memory usage, for tracking down leaks. Fields are None where the platform can't tell.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryStats {
+    /// Bytes left on the heap
+    pub heap_free: Option<usize>,
+    /// Lowest `heap_free` since boot
+    pub heap_free_min: Option<usize>,
+    /// Biggest allocation that would succeed right now. Much lower than `heap_free` means the
+    /// heap is fragmented.
+    pub largest_free_block: Option<usize>,
+    /// Bytes of RAM taken by the whole process, on platforms without a heap of their own
+    pub resident: Option<usize>,
+    /// Task name, and the least free stack it ever had in bytes
+    pub stacks: Vec<(&'static str, usize)>,
+}
+
+/// Escalation progress kept across a deep sleep
+#[derive(Clone, Copy, Debug)]
+pub struct ResumeState {
+    /// Real time spent in the escalation so far, not counting sleep
+    pub elapsed: Duration,
+    pub timeline_pos: f32,
+}
+
+/// Identifies one of the displays of a platform
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DisplayId(pub usize);
+
+impl DisplayId {
+    /// The main LCD, present on every platform
+    pub const MAIN: DisplayId = DisplayId(0);
+    /// Small secondary display for the eyes
+    pub const EYES: DisplayId = DisplayId(1);
 }
 
 pub trait Platform {
+    /// What drawing on the displays fails with, as the driver reports it
+    type DisplayError: fmt::Debug;
+
+    /// Which one this is, e.g. for the boot splash
+    fn name(&self) -> &'static str;
+    /// Where others on the network can reach this one, None if it's not on one (yet)
+    fn ip_address(&mut self) -> Option<IpAddr> {
+        None
+    }
     fn sleep(&mut self, duration: Duration);
-    fn lcd(&mut self) -> &mut impl DrawTarget<Color = Rgb565>;
+    /// None if the platform doesn't have such display. All displays of a platform have the same
+    /// type, platforms mixing different panels need to wrap them in an enum.
+    fn display(
+        &mut self,
+        id: DisplayId,
+    ) -> Option<&mut impl DrawTarget<Color = Rgb565, Error = Self::DisplayError>>;
+    /// Shorthand for the main display
+    fn lcd(&mut self) -> &mut impl DrawTarget<Color = Rgb565, Error = Self::DisplayError> {
+        self.display(DisplayId::MAIN)
+            .expect("every platform has a main display")
+    }
+    /// Sends `pixels` to `area` of the main display, row by row. `area` needs to be within the
+    /// display. Platforms whose driver can stream them straight into an address window do that,
+    /// the rest go through `fill_contiguous`.
+    fn write_window(
+        &mut self,
+        area: &Rectangle,
+        pixels: &[Rgb565],
+    ) -> Result<(), Self::DisplayError> {
+        self.lcd().fill_contiguous(area, pixels.iter().copied())
+    }
+    /// What a failed draw means for recovering from it. Unless the platform knows better, it's
+    /// a glitch on the bus and worth another try.
+    fn display_error(&self, error: Self::DisplayError) -> PlatformError {
+        PlatformError::Bus(format!("{error:?}"))
+    }
+    /// Sets the LCD backlight as it is, regardless of the ceiling. Ones that can only be on or
+    /// off are off at 0, platforms without any control over it ignore this.
+    fn write_backlight(&mut self, _brightness: Brightness) -> Result<()> {
+        Ok(())
+    }
+    /// Dims the LCD backlight, for effects. Never brighter than `[brightness]` in config.toml
+    /// allows.
+    fn set_backlight(&mut self, brightness: Brightness) -> Result<()> {
+        self.write_backlight(brightness_cap::limit(brightness))
+    }
     fn led0(&mut self) -> &mut impl LED;
     fn led1(&mut self) -> &mut impl LED;
+    fn input(&mut self) -> &mut impl Input;
+    /// Files with assets and config
+    fn storage(&mut self) -> &mut impl Storage;
+    /// Whether any of those files changed since the last call, on platforms that watch them
+    fn assets_changed(&mut self) -> bool {
+        false
+    }
+    /// Must be called regularly, otherwise the platform may assume the program hung and reboot
+    fn feed_watchdog(&mut self) -> Result<()>;
+    /// Attempts to bring an unresponsive LCD back to a working state
+    fn reset_lcd(&mut self) -> Result<()>;
+    /// Memory for the frame buffer of the main display, for platforms that don't want it on
+    /// the heap. None to allocate it there.
+    fn frame_buffer(&mut self) -> Option<&'static mut [Rgb565]> {
+        None
+    }
+    /// Size of an off-screen canvas for scenes, larger than the main display by a whole
+    /// multiple, if there's RAM to spare for one. None if there isn't.
+    fn virtual_canvas_size(&mut self) -> Option<Size> {
+        None
+    }
+    /// Called once per frame, for platforms that can display debug info
+    fn report_frame_stats(&mut self, stats: &FrameStats);
+    /// If true, the program should stop drawing and return from main
+    fn exit_requested(&self) -> bool;
+    /// Returns updated tweaks if they changed since the last call
+    fn poll_tweaks(&mut self) -> Option<crate::tweaks::Tweaks>;
+    /// Overrides some of the tweaks loaded from config, before they're first used
+    fn adjust_tweaks(&self, _tweaks: &mut crate::tweaks::Tweaks) {}
+    /// Called after each escalation, once its ending and the scenes following it are over
+    fn cycle_done(&mut self) {}
+    /// None if not battery powered
+    fn battery(&mut self) -> Option<BatteryStatus>;
+    /// None if the platform has no sensors at all. May block for a bit, call sparingly.
+    fn sensors(&mut self) -> Option<SensorReadings> {
+        None
+    }
+    /// None if the platform can't tell at all. May take a bit, call sparingly.
+    fn memory_stats(&mut self) -> Option<MemoryStats> {
+        None
+    }
+    /// Reads a value saved with `store`, None if there's none
+    fn load(&mut self, _key: &str) -> Result<Option<Vec<u8>>> {
+        Ok(None)
+    }
+    /// Saves a small value that survives reboots, on platforms with persistent storage. Keys
+    /// are at most 15 characters long.
+    fn store(&mut self, _key: &str, _value: &[u8]) -> Result<()> {
+        Ok(())
+    }
+    /// Sleeps until `wake_after` passes or any input arrives. The input that woke the platform
+    /// up is swallowed.
+    fn light_sleep(&mut self, wake_after: Option<Duration>) -> Result<()> {
+        const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+        let start = Instant::now();
+        while !(self.exit_requested() || wake_after.is_some_and(|d| start.elapsed() >= d)) {
+            self.feed_watchdog()?;
+            if self.input().poll()?.is_some() {
+                break;
+            }
+            self.sleep(POLL_INTERVAL);
+        }
+        Ok(())
+    }
+    /// Powers down until reset, `wake_after` passes or a button gets pressed. Waking up restarts
+    /// the program, with `resume` available from `resume_state`. Platforms that can't do that
+    /// request an exit instead.
+    fn deep_sleep(&mut self, wake_after: Option<Duration>, resume: Option<ResumeState>);
+    /// Whether `deep_sleep` actually sleeps, rather than requesting an exit
+    fn can_deep_sleep(&self) -> bool {
+        false
+    }
+    /// What was passed to `deep_sleep` before waking up, None after a cold boot
+    fn resume_state(&mut self) -> Option<ResumeState> {
+        None
+    }
+    /// Makes panic messages show up on the LCD.
+    ///
+    /// # Safety
+    ///
+    /// Implementations may keep a pointer to `self`, which must not be moved nor dropped
+    /// afterwards.
+    unsafe fn install_panic_screen(&mut self);
 }
 
-#[cfg(target_arch = "xtensa")]
+mod error;
+pub use error::PlatformError;
+
+#[cfg(any(target_os = "espidf", target_os = "linux"))]
+mod panic_lcd;
+
+#[cfg(target_os = "espidf")]
 mod esp32;
-#[cfg(target_arch = "xtensa")]
+#[cfg(target_os = "espidf")]
+pub use esp32::device_id;
+#[cfg(target_os = "espidf")]
+pub use esp32::http_get;
+#[cfg(target_os = "espidf")]
+pub use esp32::http_post;
+#[cfg(target_os = "espidf")]
 pub use esp32::new_platform as new_esp32;
 
+#[cfg(target_os = "linux")]
+mod fbdev;
+#[cfg(target_os = "linux")]
+mod linux_http;
+#[cfg(target_os = "linux")]
+mod linux_spi;
+#[cfg(target_os = "linux")]
+mod machine_id;
 #[cfg(target_os = "linux")]
 mod pc;
 #[cfg(target_os = "linux")]
+mod proc_status;
+#[cfg(target_os = "linux")]
+mod shared_buffer;
+#[cfg(target_os = "linux")]
+mod state_dir;
+#[cfg(target_os = "linux")]
+mod storage_dir;
+#[cfg(target_os = "linux")]
+mod term;
+#[cfg(target_os = "linux")]
+mod thermal_zone;
+#[cfg(target_os = "linux")]
+mod video;
+#[cfg(target_os = "linux")]
+pub use fbdev::new_platform as new_fbdev;
+#[cfg(target_os = "linux")]
+pub use linux_http::get as http_get;
+#[cfg(target_os = "linux")]
+pub use linux_http::post as http_post;
+#[cfg(target_os = "linux")]
+pub use linux_spi::new_platform as new_linux_spi;
+#[cfg(target_os = "linux")]
+pub use machine_id::short as device_id;
+#[cfg(target_os = "linux")]
 pub use pc::new_platform as new_pc;
+#[cfg(target_os = "linux")]
+pub use term::new_platform as new_term;
+#[cfg(target_os = "linux")]
+pub use video::new_platform as new_video;
+
+#[cfg(all(target_os = "linux", feature = "eg-simulator"))]
+mod eg_simulator;
+#[cfg(all(target_os = "linux", feature = "eg-simulator"))]
+pub use eg_simulator::new_platform as new_eg_simulator;
+
+/// Backends available on Linux, chosen at runtime
+#[cfg(target_os = "linux")]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LinuxBackend {
+    /// glium window with the mascot
+    Window,
+    /// 24-bit ANSI colors in the terminal
+    Term,
+    /// Linux framebuffer, e.g. a Raspberry Pi HDMI output
+    Fbdev,
+    /// ST7735 LCD wired to SPI and GPIO pins, e.g. on a Raspberry Pi
+    Spi,
+    /// Offscreen, encoded to a video file
+    Video,
+    #[cfg(feature = "eg-simulator")]
+    EgSimulator,
+}
+
+#[cfg(target_os = "linux")]
+impl LinuxBackend {
+    fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "window" => Ok(LinuxBackend::Window),
+            "term" => Ok(LinuxBackend::Term),
+            "fbdev" => Ok(LinuxBackend::Fbdev),
+            "spi" => Ok(LinuxBackend::Spi),
+            #[cfg(feature = "eg-simulator")]
+            "eg-simulator" => Ok(LinuxBackend::EgSimulator),
+            _ => anyhow::bail!("unknown platform: {name}"),
+        }
+    }
+
+    /// `--render-video` picks the video, otherwise taken from `--platform <name>` or
+    /// EVIL_ANDROID_PLATFORM. If neither is given, picks a window in a graphical session, the
+    /// framebuffer if there is one, or the terminal.
+    pub fn select() -> Result<Self> {
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            if arg == "--render-video" {
+                return Ok(LinuxBackend::Video);
+            } else if arg == "--platform" {
+                let name = args
+                    .next()
+                    .ok_or_else(|| anyhow::Error::msg("--platform needs a value"))?;
+                return Self::from_name(&name);
+            }
+        }
+        if let Ok(name) = std::env::var("EVIL_ANDROID_PLATFORM") {
+            return Self::from_name(&name);
+        }
+
+        let env_set = |name| std::env::var_os(name).is_some();
+        if env_set("WAYLAND_DISPLAY") || env_set("DISPLAY") {
+            Ok(LinuxBackend::Window)
+        } else if std::path::Path::new(fbdev::DEVICE).exists() {
+            Ok(LinuxBackend::Fbdev)
+        } else {
+            Ok(LinuxBackend::Term)
+        }
+    }
+}
+
+/// Of the interface that the default route goes through. Connecting a UDP socket doesn't send
+/// anything, it just picks the interface.
+#[cfg(target_os = "linux")]
+fn local_ip_address() -> Option<IpAddr> {
+    let socket = std::net::UdpSocket::bind(("0.0.0.0", 0)).ok()?;
+    socket.connect(("192.0.2.1", 9)).ok()?;
+    Some(socket.local_addr().ok()?.ip())
+}
+
+/// Of the LCD the simulators show: 160x128 like the real one, unless EVIL_ANDROID_LCD_SIZE says
+/// `<w>x<h>`, e.g. to see how a 128x64 OLED or a 240x240 panel would look
+#[cfg(target_os = "linux")]
+fn simulated_lcd_size() -> Result<Size> {
+    const DEFAULT: Size = Size::new(160, 128);
+    let Ok(value) = std::env::var("EVIL_ANDROID_LCD_SIZE") else {
+        return Ok(DEFAULT);
+    };
+    value
+        .split_once('x')
+        .and_then(|(w, h)| Some(Size::new(w.parse().ok()?, h.parse().ok()?)))
+        .filter(|size| size.width > 0 && size.height > 0)
+        .ok_or_else(|| {
+            PlatformError::OutOfRange(format!("invalid EVIL_ANDROID_LCD_SIZE: {value}")).into()
+        })
+}
@@ -0,0 +1,245 @@
+use std::{collections::VecDeque, convert::Infallible, net::IpAddr, time::Duration};
+
+use anyhow::Result;
+use embedded_graphics::{
+    geometry::{Dimensions, Point, Size},
+    pixelcolor::Rgb565,
+    prelude::{DrawTarget, PointsIter, Primitive, RgbColor},
+    primitives::{Circle, PrimitiveStyle},
+    Drawable, Pixel,
+};
+use embedded_graphics_simulator::{
+    sdl2::Keycode, OutputSettingsBuilder, SimulatorDisplay, SimulatorEvent, Window,
+};
+
+use super::{
+    storage_dir::StorageDir, BatteryStatus, Brightness, DisplayId, FrameStats, InputEvent,
+    MemoryStats, ResumeState, SensorReadings,
+};
+use crate::{battery::SimulatedBattery, console::Console};
+
+// Strip above the LCD where the LEDs are drawn
+const LED_STRIP_HEIGHT: u32 = 12;
+const LED_DIAMETER: u32 = 8;
+const WINDOW_SCALE: u32 = 4;
+
+pub struct SimulatorLED(Brightness);
+
+impl super::LED for SimulatorLED {
+    fn write_brightness(&mut self, brightness: Brightness) -> Result<()> {
+        self.0 = brightness;
+        Ok(())
+    }
+}
+
+/// Key presses collected by Platform::sleep, which is where the window gets updated
+pub struct KeyboardInput {
+    pending: VecDeque<InputEvent>,
+}
+
+impl super::Input for KeyboardInput {
+    fn poll(&mut self) -> Result<Option<InputEvent>> {
+        Ok(self.pending.pop_front())
+    }
+}
+
+/// Same bindings as the glium simulator
+fn key_to_input_event(key: Keycode) -> Option<InputEvent> {
+    match key {
+        Keycode::Left => Some(InputEvent::Scrub(-1)),
+        Keycode::Right => Some(InputEvent::Scrub(1)),
+        Keycode::Space => Some(InputEvent::TogglePause),
+        Keycode::S => Some(InputEvent::Shake(1.0)),
+        Keycode::P => Some(InputEvent::Pet),
+        Keycode::O => Some(InputEvent::Poke),
+        Keycode::N => Some(InputEvent::NextScene),
+        Keycode::Equals => Some(InputEvent::Speed(1)),
+        Keycode::Minus => Some(InputEvent::Speed(-1)),
+        Keycode::RightBracket => Some(InputEvent::Brightness(1)),
+        Keycode::LeftBracket => Some(InputEvent::Brightness(-1)),
+        Keycode::L => Some(InputEvent::ToggleLogOverlay),
+        Keycode::M => Some(InputEvent::ToggleDiagnostics),
+        Keycode::I => Some(InputEvent::ToggleStats),
+        Keycode::Z => Some(InputEvent::Sleep),
+        Keycode::F => Some(InputEvent::DumpFrame),
+        Keycode::D => Some(InputEvent::ToggleDemo),
+        Keycode::B => Some(InputEvent::ToggleBadge),
+        _ => None,
+    }
+}
+
+/// Lighter alternative to the glium simulator, built on embedded-graphics-simulator. SDL
+/// windows must be updated from the thread that created them, so there is no window thread:
+/// the window is refreshed from `sleep`, which the draw loop calls once per frame.
+pub struct Platform {
+    lcd: SimulatorDisplay<Rgb565>,
+    // LCD contents with LEDs drawn above
+    window_display: SimulatorDisplay<Rgb565>,
+    window: Window,
+    led0: SimulatorLED,
+    led1: SimulatorLED,
+    input: (KeyboardInput, Console),
+    exit_requested: bool,
+    battery: Option<SimulatedBattery>,
+    storage: StorageDir,
+}
+
+impl Platform {
+    fn update_window(&mut self) {
+        let lcd_offset = Point::new(0, LED_STRIP_HEIGHT as i32);
+        let lcd_pixels = self.lcd.bounding_box().points().map(|p| {
+            let color = self.lcd.get_pixel(p);
+            Pixel(p + lcd_offset, color)
+        });
+        // SimulatorDisplay never fails to draw
+        let _ = self.window_display.draw_iter(lcd_pixels);
+
+        let led_y = ((LED_STRIP_HEIGHT - LED_DIAMETER) / 2) as i32;
+        let width = self.lcd.bounding_box().size.width as i32;
+        let led_positions = [
+            Point::new(width / 4, led_y),
+            Point::new(width * 3 / 4, led_y),
+        ];
+        for (pos, led) in led_positions.into_iter().zip([&self.led0, &self.led1]) {
+            let red = (f32::from(led.0) * Rgb565::MAX_R as f32) as u8;
+            let _ = Circle::new(pos, LED_DIAMETER)
+                .into_styled(PrimitiveStyle::with_fill(Rgb565::new(red, 0, 0)))
+                .draw(&mut self.window_display);
+        }
+
+        self.window.update(&self.window_display);
+        for event in self.window.events() {
+            match event {
+                SimulatorEvent::Quit => self.exit_requested = true,
+                SimulatorEvent::KeyDown {
+                    keycode,
+                    repeat: false,
+                    ..
+                } => {
+                    if let Some(event) = key_to_input_event(keycode) {
+                        self.input.0.pending.push_back(event);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+pub fn new_platform() -> Result<impl crate::platform::Platform> {
+    let logger = env_logger::Builder::from_default_env().build();
+    let log_level = logger.filter();
+    crate::log_buffer::init(logger, log_level)?;
+
+    let lcd_size = super::simulated_lcd_size()?;
+    let output_settings = OutputSettingsBuilder::new().scale(WINDOW_SCALE).build();
+    let mut platform = Platform {
+        lcd: SimulatorDisplay::new(lcd_size),
+        window_display: SimulatorDisplay::new(Size::new(
+            lcd_size.width,
+            lcd_size.height + LED_STRIP_HEIGHT,
+        )),
+        window: Window::new("evil-android", &output_settings),
+        led0: SimulatorLED(0f32.into()),
+        led1: SimulatorLED(0f32.into()),
+        input: (
+            KeyboardInput {
+                pending: VecDeque::new(),
+            },
+            Console::spawn()?,
+        ),
+        exit_requested: false,
+        battery: SimulatedBattery::from_env(),
+        storage: StorageDir::from_env(),
+    };
+    // Opens the window, events can't be polled before that
+    platform.update_window();
+    Ok(platform)
+}
+
+impl crate::platform::Platform for Platform {
+    type DisplayError = Infallible;
+
+    fn name(&self) -> &'static str {
+        "eg-simulator"
+    }
+
+    fn ip_address(&mut self) -> Option<IpAddr> {
+        super::local_ip_address()
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        self.update_window();
+        std::thread::sleep(duration);
+    }
+
+    fn display(
+        &mut self,
+        id: DisplayId,
+    ) -> Option<&mut impl DrawTarget<Color = Rgb565, Error = Self::DisplayError>> {
+        (id == DisplayId::MAIN).then_some(&mut self.lcd)
+    }
+
+    fn led0(&mut self) -> &mut impl super::LED {
+        &mut self.led0
+    }
+
+    fn led1(&mut self) -> &mut impl super::LED {
+        &mut self.led1
+    }
+
+    fn input(&mut self) -> &mut impl super::Input {
+        &mut self.input
+    }
+
+    fn storage(&mut self) -> &mut impl super::Storage {
+        &mut self.storage
+    }
+
+    fn feed_watchdog(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn reset_lcd(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn report_frame_stats(&mut self, _stats: &FrameStats) {}
+
+    fn exit_requested(&self) -> bool {
+        self.exit_requested
+    }
+
+    fn poll_tweaks(&mut self) -> Option<crate::tweaks::Tweaks> {
+        None
+    }
+
+    fn battery(&mut self) -> Option<BatteryStatus> {
+        self.battery.as_ref().map(SimulatedBattery::status)
+    }
+
+    fn sensors(&mut self) -> Option<SensorReadings> {
+        super::thermal_zone::read()
+    }
+
+    fn memory_stats(&mut self) -> Option<MemoryStats> {
+        super::proc_status::memory_stats()
+    }
+
+    fn load(&mut self, key: &str) -> Result<Option<Vec<u8>>> {
+        super::state_dir::load(key)
+    }
+
+    fn store(&mut self, key: &str, value: &[u8]) -> Result<()> {
+        super::state_dir::store(key, value)
+    }
+    fn deep_sleep(&mut self, _wake_after: Option<Duration>, _resume: Option<ResumeState>) {
+        log::info!("no deep sleep on Linux, exiting instead");
+        self.exit_requested = true;
+    }
+
+    unsafe fn install_panic_screen(&mut self) {
+        // The window is only refreshed by the draw loop, which never runs again after a panic.
+        // The default hook printing to stderr is all there is.
+    }
+}
@@ -0,0 +1,62 @@
+use std::fmt;
+
+/// What kind of hardware failure it was, so that callers can tell what's worth retrying.
+/// Platforms return these wrapped in `anyhow::Error`, `downcast_ref` gets them back.
+#[derive(Debug)]
+pub enum PlatformError {
+    /// A transfer over SPI or I2C failed. Often a glitch on the bus, worth trying again.
+    Bus(String),
+    /// Requesting or setting a GPIO failed, e.g. the data/command line of a display
+    Gpio(String),
+    /// A display didn't come up. Retrying the same call won't help, resetting it might.
+    DisplayInit(String),
+    /// A value doesn't fit where it has to go, e.g. a WiFi SSID over 32 bytes
+    OutOfRange(String),
+}
+
+impl PlatformError {
+    /// Whether doing the same thing again may work
+    pub fn is_transient(&self) -> bool {
+        matches!(self, PlatformError::Bus(_))
+    }
+
+    /// Of any error, unknown ones included. Those were always retried, so they still are.
+    pub fn is_transient_error(error: &anyhow::Error) -> bool {
+        error
+            .downcast_ref::<PlatformError>()
+            .map_or(true, PlatformError::is_transient)
+    }
+}
+
+impl fmt::Display for PlatformError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlatformError::Bus(what) => write!(f, "bus error: {what}"),
+            PlatformError::Gpio(what) => write!(f, "GPIO error: {what}"),
+            PlatformError::DisplayInit(what) => write!(f, "display init failed: {what}"),
+            PlatformError::OutOfRange(what) => write!(f, "out of range: {what}"),
+        }
+    }
+}
+
+impl std::error::Error for PlatformError {}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Context;
+
+    use super::*;
+
+    #[test]
+    fn tells_transient_errors_apart() {
+        let spi = anyhow::Error::from(PlatformError::Bus("fill_contiguous".into()));
+        assert!(PlatformError::is_transient_error(&spi));
+        let init = Err::<(), _>(PlatformError::DisplayInit("ST7735::init".into()))
+            .context("reset_lcd")
+            .unwrap_err();
+        assert!(!PlatformError::is_transient_error(&init));
+        assert!(PlatformError::is_transient_error(&anyhow::anyhow!(
+            "who knows"
+        )));
+    }
+}
@@ -1,35 +1,391 @@
-use std::time::Duration;
+use std::{cell::RefCell, ffi::CStr, rc::Rc, time::Duration};
 
 use anyhow::{Context, Result};
-use embedded_graphics::{draw_target::DrawTarget, geometry::Size, pixelcolor::Rgb565};
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::{Dimensions, Size},
+    pixelcolor::{raw::RawU16, Rgb565},
+    prelude::RawData,
+    primitives::Rectangle,
+};
 use esp_idf_hal::ledc::{config::TimerConfig, LedcDriver, LedcTimerDriver, LEDC};
+#[cfg(feature = "net")]
+use esp_idf_svc::eventloop::EspSystemEventLoop;
 use esp_idf_svc::hal::{
     delay::FreeRtos,
-    gpio::{AnyInputPin, OutputPin, PinDriver, Pins},
+    gpio::{AnyInputPin, AnyOutputPin, Output, Pin, PinDriver},
+    i2c::{I2cConfig, I2cDriver},
     peripherals::Peripherals,
     spi::{
-        config::{Config, MODE_3},
-        SpiDeviceDriver, SpiDriverConfig,
+        config::{Config, MODE_0, MODE_3},
+        SpiDeviceDriver, SpiDriverConfig, SPI2,
     },
+    task::watchdog::{TWDTConfig, TWDTDriver, WatchdogSubscription},
     units::FromValueType,
 };
+use esp_idf_svc::http::server::EspHttpServer;
+use esp_idf_svc::mdns::EspMdns;
+use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault};
+use esp_idf_svc::sntp::EspSntp;
+use esp_idf_svc::sys::{
+    esp, esp_get_free_heap_size, esp_get_minimum_free_heap_size, esp_vfs_dev_uart_use_driver,
+    heap_caps_get_largest_free_block, heap_caps_get_total_size, uart_driver_install,
+    uxTaskGetStackHighWaterMark, xTaskGetHandle, MALLOC_CAP_8BIT, MALLOC_CAP_SPIRAM,
+};
 use st7735_lcd::ST7735;
 
-use super::{Brightness, LED};
+use super::{
+    panic_lcd::PanicLcd, BatteryStatus, Brightness, DisplayId, FrameStats, Input, MemoryStats,
+    PlatformError, ResumeState, SensorReadings, LED,
+};
+use crate::{
+    console::Console,
+    orientation::{Orientation, Oriented},
+};
+
+mod accel;
+mod battery;
+#[cfg(feature = "ble")]
+mod ble;
+mod board;
+mod buttons;
+#[cfg(feature = "epaper")]
+mod epaper;
+mod flash_fs;
+#[cfg(not(esp32c3))]
+mod flusher;
+mod http;
+mod ir;
+mod mdns;
+#[cfg(feature = "ssd1306")]
+mod oled;
+#[cfg(feature = "net")]
+mod provisioning;
+#[cfg(not(esp32c3))]
+mod rotary;
+#[cfg(not(esp32c3))]
+mod sd_card;
+mod sleep;
+#[cfg(any(feature = "board-t-display", feature = "board-t-display-s3"))]
+mod st7789;
+mod thermometer;
+#[cfg(feature = "net")]
+mod wifi;
+
+pub use http::get as http_get;
+pub use http::post as http_post;
+pub use mdns::device_id;
+#[cfg(all(esp32, feature = "touch"))]
+mod touch;
+
+#[cfg(all(feature = "ssd1306", feature = "epaper"))]
+compile_error!("features ssd1306 and epaper are mutually exclusive");
+
+// Long enough to survive LCD initialization
+const WATCHDOG_TIMEOUT: Duration = Duration::from_secs(5);
+// Panic triggers a reboot afterwards anyway, but if this exceeds WATCHDOG_TIMEOUT, the reboot
+// will be caused by the watchdog instead
+const PANIC_SCREEN_HOLD: Duration = Duration::from_secs(4);
+const NVS_NAMESPACE: &str = "evil-android";
+// How the panel is mounted, read at build time like the rest of the ESP32 config
+const ROTATION: Option<&str> = option_env!("EVIL_ANDROID_ROTATION");
+const MIRROR: Option<&str> = option_env!("EVIL_ANDROID_MIRROR");
+/// Tasks whose stack usage shows up in memory stats: rendering, LCD flushing, WiFi, ESP-IDF event
+/// loop, lwIP and the HTTP server. Ones that aren't running are skipped. Threads spawned with
+/// `std::thread` are all called "pthread" in FreeRTOS, so they can't be told apart here.
+const WATCHED_TASKS: &[&CStr] = &[c"main", c"lcd_flush", c"wifi", c"sys_evt", c"tiT", c"httpd"];
+
+/// Main frame buffer, kept off the heap so that weeks of uptime don't fragment it. With the
+/// `psram` feature it goes to external RAM, which only takes zero-initialized statics, all black
+/// is fine.
+#[cfg(not(any(feature = "ssd1306", feature = "epaper")))]
+#[cfg_attr(feature = "psram", link_section = ".ext_ram.bss")]
+static FRAME_BUFFER: crate::framebuffer::StaticFrameBufferBackend<
+    Rgb565,
+    { board::PANEL.size.width as usize },
+    { board::PANEL.size.height as usize },
+> = crate::framebuffer::StaticFrameBufferBackend::new(Rgb565::new(0, 0, 0));
+
+/// Fast path for drivers that can take a block of pixels into an address window in one go. The
+/// default goes through `fill_contiguous`, which is fine for displays drawn into a buffer first.
+trait WindowedWrite: DrawTarget<Color = Rgb565> {
+    /// `pixels` fill `area` row by row, `area` is within the display
+    fn write_window(&mut self, area: &Rectangle, pixels: &[Rgb565]) -> Result<(), Self::Error> {
+        self.fill_contiguous(area, pixels.iter().copied())
+    }
+}
+
+/// LCD that can be brought back to life by re-running the initialization sequence
+trait ResettableLcd: WindowedWrite {
+    fn init(&mut self) -> Result<()>;
+    /// What a failed draw means for recovering from it
+    fn classify_error(error: Self::Error) -> PlatformError;
+    /// Called after each frame is drawn, for displays that only show some of them
+    fn end_frame(&mut self, _stats: &FrameStats) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<SPI, DC, RST> ResettableLcd for ST7735<SPI, DC, RST>
+where
+    SPI: embedded_hal::spi::SpiDevice,
+    DC: embedded_hal::digital::OutputPin,
+    RST: embedded_hal::digital::OutputPin,
+{
+    fn init(&mut self) -> Result<()> {
+        // Does a hardware reset first
+        ST7735::init(self, &mut FreeRtos)
+            .map_err(|_| PlatformError::DisplayInit("ST7735::init failed".into()))?;
+        self.set_orientation(&st7735_lcd::Orientation::Landscape)
+            .map_err(|_| PlatformError::DisplayInit("ST7735::set_orientation failed".into()))?;
+        Ok(())
+    }
+
+    /// The driver drops the SPI error on the way, all that's left is that a transfer failed
+    fn classify_error(_error: Self::Error) -> PlatformError {
+        PlatformError::Bus("ST7735 transfer failed".into())
+    }
+}
+
+/// The driver's `fill_contiguous` sends every pixel in an SPI transaction of its own, which took
+/// most of the frame time. This sets the address window once and streams the pixels into it.
+impl<SPI, DC, RST> WindowedWrite for ST7735<SPI, DC, RST>
+where
+    SPI: embedded_hal::spi::SpiDevice,
+    DC: embedded_hal::digital::OutputPin,
+    RST: embedded_hal::digital::OutputPin,
+{
+    fn write_window(&mut self, area: &Rectangle, pixels: &[Rgb565]) -> Result<(), ()> {
+        let Some(bottom_right) = area.bottom_right() else {
+            return Ok(());
+        };
+        self.set_address_window(
+            area.top_left.x as u16,
+            area.top_left.y as u16,
+            bottom_right.x as u16,
+            bottom_right.y as u16,
+        )?;
+        self.write_pixels_buffered(pixels.iter().map(|&color| RawU16::from(color).into_inner()))
+    }
+}
+
+impl<Lcd: WindowedWrite> WindowedWrite for Oriented<Lcd> {
+    fn write_window(&mut self, area: &Rectangle, pixels: &[Rgb565]) -> Result<(), Lcd::Error> {
+        self.write_turned(area, pixels, Lcd::write_window)
+    }
+}
+
+/// Panels mounted any other way than landscape are still initialized in landscape, frames get
+/// turned on the way to them
+impl<Lcd: ResettableLcd> ResettableLcd for Oriented<Lcd> {
+    fn init(&mut self) -> Result<()> {
+        self.inner_mut().init()
+    }
+
+    fn classify_error(error: Lcd::Error) -> PlatformError {
+        Lcd::classify_error(error)
+    }
+
+    fn end_frame(&mut self, stats: &FrameStats) -> Result<()> {
+        self.inner_mut().end_frame(stats)
+    }
+}
+
+/// Files on the SD card override the ones in the flash filesystem
+struct AssetStorage {
+    #[cfg(not(esp32c3))]
+    sd_card: Option<sd_card::SdCard>,
+    flash_fs: Option<flash_fs::FlashFs>,
+}
+
+impl super::Storage for AssetStorage {
+    fn read(&mut self, path: &str) -> Result<Option<Vec<u8>>> {
+        #[cfg(not(esp32c3))]
+        if let Some(sd_card) = &self.sd_card {
+            if let Some(data) = sd_card.read(path)? {
+                return Ok(Some(data));
+            }
+        }
+        match &self.flash_fs {
+            Some(flash_fs) => flash_fs.read(path),
+            None => Ok(None),
+        }
+    }
+}
+
+/// None if there's no such file
+fn read_file(path: &str) -> Result<Option<Vec<u8>>> {
+    match std::fs::read(path) {
+        Ok(data) => Ok(Some(data)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("cannot read {path}")),
+    }
+}
+
+/// I2C bus with the accelerometer, thermometer and whatever else the board has there
+type SensorBus = Rc<RefCell<I2cDriver<'static>>>;
 
-impl LED for LedcDriver<'_> {
-    fn set_brightness(&mut self, brightness: Brightness) -> Result<()> {
-        let led_duty = (f32::from(brightness) * self.get_max_duty() as f32) as u32;
-        Ok(self.set_duty(led_duty)?)
+/// Whatever keeps the LCD backlight on
+trait Backlight {
+    fn set_on(&mut self, on: bool) -> Result<()>;
+    /// Ones that can't be dimmed are on for anything above 0
+    fn set_level(&mut self, brightness: Brightness) -> Result<()> {
+        self.set_on(f32::from(brightness) > 0.0)
     }
 }
 
-pub struct Platform<Lcd: DrawTarget<Color = Rgb565>, LcdLedPin, Led0Pin: LED, Led1Pin: LED> {
+/// For displays without a backlight
+impl Backlight for () {
+    fn set_on(&mut self, _on: bool) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// For boards where the backlight may be always on
+impl Backlight for Option<PinDriver<'static, AnyOutputPin, Output>> {
+    fn set_on(&mut self, on: bool) -> Result<()> {
+        if let Some(pin) = self {
+            pin.set_level(on.into())
+                .context("PinDriver::set_level failed for lcd_led")?;
+        }
+        Ok(())
+    }
+}
+
+/// Backlight on a LEDC channel, which effects can dim
+impl Backlight for Option<Led<'static>> {
+    fn set_on(&mut self, on: bool) -> Result<()> {
+        self.set_level(Brightness::from(f32::from(u8::from(on))))
+    }
+
+    fn set_level(&mut self, brightness: Brightness) -> Result<()> {
+        match self {
+            Some(led) => led.write_brightness(brightness),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Power, RD, CS and backlight of a parallel LCD
+impl Backlight for [PinDriver<'static, AnyOutputPin, Output>; 4] {
+    fn set_on(&mut self, on: bool) -> Result<()> {
+        self[3]
+            .set_level(on.into())
+            .context("PinDriver::set_level failed for lcd_led")
+    }
+}
+
+/// LED on a LEDC channel. All supported chips have the same LEDC timers and channels.
+pub struct Led<'d> {
+    driver: LedcDriver<'d>,
+    active_low: bool,
+}
+
+impl LED for Led<'_> {
+    fn write_brightness(&mut self, brightness: Brightness) -> Result<()> {
+        let mut brightness = f32::from(brightness);
+        if self.active_low {
+            brightness = 1.0 - brightness;
+        }
+        let led_duty = (brightness * self.driver.get_max_duty() as f32) as u32;
+        Ok(self.driver.set_duty(led_duty)?)
+    }
+}
+
+pub struct Platform<
+    Lcd: ResettableLcd,
+    LcdLedPin: Backlight,
+    Led0Pin: LED,
+    Led1Pin: LED,
+    Inputs: Input,
+> {
     lcd: Lcd,
     // So an instance of this must be kept around, because dropping it after init turns LED backlight off again
-    _lcd_led: LcdLedPin,
+    lcd_led: LcdLedPin,
     led0: Led0Pin,
     led1: Led1Pin,
+    inputs: Inputs,
+    battery: Option<battery::Battery>,
+    thermometer: Option<thermometer::Thermometer>,
+    storage: AssetStorage,
+    // None if NVS couldn't be initialized, nothing gets saved then
+    nvs: Option<EspNvs<NvsDefault>>,
+    wake_sources: sleep::WakeSources,
+    // Keeps serving screenshots as long as it lives
+    _http_server: Option<EspHttpServer<'static>>,
+    // Same for mDNS advertisement
+    _mdns: Option<EspMdns>,
+    // And for keeping the wall clock in sync
+    _sntp: Option<EspSntp<'static>>,
+    watchdog: WatchdogSubscription<'static>,
+}
+
+/// ST7735 SPI LCD. Its backlight is left to the caller, on a LEDC channel.
+#[cfg(not(any(
+    feature = "ssd1306",
+    feature = "epaper",
+    feature = "board-t-display",
+    feature = "board-t-display-s3"
+)))]
+fn new_st7735(
+    spi: SPI2,
+    pins: board::SpiLcdPins,
+    panel: &board::Panel,
+) -> Result<impl ResettableLcd + 'static> {
+    let lcd_spi = SpiDeviceDriver::new_single(
+        spi,
+        pins.sclk,
+        pins.mosi,
+        <Option<AnyInputPin>>::None,
+        Some(pins.cs),
+        &SpiDriverConfig::new(),
+        &Config::new().baudrate(26.MHz().into()).data_mode(MODE_3),
+    )
+    .context("SpiDeviceDriver::new_single failed")?;
+    let lcd_reset =
+        PinDriver::output(pins.reset).context("PinDriver::output failed for lcd_reset")?;
+    let lcd_a0 = PinDriver::output(pins.dc).context("PinDriver::output failed for lcd_a0")?;
+
+    let mut lcd = ST7735::new(
+        lcd_spi,
+        lcd_a0,
+        lcd_reset,
+        panel.rgb,
+        panel.inverted,
+        panel.size.width.try_into().unwrap(),
+        panel.size.height.try_into().unwrap(),
+    );
+    lcd.set_offset(panel.offset.0, panel.offset.1);
+
+    log::info!("initializing LCD");
+    ResettableLcd::init(&mut lcd)?;
+    Ok(lcd)
+}
+
+/// Waveshare 2.9" e-paper module, wired in place of the LCD
+#[cfg(feature = "epaper")]
+fn new_epaper(
+    spi: SPI2,
+    pins: board::SpiLcdPins,
+    busy: AnyInputPin,
+) -> Result<impl ResettableLcd + 'static> {
+    let spi = SpiDeviceDriver::new_single(
+        spi,
+        pins.sclk,
+        pins.mosi,
+        <Option<AnyInputPin>>::None,
+        Some(pins.cs),
+        &SpiDriverConfig::new(),
+        &Config::new().baudrate(4.MHz().into()).data_mode(MODE_0),
+    )
+    .context("SpiDeviceDriver::new_single failed")?;
+    let busy = PinDriver::input(busy).context("PinDriver::input failed for e-paper busy")?;
+    let dc = PinDriver::output(pins.dc).context("PinDriver::output failed for e-paper DC")?;
+    let reset =
+        PinDriver::output(pins.reset).context("PinDriver::output failed for e-paper reset")?;
+
+    log::info!("initializing e-paper");
+    epaper::Epaper::new(spi, busy, dc, reset)
 }
 
 pub fn new_platform() -> Result<impl super::Platform> {
@@ -38,82 +394,336 @@ pub fn new_platform() -> Result<impl super::Platform> {
     esp_idf_svc::sys::link_patches();
 
     // Bind the log crate to the ESP Logging facilities
-    esp_idf_svc::log::EspLogger::initialize_default();
+    crate::log_buffer::init(esp_idf_svc::log::EspLogger::new(), log::LevelFilter::Info)?;
+
+    // By default stdin does not block, making the console spin reading nothing
+    unsafe {
+        let uart = esp_idf_svc::sys::CONFIG_ESP_CONSOLE_UART_NUM as _;
+        esp!(uart_driver_install(
+            uart,
+            512,
+            0,
+            0,
+            std::ptr::null_mut(),
+            0
+        ))
+        .context("uart_driver_install failed")?;
+        esp_vfs_dev_uart_use_driver(uart);
+    }
 
     let Peripherals {
         spi2: lcd_spi,
+        #[cfg(not(esp32c3))]
+            spi3: sd_spi,
+        #[cfg(not(esp32c3))]
+            pcnt0: encoder_pcnt,
+        i2c0: sensors_i2c,
+        adc1,
+        #[cfg(feature = "ssd1306")]
+            i2c1: oled_i2c,
+        rmt,
+        twdt,
+        modem,
         ledc:
             LEDC {
                 timer0: led_timer,
                 channel0: led_channel0,
                 channel1: led_channel1,
+                #[cfg(not(any(
+                    feature = "ssd1306",
+                    feature = "epaper",
+                    feature = "board-t-display",
+                    feature = "board-t-display-s3"
+                )))]
+                    channel2: backlight_channel,
                 ..
             },
-        pins:
-            Pins {
-                gpio13: lcd_spi_mosi,
-                gpio14: lcd_spi_scl,
-                gpio15: lcd_spi_cs,
-                gpio16: lcd_reset,
-                gpio17: lcd_a0,
-                gpio18: lcd_led,
-                gpio19: led_pin0,
-                gpio21: led_pin1,
-                ..
-            },
+        pins,
         ..
     } = Peripherals::take().context("Peripherals::take failed")?;
+    let pins = board::Board::take(pins);
+    // RX-capable RMT channels differ between chips
+    #[cfg(not(esp32c3))]
+    let ir_channel = rmt.channel4;
+    #[cfg(esp32c3)]
+    let ir_channel = rmt.channel2;
 
     let timer_config = TimerConfig::default().frequency(5000.Hz().into());
     let ledc_timer =
         LedcTimerDriver::new(led_timer, &timer_config).context("LedcTimerDriver::new failed")?;
-    let led0 = LedcDriver::new(led_channel0, &ledc_timer, led_pin0)
-        .context("LedcDriver::new failed for LED0")?;
-    let led1 = LedcDriver::new(led_channel1, &ledc_timer, led_pin1)
-        .context("LedcDriver::new faled for LED1")?;
+    let [led_pin0, led_pin1] = pins.leds;
+    let led0 = Led {
+        driver: LedcDriver::new(led_channel0, &ledc_timer, led_pin0)
+            .context("LedcDriver::new failed for LED0")?,
+        active_low: pins.leds_active_low[0],
+    };
+    let led1 = Led {
+        driver: LedcDriver::new(led_channel1, &ledc_timer, led_pin1)
+            .context("LedcDriver::new faled for LED1")?,
+        active_low: pins.leds_active_low[1],
+    };
 
-    let lcd_spi = SpiDeviceDriver::new_single(
-        lcd_spi,
-        lcd_spi_scl,
-        lcd_spi_mosi,
-        <Option<AnyInputPin>>::None,
-        Some(lcd_spi_cs),
-        &SpiDriverConfig::new(),
-        &Config::new().baudrate(26.MHz().into()).data_mode(MODE_3),
+    // Some boards power the LCD from a PMIC on this bus, so it goes first
+    #[allow(unused_mut)]
+    let mut sensors_i2c = I2cDriver::new(
+        sensors_i2c,
+        pins.sensors_i2c.sda,
+        pins.sensors_i2c.scl,
+        &I2cConfig::new().baudrate(400.kHz().into()),
     )
-    .context("SpiDeviceDriver::new_single failed")?;
-    let lcd_reset = PinDriver::output(lcd_reset.downgrade_output())
-        .context("PinDriver::output failed for lcd_reset")?;
-    let lcd_a0 = PinDriver::output(lcd_a0.downgrade_output())
-        .context("PinDriver::output failed for lcd_a0")?;
-    let mut lcd_led = PinDriver::output(lcd_led.downgrade_output())
-        .context("PinDriver::output failed for lcd_led")?;
-
-    const LCD_SIZE: Size = Size::new(160, 128);
-    let mut lcd = ST7735::new(
-        lcd_spi,
-        lcd_a0,
-        lcd_reset,
-        true,
-        false,
-        LCD_SIZE.width.try_into().unwrap(),
-        LCD_SIZE.height.try_into().unwrap(),
-    );
+    .context("I2cDriver::new failed")?;
+    #[cfg(feature = "board-m5stickc")]
+    board::power_on_lcd(&mut sensors_i2c).context("board::power_on_lcd failed")?;
 
-    log::info!("initializing LCD");
-    lcd.init(&mut FreeRtos)
-        .map_err(|_| anyhow::Error::msg("ST7735::init failed"))?;
-    lcd.set_orientation(&st7735_lcd::Orientation::Landscape)
-        .map_err(|_| anyhow::Error::msg("ST7735::set_orientation failed"))?;
-    lcd_led
-        .set_high()
-        .context("PinDriver::set_high failed for lcd_led")?;
-
-    let platform = Platform { lcd, _lcd_led: lcd_led, led0, led1 };
+    #[cfg(not(any(
+        feature = "ssd1306",
+        feature = "epaper",
+        feature = "board-t-display",
+        feature = "board-t-display-s3"
+    )))]
+    let (lcd, lcd_led) = {
+        let mut lcd_pins = pins.lcd;
+        let backlight = lcd_pins.backlight.take();
+        let lcd = new_st7735(lcd_spi, lcd_pins, &board::PANEL)?;
+        // Only once the LCD shows something sensible
+        let lcd_led = backlight
+            .map(|pin| -> Result<_> {
+                let mut lcd_led = Led {
+                    driver: LedcDriver::new(backlight_channel, &ledc_timer, pin)
+                        .context("LedcDriver::new failed for lcd_led")?,
+                    active_low: false,
+                };
+                lcd_led.write_brightness(Brightness::from(1.0))?;
+                Ok(lcd_led)
+            })
+            .transpose()?;
+        (lcd, lcd_led)
+    };
+    #[cfg(feature = "board-t-display")]
+    let (lcd, lcd_led) = st7789::new_spi(lcd_spi, pins.lcd, &board::PANEL)?;
+    #[cfg(feature = "board-t-display-s3")]
+    let (lcd, lcd_led) = {
+        let _ = lcd_spi;
+        st7789::new_parallel(pins.lcd, &board::PANEL)?
+    };
+    #[cfg(feature = "ssd1306")]
+    let (lcd, lcd_led) = {
+        // ST7735 pins are left alone
+        let _ = lcd_spi;
+        let oled_i2c = I2cDriver::new(
+            oled_i2c,
+            pins.oled_i2c.sda,
+            pins.oled_i2c.scl,
+            &I2cConfig::new().baudrate(400.kHz().into()),
+        )
+        .context("I2cDriver::new failed for OLED")?;
+        log::info!("initializing OLED");
+        (oled::Oled::new(oled_i2c)?, ())
+    };
+    #[cfg(feature = "epaper")]
+    let (lcd, lcd_led) = {
+        // E-paper needs no backlight
+        let epaper = new_epaper(lcd_spi, pins.lcd, pins.epaper_busy)?;
+        (epaper, ())
+    };
+
+    let orientation = Orientation::parse(ROTATION, MIRROR)
+        .context("invalid EVIL_ANDROID_ROTATION or EVIL_ANDROID_MIRROR")?;
+    log::info!("LCD orientation: {orientation:?}");
+    let mut lcd = Oriented::new(lcd, orientation);
+
+    // Buttons go first, so that deep sleep picks one of them over the encoder
+    #[allow(unused_mut)]
+    let mut wake_gpios: Vec<i32> = pins.buttons.iter().map(|(pin, _)| pin.pin()).collect();
+    #[cfg(not(esp32c3))]
+    wake_gpios.extend(pins.encoder.iter().map(|encoder| encoder.button.pin()));
+    let wake_sources = sleep::WakeSources {
+        gpios: wake_gpios,
+        #[cfg(all(esp32, feature = "touch"))]
+        touch: !pins.touch_pads.is_empty(),
+        #[cfg(not(all(esp32, feature = "touch")))]
+        touch: false,
+    };
+
+    #[cfg(not(esp32c3))]
+    let encoder = pins
+        .encoder
+        .map(|encoder| {
+            rotary::RotaryEncoder::new(encoder_pcnt, encoder.a, encoder.b, encoder.button)
+        })
+        .transpose()
+        .context("RotaryEncoder::new failed")?;
+    #[cfg(esp32c3)]
+    let encoder = ();
+
+    let sensors_i2c: SensorBus = Rc::new(RefCell::new(sensors_i2c));
+    let accelerometer = accel::Accelerometer::detect(sensors_i2c.clone())
+        .context("Accelerometer::detect failed")?;
+    if accelerometer.is_none() {
+        log::warn!("no accelerometer found, shake detection disabled");
+    }
+    let thermometer =
+        thermometer::Thermometer::detect(&sensors_i2c).context("Thermometer::detect failed")?;
+    if thermometer.is_none() {
+        log::warn!("no thermometer found");
+    }
+
+    #[cfg(all(esp32, feature = "touch"))]
+    let touch_pads = (!pins.touch_pads.is_empty())
+        .then(|| touch::TouchPads::new(pins.touch_pads))
+        .transpose()
+        .context("TouchPads::new failed")?;
+    #[cfg(not(all(esp32, feature = "touch")))]
+    let touch_pads = ();
+
+    let buttons = buttons::Buttons::new(pins.buttons).context("Buttons::new failed")?;
+
+    let ir_remote = pins
+        .ir
+        .map(|pin| ir::IrRemote::new(ir_channel, pin))
+        .transpose()
+        .context("IrRemote::new failed")?;
+
+    let battery = pins
+        .battery
+        .map(|sense| battery::Battery::new(adc1, sense))
+        .transpose()
+        .context("Battery::new failed")?;
+
+    // Without a card, embedded assets are used
+    #[cfg(not(esp32c3))]
+    let sd_card = pins.sd_card.and_then(|sd_pins| {
+        sd_card::SdCard::mount(sd_spi, sd_pins)
+            .inspect_err(|e| log::info!("no SD card: {e:?}"))
+            .ok()
+    });
+    let flash_fs = flash_fs::FlashFs::mount()
+        .inspect_err(|e| log::info!("no flash filesystem: {e:?}"))
+        .ok();
+
+    let nvs_partition = EspDefaultNvsPartition::take()
+        .inspect_err(|e| log::error!("NVS unavailable, nothing will be saved: {e:?}"))
+        .ok();
+    let mut nvs = nvs_partition.clone().and_then(|partition| {
+        EspNvs::new(partition, NVS_NAMESPACE, true)
+            .inspect_err(|e| log::error!("EspNvs::new failed: {e:?}"))
+            .ok()
+    });
+
+    // WiFi is not essential, the android can rage offline just fine
+    #[cfg(feature = "net")]
+    let wifi_enabled = {
+        let sysloop = EspSystemEventLoop::take().context("EspSystemEventLoop::take failed")?;
+        match nvs_partition {
+            Some(nvs_partition) => {
+                wifi::start(modem, sysloop, nvs_partition, nvs.as_mut(), &mut lcd)
+                    .inspect_err(|e| log::error!("WiFi unavailable: {e:?}"))
+                    .unwrap_or(false)
+            }
+            None => {
+                log::error!("WiFi needs NVS, disabled");
+                false
+            }
+        }
+    };
+    #[cfg(not(feature = "net"))]
+    let wifi_enabled = {
+        let _ = modem;
+        false
+    };
+    let http_server = if wifi_enabled {
+        http::serve()
+            .inspect_err(|e| log::error!("HTTP server unavailable: {e:?}"))
+            .ok()
+    } else {
+        None
+    };
+    let mdns = if wifi_enabled {
+        mdns::advertise(&crate::device_name::get(), http_server.is_some())
+            .inspect_err(|e| log::error!("mDNS unavailable: {e:?}"))
+            .ok()
+    } else {
+        None
+    };
+    // Works without WiFi, for setting the badge text from a phone
+    #[cfg(feature = "ble")]
+    if let Err(e) = ble::advertise(&crate::device_name::get()) {
+        log::error!("BLE unavailable: {e:?}");
+    }
+    // Syncs in the background, until then the clock starts at 1970
+    let sntp = if wifi_enabled {
+        EspSntp::new_default()
+            .inspect_err(|e| log::error!("SNTP unavailable: {e:?}"))
+            .ok()
+    } else {
+        None
+    };
+
+    // Rendering stays on this task, which is pinned to core 1 by sdkconfig.defaults. Single core
+    // chips have nothing to gain from this.
+    #[cfg(not(esp32c3))]
+    let lcd = flusher::Flusher::spawn(lcd).context("Flusher::spawn failed")?;
+
+    let twdt_config = TWDTConfig {
+        duration: WATCHDOG_TIMEOUT,
+        panic_on_trigger: true,
+        ..Default::default()
+    };
+    // Subscriptions borrow the driver, which needs to outlive the Platform
+    let twdt: &'static mut TWDTDriver<'static> = Box::leak(Box::new(
+        TWDTDriver::new(twdt, &twdt_config).context("TWDTDriver::new failed")?,
+    ));
+    let watchdog = twdt
+        .watch_current_task()
+        .context("TWDTDriver::watch_current_task failed")?;
+
+    let platform = Platform {
+        lcd,
+        lcd_led,
+        led0,
+        led1,
+        inputs: (
+            (((encoder, accelerometer), touch_pads), (buttons, ir_remote)),
+            Console::spawn()?,
+        ),
+        battery,
+        thermometer,
+        storage: AssetStorage {
+            #[cfg(not(esp32c3))]
+            sd_card,
+            flash_fs,
+        },
+        nvs,
+        wake_sources,
+        _http_server: http_server,
+        _mdns: mdns,
+        _sntp: sntp,
+        watchdog,
+    };
     Ok(platform)
 }
 
-impl<Lcd: DrawTarget<Color = Rgb565>, LcdLedPin, Led0Pin: LED, Led1Pin: LED> super::Platform for Platform<Lcd, LcdLedPin, Led0Pin, Led1Pin> {
+impl<
+        Lcd: ResettableLcd + 'static,
+        LcdLedPin: Backlight,
+        Led0Pin: LED,
+        Led1Pin: LED,
+        Inputs: Input,
+    > super::Platform for Platform<Lcd, LcdLedPin, Led0Pin, Led1Pin, Inputs>
+where
+    Lcd::Error: std::fmt::Debug,
+{
+    type DisplayError = Lcd::Error;
+
+    fn name(&self) -> &'static str {
+        "esp32"
+    }
+
+    #[cfg(feature = "net")]
+    fn ip_address(&mut self) -> Option<std::net::IpAddr> {
+        wifi::ip_address().map(Into::into)
+    }
+
     fn sleep(&mut self, duration: Duration) {
         FreeRtos::delay_ms(
             duration
@@ -123,8 +733,23 @@ impl<Lcd: DrawTarget<Color = Rgb565>, LcdLedPin, Led0Pin: LED, Led1Pin: LED> sup
         );
     }
 
-    fn lcd(&mut self) -> &mut impl DrawTarget<Color = Rgb565> {
-        &mut self.lcd
+    fn display(
+        &mut self,
+        id: DisplayId,
+    ) -> Option<&mut impl DrawTarget<Color = Rgb565, Error = Self::DisplayError>> {
+        (id == DisplayId::MAIN).then_some(&mut self.lcd)
+    }
+
+    fn write_window(&mut self, area: &Rectangle, pixels: &[Rgb565]) -> Result<(), Lcd::Error> {
+        self.lcd.write_window(area, pixels)
+    }
+
+    fn display_error(&self, error: Lcd::Error) -> PlatformError {
+        Lcd::classify_error(error)
+    }
+
+    fn write_backlight(&mut self, brightness: Brightness) -> Result<()> {
+        self.lcd_led.set_level(brightness)
     }
 
     fn led0(&mut self) -> &mut impl LED {
@@ -134,4 +759,150 @@ impl<Lcd: DrawTarget<Color = Rgb565>, LcdLedPin, Led0Pin: LED, Led1Pin: LED> sup
     fn led1(&mut self) -> &mut impl LED {
         &mut self.led1
     }
+
+    fn input(&mut self) -> &mut impl Input {
+        &mut self.inputs
+    }
+
+    fn storage(&mut self) -> &mut impl super::Storage {
+        &mut self.storage
+    }
+
+    fn feed_watchdog(&mut self) -> Result<()> {
+        Ok(self.watchdog.feed()?)
+    }
+
+    fn reset_lcd(&mut self) -> Result<()> {
+        self.lcd.init()
+    }
+
+    /// OLED and e-paper are different sizes than the panel, they use the heap
+    #[cfg(not(any(feature = "ssd1306", feature = "epaper")))]
+    fn frame_buffer(&mut self) -> Option<&'static mut [Rgb565]> {
+        FRAME_BUFFER.take()
+    }
+
+    /// Only with PSRAM, internal RAM is too tight. Large allocations go to PSRAM by default.
+    fn virtual_canvas_size(&mut self) -> Option<Size> {
+        // SAFETY: no preconditions
+        let psram = unsafe { heap_caps_get_total_size(MALLOC_CAP_SPIRAM) };
+        (psram > 0).then(|| self.lcd.bounding_box().size * 2)
+    }
+
+    fn report_frame_stats(&mut self, stats: &FrameStats) {
+        if let Err(e) = self.lcd.end_frame(stats) {
+            log::error!("{e:?}");
+        }
+    }
+
+    fn exit_requested(&self) -> bool {
+        // There's nowhere to exit to
+        false
+    }
+
+    fn poll_tweaks(&mut self) -> Option<crate::tweaks::Tweaks> {
+        None
+    }
+
+    fn battery(&mut self) -> Option<BatteryStatus> {
+        self.battery
+            .as_mut()?
+            .status()
+            .inspect_err(|e| log::error!("{e:?}"))
+            .ok()
+    }
+
+    fn sensors(&mut self) -> Option<SensorReadings> {
+        let temperature = self
+            .thermometer
+            .as_mut()?
+            .read()
+            .inspect_err(|e| log::error!("{e:?}"))
+            .ok();
+        Some(SensorReadings { temperature })
+    }
+
+    fn memory_stats(&mut self) -> Option<MemoryStats> {
+        // SAFETY: no preconditions, task handles are checked before use. Tasks are never deleted,
+        // so handles don't go stale in between.
+        let stacks = WATCHED_TASKS
+            .iter()
+            .filter_map(|name| {
+                let task = unsafe { xTaskGetHandle(name.as_ptr()) };
+                (!task.is_null()).then(|| {
+                    let headroom = unsafe { uxTaskGetStackHighWaterMark(task) };
+                    (name.to_str().unwrap_or("?"), headroom as usize)
+                })
+            })
+            .collect();
+        // SAFETY: no preconditions
+        unsafe {
+            Some(MemoryStats {
+                heap_free: Some(esp_get_free_heap_size() as usize),
+                heap_free_min: Some(esp_get_minimum_free_heap_size() as usize),
+                largest_free_block: Some(heap_caps_get_largest_free_block(MALLOC_CAP_8BIT)),
+                resident: None,
+                stacks,
+            })
+        }
+    }
+
+    fn load(&mut self, key: &str) -> Result<Option<Vec<u8>>> {
+        let Some(nvs) = &self.nvs else {
+            return Ok(None);
+        };
+        let Some(len) = nvs
+            .blob_len(key)
+            .with_context(|| format!("EspNvs::blob_len failed for {key}"))?
+        else {
+            return Ok(None);
+        };
+        let mut value = vec![0u8; len];
+        let value = nvs
+            .get_blob(key, &mut value)
+            .with_context(|| format!("EspNvs::get_blob failed for {key}"))?
+            .map(|value| value.to_vec());
+        Ok(value)
+    }
+
+    fn store(&mut self, key: &str, value: &[u8]) -> Result<()> {
+        if let Some(nvs) = &mut self.nvs {
+            nvs.set_blob(key, value)
+                .with_context(|| format!("EspNvs::set_blob failed for {key}"))?;
+        }
+        Ok(())
+    }
+
+    fn light_sleep(&mut self, wake_after: Option<Duration>) -> Result<()> {
+        self.lcd_led.set_on(false)?;
+        // The watchdog timer is stopped too, but it may be close to expiring already
+        self.feed_watchdog()?;
+        sleep::light_sleep(&self.wake_sources, wake_after)?;
+        self.feed_watchdog()?;
+        self.lcd_led.set_on(true)
+    }
+
+    fn can_deep_sleep(&self) -> bool {
+        true
+    }
+
+    fn deep_sleep(&mut self, wake_after: Option<Duration>, resume: Option<ResumeState>) {
+        log::info!("entering deep sleep");
+        // Pins keep their last level until the chip actually powers down
+        let _ = self.led0.set_brightness(0f32.into());
+        let _ = self.led1.set_brightness(0f32.into());
+        let _ = self.lcd_led.set_on(false);
+        sleep::deep_sleep(&self.wake_sources, wake_after, resume)
+    }
+
+    fn resume_state(&mut self) -> Option<ResumeState> {
+        sleep::take_resume_state()
+    }
+
+    unsafe fn install_panic_screen(&mut self) {
+        // The LCD may be mid-update when the panic happens. This is not exactly sound, but
+        // execution of the panicking task never gets back to that update, and a garbled frame
+        // is still better than a frozen one with no hint of what went wrong.
+        crate::panic_screen::install(PanicLcd(&mut self.lcd as *mut Lcd), PANIC_SCREEN_HOLD);
+    }
 }
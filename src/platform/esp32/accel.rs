@@ -0,0 +1,153 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use esp_idf_svc::hal::{delay::BLOCK, i2c::I2cDriver};
+
+use super::SensorBus;
+use crate::platform::{Input, InputEvent};
+
+// Both supported chips are configured for the ±2g range, where 1g reads as roughly this value.
+const LSB_PER_G: f32 = 16384.0;
+// Deviation from the 1g of gravity that counts as a shake
+const SHAKE_THRESHOLD_G: f32 = 0.8;
+// Deviation that maps to shake strength of 1.0
+const SHAKE_FULL_STRENGTH_G: f32 = 2.0;
+// A single shake spans multiple samples, don't report each of them
+const SHAKE_COOLDOWN: Duration = Duration::from_millis(300);
+
+#[derive(Clone, Copy, Debug)]
+enum Chip {
+    Mpu6050 { addr: u8 },
+    Lis3dh { addr: u8 },
+}
+
+impl Chip {
+    const MPU6050_ADDRS: [u8; 2] = [0x68, 0x69];
+    const MPU6050_REG_WHO_AM_I: u8 = 0x75;
+    const MPU6050_REG_PWR_MGMT_1: u8 = 0x6b;
+    const MPU6050_REG_ACCEL_XOUT_H: u8 = 0x3b;
+
+    const LIS3DH_ADDRS: [u8; 2] = [0x18, 0x19];
+    const LIS3DH_REG_WHO_AM_I: u8 = 0x0f;
+    const LIS3DH_WHO_AM_I: u8 = 0x33;
+    const LIS3DH_REG_CTRL_REG1: u8 = 0x20;
+    const LIS3DH_REG_CTRL_REG4: u8 = 0x23;
+    const LIS3DH_REG_OUT_X_L: u8 = 0x28;
+    // Set on register address to make LIS3DH auto-increment it on multi-byte reads
+    const LIS3DH_AUTO_INCREMENT: u8 = 0x80;
+
+    fn detect(i2c: &mut I2cDriver) -> Option<Chip> {
+        let mut who_am_i = [0u8];
+        for addr in Self::MPU6050_ADDRS {
+            // WHO_AM_I holds upper bits of the I2C address, regardless of AD0 pin state
+            if i2c
+                .write_read(addr, &[Self::MPU6050_REG_WHO_AM_I], &mut who_am_i, BLOCK)
+                .is_ok()
+                && who_am_i[0] & 0x7e == 0x68
+            {
+                return Some(Chip::Mpu6050 { addr });
+            }
+        }
+        for addr in Self::LIS3DH_ADDRS {
+            if i2c
+                .write_read(addr, &[Self::LIS3DH_REG_WHO_AM_I], &mut who_am_i, BLOCK)
+                .is_ok()
+                && who_am_i[0] == Self::LIS3DH_WHO_AM_I
+            {
+                return Some(Chip::Lis3dh { addr });
+            }
+        }
+        None
+    }
+
+    fn init(self, i2c: &mut I2cDriver) -> Result<()> {
+        match self {
+            Chip::Mpu6050 { addr } => {
+                // Wake up from sleep, default ±2g range
+                i2c.write(addr, &[Self::MPU6050_REG_PWR_MGMT_1, 0x00], BLOCK)?;
+            }
+            Chip::Lis3dh { addr } => {
+                // 100Hz, all axes enabled
+                i2c.write(addr, &[Self::LIS3DH_REG_CTRL_REG1, 0x57], BLOCK)?;
+                // High resolution mode, ±2g range
+                i2c.write(addr, &[Self::LIS3DH_REG_CTRL_REG4, 0x08], BLOCK)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn read_raw(self, i2c: &mut I2cDriver) -> Result<[i16; 3]> {
+        let mut buf = [0u8; 6];
+        match self {
+            Chip::Mpu6050 { addr } => {
+                i2c.write_read(addr, &[Self::MPU6050_REG_ACCEL_XOUT_H], &mut buf, BLOCK)?;
+                Ok([0, 1, 2].map(|i| i16::from_be_bytes([buf[2 * i], buf[2 * i + 1]])))
+            }
+            Chip::Lis3dh { addr } => {
+                i2c.write_read(
+                    addr,
+                    &[Self::LIS3DH_REG_OUT_X_L | Self::LIS3DH_AUTO_INCREMENT],
+                    &mut buf,
+                    BLOCK,
+                )?;
+                Ok([0, 1, 2].map(|i| i16::from_le_bytes([buf[2 * i], buf[2 * i + 1]])))
+            }
+        }
+    }
+}
+
+/// MPU6050 or LIS3DH accelerometer, reporting shakes
+pub struct Accelerometer {
+    i2c: SensorBus,
+    chip: Chip,
+    last_shake: Option<Instant>,
+}
+
+impl Accelerometer {
+    /// Returns None if no supported accelerometer is connected
+    pub fn detect(i2c: SensorBus) -> Result<Option<Self>> {
+        let Some(chip) = Chip::detect(&mut i2c.borrow_mut()) else {
+            return Ok(None);
+        };
+        log::info!("found accelerometer: {chip:?}");
+        chip.init(&mut i2c.borrow_mut())
+            .with_context(|| format!("failed to initialize {chip:?}"))?;
+        Ok(Some(Self {
+            i2c,
+            chip,
+            last_shake: None,
+        }))
+    }
+}
+
+impl Input for Accelerometer {
+    fn poll(&mut self) -> Result<Option<InputEvent>> {
+        if self
+            .last_shake
+            .is_some_and(|t| t.elapsed() < SHAKE_COOLDOWN)
+        {
+            return Ok(None);
+        }
+
+        // A loose wire shouldn't take the buttons down with it, try again on the next poll
+        let raw = match self.chip.read_raw(&mut self.i2c.borrow_mut()) {
+            Ok(raw) => raw,
+            Err(e) => {
+                log::warn!("failed to read {:?}: {e:?}", self.chip);
+                return Ok(None);
+            }
+        };
+        let g = raw
+            .iter()
+            .map(|&v| (v as f32 / LSB_PER_G).powi(2))
+            .sum::<f32>()
+            .sqrt();
+        let deviation = (g - 1.0).abs();
+        if deviation < SHAKE_THRESHOLD_G {
+            return Ok(None);
+        }
+
+        self.last_shake = Some(Instant::now());
+        Ok(Some(InputEvent::Shake(deviation / SHAKE_FULL_STRENGTH_G)))
+    }
+}
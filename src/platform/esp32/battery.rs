@@ -0,0 +1,59 @@
+use anyhow::{Context, Result};
+use esp_idf_svc::hal::{
+    adc::{
+        attenuation::DB_11,
+        oneshot::{config::AdcChannelConfig, AdcChannelDriver, AdcDriver},
+        ADC1,
+    },
+    gpio::{AnyOutputPin, Output, PinDriver},
+};
+
+use super::board::{BatteryPin, BatterySense};
+use crate::platform::BatteryStatus;
+
+/// Reads battery voltage through a resistor divider
+pub struct Battery {
+    channel: AdcChannelDriver<'static, BatteryPin, AdcDriver<'static, ADC1>>,
+    divider: f32,
+    // Keeps the divider connected
+    _enable: Option<PinDriver<'static, AnyOutputPin, Output>>,
+}
+
+impl Battery {
+    pub fn new(adc: ADC1, sense: BatterySense) -> Result<Self> {
+        let enable = sense
+            .enable
+            .map(|pin| -> Result<_> {
+                let mut enable = PinDriver::output(pin)
+                    .context("PinDriver::output failed for battery enable")?;
+                enable
+                    .set_high()
+                    .context("PinDriver::set_high failed for battery enable")?;
+                Ok(enable)
+            })
+            .transpose()?;
+        let adc = AdcDriver::new(adc).context("AdcDriver::new failed")?;
+        // 11dB covers up to ~3.1V on the pin, enough for a full LiPo behind a 1:2 divider
+        let config = AdcChannelConfig {
+            attenuation: DB_11,
+            calibration: true,
+            ..Default::default()
+        };
+        let channel = AdcChannelDriver::new(adc, sense.pin, &config)
+            .context("AdcChannelDriver::new failed")?;
+        Ok(Battery {
+            channel,
+            divider: sense.divider,
+            _enable: enable,
+        })
+    }
+
+    pub fn status(&mut self) -> Result<BatteryStatus> {
+        let millivolts = self.channel.read().context("ADC read failed")?;
+        let voltage = millivolts as f32 / 1000.0 * self.divider;
+        Ok(BatteryStatus {
+            voltage,
+            charge: crate::battery::lipo_charge(voltage),
+        })
+    }
+}
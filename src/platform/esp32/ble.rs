@@ -0,0 +1,35 @@
+use anyhow::{anyhow, Result};
+use esp32_nimble::{
+    utilities::BleUuid, uuid128, BLEAdvertisementData, BLEDevice, NimbleProperties,
+};
+
+const SERVICE_UUID: BleUuid = uuid128!("6e1f0000-8a3c-4d1e-9b7e-0e5c1a2b3c4d");
+/// Written like the body of `POST /badge`: name in the first line, tagline in the rest
+const BADGE_UUID: BleUuid = uuid128!("6e1f0001-8a3c-4d1e-9b7e-0e5c1a2b3c4d");
+
+/// Advertises as `name`, with a service for setting the badge text. Keeps going for as long as
+/// the program runs, the BLE stack is a singleton.
+pub fn advertise(name: &str) -> Result<()> {
+    let device = BLEDevice::take();
+    BLEDevice::set_device_name(name)
+        .map_err(|e| anyhow!("BLEDevice::set_device_name failed: {e:?}"))?;
+
+    let service = device.get_server().create_service(SERVICE_UUID);
+    service
+        .lock()
+        .create_characteristic(BADGE_UUID, NimbleProperties::WRITE)
+        .lock()
+        .on_write(|args| crate::badge::set_text(&String::from_utf8_lossy(args.recv_data())));
+
+    // A 128-bit UUID and the name don't both fit in an advertisement, the name is what people
+    // look for
+    let mut advertising = device.get_advertising().lock();
+    advertising
+        .set_data(BLEAdvertisementData::new().name(name))
+        .map_err(|e| anyhow!("BLEAdvertising::set_data failed: {e:?}"))?;
+    advertising
+        .start()
+        .map_err(|e| anyhow!("BLEAdvertising::start failed: {e:?}"))?;
+    log::info!("advertising as {name} over BLE");
+    Ok(())
+}
@@ -0,0 +1,515 @@
+//! Pin assignments. Dev boards with a built-in display are selected with a `board-*` feature,
+//! otherwise the hand-wired pinout for the chip is used.
+
+use embedded_graphics::geometry::Size;
+use esp_idf_svc::hal::gpio::{
+    AnyIOPin, AnyInputPin, AnyOutputPin, IOPin, InputPin, OutputPin, Pins,
+};
+
+use crate::platform::InputEvent;
+
+#[cfg(any(
+    all(feature = "board-t-display", feature = "board-t-display-s3"),
+    all(feature = "board-t-display", feature = "board-m5stickc"),
+    all(feature = "board-t-display-s3", feature = "board-m5stickc"),
+))]
+compile_error!("at most one board-* feature can be enabled");
+
+#[cfg(all(
+    any(feature = "board-t-display", feature = "board-m5stickc"),
+    not(esp32)
+))]
+compile_error!("this board has an ESP32, set MCU=esp32");
+
+#[cfg(all(feature = "board-t-display-s3", not(esp32s3)))]
+compile_error!("this board has an ESP32-S3, set MCU=esp32s3");
+
+#[cfg(all(
+    any(feature = "ssd1306", feature = "epaper"),
+    any(
+        feature = "board-t-display",
+        feature = "board-t-display-s3",
+        feature = "board-m5stickc",
+        not(esp32)
+    )
+))]
+compile_error!("ssd1306 and epaper are only wired up for the hand-wired ESP32");
+
+/// Display controller settings that differ between modules
+#[cfg_attr(any(feature = "ssd1306", feature = "epaper"), allow(dead_code))]
+pub struct Panel {
+    /// In landscape orientation
+    pub size: Size,
+    /// Position of the visible area in controller RAM, in landscape orientation
+    pub offset: (u16, u16),
+    /// false for BGR
+    pub rgb: bool,
+    pub inverted: bool,
+}
+
+/// LCD on a SPI bus
+#[cfg_attr(any(feature = "ssd1306", feature = "epaper"), allow(dead_code))]
+pub struct SpiLcdPins {
+    pub sclk: AnyOutputPin,
+    pub mosi: AnyOutputPin,
+    pub cs: AnyOutputPin,
+    /// Labeled A0 on some modules
+    pub dc: AnyOutputPin,
+    pub reset: AnyOutputPin,
+    /// None if the backlight is not controlled by a GPIO
+    pub backlight: Option<AnyOutputPin>,
+}
+
+/// LCD on an 8-bit Intel 8080 style parallel bus
+#[cfg(feature = "board-t-display-s3")]
+pub struct ParallelLcdPins {
+    pub data: [AnyOutputPin; 8],
+    pub wr: AnyOutputPin,
+    pub rd: AnyOutputPin,
+    pub cs: AnyOutputPin,
+    pub dc: AnyOutputPin,
+    pub reset: AnyOutputPin,
+    pub backlight: AnyOutputPin,
+    /// Enables the LCD power supply
+    pub power: AnyOutputPin,
+}
+
+pub struct I2cPins {
+    pub sda: AnyIOPin,
+    pub scl: AnyIOPin,
+}
+
+/// Battery voltage through a resistor divider, on an ADC1 pin. ADC2 is unusable while WiFi is
+/// on, so only ADC1 pins will do.
+pub struct BatterySense {
+    pub pin: BatteryPin,
+    /// Battery voltage over the voltage on the pin
+    pub divider: f32,
+    /// Connects the divider, for boards that disconnect it to save power
+    pub enable: Option<AnyOutputPin>,
+}
+
+/// SD card on its own SPI bus. The ESP32-C3 only has one usable SPI bus, taken by the LCD.
+#[cfg(not(esp32c3))]
+pub struct SdCardPins {
+    pub sclk: AnyOutputPin,
+    pub mosi: AnyOutputPin,
+    pub miso: AnyIOPin,
+    pub cs: AnyOutputPin,
+}
+
+/// ESP32-C3 has no pulse counter, so no rotary encoder there
+#[cfg(not(esp32c3))]
+pub struct EncoderPins {
+    pub a: AnyInputPin,
+    pub b: AnyInputPin,
+    pub button: AnyInputPin,
+}
+
+pub struct Board {
+    #[cfg(not(feature = "board-t-display-s3"))]
+    pub lcd: SpiLcdPins,
+    #[cfg(feature = "board-t-display-s3")]
+    pub lcd: ParallelLcdPins,
+    pub leds: [AnyOutputPin; 2],
+    /// LEDs wired between the pin and VCC, lit when the pin is low
+    pub leds_active_low: [bool; 2],
+    #[cfg(not(esp32c3))]
+    pub encoder: Option<EncoderPins>,
+    /// Pin, touch pad number of that pin, event to report on touch. Only the original ESP32
+    /// touch sensor is supported.
+    #[cfg(esp32)]
+    #[cfg_attr(not(feature = "touch"), allow(dead_code))]
+    pub touch_pads: Vec<(AnyIOPin, esp_idf_svc::sys::touch_pad_t, InputEvent)>,
+    /// Active-low push buttons with external pull-ups
+    pub buttons: Vec<(AnyInputPin, InputEvent)>,
+    pub ir: Option<AnyInputPin>,
+    pub sensors_i2c: I2cPins,
+    /// None if not battery powered, or the battery can't be measured
+    pub battery: Option<BatterySense>,
+    /// None if there's no SD card slot
+    #[cfg(not(esp32c3))]
+    pub sd_card: Option<SdCardPins>,
+    #[cfg(feature = "ssd1306")]
+    pub oled_i2c: I2cPins,
+    #[cfg(feature = "epaper")]
+    pub epaper_busy: AnyInputPin,
+}
+
+/// 1.8" 160x128 ST7735 module, on all hand-wired builds
+#[cfg(not(any(
+    feature = "board-t-display",
+    feature = "board-t-display-s3",
+    feature = "board-m5stickc"
+)))]
+#[cfg_attr(any(feature = "ssd1306", feature = "epaper"), allow(dead_code))]
+pub const PANEL: Panel = Panel {
+    size: Size::new(160, 128),
+    offset: (0, 0),
+    rgb: true,
+    inverted: false,
+};
+
+#[cfg(all(
+    esp32,
+    not(any(feature = "board-t-display", feature = "board-m5stickc"))
+))]
+pub type BatteryPin = esp_idf_svc::hal::gpio::Gpio36;
+
+/// Hand-wired ESP32, see README
+#[cfg(all(
+    esp32,
+    not(any(feature = "board-t-display", feature = "board-m5stickc"))
+))]
+impl Board {
+    pub fn take(pins: Pins) -> Self {
+        Board {
+            lcd: SpiLcdPins {
+                sclk: pins.gpio14.downgrade_output(),
+                mosi: pins.gpio13.downgrade_output(),
+                cs: pins.gpio15.downgrade_output(),
+                dc: pins.gpio17.downgrade_output(),
+                reset: pins.gpio16.downgrade_output(),
+                backlight: Some(pins.gpio18.downgrade_output()),
+            },
+            leds: [
+                pins.gpio19.downgrade_output(),
+                pins.gpio21.downgrade_output(),
+            ],
+            leds_active_low: [false, false],
+            encoder: Some(EncoderPins {
+                a: pins.gpio25.downgrade_input(),
+                b: pins.gpio26.downgrade_input(),
+                button: pins.gpio27.downgrade_input(),
+            }),
+            touch_pads: vec![
+                (
+                    pins.gpio4.downgrade(),
+                    esp_idf_svc::sys::touch_pad_t_TOUCH_PAD_NUM0,
+                    InputEvent::Pet,
+                ),
+                (
+                    pins.gpio2.downgrade(),
+                    esp_idf_svc::sys::touch_pad_t_TOUCH_PAD_NUM2,
+                    InputEvent::Poke,
+                ),
+            ],
+            buttons: Vec::new(),
+            ir: Some(pins.gpio35.downgrade_input()),
+            sensors_i2c: I2cPins {
+                sda: pins.gpio32.downgrade(),
+                scl: pins.gpio33.downgrade(),
+            },
+            // 2x 100k divider
+            battery: Some(BatterySense {
+                pin: pins.gpio36,
+                divider: 2.0,
+                enable: None,
+            }),
+            // CS is a strapping pin, but SD modules pull it up, which is what booting needs
+            #[cfg(not(feature = "ssd1306"))]
+            sd_card: Some(SdCardPins {
+                sclk: pins.gpio5.downgrade_output(),
+                mosi: pins.gpio23.downgrade_output(),
+                miso: pins.gpio22.downgrade(),
+                cs: pins.gpio0.downgrade_output(),
+            }),
+            // Its pins are taken by the OLED
+            #[cfg(feature = "ssd1306")]
+            sd_card: None,
+            #[cfg(feature = "ssd1306")]
+            oled_i2c: I2cPins {
+                sda: pins.gpio23.downgrade(),
+                scl: pins.gpio22.downgrade(),
+            },
+            #[cfg(feature = "epaper")]
+            epaper_busy: pins.gpio34.downgrade_input(),
+        }
+    }
+}
+
+#[cfg(all(esp32s3, not(feature = "board-t-display-s3")))]
+pub type BatteryPin = esp_idf_svc::hal::gpio::Gpio6;
+
+/// Hand-wired ESP32-S3. SPI pins are the SPI2 IO_MUX ones.
+#[cfg(all(esp32s3, not(feature = "board-t-display-s3")))]
+impl Board {
+    pub fn take(pins: Pins) -> Self {
+        Board {
+            lcd: SpiLcdPins {
+                sclk: pins.gpio12.downgrade_output(),
+                mosi: pins.gpio11.downgrade_output(),
+                cs: pins.gpio10.downgrade_output(),
+                dc: pins.gpio9.downgrade_output(),
+                reset: pins.gpio8.downgrade_output(),
+                backlight: Some(pins.gpio7.downgrade_output()),
+            },
+            leds: [pins.gpio4.downgrade_output(), pins.gpio5.downgrade_output()],
+            leds_active_low: [false, false],
+            encoder: Some(EncoderPins {
+                a: pins.gpio15.downgrade_input(),
+                b: pins.gpio16.downgrade_input(),
+                button: pins.gpio17.downgrade_input(),
+            }),
+            // BOOT button
+            buttons: vec![(pins.gpio0.downgrade_input(), InputEvent::NextScene)],
+            ir: Some(pins.gpio18.downgrade_input()),
+            sensors_i2c: I2cPins {
+                sda: pins.gpio1.downgrade(),
+                scl: pins.gpio2.downgrade(),
+            },
+            // 2x 100k divider
+            battery: Some(BatterySense {
+                pin: pins.gpio6,
+                divider: 2.0,
+                enable: None,
+            }),
+            sd_card: Some(SdCardPins {
+                sclk: pins.gpio14.downgrade_output(),
+                mosi: pins.gpio13.downgrade_output(),
+                miso: pins.gpio21.downgrade(),
+                cs: pins.gpio47.downgrade_output(),
+            }),
+        }
+    }
+}
+
+/// All ADC1 pins of the ESP32-C3 are taken, so no battery sensing there
+#[cfg(esp32c3)]
+pub type BatteryPin = esp_idf_svc::hal::gpio::Gpio0;
+
+/// Hand-wired ESP32-C3. Few pins are left after flash and the console UART, so there's no
+/// encoder, and the IR receiver takes the USB D- pin.
+#[cfg(esp32c3)]
+impl Board {
+    pub fn take(pins: Pins) -> Self {
+        Board {
+            lcd: SpiLcdPins {
+                sclk: pins.gpio6.downgrade_output(),
+                mosi: pins.gpio7.downgrade_output(),
+                cs: pins.gpio10.downgrade_output(),
+                dc: pins.gpio4.downgrade_output(),
+                reset: pins.gpio5.downgrade_output(),
+                backlight: Some(pins.gpio3.downgrade_output()),
+            },
+            leds: [pins.gpio0.downgrade_output(), pins.gpio1.downgrade_output()],
+            leds_active_low: [false, false],
+            // BOOT button
+            buttons: vec![(pins.gpio9.downgrade_input(), InputEvent::NextScene)],
+            ir: Some(pins.gpio18.downgrade_input()),
+            sensors_i2c: I2cPins {
+                sda: pins.gpio8.downgrade(),
+                scl: pins.gpio2.downgrade(),
+            },
+            battery: None,
+        }
+    }
+}
+
+/// LilyGo T-Display: 1.14" 135x240 ST7789. Everything else goes on the header pins.
+#[cfg(feature = "board-t-display")]
+pub const PANEL: Panel = Panel {
+    size: Size::new(240, 135),
+    offset: (40, 53),
+    rgb: true,
+    inverted: true,
+};
+
+#[cfg(feature = "board-t-display")]
+pub type BatteryPin = esp_idf_svc::hal::gpio::Gpio34;
+
+#[cfg(feature = "board-t-display")]
+impl Board {
+    pub fn take(pins: Pins) -> Self {
+        Board {
+            lcd: SpiLcdPins {
+                sclk: pins.gpio18.downgrade_output(),
+                mosi: pins.gpio19.downgrade_output(),
+                cs: pins.gpio5.downgrade_output(),
+                dc: pins.gpio16.downgrade_output(),
+                reset: pins.gpio23.downgrade_output(),
+                backlight: Some(pins.gpio4.downgrade_output()),
+            },
+            leds: [
+                pins.gpio25.downgrade_output(),
+                pins.gpio26.downgrade_output(),
+            ],
+            leds_active_low: [false, false],
+            encoder: Some(EncoderPins {
+                a: pins.gpio32.downgrade_input(),
+                b: pins.gpio33.downgrade_input(),
+                button: pins.gpio27.downgrade_input(),
+            }),
+            touch_pads: vec![
+                (
+                    pins.gpio15.downgrade(),
+                    esp_idf_svc::sys::touch_pad_t_TOUCH_PAD_NUM3,
+                    InputEvent::Pet,
+                ),
+                (
+                    pins.gpio2.downgrade(),
+                    esp_idf_svc::sys::touch_pad_t_TOUCH_PAD_NUM2,
+                    InputEvent::Poke,
+                ),
+            ],
+            buttons: vec![
+                (pins.gpio0.downgrade_input(), InputEvent::NextScene),
+                (pins.gpio35.downgrade_input(), InputEvent::TogglePause),
+            ],
+            ir: Some(pins.gpio36.downgrade_input()),
+            sensors_i2c: I2cPins {
+                sda: pins.gpio21.downgrade(),
+                scl: pins.gpio22.downgrade(),
+            },
+            // On-board 2x 100k divider, switched by ADC_EN
+            battery: Some(BatterySense {
+                pin: pins.gpio34,
+                divider: 2.0,
+                enable: Some(pins.gpio14.downgrade_output()),
+            }),
+            sd_card: None,
+        }
+    }
+}
+
+/// LilyGo T-Display-S3: 1.9" 170x320 ST7789 on a parallel bus
+#[cfg(feature = "board-t-display-s3")]
+pub const PANEL: Panel = Panel {
+    size: Size::new(320, 170),
+    offset: (0, 35),
+    rgb: true,
+    inverted: true,
+};
+
+#[cfg(feature = "board-t-display-s3")]
+pub type BatteryPin = esp_idf_svc::hal::gpio::Gpio4;
+
+#[cfg(feature = "board-t-display-s3")]
+impl Board {
+    pub fn take(pins: Pins) -> Self {
+        Board {
+            lcd: ParallelLcdPins {
+                data: [
+                    pins.gpio39.downgrade_output(),
+                    pins.gpio40.downgrade_output(),
+                    pins.gpio41.downgrade_output(),
+                    pins.gpio42.downgrade_output(),
+                    pins.gpio45.downgrade_output(),
+                    pins.gpio46.downgrade_output(),
+                    pins.gpio47.downgrade_output(),
+                    pins.gpio48.downgrade_output(),
+                ],
+                wr: pins.gpio8.downgrade_output(),
+                rd: pins.gpio9.downgrade_output(),
+                cs: pins.gpio6.downgrade_output(),
+                dc: pins.gpio7.downgrade_output(),
+                reset: pins.gpio5.downgrade_output(),
+                backlight: pins.gpio38.downgrade_output(),
+                power: pins.gpio15.downgrade_output(),
+            },
+            leds: [pins.gpio1.downgrade_output(), pins.gpio2.downgrade_output()],
+            leds_active_low: [false, false],
+            encoder: Some(EncoderPins {
+                a: pins.gpio10.downgrade_input(),
+                b: pins.gpio11.downgrade_input(),
+                button: pins.gpio12.downgrade_input(),
+            }),
+            buttons: vec![
+                (pins.gpio0.downgrade_input(), InputEvent::NextScene),
+                (pins.gpio14.downgrade_input(), InputEvent::TogglePause),
+            ],
+            ir: Some(pins.gpio16.downgrade_input()),
+            sensors_i2c: I2cPins {
+                sda: pins.gpio18.downgrade(),
+                scl: pins.gpio17.downgrade(),
+            },
+            // On-board 2x 100k divider
+            battery: Some(BatterySense {
+                pin: pins.gpio4,
+                divider: 2.0,
+                enable: None,
+            }),
+            sd_card: None,
+        }
+    }
+}
+
+/// M5StickC: 0.96" 80x160 ST7735S, backlight powered by the AXP192 PMIC. The red LED on GPIO10
+/// is LED0, LED1 goes on G26 of the header. There's no IR receiver, only a transmitter.
+#[cfg(feature = "board-m5stickc")]
+pub const PANEL: Panel = Panel {
+    size: Size::new(160, 80),
+    offset: (1, 26),
+    rgb: false,
+    inverted: true,
+};
+
+/// The battery is only measured by the AXP192, which is not read yet
+#[cfg(feature = "board-m5stickc")]
+pub type BatteryPin = esp_idf_svc::hal::gpio::Gpio36;
+
+#[cfg(feature = "board-m5stickc")]
+impl Board {
+    pub fn take(pins: Pins) -> Self {
+        Board {
+            lcd: SpiLcdPins {
+                sclk: pins.gpio13.downgrade_output(),
+                mosi: pins.gpio15.downgrade_output(),
+                cs: pins.gpio5.downgrade_output(),
+                dc: pins.gpio23.downgrade_output(),
+                reset: pins.gpio18.downgrade_output(),
+                backlight: None,
+            },
+            leds: [
+                pins.gpio10.downgrade_output(),
+                pins.gpio26.downgrade_output(),
+            ],
+            leds_active_low: [true, false],
+            encoder: None,
+            touch_pads: Vec::new(),
+            buttons: vec![
+                (pins.gpio37.downgrade_input(), InputEvent::NextScene),
+                (pins.gpio39.downgrade_input(), InputEvent::TogglePause),
+            ],
+            ir: None,
+            // Shared with the AXP192 and the MPU6886
+            sensors_i2c: I2cPins {
+                sda: pins.gpio21.downgrade(),
+                scl: pins.gpio22.downgrade(),
+            },
+            battery: None,
+            sd_card: None,
+        }
+    }
+}
+
+/// Turns on the LCD backlight, which is powered by LDO2 of the AXP192
+#[cfg(feature = "board-m5stickc")]
+pub fn power_on_lcd(i2c: &mut esp_idf_svc::hal::i2c::I2cDriver) -> anyhow::Result<()> {
+    use anyhow::Context;
+    use esp_idf_svc::hal::delay::BLOCK;
+
+    const AXP192_ADDR: u8 = 0x34;
+    const REG_POWER_OUTPUT_CONTROL: u8 = 0x12;
+    const REG_LDO2_LDO3_VOLTAGE: u8 = 0x28;
+    const LDO2_ENABLE: u8 = 1 << 2;
+
+    // 3.0V on both LDO2 (backlight) and LDO3 (LCD logic)
+    i2c.write(AXP192_ADDR, &[REG_LDO2_LDO3_VOLTAGE, 0xCC], BLOCK)
+        .context("AXP192 LDO voltage write failed")?;
+    let mut control = [0u8];
+    i2c.write_read(
+        AXP192_ADDR,
+        &[REG_POWER_OUTPUT_CONTROL],
+        &mut control,
+        BLOCK,
+    )
+    .context("AXP192 power control read failed")?;
+    i2c.write(
+        AXP192_ADDR,
+        &[REG_POWER_OUTPUT_CONTROL, control[0] | LDO2_ENABLE],
+        BLOCK,
+    )
+    .context("AXP192 power control write failed")?;
+    Ok(())
+}
@@ -0,0 +1,60 @@
+use anyhow::{Context, Result};
+use esp_idf_svc::hal::gpio::{self, AnyInputPin, Pin, PinDriver};
+
+use crate::platform::{Input, InputEvent};
+
+struct Button {
+    pin: PinDriver<'static, AnyInputPin, gpio::Input>,
+    was_pressed: bool,
+    event: InputEvent,
+}
+
+/// Push buttons found on dev boards, reported on press. They all have external pull-ups, some
+/// are on input-only pins that have no internal ones. Pressing one while another is held
+/// toggles demo mode instead.
+pub struct Buttons {
+    buttons: Vec<Button>,
+}
+
+impl Buttons {
+    pub fn new(buttons: impl IntoIterator<Item = (AnyInputPin, InputEvent)>) -> Result<Self> {
+        let buttons = buttons
+            .into_iter()
+            .map(|(pin, event)| -> Result<_> {
+                let pin_number = pin.pin();
+                Ok(Button {
+                    pin: PinDriver::input(pin).with_context(|| {
+                        format!("PinDriver::input failed for button on GPIO{pin_number}")
+                    })?,
+                    was_pressed: false,
+                    event,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { buttons })
+    }
+}
+
+impl Input for Buttons {
+    fn poll(&mut self) -> Result<Option<InputEvent>> {
+        let mut result = None;
+        let held = self
+            .buttons
+            .iter()
+            .filter(|button| button.was_pressed)
+            .count();
+        for button in self.buttons.iter_mut() {
+            // Active low
+            let pressed = button.pin.is_low();
+            if pressed && !button.was_pressed && result.is_none() {
+                result = Some(if held > 0 {
+                    InputEvent::ToggleDemo
+                } else {
+                    button.event
+                });
+            }
+            button.was_pressed = pressed;
+        }
+        Ok(result)
+    }
+}
@@ -0,0 +1,200 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use embedded_graphics::{
+    draw_target::{ColorConverted, DrawTarget, DrawTargetExt},
+    geometry::Dimensions,
+    pixelcolor::{BinaryColor, Rgb565},
+    primitives::Rectangle,
+    Pixel,
+};
+use epd_waveshare::{
+    epd2in9::{Display2in9, Epd2in9},
+    prelude::*,
+};
+use esp_idf_svc::hal::delay::FreeRtos;
+
+use super::{ResettableLcd, WindowedWrite};
+use crate::{
+    dither::Dithered,
+    platform::{FrameStats, PlatformError},
+};
+
+// Slow refresh is the whole point of this module, showing minute ticks more often than that
+// would keep the panel flashing all the time
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+// Partial refreshes leave ghosting behind, a full one every now and then cleans it up
+const FULL_REFRESH_EVERY: usize = 10;
+// Number of evenly spaced points in a scene that are always worth showing
+const PHASES: f32 = 8.0;
+
+/// Decides which frames are worth the slow e-paper refresh
+#[derive(Default)]
+struct KeyFrameFilter {
+    scene: &'static str,
+    phase: usize,
+    glitching: bool,
+    minute: Option<u64>,
+    last_refresh: Option<Instant>,
+}
+
+enum Refresh {
+    Skip,
+    Partial,
+    Full,
+}
+
+impl KeyFrameFilter {
+    fn check(&mut self, stats: &FrameStats) -> Refresh {
+        let phase = (stats.progress * PHASES) as usize;
+        let glitching = stats.glitchiness > 0;
+        let minute = stats.shown_time.map(|t| t.as_secs() / 60);
+
+        let refresh = if stats.scene != self.scene {
+            Refresh::Full
+        } else if phase != self.phase || glitching != self.glitching {
+            Refresh::Partial
+        } else if minute != self.minute
+            && self
+                .last_refresh
+                .map_or(true, |t| t.elapsed() >= MIN_REFRESH_INTERVAL)
+        {
+            Refresh::Partial
+        } else {
+            return Refresh::Skip;
+        };
+
+        self.scene = stats.scene;
+        self.phase = phase;
+        self.glitching = glitching;
+        self.minute = minute;
+        self.last_refresh = Some(Instant::now());
+        refresh
+    }
+}
+
+/// Waveshare 2.9" e-paper panel. Frames are drawn into a buffer in RAM, and only key frames
+/// get sent to the panel, using partial refresh where possible.
+pub struct Epaper<SPI, BUSY, DC, RST> {
+    spi: SPI,
+    epd: Epd2in9<SPI, BUSY, DC, RST, FreeRtos>,
+    buffer: Display2in9,
+    filter: KeyFrameFilter,
+    partial_refreshes: usize,
+}
+
+impl<SPI, BUSY, DC, RST> Epaper<SPI, BUSY, DC, RST>
+where
+    SPI: embedded_hal::spi::SpiDevice,
+    BUSY: embedded_hal::digital::InputPin,
+    DC: embedded_hal::digital::OutputPin,
+    RST: embedded_hal::digital::OutputPin,
+{
+    pub fn new(mut spi: SPI, busy: BUSY, dc: DC, rst: RST) -> Result<Self> {
+        let epd = Epd2in9::new(&mut spi, busy, dc, rst, &mut FreeRtos, None)
+            .map_err(|e| PlatformError::DisplayInit(format!("Epd2in9::new failed: {e:?}")))?;
+        let mut buffer = Display2in9::default();
+        // Landscape, like the LCD
+        buffer.set_rotation(DisplayRotation::Rotate90);
+        Ok(Self {
+            spi,
+            epd,
+            buffer,
+            filter: KeyFrameFilter::default(),
+            partial_refreshes: 0,
+        })
+    }
+
+    fn dithered(&mut self) -> Dithered<ColorConverted<'_, Display2in9, BinaryColor>> {
+        Dithered::new(self.buffer.color_converted())
+    }
+
+    fn refresh(&mut self, lut: RefreshLut) -> Result<()> {
+        let map_err = |e: SPI::Error| {
+            anyhow::Error::from(PlatformError::Bus(format!("e-paper refresh failed: {e:?}")))
+        };
+        self.epd
+            .set_lut(&mut self.spi, &mut FreeRtos, Some(lut))
+            .map_err(map_err)?;
+        self.epd
+            .update_and_display_frame(&mut self.spi, self.buffer.buffer(), &mut FreeRtos)
+            .map_err(map_err)
+    }
+}
+
+impl<SPI, BUSY, DC, RST> Dimensions for Epaper<SPI, BUSY, DC, RST> {
+    fn bounding_box(&self) -> Rectangle {
+        self.buffer.bounding_box()
+    }
+}
+
+impl<SPI, BUSY, DC, RST> DrawTarget for Epaper<SPI, BUSY, DC, RST>
+where
+    SPI: embedded_hal::spi::SpiDevice,
+    BUSY: embedded_hal::digital::InputPin,
+    DC: embedded_hal::digital::OutputPin,
+    RST: embedded_hal::digital::OutputPin,
+{
+    type Color = Rgb565;
+    // Drawing only touches the RAM buffer, which can't fail
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        self.dithered().draw_iter(pixels)
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        self.dithered().fill_contiguous(area, colors)
+    }
+}
+
+/// Frames go into the buffer, nothing to gain from an address window
+impl<SPI, BUSY, DC, RST> WindowedWrite for Epaper<SPI, BUSY, DC, RST>
+where
+    SPI: embedded_hal::spi::SpiDevice,
+    BUSY: embedded_hal::digital::InputPin,
+    DC: embedded_hal::digital::OutputPin,
+    RST: embedded_hal::digital::OutputPin,
+{
+}
+
+impl<SPI, BUSY, DC, RST> ResettableLcd for Epaper<SPI, BUSY, DC, RST>
+where
+    SPI: embedded_hal::spi::SpiDevice,
+    BUSY: embedded_hal::digital::InputPin,
+    DC: embedded_hal::digital::OutputPin,
+    RST: embedded_hal::digital::OutputPin,
+{
+    fn init(&mut self) -> Result<()> {
+        self.epd
+            .wake_up(&mut self.spi, &mut FreeRtos)
+            .map_err(|e| PlatformError::DisplayInit(format!("Epd2in9::wake_up failed: {e:?}")))?;
+        // Whatever was on the panel is unknown now, next frame needs a full refresh
+        self.filter = KeyFrameFilter::default();
+        Ok(())
+    }
+
+    fn classify_error(error: core::convert::Infallible) -> PlatformError {
+        match error {}
+    }
+
+    fn end_frame(&mut self, stats: &FrameStats) -> Result<()> {
+        match self.filter.check(stats) {
+            Refresh::Skip => Ok(()),
+            Refresh::Partial if self.partial_refreshes < FULL_REFRESH_EVERY => {
+                self.partial_refreshes += 1;
+                self.refresh(RefreshLut::Quick)
+            }
+            Refresh::Partial | Refresh::Full => {
+                self.partial_refreshes = 0;
+                self.refresh(RefreshLut::Full)
+            }
+        }
+    }
+}
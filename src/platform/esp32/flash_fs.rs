@@ -0,0 +1,49 @@
+use std::ffi::CString;
+
+use anyhow::{Context, Result};
+use esp_idf_svc::sys::{
+    esp,
+    littlefs::{esp_vfs_littlefs_conf_t, esp_vfs_littlefs_register, esp_vfs_littlefs_unregister},
+};
+
+/// Label in partitions.csv
+const PARTITION_LABEL: &str = "storage";
+const MOUNT_POINT: &str = "/storage";
+
+/// LittleFS partition in flash, mounted for as long as this lives
+pub struct FlashFs {
+    partition_label: CString,
+}
+
+impl FlashFs {
+    /// Fails if the partition is missing or was never written. It's never formatted: files come
+    /// from `cargo xtask flash --storage`, uploads over HTTP only add to them.
+    pub fn mount() -> Result<Self> {
+        let partition_label = CString::new(PARTITION_LABEL)?;
+        let base_path = CString::new(MOUNT_POINT)?;
+        let config = esp_vfs_littlefs_conf_t {
+            base_path: base_path.as_ptr(),
+            partition_label: partition_label.as_ptr(),
+            ..Default::default()
+        };
+        // Paths are copied, so they don't need to outlive the call
+        esp!(unsafe { esp_vfs_littlefs_register(&config) })
+            .context("esp_vfs_littlefs_register failed")?;
+        log::info!("flash filesystem mounted at {MOUNT_POINT}");
+        Ok(Self { partition_label })
+    }
+
+    /// None if there's no such file
+    pub fn read(&self, path: &str) -> Result<Option<Vec<u8>>> {
+        super::read_file(&format!("{MOUNT_POINT}/{path}"))
+    }
+}
+
+impl Drop for FlashFs {
+    fn drop(&mut self) {
+        if let Err(e) = esp!(unsafe { esp_vfs_littlefs_unregister(self.partition_label.as_ptr()) })
+        {
+            log::error!("esp_vfs_littlefs_unregister failed: {e:?}");
+        }
+    }
+}
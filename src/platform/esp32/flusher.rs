@@ -0,0 +1,268 @@
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+
+use anyhow::{Context, Result};
+use embedded_graphics::{
+    draw_target::DrawTarget, geometry::Dimensions, pixelcolor::Rgb565, primitives::Rectangle, Pixel,
+};
+use esp_idf_svc::hal::{cpu::Core, task::thread::ThreadSpawnConfiguration};
+
+use super::{FrameStats, PlatformError, ResettableLcd, WindowedWrite};
+
+/// SPI transfers are mostly waiting, the flushing task doesn't need much more than that
+const STACK_SIZE: usize = 4096;
+
+struct State<Lcd: DrawTarget> {
+    lcd: Lcd,
+    /// Waiting to be sent to the LCD. Taken out of the slot only after the transfer is done, so
+    /// that whoever gets the lock next knows the LCD is idle.
+    frame: Option<Vec<Rgb565>>,
+    /// Buffer for the next frame, so that it's allocated only once
+    spare: Vec<Rgb565>,
+    /// Failure of the last transfer, reported when the next frame is queued
+    error: Option<Lcd::Error>,
+}
+
+/// What `end_frame` leaves for the flushing task, under a lock of its own so that it doesn't
+/// wait for the transfer
+#[derive(Default)]
+struct EndFrame {
+    /// The queued frame hasn't been sent yet
+    in_flight: bool,
+    /// Of the frame in flight, for once it's on the screen
+    stats: Option<FrameStats>,
+    /// Failure of the last deferred `end_frame`, reported by the next one
+    error: Option<anyhow::Error>,
+}
+
+struct Shared<Lcd: DrawTarget> {
+    state: Mutex<State<Lcd>>,
+    /// A frame was queued or sent
+    changed: Condvar,
+    end_frame: Mutex<EndFrame>,
+}
+
+impl<Lcd: DrawTarget> Shared<Lcd> {
+    /// A panic while drawing leaves the lock poisoned, the panic screen should still get through
+    fn lock(&self) -> MutexGuard<'_, State<Lcd>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Locks the state once the queued frame, if any, is on the screen
+    fn lock_idle(&self) -> MutexGuard<'_, State<Lcd>> {
+        self.changed
+            .wait_while(self.lock(), |state| state.frame.is_some())
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn lock_end_frame(&self) -> MutexGuard<'_, EndFrame> {
+        self.end_frame
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Sends full frames to the LCD from a task pinned to core 0, next to WiFi and lwIP, so that the
+/// main task on core 1 can render the next frame in the meantime. A single frame is queued at a
+/// time: queuing another one waits until the previous one is on the screen, so the slower of
+/// rendering and flushing sets the frame rate, rather than the two of them added up.
+///
+/// Anything other than a full frame is drawn directly, once the queued frame is out of the way.
+pub struct Flusher<Lcd: DrawTarget> {
+    shared: Arc<Shared<Lcd>>,
+    bounding_box: Rectangle,
+}
+
+impl<Lcd> Flusher<Lcd>
+where
+    Lcd: ResettableLcd + Send + 'static,
+    Lcd::Error: Send,
+{
+    pub fn spawn(lcd: Lcd) -> Result<Self> {
+        let bounding_box = lcd.bounding_box();
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                lcd,
+                frame: None,
+                spare: Vec::new(),
+                error: None,
+            }),
+            changed: Condvar::new(),
+            end_frame: Mutex::default(),
+        });
+
+        // Applies to std::thread::spawn calls from this thread, until reset below
+        ThreadSpawnConfiguration {
+            name: Some(b"lcd_flush\0"),
+            pin_to_core: Some(Core::Core0),
+            ..Default::default()
+        }
+        .set()
+        .context("ThreadSpawnConfiguration::set failed")?;
+        let task = shared.clone();
+        let spawned = std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn(move || flush_frames(&task));
+        ThreadSpawnConfiguration::default()
+            .set()
+            .context("ThreadSpawnConfiguration::set failed")?;
+        spawned.context("failed to spawn the LCD flushing task")?;
+
+        Ok(Self {
+            shared,
+            bounding_box,
+        })
+    }
+}
+
+impl<Lcd> Flusher<Lcd>
+where
+    Lcd: WindowedWrite + Send + 'static,
+    Lcd::Error: Send,
+{
+    /// Waits for the queued frame to be sent, then gives access to the LCD
+    fn lcd(&mut self) -> LcdGuard<'_, Lcd> {
+        LcdGuard(self.shared.lock_idle())
+    }
+
+    /// Queues a full frame, once the previous one is out of the way
+    fn queue(&mut self, colors: impl IntoIterator<Item = Rgb565>) -> Result<(), Lcd::Error> {
+        let mut state = self.shared.lock_idle();
+        // Errors show up one frame late. The frame that failed isn't sent again, but retrying
+        // this one covers it.
+        if let Some(e) = state.error.take() {
+            return Err(e);
+        }
+        let mut frame = std::mem::take(&mut state.spare);
+        frame.clear();
+        frame.extend(colors);
+        state.frame = Some(frame);
+        self.shared.lock_end_frame().in_flight = true;
+        drop(state);
+        self.shared.changed.notify_all();
+        Ok(())
+    }
+}
+
+fn flush_frames<Lcd: ResettableLcd>(shared: &Shared<Lcd>) {
+    let mut state = shared.lock();
+    loop {
+        state = shared
+            .changed
+            .wait_while(state, |state| state.frame.is_none())
+            .unwrap_or_else(PoisonError::into_inner);
+        let State {
+            lcd, frame, error, ..
+        } = &mut *state;
+        if let Some(pixels) = frame.as_deref() {
+            let area = lcd.bounding_box();
+            if let Err(e) = lcd.write_window(&area, pixels) {
+                *error = Some(e);
+            }
+        }
+        let stats = {
+            let mut end_frame = shared.lock_end_frame();
+            end_frame.in_flight = false;
+            end_frame.stats.take()
+        };
+        if let Some(Err(e)) = stats.map(|stats| lcd.end_frame(&stats)) {
+            shared.lock_end_frame().error = Some(e);
+        }
+        state.spare = state.frame.take().unwrap_or_default();
+        shared.changed.notify_all();
+    }
+}
+
+/// Locked LCD, with no frame in flight
+struct LcdGuard<'a, Lcd: DrawTarget>(MutexGuard<'a, State<Lcd>>);
+
+impl<Lcd: DrawTarget> std::ops::Deref for LcdGuard<'_, Lcd> {
+    type Target = Lcd;
+
+    fn deref(&self) -> &Lcd {
+        &self.0.lcd
+    }
+}
+
+impl<Lcd: DrawTarget> std::ops::DerefMut for LcdGuard<'_, Lcd> {
+    fn deref_mut(&mut self) -> &mut Lcd {
+        &mut self.0.lcd
+    }
+}
+
+impl<Lcd: DrawTarget> Dimensions for Flusher<Lcd> {
+    fn bounding_box(&self) -> Rectangle {
+        self.bounding_box
+    }
+}
+
+impl<Lcd> DrawTarget for Flusher<Lcd>
+where
+    Lcd: WindowedWrite + Send + 'static,
+    Lcd::Error: Send,
+{
+    type Color = Rgb565;
+    type Error = Lcd::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        self.lcd().draw_iter(pixels)
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        match *area == self.bounding_box {
+            true => self.queue(colors),
+            false => self.lcd().fill_contiguous(area, colors),
+        }
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.lcd().clear(color)
+    }
+}
+
+impl<Lcd> WindowedWrite for Flusher<Lcd>
+where
+    Lcd: WindowedWrite + Send + 'static,
+    Lcd::Error: Send,
+{
+    fn write_window(&mut self, area: &Rectangle, pixels: &[Rgb565]) -> Result<(), Lcd::Error> {
+        match *area == self.bounding_box {
+            true => self.queue(pixels.iter().copied()),
+            false => self.lcd().write_window(area, pixels),
+        }
+    }
+}
+
+impl<Lcd> ResettableLcd for Flusher<Lcd>
+where
+    Lcd: ResettableLcd + Send + 'static,
+    Lcd::Error: Send,
+{
+    fn init(&mut self) -> Result<()> {
+        self.lcd().init()
+    }
+
+    fn classify_error(error: Lcd::Error) -> PlatformError {
+        Lcd::classify_error(error)
+    }
+
+    /// Only after the frame is on the screen, e-paper refreshes what it has got by then. With a
+    /// frame in flight, that's up to the flushing task, and rendering goes on in the meantime.
+    fn end_frame(&mut self, stats: &FrameStats) -> Result<()> {
+        let mut end_frame = self.shared.lock_end_frame();
+        if let Some(e) = end_frame.error.take() {
+            return Err(e);
+        }
+        if end_frame.in_flight {
+            end_frame.stats = Some(stats.clone());
+            return Ok(());
+        }
+        drop(end_frame);
+        self.lcd().end_frame(stats)
+    }
+}
@@ -0,0 +1,295 @@
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use esp_idf_svc::{
+    http::{
+        client::{Configuration, EspHttpConnection},
+        server::{self, EspHttpConnection as ServerConnection, EspHttpServer, Request},
+        Headers, Method,
+    },
+    io::{Read, Write},
+};
+
+use crate::version;
+
+// PNG encoding needs quite a bit of stack
+const SERVER_STACK_SIZE: usize = 10 * 1024;
+// Name and tagline, nobody needs more on a badge
+const MAX_BADGE_REQUEST_SIZE: usize = 512;
+// Longest console commands are badge and message text
+const MAX_COMMAND_SIZE: usize = 512;
+/// Bigger than any image or config, while leaving room on the 1 MB flash filesystem
+const MAX_UPLOAD_SIZE: usize = 256 * 1024;
+/// Needed by `POST /console` and uploads, as `Authorization: Bearer <token>`. Without one they
+/// aren't served at all, or anyone on the network could rewrite config.toml.
+const TOKEN: Option<&str> = option_env!("EVIL_ANDROID_HTTP_TOKEN");
+// Where uploads can go, the mount points of the flash filesystem and the SD card
+const UPLOAD_DIRS: &[&str] = &["/storage", "/sdcard"];
+// A few frames' worth. Nothing gets drawn while asleep, so don't wait forever.
+const SCREENSHOT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Returns the HTTP status code, error statuses included. Needs WiFi to be connected.
+pub fn post(url: &str, content_type: &str, body: &[u8]) -> Result<u16> {
+    let mut connection = EspHttpConnection::new(&Configuration {
+        // Webhook services are all HTTPS, trust the same CAs as browsers do
+        crt_bundle_attach: Some(esp_idf_svc::sys::esp_crt_bundle_attach),
+        ..Default::default()
+    })
+    .context("EspHttpConnection::new failed")?;
+    let content_length = body.len().to_string();
+    connection
+        .initiate_request(
+            Method::Post,
+            url,
+            &[
+                ("Content-Type", content_type),
+                ("Content-Length", &content_length),
+            ],
+        )
+        .context("EspHttpConnection::initiate_request failed")?;
+    connection
+        .write_all(body)
+        .context("EspHttpConnection::write_all failed")?;
+    connection
+        .initiate_response()
+        .context("EspHttpConnection::initiate_response failed")?;
+    Ok(connection.status())
+}
+
+/// Fails on error statuses. The body is cut off at `max_size` bytes. Needs WiFi to be
+/// connected.
+pub fn get(url: &str, max_size: usize) -> Result<Vec<u8>> {
+    let mut connection = EspHttpConnection::new(&Configuration {
+        crt_bundle_attach: Some(esp_idf_svc::sys::esp_crt_bundle_attach),
+        ..Default::default()
+    })
+    .context("EspHttpConnection::new failed")?;
+    connection
+        .initiate_request(Method::Get, url, &[])
+        .context("EspHttpConnection::initiate_request failed")?;
+    connection
+        .initiate_response()
+        .context("EspHttpConnection::initiate_response failed")?;
+    let status = connection.status();
+    if !(200..300).contains(&status) {
+        bail!("HTTP {status}");
+    }
+    read_body(&mut connection, max_size).context("EspHttpConnection::read failed")
+}
+
+/// Up to `max_size` bytes, or until the end of the body
+fn read_body<R: Read>(reader: &mut R, max_size: usize) -> Result<Vec<u8>, R::Error> {
+    let mut body = vec![0; max_size];
+    let mut len = 0;
+    while len < max_size {
+        match reader.read(&mut body[len..])? {
+            0 => break,
+            n => len += n,
+        }
+    }
+    body.truncate(len);
+    Ok(body)
+}
+
+/// Writes `body` to `path`, creating the directories on the way. Returns the number of bytes
+/// written. Goes to a temporary file first, so that a failed upload leaves the old file as it
+/// was rather than a truncated one.
+fn write_file<R: Read>(path: &str, body: &mut R) -> Result<usize>
+where
+    R::Error: std::error::Error + Send + Sync + 'static,
+{
+    if let Some((dir, _)) = path.rsplit_once('/') {
+        std::fs::create_dir_all(dir).with_context(|| format!("cannot create {dir}"))?;
+    }
+    let temporary = format!("{path}.part");
+    let written = write_new_file(&temporary, body).and_then(|written| {
+        // FAT on the SD card doesn't rename over existing files, littlefs does
+        if std::fs::rename(&temporary, path).is_err() {
+            std::fs::remove_file(path).with_context(|| format!("cannot replace {path}"))?;
+            std::fs::rename(&temporary, path)
+                .with_context(|| format!("cannot rename {temporary} to {path}"))?;
+        }
+        Ok(written)
+    });
+    if written.is_err() {
+        let _ = std::fs::remove_file(&temporary);
+    }
+    written
+}
+
+/// Fails once `body` goes over MAX_UPLOAD_SIZE
+fn write_new_file<R: Read>(path: &str, body: &mut R) -> Result<usize>
+where
+    R::Error: std::error::Error + Send + Sync + 'static,
+{
+    let mut file = std::fs::File::create(path).with_context(|| format!("cannot create {path}"))?;
+    let mut chunk = [0; 1024];
+    let mut written = 0;
+    loop {
+        match body.read(&mut chunk)? {
+            0 => break,
+            n if written + n > MAX_UPLOAD_SIZE => bail!("over {MAX_UPLOAD_SIZE} bytes"),
+            n => {
+                std::io::Write::write_all(&mut file, &chunk[..n])
+                    .with_context(|| format!("cannot write {path}"))?;
+                written += n;
+            }
+        }
+    }
+    file.sync_all()
+        .with_context(|| format!("cannot write {path}"))?;
+    Ok(written)
+}
+
+/// Whether the request carries TOKEN
+fn is_authorized(request: &impl Headers) -> bool {
+    let Some(token) = TOKEN.filter(|token| !token.is_empty()) else {
+        return false;
+    };
+    let given = request
+        .header("Authorization")
+        .and_then(|value| value.strip_prefix("Bearer "));
+    given == Some(token)
+}
+
+/// Whether `uri` names a file in one of the upload dirs, without going outside of it
+fn is_upload_path(uri: &str) -> bool {
+    let Some(rest) = UPLOAD_DIRS
+        .iter()
+        .find_map(|dir| uri.strip_prefix(dir)?.strip_prefix('/'))
+    else {
+        return false;
+    };
+    rest.split('/')
+        .all(|part| !part.is_empty() && part != "." && part != "..")
+}
+
+/// `PUT` of a file in one of the upload dirs. Read at boot like everything else there.
+fn upload(mut request: Request<&mut ServerConnection<'_>>) -> Result<()> {
+    let path = request
+        .uri()
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .to_owned();
+    if !is_authorized(&request) {
+        request.into_status_response(401)?;
+        return Ok(());
+    }
+    if !is_upload_path(&path) {
+        request
+            .into_status_response(400)?
+            .write_all(b"invalid path")?;
+        return Ok(());
+    }
+    if request
+        .content_len()
+        .is_some_and(|len| len > MAX_UPLOAD_SIZE as u64)
+    {
+        request
+            .into_status_response(413)?
+            .write_all(format!("over {MAX_UPLOAD_SIZE} bytes").as_bytes())?;
+        return Ok(());
+    }
+    match write_file(&path, &mut request) {
+        Ok(written) => {
+            log::info!("{path} uploaded, {written} bytes");
+            request.into_ok_response()?;
+        }
+        Err(e) => request
+            .into_status_response(500)?
+            .write_all(format!("{e:#}").as_bytes())?,
+    }
+    Ok(())
+}
+
+/// Serves `GET /screenshot.png`, `POST /badge` and `GET /status`. With TOKEN set, also
+/// `POST /console` and uploads with `PUT /storage/<path>` or `PUT /sdcard/<path>`. Requests are
+/// handled as long as the returned server lives.
+pub fn serve() -> Result<EspHttpServer<'static>> {
+    let mut server = EspHttpServer::new(&server::Configuration {
+        stack_size: SERVER_STACK_SIZE,
+        // For uploads, whatever the path
+        uri_match_wildcard: true,
+        ..Default::default()
+    })
+    .context("EspHttpServer::new failed")?;
+    server
+        .fn_handler("/screenshot.png", Method::Get, |request| -> Result<()> {
+            match crate::screenshot::capture_png(SCREENSHOT_TIMEOUT) {
+                Ok(png) => request
+                    .into_response(200, None, &[("Content-Type", "image/png")])?
+                    .write_all(&png)?,
+                Err(e) => request
+                    .into_status_response(503)?
+                    .write_all(format!("{e:?}").as_bytes())?,
+            }
+            Ok(())
+        })
+        .context("EspHttpServer::fn_handler failed")?;
+    server
+        .fn_handler("/badge", Method::Post, |mut request| -> Result<()> {
+            let body = read_body(&mut request, MAX_BADGE_REQUEST_SIZE)?;
+            crate::badge::set_text(&String::from_utf8_lossy(&body));
+            request.into_ok_response()?;
+            Ok(())
+        })
+        .context("EspHttpServer::fn_handler failed")?;
+    server
+        .fn_handler("/status", Method::Get, |request| -> Result<()> {
+            request
+                .into_response(200, None, &[("Content-Type", "application/json")])?
+                .write_all(status_json().as_bytes())?;
+            Ok(())
+        })
+        .context("EspHttpServer::fn_handler failed")?;
+    log::info!("serving screenshots at /screenshot.png");
+    if TOKEN.map_or(true, str::is_empty) {
+        log::info!("no EVIL_ANDROID_HTTP_TOKEN, console and uploads over HTTP are off");
+        return Ok(server);
+    }
+    server
+        .fn_handler("/console", Method::Post, |mut request| -> Result<()> {
+            if !is_authorized(&request) {
+                request.into_status_response(401)?;
+                return Ok(());
+            }
+            let body = read_body(&mut request, MAX_COMMAND_SIZE)?;
+            let mut output = Vec::new();
+            match crate::console::execute(&String::from_utf8_lossy(&body), &mut output) {
+                Ok(()) => request.into_ok_response()?.write_all(&output)?,
+                Err(e) => request
+                    .into_status_response(400)?
+                    .write_all(format!("{e:#}").as_bytes())?,
+            }
+            Ok(())
+        })
+        .context("EspHttpServer::fn_handler failed")?;
+    for dir in UPLOAD_DIRS {
+        server
+            .fn_handler(&format!("{dir}/*"), Method::Put, upload)
+            .context("EspHttpServer::fn_handler failed")?;
+    }
+    Ok(server)
+}
+
+/// Which android this is and what it runs. None of the values have anything to escape.
+fn status_json() -> String {
+    let features = version::FEATURES
+        .split(',')
+        .filter(|feature| !feature.is_empty())
+        .map(|feature| format!("\"{feature}\""))
+        .collect::<Vec<_>>()
+        .join(",");
+    format!(
+        concat!(
+            r#"{{"name":"{}","version":"{}","commit":"{}","#,
+            r#""build_date":"{}","features":[{}]}}"#,
+        ),
+        crate::device_name::get(),
+        version::VERSION,
+        version::COMMIT,
+        version::BUILD_DATE,
+        features,
+    )
+}
@@ -0,0 +1,157 @@
+use anyhow::{Context, Result};
+use esp_idf_svc::hal::{
+    gpio::InputPin,
+    peripheral::Peripheral,
+    rmt::{config::ReceiveConfig, PinState, Pulse, Receive, RmtChannel, RxRmtDriver},
+};
+
+use crate::platform::{Input, InputEvent};
+
+// With default clock divider of 80, RMT ticks are 1us long
+const RMT_CLOCK_DIVIDER: u8 = 80;
+// Longest gap inside a NEC frame is 4.5ms, frames are ~40ms apart
+const IDLE_THRESHOLD_US: u16 = 12000;
+// Ignore spikes shorter than this many APB clock cycles
+const FILTER_TICKS: u8 = 100;
+const MAX_PULSE_PAIRS: usize = 64;
+// Pulse lengths from cheap receivers are all over the place
+const TOLERANCE_PERCENT: u32 = 25;
+
+/// Command codes of the ubiquitous 21-key "Car MP3" remote. Codes of other remotes get logged
+/// on receive, to make adding them here easier.
+const KEYMAP: &[(u8, InputEvent)] = &[
+    (0x43, InputEvent::TogglePause),    // play/pause
+    (0x40, InputEvent::NextScene),      // next
+    (0x44, InputEvent::Scrub(-1)),      // prev
+    (0x47, InputEvent::Speed(1)),       // CH+
+    (0x45, InputEvent::Speed(-1)),      // CH-
+    (0x46, InputEvent::Sleep),          // CH
+    (0x09, InputEvent::ToggleStats),    // EQ
+    (0x15, InputEvent::Brightness(1)),  // VOL+
+    (0x07, InputEvent::Brightness(-1)), // VOL-
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum NecCode {
+    Command {
+        address: u16,
+        command: u8,
+    },
+    /// Sent periodically while a button is held
+    Repeat,
+}
+
+fn is_about(duration_us: u32, expected_us: u32) -> bool {
+    duration_us.abs_diff(expected_us) <= expected_us * TOLERANCE_PERCENT / 100
+}
+
+/// `pulses` are (is_burst, duration in us) pairs. Receivers are active low, so a burst of IR
+/// carrier reads as low level.
+fn decode_nec(pulses: &[(bool, u32)]) -> Option<NecCode> {
+    const LEADER_BURST_US: u32 = 9000;
+    const LEADER_SPACE_US: u32 = 4500;
+    const REPEAT_SPACE_US: u32 = 2250;
+    const BIT_BURST_US: u32 = 562;
+    const ZERO_SPACE_US: u32 = 562;
+    const ONE_SPACE_US: u32 = 1687;
+
+    let mut pulses = pulses.iter().copied();
+    match (pulses.next(), pulses.next()) {
+        (Some((true, burst)), Some((false, space)))
+            if is_about(burst, LEADER_BURST_US) && is_about(space, REPEAT_SPACE_US) =>
+        {
+            return Some(NecCode::Repeat);
+        }
+        (Some((true, burst)), Some((false, space)))
+            if is_about(burst, LEADER_BURST_US) && is_about(space, LEADER_SPACE_US) => {}
+        _ => return None,
+    }
+
+    let mut data = 0u32;
+    for bit in 0..32 {
+        match (pulses.next(), pulses.next()) {
+            (Some((true, burst)), Some((false, space))) if is_about(burst, BIT_BURST_US) => {
+                if is_about(space, ONE_SPACE_US) {
+                    data |= 1 << bit;
+                } else if !is_about(space, ZERO_SPACE_US) {
+                    return None;
+                }
+            }
+            _ => return None,
+        }
+    }
+
+    let [address_lo, address_hi, command, command_inv] = data.to_le_bytes();
+    if command != !command_inv {
+        return None;
+    }
+    // Original NEC sends inverted address as a checksum, extended NEC uses it as a high byte
+    let address = if address_lo == !address_hi {
+        address_lo as u16
+    } else {
+        u16::from_le_bytes([address_lo, address_hi])
+    };
+    Some(NecCode::Command { address, command })
+}
+
+/// Receiver module (e.g. TSOP38238 or VS1838B) decoded using the RMT peripheral
+pub struct IrRemote<'d> {
+    rx: RxRmtDriver<'d>,
+    pulses: [(Pulse, Pulse); MAX_PULSE_PAIRS],
+}
+
+impl<'d> IrRemote<'d> {
+    pub fn new<C: RmtChannel>(
+        channel: impl Peripheral<P = C> + 'd,
+        pin: impl Peripheral<P = impl InputPin> + 'd,
+    ) -> Result<Self> {
+        let config = ReceiveConfig::new()
+            .clock_divider(RMT_CLOCK_DIVIDER)
+            .idle_threshold(IDLE_THRESHOLD_US)
+            .filter_ticks_thresh(FILTER_TICKS);
+        let mut rx = RxRmtDriver::new(channel, pin, &config, MAX_PULSE_PAIRS * 4)
+            .context("RxRmtDriver::new failed")?;
+        rx.start().context("RxRmtDriver::start failed")?;
+        Ok(Self {
+            rx,
+            pulses: [(Pulse::zero(), Pulse::zero()); MAX_PULSE_PAIRS],
+        })
+    }
+}
+
+impl Input for IrRemote<'_> {
+    fn poll(&mut self) -> Result<Option<InputEvent>> {
+        let len = match self
+            .rx
+            .receive(&mut self.pulses, 0)
+            .context("RxRmtDriver::receive failed")?
+        {
+            Receive::Read(len) => len,
+            Receive::Overflow(_) | Receive::Timeout => return Ok(None),
+        };
+
+        let pulses = self.pulses[..len]
+            .iter()
+            .flat_map(|(a, b)| [a, b])
+            .filter(|p| p.ticks.ticks() > 0)
+            .map(|p| (p.pin_state == PinState::Low, p.ticks.ticks() as u32))
+            .collect::<Vec<_>>();
+
+        match decode_nec(&pulses) {
+            Some(NecCode::Command { address, command }) => {
+                let event = KEYMAP
+                    .iter()
+                    .find(|(c, _)| *c == command)
+                    .map(|(_, event)| *event);
+                if event.is_none() {
+                    log::info!(
+                        "unmapped IR remote code: address {address:#06x}, command {command:#04x}"
+                    );
+                }
+                Ok(event)
+            }
+            // Holding buttons does nothing special, at least for now
+            Some(NecCode::Repeat) | None => Ok(None),
+        }
+    }
+}
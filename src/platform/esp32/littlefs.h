@@ -0,0 +1,2 @@
+// Bindings for the joltwallet/littlefs component, generated into esp_idf_svc::sys::littlefs
+#include "esp_littlefs.h"
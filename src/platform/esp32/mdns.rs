@@ -0,0 +1,36 @@
+use anyhow::{Context, Result};
+use esp_idf_svc::{
+    mdns::EspMdns,
+    sys::{esp, esp_efuse_mac_get_default},
+};
+
+/// Last 3 bytes of the factory MAC address, in hex
+pub fn device_id() -> String {
+    let mut mac = [0u8; 6];
+    if let Err(e) = esp!(unsafe { esp_efuse_mac_get_default(mac.as_mut_ptr()) }) {
+        log::error!("esp_efuse_mac_get_default failed: {e:?}");
+    }
+    format!("{:02x}{:02x}{:02x}", mac[3], mac[4], mac[5])
+}
+
+/// Makes the android reachable as `<name>.local`, with the HTTP server advertised if there is
+/// one. Stops advertising when dropped.
+pub fn advertise(name: &str, http: bool) -> Result<EspMdns> {
+    let mut mdns = EspMdns::take().context("EspMdns::take failed")?;
+    mdns.set_hostname(name)
+        .context("EspMdns::set_hostname failed")?;
+    mdns.set_instance_name(name)
+        .context("EspMdns::set_instance_name failed")?;
+    if http {
+        mdns.add_service(
+            Some(name),
+            "_http",
+            "_tcp",
+            80,
+            &[("path", "/screenshot.png")],
+        )
+        .context("EspMdns::add_service failed")?;
+    }
+    log::info!("advertising as {name}.local");
+    Ok(mdns)
+}
@@ -0,0 +1,97 @@
+use anyhow::Result;
+use display_interface::DisplayError;
+use embedded_graphics::{
+    draw_target::DrawTarget, geometry::Dimensions, pixelcolor::Rgb565, primitives::Rectangle, Pixel,
+};
+use esp_idf_svc::hal::i2c::I2cDriver;
+use ssd1306::{mode::BufferedGraphicsMode, prelude::*, I2CDisplayInterface, Ssd1306};
+
+use super::{ResettableLcd, WindowedWrite};
+use crate::{
+    dither::Dithered,
+    platform::{FrameStats, PlatformError},
+};
+
+type Display = Ssd1306<
+    I2CInterface<I2cDriver<'static>>,
+    DisplaySize128x64,
+    BufferedGraphicsMode<DisplaySize128x64>,
+>;
+
+/// 128x64 SSD1306 OLED on I2C, dithered down from Rgb565.
+///
+/// Drawing goes to a buffer in RAM, which is sent once the frame is done. The driver only sends
+/// the part that changed.
+pub struct Oled(Dithered<Display>);
+
+impl Oled {
+    pub fn new(i2c: I2cDriver<'static>) -> Result<Self> {
+        let display = Ssd1306::new(
+            I2CDisplayInterface::new(i2c),
+            DisplaySize128x64,
+            DisplayRotation::Rotate0,
+        )
+        .into_buffered_graphics_mode();
+        let mut oled = Oled(Dithered::new(display));
+        oled.init()?;
+        Ok(oled)
+    }
+}
+
+impl Dimensions for Oled {
+    fn bounding_box(&self) -> Rectangle {
+        self.0.bounding_box()
+    }
+}
+
+impl DrawTarget for Oled {
+    type Color = Rgb565;
+    type Error = DisplayError;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        self.0.draw_iter(pixels)
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        self.0.fill_contiguous(area, colors)
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.0.clear(color)
+    }
+}
+
+/// Frames go into the buffer and get flushed whole
+impl WindowedWrite for Oled {}
+
+impl ResettableLcd for Oled {
+    fn init(&mut self) -> Result<()> {
+        self.0
+            .inner
+            .init()
+            .map_err(|e| PlatformError::DisplayInit(format!("Ssd1306::init failed: {e:?}")).into())
+    }
+
+    fn classify_error(error: DisplayError) -> PlatformError {
+        match error {
+            DisplayError::BusWriteError => PlatformError::Bus("SSD1306 I2C write failed".into()),
+            DisplayError::DCError | DisplayError::CSError | DisplayError::RSError => {
+                PlatformError::Gpio(format!("SSD1306: {error:?}"))
+            }
+            e => PlatformError::OutOfRange(format!("SSD1306: {e:?}")),
+        }
+    }
+
+    fn end_frame(&mut self, _stats: &FrameStats) -> Result<()> {
+        self.0
+            .inner
+            .flush()
+            .map_err(|e| Self::classify_error(e).into())
+    }
+}
@@ -0,0 +1,315 @@
+use std::{
+    net::{Ipv4Addr, UdpSocket},
+    sync::mpsc::{self, RecvTimeoutError},
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::{Point, Size},
+    mono_font::{iso_8859_1::FONT_6X10, MonoTextStyle},
+    pixelcolor::Rgb565,
+    prelude::RgbColor,
+    primitives::Rectangle,
+    text::{Baseline, Text},
+    Drawable,
+};
+use esp_idf_svc::{
+    http::{
+        server::{self, EspHttpServer},
+        Method,
+    },
+    io::{Read, Write},
+    wifi::{AccessPointConfiguration, AuthMethod, Configuration, EspWifi},
+};
+use qrcode::QrCode;
+
+use super::{wifi::Credentials, ResettableLcd};
+use crate::platform::{FrameStats, PlatformError};
+
+/// Open network the phone joins to reach the setup page
+const AP_SSID: &str = "evil-android-setup";
+// SSID is up to 32 bytes, WPA2 passwords up to 64, plus form field names and URL encoding
+const MAX_FORM_LEN: usize = 512;
+const DNS_PORT: u16 = 53;
+
+const SETUP_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head><meta name="viewport" content="width=device-width, initial-scale=1"><title>evil-android WiFi setup</title></head>
+<body>
+<h1>evil-android WiFi setup</h1>
+<form method="post" action="/wifi">
+<p><label>Network name<br><input name="ssid" maxlength="32" required></label></p>
+<p><label>Password (empty for open networks)<br><input name="password" type="password" maxlength="64"></label></p>
+<p><button type="submit">Connect</button></p>
+</form>
+</body>
+</html>"#;
+
+const SAVED_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head><meta name="viewport" content="width=device-width, initial-scale=1"><title>evil-android WiFi setup</title></head>
+<body><h1>Saved</h1><p>The android will now connect to your network. This one goes away.</p></body>
+</html>"#;
+
+/// Setup network and page, up for as long as this is around
+pub struct Portal {
+    server: EspHttpServer<'static>,
+    receiver: mpsc::Receiver<Credentials>,
+}
+
+impl Portal {
+    /// Credentials entered on the setup page within `timeout`, None if nobody did. Waits for as
+    /// long as it takes without one.
+    pub fn wait(&self, timeout: Option<Duration>) -> Result<Option<Credentials>> {
+        let received = match timeout {
+            Some(timeout) => self.receiver.recv_timeout(timeout),
+            None => self
+                .receiver
+                .recv()
+                .map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match received {
+            Ok(credentials) => {
+                log::info!("WiFi setup done, network: {}", credentials.ssid);
+                Ok(Some(credentials))
+            }
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => bail!("setup server is gone"),
+        }
+    }
+
+    /// Takes the setup network down, for `wifi` to join the real one
+    pub fn close(self, wifi: &mut EspWifi<'static>) -> Result<()> {
+        drop(self.server);
+        wifi.stop().context("EspWifi::stop failed for AP")
+    }
+}
+
+/// Brings up an open access point with a captive portal, where credentials can be entered on
+/// the setup page. The LCD shows how to get there.
+pub fn start<Lcd: ResettableLcd>(wifi: &mut EspWifi<'static>, lcd: &mut Lcd) -> Result<Portal> {
+    wifi.set_configuration(&Configuration::AccessPoint(AccessPointConfiguration {
+        ssid: AP_SSID
+            .try_into()
+            .map_err(|_| PlatformError::OutOfRange("AP SSID too long".into()))?,
+        auth_method: AuthMethod::None,
+        ..Default::default()
+    }))
+    .context("EspWifi::set_configuration failed for AP")?;
+    wifi.start().context("EspWifi::start failed for AP")?;
+    let ip = wifi
+        .ap_netif()
+        .get_ip_info()
+        .context("EspNetif::get_ip_info failed")?
+        .ip;
+    log::info!("WiFi setup: join {AP_SSID} and open http://{ip}/");
+
+    draw_instructions(lcd, ip).map_err(Lcd::classify_error)?;
+    lcd.end_frame(&FrameStats {
+        scene: "wifi-setup",
+        ..Default::default()
+    })?;
+
+    spawn_dns_server(ip)?;
+
+    let (sender, receiver) = mpsc::sync_channel::<Credentials>(1);
+    let mut server = EspHttpServer::new(&server::Configuration {
+        // Captive portal checks of phones hit all sorts of paths, they all get redirected
+        uri_match_wildcard: true,
+        ..Default::default()
+    })
+    .context("EspHttpServer::new failed")?;
+    server
+        .fn_handler("/", Method::Get, |request| -> Result<()> {
+            request
+                .into_response(200, None, &[("Content-Type", "text/html")])?
+                .write_all(SETUP_PAGE.as_bytes())?;
+            Ok(())
+        })
+        .context("EspHttpServer::fn_handler failed for /")?;
+    server
+        .fn_handler("/wifi", Method::Post, move |mut request| -> Result<()> {
+            let mut form = Vec::new();
+            let mut buf = [0u8; 64];
+            loop {
+                let len = request.read(&mut buf)?;
+                if len == 0 {
+                    break;
+                }
+                form.extend_from_slice(&buf[..len]);
+                if form.len() > MAX_FORM_LEN {
+                    request.into_status_response(413)?;
+                    return Ok(());
+                }
+            }
+            let Some(credentials) = parse_form(&String::from_utf8_lossy(&form)) else {
+                request.into_status_response(400)?;
+                return Ok(());
+            };
+            request
+                .into_response(200, None, &[("Content-Type", "text/html")])?
+                .write_all(SAVED_PAGE.as_bytes())?;
+            // Only the first submission counts, the rest is dropped along with the server
+            let _ = sender.try_send(credentials);
+            Ok(())
+        })
+        .context("EspHttpServer::fn_handler failed for /wifi")?;
+    let location = format!("http://{ip}/");
+    server
+        .fn_handler("/*", Method::Get, move |request| -> Result<()> {
+            request.into_response(302, None, &[("Location", &location)])?;
+            Ok(())
+        })
+        .context("EspHttpServer::fn_handler failed for /*")?;
+
+    Ok(Portal { server, receiver })
+}
+
+fn draw_instructions<D: DrawTarget<Color = Rgb565>>(
+    target: &mut D,
+    ip: Ipv4Addr,
+) -> Result<(), D::Error> {
+    const QUIET_ZONE: u32 = 1;
+
+    target.clear(Rgb565::BLACK)?;
+
+    // Scanning it joins the setup network, phones then open the captive portal on their own
+    let qr = QrCode::new(format!("WIFI:T:nopass;S:{AP_SSID};;"))
+        .expect("setup network QR code must fit");
+    let modules = qr.width() as u32;
+    let size = target.bounding_box().size;
+    // Leaves at least half of the longer side for the text
+    let longer = size.width.max(size.height);
+    let shorter = size.width.min(size.height);
+    let scale = ((longer / 2).min(shorter) / (modules + 2 * QUIET_ZONE)).max(1);
+    let qr_size = (modules + 2 * QUIET_ZONE) * scale;
+    target.fill_solid(
+        &Rectangle::new(Point::zero(), Size::new(qr_size, qr_size)),
+        Rgb565::WHITE,
+    )?;
+    for y in 0..modules {
+        for x in 0..modules {
+            if qr[(x as usize, y as usize)] == qrcode::Color::Dark {
+                let top_left = Point::new(
+                    ((x + QUIET_ZONE) * scale) as i32,
+                    ((y + QUIET_ZONE) * scale) as i32,
+                );
+                target.fill_solid(
+                    &Rectangle::new(top_left, Size::new(scale, scale)),
+                    Rgb565::BLACK,
+                )?;
+            }
+        }
+    }
+
+    // Next to the code on landscape displays, below it on portrait ones
+    let text_position = if size.width > size.height {
+        Point::new(qr_size as i32 + 4, 4)
+    } else {
+        Point::new(4, qr_size as i32 + 4)
+    };
+    let text =
+        format!("WiFi setup\n\nJoin network\nevil-android-\nsetup, then\nopen\nhttp://{ip}/");
+    Text::with_baseline(
+        &text,
+        text_position,
+        MonoTextStyle::new(&FONT_6X10, Rgb565::WHITE),
+        Baseline::Top,
+    )
+    .draw(target)?;
+    Ok(())
+}
+
+/// `ssid=...&password=...`, as sent by the setup page
+fn parse_form(form: &str) -> Option<Credentials> {
+    let mut ssid = None;
+    let mut password = String::new();
+    for pair in form.split('&') {
+        let (key, value) = pair.split_once('=')?;
+        match key {
+            "ssid" => ssid = Some(url_decode(value)?),
+            "password" => password = url_decode(value)?,
+            _ => {}
+        }
+    }
+    Some(Credentials {
+        ssid: ssid.filter(|ssid| !ssid.is_empty())?,
+        password,
+    })
+}
+
+fn url_decode(s: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut input = s.bytes();
+    while let Some(b) = input.next() {
+        match b {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex = [input.next()?, input.next()?];
+                bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+            }
+            b => bytes.push(b),
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Answers every A query with our own address, so that whatever the phone tries to open ends up
+/// at the setup page
+fn spawn_dns_server(ip: Ipv4Addr) -> Result<()> {
+    let socket =
+        UdpSocket::bind((Ipv4Addr::UNSPECIFIED, DNS_PORT)).context("binding DNS socket failed")?;
+    std::thread::Builder::new()
+        .name("dns".to_owned())
+        .stack_size(4096)
+        .spawn(move || {
+            let mut buf = [0u8; 512];
+            loop {
+                let (len, peer) = match socket.recv_from(&mut buf) {
+                    Ok(received) => received,
+                    Err(e) => {
+                        log::error!("DNS recv_from failed: {e:?}");
+                        return;
+                    }
+                };
+                if let Some(response) = dns_response(&buf[..len], ip) {
+                    if let Err(e) = socket.send_to(&response, peer) {
+                        log::warn!("DNS send_to failed: {e:?}");
+                    }
+                }
+            }
+        })?;
+    Ok(())
+}
+
+fn dns_response(query: &[u8], ip: Ipv4Addr) -> Option<Vec<u8>> {
+    const HEADER_LEN: usize = 12;
+
+    // Exactly one question, anything else is not worth answering
+    if query.len() < HEADER_LEN || query[4..6] != [0, 1] {
+        return None;
+    }
+    // Name labels, terminated with an empty one, then type and class
+    let mut pos = HEADER_LEN;
+    while *query.get(pos)? != 0 {
+        pos += 1 + query[pos] as usize;
+    }
+    let question_end = pos + 1 + 4;
+    if question_end > query.len() {
+        return None;
+    }
+
+    let mut response = Vec::with_capacity(question_end + 16);
+    response.extend_from_slice(&query[..2]);
+    // Standard response, recursion desired and available, no error
+    response.extend_from_slice(&[0x81, 0x80]);
+    // 1 question, 1 answer, no authority nor additional records
+    response.extend_from_slice(&[0, 1, 0, 1, 0, 0, 0, 0]);
+    response.extend_from_slice(&query[HEADER_LEN..question_end]);
+    // Pointer to the name in the question, type A, class IN, TTL 60s, 4 bytes of address
+    response.extend_from_slice(&[0xc0, 0x0c, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4]);
+    response.extend_from_slice(&ip.octets());
+    Some(response)
+}
@@ -0,0 +1,122 @@
+use anyhow::{Context, Result};
+use esp_idf_svc::hal::{
+    gpio::{self, AnyInputPin, InputPin, PinDriver, Pull},
+    pcnt::{
+        Pcnt, PcntChannel, PcntChannelConfig, PcntControlMode, PcntCountMode, PcntDriver, PinIndex,
+    },
+    peripheral::Peripheral,
+};
+
+use crate::platform::{Input, InputEvent};
+
+// PCNT resets the counter to 0 when it reaches either limit. Keep it a multiple of
+// COUNTS_PER_DETENT, so that wrapping around does not lose partial steps.
+const COUNTER_LIMIT: i16 = 400;
+// Full quadrature decoding: 4 edges per mechanical click
+const COUNTS_PER_DETENT: i16 = 4;
+
+/// Quadrature rotary encoder decoded by the PCNT peripheral, with a push button
+pub struct RotaryEncoder<'d> {
+    pcnt: PcntDriver<'d>,
+    button: PinDriver<'d, AnyInputPin, gpio::Input>,
+    last_count: i16,
+    button_was_pressed: bool,
+}
+
+impl<'d> RotaryEncoder<'d> {
+    pub fn new<P: Pcnt>(
+        pcnt: impl Peripheral<P = P> + 'd,
+        pin_a: impl Peripheral<P = impl InputPin> + 'd,
+        pin_b: impl Peripheral<P = impl InputPin> + 'd,
+        button_pin: impl InputPin + 'd,
+    ) -> Result<Self> {
+        let mut pcnt = PcntDriver::new(
+            pcnt,
+            Some(pin_a),
+            Some(pin_b),
+            Option::<AnyInputPin>::None,
+            Option::<AnyInputPin>::None,
+        )
+        .context("PcntDriver::new failed")?;
+
+        let channel_config = |pos_mode, neg_mode| PcntChannelConfig {
+            lctrl_mode: PcntControlMode::Reverse,
+            hctrl_mode: PcntControlMode::Keep,
+            pos_mode,
+            neg_mode,
+            counter_h_lim: COUNTER_LIMIT,
+            counter_l_lim: -COUNTER_LIMIT,
+        };
+        pcnt.channel_config(
+            PcntChannel::Channel0,
+            PinIndex::Pin0,
+            PinIndex::Pin1,
+            &channel_config(PcntCountMode::Decrement, PcntCountMode::Increment),
+        )
+        .context("PcntDriver::channel_config failed for channel 0")?;
+        pcnt.channel_config(
+            PcntChannel::Channel1,
+            PinIndex::Pin1,
+            PinIndex::Pin0,
+            &channel_config(PcntCountMode::Increment, PcntCountMode::Decrement),
+        )
+        .context("PcntDriver::channel_config failed for channel 1")?;
+
+        // Mechanical contacts bounce a lot. Value is in APB clock cycles, max 1023.
+        pcnt.set_filter_value(1023)
+            .context("PcntDriver::set_filter_value failed")?;
+        pcnt.filter_enable()
+            .context("PcntDriver::filter_enable failed")?;
+        pcnt.counter_pause()
+            .context("PcntDriver::counter_pause failed")?;
+        pcnt.counter_clear()
+            .context("PcntDriver::counter_clear failed")?;
+        pcnt.counter_resume()
+            .context("PcntDriver::counter_resume failed")?;
+
+        let mut button = PinDriver::input(button_pin.downgrade_input())
+            .context("PinDriver::input failed for encoder button")?;
+        button
+            .set_pull(Pull::Up)
+            .context("PinDriver::set_pull failed for encoder button")?;
+
+        Ok(Self {
+            pcnt,
+            button,
+            last_count: 0,
+            button_was_pressed: false,
+        })
+    }
+}
+
+impl Input for RotaryEncoder<'_> {
+    fn poll(&mut self) -> Result<Option<InputEvent>> {
+        // Button is active low
+        let button_pressed = self.button.is_low();
+        let just_pressed = button_pressed && !self.button_was_pressed;
+        self.button_was_pressed = button_pressed;
+        if just_pressed {
+            return Ok(Some(InputEvent::TogglePause));
+        }
+
+        let count = self
+            .pcnt
+            .get_counter_value()
+            .context("PcntDriver::get_counter_value failed")?;
+        let mut delta = count - self.last_count;
+        // The counter wrapped around one of the limits since last poll. Assumes nobody can turn
+        // the knob by more than half of the range between two polls.
+        if delta > COUNTER_LIMIT / 2 {
+            delta -= COUNTER_LIMIT;
+        } else if delta < -COUNTER_LIMIT / 2 {
+            delta += COUNTER_LIMIT;
+        }
+
+        let detents = delta / COUNTS_PER_DETENT;
+        if detents == 0 {
+            return Ok(None);
+        }
+        self.last_count = (self.last_count + detents * COUNTS_PER_DETENT) % COUNTER_LIMIT;
+        Ok(Some(InputEvent::Scrub(detents.into())))
+    }
+}
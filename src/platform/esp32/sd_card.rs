@@ -0,0 +1,56 @@
+use anyhow::{Context, Result};
+use esp_idf_svc::{
+    fs::fatfs::Fatfs,
+    hal::{
+        gpio::AnyIOPin,
+        sd::{spi::SdSpiHostDriver, SdCardConfiguration, SdCardDriver},
+        spi::{SpiDriver, SpiDriverConfig, SPI3},
+    },
+    io::vfs::MountedFatfs,
+};
+
+use super::board::SdCardPins;
+
+const MOUNT_POINT: &str = "/sdcard";
+/// Assets are read one at a time, no need for more
+const MAX_OPEN_FILES: usize = 2;
+
+type Driver = SdCardDriver<SdSpiHostDriver<'static, SpiDriver<'static>>>;
+
+/// FAT-formatted SD card, mounted for as long as this lives
+pub struct SdCard(MountedFatfs<Fatfs<Driver>>);
+
+impl SdCard {
+    /// Fails if there's no card in the slot
+    pub fn mount(spi: SPI3, pins: SdCardPins) -> Result<Self> {
+        let spi = SpiDriver::new(
+            spi,
+            pins.sclk,
+            pins.mosi,
+            Some(pins.miso),
+            &SpiDriverConfig::default(),
+        )
+        .context("SpiDriver::new failed for SD card")?;
+        let host = SdSpiHostDriver::new(
+            spi,
+            Some(pins.cs),
+            AnyIOPin::none(),
+            AnyIOPin::none(),
+            AnyIOPin::none(),
+            None,
+        )
+        .context("SdSpiHostDriver::new failed")?;
+        let driver = SdCardDriver::new_spi(host, &SdCardConfiguration::new())
+            .context("SdCardDriver::new_spi failed")?;
+        let fatfs = Fatfs::new_sdcard(0, driver).context("Fatfs::new_sdcard failed")?;
+        let mounted = MountedFatfs::mount(fatfs, MOUNT_POINT, MAX_OPEN_FILES)
+            .context("MountedFatfs::mount failed")?;
+        log::info!("SD card mounted at {MOUNT_POINT}");
+        Ok(Self(mounted))
+    }
+
+    /// None if there's no such file
+    pub fn read(&self, path: &str) -> Result<Option<Vec<u8>>> {
+        super::read_file(&format!("{MOUNT_POINT}/{path}"))
+    }
+}
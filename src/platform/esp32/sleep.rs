@@ -0,0 +1,115 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use esp_idf_svc::sys::{
+    esp, esp_deep_sleep_start, esp_light_sleep_start, esp_sleep_disable_wakeup_source,
+    esp_sleep_enable_gpio_wakeup, esp_sleep_enable_timer_wakeup,
+    esp_sleep_source_t_ESP_SLEEP_WAKEUP_ALL, gpio_int_type_t_GPIO_INTR_LOW_LEVEL,
+    gpio_wakeup_disable, gpio_wakeup_enable,
+};
+
+use crate::platform::ResumeState;
+
+// RTC slow memory survives deep sleep, and gets reinitialized on any other kind of boot.
+// Only ever accessed from the main task.
+#[link_section = ".rtc.data"]
+static mut RESUME_STATE: Option<ResumeState> = None;
+
+/// What can wake the chip up, other than a timer
+pub struct WakeSources {
+    /// Active-low buttons
+    pub gpios: Vec<i32>,
+    /// Touch pads, with thresholds already set up
+    pub touch: bool,
+}
+
+fn enable_timer_wakeup(wake_after: Option<Duration>) -> Result<()> {
+    if let Some(wake_after) = wake_after {
+        esp!(unsafe { esp_sleep_enable_timer_wakeup(wake_after.as_micros() as u64) })
+            .context("esp_sleep_enable_timer_wakeup failed")?;
+    }
+    Ok(())
+}
+
+#[cfg(esp32)]
+fn enable_touch_wakeup(wake: &WakeSources) -> Result<()> {
+    if wake.touch {
+        esp!(unsafe { esp_idf_svc::sys::esp_sleep_enable_touchpad_wakeup() })
+            .context("esp_sleep_enable_touchpad_wakeup failed")?;
+    }
+    Ok(())
+}
+
+#[cfg(not(esp32))]
+fn enable_touch_wakeup(_wake: &WakeSources) -> Result<()> {
+    Ok(())
+}
+
+/// Any GPIO can wake the chip from light sleep, and all of RAM and peripherals stay as they were
+pub fn light_sleep(wake: &WakeSources, wake_after: Option<Duration>) -> Result<()> {
+    enable_timer_wakeup(wake_after)?;
+    enable_touch_wakeup(wake)?;
+    for &gpio in &wake.gpios {
+        esp!(unsafe { gpio_wakeup_enable(gpio, gpio_int_type_t_GPIO_INTR_LOW_LEVEL) })
+            .with_context(|| format!("gpio_wakeup_enable failed for GPIO{gpio}"))?;
+    }
+    esp!(unsafe { esp_sleep_enable_gpio_wakeup() })
+        .context("esp_sleep_enable_gpio_wakeup failed")?;
+
+    let result = esp!(unsafe { esp_light_sleep_start() }).context("esp_light_sleep_start failed");
+
+    for &gpio in &wake.gpios {
+        // Otherwise the pin keeps triggering interrupts meant for waking up
+        unsafe { gpio_wakeup_disable(gpio) };
+    }
+    unsafe { esp_sleep_disable_wakeup_source(esp_sleep_source_t_ESP_SLEEP_WAKEUP_ALL) };
+    result
+}
+
+/// Only RTC GPIOs can wake the chip from deep sleep, and there's a single ext0 source for them.
+/// The first such button is used.
+#[cfg(not(esp32c3))]
+fn enable_button_wakeup(wake: &WakeSources) -> Result<()> {
+    use esp_idf_svc::sys::{esp_sleep_enable_ext0_wakeup, esp_sleep_is_valid_wakeup_gpio};
+
+    match wake
+        .gpios
+        .iter()
+        .find(|&&gpio| unsafe { esp_sleep_is_valid_wakeup_gpio(gpio) })
+    {
+        Some(&gpio) => esp!(unsafe { esp_sleep_enable_ext0_wakeup(gpio, 0) })
+            .with_context(|| format!("esp_sleep_enable_ext0_wakeup failed for GPIO{gpio}")),
+        None => {
+            log::warn!("no button can wake up from deep sleep");
+            Ok(())
+        }
+    }
+}
+
+/// ESP32-C3 can only wake up from deep sleep on GPIO0-5, none of which has a button
+#[cfg(esp32c3)]
+fn enable_button_wakeup(_wake: &WakeSources) -> Result<()> {
+    Ok(())
+}
+
+/// Never returns, waking up goes through a reboot
+pub fn deep_sleep(
+    wake: &WakeSources,
+    wake_after: Option<Duration>,
+    resume: Option<ResumeState>,
+) -> ! {
+    unsafe { RESUME_STATE = resume };
+    let result = enable_timer_wakeup(wake_after)
+        .and_then(|_| enable_touch_wakeup(wake))
+        .and_then(|_| enable_button_wakeup(wake));
+    if let Err(e) = result {
+        // Still better to sleep until reset than to drain the battery
+        log::error!("{e:?}");
+    }
+    unsafe { esp_deep_sleep_start() }
+}
+
+/// State saved before the last deep sleep, None after any other kind of boot
+pub fn take_resume_state() -> Option<ResumeState> {
+    unsafe { std::ptr::replace(std::ptr::addr_of_mut!(RESUME_STATE), None) }
+}
@@ -0,0 +1,260 @@
+use anyhow::{Context, Result};
+use embedded_graphics::{
+    draw_target::DrawTarget, geometry::Dimensions, pixelcolor::Rgb565, primitives::Rectangle, Pixel,
+};
+use esp_idf_svc::hal::{
+    delay::FreeRtos,
+    gpio::{AnyOutputPin, Output, PinDriver},
+};
+#[cfg(feature = "board-t-display")]
+use esp_idf_svc::hal::{
+    gpio::AnyInputPin,
+    spi::{
+        config::{Config, MODE_0},
+        SpiDeviceDriver, SpiDriverConfig, SPI2,
+    },
+    units::FromValueType,
+};
+#[cfg(feature = "board-t-display-s3")]
+use mipidsi::interface::{Generic8BitBus, ParallelError, ParallelInterface};
+#[cfg(feature = "board-t-display")]
+use mipidsi::interface::{SpiError, SpiInterface};
+use mipidsi::{
+    interface::Interface,
+    models::ST7789,
+    options::{ColorInversion, ColorOrder, Orientation, Rotation},
+    Builder, Display,
+};
+
+use super::{board, board::Panel, ResettableLcd, WindowedWrite};
+use crate::platform::PlatformError;
+
+type OutputPinDriver = PinDriver<'static, AnyOutputPin, Output>;
+
+fn output(pin: AnyOutputPin, name: &str) -> Result<OutputPinDriver> {
+    PinDriver::output(pin).with_context(|| format!("PinDriver::output failed for {name}"))
+}
+
+/// Errors of the interfaces mipidsi drives the LCD through, told apart by what failed
+pub trait InterfaceError: core::fmt::Debug {
+    fn classify(self) -> PlatformError;
+}
+
+/// A SPI transfer may work the next time, a D/C pin that can't be set won't
+#[cfg(feature = "board-t-display")]
+impl<SPI: core::fmt::Debug, DC: core::fmt::Debug> InterfaceError for SpiError<SPI, DC> {
+    fn classify(self) -> PlatformError {
+        match self {
+            SpiError::Spi(e) => PlatformError::Bus(format!("ST7789 SPI transfer failed: {e:?}")),
+            SpiError::Dc(e) => PlatformError::Gpio(format!("ST7789 D/C pin: {e:?}")),
+        }
+    }
+}
+
+/// The bus is bit-banged, so every part of it is a GPIO
+#[cfg(feature = "board-t-display-s3")]
+impl<BUS, DC, WR> InterfaceError for ParallelError<BUS, DC, WR>
+where
+    BUS: core::fmt::Debug,
+    DC: core::fmt::Debug,
+    WR: core::fmt::Debug,
+{
+    fn classify(self) -> PlatformError {
+        PlatformError::Gpio(format!("ST7789 parallel bus: {self:?}"))
+    }
+}
+
+/// ST7789 LCD driven by mipidsi, which unlike st7735-lcd has no way to re-run the
+/// initialization sequence in place. The display gets taken apart and built again instead.
+pub struct St7789<DI, RST> {
+    // Only None if re-initialization failed
+    display: Option<Display<DI, ST7789, RST>>,
+    panel: &'static Panel,
+    bounding_box: Rectangle,
+}
+
+fn build<DI, RST>(di: DI, reset: RST, panel: &Panel) -> Result<Display<DI, ST7789, RST>>
+where
+    DI: Interface<Word = u8>,
+    RST: embedded_hal::digital::OutputPin,
+{
+    // mipidsi takes the size and offset in the native portrait orientation
+    let color_order = if panel.rgb {
+        ColorOrder::Rgb
+    } else {
+        ColorOrder::Bgr
+    };
+    let inversion = if panel.inverted {
+        ColorInversion::Inverted
+    } else {
+        ColorInversion::Normal
+    };
+    Builder::new(ST7789, di)
+        .display_size(panel.size.height as u16, panel.size.width as u16)
+        .display_offset(panel.offset.1, panel.offset.0)
+        .orientation(Orientation::new().rotate(Rotation::Deg90))
+        .color_order(color_order)
+        .invert_colors(inversion)
+        .reset_pin(reset)
+        .init(&mut FreeRtos)
+        .map_err(|e| PlatformError::DisplayInit(format!("ST7789 init failed: {e:?}")).into())
+}
+
+impl<DI, RST> St7789<DI, RST>
+where
+    DI: Interface<Word = u8>,
+    RST: embedded_hal::digital::OutputPin,
+{
+    pub fn new(di: DI, reset: RST, panel: &'static Panel) -> Result<Self> {
+        Ok(Self {
+            display: Some(build(di, reset, panel)?),
+            panel,
+            bounding_box: Rectangle::new(Default::default(), panel.size),
+        })
+    }
+}
+
+impl<DI, RST> Dimensions for St7789<DI, RST> {
+    fn bounding_box(&self) -> Rectangle {
+        self.bounding_box
+    }
+}
+
+impl<DI, RST> DrawTarget for St7789<DI, RST>
+where
+    DI: Interface<Word = u8>,
+    RST: embedded_hal::digital::OutputPin,
+{
+    type Color = Rgb565;
+    type Error = <Display<DI, ST7789, RST> as DrawTarget>::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        match self.display.as_mut() {
+            Some(display) => display.draw_iter(pixels),
+            None => Ok(()),
+        }
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        match self.display.as_mut() {
+            Some(display) => display.fill_contiguous(area, colors),
+            None => Ok(()),
+        }
+    }
+}
+
+/// mipidsi's `fill_contiguous` already streams the pixels into an address window
+impl<DI, RST> WindowedWrite for St7789<DI, RST>
+where
+    DI: Interface<Word = u8>,
+    RST: embedded_hal::digital::OutputPin,
+{
+}
+
+impl<DI, RST> ResettableLcd for St7789<DI, RST>
+where
+    DI: Interface<Word = u8>,
+    DI::Error: InterfaceError,
+    RST: embedded_hal::digital::OutputPin,
+{
+    fn init(&mut self) -> Result<()> {
+        let Some(display) = self.display.take() else {
+            anyhow::bail!("ST7789 was lost in an earlier failed re-initialization");
+        };
+        let (di, _, reset) = display.release();
+        let reset = reset.expect("reset pin is always set by build()");
+        self.display = Some(build(di, reset, self.panel)?);
+        Ok(())
+    }
+
+    fn classify_error(error: DI::Error) -> PlatformError {
+        error.classify()
+    }
+}
+
+/// ST7789 on a SPI bus, with backlight that stays on as long as the returned pin lives
+#[cfg(feature = "board-t-display")]
+pub fn new_spi(
+    spi: SPI2,
+    pins: board::SpiLcdPins,
+    panel: &'static Panel,
+) -> Result<(impl ResettableLcd + 'static, Option<OutputPinDriver>)> {
+    let spi = SpiDeviceDriver::new_single(
+        spi,
+        pins.sclk,
+        pins.mosi,
+        <Option<AnyInputPin>>::None,
+        Some(pins.cs),
+        &SpiDriverConfig::new(),
+        &Config::new().baudrate(40.MHz().into()).data_mode(MODE_0),
+    )
+    .context("SpiDeviceDriver::new_single failed")?;
+    // Lives as long as the LCD, which is forever
+    let buffer = Box::leak(Box::new([0u8; 512]));
+    let di = SpiInterface::new(spi, output(pins.dc, "lcd_dc")?, buffer);
+
+    log::info!("initializing LCD");
+    let lcd = St7789::new(di, output(pins.reset, "lcd_reset")?, panel)?;
+    let backlight = pins
+        .backlight
+        .map(|pin| -> Result<_> {
+            let mut backlight = output(pin, "lcd_led")?;
+            backlight
+                .set_high()
+                .context("PinDriver::set_high failed for lcd_led")?;
+            Ok(backlight)
+        })
+        .transpose()?;
+    Ok((lcd, backlight))
+}
+
+/// ST7789 on an 8-bit parallel bus, bit-banged through GPIOs. Returned pins keep the LCD
+/// powered and selected.
+#[cfg(feature = "board-t-display-s3")]
+pub fn new_parallel(
+    pins: board::ParallelLcdPins,
+    panel: &'static Panel,
+) -> Result<(impl ResettableLcd + 'static, [OutputPinDriver; 4])> {
+    let mut power = output(pins.power, "lcd_power")?;
+    power
+        .set_high()
+        .context("PinDriver::set_high failed for lcd_power")?;
+    // Write-only, chip always selected
+    let mut rd = output(pins.rd, "lcd_rd")?;
+    rd.set_high()
+        .context("PinDriver::set_high failed for lcd_rd")?;
+    let mut cs = output(pins.cs, "lcd_cs")?;
+    cs.set_low()
+        .context("PinDriver::set_low failed for lcd_cs")?;
+
+    let [d0, d1, d2, d3, d4, d5, d6, d7] = pins.data;
+    let data = [
+        output(d0, "lcd_d0")?,
+        output(d1, "lcd_d1")?,
+        output(d2, "lcd_d2")?,
+        output(d3, "lcd_d3")?,
+        output(d4, "lcd_d4")?,
+        output(d5, "lcd_d5")?,
+        output(d6, "lcd_d6")?,
+        output(d7, "lcd_d7")?,
+    ];
+    let di = ParallelInterface::new(
+        Generic8BitBus::new(data),
+        output(pins.dc, "lcd_dc")?,
+        output(pins.wr, "lcd_wr")?,
+    );
+
+    log::info!("initializing LCD");
+    let lcd = St7789::new(di, output(pins.reset, "lcd_reset")?, panel)?;
+    let mut backlight = output(pins.backlight, "lcd_led")?;
+    backlight
+        .set_high()
+        .context("PinDriver::set_high failed for lcd_led")?;
+    Ok((lcd, [power, rd, cs, backlight]))
+}
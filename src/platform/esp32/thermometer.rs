@@ -0,0 +1,91 @@
+use anyhow::{Context, Result};
+use esp_idf_svc::hal::delay::BLOCK;
+
+use super::SensorBus;
+
+const SHT31_ADDRS: [u8; 2] = [0x44, 0x45];
+const SHT31_CMD_READ_STATUS: [u8; 2] = [0xf3, 0x2d];
+// Periodic mode, 1 measurement per second, high repeatability
+const SHT31_CMD_PERIODIC_1MPS: [u8; 2] = [0x21, 0x30];
+const SHT31_CMD_FETCH_DATA: [u8; 2] = [0xe0, 0x00];
+
+/// SHT31 on the sensors bus, or the internal sensor of chips that have a supported one
+pub enum Thermometer {
+    Sht31 {
+        i2c: SensorBus,
+        addr: u8,
+    },
+    /// The original ESP32 has one too, but ESP-IDF doesn't support it
+    #[cfg(not(esp32))]
+    Internal(esp_idf_svc::sys::temperature_sensor_handle_t),
+}
+
+impl Thermometer {
+    /// Prefers an SHT31 if one is connected, None if there's no thermometer at all
+    pub fn detect(i2c: &SensorBus) -> Result<Option<Self>> {
+        let mut status = [0u8; 3];
+        let sht31_addr = SHT31_ADDRS.into_iter().find(|&addr| {
+            i2c.borrow_mut()
+                .write_read(addr, &SHT31_CMD_READ_STATUS, &mut status, BLOCK)
+                .is_ok()
+        });
+        if let Some(addr) = sht31_addr {
+            log::info!("found SHT31 at {addr:#x}");
+            i2c.borrow_mut()
+                .write(addr, &SHT31_CMD_PERIODIC_1MPS, BLOCK)
+                .context("failed to start SHT31 measurements")?;
+            return Ok(Some(Thermometer::Sht31 {
+                i2c: i2c.clone(),
+                addr,
+            }));
+        }
+
+        #[cfg(not(esp32))]
+        {
+            use esp_idf_svc::sys::{
+                esp, temperature_sensor_config_t, temperature_sensor_enable,
+                temperature_sensor_install,
+            };
+
+            let config = temperature_sensor_config_t {
+                // Accuracy is best in the range around what a busy chip reaches
+                range_min: 20,
+                range_max: 100,
+                ..Default::default()
+            };
+            let mut handle = std::ptr::null_mut();
+            esp!(unsafe { temperature_sensor_install(&config, &mut handle) })
+                .context("temperature_sensor_install failed")?;
+            esp!(unsafe { temperature_sensor_enable(handle) })
+                .context("temperature_sensor_enable failed")?;
+            log::info!("using internal temperature sensor");
+            Ok(Some(Thermometer::Internal(handle)))
+        }
+        #[cfg(esp32)]
+        Ok(None)
+    }
+
+    /// In °C
+    pub fn read(&mut self) -> Result<f32> {
+        match self {
+            Thermometer::Sht31 { i2c, addr } => {
+                // Temperature MSB, LSB, CRC, then the same for humidity, which is ignored
+                let mut data = [0u8; 3];
+                i2c.borrow_mut()
+                    .write_read(*addr, &SHT31_CMD_FETCH_DATA, &mut data, BLOCK)
+                    .context("SHT31 read failed")?;
+                let raw = u16::from_be_bytes([data[0], data[1]]);
+                Ok(-45.0 + 175.0 * raw as f32 / u16::MAX as f32)
+            }
+            #[cfg(not(esp32))]
+            Thermometer::Internal(handle) => {
+                let mut celsius = 0f32;
+                esp_idf_svc::sys::esp!(unsafe {
+                    esp_idf_svc::sys::temperature_sensor_get_celsius(*handle, &mut celsius)
+                })
+                .context("temperature_sensor_get_celsius failed")?;
+                Ok(celsius)
+            }
+        }
+    }
+}
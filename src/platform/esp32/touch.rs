@@ -0,0 +1,108 @@
+use std::collections::VecDeque;
+
+use anyhow::{Context, Result};
+use esp_idf_svc::{
+    hal::gpio::AnyIOPin,
+    sys::{
+        esp, touch_pad_config, touch_pad_filter_start, touch_pad_init, touch_pad_read_filtered,
+        touch_pad_set_thresh, touch_pad_t,
+    },
+};
+
+use crate::platform::{Input, InputEvent};
+
+const FILTER_PERIOD_MS: u32 = 10;
+// Touching the pad lowers the reading. Anything below this fraction of the untouched baseline
+// counts as a touch.
+const TOUCH_THRESHOLD_RATIO: f32 = 0.66;
+
+struct TouchPad {
+    // Held only to make sure nothing else uses the GPIO
+    _pin: AnyIOPin,
+    num: touch_pad_t,
+    threshold: u16,
+    was_touched: bool,
+    event: InputEvent,
+}
+
+impl TouchPad {
+    fn read(&self) -> Result<u16> {
+        let mut value = 0u16;
+        esp!(unsafe { touch_pad_read_filtered(self.num, &mut value) })
+            .with_context(|| format!("touch_pad_read_filtered failed for pad {}", self.num))?;
+        Ok(value)
+    }
+}
+
+/// Bare copper pads used as buttons, reported on touch (not release)
+pub struct TouchPads {
+    pads: Vec<TouchPad>,
+    // Touches seen together, reported one per poll
+    pending: VecDeque<InputEvent>,
+}
+
+impl TouchPads {
+    /// `pads` is a list of (pin, touch pad number of that pin, event to report on touch). Pads
+    /// must not be touched during initialization, as it's when baseline readings are taken.
+    pub fn new(
+        pads: impl IntoIterator<Item = (AnyIOPin, touch_pad_t, InputEvent)>,
+    ) -> Result<Self> {
+        esp!(unsafe { touch_pad_init() }).context("touch_pad_init failed")?;
+
+        let pads = pads
+            .into_iter()
+            .map(|(pin, num, event)| -> Result<_> {
+                esp!(unsafe { touch_pad_config(num, 0) })
+                    .with_context(|| format!("touch_pad_config failed for pad {num}"))?;
+                Ok(TouchPad {
+                    _pin: pin,
+                    num,
+                    threshold: 0,
+                    was_touched: false,
+                    event,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        esp!(unsafe { touch_pad_filter_start(FILTER_PERIOD_MS) })
+            .context("touch_pad_filter_start failed")?;
+        // Let the filter settle before taking the baseline
+        std::thread::sleep(std::time::Duration::from_millis(
+            FILTER_PERIOD_MS as u64 * 10,
+        ));
+
+        let mut touch_pads = Self {
+            pads,
+            pending: VecDeque::new(),
+        };
+        for pad in touch_pads.pads.iter_mut() {
+            let baseline = pad.read()?;
+            pad.threshold = (baseline as f32 * TOUCH_THRESHOLD_RATIO) as u16;
+            // Only used for waking up from sleep
+            esp!(unsafe { touch_pad_set_thresh(pad.num, pad.threshold) })
+                .with_context(|| format!("touch_pad_set_thresh failed for pad {}", pad.num))?;
+            log::info!(
+                "touch pad {}: baseline {baseline}, threshold {}",
+                pad.num,
+                pad.threshold
+            );
+        }
+        Ok(touch_pads)
+    }
+}
+
+impl Input for TouchPads {
+    fn poll(&mut self) -> Result<Option<InputEvent>> {
+        if let Some(event) = self.pending.pop_front() {
+            return Ok(Some(event));
+        }
+        for pad in self.pads.iter_mut() {
+            let touched = pad.read()? < pad.threshold;
+            if touched && !pad.was_touched {
+                self.pending.push_back(pad.event);
+            }
+            pad.was_touched = touched;
+        }
+        Ok(self.pending.pop_front())
+    }
+}
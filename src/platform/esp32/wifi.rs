@@ -0,0 +1,188 @@
+use std::{net::Ipv4Addr, time::Duration};
+
+use anyhow::{Context, Result};
+use esp_idf_svc::{
+    eventloop::EspSystemEventLoop,
+    hal::{modem::Modem, reset},
+    nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault},
+    sys::{esp, esp_netif_get_handle_from_ifkey, esp_netif_get_ip_info, esp_netif_ip_info_t},
+    wifi::{AuthMethod, ClientConfiguration, Configuration, EspWifi},
+};
+
+use super::{provisioning, ResettableLcd, NVS_NAMESPACE};
+use crate::platform::PlatformError;
+
+// Set at build time, these take precedence over whatever got provisioned
+const SSID: Option<&str> = option_env!("EVIL_ANDROID_WIFI_SSID");
+const PASSWORD: Option<&str> = option_env!("EVIL_ANDROID_WIFI_PASSWORD");
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(10);
+/// How long booting waits for WiFi setup before going on without WiFi
+const SETUP_TIMEOUT: Duration = Duration::from_secs(180);
+const NVS_SSID_KEY: &str = "wifi_ssid";
+const NVS_PASSWORD_KEY: &str = "wifi_password";
+
+pub struct Credentials {
+    pub ssid: String,
+    /// Empty for open networks
+    pub password: String,
+}
+
+impl Credentials {
+    fn from_build_env() -> Option<Self> {
+        Some(Self {
+            ssid: SSID?.to_owned(),
+            password: PASSWORD.unwrap_or_default().to_owned(),
+        })
+    }
+
+    fn load(nvs: &EspNvs<NvsDefault>) -> Result<Option<Self>> {
+        // Max SSID is 32 bytes, max WPA2 password is 64, plus NUL
+        let mut ssid = [0u8; 33];
+        let mut password = [0u8; 65];
+        let Some(ssid) = nvs
+            .get_str(NVS_SSID_KEY, &mut ssid)
+            .context("EspNvs::get_str failed for SSID")?
+        else {
+            return Ok(None);
+        };
+        let password = nvs
+            .get_str(NVS_PASSWORD_KEY, &mut password)
+            .context("EspNvs::get_str failed for password")?
+            .unwrap_or_default();
+        Ok(Some(Self {
+            ssid: ssid.to_owned(),
+            password: password.to_owned(),
+        }))
+    }
+
+    fn store(&self, nvs: &mut EspNvs<NvsDefault>) -> Result<()> {
+        nvs.set_str(NVS_PASSWORD_KEY, &self.password)
+            .context("EspNvs::set_str failed for password")?;
+        // Last, so that a half-saved password is never used
+        nvs.set_str(NVS_SSID_KEY, &self.ssid)
+            .context("EspNvs::set_str failed for SSID")?;
+        Ok(())
+    }
+}
+
+/// Connects in the background and keeps reconnecting whenever the connection drops.
+/// Credentials come from the build environment, or NVS. If neither has any, runs WiFi
+/// provisioning first, with instructions shown on the LCD. If nobody sets it up within
+/// SETUP_TIMEOUT, boots without WiFi, keeping the setup page up in the background until someone
+/// does. Returns whether WiFi is enabled.
+pub fn start(
+    modem: Modem,
+    sysloop: EspSystemEventLoop,
+    partition: EspDefaultNvsPartition,
+    nvs: Option<&mut EspNvs<NvsDefault>>,
+    lcd: &mut impl ResettableLcd,
+) -> Result<bool> {
+    let mut wifi =
+        EspWifi::new(modem, sysloop, Some(partition.clone())).context("EspWifi::new failed")?;
+
+    let saved = match &nvs {
+        Some(nvs) => Credentials::load(nvs)
+            .inspect_err(|e| log::error!("loading WiFi credentials failed: {e:?}"))
+            .ok()
+            .flatten(),
+        None => None,
+    };
+    let credentials = match (Credentials::from_build_env(), saved, nvs) {
+        (Some(credentials), _, _) | (None, Some(credentials), _) => credentials,
+        (None, None, Some(nvs)) => {
+            let portal = provisioning::start(&mut wifi, lcd).context("WiFi setup failed")?;
+            let Some(credentials) = portal.wait(Some(SETUP_TIMEOUT))? else {
+                log::warn!("no WiFi setup within {SETUP_TIMEOUT:?}, going on without WiFi");
+                finish_setup_in_background(portal, wifi, partition)?;
+                return Ok(false);
+            };
+            portal.close(&mut wifi)?;
+            credentials.store(nvs)?;
+            credentials
+        }
+        (None, None, None) => {
+            log::info!("no WiFi credentials and nowhere to save them, WiFi disabled");
+            return Ok(false);
+        }
+    };
+
+    wifi.set_configuration(&Configuration::Client(ClientConfiguration {
+        ssid: credentials
+            .ssid
+            .as_str()
+            .try_into()
+            .map_err(|_| PlatformError::OutOfRange("WiFi SSID too long".into()))?,
+        password: credentials
+            .password
+            .as_str()
+            .try_into()
+            .map_err(|_| PlatformError::OutOfRange("WiFi password too long".into()))?,
+        auth_method: if credentials.password.is_empty() {
+            AuthMethod::None
+        } else {
+            AuthMethod::WPA2Personal
+        },
+        ..Default::default()
+    }))
+    .context("EspWifi::set_configuration failed")?;
+    wifi.start().context("EspWifi::start failed")?;
+
+    let ssid = credentials.ssid;
+    std::thread::Builder::new()
+        .name("wifi".to_owned())
+        .stack_size(4096)
+        .spawn(move || loop {
+            if !wifi.is_connected().unwrap_or(false) {
+                log::info!("connecting to WiFi {ssid}");
+                if let Err(e) = wifi.connect() {
+                    log::warn!("EspWifi::connect failed: {e:?}");
+                }
+            }
+            std::thread::sleep(RECONNECT_INTERVAL);
+        })?;
+    Ok(true)
+}
+
+/// Keeps the setup network up until credentials are entered, saves them and restarts, for WiFi
+/// and everything that needs it to come up
+fn finish_setup_in_background(
+    portal: provisioning::Portal,
+    wifi: EspWifi<'static>,
+    partition: EspDefaultNvsPartition,
+) -> Result<()> {
+    std::thread::Builder::new()
+        .name("wifi".to_owned())
+        .stack_size(4096)
+        .spawn(move || {
+            let saved = || -> Result<()> {
+                // Without a timeout, there's no returning empty-handed
+                let credentials = portal.wait(None)?.context("no credentials")?;
+                let mut nvs =
+                    EspNvs::new(partition, NVS_NAMESPACE, true).context("EspNvs::new failed")?;
+                credentials.store(&mut nvs)
+            };
+            match saved() {
+                Ok(()) => {
+                    log::info!("WiFi set up, restarting");
+                    drop(wifi);
+                    reset::restart();
+                }
+                Err(e) => log::error!("WiFi setup failed: {e:?}"),
+            }
+        })?;
+    Ok(())
+}
+
+/// What DHCP gave the station, None until connected
+pub fn ip_address() -> Option<Ipv4Addr> {
+    // Created by EspWifi::new, without a handle to it outside of the wifi thread
+    let netif = unsafe { esp_netif_get_handle_from_ifkey(c"WIFI_STA_DEF".as_ptr()) };
+    if netif.is_null() {
+        return None;
+    }
+    let mut info = esp_netif_ip_info_t::default();
+    esp!(unsafe { esp_netif_get_ip_info(netif, &mut info) }).ok()?;
+    // Network byte order, first octet in the lowest byte
+    let ip = Ipv4Addr::from(info.ip.addr.to_le_bytes());
+    (!ip.is_unspecified()).then_some(ip)
+}
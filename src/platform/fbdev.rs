@@ -0,0 +1,355 @@
+use std::{
+    convert::Infallible,
+    fs::{File, OpenOptions},
+    net::IpAddr,
+    os::unix::fs::FileExt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use embedded_graphics::{
+    geometry::Size,
+    pixelcolor::{Rgb565, Rgb888},
+    prelude::{DrawTarget, IntoStorage, RgbColor},
+};
+use embedded_graphics_framebuf::FrameBuf;
+
+use super::{
+    shared_buffer::SharedBuffer, storage_dir::StorageDir, BatteryStatus, Brightness, DisplayId,
+    FrameStats, MemoryStats, PlatformError, ResumeState, SensorReadings,
+};
+use crate::{battery::SimulatedBattery, console::Console};
+
+pub const DEVICE: &str = "/dev/fb0";
+const SYSFS_DIR: &str = "/sys/class/graphics/fb0";
+
+const FRAME_INTERVAL: Duration = Duration::from_millis(33);
+// LEDs are drawn as squares above the LCD image, in LCD pixels
+const LED_SIZE: u32 = 8;
+const LED_MARGIN: u32 = 4;
+
+#[derive(Clone)]
+pub struct FbdevLED(Arc<Mutex<Brightness>>);
+
+impl super::LED for FbdevLED {
+    fn write_brightness(&mut self, brightness: Brightness) -> Result<()> {
+        *self.0.lock().unwrap() = brightness;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy)]
+enum PixelFormat {
+    Rgb565,
+    // Little-endian XRGB8888, i.e. BGRX in memory
+    Bgra8888,
+}
+
+impl PixelFormat {
+    fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::Rgb565 => 2,
+            PixelFormat::Bgra8888 => 4,
+        }
+    }
+
+    fn write(self, out: &mut Vec<u8>, color: Rgb888) {
+        match self {
+            PixelFormat::Rgb565 => {
+                out.extend_from_slice(&Rgb565::from(color).into_storage().to_le_bytes())
+            }
+            PixelFormat::Bgra8888 => out.extend_from_slice(&[color.b(), color.g(), color.r(), 0]),
+        }
+    }
+}
+
+/// Screen geometry, as reported by the fbdev driver
+struct Geometry {
+    size: Size,
+    stride: usize,
+    format: PixelFormat,
+}
+
+fn read_sysfs(name: &str) -> Result<String> {
+    let path = format!("{SYSFS_DIR}/{name}");
+    let contents = std::fs::read_to_string(&path).with_context(|| format!("cannot read {path}"))?;
+    Ok(contents.trim().to_owned())
+}
+
+impl Geometry {
+    fn read() -> Result<Self> {
+        let virtual_size = read_sysfs("virtual_size")?;
+        let (width, height) = virtual_size.split_once(',').ok_or_else(|| {
+            PlatformError::DisplayInit(format!("bad virtual_size: {virtual_size}"))
+        })?;
+        let format = match read_sysfs("bits_per_pixel")?.as_str() {
+            "16" => PixelFormat::Rgb565,
+            "32" => PixelFormat::Bgra8888,
+            bpp => {
+                let error = format!("unsupported framebuffer depth: {bpp} bpp");
+                return Err(PlatformError::DisplayInit(error).into());
+            }
+        };
+        Ok(Geometry {
+            size: Size::new(width.parse()?, height.parse()?),
+            stride: read_sysfs("stride")?.parse()?,
+            format,
+        })
+    }
+}
+
+fn led_color(brightness: Brightness) -> Rgb888 {
+    // Not completely black, so that an LED that's off is still visible
+    Rgb888::new(40 + (f32::from(brightness) * 215.0) as u8, 0, 0)
+}
+
+/// Draws the LCD contents scaled up by an integer factor, centered on the screen, with the LEDs
+/// above. Only the rows covered by the picture get written.
+struct Renderer {
+    file: File,
+    geometry: Geometry,
+    lcd_size: Size,
+    scale: u32,
+    origin: (u32, u32),
+    row: Vec<u8>,
+}
+
+impl Renderer {
+    fn new(file: File, geometry: Geometry, lcd_size: Size) -> Self {
+        let led_strip = LED_SIZE + LED_MARGIN;
+        let scale = (geometry.size.width / lcd_size.width)
+            .min(geometry.size.height / (lcd_size.height + led_strip))
+            .max(1);
+        let picture = Size::new(lcd_size.width, lcd_size.height + led_strip) * scale;
+        let origin = (
+            geometry.size.width.saturating_sub(picture.width) / 2,
+            geometry.size.height.saturating_sub(picture.height) / 2,
+        );
+        Renderer {
+            file,
+            geometry,
+            lcd_size,
+            scale,
+            origin,
+            row: Vec::new(),
+        }
+    }
+
+    fn write_row(&mut self, y: u32) -> std::io::Result<()> {
+        if y >= self.geometry.size.height {
+            return Ok(());
+        }
+        let offset = y as usize * self.geometry.stride
+            + self.origin.0 as usize * self.geometry.format.bytes_per_pixel();
+        self.file.write_all_at(&self.row, offset as u64)
+    }
+
+    fn render(&mut self, pixels: &[Rgb565], leds: [Brightness; 2]) -> std::io::Result<()> {
+        let format = self.geometry.format;
+        let width = self.lcd_size.width as usize;
+        let max_columns = self.geometry.size.width.saturating_sub(self.origin.0) / self.scale;
+        let columns = width.min(max_columns as usize);
+
+        self.row.clear();
+        for x in 0..columns {
+            // Centered at 1/4 and 3/4 of the LCD width
+            let led = leds.iter().enumerate().find(|(i, _)| {
+                let center = width * (2 * i + 1) / 4;
+                x.abs_diff(center) < LED_SIZE as usize / 2
+            });
+            let color = led.map_or(Rgb888::BLACK, |(_, &brightness)| led_color(brightness));
+            for _ in 0..self.scale {
+                format.write(&mut self.row, color);
+            }
+        }
+        for y in 0..LED_SIZE * self.scale {
+            self.write_row(self.origin.1 + y)?;
+        }
+
+        let lcd_y = self.origin.1 + (LED_SIZE + LED_MARGIN) * self.scale;
+        for (y, line) in pixels.chunks(width).enumerate() {
+            self.row.clear();
+            for &pixel in &line[..columns] {
+                for _ in 0..self.scale {
+                    format.write(&mut self.row, pixel.into());
+                }
+            }
+            for dy in 0..self.scale {
+                self.write_row(lcd_y + y as u32 * self.scale + dy)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Draws straight to the Linux framebuffer device, e.g. the HDMI output of a Raspberry Pi
+/// running without X or Wayland
+pub struct Platform {
+    lcd: FrameBuf<Rgb565, SharedBuffer>,
+    led0: FbdevLED,
+    led1: FbdevLED,
+    input: Console,
+    exit_requested: Arc<AtomicBool>,
+    render_thread: Option<JoinHandle<()>>,
+    battery: Option<SimulatedBattery>,
+    storage: StorageDir,
+}
+
+impl Drop for Platform {
+    fn drop(&mut self) {
+        self.exit_requested.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.render_thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+pub fn new_platform() -> Result<impl crate::platform::Platform> {
+    let logger = env_logger::Builder::from_default_env().build();
+    let log_level = logger.filter();
+    crate::log_buffer::init(logger, log_level)?;
+
+    let geometry = Geometry::read()?;
+    log::info!(
+        "framebuffer: {}x{}, stride {}",
+        geometry.size.width,
+        geometry.size.height,
+        geometry.stride
+    );
+    let file = OpenOptions::new()
+        .write(true)
+        .open(DEVICE)
+        .with_context(|| format!("cannot open {DEVICE}"))?;
+    let lcd_size = super::simulated_lcd_size()?;
+    let mut renderer = Renderer::new(file, geometry, lcd_size);
+
+    let pixels = SharedBuffer::new(lcd_size);
+    let lcd = FrameBuf::new(
+        pixels.clone(),
+        lcd_size.width.try_into()?,
+        lcd_size.height.try_into()?,
+    );
+    let led0 = FbdevLED(Arc::new(Mutex::new(0f32.into())));
+    let led1 = FbdevLED(Arc::new(Mutex::new(0f32.into())));
+    let exit_requested = Arc::new(AtomicBool::new(false));
+
+    let led0_clone = led0.clone();
+    let led1_clone = led1.clone();
+    let exit_requested_clone = exit_requested.clone();
+    let render_thread = std::thread::spawn(move || {
+        while !exit_requested_clone.load(Ordering::Relaxed) {
+            let snapshot = pixels.snapshot();
+            let leds = [*led0_clone.0.lock().unwrap(), *led1_clone.0.lock().unwrap()];
+            if let Err(e) = renderer.render(&snapshot, leds) {
+                log::error!("writing to {DEVICE} failed: {e}");
+                exit_requested_clone.store(true, Ordering::Relaxed);
+            }
+            std::thread::sleep(FRAME_INTERVAL);
+        }
+    });
+
+    Ok(Platform {
+        lcd,
+        led0,
+        led1,
+        input: Console::spawn()?,
+        exit_requested,
+        render_thread: Some(render_thread),
+        battery: SimulatedBattery::from_env(),
+        storage: StorageDir::from_env(),
+    })
+}
+
+impl crate::platform::Platform for Platform {
+    type DisplayError = Infallible;
+
+    fn name(&self) -> &'static str {
+        "fbdev"
+    }
+
+    fn ip_address(&mut self) -> Option<IpAddr> {
+        super::local_ip_address()
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+
+    fn display(
+        &mut self,
+        id: DisplayId,
+    ) -> Option<&mut impl DrawTarget<Color = Rgb565, Error = Self::DisplayError>> {
+        (id == DisplayId::MAIN).then_some(&mut self.lcd)
+    }
+
+    fn led0(&mut self) -> &mut impl super::LED {
+        &mut self.led0
+    }
+
+    fn led1(&mut self) -> &mut impl super::LED {
+        &mut self.led1
+    }
+
+    fn input(&mut self) -> &mut impl super::Input {
+        &mut self.input
+    }
+
+    fn storage(&mut self) -> &mut impl super::Storage {
+        &mut self.storage
+    }
+
+    fn feed_watchdog(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn reset_lcd(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn report_frame_stats(&mut self, _stats: &FrameStats) {}
+
+    fn exit_requested(&self) -> bool {
+        self.exit_requested.load(Ordering::Relaxed)
+    }
+
+    fn poll_tweaks(&mut self) -> Option<crate::tweaks::Tweaks> {
+        None
+    }
+
+    fn battery(&mut self) -> Option<BatteryStatus> {
+        self.battery.as_ref().map(SimulatedBattery::status)
+    }
+
+    fn sensors(&mut self) -> Option<SensorReadings> {
+        super::thermal_zone::read()
+    }
+
+    fn memory_stats(&mut self) -> Option<MemoryStats> {
+        super::proc_status::memory_stats()
+    }
+
+    fn load(&mut self, key: &str) -> Result<Option<Vec<u8>>> {
+        super::state_dir::load(key)
+    }
+
+    fn store(&mut self, key: &str, value: &[u8]) -> Result<()> {
+        super::state_dir::store(key, value)
+    }
+    fn deep_sleep(&mut self, _wake_after: Option<Duration>, _resume: Option<ResumeState>) {
+        log::info!("no deep sleep on Linux, exiting instead");
+        self.exit_requested.store(true, Ordering::Relaxed);
+    }
+
+    unsafe fn install_panic_screen(&mut self) {
+        const PANIC_SCREEN_HOLD: Duration = Duration::from_secs(10);
+
+        // The render thread keeps running while the panic hook holds, showing what it draws
+        let lcd = FrameBuf::new(self.lcd.data.clone(), self.lcd.width(), self.lcd.height());
+        crate::panic_screen::install(lcd, PANIC_SCREEN_HOLD);
+    }
+}
@@ -0,0 +1,26 @@
+use std::io::Read;
+
+use anyhow::Result;
+
+/// Returns the HTTP status code, error statuses included
+pub fn post(url: &str, content_type: &str, body: &[u8]) -> Result<u16> {
+    match ureq::post(url)
+        .set("Content-Type", content_type)
+        .send_bytes(body)
+    {
+        Ok(response) => Ok(response.status()),
+        Err(ureq::Error::Status(status, _)) => Ok(status),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Fails on error statuses. The body is cut off at `max_size` bytes.
+pub fn get(url: &str, max_size: usize) -> Result<Vec<u8>> {
+    let mut body = Vec::new();
+    ureq::get(url)
+        .call()?
+        .into_reader()
+        .take(max_size as u64)
+        .read_to_end(&mut body)?;
+    Ok(body)
+}
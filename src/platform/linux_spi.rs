@@ -0,0 +1,291 @@
+use std::{
+    net::IpAddr,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use embedded_graphics::{draw_target::DrawTarget, geometry::Size, pixelcolor::Rgb565};
+use embedded_hal::digital::OutputPin;
+use linux_embedded_hal::{
+    gpio_cdev::{Chip, LineRequestFlags},
+    spidev::{SpiModeFlags, SpidevOptions},
+    CdevPin, Delay, SpidevDevice,
+};
+use st7735_lcd::ST7735;
+
+use super::{
+    panic_lcd::PanicLcd, storage_dir::StorageDir, BatteryStatus, Brightness, DisplayId, FrameStats,
+    MemoryStats, PlatformError, ResumeState, SensorReadings, LED,
+};
+use crate::{
+    console::Console,
+    orientation::{Orientation, Oriented},
+};
+
+const LCD_SIZE: Size = Size::new(160, 128);
+const SPI_HZ: u32 = 26_000_000;
+const PWM_PERIOD_NS: u32 = 200_000;
+const PANIC_SCREEN_HOLD: Duration = Duration::from_secs(10);
+
+/// Where the LCD and LEDs are wired. Defaults match a Raspberry Pi with the LCD on SPI0 CE0
+/// and the LEDs on the two hardware PWM channels (`dtoverlay=pwm-2chan`, GPIO18 and GPIO19).
+struct Wiring {
+    spi_device: String,
+    gpio_chip: String,
+    dc: u32,
+    reset: u32,
+    backlight: u32,
+    pwm_chip: String,
+    led_channels: [u32; 2],
+    /// How the LCD is mounted
+    orientation: Orientation,
+}
+
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> Result<T>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    match std::env::var(name) {
+        Ok(value) => value
+            .parse()
+            .with_context(|| format!("invalid {name}: {value}")),
+        Err(_) => Ok(default),
+    }
+}
+
+impl Wiring {
+    fn from_env() -> Result<Self> {
+        Ok(Wiring {
+            spi_device: env_or("EVIL_ANDROID_SPI_DEVICE", "/dev/spidev0.0".to_owned())?,
+            gpio_chip: env_or("EVIL_ANDROID_GPIO_CHIP", "/dev/gpiochip0".to_owned())?,
+            dc: env_or("EVIL_ANDROID_LCD_DC", 24)?,
+            reset: env_or("EVIL_ANDROID_LCD_RESET", 25)?,
+            backlight: env_or("EVIL_ANDROID_LCD_BACKLIGHT", 23)?,
+            pwm_chip: env_or(
+                "EVIL_ANDROID_PWM_CHIP",
+                "/sys/class/pwm/pwmchip0".to_owned(),
+            )?,
+            led_channels: [
+                env_or("EVIL_ANDROID_LED0_PWM", 0)?,
+                env_or("EVIL_ANDROID_LED1_PWM", 1)?,
+            ],
+            orientation: Orientation::parse(
+                std::env::var("EVIL_ANDROID_ROTATION").ok().as_deref(),
+                std::env::var("EVIL_ANDROID_MIRROR").ok().as_deref(),
+            )?,
+        })
+    }
+}
+
+/// LED driven by a sysfs PWM channel
+pub struct SysfsPwmLed {
+    channel_dir: PathBuf,
+}
+
+fn write_sysfs(path: &Path, value: impl ToString) -> Result<()> {
+    std::fs::write(path, value.to_string())
+        .with_context(|| format!("cannot write {}", path.display()))
+}
+
+impl SysfsPwmLed {
+    fn new(chip_dir: &Path, channel: u32) -> Result<Self> {
+        let channel_dir = chip_dir.join(format!("pwm{channel}"));
+        if !channel_dir.exists() {
+            write_sysfs(&chip_dir.join("export"), channel)?;
+        }
+        let led = SysfsPwmLed { channel_dir };
+        // Duty cycle can't exceed the period, so it has to go to 0 before changing the period
+        write_sysfs(&led.channel_dir.join("duty_cycle"), 0)?;
+        write_sysfs(&led.channel_dir.join("period"), PWM_PERIOD_NS)?;
+        write_sysfs(&led.channel_dir.join("enable"), 1)?;
+        Ok(led)
+    }
+}
+
+impl LED for SysfsPwmLed {
+    fn write_brightness(&mut self, brightness: Brightness) -> Result<()> {
+        let duty = (f32::from(brightness) * PWM_PERIOD_NS as f32) as u32;
+        write_sysfs(&self.channel_dir.join("duty_cycle"), duty)
+    }
+}
+
+impl Drop for SysfsPwmLed {
+    fn drop(&mut self) {
+        let _ = write_sysfs(&self.channel_dir.join("enable"), 0);
+    }
+}
+
+type Lcd = ST7735<SpidevDevice, CdevPin, CdevPin>;
+
+fn init_lcd(lcd: &mut Lcd) -> Result<()> {
+    // Does a hardware reset first
+    lcd.init(&mut Delay)
+        .map_err(|_| PlatformError::DisplayInit("ST7735::init failed".into()))?;
+    lcd.set_orientation(&st7735_lcd::Orientation::Landscape)
+        .map_err(|_| PlatformError::DisplayInit("ST7735::set_orientation failed".into()))?;
+    Ok(())
+}
+
+fn output_pin(chip: &mut Chip, line: u32, name: &str) -> Result<CdevPin> {
+    let handle = chip
+        .get_line(line)
+        .and_then(|l| l.request(LineRequestFlags::OUTPUT, 0, "evil-android"))
+        .with_context(|| format!("cannot request GPIO{line} for {name}"))?;
+    CdevPin::new(handle)
+        .map_err(|e| PlatformError::Gpio(format!("CdevPin::new failed: {e:?}")).into())
+}
+
+/// The same ST7735 LCD as on the ESP32, wired to a Linux SBC such as a Raspberry Pi
+pub struct Platform {
+    lcd: Oriented<Lcd>,
+    // Only on or off, effects that dim it blank it at 0
+    backlight: CdevPin,
+    led0: SysfsPwmLed,
+    led1: SysfsPwmLed,
+    input: Console,
+    storage: StorageDir,
+    exit_requested: bool,
+}
+
+pub fn new_platform() -> Result<impl crate::platform::Platform> {
+    let logger = env_logger::Builder::from_default_env().build();
+    let log_level = logger.filter();
+    crate::log_buffer::init(logger, log_level)?;
+
+    let wiring = Wiring::from_env()?;
+
+    let mut spi = SpidevDevice::open(&wiring.spi_device)
+        .with_context(|| format!("cannot open {}", wiring.spi_device))?;
+    spi.configure(
+        &SpidevOptions::new()
+            .max_speed_hz(SPI_HZ)
+            .mode(SpiModeFlags::SPI_MODE_3)
+            .build(),
+    )
+    .context("configuring SPI failed")?;
+
+    let mut chip = Chip::new(&wiring.gpio_chip)
+        .with_context(|| format!("cannot open {}", wiring.gpio_chip))?;
+    let dc = output_pin(&mut chip, wiring.dc, "LCD DC")?;
+    let reset = output_pin(&mut chip, wiring.reset, "LCD reset")?;
+    let mut backlight = output_pin(&mut chip, wiring.backlight, "LCD backlight")?;
+
+    let mut lcd = ST7735::new(spi, dc, reset, true, false, LCD_SIZE.width, LCD_SIZE.height);
+    log::info!("initializing LCD on {}", wiring.spi_device);
+    init_lcd(&mut lcd)?;
+    backlight
+        .set_high()
+        .map_err(|e| PlatformError::Gpio(format!("cannot turn backlight on: {e:?}")))?;
+
+    let pwm_chip = Path::new(&wiring.pwm_chip);
+    Ok(Platform {
+        lcd: Oriented::new(lcd, wiring.orientation),
+        backlight,
+        led0: SysfsPwmLed::new(pwm_chip, wiring.led_channels[0])?,
+        led1: SysfsPwmLed::new(pwm_chip, wiring.led_channels[1])?,
+        input: Console::spawn()?,
+        storage: StorageDir::from_env(),
+        exit_requested: false,
+    })
+}
+
+impl crate::platform::Platform for Platform {
+    type DisplayError = ();
+
+    fn name(&self) -> &'static str {
+        "spi"
+    }
+
+    fn ip_address(&mut self) -> Option<IpAddr> {
+        super::local_ip_address()
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+
+    fn display(
+        &mut self,
+        id: DisplayId,
+    ) -> Option<&mut impl DrawTarget<Color = Rgb565, Error = Self::DisplayError>> {
+        (id == DisplayId::MAIN).then_some(&mut self.lcd)
+    }
+
+    /// The driver drops the SPI error on the way, all that's left is that a transfer failed
+    fn display_error(&self, _error: ()) -> PlatformError {
+        PlatformError::Bus("ST7735 transfer failed".into())
+    }
+
+    fn write_backlight(&mut self, brightness: Brightness) -> Result<()> {
+        self.backlight
+            .set_state((f32::from(brightness) > 0.0).into())
+            .map_err(|e| PlatformError::Gpio(format!("cannot set backlight: {e:?}")).into())
+    }
+
+    fn led0(&mut self) -> &mut impl LED {
+        &mut self.led0
+    }
+
+    fn led1(&mut self) -> &mut impl LED {
+        &mut self.led1
+    }
+
+    fn input(&mut self) -> &mut impl super::Input {
+        &mut self.input
+    }
+
+    fn storage(&mut self) -> &mut impl super::Storage {
+        &mut self.storage
+    }
+
+    fn feed_watchdog(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn reset_lcd(&mut self) -> Result<()> {
+        init_lcd(self.lcd.inner_mut())
+    }
+
+    fn report_frame_stats(&mut self, _stats: &FrameStats) {}
+
+    fn exit_requested(&self) -> bool {
+        self.exit_requested
+    }
+
+    fn poll_tweaks(&mut self) -> Option<crate::tweaks::Tweaks> {
+        None
+    }
+
+    fn battery(&mut self) -> Option<BatteryStatus> {
+        None
+    }
+
+    fn sensors(&mut self) -> Option<SensorReadings> {
+        super::thermal_zone::read()
+    }
+
+    fn memory_stats(&mut self) -> Option<MemoryStats> {
+        super::proc_status::memory_stats()
+    }
+
+    fn load(&mut self, key: &str) -> Result<Option<Vec<u8>>> {
+        super::state_dir::load(key)
+    }
+
+    fn store(&mut self, key: &str, value: &[u8]) -> Result<()> {
+        super::state_dir::store(key, value)
+    }
+    fn deep_sleep(&mut self, _wake_after: Option<Duration>, _resume: Option<ResumeState>) {
+        log::info!("no deep sleep on Linux, exiting instead");
+        self.exit_requested = true;
+    }
+
+    unsafe fn install_panic_screen(&mut self) {
+        // Same caveats as on the ESP32, see esp32::Platform::install_panic_screen
+        crate::panic_screen::install(
+            PanicLcd(&mut self.lcd as *mut Oriented<Lcd>),
+            PANIC_SCREEN_HOLD,
+        );
+    }
+}
@@ -0,0 +1,31 @@
+use embedded_graphics::{
+    draw_target::DrawTarget, geometry::Dimensions, pixelcolor::Rgb565, primitives::Rectangle, Pixel,
+};
+
+/// Aliases the LCD owned by Platform, so that the panic hook can draw on it
+pub struct PanicLcd<Lcd>(pub *mut Lcd);
+
+// SAFETY: only ever used by the panic hook, see Platform::install_panic_screen
+unsafe impl<Lcd> Send for PanicLcd<Lcd> {}
+
+impl<Lcd: DrawTarget<Color = Rgb565>> Dimensions for PanicLcd<Lcd> {
+    fn bounding_box(&self) -> Rectangle {
+        unsafe { &*self.0 }.bounding_box()
+    }
+}
+
+impl<Lcd: DrawTarget<Color = Rgb565>> DrawTarget for PanicLcd<Lcd> {
+    type Color = Rgb565;
+    type Error = Lcd::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> std::result::Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        unsafe { &mut *self.0 }.draw_iter(pixels)
+    }
+
+    fn clear(&mut self, color: Self::Color) -> std::result::Result<(), Self::Error> {
+        unsafe { &mut *self.0 }.clear(color)
+    }
+}
@@ -1,20 +1,52 @@
 use std::{
-    sync::{Arc, Mutex},
-    time::Duration,
+    convert::Infallible,
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant, SystemTime},
 };
 
 use anyhow::Result;
 use embedded_graphics::{
-    geometry::Size,
+    geometry::{Dimensions, Size},
     pixelcolor::{Rgb565, Rgb888, RgbColor},
     prelude::DrawTarget,
 };
 use embedded_graphics_framebuf::{backends::FrameBufferBackend, FrameBuf};
 use glium::{backend::glutin::SimpleWindowBuilder, implement_vertex, Surface};
 use slice_of_array::SliceFlatExt;
-use winit::platform::wayland::EventLoopBuilderExtWayland;
+use winit::{
+    event::ElementState,
+    keyboard::{Key, NamedKey},
+    platform::wayland::EventLoopBuilderExtWayland,
+    window::Fullscreen,
+};
+
+use super::{
+    storage_dir::StorageDir, BatteryStatus, Brightness, DisplayId, FrameStats, InputEvent,
+    MemoryStats, ResumeState, SensorReadings,
+};
+use crate::{battery::SimulatedBattery, console::Console, tweaks::Tweaks};
+
+mod debug_overlay;
+mod lcd;
+mod mascot;
+mod shaders;
+mod tweak_panel;
+mod view;
 
-use super::Brightness;
+/// Color of the bars around the picture when the window doesn't match its aspect ratio
+const LETTERBOX_COLOR: (f32, f32, f32) = (0.2, 0.2, 0.2);
+
+/// Size of the optional eyes display, as on a typical 0.91" OLED
+const EYES_DISPLAY_SIZE: Size = Size::new(128, 32);
+
+// No point hitting the filesystem every frame
+const STORAGE_CHECK_INTERVAL: Duration = Duration::from_millis(500);
 
 struct Rgba32FrameBufferBackend {
     pixels: Vec<[u8; 4]>,
@@ -67,20 +99,101 @@ impl FrameBufferBackend for SyncFBBackend {
     }
 }
 
+/// An RGB LED, so that the eyes look the same as on any hardware. Each channel is 0..1, like
+/// the shader takes them.
 #[derive(Clone)]
-pub struct FakeLED(Arc<Mutex<Brightness>>);
+pub struct FakeLED(Arc<Mutex<[f32; 3]>>);
+
+impl FakeLED {
+    fn color(&self) -> [f32; 3] {
+        *self.0.lock().unwrap()
+    }
+
+    /// Of the brightest channel
+    fn brightness(&self) -> Brightness {
+        let [r, g, b] = self.color();
+        r.max(g).max(b).into()
+    }
+}
 
 impl super::LED for FakeLED {
-    fn set_brightness(&mut self, brightness: Brightness) -> Result<()> {
-        *self.0.lock().unwrap() = brightness;
+    fn write_brightness(&mut self, brightness: Brightness) -> Result<()> {
+        *self.0.lock().unwrap() = [brightness.into(), 0.0, 0.0];
+        Ok(())
+    }
+
+    fn write_color(&mut self, color: Rgb888) -> Result<()> {
+        *self.0.lock().unwrap() =
+            [color.r(), color.g(), color.b()].map(|channel| f32::from(channel) / 255.0);
         Ok(())
     }
 }
 
+/// Input events translated from window key presses
+pub struct KeyboardInput(Receiver<InputEvent>);
+
+impl super::Input for KeyboardInput {
+    fn poll(&mut self) -> Result<Option<InputEvent>> {
+        // Disconnected just means the window is gone, and the process is about to exit anyway
+        Ok(self.0.try_recv().ok())
+    }
+}
+
+fn key_to_input_event(key: &Key) -> Option<InputEvent> {
+    match key {
+        Key::Named(NamedKey::ArrowLeft) => Some(InputEvent::Scrub(-1)),
+        Key::Named(NamedKey::ArrowRight) => Some(InputEvent::Scrub(1)),
+        Key::Named(NamedKey::Space) => Some(InputEvent::TogglePause),
+        Key::Character(c) if c.as_str() == "s" => Some(InputEvent::Shake(1.0)),
+        Key::Character(c) if c.as_str() == "p" => Some(InputEvent::Pet),
+        Key::Character(c) if c.as_str() == "o" => Some(InputEvent::Poke),
+        Key::Character(c) if c.as_str() == "n" => Some(InputEvent::NextScene),
+        Key::Character(c) if c.as_str() == "=" => Some(InputEvent::Speed(1)),
+        Key::Character(c) if c.as_str() == "-" => Some(InputEvent::Speed(-1)),
+        Key::Character(c) if c.as_str() == "]" => Some(InputEvent::Brightness(1)),
+        Key::Character(c) if c.as_str() == "[" => Some(InputEvent::Brightness(-1)),
+        Key::Character(c) if c.as_str() == "l" => Some(InputEvent::ToggleLogOverlay),
+        Key::Character(c) if c.as_str() == "m" => Some(InputEvent::ToggleDiagnostics),
+        Key::Character(c) if c.as_str() == "i" => Some(InputEvent::ToggleStats),
+        Key::Character(c) if c.as_str() == "z" => Some(InputEvent::Sleep),
+        Key::Character(c) if c.as_str() == "f" => Some(InputEvent::DumpFrame),
+        Key::Character(c) if c.as_str() == "d" => Some(InputEvent::ToggleDemo),
+        Key::Character(c) if c.as_str() == "b" => Some(InputEvent::ToggleBadge),
+        _ => None,
+    }
+}
+
+type SimulatedDisplay = lcd::SimulatedLcd<FrameBuf<Rgb565, SyncFBBackend>>;
+
 pub struct Platform {
-    draw_target: FrameBuf<Rgb565, SyncFBBackend>,
+    lcd: SimulatedDisplay,
+    // Enabled with EVIL_ANDROID_EYES_DISPLAY=1
+    eyes: Option<SimulatedDisplay>,
     led0: FakeLED,
     led1: FakeLED,
+    input: (KeyboardInput, Console),
+    // Set either by closing the window or by dropping the Platform
+    exit_requested: Arc<AtomicBool>,
+    window_thread: Option<JoinHandle<()>>,
+    frame_stats: Arc<Mutex<FrameStats>>,
+    // Edited by the tweak panel in the window thread
+    tweaks: Arc<Mutex<Tweaks>>,
+    last_tweaks: Tweaks,
+    battery: Option<SimulatedBattery>,
+    storage: StorageDir,
+    storage_modified: Option<SystemTime>,
+    storage_checked: Instant,
+}
+
+impl Drop for Platform {
+    fn drop(&mut self) {
+        self.exit_requested.store(true, Ordering::Relaxed);
+        if let Some(window_thread) = self.window_thread.take() {
+            if window_thread.join().is_err() {
+                log::error!("window thread panicked");
+            }
+        }
+    }
 }
 
 #[derive(Clone, Copy, Default)]
@@ -91,24 +204,56 @@ struct Vertex {
 implement_vertex!(Vertex, pos);
 
 pub fn new_platform() -> Result<impl crate::platform::Platform> {
-    env_logger::init();
+    let logger = env_logger::Builder::from_default_env().build();
+    let log_level = logger.filter();
+    crate::log_buffer::init(logger, log_level)?;
 
-    let size = Size::new(160, 128);
+    let size = super::simulated_lcd_size()?;
     let pixel_buffer = SyncFBBackend(Arc::new(Mutex::new(Rgba32FrameBufferBackend::new(
         size,
         Rgb565::BLACK,
     ))));
-    let draw_target = FrameBuf::new(
-        pixel_buffer.clone(),
-        size.width.try_into()?,
-        size.height.try_into()?,
+    let lcd = lcd::SimulatedLcd::new(
+        FrameBuf::new(
+            pixel_buffer.clone(),
+            size.width.try_into()?,
+            size.height.try_into()?,
+        ),
+        lcd::LcdModel::from_env()?,
     );
-    let led0 = FakeLED(Arc::new(Mutex::new(0f32.into())));
-    let led1 = FakeLED(Arc::new(Mutex::new(0f32.into())));
+    let has_eyes_display = std::env::var("EVIL_ANDROID_EYES_DISPLAY").is_ok_and(|v| v == "1");
+    let eyes_buffer = SyncFBBackend(Arc::new(Mutex::new(Rgba32FrameBufferBackend::new(
+        EYES_DISPLAY_SIZE,
+        Rgb565::BLACK,
+    ))));
+    let eyes = if has_eyes_display {
+        Some(lcd::SimulatedLcd::new(
+            FrameBuf::new(
+                eyes_buffer.clone(),
+                EYES_DISPLAY_SIZE.width.try_into()?,
+                EYES_DISPLAY_SIZE.height.try_into()?,
+            ),
+            lcd::LcdModel::from_env()?,
+        ))
+    } else {
+        None
+    };
+    let led0 = FakeLED(Arc::new(Mutex::new([0.0; 3])));
+    let led1 = FakeLED(Arc::new(Mutex::new([0.0; 3])));
+
+    let frame_stats = Arc::new(Mutex::new(FrameStats::default()));
+    let tweaks = Arc::new(Mutex::new(Tweaks::default()));
+    let mut skin = mascot::Skin::from_env()?;
+    let mut view_mode = view::ViewMode::from_env()?;
 
     let led0_clone = led0.clone();
     let led1_clone = led1.clone();
-    std::thread::spawn(move || {
+    let frame_stats_clone = frame_stats.clone();
+    let tweaks_clone = tweaks.clone();
+    let (input_sender, input_receiver) = mpsc::channel();
+    let exit_requested = Arc::new(AtomicBool::new(false));
+    let exit_requested_clone = exit_requested.clone();
+    let window_thread = std::thread::spawn(move || {
         let event_loop = match winit::event_loop::EventLoopBuilder::new()
             .with_any_thread(true)
             .build()
@@ -116,7 +261,8 @@ pub fn new_platform() -> Result<impl crate::platform::Platform> {
             Ok(l) => l,
             Err(e) => {
                 log::error!("EventLoopBuilder::build failed: {e:?}");
-                std::process::exit(1);
+                exit_requested_clone.store(true, Ordering::Relaxed);
+                return;
             }
         };
         let (window, display) = SimpleWindowBuilder::new()
@@ -124,108 +270,15 @@ pub fn new_platform() -> Result<impl crate::platform::Platform> {
             .with_inner_size(1600, 1200)
             .build(&event_loop);
 
-        let vs_src = r#"
-#version 140
-
-in vec2 pos;
-
-void main() {
-    gl_Position = vec4(pos, 0.0, 1.0);
-}
-        "#;
-
-        // https://www.shadertoy.com/view/McfcWB
-        let fs_src = r#"
-#version 140
-
-uniform vec2 u_resolution;
-uniform vec3 u_left_eye_color;
-uniform vec3 u_right_eye_color;
-uniform sampler2D u_lcd_texture;
-
-out vec4 fragColor;
-
-const float PI = 3.1415926535897932384626433832795;
-
-vec2 translate(vec2 pos, vec2 delta) {
-    return pos + delta;
-}
-
-vec2 rotate(vec2 pos, float angle) {
-    return vec2(pos.x * cos(angle) - pos.y * sin(angle),
-                pos.y * cos(angle) + pos.x * sin(angle));
-}
-
-bool in_ellipse(vec2 pos, vec2 center, vec2 radii) {
-    vec2 delta = pos - center;
-    delta.y /= radii.y / radii.x;
-    return length(delta) < radii.x;
-}
-
-bool in_circle(vec2 pos, vec2 center, float radius) {
-    return distance(pos, center) < radius;
-}
-
-bool in_rect(vec2 pos,vec2 top_left, vec2 bottom_right) {
-    return !(pos.x < top_left.x || pos.x > bottom_right.x || pos.y < top_left.y || pos.y > bottom_right.y);
-}
-
-void main() {
-    // Normalized pixel coordinates -200..200 on y, aspect ratio preserving on x
-    vec2 pos = vec2(gl_FragCoord.x - u_resolution.x / 2.0,
-                    gl_FragCoord.y - u_resolution.y / 2.0);
-    pos /= u_resolution.y;
-    pos *= 400.0;
-    
-    vec4 col_bg = vec4(1.0, 1.0, 1.0, 0.0);
-    vec4 col_android = vec4(0.23921568627450981, 0.8627450980392157, 0.5176470588235295, 1.0);
-    
-    bool in_left_eye = in_circle(vec2(-pos.x, pos.y), vec2(42, 84), 8.0);
-    bool in_right_eye = in_circle(vec2(pos.x, pos.y), vec2(42, 84), 8.0);
-
-    float angle_rad = 29.0 * PI / 180.0;
-    bool in_android_antennas = in_rect(rotate(vec2(abs(pos.x), pos.y), angle_rad), vec2(-14, 86), vec2(-14+6, 86+66));
-    bool in_android_antenna_tips = in_circle(rotate(vec2(abs(pos.x), pos.y), angle_rad), vec2(-14+3, 86+66), 3.0);
-    bool in_android_head_base = in_ellipse(pos, vec2(0, 41), vec2(91, 84)) && pos.y > 41.0;
-    bool in_android_head = in_android_head_base || in_android_antennas || in_android_antenna_tips;
-    
-    bool in_android_body_upper = in_rect(pos, vec2(-91, 35-142+22), vec2(-91+182, 35));
-    bool in_android_body_mid = in_rect(pos, vec2(-91+22, 35-142), vec2(-91+182-22, 35));
-    bool in_android_body_lower_corners = in_circle(vec2(abs(pos.x), pos.y), vec2(91-22, 35-142+22), 22.0);
-    bool in_android_body = in_android_body_upper || in_android_body_mid || in_android_body_lower_corners;
-    
-    bool in_android_arms_upper = in_circle(vec2(abs(pos.x), pos.y), vec2(145-24, 10), 24.0);
-    bool in_android_arms_mid= in_rect(vec2(abs(pos.x), pos.y), vec2(145-48, 10-133+58), vec2(145, 10));
-    bool in_android_arms_lower = in_circle(vec2(abs(pos.x), pos.y), vec2(145-24, 10-133+58), 24.0);
-    bool in_android_arms = in_android_arms_upper || in_android_arms_mid || in_android_arms_lower;
-    
-    bool in_android_legs_mid= in_rect(vec2(abs(pos.x), pos.y), vec2(65-48, 10-133-25), vec2(65, 10-25));
-    bool in_android_legs_lower = in_circle(vec2(abs(pos.x), pos.y), vec2(65-24, 10-133-25), 24.0);
-    bool in_android_legs = in_android_legs_mid || in_android_legs_lower;
-    
-    bool in_android = in_android_head || in_android_body || in_android_arms || in_android_legs;
-    
-    vec2 display_center = vec2(0, -35);
-    vec2 display_size = vec2(160, 128);
-    float display_scale = 0.7;
-    display_size *= display_scale;
-    vec2 display_uv = (pos - (display_center - display_size / 2.0)) / display_size;
-    bool in_display = in_rect(pos, display_center - display_size / 2.0, display_center + display_size / 2.0);
-
-    if (in_left_eye) {
-        fragColor = vec4(u_left_eye_color, 1.0);
-    } else if (in_right_eye) {
-        fragColor = vec4(u_right_eye_color, 1.0);
-    } else if (in_display) {
-        fragColor = texture2D(u_lcd_texture, display_uv);
-    } else if (in_android) {
-        fragColor = col_android;
-    } else {
-        fragColor = col_bg;
-    }
-}
-        "#;
-        let program = glium::Program::from_source(&display, vs_src, fs_src, None).unwrap();
+        let mut shaders = shaders::Shaders::from_env();
+        let mut program = match shaders.load(&display) {
+            Ok(p) => p,
+            Err(e) => {
+                log::error!("cannot compile shaders: {e:?}");
+                exit_requested_clone.store(true, Ordering::Relaxed);
+                return;
+            }
+        };
 
         let vertices = vec![
             Vertex { pos: [-1.0, -1.0] },
@@ -235,75 +288,242 @@ void main() {
         ];
         let vertices = glium::VertexBuffer::new(&display, &vertices).unwrap();
 
+        let overlay_buffer = SyncFBBackend(Arc::new(Mutex::new(Rgba32FrameBufferBackend::new(
+            debug_overlay::SIZE,
+            Rgb565::BLACK,
+        ))));
+        let mut overlay = FrameBuf::new(
+            overlay_buffer.clone(),
+            debug_overlay::SIZE.width as usize,
+            debug_overlay::SIZE.height as usize,
+        );
+        let mut show_debug_overlay = false;
+
+        let mut egui =
+            egui_glium::EguiGlium::new(egui::ViewportId::ROOT, &display, &window, &event_loop);
+        let mut show_tweak_panel = false;
+        let start_time = Instant::now();
+
+        let exit_requested_by_loop = exit_requested_clone.clone();
         let result = event_loop.run(move |event, window_target| match event {
-            winit::event::Event::WindowEvent { event, .. } => match event {
-                winit::event::WindowEvent::CloseRequested => window_target.exit(),
-                winit::event::WindowEvent::RedrawRequested => {
-                    let mut frame = display.draw();
-                    frame.clear_color_srgb(1.0f32, 1.0f32, 1.0f32, 1.0f32);
-
-                    let window_size = window.inner_size();
-                    let texture = pixel_buffer
-                        .0
-                        .lock()
-                        .unwrap()
-                        .to_gl_texture(&display)
-                        .unwrap();
-                    let uniforms = glium::uniform! {
-                        u_resolution: [window_size.width as f32, window_size.height as f32],
-                        u_left_eye_color: [(*led0_clone.0.lock().unwrap()).into(), 0.0f32, 0.0f32],
-                        u_right_eye_color: [(*led1_clone.0.lock().unwrap()).into(), 0.0f32, 0.0f32],
-                        u_lcd_texture: &texture,
-                    };
-                    frame
-                        .draw(
-                            &vertices,
-                            glium::index::NoIndices(glium::index::PrimitiveType::TriangleStrip),
-                            &program,
-                            &uniforms,
-                            &Default::default(),
-                        )
-                        .unwrap();
-
-                    match frame.finish() {
-                        Ok(_) => {}
-                        Err(e) => log::error!("Surface::finish failed: {e:?}"),
+            winit::event::Event::WindowEvent { event, .. } => {
+                // Don't let keys typed into the tweak panel control the animation
+                let response = egui.on_event(&window, &event);
+                let consumed_by_egui = show_tweak_panel && response.consumed;
+                match event {
+                    winit::event::WindowEvent::CloseRequested => window_target.exit(),
+                    winit::event::WindowEvent::Resized(new_size) => {
+                        display.resize((new_size.width, new_size.height));
+                    }
+                    winit::event::WindowEvent::KeyboardInput { event, .. }
+                        if event.state == ElementState::Pressed && !consumed_by_egui =>
+                    {
+                        if event.logical_key == Key::Named(NamedKey::F3) {
+                            show_debug_overlay = !show_debug_overlay;
+                        } else if event.logical_key == Key::Named(NamedKey::F2) {
+                            show_tweak_panel = !show_tweak_panel;
+                        } else if event.logical_key == Key::Named(NamedKey::F4) {
+                            skin = skin.next();
+                            log::info!("skin: {skin:?}");
+                        } else if event.logical_key == Key::Named(NamedKey::F5) {
+                            view_mode = view_mode.next();
+                            log::info!("view: {view_mode:?}");
+                        } else if event.logical_key == Key::Named(NamedKey::F11) {
+                            window.set_fullscreen(match window.fullscreen() {
+                                Some(_) => None,
+                                None => Some(Fullscreen::Borderless(None)),
+                            });
+                        } else if let Some(input_event) = key_to_input_event(&event.logical_key) {
+                            let _ = input_sender.send(input_event);
+                        }
+                    }
+                    winit::event::WindowEvent::RedrawRequested => {
+                        if let Some(new_program) = shaders.reload_if_changed(&display) {
+                            program = new_program;
+                        }
+
+                        let mut frame = display.draw();
+                        let (r, g, b) = LETTERBOX_COLOR;
+                        frame.clear_color_srgb(r, g, b, 1.0f32);
+
+                        let window_size = window.inner_size();
+                        let window_size = Size::new(window_size.width, window_size.height);
+                        let texture = pixel_buffer
+                            .0
+                            .lock()
+                            .unwrap()
+                            .to_gl_texture(&display)
+                            .unwrap();
+                        let eyes_texture = eyes_buffer
+                            .0
+                            .lock()
+                            .unwrap()
+                            .to_gl_texture(&display)
+                            .unwrap();
+
+                        let stats = frame_stats_clone.lock().unwrap().clone();
+                        let pose = mascot::pose(&stats, start_time.elapsed().as_secs_f32());
+                        if show_debug_overlay {
+                            let led0 = led0_clone.brightness();
+                            let led1 = led1_clone.brightness();
+                            let _ = debug_overlay::draw(&mut overlay, &stats, led0, led1);
+                        }
+                        let overlay_texture = overlay_buffer
+                            .0
+                            .lock()
+                            .unwrap()
+                            .to_gl_texture(&display)
+                            .unwrap();
+
+                        let viewport = view::letterbox(window_size, view::MASCOT_ASPECT);
+                        let uniforms = glium::uniform! {
+                            u_origin: [viewport.left as f32, viewport.bottom as f32],
+                            u_resolution: [viewport.width as f32, viewport.height as f32],
+                            u_left_eye_color: led0_clone.color(),
+                            u_right_eye_color: led1_clone.color(),
+                            u_lcd_texture: &texture,
+                            u_lcd_size: [size.width as f32, size.height as f32],
+                            u_has_eyes_display: has_eyes_display,
+                            u_eyes_display_size: [EYES_DISPLAY_SIZE.width as f32, EYES_DISPLAY_SIZE.height as f32],
+                            u_eyes_texture: &eyes_texture,
+                            u_show_overlay: show_debug_overlay,
+                            u_overlay_size: [debug_overlay::SIZE.width as f32, debug_overlay::SIZE.height as f32],
+                            u_overlay_scale: debug_overlay::SCALE,
+                            u_overlay_texture: overlay_texture
+                                .sampled()
+                                .magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest),
+                            u_skin: skin.index(),
+                            u_body_color: skin.body_color(mascot::damage(&stats)),
+                            u_damage: mascot::damage(&stats),
+                            u_at_peak: mascot::at_peak(&stats),
+                            u_head_tilt: pose.head_tilt,
+                            u_arm_angles: pose.arm_angles,
+                            u_eye_scale: pose.eye_scale,
+                        };
+                        match view_mode.lcd_rect(window_size, size) {
+                            None => frame
+                                .draw(
+                                    &vertices,
+                                    glium::index::NoIndices(
+                                        glium::index::PrimitiveType::TriangleStrip,
+                                    ),
+                                    &program,
+                                    &uniforms,
+                                    &glium::DrawParameters {
+                                        viewport: Some(viewport),
+                                        ..Default::default()
+                                    },
+                                )
+                                .unwrap(),
+                            Some(lcd_rect) => {
+                                let filter = glium::uniforms::MagnifySamplerFilter::Nearest;
+                                texture.as_surface().blit_whole_color_to(
+                                    &frame,
+                                    &view::blit_target(lcd_rect),
+                                    filter,
+                                );
+                                if show_debug_overlay {
+                                    // Top-left corner, same as over the mascot
+                                    let overlay_size =
+                                        debug_overlay::SIZE * debug_overlay::SCALE as u32;
+                                    let overlay_rect = glium::Rect {
+                                        left: 0,
+                                        bottom: window_size
+                                            .height
+                                            .saturating_sub(overlay_size.height),
+                                        width: overlay_size.width,
+                                        height: overlay_size.height,
+                                    };
+                                    overlay_texture.as_surface().blit_whole_color_to(
+                                        &frame,
+                                        &view::blit_target(overlay_rect),
+                                        filter,
+                                    );
+                                }
+                            }
+                        }
+
+                        if show_tweak_panel {
+                            egui.run(&window, |ctx| {
+                                tweak_panel::show(ctx, &mut tweaks_clone.lock().unwrap())
+                            });
+                            egui.paint(&display, &mut frame);
+                        }
+
+                        match frame.finish() {
+                            Ok(_) => {}
+                            Err(e) => log::error!("Surface::finish failed: {e:?}"),
+                        }
                     }
+                    _ => {}
                 }
-                _ => {}
-            },
-            winit::event::Event::AboutToWait => window.request_redraw(),
+            }
+            winit::event::Event::AboutToWait => {
+                if exit_requested_by_loop.load(Ordering::Relaxed) {
+                    window_target.exit();
+                } else {
+                    window.request_redraw();
+                }
+            }
             _ => {}
         });
 
         match result {
-            Ok(_) => {
-                log::info!("window closed");
-                std::process::exit(0);
-            }
-            Err(e) => {
-                log::error!("event loop terminated with error: {e:?}");
-                std::process::exit(1);
-            }
+            Ok(_) => log::info!("window closed"),
+            Err(e) => log::error!("event loop terminated with error: {e:?}"),
         }
+        exit_requested_clone.store(true, Ordering::Relaxed);
     });
 
+    // Debug builds are run from the source tree, where edits to the built-in images can be
+    // picked up without rebuilding
+    let mut storage = StorageDir::from_env();
+    if cfg!(debug_assertions) {
+        storage = storage.with_fallback(concat!(env!("CARGO_MANIFEST_DIR"), "/data"));
+    }
+
     Ok(Platform {
-        draw_target,
+        lcd,
+        eyes,
         led0,
         led1,
+        input: (KeyboardInput(input_receiver), Console::spawn()?),
+        exit_requested,
+        window_thread: Some(window_thread),
+        frame_stats,
+        last_tweaks: tweaks.lock().unwrap().clone(),
+        tweaks,
+        battery: SimulatedBattery::from_env(),
+        storage_modified: storage.modified(),
+        storage_checked: Instant::now(),
+        storage,
     })
 }
 
 impl crate::platform::Platform for Platform {
+    type DisplayError = Infallible;
+
+    fn name(&self) -> &'static str {
+        "window"
+    }
+
+    fn ip_address(&mut self) -> Option<IpAddr> {
+        super::local_ip_address()
+    }
+
     fn sleep(&mut self, duration: Duration) {
         std::thread::sleep(duration);
     }
 
-    fn lcd(&mut self) -> &mut impl DrawTarget<Color = Rgb565> {
-        // Artificially limit FPS. The real LCD is pretty slow.
-        std::thread::sleep(Duration::from_millis(10));
-        &mut self.draw_target
+    fn display(
+        &mut self,
+        id: DisplayId,
+    ) -> Option<&mut impl DrawTarget<Color = Rgb565, Error = Self::DisplayError>> {
+        match id {
+            DisplayId::MAIN => Some(&mut self.lcd),
+            DisplayId::EYES => self.eyes.as_mut(),
+            _ => None,
+        }
     }
 
     fn led0(&mut self) -> &mut impl super::LED {
@@ -313,4 +533,88 @@ impl crate::platform::Platform for Platform {
     fn led1(&mut self) -> &mut impl super::LED {
         &mut self.led1
     }
+
+    fn input(&mut self) -> &mut impl super::Input {
+        &mut self.input
+    }
+
+    fn storage(&mut self) -> &mut impl super::Storage {
+        &mut self.storage
+    }
+
+    fn assets_changed(&mut self) -> bool {
+        if self.storage_checked.elapsed() < STORAGE_CHECK_INTERVAL {
+            return false;
+        }
+        self.storage_checked = Instant::now();
+        let modified = self.storage.modified();
+        std::mem::replace(&mut self.storage_modified, modified) != modified
+    }
+
+    fn feed_watchdog(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn reset_lcd(&mut self) -> Result<()> {
+        // Nothing to reset, the simulated LCD never fails
+        Ok(())
+    }
+
+    /// Like an ESP32 with PSRAM
+    fn virtual_canvas_size(&mut self) -> Option<Size> {
+        Some(self.lcd.bounding_box().size * 2)
+    }
+
+    fn report_frame_stats(&mut self, stats: &FrameStats) {
+        *self.frame_stats.lock().unwrap() = stats.clone();
+    }
+
+    fn exit_requested(&self) -> bool {
+        self.exit_requested.load(Ordering::Relaxed)
+    }
+
+    fn poll_tweaks(&mut self) -> Option<Tweaks> {
+        let tweaks = self.tweaks.lock().unwrap();
+        if *tweaks == self.last_tweaks {
+            return None;
+        }
+        self.last_tweaks = tweaks.clone();
+        Some(self.last_tweaks.clone())
+    }
+
+    fn battery(&mut self) -> Option<BatteryStatus> {
+        self.battery.as_ref().map(SimulatedBattery::status)
+    }
+
+    fn sensors(&mut self) -> Option<SensorReadings> {
+        super::thermal_zone::read()
+    }
+
+    fn memory_stats(&mut self) -> Option<MemoryStats> {
+        super::proc_status::memory_stats()
+    }
+
+    fn load(&mut self, key: &str) -> Result<Option<Vec<u8>>> {
+        super::state_dir::load(key)
+    }
+
+    fn store(&mut self, key: &str, value: &[u8]) -> Result<()> {
+        super::state_dir::store(key, value)
+    }
+    fn deep_sleep(&mut self, _wake_after: Option<Duration>, _resume: Option<ResumeState>) {
+        log::info!("no deep sleep on Linux, exiting instead");
+        self.exit_requested.store(true, Ordering::Relaxed);
+    }
+
+    unsafe fn install_panic_screen(&mut self) {
+        // The window stays open only as long as the process is alive
+        const PANIC_SCREEN_HOLD: Duration = Duration::from_secs(10);
+
+        let lcd = FrameBuf::new(
+            self.lcd.target.data.clone(),
+            self.lcd.target.width(),
+            self.lcd.target.height(),
+        );
+        crate::panic_screen::install(lcd, PANIC_SCREEN_HOLD);
+    }
 }
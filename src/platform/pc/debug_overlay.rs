@@ -0,0 +1,63 @@
+use std::time::Duration;
+
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::{Point, Size},
+    mono_font::{ascii::FONT_6X10, MonoTextStyle},
+    pixelcolor::Rgb565,
+    prelude::RgbColor,
+    text::{Baseline, Text},
+    Drawable,
+};
+
+use crate::platform::{Brightness, FrameStats};
+
+/// Fits 6 lines of 26 characters
+pub const SIZE: Size = Size::new(160, 60);
+/// Window pixels per overlay pixel
+pub const SCALE: f32 = 2.0;
+
+fn ms(d: Duration) -> f32 {
+    d.as_secs_f32() * 1000.0
+}
+
+pub fn draw<D: DrawTarget<Color = Rgb565>>(
+    target: &mut D,
+    stats: &FrameStats,
+    led0: Brightness,
+    led1: Brightness,
+) -> Result<(), D::Error> {
+    let fps = if stats.frame_time.is_zero() {
+        0.0
+    } else {
+        1.0 / stats.frame_time.as_secs_f32()
+    };
+    let text = format!(
+        "FPS {fps:.1} ({:.1} ms)\n\
+         clear {:.1} text {:.1}\n\
+         fx {:.1} flush {:.1}\n\
+         scene: {}\n\
+         glitchiness {} skipped {}\n\
+         LED0 {:.3} LED1 {:.3}",
+        ms(stats.frame_time),
+        ms(stats.clear),
+        ms(stats.text),
+        ms(stats.effects),
+        ms(stats.flush),
+        stats.scene,
+        stats.glitchiness,
+        stats.skipped_frames,
+        f32::from(led0),
+        f32::from(led1),
+    );
+
+    target.clear(Rgb565::BLACK)?;
+    Text::with_baseline(
+        &text,
+        Point::zero(),
+        MonoTextStyle::new(&FONT_6X10, Rgb565::WHITE),
+        Baseline::Top,
+    )
+    .draw(target)?;
+    Ok(())
+}
@@ -0,0 +1,144 @@
+use std::{
+    env,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::{Dimensions, Point},
+    pixelcolor::Rgb565,
+    primitives::Rectangle,
+    Pixel,
+};
+
+// Sleeping for shorter periods is dominated by the syscall overhead
+const MIN_SLEEP: Duration = Duration::from_micros(500);
+
+/// Timing characteristics of a real SPI-connected panel
+#[derive(Clone, Copy, Debug)]
+pub struct LcdModel {
+    /// None means infinitely fast
+    pub spi_bitrate_hz: Option<u32>,
+    /// Time spent on setting up a drawing window (CASET + RASET + RAMWR and bus transactions
+    /// for each of them), paid once per fill and once per *pixel* for draw_iter
+    pub window_overhead: Duration,
+    pub bits_per_pixel: u32,
+}
+
+impl Default for LcdModel {
+    /// Roughly the ST7735 on ESP32, with the SPI config from esp32.rs
+    fn default() -> Self {
+        Self {
+            spi_bitrate_hz: Some(26_000_000),
+            window_overhead: Duration::from_micros(100),
+            bits_per_pixel: 16,
+        }
+    }
+}
+
+impl LcdModel {
+    /// Default model, overridden by EVIL_ANDROID_LCD_SPI_HZ (0 disables the simulation of
+    /// transfer time) and EVIL_ANDROID_LCD_WINDOW_OVERHEAD_US env vars
+    pub fn from_env() -> Result<Self> {
+        let mut model = Self::default();
+        if let Ok(hz) = env::var("EVIL_ANDROID_LCD_SPI_HZ") {
+            let hz: u32 = hz.parse().context("invalid EVIL_ANDROID_LCD_SPI_HZ")?;
+            model.spi_bitrate_hz = if hz == 0 { None } else { Some(hz) };
+        }
+        if let Ok(us) = env::var("EVIL_ANDROID_LCD_WINDOW_OVERHEAD_US") {
+            let us = us
+                .parse()
+                .context("invalid EVIL_ANDROID_LCD_WINDOW_OVERHEAD_US")?;
+            model.window_overhead = Duration::from_micros(us);
+        }
+        Ok(model)
+    }
+
+    fn transfer_time(&self, pixels: u32) -> Duration {
+        match self.spi_bitrate_hz {
+            Some(hz) => Duration::from_secs_f64((pixels * self.bits_per_pixel) as f64 / hz as f64),
+            None => Duration::ZERO,
+        }
+    }
+}
+
+/// Wraps a DrawTarget, making draws take about as long as they would on a real panel. Fills
+/// are applied line by line, so whoever displays the target concurrently sees tearing.
+pub struct SimulatedLcd<D> {
+    pub target: D,
+    model: LcdModel,
+    // Time that should have passed already, but was too short to sleep for
+    debt: Duration,
+}
+
+impl<D> SimulatedLcd<D> {
+    pub fn new(target: D, model: LcdModel) -> Self {
+        Self {
+            target,
+            model,
+            debt: Duration::ZERO,
+        }
+    }
+
+    fn wait(&mut self, duration: Duration) {
+        self.debt += duration;
+        if self.debt >= MIN_SLEEP {
+            let start = Instant::now();
+            std::thread::sleep(self.debt);
+            // Oversleeping is the norm, make up for it on next waits
+            self.debt = self.debt.saturating_sub(start.elapsed());
+        }
+    }
+}
+
+impl<D: DrawTarget<Color = Rgb565>> Dimensions for SimulatedLcd<D> {
+    fn bounding_box(&self) -> Rectangle {
+        self.target.bounding_box()
+    }
+}
+
+impl<D: DrawTarget<Color = Rgb565>> DrawTarget for SimulatedLcd<D> {
+    type Color = Rgb565;
+    type Error = D::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> std::result::Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for pixel in pixels {
+            self.target.draw_iter([pixel])?;
+            self.wait(self.model.window_overhead + self.model.transfer_time(1));
+        }
+        Ok(())
+    }
+
+    fn fill_contiguous<I>(
+        &mut self,
+        area: &Rectangle,
+        colors: I,
+    ) -> std::result::Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        self.wait(self.model.window_overhead);
+        let mut colors = colors.into_iter();
+        for y in area.rows() {
+            let row = area
+                .columns()
+                .zip(&mut colors)
+                .map(|(x, color)| Pixel(Point::new(x, y), color));
+            self.target.draw_iter(row)?;
+            self.wait(self.model.transfer_time(area.size.width));
+        }
+        Ok(())
+    }
+
+    fn fill_solid(
+        &mut self,
+        area: &Rectangle,
+        color: Self::Color,
+    ) -> std::result::Result<(), Self::Error> {
+        self.fill_contiguous(area, std::iter::repeat(color))
+    }
+}
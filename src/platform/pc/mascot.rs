@@ -0,0 +1,99 @@
+use std::env;
+
+use anyhow::{bail, Result};
+
+use crate::platform::FrameStats;
+
+/// Look of the simulated android
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Skin {
+    Classic,
+    /// Rust spreads over the body as the build drags on
+    Rusted,
+    /// Body wears down to the ribs and loses an antenna at peak glitchiness
+    Skeletal,
+}
+
+impl Skin {
+    const ALL: [Skin; 3] = [Skin::Classic, Skin::Rusted, Skin::Skeletal];
+
+    /// Reads EVIL_ANDROID_SKIN, defaulting to classic
+    pub fn from_env() -> Result<Self> {
+        match env::var("EVIL_ANDROID_SKIN").as_deref() {
+            Err(_) | Ok("classic") => Ok(Skin::Classic),
+            Ok("rusted") => Ok(Skin::Rusted),
+            Ok("skeletal") => Ok(Skin::Skeletal),
+            Ok(other) => bail!("invalid EVIL_ANDROID_SKIN: {other}"),
+        }
+    }
+
+    pub fn next(self) -> Self {
+        let idx = Self::ALL.iter().position(|&s| s == self).unwrap();
+        Self::ALL[(idx + 1) % Self::ALL.len()]
+    }
+
+    /// Value of the u_skin shader uniform
+    pub fn index(self) -> i32 {
+        self as i32
+    }
+
+    pub fn body_color(self, damage: f32) -> [f32; 3] {
+        const GREEN: [f32; 3] = [0.239, 0.863, 0.518];
+        const RUST: [f32; 3] = [0.545, 0.271, 0.075];
+        const BONE: [f32; 3] = [0.890, 0.855, 0.788];
+
+        let target = match self {
+            Skin::Classic => GREEN,
+            Skin::Rusted => RUST,
+            Skin::Skeletal => BONE,
+        };
+        std::array::from_fn(|i| GREEN[i] + (target[i] - GREEN[i]) * damage)
+    }
+}
+
+/// How worn out the android looks, 0..1
+pub fn damage(stats: &FrameStats) -> f32 {
+    stats.progress.clamp(0.0, 1.0)
+}
+
+/// Whether the escalation reached its peak
+pub fn at_peak(stats: &FrameStats) -> bool {
+    stats.glitchiness > 0 || stats.progress >= 1.0
+}
+
+/// Body posture, passed to the shader as uniforms
+pub struct Pose {
+    /// Radians, positive tilts to the android's left
+    pub head_tilt: f32,
+    /// Left and right arm swing in radians, positive is away from the body
+    pub arm_angles: [f32; 2],
+    /// Relative to normal eye size
+    pub eye_scale: f32,
+}
+
+/// Twitches every this many seconds
+const TWITCH_PERIOD: f32 = 0.125;
+const MAX_HEAD_SLUMP: f32 = 0.35;
+
+/// Pseudo-random value in -0.5..0.5, constant within a twitch period
+fn jitter(t: f32, seed: f32) -> f32 {
+    let n = (t / TWITCH_PERIOD).floor() + seed * 101.0;
+    ((n * 12.9898).sin() * 43758.547).fract().abs() - 0.5
+}
+
+/// Slumps as the build drags on, twitches when glitching or enraged. `t` is wall time in seconds.
+pub fn pose(stats: &FrameStats, t: f32) -> Pose {
+    let slump = damage(stats);
+    let glitch = (stats.glitchiness as f32 / 32.0).min(1.0);
+    let twitch = glitch.max(stats.rage);
+
+    Pose {
+        head_tilt: slump * MAX_HEAD_SLUMP + twitch * 0.3 * jitter(t, 0.0),
+        arm_angles: [
+            stats.rage * 0.8 + twitch * 0.5 * jitter(t, 1.0),
+            stats.rage * 0.8 + twitch * 0.5 * jitter(t, 2.0),
+        ],
+        // Tired eyes get narrower, angry ones bulge
+        eye_scale: 1.0 - 0.4 * slump + 0.6 * stats.rage,
+    }
+}
@@ -0,0 +1,118 @@
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::{Context, Result};
+
+const VERTEX_SHADER: &str = "android_vs.glsl";
+const FRAGMENT_SHADER: &str = "android_fs.glsl";
+
+// Used when the shader directory is not available, e.g. in release builds run outside the repo
+const EMBEDDED_VERTEX_SHADER: &str = include_str!("../../../shaders/android_vs.glsl");
+const EMBEDDED_FRAGMENT_SHADER: &str = include_str!("../../../shaders/android_fs.glsl");
+
+// No point hitting the filesystem every frame
+const CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Loads the mascot shaders from `shaders/` and reloads them whenever the files change.
+pub struct Shaders {
+    dir: Option<PathBuf>,
+    last_modified: Option<SystemTime>,
+    last_check: Instant,
+}
+
+impl Shaders {
+    /// Shader directory is taken from EVIL_ANDROID_SHADER_DIR. If unset, debug builds use
+    /// `shaders/` from the source tree and release builds stick to the embedded copies.
+    pub fn from_env() -> Self {
+        let dir = match std::env::var_os("EVIL_ANDROID_SHADER_DIR") {
+            Some(dir) => Some(PathBuf::from(dir)),
+            None if cfg!(debug_assertions) => Some(PathBuf::from(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/shaders"
+            ))),
+            None => None,
+        };
+        if let Some(dir) = &dir {
+            log::info!("loading shaders from {}", dir.display());
+        }
+        Self {
+            dir,
+            last_modified: None,
+            last_check: Instant::now(),
+        }
+    }
+
+    /// Compiles the shaders from files, falling back to embedded ones if that fails.
+    pub fn load(&mut self, display: &impl glium::backend::Facade) -> Result<glium::Program> {
+        self.last_modified = self.modified();
+        match self.load_from_dir(display) {
+            Some(Ok(program)) => return Ok(program),
+            Some(Err(e)) => log::error!("{e:#}, using embedded shaders"),
+            None => {}
+        }
+        Ok(glium::Program::from_source(
+            display,
+            EMBEDDED_VERTEX_SHADER,
+            EMBEDDED_FRAGMENT_SHADER,
+            None,
+        )?)
+    }
+
+    /// Returns a new program if shader files changed and compiled successfully. Compilation
+    /// errors are logged and the old program should be kept.
+    pub fn reload_if_changed(
+        &mut self,
+        display: &impl glium::backend::Facade,
+    ) -> Option<glium::Program> {
+        if self.dir.is_none() || self.last_check.elapsed() < CHECK_INTERVAL {
+            return None;
+        }
+        self.last_check = Instant::now();
+
+        let modified = self.modified();
+        if modified == self.last_modified {
+            return None;
+        }
+        self.last_modified = modified;
+
+        match self.load_from_dir(display)? {
+            Ok(program) => {
+                log::info!("shaders reloaded");
+                Some(program)
+            }
+            Err(e) => {
+                log::error!("{e:#}");
+                None
+            }
+        }
+    }
+
+    fn load_from_dir(
+        &self,
+        display: &impl glium::backend::Facade,
+    ) -> Option<Result<glium::Program>> {
+        let dir = self.dir.as_ref()?;
+        let read = |name: &str| {
+            let path = dir.join(name);
+            std::fs::read_to_string(&path)
+                .with_context(|| format!("cannot read {}", path.display()))
+        };
+        Some((|| {
+            let vertex = read(VERTEX_SHADER)?;
+            let fragment = read(FRAGMENT_SHADER)?;
+            glium::Program::from_source(display, &vertex, &fragment, None)
+                .context("shader compilation failed")
+        })())
+    }
+
+    /// Latest modification time of either shader file
+    fn modified(&self) -> Option<SystemTime> {
+        let dir = self.dir.as_ref()?;
+        [VERTEX_SHADER, FRAGMENT_SHADER]
+            .iter()
+            .filter_map(|name| std::fs::metadata(dir.join(name)).ok()?.modified().ok())
+            .max()
+    }
+}
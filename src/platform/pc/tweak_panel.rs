@@ -0,0 +1,156 @@
+use crate::tweaks::{Curve, LedRole, Tweaks};
+
+/// Draws the tweak panel. Edits `tweaks` in place.
+pub fn show(ctx: &egui::Context, tweaks: &mut Tweaks) {
+    egui::Window::new("tweaks")
+        .default_pos([8.0, 140.0])
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.add(
+                egui::Slider::new(&mut tweaks.frames_per_shade, 1..=64).text("frames per shade"),
+            );
+            ui.add(
+                egui::Slider::new(&mut tweaks.unexaggerated_shades, 0..=32)
+                    .text("unexaggerated shades"),
+            );
+            ui.add(
+                egui::Slider::new(&mut tweaks.exaggeration_base, 1.0..=1.1)
+                    .text("exaggeration base"),
+            );
+            ui.add(
+                egui::Slider::new(&mut tweaks.exaggeration_factor, 1.0..=2.0)
+                    .text("exaggeration factor"),
+            );
+            ui.add(egui::Slider::new(&mut tweaks.max_text_shake, 0..=16).text("max text shake"));
+            curve_picker(ui, "shake curve", &mut tweaks.shake_curve);
+            ui.checkbox(&mut tweaks.supersampled_text, "shake text by half pixels");
+            curve_picker(ui, "LED curve", &mut tweaks.led_curve);
+            let [left, right] = &mut tweaks.led_roles;
+            led_role_picker(ui, "left eye", left);
+            led_role_picker(ui, "right eye", right);
+            curve_picker(ui, "glitch curve", &mut tweaks.glitch_curve);
+            ui.add(
+                egui::Slider::new(&mut tweaks.glitch_probability, 0.0..=1.0)
+                    .text("glitch probability"),
+            );
+            ui.checkbox(&mut tweaks.glitch_wrap, "wrap glitches around");
+            ui.checkbox(&mut tweaks.glitch_spread, "spread glitches from the timer");
+            // 1.0 would freeze the screen
+            ui.add(egui::Slider::new(&mut tweaks.ghosting, 0.0..=0.95).text("ghosting"));
+            ui.add(
+                egui::Slider::new(&mut tweaks.frame_budget_ms, 0..=100).text("frame budget (ms)"),
+            );
+            ui.add(
+                egui::Slider::new(&mut tweaks.throttle_celsius, 0.0..=100.0)
+                    .text("throttle above (°C)"),
+            );
+            ui.add(egui::Slider::new(&mut tweaks.max_noise, 0.0..=1.0).text("max noise"));
+            curve_picker(ui, "noise curve", &mut tweaks.noise_curve);
+            ui.collapsing("noise", |ui| {
+                let noise = &mut tweaks.noise;
+                ui.add(egui::Slider::new(&mut noise.color, 0.0..=1.0).text("color"));
+                ui.add(egui::Slider::new(&mut noise.monochrome, 0.0..=1.0).text("monochrome"));
+                ui.add(egui::Slider::new(&mut noise.sensor, 0.0..=1.0).text("sensor"));
+                ui.add(
+                    egui::Slider::new(&mut noise.salt_and_pepper, 0.0..=1.0)
+                        .text("salt and pepper"),
+                );
+                ui.add(egui::Slider::new(&mut noise.banding, 0.0..=1.0).text("banding"));
+            });
+            ui.collapsing("screen shake", |ui| {
+                let shake = &mut tweaks.screen_shake;
+                ui.add(egui::Slider::new(&mut shake.burst, 0.0..=16.0).text("burst"));
+                ui.add(egui::Slider::new(&mut shake.error, 0.0..=16.0).text("error"));
+                ui.add(egui::Slider::new(&mut shake.decay_secs, 0.05..=2.0).text("decay (s)"));
+            });
+            ui.collapsing("backlight", |ui| {
+                let backlight = &mut tweaks.backlight;
+                ui.add(egui::Slider::new(&mut backlight.burst_dip, 0.0..=1.0).text("burst dip"));
+                ui.add(egui::Slider::new(&mut backlight.breathing, 0.0..=1.0).text("breathing"));
+                ui.add(
+                    egui::Slider::new(&mut backlight.breathing_secs, 0.5..=10.0).text("breath (s)"),
+                );
+                ui.add(egui::Slider::new(&mut backlight.climax, 0.0..=1.0).text("climax"));
+                ui.add(egui::Slider::new(&mut backlight.blackout, 0.0..=1.0).text("blackout"));
+            });
+            ui.add(
+                egui::Slider::new(&mut tweaks.success_probability, 0.0..=1.0)
+                    .logarithmic(true)
+                    .text("success probability"),
+            );
+
+            ui.horizontal(|ui| {
+                let mut fixed_seed = tweaks.rng_seed.is_some();
+                if ui.checkbox(&mut fixed_seed, "fixed RNG seed").changed() {
+                    tweaks.rng_seed = fixed_seed.then_some(0);
+                }
+                if let Some(seed) = &mut tweaks.rng_seed {
+                    ui.add(egui::DragValue::new(seed));
+                }
+            });
+
+            if ui.button("reset").clicked() {
+                *tweaks = Tweaks::default();
+            }
+        });
+}
+
+fn led_role_picker(ui: &mut egui::Ui, label: &str, role: &mut LedRole) {
+    let roles = [
+        ("progress", LedRole::Progress),
+        ("glitchiness", LedRole::Glitchiness),
+        ("rage", LedRole::Rage),
+        ("mirror", LedRole::Mirror),
+        ("off", LedRole::Off),
+    ];
+    let selected = roles
+        .iter()
+        .find(|(_, r)| *r == *role)
+        .map_or("", |(name, _)| *name);
+    egui::ComboBox::from_label(label)
+        .selected_text(selected)
+        .show_ui(ui, |ui| {
+            for (name, r) in roles {
+                ui.selectable_value(role, r, name);
+            }
+        });
+}
+
+fn curve_picker(ui: &mut egui::Ui, label: &str, curve: &mut Curve) {
+    let kinds = [
+        ("linear", Curve::Linear),
+        ("exponential", Curve::Exponential(2.0)),
+        ("stepped", Curve::Stepped(8)),
+    ];
+    let kind_of = |curve: &Curve| std::mem::discriminant(curve);
+    ui.horizontal(|ui| {
+        let selected = kinds
+            .iter()
+            .find(|(_, kind)| kind_of(kind) == kind_of(curve))
+            .map_or("", |(name, _)| *name);
+        egui::ComboBox::from_label(label)
+            .selected_text(selected)
+            .show_ui(ui, |ui| {
+                for (name, kind) in kinds {
+                    // Keeps the parameter when picking the same kind again
+                    let is_selected = kind_of(&kind) == kind_of(curve);
+                    if ui.selectable_label(is_selected, name).clicked() && !is_selected {
+                        *curve = kind;
+                    }
+                }
+            });
+        match curve {
+            Curve::Linear => {}
+            Curve::Exponential(exponent) => {
+                ui.add(
+                    egui::DragValue::new(exponent)
+                        .speed(0.05)
+                        .clamp_range(0.1..=8.0),
+                );
+            }
+            Curve::Stepped(steps) => {
+                ui.add(egui::DragValue::new(steps).clamp_range(1..=64));
+            }
+        }
+    });
+}
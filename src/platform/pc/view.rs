@@ -0,0 +1,150 @@
+use std::env;
+
+use anyhow::{bail, Result};
+use embedded_graphics::geometry::Size;
+
+/// What the window shows
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ViewMode {
+    /// The whole android, with the LCD on its belly
+    Mascot,
+    /// Just the LCD, as large as fits the window
+    Lcd,
+    /// Just the LCD, scaled by a whole number so that every pixel is the same size
+    PixelPerfectLcd,
+}
+
+impl ViewMode {
+    const ALL: [ViewMode; 3] = [ViewMode::Mascot, ViewMode::Lcd, ViewMode::PixelPerfectLcd];
+
+    /// Reads EVIL_ANDROID_VIEW, defaulting to the mascot
+    pub fn from_env() -> Result<Self> {
+        match env::var("EVIL_ANDROID_VIEW").as_deref() {
+            Err(_) | Ok("mascot") => Ok(ViewMode::Mascot),
+            Ok("lcd") => Ok(ViewMode::Lcd),
+            Ok("pixel-perfect") => Ok(ViewMode::PixelPerfectLcd),
+            Ok(other) => bail!("invalid EVIL_ANDROID_VIEW: {other}"),
+        }
+    }
+
+    pub fn next(self) -> Self {
+        let idx = Self::ALL.iter().position(|&v| v == self).unwrap();
+        Self::ALL[(idx + 1) % Self::ALL.len()]
+    }
+
+    /// Where the LCD goes in a window of given size, None if it's on the mascot
+    pub fn lcd_rect(self, window: Size, lcd: Size) -> Option<glium::Rect> {
+        match self {
+            ViewMode::Mascot => None,
+            ViewMode::Lcd => Some(letterbox(window, (lcd.width, lcd.height))),
+            ViewMode::PixelPerfectLcd => Some(integer_scaled(window, lcd)),
+        }
+    }
+}
+
+/// Width to height of the area the mascot shader is designed for
+pub const MASCOT_ASPECT: (u32, u32) = (4, 3);
+
+/// Largest `aspect` shaped area centered in the window. The rest is left as bars on either
+/// the sides or the top and bottom.
+pub fn letterbox(window: Size, aspect: (u32, u32)) -> glium::Rect {
+    let (aspect_w, aspect_h) = (u64::from(aspect.0), u64::from(aspect.1));
+    let (window_w, window_h) = (u64::from(window.width), u64::from(window.height));
+    let (width, height) = if window_w * aspect_h > window_h * aspect_w {
+        (window_h * aspect_w / aspect_h, window_h)
+    } else {
+        (window_w, window_w * aspect_h / aspect_w)
+    };
+    // Both fit in the window, so in u32 too
+    centered(window, Size::new(width as u32, height as u32))
+}
+
+/// `content` scaled up by the largest whole number that fits, centered in the window. Never
+/// scaled below 1, a window that's too small just crops it.
+pub fn integer_scaled(window: Size, content: Size) -> glium::Rect {
+    let scale = (window.width / content.width.max(1))
+        .min(window.height / content.height.max(1))
+        .max(1);
+    centered(window, content * scale)
+}
+
+/// Where `glium::Surface::blit_whole_color_to` should put things to fill `rect`
+pub fn blit_target(rect: glium::Rect) -> glium::BlitTarget {
+    glium::BlitTarget {
+        left: rect.left,
+        bottom: rect.bottom,
+        width: rect.width as i32,
+        height: rect.height as i32,
+    }
+}
+
+fn centered(window: Size, size: Size) -> glium::Rect {
+    glium::Rect {
+        left: window.width.saturating_sub(size.width) / 2,
+        bottom: window.height.saturating_sub(size.height) / 2,
+        width: size.width,
+        height: size.height,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(left: u32, bottom: u32, width: u32, height: u32) -> glium::Rect {
+        glium::Rect {
+            left,
+            bottom,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn letterbox_keeps_aspect() {
+        assert_eq!(
+            letterbox(Size::new(1600, 1200), MASCOT_ASPECT),
+            rect(0, 0, 1600, 1200)
+        );
+        // Bars on the sides
+        assert_eq!(
+            letterbox(Size::new(1920, 1080), MASCOT_ASPECT),
+            rect(240, 0, 1440, 1080)
+        );
+        // Bars on top and bottom
+        assert_eq!(
+            letterbox(Size::new(800, 1000), MASCOT_ASPECT),
+            rect(0, 200, 800, 600)
+        );
+        assert_eq!(letterbox(Size::zero(), MASCOT_ASPECT), rect(0, 0, 0, 0));
+    }
+
+    #[test]
+    fn lcd_fills_the_window() {
+        let lcd = Size::new(160, 128);
+        let window = Size::new(1600, 1200);
+        assert_eq!(ViewMode::Mascot.lcd_rect(window, lcd), None);
+        assert_eq!(
+            ViewMode::Lcd.lcd_rect(window, lcd),
+            Some(rect(50, 0, 1500, 1200))
+        );
+    }
+
+    #[test]
+    fn integer_scaled_uses_whole_multiples() {
+        let lcd = Size::new(160, 128);
+        assert_eq!(
+            integer_scaled(Size::new(1600, 1200), lcd),
+            rect(80, 24, 1440, 1152)
+        );
+        assert_eq!(
+            integer_scaled(Size::new(1920, 1080), lcd),
+            rect(320, 28, 1280, 1024)
+        );
+        // Too small to scale down
+        assert_eq!(
+            integer_scaled(Size::new(100, 100), lcd),
+            rect(0, 0, 160, 128)
+        );
+    }
+}
@@ -0,0 +1,21 @@
+use super::MemoryStats;
+
+const STATUS_PATH: &str = "/proc/self/status";
+
+/// Resident memory of this process. Linux has no fixed size heap, so nothing about free heap.
+/// Thread stacks grow on demand and nothing records how far, so nothing about them either.
+pub fn memory_stats() -> Option<MemoryStats> {
+    let status = std::fs::read_to_string(STATUS_PATH).ok()?;
+    let resident_kb: usize = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))?
+        .trim()
+        .strip_suffix("kB")?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(MemoryStats {
+        resident: Some(resident_kb * 1024),
+        ..Default::default()
+    })
+}
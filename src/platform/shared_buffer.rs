@@ -0,0 +1,35 @@
+use std::sync::{Arc, Mutex};
+
+use embedded_graphics::{geometry::Size, pixelcolor::Rgb565, prelude::RgbColor};
+use embedded_graphics_framebuf::backends::FrameBufferBackend;
+
+/// Frame buffer that can be drawn to from one thread and displayed from another
+#[derive(Clone)]
+pub struct SharedBuffer(pub Arc<Mutex<Vec<Rgb565>>>);
+
+impl SharedBuffer {
+    pub fn new(size: Size) -> Self {
+        let pixel_count = (size.width * size.height) as usize;
+        Self(Arc::new(Mutex::new(vec![Rgb565::BLACK; pixel_count])))
+    }
+
+    pub fn snapshot(&self) -> Vec<Rgb565> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+impl FrameBufferBackend for SharedBuffer {
+    type Color = Rgb565;
+
+    fn set(&mut self, index: usize, color: Self::Color) {
+        self.0.lock().unwrap()[index] = color;
+    }
+
+    fn get(&self, index: usize) -> Self::Color {
+        self.0.lock().unwrap()[index]
+    }
+
+    fn nr_elements(&self) -> usize {
+        self.0.lock().unwrap().len()
+    }
+}
@@ -0,0 +1,40 @@
+use std::{io::ErrorKind, path::PathBuf};
+
+use anyhow::{Context, Result};
+
+/// EVIL_ANDROID_STATE_DIR, or the XDG state directory
+fn dir() -> Result<PathBuf> {
+    if let Some(dir) = std::env::var_os("EVIL_ANDROID_STATE_DIR") {
+        return Ok(dir.into());
+    }
+    let base = match std::env::var_os("XDG_STATE_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(
+            std::env::var_os("HOME").context("neither XDG_STATE_HOME nor HOME is set")?,
+        )
+        .join(".local/state"),
+    };
+    Ok(base.join("evil-android"))
+}
+
+/// Values saved with Platform::store are kept in files named after their keys
+pub fn load(key: &str) -> Result<Option<Vec<u8>>> {
+    let path = dir()?.join(key);
+    match std::fs::read(&path) {
+        Ok(value) => Ok(Some(value)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("cannot read {}", path.display())),
+    }
+}
+
+pub fn store(key: &str, value: &[u8]) -> Result<()> {
+    let dir = dir()?;
+    std::fs::create_dir_all(&dir).with_context(|| format!("cannot create {}", dir.display()))?;
+    // Renaming is atomic, so a crash mid-write leaves the old value intact
+    let path = dir.join(key);
+    let tmp_path = dir.join(format!("{key}.tmp"));
+    std::fs::write(&tmp_path, value)
+        .with_context(|| format!("cannot write {}", tmp_path.display()))?;
+    std::fs::rename(&tmp_path, &path)
+        .with_context(|| format!("cannot rename to {}", path.display()))
+}
@@ -0,0 +1,69 @@
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::{Context, Result};
+
+/// Plain directory standing in for the flash filesystem and SD card of the ESP32:
+/// EVIL_ANDROID_STORAGE_DIR, or `storage` in the current directory
+pub struct StorageDir {
+    dir: PathBuf,
+    /// Looked in for files the main one doesn't have
+    fallback: Option<PathBuf>,
+}
+
+impl StorageDir {
+    pub fn from_env() -> Self {
+        let dir = std::env::var_os("EVIL_ANDROID_STORAGE_DIR").unwrap_or_else(|| "storage".into());
+        Self {
+            dir: dir.into(),
+            fallback: None,
+        }
+    }
+
+    pub fn with_fallback(self, fallback: impl Into<PathBuf>) -> Self {
+        Self {
+            fallback: Some(fallback.into()),
+            ..self
+        }
+    }
+
+    /// Latest modification time of any file in either directory, None if there are none
+    pub fn modified(&self) -> Option<SystemTime> {
+        std::iter::once(&self.dir)
+            .chain(&self.fallback)
+            .filter_map(|dir| latest_modified(dir))
+            .max()
+    }
+}
+
+/// Of the files in `dir` and its subdirectories
+fn latest_modified(dir: &Path) -> Option<SystemTime> {
+    std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let metadata = entry.metadata().ok()?;
+            match metadata.is_dir() {
+                true => latest_modified(&entry.path()),
+                false => metadata.modified().ok(),
+            }
+        })
+        .max()
+}
+
+impl super::Storage for StorageDir {
+    fn read(&mut self, path: &str) -> Result<Option<Vec<u8>>> {
+        for dir in std::iter::once(&self.dir).chain(&self.fallback) {
+            let path = dir.join(path);
+            match std::fs::read(&path) {
+                Ok(data) => return Ok(Some(data)),
+                Err(e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => return Err(e).with_context(|| format!("cannot read {}", path.display())),
+            }
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,376 @@
+use std::{
+    convert::Infallible,
+    fmt::Write as _,
+    io::{self, Write as _},
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use anyhow::Result;
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    style::ResetColor,
+    terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use embedded_graphics::{
+    geometry::Size,
+    pixelcolor::{Rgb565, Rgb888},
+    prelude::{DrawTarget, RgbColor},
+};
+use embedded_graphics_framebuf::FrameBuf;
+use log::{LevelFilter, Log, Metadata, Record};
+
+use super::{
+    shared_buffer::SharedBuffer, storage_dir::StorageDir, BatteryStatus, Brightness, DisplayId,
+    FrameStats, InputEvent, MemoryStats, ResumeState, SensorReadings,
+};
+use crate::battery::SimulatedBattery;
+
+const FRAME_INTERVAL: Duration = Duration::from_millis(33);
+
+#[derive(Clone)]
+pub struct TermLED(Arc<Mutex<Brightness>>);
+
+impl super::LED for TermLED {
+    fn write_brightness(&mut self, brightness: Brightness) -> Result<()> {
+        *self.0.lock().unwrap() = brightness;
+        Ok(())
+    }
+}
+
+/// Key presses read from the terminal in raw mode
+pub struct KeyboardInput {
+    exit_requested: Arc<AtomicBool>,
+}
+
+impl super::Input for KeyboardInput {
+    fn poll(&mut self) -> Result<Option<InputEvent>> {
+        while event::poll(Duration::ZERO)? {
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            // Raw mode swallows Ctrl+C, so it has to be handled here
+            let ctrl_c =
+                key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+            if ctrl_c || key.code == KeyCode::Char('q') || key.code == KeyCode::Esc {
+                self.exit_requested.store(true, Ordering::Relaxed);
+                return Ok(None);
+            }
+            if let Some(event) = key_to_input_event(key.code) {
+                return Ok(Some(event));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Same bindings as the windowed simulator
+fn key_to_input_event(key: KeyCode) -> Option<InputEvent> {
+    match key {
+        KeyCode::Left => Some(InputEvent::Scrub(-1)),
+        KeyCode::Right => Some(InputEvent::Scrub(1)),
+        KeyCode::Char(' ') => Some(InputEvent::TogglePause),
+        KeyCode::Char('s') => Some(InputEvent::Shake(1.0)),
+        KeyCode::Char('p') => Some(InputEvent::Pet),
+        KeyCode::Char('o') => Some(InputEvent::Poke),
+        KeyCode::Char('n') => Some(InputEvent::NextScene),
+        KeyCode::Char('=') => Some(InputEvent::Speed(1)),
+        KeyCode::Char('-') => Some(InputEvent::Speed(-1)),
+        KeyCode::Char(']') => Some(InputEvent::Brightness(1)),
+        KeyCode::Char('[') => Some(InputEvent::Brightness(-1)),
+        KeyCode::Char('l') => Some(InputEvent::ToggleLogOverlay),
+        KeyCode::Char('m') => Some(InputEvent::ToggleDiagnostics),
+        KeyCode::Char('i') => Some(InputEvent::ToggleStats),
+        KeyCode::Char('z') => Some(InputEvent::Sleep),
+        KeyCode::Char('f') => Some(InputEvent::DumpFrame),
+        KeyCode::Char('d') => Some(InputEvent::ToggleDemo),
+        KeyCode::Char('b') => Some(InputEvent::ToggleBadge),
+        _ => None,
+    }
+}
+
+/// Anything printed would mess up the picture. Log lines are only buffered, and the latest one
+/// is shown in the status line.
+struct NullLogger;
+
+impl Log for NullLogger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, _record: &Record) {}
+
+    fn flush(&self) {}
+}
+
+fn restore_terminal() {
+    let _ = crossterm::execute!(io::stdout(), ResetColor, cursor::Show, LeaveAlternateScreen);
+    let _ = terminal::disable_raw_mode();
+}
+
+fn write_fg(out: &mut String, color: Rgb888) {
+    let _ = write!(out, "\x1b[38;2;{};{};{}m", color.r(), color.g(), color.b());
+}
+
+fn write_bg(out: &mut String, color: Rgb888) {
+    let _ = write!(out, "\x1b[48;2;{};{};{}m", color.r(), color.g(), color.b());
+}
+
+fn led_color(brightness: Brightness) -> Rgb888 {
+    // Not completely black, so that an LED that's off is still visible
+    Rgb888::new(40 + (f32::from(brightness) * 215.0) as u8, 0, 0)
+}
+
+/// Renders the whole screen into `out`, with upper half blocks colored by the upper pixel in
+/// the foreground and the lower one in the background
+fn render(
+    size: Size,
+    pixels: &[Rgb565],
+    led0: Brightness,
+    led1: Brightness,
+    stats: &FrameStats,
+    out: &mut String,
+) {
+    let width = size.width as usize;
+    out.clear();
+    out.push_str("\x1b[H");
+    for rows in pixels.chunks(width * 2) {
+        let (upper, lower) = rows.split_at(width);
+        // Below the last row of an odd height
+        let lower = lower.iter().chain(std::iter::repeat(&Rgb565::BLACK));
+        let mut last = None;
+        for (&top, &bottom) in upper.iter().zip(lower) {
+            let colors = (Rgb888::from(top), Rgb888::from(bottom));
+            if last != Some(colors) {
+                write_fg(out, colors.0);
+                write_bg(out, colors.1);
+                last = Some(colors);
+            }
+            out.push('▀');
+        }
+        out.push_str("\x1b[0m\r\n");
+    }
+
+    write_fg(out, led_color(led0));
+    out.push('●');
+    write_fg(out, led_color(led1));
+    out.push('●');
+    out.push_str("\x1b[0m ");
+    let fps = if stats.frame_time.is_zero() {
+        0.0
+    } else {
+        1.0 / stats.frame_time.as_secs_f32()
+    };
+    let mut status = format!("{fps:4.1} FPS {}", stats.scene);
+    if let Some((level, line)) = crate::log_buffer::lines().pop() {
+        let _ = write!(status, " | {level}: {line}");
+    }
+    out.extend(status.chars().take(width.saturating_sub(3)));
+    out.push_str("\x1b[K");
+}
+
+pub struct Platform {
+    lcd: FrameBuf<Rgb565, SharedBuffer>,
+    led0: TermLED,
+    led1: TermLED,
+    input: KeyboardInput,
+    // Set by pressing q/Esc/Ctrl+C or by dropping the Platform
+    exit_requested: Arc<AtomicBool>,
+    render_thread: Option<JoinHandle<()>>,
+    frame_stats: Arc<Mutex<FrameStats>>,
+    battery: Option<SimulatedBattery>,
+    storage: StorageDir,
+}
+
+impl Drop for Platform {
+    fn drop(&mut self) {
+        self.exit_requested.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.render_thread.take() {
+            let _ = thread.join();
+        }
+        restore_terminal();
+    }
+}
+
+pub fn new_platform() -> Result<impl crate::platform::Platform> {
+    crate::log_buffer::init(NullLogger, LevelFilter::Info)?;
+
+    terminal::enable_raw_mode()?;
+    crossterm::execute!(
+        io::stdout(),
+        EnterAlternateScreen,
+        cursor::Hide,
+        Clear(ClearType::All)
+    )?;
+    let size = super::simulated_lcd_size()?;
+    // Each character cell holds two pixels, one above the other, plus a status line at the bottom
+    let min_size = (size.width, size.height.div_ceil(2) + 1);
+    let (cols, rows) = terminal::size()?;
+    if u32::from(cols) < min_size.0 || u32::from(rows) < min_size.1 {
+        log::warn!(
+            "terminal is {cols}x{rows}, at least {}x{} needed",
+            min_size.0,
+            min_size.1
+        );
+    }
+
+    let pixels = SharedBuffer::new(size);
+    let lcd = FrameBuf::new(
+        pixels.clone(),
+        size.width.try_into()?,
+        size.height.try_into()?,
+    );
+    let led0 = TermLED(Arc::new(Mutex::new(0f32.into())));
+    let led1 = TermLED(Arc::new(Mutex::new(0f32.into())));
+    let exit_requested = Arc::new(AtomicBool::new(false));
+
+    let led0_clone = led0.clone();
+    let led1_clone = led1.clone();
+    let frame_stats = Arc::new(Mutex::new(FrameStats::default()));
+    let frame_stats_clone = frame_stats.clone();
+    let exit_requested_clone = exit_requested.clone();
+    let render_thread = std::thread::spawn(move || {
+        let mut out = String::new();
+        while !exit_requested_clone.load(Ordering::Relaxed) {
+            let snapshot = pixels.snapshot();
+            let led0 = *led0_clone.0.lock().unwrap();
+            let led1 = *led1_clone.0.lock().unwrap();
+            let stats = frame_stats_clone.lock().unwrap().clone();
+            render(size, &snapshot, led0, led1, &stats, &mut out);
+
+            let mut stdout = io::stdout().lock();
+            if stdout
+                .write_all(out.as_bytes())
+                .and_then(|_| stdout.flush())
+                .is_err()
+            {
+                // Terminal is gone, e.g. SSH connection dropped
+                exit_requested_clone.store(true, Ordering::Relaxed);
+            }
+            drop(stdout);
+            std::thread::sleep(FRAME_INTERVAL);
+        }
+    });
+
+    Ok(Platform {
+        lcd,
+        led0,
+        led1,
+        input: KeyboardInput {
+            exit_requested: exit_requested.clone(),
+        },
+        exit_requested,
+        render_thread: Some(render_thread),
+        frame_stats,
+        battery: SimulatedBattery::from_env(),
+        storage: StorageDir::from_env(),
+    })
+}
+
+impl crate::platform::Platform for Platform {
+    type DisplayError = Infallible;
+
+    fn name(&self) -> &'static str {
+        "term"
+    }
+
+    fn ip_address(&mut self) -> Option<IpAddr> {
+        super::local_ip_address()
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+
+    fn display(
+        &mut self,
+        id: DisplayId,
+    ) -> Option<&mut impl DrawTarget<Color = Rgb565, Error = Self::DisplayError>> {
+        (id == DisplayId::MAIN).then_some(&mut self.lcd)
+    }
+
+    fn led0(&mut self) -> &mut impl super::LED {
+        &mut self.led0
+    }
+
+    fn led1(&mut self) -> &mut impl super::LED {
+        &mut self.led1
+    }
+
+    fn input(&mut self) -> &mut impl super::Input {
+        &mut self.input
+    }
+
+    fn storage(&mut self) -> &mut impl super::Storage {
+        &mut self.storage
+    }
+
+    fn feed_watchdog(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn reset_lcd(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn report_frame_stats(&mut self, stats: &FrameStats) {
+        *self.frame_stats.lock().unwrap() = stats.clone();
+    }
+
+    fn exit_requested(&self) -> bool {
+        self.exit_requested.load(Ordering::Relaxed)
+    }
+
+    fn poll_tweaks(&mut self) -> Option<crate::tweaks::Tweaks> {
+        None
+    }
+
+    fn battery(&mut self) -> Option<BatteryStatus> {
+        self.battery.as_ref().map(SimulatedBattery::status)
+    }
+
+    fn sensors(&mut self) -> Option<SensorReadings> {
+        super::thermal_zone::read()
+    }
+
+    fn memory_stats(&mut self) -> Option<MemoryStats> {
+        super::proc_status::memory_stats()
+    }
+
+    fn load(&mut self, key: &str) -> Result<Option<Vec<u8>>> {
+        super::state_dir::load(key)
+    }
+
+    fn store(&mut self, key: &str, value: &[u8]) -> Result<()> {
+        super::state_dir::store(key, value)
+    }
+    fn deep_sleep(&mut self, _wake_after: Option<Duration>, _resume: Option<ResumeState>) {
+        log::info!("no deep sleep on Linux, exiting instead");
+        self.exit_requested.store(true, Ordering::Relaxed);
+    }
+
+    unsafe fn install_panic_screen(&mut self) {
+        const PANIC_SCREEN_HOLD: Duration = Duration::from_secs(10);
+
+        let lcd = FrameBuf::new(self.lcd.data.clone(), self.lcd.width(), self.lcd.height());
+        crate::panic_screen::install(lcd, PANIC_SCREEN_HOLD);
+
+        // Messages printed while the alternate screen is active get lost, print them again
+        // once the terminal is back to normal
+        let show_panic_screen = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            show_panic_screen(info);
+            restore_terminal();
+            eprintln!("{info}");
+        }));
+    }
+}
@@ -0,0 +1,29 @@
+use super::SensorReadings;
+
+/// SoC on a Raspberry Pi, usually the CPU package on a PC
+const TEMP_PATH: &str = "/sys/class/thermal/thermal_zone0/temp";
+
+/// Temperature of the first thermal zone, or EVIL_ANDROID_TEMPERATURE if set, to see how the
+/// android behaves when it's hot
+pub fn read() -> Option<SensorReadings> {
+    if let Ok(value) = std::env::var("EVIL_ANDROID_TEMPERATURE") {
+        return match value.parse() {
+            Ok(celsius) => Some(SensorReadings {
+                temperature: Some(celsius),
+            }),
+            Err(e) => {
+                log::warn!("invalid EVIL_ANDROID_TEMPERATURE: {value}: {e}");
+                None
+            }
+        };
+    }
+
+    let millicelsius: i32 = std::fs::read_to_string(TEMP_PATH)
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(SensorReadings {
+        temperature: Some(millicelsius as f32 / 1000.0),
+    })
+}
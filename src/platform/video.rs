@@ -0,0 +1,267 @@
+use std::{
+    convert::Infallible,
+    io::Write,
+    path::PathBuf,
+    process::{Child, ChildStdin, Command, Stdio},
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+use embedded_graphics::{
+    pixelcolor::{Rgb565, Rgb888},
+    prelude::{DrawTarget, RgbColor},
+};
+use embedded_graphics_framebuf::FrameBuf;
+
+use super::{
+    shared_buffer::SharedBuffer, storage_dir::StorageDir, BatteryStatus, Brightness, DisplayId,
+    FrameStats, ResumeState,
+};
+use crate::{frame_clock, tweaks::Tweaks};
+
+const DEFAULT_FPS: u32 = 30;
+/// Video pixels per LCD pixel. Players smear anything that small when scaling it up, and a
+/// multiple of 2 keeps the halved resolution of yuv420p colors within LCD pixels.
+const SCALE: u32 = 4;
+
+/// Nobody sees the LEDs in the video
+pub struct NullLED;
+
+impl super::LED for NullLED {
+    fn write_brightness(&mut self, _brightness: Brightness) -> Result<()> {
+        Ok(())
+    }
+}
+
+struct Options {
+    path: PathBuf,
+    seed: u64,
+    fps: u32,
+}
+
+impl Options {
+    /// Taken from `--render-video <path> [--seed <n>] [--fps <n>]`
+    fn from_args() -> Result<Self> {
+        let mut path = None;
+        // Fixed even if not given, so that renders are always reproducible
+        let mut seed = 0;
+        let mut fps = DEFAULT_FPS;
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().with_context(|| format!("{arg} needs a value"));
+            match arg.as_str() {
+                "--render-video" => path = Some(PathBuf::from(value()?)),
+                "--seed" => seed = value()?.parse().context("invalid --seed")?,
+                "--fps" => fps = value()?.parse().context("invalid --fps")?,
+                _ => {}
+            }
+        }
+        let Some(path) = path else {
+            bail!("--render-video not given");
+        };
+        if fps == 0 {
+            bail!("--fps must be positive");
+        }
+        Ok(Self { path, seed, fps })
+    }
+}
+
+/// Renders one escalation cycle offscreen, as fast as it goes, and pipes the frames to ffmpeg.
+/// Time is virtual and moves by exactly one video frame per frame drawn, so that the result
+/// depends on nothing but the seed and the config.
+pub struct Platform {
+    lcd: FrameBuf<Rgb565, SharedBuffer>,
+    led0: NullLED,
+    led1: NullLED,
+    input: (),
+    storage: StorageDir,
+    ffmpeg: Child,
+    /// Dropping it tells ffmpeg the video is over
+    ffmpeg_stdin: Option<ChildStdin>,
+    options: Options,
+    frames: u64,
+    /// rgb24 pixels of the frame being written, kept to avoid allocating it every time
+    rgb: Vec<u8>,
+    exit_requested: bool,
+}
+
+impl Platform {
+    fn write_frame(&mut self) -> Result<()> {
+        let Some(stdin) = &mut self.ffmpeg_stdin else {
+            return Ok(());
+        };
+        self.rgb.clear();
+        for &pixel in self.lcd.data.0.lock().unwrap().iter() {
+            let pixel = Rgb888::from(pixel);
+            self.rgb
+                .extend_from_slice(&[pixel.r(), pixel.g(), pixel.b()]);
+        }
+        stdin
+            .write_all(&self.rgb)
+            .context("cannot write to ffmpeg")?;
+        self.frames += 1;
+        // Computed from the frame count rather than summed up, so that rounding doesn't add up
+        frame_clock::advance_to(Duration::from_nanos(
+            self.frames * 1_000_000_000 / u64::from(self.options.fps),
+        ));
+        Ok(())
+    }
+}
+
+impl Drop for Platform {
+    fn drop(&mut self) {
+        drop(self.ffmpeg_stdin.take());
+        match self.ffmpeg.wait() {
+            Ok(status) if status.success() => log::info!(
+                "{} frames written to {}",
+                self.frames,
+                self.options.path.display()
+            ),
+            Ok(status) => log::error!("ffmpeg failed: {status}"),
+            Err(e) => log::error!("cannot wait for ffmpeg: {e}"),
+        }
+    }
+}
+
+pub fn new_platform() -> Result<impl crate::platform::Platform> {
+    let logger = env_logger::Builder::from_default_env().build();
+    let log_level = logger.filter();
+    crate::log_buffer::init(logger, log_level)?;
+
+    let options = Options::from_args()?;
+    let lcd_size = super::simulated_lcd_size()?;
+    log::info!(
+        "rendering to {} at {} FPS, seed {}",
+        options.path.display(),
+        options.fps,
+        options.seed
+    );
+    let mut ffmpeg = Command::new("ffmpeg")
+        .args(["-y", "-loglevel", "error"])
+        .args(["-f", "rawvideo", "-pixel_format", "rgb24"])
+        .args([
+            "-video_size",
+            &format!("{}x{}", lcd_size.width, lcd_size.height),
+        ])
+        .args(["-framerate", &options.fps.to_string()])
+        .args(["-i", "-"])
+        .args([
+            "-vf",
+            &format!("scale=iw*{SCALE}:ih*{SCALE}:flags=neighbor"),
+        ])
+        // Anything else won't play in browsers
+        .args(["-pix_fmt", "yuv420p"])
+        .arg(&options.path)
+        .stdin(Stdio::piped())
+        .spawn()
+        .context("cannot run ffmpeg, is it installed?")?;
+    let ffmpeg_stdin = ffmpeg.stdin.take();
+    frame_clock::make_virtual();
+
+    Ok(Platform {
+        lcd: FrameBuf::new(
+            SharedBuffer::new(lcd_size),
+            lcd_size.width.try_into()?,
+            lcd_size.height.try_into()?,
+        ),
+        led0: NullLED,
+        led1: NullLED,
+        input: (),
+        storage: StorageDir::from_env(),
+        ffmpeg,
+        ffmpeg_stdin,
+        options,
+        frames: 0,
+        rgb: Vec::new(),
+        exit_requested: false,
+    })
+}
+
+impl crate::platform::Platform for Platform {
+    type DisplayError = Infallible;
+
+    fn name(&self) -> &'static str {
+        "video"
+    }
+
+    /// Nothing to wait for, only drawn frames move the clock
+    fn sleep(&mut self, _duration: Duration) {}
+
+    fn display(
+        &mut self,
+        id: DisplayId,
+    ) -> Option<&mut impl DrawTarget<Color = Rgb565, Error = Self::DisplayError>> {
+        (id == DisplayId::MAIN).then_some(&mut self.lcd)
+    }
+
+    fn led0(&mut self) -> &mut impl super::LED {
+        &mut self.led0
+    }
+
+    fn led1(&mut self) -> &mut impl super::LED {
+        &mut self.led1
+    }
+
+    fn input(&mut self) -> &mut impl super::Input {
+        &mut self.input
+    }
+
+    fn storage(&mut self) -> &mut impl super::Storage {
+        &mut self.storage
+    }
+
+    fn feed_watchdog(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn reset_lcd(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Every frame is followed by this, whichever scene drew it
+    fn report_frame_stats(&mut self, _stats: &FrameStats) {
+        if let Err(e) = self.write_frame() {
+            log::error!("{e:#}");
+            self.exit_requested = true;
+        }
+    }
+
+    fn exit_requested(&self) -> bool {
+        self.exit_requested
+    }
+
+    fn poll_tweaks(&mut self) -> Option<Tweaks> {
+        None
+    }
+
+    fn adjust_tweaks(&self, tweaks: &mut Tweaks) {
+        tweaks.rng_seed = Some(self.options.seed);
+        // Frames are never late, the clock waits for them
+        tweaks.frame_budget_ms = 0;
+    }
+
+    fn cycle_done(&mut self) {
+        self.exit_requested = true;
+    }
+
+    fn battery(&mut self) -> Option<BatteryStatus> {
+        None
+    }
+
+    /// Straight into an escalation: no boot screen with the device name, and none of the scenes
+    /// that depend on the time of day
+    fn resume_state(&mut self) -> Option<ResumeState> {
+        Some(ResumeState {
+            elapsed: Duration::ZERO,
+            timeline_pos: 0.0,
+        })
+    }
+
+    fn deep_sleep(&mut self, _wake_after: Option<Duration>, _resume: Option<ResumeState>) {
+        log::warn!("went to sleep halfway through the video");
+        self.exit_requested = true;
+    }
+
+    /// Nobody's watching, the panic message on stderr is enough
+    unsafe fn install_panic_screen(&mut self) {}
+}
@@ -0,0 +1,130 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use embedded_graphics::{draw_target::DrawTarget, pixelcolor::Rgb565, prelude::RgbColor};
+
+use crate::{
+    artnet,
+    platform::{DisplayId, InputEvent, Platform, ResumeState, LED},
+};
+
+// Read at build time, so that they work on the ESP32 too
+const SLEEP_AFTER_SECS: Option<&str> = option_env!("EVIL_ANDROID_SLEEP_AFTER_SECS");
+const SLEEP_MODE: Option<&str> = option_env!("EVIL_ANDROID_SLEEP_MODE");
+const WAKE_AFTER_SECS: Option<&str> = option_env!("EVIL_ANDROID_WAKE_AFTER_SECS");
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SleepMode {
+    /// Wakes up quickly and carries on where it left off
+    Light,
+    /// Draws much less power, but waking up reboots, resuming from the saved elapsed time
+    Deep,
+}
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// How long without any input before going to sleep. None to only sleep on request.
+    pub sleep_after: Option<Duration>,
+    pub mode: SleepMode,
+    /// Wakes up by itself after that long. None to only wake up on input.
+    pub wake_after: Option<Duration>,
+}
+
+fn parse_secs(name: &str, value: Option<&str>) -> Option<Duration> {
+    let value = value?;
+    match value.parse() {
+        Ok(secs) => Some(Duration::from_secs(secs)),
+        Err(e) => {
+            log::warn!("invalid {name}: {value}: {e}");
+            None
+        }
+    }
+}
+
+impl Config {
+    /// Sleeps deep by default, on platforms that can
+    pub fn from_build_env(can_deep_sleep: bool) -> Self {
+        let default = match can_deep_sleep {
+            true => SleepMode::Deep,
+            false => SleepMode::Light,
+        };
+        let mode = match SLEEP_MODE {
+            None => default,
+            Some("deep") => SleepMode::Deep,
+            Some("light") => SleepMode::Light,
+            Some(other) => {
+                log::warn!("invalid EVIL_ANDROID_SLEEP_MODE: {other}, using {default:?}");
+                default
+            }
+        };
+        Self {
+            sleep_after: parse_secs("EVIL_ANDROID_SLEEP_AFTER_SECS", SLEEP_AFTER_SECS),
+            mode,
+            wake_after: parse_secs("EVIL_ANDROID_WAKE_AFTER_SECS", WAKE_AFTER_SECS),
+        }
+    }
+}
+
+/// Decides when to go to sleep, and takes care of the displays and LEDs when it happens
+pub struct PowerManager {
+    config: Config,
+    last_activity: Instant,
+    sleep_requested: bool,
+}
+
+impl PowerManager {
+    pub fn new(config: Config) -> Self {
+        log::info!("power config: {config:?}");
+        Self {
+            config,
+            last_activity: Instant::now(),
+            sleep_requested: false,
+        }
+    }
+
+    /// Any input counts as activity, InputEvent::Sleep also requests going to sleep
+    pub fn on_input(&mut self, event: InputEvent) {
+        self.last_activity = Instant::now();
+        if event == InputEvent::Sleep {
+            self.sleep_requested = true;
+        }
+    }
+
+    pub fn should_sleep(&self) -> bool {
+        self.sleep_requested
+            || self
+                .config
+                .sleep_after
+                .is_some_and(|timeout| self.last_activity.elapsed() >= timeout)
+    }
+
+    /// Blanks the displays, turns off the LEDs and sleeps. Returns after waking up from a light
+    /// sleep, or if the platform can't deep sleep.
+    pub fn sleep(&mut self, platform: &mut impl Platform, resume: ResumeState) -> Result<()> {
+        log::info!("going to {:?} sleep", self.config.mode);
+        platform.led0().set_brightness(0f32.into())?;
+        platform.led1().set_brightness(0f32.into())?;
+        artnet::output([0f32.into(); 2]);
+        for id in [DisplayId::MAIN, DisplayId::EYES] {
+            if let Some(display) = platform.display(id) {
+                display
+                    .clear(Rgb565::BLACK)
+                    .map_err(|e| platform.display_error(e))
+                    .context("DrawTarget::clear failed")?;
+            }
+        }
+
+        match self.config.mode {
+            SleepMode::Light => {
+                platform
+                    .light_sleep(self.config.wake_after)
+                    .context("Platform::light_sleep failed")?;
+                log::info!("woke up");
+            }
+            SleepMode::Deep => platform.deep_sleep(self.config.wake_after, Some(resume)),
+        }
+        self.last_activity = Instant::now();
+        self.sleep_requested = false;
+        Ok(())
+    }
+}
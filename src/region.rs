@@ -0,0 +1,130 @@
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use embedded_graphics::{
+    geometry::{Point, Size},
+    primitives::Rectangle,
+};
+
+use crate::intensity::Intensity;
+
+/// Part of the screen an effect is limited to
+#[derive(Clone, Debug)]
+pub enum Region<'a> {
+    Full,
+    Rect(Rectangle),
+    Mask(&'a Mask),
+}
+
+impl Region<'_> {
+    /// Calls `f` with each row of a `width`x`height` framebuffer that's (partially) within the
+    /// region, and the columns of that row that are. Rows come in order, top to bottom.
+    pub fn for_each_span(
+        &self,
+        width: usize,
+        height: usize,
+        mut f: impl FnMut(usize, Range<usize>),
+    ) {
+        match self {
+            Region::Full => (0..height).for_each(|row| f(row, 0..width)),
+            Region::Rect(rect) => {
+                let clip = |v: i32, max: usize| (v.max(0) as usize).min(max);
+                let Some(bottom_right) = rect.bottom_right() else {
+                    return;
+                };
+                let columns = clip(rect.top_left.x, width)..clip(bottom_right.x + 1, width);
+                if columns.is_empty() {
+                    return;
+                }
+                for row in clip(rect.top_left.y, height)..clip(bottom_right.y + 1, height) {
+                    f(row, columns.clone());
+                }
+            }
+            Region::Mask(mask) => {
+                for (row, columns) in &mask.spans {
+                    if *row >= height {
+                        break;
+                    }
+                    let columns = columns.start.min(width)..columns.end.min(width);
+                    if !columns.is_empty() {
+                        f(*row, columns);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Rectangle in between `from` and `to`
+pub fn grow(from: Rectangle, to: Rectangle, progress: Intensity) -> Rectangle {
+    // Fixed point, f32::round is std only
+    let progress = (progress.get() * 1024.0) as i32;
+    let lerp = |a: i32, b: i32| a + ((b - a) * progress + 512).div_euclid(1024);
+    let from_end = from.top_left + from.size;
+    let to_end = to.top_left + to.size;
+    let top_left = Point::new(
+        lerp(from.top_left.x, to.top_left.x),
+        lerp(from.top_left.y, to.top_left.y),
+    );
+    let end = Point::new(lerp(from_end.x, to_end.x), lerp(from_end.y, to_end.y));
+    let size = end - top_left;
+    Rectangle::new(
+        top_left,
+        Size::new(size.x.max(0) as u32, size.y.max(0) as u32),
+    )
+}
+
+/// Smallest rectangle containing both. Empty ones are left out.
+pub fn envelope(a: Rectangle, b: Rectangle) -> Rectangle {
+    if a.is_zero_sized() {
+        return b;
+    }
+    if b.is_zero_sized() {
+        return a;
+    }
+    let top_left = Point::new(
+        a.top_left.x.min(b.top_left.x),
+        a.top_left.y.min(b.top_left.y),
+    );
+    let (a_end, b_end) = (a.top_left + a.size, b.top_left + b.size);
+    let end = Point::new(a_end.x.max(b_end.x), a_end.y.max(b_end.y));
+    let size = end - top_left;
+    Rectangle::new(top_left, Size::new(size.x as u32, size.y as u32))
+}
+
+/// Arbitrarily shaped region, stored as runs of pixels so that effects don't need to check
+/// every pixel
+#[derive(Clone, Debug)]
+pub struct Mask {
+    size: Size,
+    /// Row and columns, ordered by row
+    spans: Vec<(usize, Range<usize>)>,
+}
+
+impl Mask {
+    /// Pixels for which `is_inside(column, row)` returns true, e.g. opaque pixels of an image.
+    /// Positioned at the top left corner of the screen.
+    pub fn from_fn(size: Size, is_inside: impl Fn(usize, usize) -> bool) -> Self {
+        let (width, height) = (size.width as usize, size.height as usize);
+        let mut spans = Vec::new();
+        for row in 0..height {
+            let mut start = None;
+            for column in 0..=width {
+                let inside = column < width && is_inside(column, row);
+                match (start, inside) {
+                    (None, true) => start = Some(column),
+                    (Some(first), false) => {
+                        spans.push((row, first..column));
+                        start = None;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Self { size, spans }
+    }
+
+    pub fn size(&self) -> Size {
+        self.size
+    }
+}
@@ -0,0 +1,134 @@
+use alloc::boxed::Box;
+use core::time::Duration;
+
+use anyhow::{bail, Result};
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::{Dimensions, Size},
+    pixelcolor::{Rgb565, Rgb888},
+    primitives::Rectangle,
+    Pixel,
+};
+use embedded_graphics_framebuf::FrameBuf;
+use rand::rngs::StdRng;
+
+#[cfg(feature = "scripting")]
+use crate::script::Script;
+use crate::{
+    animation::{self, Animation},
+    platform::Storage,
+    timeline::Timeline,
+    SliceFrameBufferBackend,
+};
+
+/// Same as the escalation with default tweaks
+pub const GLITCH_LINE_PROBABILITY: f32 = 0.25;
+
+/// What scenes draw on, the size of the LCD
+pub type Canvas<'a> = FrameBuf<Rgb565, &'a mut SliceFrameBufferBackend<Rgb565>>;
+
+/// What scenes can use besides the canvas
+pub struct SceneContext<'a> {
+    /// For loading images and such on demand
+    #[cfg_attr(not(feature = "scripting"), allow(dead_code))]
+    pub storage: &'a mut dyn Storage,
+    pub rng: &'a mut StdRng,
+    /// Brightness of both LEDs, 0..1. Kept between frames unless the scene changes it.
+    pub leds: [f32; 2],
+    /// Off-screen canvas a whole multiple of the LCD size, on platforms with RAM to spare.
+    /// Scenes that pan or zoom can draw on it, and `viewport::resample` it onto the canvas.
+    pub virtual_canvas: Option<&'a mut SliceFrameBufferBackend<Rgb565>>,
+}
+
+/// Self-contained piece of content, played between escalations
+pub trait Scene {
+    /// Shown in frame stats
+    fn name(&self) -> &'static str;
+
+    /// Draws the frame at `t` since the scene started. Returns false, without drawing anything,
+    /// once the scene is over.
+    fn draw(&mut self, t: Duration, canvas: &mut Canvas, ctx: &mut SceneContext) -> Result<bool>;
+}
+
+/// Parsed scene file, can be played any number of times
+pub enum SceneSource {
+    #[cfg(feature = "scripting")]
+    Script(Script),
+    Timeline(Timeline),
+    Animation(Animation),
+}
+
+impl SceneSource {
+    /// Type is picked based on the extension: `.rhai`, `.toml` or `.gif`. GIFs must fit on the
+    /// LCD, `lcd_size` big.
+    pub fn parse(
+        path: &str,
+        data: &[u8],
+        animation: &animation::Config,
+        lcd_size: Size,
+    ) -> Result<Self> {
+        if path.ends_with(".rhai") {
+            #[cfg(feature = "scripting")]
+            return Ok(Self::Script(Script::compile(data)?));
+            #[cfg(not(feature = "scripting"))]
+            bail!("built without the scripting feature");
+        } else if path.ends_with(".toml") {
+            Ok(Self::Timeline(Timeline::parse(data)?))
+        } else if path.ends_with(".gif") {
+            Ok(Self::Animation(Animation::parse(
+                data, animation, lcd_size,
+            )?))
+        } else {
+            bail!("unknown scene type, expected .rhai, .toml or .gif");
+        }
+    }
+
+    /// Plays it from the start
+    pub fn start(&self) -> Box<dyn Scene + '_> {
+        match self {
+            #[cfg(feature = "scripting")]
+            Self::Script(script) => Box::new(script.start()),
+            Self::Timeline(timeline) => Box::new(timeline.start()),
+            Self::Animation(animation) => Box::new(animation.start()),
+        }
+    }
+}
+
+/// `0xRRGGBB`, like in HTML
+pub fn color_from_hex(rgb: u32) -> Rgb565 {
+    Rgb888::new((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8).into()
+}
+
+/// Draws everything `scale` times bigger, for text larger than the biggest font
+pub struct Scaled<'a, D> {
+    pub target: &'a mut D,
+    pub scale: u32,
+}
+
+impl<D: DrawTarget> Dimensions for Scaled<'_, D> {
+    fn bounding_box(&self) -> Rectangle {
+        let bounds = self.target.bounding_box();
+        Rectangle::new(
+            bounds.top_left / self.scale as i32,
+            bounds.size / self.scale,
+        )
+    }
+}
+
+impl<D: DrawTarget> DrawTarget for Scaled<'_, D> {
+    type Color = D::Color;
+    type Error = D::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            self.target.fill_solid(
+                &Rectangle::new(point * self.scale as i32, Size::new_equal(self.scale)),
+                color,
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,125 @@
+use std::time::Duration;
+
+use embedded_graphics::{geometry::Point, pixelcolor::Rgb565};
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::intensify;
+
+/// Below this, the shake stops rather than jitter by a pixel forever
+const SETTLED: f32 = 0.5;
+
+/// `[tweaks.screen_shake]` in config.toml. Amplitudes are in pixels, 0 turns that trigger off.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Kick of a full strength glitch burst, weaker ones kick less
+    pub burst: f32,
+    /// Kick of each error logged
+    pub error: f32,
+    /// Time it takes the shake to calm down to about a third
+    pub decay_secs: f32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            burst: 4.0,
+            error: 2.0,
+            decay_secs: 0.3,
+        }
+    }
+}
+
+/// The whole screen jolted around, calming down over time
+#[derive(Default)]
+pub struct ScreenShake {
+    /// In pixels
+    amplitude: f32,
+    /// Reused by `apply`
+    scratch: Vec<Rgb565>,
+}
+
+impl ScreenShake {
+    /// Shakes at least `amplitude` pixels. Kicks during a shake don't add up, so that a flood of
+    /// errors doesn't shake the screen to pieces.
+    pub fn kick(&mut self, amplitude: f32) {
+        self.amplitude = self.amplitude.max(amplitude);
+    }
+
+    /// Calms the shake down by `elapsed` worth
+    pub fn settle(&mut self, elapsed: Duration, decay_secs: f32) {
+        self.amplitude *= (-elapsed.as_secs_f32() / decay_secs.max(f32::EPSILON)).exp();
+        if self.amplitude < SETTLED {
+            self.amplitude = 0.0;
+        }
+    }
+
+    pub fn is_shaking(&self) -> bool {
+        self.amplitude > 0.0
+    }
+
+    /// Moves the frame in `pixels`, `width` wide, by a random offset. Returns whether it moved.
+    pub fn apply(&mut self, pixels: &mut [Rgb565], width: usize, rng: &mut impl Rng) -> bool {
+        let offset = intensify(rng, Point::zero(), self.amplitude.round() as i32);
+        if offset == Point::zero() {
+            return false;
+        }
+        self.scratch.clear();
+        self.scratch.extend_from_slice(pixels);
+        shift(&self.scratch, pixels, width, offset);
+        true
+    }
+}
+
+/// Copies `from` to `to` moved by `offset`, with the edge pixels repeated into the gap it leaves
+fn shift(from: &[Rgb565], to: &mut [Rgb565], width: usize, offset: Point) {
+    if width == 0 {
+        return;
+    }
+    let height = from.len() / width;
+    let source =
+        |at: usize, by: i32, len: usize| (at as i32 - by).clamp(0, len as i32 - 1) as usize;
+    for (y, row) in to.chunks_exact_mut(width).enumerate() {
+        let from_row = &from[source(y, offset.y, height) * width..][..width];
+        for (x, pixel) in row.iter_mut().enumerate() {
+            *pixel = from_row[source(x, offset.x, width)];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_graphics::pixelcolor::RgbColor;
+
+    use super::*;
+
+    #[test]
+    fn shifts_with_edges_repeated() {
+        let (r, g, b, w) = (Rgb565::RED, Rgb565::GREEN, Rgb565::BLUE, Rgb565::WHITE);
+        #[rustfmt::skip]
+        let frame = [
+            r, g,
+            b, w,
+        ];
+        let mut shifted = [Rgb565::BLACK; 4];
+        shift(&frame, &mut shifted, 2, Point::new(1, 0));
+        assert_eq!(shifted, [r, r, b, b]);
+        shift(&frame, &mut shifted, 2, Point::new(0, -1));
+        assert_eq!(shifted, [b, w, b, w]);
+        shift(&frame, &mut shifted, 2, Point::zero());
+        assert_eq!(shifted, frame);
+    }
+
+    #[test]
+    fn calms_down() {
+        let mut shake = ScreenShake::default();
+        shake.kick(4.0);
+        shake.kick(1.0);
+        assert_eq!(shake.amplitude, 4.0);
+        shake.settle(Duration::from_millis(300), 0.3);
+        assert!((shake.amplitude - 4.0 / std::f32::consts::E).abs() < 0.01);
+        shake.settle(Duration::from_secs(1), 0.3);
+        assert!(!shake.is_shaking());
+    }
+}
@@ -0,0 +1,113 @@
+use std::{
+    path::PathBuf,
+    sync::atomic::{AtomicBool, Ordering},
+};
+#[cfg(target_os = "espidf")]
+use std::{
+    sync::{Condvar, Mutex},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use embedded_graphics::{
+    geometry::Size,
+    pixelcolor::{Rgb565, Rgb888},
+    prelude::RgbColor,
+};
+
+#[cfg(target_os = "espidf")]
+struct Frame {
+    size: Size,
+    pixels: Vec<Rgb565>,
+}
+
+// Frames are only copied when someone asks for them, not to slow down every frame
+static DUMP_REQUESTED: AtomicBool = AtomicBool::new(false);
+#[cfg(target_os = "espidf")]
+static CAPTURE_REQUESTED: AtomicBool = AtomicBool::new(false);
+#[cfg(target_os = "espidf")]
+static CAPTURED: Mutex<Option<Frame>> = Mutex::new(None);
+#[cfg(target_os = "espidf")]
+static CAPTURED_CHANGED: Condvar = Condvar::new();
+
+/// Taken from `--dump-frame <path>`
+fn dump_frame_path() -> Option<PathBuf> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--dump-frame" {
+            return args.next().map(PathBuf::from);
+        }
+    }
+    None
+}
+
+pub fn encode_png(size: Size, pixels: &[Rgb565]) -> Result<Vec<u8>> {
+    let rgb: Vec<u8> = pixels
+        .iter()
+        .flat_map(|&pixel| {
+            let pixel = Rgb888::from(pixel);
+            [pixel.r(), pixel.g(), pixel.b()]
+        })
+        .collect();
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, size.width, size.height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder
+        .write_header()
+        .context("writing PNG header failed")?;
+    writer
+        .write_image_data(&rgb)
+        .context("writing PNG data failed")?;
+    writer.finish().context("finishing PNG failed")?;
+    Ok(png)
+}
+
+/// Called with every frame sent to the LCD
+pub fn offer(size: Size, pixels: &[Rgb565]) {
+    if DUMP_REQUESTED.swap(false, Ordering::Relaxed) {
+        if let Err(e) = dump(size, pixels) {
+            log::error!("{e:?}");
+        }
+    }
+    #[cfg(target_os = "espidf")]
+    if CAPTURE_REQUESTED.swap(false, Ordering::Relaxed) {
+        *CAPTURED.lock().unwrap() = Some(Frame {
+            size,
+            pixels: pixels.to_vec(),
+        });
+        CAPTURED_CHANGED.notify_all();
+    }
+}
+
+/// Saves the next frame to the `--dump-frame` path
+pub fn request_dump() {
+    DUMP_REQUESTED.store(true, Ordering::Relaxed);
+}
+
+fn dump(size: Size, pixels: &[Rgb565]) -> Result<()> {
+    let Some(path) = dump_frame_path() else {
+        anyhow::bail!("no --dump-frame <path> given, not saving the frame");
+    };
+    std::fs::write(&path, encode_png(size, pixels)?)
+        .with_context(|| format!("cannot write {}", path.display()))?;
+    log::info!("frame saved to {}", path.display());
+    Ok(())
+}
+
+/// Waits for the next frame and returns it as PNG. Can be called from any thread but the one
+/// drawing the frames.
+#[cfg(target_os = "espidf")]
+pub fn capture_png(timeout: Duration) -> Result<Vec<u8>> {
+    let mut captured = CAPTURED.lock().unwrap();
+    *captured = None;
+    CAPTURE_REQUESTED.store(true, Ordering::Relaxed);
+    let (mut captured, _) = CAPTURED_CHANGED
+        .wait_timeout_while(captured, timeout, |captured| captured.is_none())
+        .unwrap();
+    let frame = captured
+        .take()
+        .context("no frame drawn in time, is the android asleep?")?;
+    drop(captured);
+    encode_png(frame.size, &frame.pixels)
+}
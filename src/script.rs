@@ -0,0 +1,259 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc, time::Duration};
+
+use anyhow::{anyhow, bail, Context, Result};
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::{Dimensions, Point},
+    mono_font::{iso_8859_1::FONT_6X10, MonoTextStyle},
+    pixelcolor::Rgb565,
+    primitives::Rectangle,
+    text::{Alignment, Baseline, Text, TextStyleBuilder},
+    Drawable,
+};
+use rhai::{Engine, Scope, AST, FLOAT, INT};
+
+use crate::{
+    assets::Image,
+    intensity::Intensity,
+    layout,
+    platform::Storage,
+    scene::{self, Canvas, Scene, SceneContext, GLITCH_LINE_PROBABILITY},
+};
+
+// Keeps a runaway script from starving the watchdog
+const MAX_OPERATIONS_PER_FRAME: u64 = 50_000;
+const MAX_CALL_LEVELS: usize = 16;
+const MAX_STRING_SIZE: usize = 1024;
+
+/// Drawing requested by the script, done once it returns from `frame`
+enum Command {
+    Clear(Rgb565),
+    /// Centered if there's no position
+    Text {
+        text: String,
+        pos: Option<Point>,
+        color: Rgb565,
+    },
+    /// Centered if there's no position
+    Image {
+        path: String,
+        pos: Option<Point>,
+    },
+    Shake(i32),
+    Glitch(usize),
+    Noise(f32),
+    Leds([f32; 2]),
+}
+
+/// Scene script, compiled. See README for what it can do.
+pub struct Script {
+    ast: AST,
+}
+
+impl Script {
+    pub fn compile(source: &[u8]) -> Result<Self> {
+        let source = std::str::from_utf8(source).context("not valid UTF-8")?;
+        let ast = new_engine()
+            .compile(source)
+            .map_err(|e| anyhow!("compilation failed: {e}"))?;
+        if !ast
+            .iter_functions()
+            .any(|f| f.name == "frame" && f.params.len() == 1)
+        {
+            bail!("no fn frame(t) defined");
+        }
+        Ok(Self { ast })
+    }
+
+    /// Each call plays the script from the start
+    pub fn start(&self) -> ScriptScene<'_> {
+        let commands = Rc::new(RefCell::new(Vec::new()));
+        let mut engine = new_engine();
+        register_bindings(&mut engine, &commands);
+        ScriptScene {
+            ast: &self.ast,
+            engine,
+            commands,
+            images: HashMap::new(),
+        }
+    }
+}
+
+pub struct ScriptScene<'a> {
+    ast: &'a AST,
+    engine: Engine,
+    commands: Rc<RefCell<Vec<Command>>>,
+    /// None for images that failed to load, not to retry every frame
+    images: HashMap<String, Option<Image>>,
+}
+
+impl Scene for ScriptScene<'_> {
+    fn name(&self) -> &'static str {
+        "script"
+    }
+
+    fn draw(&mut self, t: Duration, canvas: &mut Canvas, ctx: &mut SceneContext) -> Result<bool> {
+        let go_on = self
+            .engine
+            .call_fn::<bool>(
+                &mut Scope::new(),
+                self.ast,
+                "frame",
+                (t.as_secs_f32() as FLOAT,),
+            )
+            .map_err(|e| anyhow!("fn frame failed: {e}"))?;
+        // Whatever got drawn before returning false is dropped, so that the last frame shown is
+        // a complete one
+        let commands = std::mem::take(&mut *self.commands.borrow_mut());
+        if !go_on {
+            return Ok(false);
+        }
+
+        let center = canvas.bounding_box().center();
+        let mut shake = 0;
+        for command in commands {
+            match command {
+                Command::Clear(color) => canvas.clear(color)?,
+                Command::Text { text, pos, color } => {
+                    let style = MonoTextStyle::new(&FONT_6X10, color);
+                    let text = match pos {
+                        Some(pos) => Text::with_text_style(
+                            &text,
+                            crate::intensify(ctx.rng, pos, shake),
+                            style,
+                            TextStyleBuilder::new()
+                                .alignment(Alignment::Left)
+                                .baseline(Baseline::Top)
+                                .build(),
+                        ),
+                        None => Text::with_alignment(
+                            &text,
+                            crate::intensify(
+                                ctx.rng,
+                                layout::text_block(center, text.lines().count(), &style),
+                                shake,
+                            ),
+                            style,
+                            Alignment::Center,
+                        ),
+                    };
+                    text.draw(canvas)?;
+                }
+                Command::Image { path, pos } => {
+                    let image = self
+                        .images
+                        .entry(path)
+                        .or_insert_with_key(|path| load_image(ctx.storage, path));
+                    if let Some(image) = image {
+                        let pos = pos.unwrap_or_else(|| {
+                            center - Rectangle::new(Point::zero(), image.size()).center()
+                        });
+                        image
+                            .at(crate::intensify(ctx.rng, pos, shake))?
+                            .draw(canvas)?;
+                    }
+                }
+                Command::Shake(amplitude) => shake = amplitude.max(0),
+                Command::Glitch(max_offset) => {
+                    crate::glitch(canvas, ctx.rng, max_offset, GLITCH_LINE_PROBABILITY)
+                }
+                Command::Noise(fraction) => {
+                    crate::add_noise(canvas, ctx.rng, Intensity::new(fraction))
+                }
+                Command::Leds(brightness) => ctx.leds = brightness,
+            }
+        }
+        Ok(true)
+    }
+}
+
+fn new_engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS_PER_FRAME);
+    engine.set_max_call_levels(MAX_CALL_LEVELS);
+    engine.set_max_string_size(MAX_STRING_SIZE);
+    engine.on_print(|s| log::info!("script: {s}"));
+    engine.on_debug(|s, _, pos| log::debug!("script {pos}: {s}"));
+    engine
+}
+
+fn color(rgb: INT) -> Rgb565 {
+    scene::color_from_hex(rgb as u32)
+}
+
+fn position(x: INT, y: INT) -> Option<Point> {
+    Some(Point::new(x as i32, y as i32))
+}
+
+fn register_bindings(engine: &mut Engine, commands: &Rc<RefCell<Vec<Command>>>) {
+    let push = {
+        let commands = Rc::clone(commands);
+        move |command: Command| commands.borrow_mut().push(command)
+    };
+
+    let p = push.clone();
+    engine.register_fn("clear", move |rgb: INT| p(Command::Clear(color(rgb))));
+    let p = push.clone();
+    engine.register_fn("text", move |text: &str, rgb: INT| {
+        p(Command::Text {
+            text: text.to_owned(),
+            pos: None,
+            color: color(rgb),
+        })
+    });
+    let p = push.clone();
+    engine.register_fn("text", move |text: &str, x: INT, y: INT, rgb: INT| {
+        p(Command::Text {
+            text: text.to_owned(),
+            pos: position(x, y),
+            color: color(rgb),
+        })
+    });
+    let p = push.clone();
+    engine.register_fn("image", move |path: &str| {
+        p(Command::Image {
+            path: path.to_owned(),
+            pos: None,
+        })
+    });
+    let p = push.clone();
+    engine.register_fn("image", move |path: &str, x: INT, y: INT| {
+        p(Command::Image {
+            path: path.to_owned(),
+            pos: position(x, y),
+        })
+    });
+    let p = push.clone();
+    engine.register_fn("shake", move |amplitude: INT| {
+        p(Command::Shake(amplitude as i32))
+    });
+    let p = push.clone();
+    engine.register_fn("glitch", move |max_offset: INT| {
+        p(Command::Glitch(max_offset.max(0) as usize))
+    });
+    let p = push.clone();
+    engine.register_fn("noise", move |fraction: FLOAT| p(Command::Noise(fraction)));
+    let p = push.clone();
+    engine.register_fn("leds", move |brightness: FLOAT| {
+        p(Command::Leds([brightness; 2]))
+    });
+    engine.register_fn("leds", move |led0: FLOAT, led1: FLOAT| {
+        push(Command::Leds([led0, led1]))
+    });
+}
+
+fn load_image(storage: &mut dyn Storage, path: &str) -> Option<Image> {
+    match storage.read(path) {
+        Ok(Some(data)) => Image::from_png(&data)
+            .inspect_err(|e| log::error!("invalid image {path}: {e:?}"))
+            .ok(),
+        Ok(None) => {
+            log::error!("image {path} not found");
+            None
+        }
+        Err(e) => {
+            log::error!("reading {path} failed: {e:?}");
+            None
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use std::{
+    io::{Read, Write},
+    sync::{
+        mpsc::{self, Receiver, SyncSender, TryRecvError},
+        Mutex,
+    },
+};
+
+use anyhow::{Context, Result};
+use embedded_graphics::{
+    geometry::Size,
+    pixelcolor::{raw::RawU16, Rgb565},
+    prelude::RawData,
+};
+
+use crate::{
+    frame_codec::{Decoder, Encoder, Header, HEADER_SIZE},
+    mirror,
+};
+
+struct Frame {
+    size: Size,
+    pixels: Vec<Rgb565>,
+}
+
+/// Frames go back and forth between the renderer and the stream thread, so that none get
+/// allocated while streaming
+struct Stream {
+    frames: SyncSender<Frame>,
+    /// Sent, to be filled again
+    spent: Receiver<Frame>,
+}
+
+/// One being written out, one waiting for its turn
+const FRAMES_IN_FLIGHT: usize = 2;
+
+// Set while frames are streamed to stdout, from the console thread
+static STREAM: Mutex<Option<Stream>> = Mutex::new(None);
+
+/// Reads the rest of a packet whose first byte is next in `input`, into `payload`
+pub fn read_packet(input: &mut impl Read, payload: &mut Vec<u8>) -> Result<Header> {
+    let mut header = [0; HEADER_SIZE];
+    input.read_exact(&mut header)?;
+    let header = Header::parse(&header)?;
+    payload.resize(header.payload_len, 0);
+    input.read_exact(payload)?;
+    Ok(header)
+}
+
+/// Reads a packet pushed by the host from `input` and shows the frame like a screen mirror
+pub fn receive(input: &mut impl Read, decoder: &mut Decoder) -> Result<()> {
+    let mut payload = Vec::new();
+    let header = read_packet(input, &mut payload)?;
+    decoder.decode(&header, &payload)?;
+    let Some((size, pixels)) = decoder.frame() else {
+        return Ok(());
+    };
+    let mut bytes: Vec<u8> = pixels
+        .flat_map(|pixel| RawU16::from(pixel).into_inner().to_be_bytes())
+        .collect();
+    mirror::set_frame(size, &mut bytes);
+    Ok(())
+}
+
+/// Starts or stops streaming every frame drawn to stdout, in `frame_codec` packets
+pub fn set_streaming(enabled: bool) -> Result<()> {
+    let mut stream = STREAM.lock().unwrap();
+    if !enabled {
+        // Ends the thread
+        if stream.take().is_some() {
+            log::info!("stopped streaming frames");
+        }
+        return Ok(());
+    }
+    if stream.is_some() {
+        return Ok(());
+    }
+    // The rest are dropped while the port is busy
+    let (sender, receiver) = mpsc::sync_channel::<Frame>(FRAMES_IN_FLIGHT);
+    let (spent_sender, spent) = mpsc::sync_channel(FRAMES_IN_FLIGHT);
+    for _ in 0..FRAMES_IN_FLIGHT {
+        spent_sender.send(Frame {
+            size: Size::zero(),
+            pixels: Vec::new(),
+        })?;
+    }
+    std::thread::Builder::new()
+        .name("frame stream".to_owned())
+        .stack_size(8192)
+        .spawn(move || {
+            let mut encoder = Encoder::default();
+            let mut packet = Vec::new();
+            for frame in receiver {
+                packet.clear();
+                encoder.encode(frame.size, &frame.pixels, &mut packet);
+                let mut stdout = std::io::stdout().lock();
+                if let Err(e) = stdout.write_all(&packet).and_then(|()| stdout.flush()) {
+                    log::error!("cannot stream frames: {e}");
+                    return;
+                }
+                if spent_sender.send(frame).is_err() {
+                    return;
+                }
+            }
+        })
+        .context("cannot spawn frame stream thread")?;
+    *stream = Some(Stream {
+        frames: sender,
+        spent,
+    });
+    log::info!("streaming frames to stdout");
+    Ok(())
+}
+
+pub fn is_streaming() -> bool {
+    STREAM.lock().unwrap().is_some()
+}
+
+/// Called with every frame sent to the LCD
+pub fn offer(size: Size, pixels: &[Rgb565]) {
+    let mut guard = STREAM.lock().unwrap();
+    let Some(stream) = guard.as_ref() else {
+        return;
+    };
+    let mut frame = match stream.spent.try_recv() {
+        Ok(frame) => frame,
+        // Both still on their way out, this one's dropped
+        Err(TryRecvError::Empty) => return,
+        // The thread gave up, and said why
+        Err(TryRecvError::Disconnected) => {
+            *guard = None;
+            return;
+        }
+    };
+    frame.size = size;
+    frame.pixels.clear();
+    frame.pixels.extend_from_slice(pixels);
+    if stream.frames.try_send(frame).is_err() {
+        *guard = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn receives_pushed_frames() {
+        let size = Size::new(2, 2);
+        let mut encoder = Encoder::default();
+        let mut stream = Vec::new();
+        encoder.encode(size, &[Rgb565::new(31, 0, 0); 4], &mut stream);
+        encoder.encode(size, &[Rgb565::new(0, 63, 0); 4], &mut stream);
+        let mut input = &stream[..];
+        let mut decoder = Decoder::default();
+        let mut payload = Vec::new();
+        let header = read_packet(&mut input, &mut payload).unwrap();
+        decoder.decode(&header, &payload).unwrap();
+        receive(&mut input, &mut decoder).unwrap();
+        assert!(input.is_empty());
+        assert!(mirror::is_live());
+        let (_, mut pixels) = decoder.frame().unwrap();
+        assert!(pixels.all(|p| p == Rgb565::new(0, 63, 0)));
+    }
+}
@@ -0,0 +1,213 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{Read, Write},
+    path::PathBuf,
+    process::Command,
+};
+
+use anyhow::{bail, Context, Result};
+use embedded_graphics::{
+    geometry::Size,
+    pixelcolor::{raw::RawU16, Rgb565},
+    prelude::RawData,
+};
+
+use crate::frame_codec::{Chunk, Decoder, Encoder, Header, Splitter};
+
+const DEFAULT_BAUD: u32 = 115200;
+const DEFAULT_SIZE: Size = Size::new(160, 128);
+
+enum Direction {
+    /// Raw frames from stdin go to the android
+    Send,
+    /// Frames streamed by the android go to stdout
+    Receive,
+    /// Just the log goes to stdout, stdin goes to the console
+    Monitor,
+}
+
+struct Options {
+    direction: Direction,
+    port: PathBuf,
+    baud: u32,
+    /// Of frames read from stdin
+    size: Size,
+}
+
+impl Options {
+    /// Taken from `--serial-send <port>`, `--serial-receive <port>` or `--serial-monitor <port>`,
+    /// with `[--baud <n>]` and `[--size <w>x<h>]`
+    fn from_args() -> Result<Self> {
+        let mut direction = None;
+        let mut baud = DEFAULT_BAUD;
+        let mut size = DEFAULT_SIZE;
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().with_context(|| format!("{arg} needs a value"));
+            match arg.as_str() {
+                "--serial-send" => direction = Some((Direction::Send, value()?)),
+                "--serial-receive" => direction = Some((Direction::Receive, value()?)),
+                "--serial-monitor" => direction = Some((Direction::Monitor, value()?)),
+                "--baud" => baud = value()?.parse().context("invalid --baud")?,
+                "--size" => {
+                    let value = value()?;
+                    size = value
+                        .split_once('x')
+                        .and_then(|(w, h)| Some(Size::new(w.parse().ok()?, h.parse().ok()?)))
+                        .filter(|s| s.width > 0 && s.height > 0)
+                        .with_context(|| format!("invalid --size: {value}"))?;
+                }
+                _ => {}
+            }
+        }
+        let Some((direction, port)) = direction else {
+            bail!("none of --serial-send, --serial-receive or --serial-monitor given");
+        };
+        Ok(Self {
+            direction,
+            port: port.into(),
+            baud,
+            size,
+        })
+    }
+}
+
+pub fn requested() -> bool {
+    std::env::args()
+        .any(|arg| arg == "--serial-send" || arg == "--serial-receive" || arg == "--serial-monitor")
+}
+
+/// Opens the serial port raw, so that no byte of a frame gets mangled on the way
+fn open_port(options: &Options) -> Result<File> {
+    let status = Command::new("stty")
+        .arg("-F")
+        .arg(&options.port)
+        .args(["raw", "-echo", &options.baud.to_string()])
+        .status()
+        .context("cannot run stty")?;
+    if !status.success() {
+        bail!("stty failed to set up {}: {status}", options.port.display());
+    }
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&options.port)
+        .with_context(|| format!("cannot open {}", options.port.display()))
+}
+
+/// Pushes raw big-endian Rgb565 frames from stdin, e.g. from ffmpeg, to the android
+fn send(port: &mut File, size: Size) -> Result<()> {
+    // Whatever's playing ends, and the pushed frames show up right away
+    port.write_all(b"next\n")?;
+    let mut stdin = std::io::stdin().lock();
+    let mut raw = vec![0; (size.width * size.height * 2) as usize];
+    let mut encoder = Encoder::default();
+    let mut packet = Vec::new();
+    let mut frames = 0u64;
+    loop {
+        if let Err(e) = stdin.read_exact(&mut raw) {
+            log::info!("{frames} frames sent");
+            return match e.kind() {
+                std::io::ErrorKind::UnexpectedEof => Ok(()),
+                _ => Err(e.into()),
+            };
+        }
+        let pixels: Vec<Rgb565> = raw
+            .chunks_exact(2)
+            .map(|word| RawU16::new(u16::from_be_bytes([word[0], word[1]])).into())
+            .collect();
+        packet.clear();
+        encoder.encode(size, &pixels, &mut packet);
+        port.write_all(&packet)
+            .context("cannot write to the serial port")?;
+        frames += 1;
+    }
+}
+
+/// Splits what the android sends into text, copied to `text`, and frame packets handed to
+/// `on_packet`. Returns once the port closes.
+fn split_stream(
+    mut port: &File,
+    text: &mut impl Write,
+    mut on_packet: impl FnMut(Header, &[u8]) -> Result<()>,
+) -> Result<()> {
+    let mut splitter = Splitter::default();
+    let mut buf = [0; 4096];
+    loop {
+        let len = port.read(&mut buf)?;
+        if len == 0 {
+            return Ok(());
+        }
+        let mut result = Ok(());
+        splitter.push(&buf[..len], |chunk| {
+            if result.is_ok() {
+                result = match chunk {
+                    Chunk::Text(bytes) => text.write_all(bytes).map_err(Into::into),
+                    Chunk::Packet(header, payload) => on_packet(header, payload),
+                };
+            }
+        });
+        result?;
+        text.flush()?;
+    }
+}
+
+/// Turns on streaming and writes frames to stdout as raw big-endian Rgb565, with everything
+/// else the android prints going to stderr
+fn receive(port: &mut File) -> Result<()> {
+    port.write_all(b"stream on\n")?;
+    let mut stdout = std::io::stdout().lock();
+    let mut decoder = Decoder::default();
+    let mut size = None;
+    split_stream(port, &mut std::io::stderr(), |header, payload| {
+        if let Err(e) = decoder.decode(&header, payload) {
+            log::warn!("{e:#}");
+            return Ok(());
+        }
+        let Some((frame_size, pixels)) = decoder.frame() else {
+            return Ok(());
+        };
+        // Raw video has no way of saying the size changed
+        if size.is_some_and(|size| size != frame_size) {
+            bail!("frame size changed to {frame_size:?}");
+        }
+        if size.is_none() {
+            log::info!(
+                "receiving {}x{} frames",
+                frame_size.width,
+                frame_size.height
+            );
+            size = Some(frame_size);
+        }
+        for pixel in pixels {
+            stdout.write_all(&RawU16::from(pixel).into_inner().to_be_bytes())?;
+        }
+        stdout.flush()?;
+        Ok(())
+    })
+}
+
+/// Prints the android's log, leaving out frames if streaming was left on, and passes lines
+/// typed on stdin on to its console
+fn monitor(port: &mut File) -> Result<()> {
+    let mut console = port.try_clone()?;
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lines().map_while(Result::ok) {
+            if console.write_all(format!("{line}\n").as_bytes()).is_err() {
+                break;
+            }
+        }
+    });
+    split_stream(port, &mut std::io::stdout(), |_, _| Ok(()))
+}
+
+/// Pushes frames to, receives frames from, or shows the log of an android's serial console
+pub fn run() -> Result<()> {
+    let options = Options::from_args()?;
+    let mut port = open_port(&options)?;
+    match options.direction {
+        Direction::Send => send(&mut port, options.size),
+        Direction::Receive => receive(&mut port),
+        Direction::Monitor => monitor(&mut port),
+    }
+}
@@ -0,0 +1,455 @@
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap},
+    hash::{Hash, Hasher},
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Result};
+use embedded_graphics::{
+    geometry::{Point, Size},
+    primitives::Rectangle,
+};
+use rand::rngs::StdRng;
+use serde::Deserialize;
+
+use crate::{
+    effects::{glitch_rows, intensify},
+    region::{self, Region},
+    scene::{Canvas, GLITCH_LINE_PROBABILITY},
+};
+
+/// Slot in the display list. Everything in it is drawn in `z` order, back to front.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layer {
+    pub name: &'static str,
+    pub z: i32,
+}
+
+impl Layer {
+    const fn new(name: &'static str, z: i32) -> Self {
+        Self { name, z }
+    }
+}
+
+// Layers of the escalation and the noise ending, in the default order
+pub const BACKGROUND: Layer = Layer::new("background", 0);
+/// Timer, message and temperature
+pub const TEXT: Layer = Layer::new("text", 10);
+pub const FIRE: Layer = Layer::new("fire", 20);
+/// Glitches everything below it
+pub const GLITCH: Layer = Layer::new("glitch", 30);
+/// Noise ending, or noise puppeted over OSC
+pub const NOISE: Layer = Layer::new("noise", 40);
+pub const GHOSTING: Layer = Layer::new("ghosting", 50);
+/// Moves everything below it around, on glitch bursts and errors
+pub const SHAKE: Layer = Layer::new("shake", 55);
+pub const BATTERY: Layer = Layer::new("battery", 60);
+pub const LOG: Layer = Layer::new("log", 70);
+pub const DIAGNOSTICS: Layer = Layer::new("diagnostics", 80);
+pub const ALL: &[Layer] = &[
+    BACKGROUND,
+    TEXT,
+    FIRE,
+    GLITCH,
+    NOISE,
+    GHOSTING,
+    SHAKE,
+    BATTERY,
+    LOG,
+    DIAGNOSTICS,
+];
+
+/// `[layers.<name>]` in config.toml, changes a layer of the display list
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Override {
+    /// Moves it in front of, or behind, other layers
+    pub z: Option<i32>,
+    #[serde(default)]
+    pub hidden: bool,
+    /// Random offset in pixels, on top of any the layer has already
+    #[serde(default)]
+    pub shake: u32,
+    /// Max offset of glitched lines in pixels, within the area the layer drew on
+    #[serde(default)]
+    pub glitch: usize,
+}
+
+pub type Overrides = BTreeMap<String, Override>;
+
+pub fn validate(overrides: &Overrides) -> Result<()> {
+    for name in overrides.keys() {
+        if !ALL.iter().any(|layer| layer.name == name) {
+            bail!("unknown layer: {name}");
+        }
+    }
+    Ok(())
+}
+
+/// What sprites get besides the canvas
+pub struct DrawContext<'a> {
+    pub rng: &'a mut StdRng,
+    /// Where to draw, relative to where the sprite would be without any shake
+    pub offset: Point,
+    drawn: &'a [(Layer, Rectangle)],
+}
+
+impl DrawContext<'_> {
+    /// What sprites on a layer below drew on this frame, None if it's hidden or in front
+    pub fn area(&self, layer: Layer) -> Option<Rectangle> {
+        self.drawn
+            .iter()
+            .filter(|(drawn, _)| *drawn == layer)
+            .map(|(_, area)| *area)
+            .reduce(region::envelope)
+    }
+}
+
+/// For `Sprite::key`, from anything that decides what a sprite looks like
+pub fn key(value: impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+type DrawFn<'a> = Box<dyn FnMut(&mut Canvas, &mut DrawContext) -> Result<Rectangle> + 'a>;
+
+/// Image, text, widget or effect on its own layer. Effects change what the layers below drew,
+/// instead of drawing over it.
+pub struct Sprite<'a> {
+    layer: Layer,
+    /// `layer.z`, unless overridden
+    z: i32,
+    visible: bool,
+    /// Random offset in pixels, a new one every frame
+    shake: u32,
+    /// Max offset of glitched lines, within the area the sprite drew on
+    glitch: usize,
+    /// None if it may look different every time
+    key: Option<u64>,
+    draw: DrawFn<'a>,
+}
+
+impl<'a> Sprite<'a> {
+    /// `draw` returns the area it drew on, for effects limited to it
+    pub fn new(
+        layer: Layer,
+        draw: impl FnMut(&mut Canvas, &mut DrawContext) -> Result<Rectangle> + 'a,
+    ) -> Self {
+        Self {
+            layer,
+            z: layer.z,
+            visible: true,
+            shake: 0,
+            glitch: 0,
+            key: None,
+            draw: Box::new(draw),
+        }
+    }
+
+    pub fn visible(self, visible: bool) -> Self {
+        Self { visible, ..self }
+    }
+
+    /// Promises the sprite draws the same pixels as on the last frame if `key` and its area are
+    /// the same, so that they don't need flushing again. Sprites without a key are flushed
+    /// whenever they draw anything.
+    pub fn key(self, key: u64) -> Self {
+        Self {
+            key: Some(key),
+            ..self
+        }
+    }
+}
+
+/// Time spent drawing each layer
+pub struct Timings(Vec<(Layer, Duration)>);
+
+impl Timings {
+    pub fn of(&self, layers: &[Layer]) -> Duration {
+        self.0
+            .iter()
+            .filter(|(layer, _)| layers.contains(layer))
+            .map(|(_, took)| *took)
+            .sum()
+    }
+}
+
+/// What sprites drew on the last frames, for flushing only the part of the screen that changed
+#[derive(Default)]
+pub struct Damage {
+    /// Key and area of each layer drawn on the last frame
+    last: Vec<(Layer, Option<u64>, Rectangle)>,
+    /// Changed since the last flush, None if nothing did
+    dirty: Option<Rectangle>,
+    /// Something other than sprites changed the screen, e.g. another scene
+    everything: bool,
+}
+
+impl Damage {
+    /// The next flush covers the whole screen
+    pub fn invalidate(&mut self) {
+        self.everything = true;
+    }
+
+    fn add(&mut self, area: Rectangle) {
+        if area.is_zero_sized() {
+            return;
+        }
+        self.dirty = Some(match self.dirty {
+            Some(dirty) => region::envelope(dirty, area),
+            None => area,
+        });
+    }
+
+    /// Compares what was drawn on this frame with the last one
+    fn update(&mut self, drawn: Vec<(Layer, Option<u64>, Rectangle)>) {
+        type Drawn = [(Layer, Option<u64>, Rectangle)];
+        // Sprites are told apart by their layer, and the order they were pushed in
+        let nth_on_layer = |drawn: &Drawn, i: usize| {
+            let layer = drawn[i].0;
+            drawn[..i].iter().filter(|(l, ..)| *l == layer).count()
+        };
+        let find = |drawn: &Drawn, layer: Layer, n: usize| {
+            drawn.iter().filter(|(l, ..)| *l == layer).nth(n).copied()
+        };
+
+        let last = std::mem::replace(&mut self.last, drawn);
+        for i in 0..self.last.len() {
+            let (layer, key, area) = self.last[i];
+            match find(&last, layer, nth_on_layer(&self.last, i)) {
+                Some((_, last_key, last_area))
+                    if key.is_some() && key == last_key && area == last_area => {}
+                Some((.., last_area)) => {
+                    self.add(area);
+                    self.add(last_area);
+                }
+                None => self.add(area),
+            }
+        }
+        // Hidden since, whatever was under them shows now
+        for (i, &(layer, _, area)) in last.iter().enumerate() {
+            if find(&self.last, layer, nth_on_layer(&last, i)).is_none() {
+                self.add(area);
+            }
+        }
+    }
+
+    /// Part of `screen` that changed since the last call, to be flushed now. Zero sized if
+    /// nothing did.
+    pub fn take(&mut self, screen: Rectangle) -> Rectangle {
+        let dirty = match std::mem::take(&mut self.everything) {
+            true => Some(screen),
+            false => self.dirty,
+        };
+        self.dirty = None;
+        dirty.map_or(Rectangle::new(screen.top_left, Size::zero()), |dirty| {
+            dirty.intersection(&screen)
+        })
+    }
+}
+
+/// Sprites of a single frame, composited back to front
+pub struct DisplayList<'a> {
+    sprites: Vec<Sprite<'a>>,
+    overrides: &'a Overrides,
+}
+
+impl<'a> DisplayList<'a> {
+    pub fn new(overrides: &'a Overrides) -> Self {
+        Self {
+            sprites: Vec::new(),
+            overrides,
+        }
+    }
+
+    pub fn push(&mut self, sprite: Sprite<'a>) {
+        self.sprites.push(sprite);
+    }
+
+    /// Draws all visible sprites, in `z` order, and adds whatever changed since the last frame
+    /// to `damage`. Sprites on the same layer are drawn in the order they were pushed.
+    pub fn draw(
+        mut self,
+        canvas: &mut Canvas,
+        rng: &mut StdRng,
+        damage: &mut Damage,
+    ) -> Result<Timings> {
+        for sprite in &mut self.sprites {
+            let Some(o) = self.overrides.get(sprite.layer.name) else {
+                continue;
+            };
+            sprite.z = o.z.unwrap_or(sprite.z);
+            sprite.visible &= !o.hidden;
+            sprite.shake = sprite.shake.saturating_add(o.shake);
+            sprite.glitch += o.glitch;
+        }
+        self.sprites.sort_by_key(|sprite| sprite.z);
+
+        let mut drawn = Vec::new();
+        let mut timings = Vec::new();
+        for sprite in self.sprites.iter_mut().filter(|sprite| sprite.visible) {
+            let t = Instant::now();
+            let mut ctx = DrawContext {
+                offset: intensify(
+                    rng,
+                    Point::zero(),
+                    i32::try_from(sprite.shake).unwrap_or(i32::MAX),
+                ),
+                rng: &mut *rng,
+                drawn: &drawn,
+            };
+            let area = (sprite.draw)(canvas, &mut ctx)?;
+            glitch_rows(
+                canvas,
+                rng,
+                sprite.glitch,
+                GLITCH_LINE_PROBABILITY,
+                false,
+                &Region::Rect(area),
+            );
+            drawn.push((sprite.layer, area));
+            timings.push((sprite.layer, t.elapsed()));
+        }
+        damage.update(
+            self.sprites
+                .iter()
+                .filter(|sprite| sprite.visible)
+                .zip(drawn)
+                // Glitched lines are random
+                .map(|(sprite, (layer, area))| {
+                    (layer, sprite.key.filter(|_| sprite.glitch == 0), area)
+                })
+                .collect(),
+        );
+        Ok(Timings(timings))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_graphics::{
+        draw_target::DrawTarget,
+        geometry::{Dimensions, Size},
+        pixelcolor::Rgb565,
+        prelude::RgbColor,
+    };
+    use embedded_graphics_framebuf::FrameBuf;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::SliceFrameBufferBackend;
+
+    fn fill(layer: Layer, color: Rgb565) -> Sprite<'static> {
+        Sprite::new(layer, move |canvas, _ctx| {
+            canvas.clear(color)?;
+            Ok(canvas.bounding_box())
+        })
+    }
+
+    /// Color of the top left pixel once `list` is drawn
+    fn draw(list: DisplayList) -> Rgb565 {
+        let mut backend = SliceFrameBufferBackend::new(Size::new(4, 4), Rgb565::BLACK);
+        let mut canvas = FrameBuf::new(&mut backend, 4, 4);
+        list.draw(
+            &mut canvas,
+            &mut StdRng::seed_from_u64(0),
+            &mut Damage::default(),
+        )
+        .unwrap();
+        backend.pixels[0]
+    }
+
+    #[test]
+    fn draws_in_z_order() {
+        let overrides = Overrides::new();
+        let mut list = DisplayList::new(&overrides);
+        list.push(fill(FIRE, Rgb565::RED));
+        list.push(fill(BACKGROUND, Rgb565::BLUE));
+        assert_eq!(draw(list), Rgb565::RED);
+    }
+
+    #[test]
+    fn applies_overrides() {
+        let mut overrides = Overrides::new();
+        overrides.insert(
+            "fire".to_owned(),
+            Override {
+                z: Some(-1),
+                ..Default::default()
+            },
+        );
+        let mut list = DisplayList::new(&overrides);
+        list.push(fill(FIRE, Rgb565::RED));
+        list.push(fill(BACKGROUND, Rgb565::BLUE));
+        assert_eq!(draw(list), Rgb565::BLUE);
+
+        overrides.insert(
+            "background".to_owned(),
+            Override {
+                hidden: true,
+                ..Default::default()
+            },
+        );
+        let mut list = DisplayList::new(&overrides);
+        list.push(fill(BACKGROUND, Rgb565::BLUE));
+        assert_eq!(draw(list), Rgb565::BLACK);
+
+        assert!(validate(&overrides).is_ok());
+        overrides.insert("nope".to_owned(), Override::default());
+        assert!(validate(&overrides).is_err());
+        assert!(toml::from_str::<Override>("shake = -2").is_err());
+    }
+
+    #[test]
+    fn sees_what_layers_below_drew() {
+        let overrides = Overrides::new();
+        let mut list = DisplayList::new(&overrides);
+        let text = Rectangle::new(Point::new(1, 1), Size::new(2, 1));
+        let mut seen = None;
+        list.push(Sprite::new(GLITCH, |_canvas, ctx| {
+            seen = Some((ctx.area(TEXT), ctx.area(FIRE)));
+            Ok(text)
+        }));
+        list.push(Sprite::new(TEXT, move |_canvas, _ctx| Ok(text)));
+        list.push(fill(FIRE, Rgb565::RED).visible(false));
+        draw(list);
+        assert_eq!(seen, Some((Some(text), None)));
+    }
+
+    #[test]
+    fn flushes_only_what_changed() {
+        let screen = Rectangle::new(Point::zero(), Size::new(160, 128));
+        let timer = Rectangle::new(Point::new(50, 59), Size::new(60, 10));
+        let mut backend = SliceFrameBufferBackend::new(screen.size, Rgb565::BLACK);
+        let mut canvas = FrameBuf::new(&mut backend, 160, 128);
+        let mut rng = StdRng::seed_from_u64(0);
+        let overrides = Overrides::new();
+        let mut frame = |damage: &mut Damage, shown: &str, text_visible: bool| {
+            let mut list = DisplayList::new(&overrides);
+            list.push(fill(BACKGROUND, Rgb565::RED).key(key(Rgb565::RED)));
+            list.push(
+                Sprite::new(TEXT, move |_canvas, _ctx| Ok(timer))
+                    .key(key(shown))
+                    .visible(text_visible),
+            );
+            list.draw(&mut canvas, &mut rng, damage).unwrap();
+            damage.take(screen)
+        };
+
+        let mut damage = Damage::default();
+        assert_eq!(frame(&mut damage, "1m 2s", true), screen);
+        assert!(frame(&mut damage, "1m 2s", true).is_zero_sized());
+        let changed = frame(&mut damage, "1m 3s", true);
+        assert_eq!(changed, timer);
+        // Under 3% of the screen
+        assert!(
+            changed.size.width * changed.size.height * 100
+                < 3 * screen.size.width * screen.size.height
+        );
+        assert_eq!(frame(&mut damage, "1m 3s", false), timer);
+        assert!(frame(&mut damage, "1m 3s", false).is_zero_sized());
+        damage.invalidate();
+        assert_eq!(frame(&mut damage, "1m 3s", false), screen);
+    }
+}
@@ -0,0 +1,157 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    mono_font::{ascii::FONT_6X10, MonoTextStyle},
+    pixelcolor::Rgb565,
+    prelude::RgbColor,
+    text::{Alignment, Text},
+    Drawable,
+};
+
+use crate::{
+    duration_fmt::{self, Style},
+    layout,
+    platform::Platform,
+    version,
+};
+
+const STORAGE_KEY: &str = "stats";
+// Flash wears out, don't save too often
+const SAVE_INTERVAL: Duration = Duration::from_secs(300);
+/// Hides itself after that long, in case someone forgets about it
+pub const SCENE_DURATION: Duration = Duration::from_secs(15);
+// Bump when the serialized layout changes, old stats are discarded then
+const FORMAT_VERSION: u8 = 1;
+
+/// Counters kept across reboots
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    pub uptime: Duration,
+    /// Escalations that made it all the way to the noise ending
+    pub escalations: u64,
+    pub peak_glitchiness: u64,
+    pub frames: u64,
+}
+
+impl Stats {
+    const SERIALIZED_LEN: usize = 1 + 4 * 8;
+
+    fn serialize(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(Self::SERIALIZED_LEN);
+        data.push(FORMAT_VERSION);
+        for value in [
+            self.uptime.as_secs(),
+            self.escalations,
+            self.peak_glitchiness,
+            self.frames,
+        ] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data
+    }
+
+    fn deserialize(data: &[u8]) -> Option<Self> {
+        if data.len() != Self::SERIALIZED_LEN || data[0] != FORMAT_VERSION {
+            return None;
+        }
+        let mut values = data[1..]
+            .chunks_exact(8)
+            .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()));
+        Some(Self {
+            uptime: Duration::from_secs(values.next()?),
+            escalations: values.next()?,
+            peak_glitchiness: values.next()?,
+            frames: values.next()?,
+        })
+    }
+}
+
+/// Updates the counters as the android does its thing, saving them every now and then
+pub struct Tracker {
+    /// With uptime as of `session_start`
+    stats: Stats,
+    session_start: Instant,
+    last_save: Instant,
+}
+
+impl Tracker {
+    pub fn load(platform: &mut impl Platform) -> Self {
+        let stats = match platform.load(STORAGE_KEY) {
+            Ok(Some(data)) => Stats::deserialize(&data).unwrap_or_else(|| {
+                log::warn!("discarding stats saved in unknown format");
+                Stats::default()
+            }),
+            Ok(None) => Stats::default(),
+            Err(e) => {
+                log::error!("loading stats failed: {e:?}");
+                Stats::default()
+            }
+        };
+        log::info!("stats: {stats:?}");
+        Self {
+            stats,
+            session_start: Instant::now(),
+            last_save: Instant::now(),
+        }
+    }
+
+    pub fn current(&self) -> Stats {
+        Stats {
+            uptime: self.stats.uptime + self.session_start.elapsed(),
+            ..self.stats.clone()
+        }
+    }
+
+    pub fn on_frame(&mut self, glitchiness: usize) {
+        self.stats.frames += 1;
+        self.stats.peak_glitchiness = self.stats.peak_glitchiness.max(glitchiness as u64);
+    }
+
+    pub fn on_escalation_done(&mut self) {
+        self.stats.escalations += 1;
+    }
+
+    /// Saves if it's been a while since the last time
+    pub fn maybe_save(&mut self, platform: &mut impl Platform) {
+        if self.last_save.elapsed() >= SAVE_INTERVAL {
+            self.save(platform);
+        }
+    }
+
+    pub fn save(&mut self, platform: &mut impl Platform) {
+        self.last_save = Instant::now();
+        if let Err(e) = platform.store(STORAGE_KEY, &self.current().serialize()) {
+            log::error!("saving stats failed: {e:?}");
+        }
+    }
+}
+
+/// `throttled` is whether it's too hot for the full frame rate right now
+pub fn draw<D: DrawTarget<Color = Rgb565>>(
+    target: &mut D,
+    stats: &Stats,
+    throttled: bool,
+) -> Result<(), D::Error> {
+    target.clear(Rgb565::BLACK)?;
+    let text = format!(
+        "STATS\n\nuptime {}\nescalations {}\npeak glitchiness {}\nframes {}\nthermal {}\nv{}",
+        duration_fmt::format(stats.uptime, Style::Compact),
+        stats.escalations,
+        stats.peak_glitchiness,
+        stats.frames,
+        if throttled { "THROTTLED" } else { "ok" },
+        version::short(),
+    );
+    let style = MonoTextStyle::new(&FONT_6X10, Rgb565::WHITE);
+    let center = target.bounding_box().center();
+    Text::with_alignment(
+        &text,
+        layout::text_block(center, text.lines().count(), &style),
+        style,
+        Alignment::Center,
+    )
+    .draw(target)?;
+    Ok(())
+}
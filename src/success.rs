@@ -0,0 +1,104 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::Dimensions,
+    mono_font::{iso_8859_1::FONT_6X10, MonoTextStyle},
+    pixelcolor::Rgb565,
+    prelude::RgbColor,
+    text::{Alignment, Text},
+    Drawable,
+};
+
+use crate::{
+    duration_fmt::{self, Style},
+    layout,
+    scene::{Canvas, Scene, SceneContext, GLITCH_LINE_PROBABILITY},
+};
+
+const SUCCESS_DURATION: Duration = Duration::from_secs(10);
+/// How long it takes for the success to fall apart, for fake ones
+const FAILURE_DURATION: Duration = Duration::from_secs(2);
+const SUCCESS_BACKGROUND: Rgb565 = Rgb565::new(0, 24, 0);
+const MAX_FAILURE_GLITCH: f32 = 48.0;
+/// Of the celebratory LED pattern
+const LED_STEP: Duration = Duration::from_millis(150);
+
+/// The build completes, for once
+pub struct SuccessScene {
+    /// Build time to brag about
+    took: Duration,
+    /// Falls apart after a while
+    fake: bool,
+}
+
+impl SuccessScene {
+    pub fn real(took: Duration) -> Self {
+        Self { took, fake: false }
+    }
+
+    /// Too good to be true
+    pub fn fake(took: Duration) -> Self {
+        Self { took, fake: true }
+    }
+}
+
+/// Back and forth, then both at once, like fireworks
+fn celebrate(t: Duration) -> [f32; 2] {
+    match t.as_millis() / LED_STEP.as_millis() % 4 {
+        0 => [1.0, 0.0],
+        1 => [0.0, 1.0],
+        2 => [1.0, 1.0],
+        _ => [0.0, 0.0],
+    }
+}
+
+impl Scene for SuccessScene {
+    fn name(&self) -> &'static str {
+        "success"
+    }
+
+    fn draw(&mut self, t: Duration, canvas: &mut Canvas, ctx: &mut SceneContext) -> Result<bool> {
+        let center = canvas.bounding_box().center();
+        let style = MonoTextStyle::new(&FONT_6X10, Rgb565::WHITE);
+        if t < SUCCESS_DURATION {
+            canvas.clear(SUCCESS_BACKGROUND)?;
+            let took = duration_fmt::format(self.took, Style::Compact);
+            let text = if self.fake {
+                format!("#### build completed\nsuccessfully\n(took {took}) ####")
+            } else {
+                format!("BUILD SUCCESSFUL\n(took {took})")
+            };
+            let position = layout::text_block(center, text.lines().count(), &style);
+            Text::with_alignment(&text, position, style, Alignment::Center).draw(canvas)?;
+            ctx.leds = celebrate(t);
+            return Ok(true);
+        }
+        if !self.fake {
+            return Ok(false);
+        }
+
+        let t = t - SUCCESS_DURATION;
+        if t >= FAILURE_DURATION {
+            return Ok(false);
+        }
+        let progress = t.as_secs_f32() / FAILURE_DURATION.as_secs_f32();
+        canvas.clear(Rgb565::new((progress * 31.0) as u8, 0, 0))?;
+        Text::with_alignment(
+            "FAILED: out/soong/\nbuild.ninja\n\njust kidding",
+            crate::intensify(ctx.rng, center, (progress * 3.0) as i32),
+            style,
+            Alignment::Center,
+        )
+        .draw(canvas)?;
+        crate::glitch(
+            canvas,
+            ctx.rng,
+            (progress * MAX_FAILURE_GLITCH) as usize,
+            GLITCH_LINE_PROBABILITY,
+        );
+        ctx.leds = [1.0 - progress; 2];
+        Ok(true)
+    }
+}
@@ -0,0 +1,117 @@
+use std::time::{Duration, Instant};
+
+use crate::platform::Platform;
+
+// Sensors may block for a bit, and temperature doesn't change that fast anyway
+const CHECK_INTERVAL: Duration = Duration::from_secs(2);
+/// Real temperature above which glitches start getting worse
+const WARM_CELSIUS: f32 = 40.0;
+/// Real temperature at which glitches are worst
+const HOT_CELSIUS: f32 = 80.0;
+/// How much worse glitches get when hot. Just a bit, the act is the main thing.
+const MAX_GLITCH_BIAS: f32 = 0.25;
+/// What the simulated temperature starts at, if there's no real one
+const ROOM_CELSIUS: f32 = 25.0;
+/// How much the simulated temperature rises during the escalation
+const SIMULATED_RISE_CELSIUS: f32 = 70.0;
+/// Extra simulated heat at full rage
+const RAGE_CELSIUS: f32 = 15.0;
+/// Throttling stops only this far below where it started, so that it doesn't flap
+const THROTTLE_HYSTERESIS_CELSIUS: f32 = 5.0;
+/// Between frames, instead of the usual 10ms, to let the SoC cool down
+const THROTTLED_FRAME_DELAY: Duration = Duration::from_millis(50);
+const FRAME_DELAY: Duration = Duration::from_millis(10);
+
+/// Keeps the last temperature reported by the platform, checking every now and then, and
+/// throttles when it gets too hot
+#[derive(Default)]
+pub struct Monitor {
+    last_check: Option<Instant>,
+    celsius: Option<f32>,
+    throttled: bool,
+}
+
+impl Monitor {
+    /// Throttles above `throttle_celsius`, 0 never does
+    pub fn poll(&mut self, platform: &mut impl Platform, throttle_celsius: f32) {
+        if self
+            .last_check
+            .is_some_and(|t| t.elapsed() < CHECK_INTERVAL)
+        {
+            return;
+        }
+        self.last_check = Some(Instant::now());
+        self.celsius = platform.sensors().and_then(|s| s.temperature);
+        self.update_throttle(throttle_celsius);
+    }
+
+    fn update_throttle(&mut self, throttle_celsius: f32) {
+        let Some(celsius) = self.celsius else {
+            self.throttled = false;
+            return;
+        };
+        let throttled = match self.throttled {
+            _ if throttle_celsius <= 0.0 => false,
+            true => celsius > throttle_celsius - THROTTLE_HYSTERESIS_CELSIUS,
+            false => celsius >= throttle_celsius,
+        };
+        match (self.throttled, throttled) {
+            (false, true) => log::warn!("SoC at {celsius:.0}°C, throttling"),
+            (true, false) => log::info!("SoC at {celsius:.0}°C, no longer throttling"),
+            _ => {}
+        }
+        self.throttled = throttled;
+    }
+
+    /// Too hot for expensive effects and full frame rate
+    pub fn is_throttled(&self) -> bool {
+        self.throttled
+    }
+
+    /// Pause between frames, longer when throttled
+    pub fn frame_delay(&self) -> Duration {
+        match self.throttled {
+            true => THROTTLED_FRAME_DELAY,
+            false => FRAME_DELAY,
+        }
+    }
+
+    /// Multiplier for glitchiness, 1 unless it's warm
+    pub fn glitch_scale(&self) -> f32 {
+        let Some(celsius) = self.celsius else {
+            return 1.0;
+        };
+        let heat = ((celsius - WARM_CELSIUS) / (HOT_CELSIUS - WARM_CELSIUS)).clamp(0.0, 1.0);
+        1.0 + heat * MAX_GLITCH_BIAS
+    }
+
+    /// What gets shown on the LCD: the real temperature, heating up more and more as the build
+    /// drags on. `progress` and `rage` are 0..1.
+    pub fn simulated(&self, progress: f32, rage: f32) -> f32 {
+        let base = self.celsius.unwrap_or(ROOM_CELSIUS);
+        base + progress.powi(2) * SIMULATED_RISE_CELSIUS + rage * RAGE_CELSIUS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn throttles_with_hysteresis() {
+        let mut monitor = Monitor::default();
+        let mut at = |celsius| {
+            monitor.celsius = Some(celsius);
+            monitor.update_throttle(75.0);
+            monitor.is_throttled()
+        };
+        assert!(!at(74.0));
+        assert!(at(75.0));
+        assert!(at(71.0));
+        assert!(!at(70.0));
+        assert!(!at(74.0));
+        monitor.celsius = Some(90.0);
+        monitor.update_throttle(0.0);
+        assert!(!monitor.is_throttled());
+    }
+}
@@ -0,0 +1,91 @@
+//! Helpers for testing what gets drawn, on the host: an in-memory display that anything drawing
+//! on embedded-graphics targets can be pointed at, and checked pixel by pixel afterwards
+
+use alloc::{vec, vec::Vec};
+use core::convert::Infallible;
+
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::{Dimensions, Point, Size},
+    pixelcolor::{Rgb565, RgbColor},
+    primitives::Rectangle,
+    Pixel,
+};
+
+/// Display of any size, all black to start with. Pixels drawn outside it are dropped.
+pub struct MockLcd {
+    size: Size,
+    pixels: Vec<Rgb565>,
+}
+
+impl MockLcd {
+    pub fn new(size: Size) -> Self {
+        Self {
+            size,
+            pixels: vec![Rgb565::BLACK; (size.width * size.height) as usize],
+        }
+    }
+
+    /// Black outside the screen
+    pub fn pixel(&self, point: Point) -> Rgb565 {
+        match self.bounding_box().contains(point) {
+            true => self.pixels[point.y as usize * self.size.width as usize + point.x as usize],
+            false => Rgb565::BLACK,
+        }
+    }
+
+    /// Row by row
+    pub fn pixels(&self) -> &[Rgb565] {
+        &self.pixels
+    }
+}
+
+impl Dimensions for MockLcd {
+    fn bounding_box(&self) -> Rectangle {
+        Rectangle::new(Point::zero(), self.size)
+    }
+}
+
+impl DrawTarget for MockLcd {
+    type Color = Rgb565;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let bounds = self.bounding_box();
+        for Pixel(point, color) in pixels {
+            if bounds.contains(point) {
+                self.pixels[point.y as usize * self.size.width as usize + point.x as usize] = color;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_graphics::{
+        primitives::{Primitive, PrimitiveStyle},
+        Drawable,
+    };
+
+    use super::*;
+
+    #[test]
+    fn keeps_what_was_drawn() {
+        let mut lcd = MockLcd::new(Size::new(8, 4));
+        Rectangle::new(Point::new(6, 2), Size::new(4, 4))
+            .into_styled(PrimitiveStyle::with_fill(Rgb565::RED))
+            .draw(&mut lcd)
+            .unwrap();
+        assert_eq!(lcd.pixel(Point::new(7, 3)), Rgb565::RED);
+        assert_eq!(lcd.pixel(Point::new(5, 3)), Rgb565::BLACK);
+        assert_eq!(lcd.pixel(Point::new(8, 3)), Rgb565::BLACK);
+        assert_eq!(
+            lcd.pixels().iter().filter(|&&p| p == Rgb565::RED).count(),
+            4
+        );
+    }
+}
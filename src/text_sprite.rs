@@ -0,0 +1,485 @@
+use alloc::{collections::BTreeSet, string::String, vec::Vec};
+use core::{convert::Infallible, ptr};
+
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::{Dimensions, Point, Size},
+    mono_font::MonoTextStyle,
+    pixelcolor::Rgb565,
+    primitives::Rectangle,
+    text::{Alignment, Text},
+    transform::Transform,
+    Drawable, Pixel,
+};
+
+use crate::color565;
+
+/// Horizontal line of same colored pixels, relative to the text position
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Run {
+    start: Point,
+    len: u32,
+    color: Rgb565,
+}
+
+/// Drawn around the glyphs, to keep text readable over noise and bright backgrounds
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Edge {
+    /// 1px all around
+    pub outline: Option<Rgb565>,
+    /// 1px below and to the right, or 2px with an outline, which goes over it
+    pub shadow: Option<Rgb565>,
+}
+
+impl Edge {
+    /// Of the shadow, down and to the right
+    fn shadow_offset(self) -> i32 {
+        match self.outline {
+            Some(_) => 2,
+            None => 1,
+        }
+    }
+}
+
+struct Rasterized {
+    text: String,
+    style: MonoTextStyle<'static, Rgb565>,
+    alignment: Alignment,
+    edge: Edge,
+    runs: Vec<Run>,
+    bounding_box: Rectangle,
+}
+
+impl Rasterized {
+    fn matches(
+        &self,
+        text: &str,
+        style: &MonoTextStyle<'static, Rgb565>,
+        alignment: Alignment,
+        edge: Edge,
+    ) -> bool {
+        // Comparing fonts by address, their == goes through the whole glyph image
+        self.text == text
+            && ptr::eq(self.style.font, style.font)
+            && self.style.text_color == style.text_color
+            && self.style.background_color == style.background_color
+            && self.style.underline_color == style.underline_color
+            && self.style.strikethrough_color == style.strikethrough_color
+            && self.alignment == alignment
+            && self.edge == edge
+    }
+}
+
+/// Text that is rasterized once and then copied on each draw, for strings that stay the same
+/// for many frames. Rasterized again only when the string, the font or the colors change.
+#[derive(Default)]
+pub struct TextSprite {
+    rasterized: Option<Rasterized>,
+    /// Rasterized along with the glyphs, so it costs nothing extra to draw
+    edge: Edge,
+    /// Reused by `draw_supersampled`
+    samples: Vec<Option<Rgb565>>,
+}
+
+impl TextSprite {
+    /// For everything drawn from now on
+    pub fn set_edge(&mut self, edge: Edge) {
+        self.edge = edge;
+    }
+
+    fn rasterized(
+        &mut self,
+        text: &str,
+        style: MonoTextStyle<'static, Rgb565>,
+        alignment: Alignment,
+    ) -> &Rasterized {
+        let edge = self.edge;
+        let rasterized = match self.rasterized.take() {
+            Some(rasterized) if rasterized.matches(text, &style, alignment, edge) => rasterized,
+            _ => rasterize(text, style, alignment, edge),
+        };
+        self.rasterized.insert(rasterized)
+    }
+
+    /// Draws the same pixels `Text::with_alignment(text, position, style, alignment)` would,
+    /// plus the edge around them, returns their bounding box
+    pub fn draw<D: DrawTarget<Color = Rgb565>>(
+        &mut self,
+        text: &str,
+        position: Point,
+        style: MonoTextStyle<'static, Rgb565>,
+        alignment: Alignment,
+        target: &mut D,
+    ) -> Result<Rectangle, D::Error> {
+        let rasterized = self.rasterized(text, style, alignment);
+        for run in &rasterized.runs {
+            let line = Rectangle::new(position + run.start, Size::new(run.len, 1));
+            target.fill_solid(&line, run.color)?;
+        }
+        Ok(rasterized.bounding_box.translate(position))
+    }
+
+    /// Like `draw`, but `position` is in half pixels, for text that moves by less than a pixel.
+    /// Between pixels, the text is rendered at twice the resolution and scaled back down, with
+    /// the edges blended into `background`.
+    pub fn draw_supersampled<D: DrawTarget<Color = Rgb565>>(
+        &mut self,
+        text: &str,
+        position: Point,
+        style: MonoTextStyle<'static, Rgb565>,
+        alignment: Alignment,
+        background: Rgb565,
+        target: &mut D,
+    ) -> Result<Rectangle, D::Error> {
+        let whole = Point::new(position.x.div_euclid(2), position.y.div_euclid(2));
+        let half = Point::new(position.x.rem_euclid(2), position.y.rem_euclid(2));
+        if half == Point::zero() {
+            return self.draw(text, whole, style, alignment, target);
+        }
+        let mut samples = core::mem::take(&mut self.samples);
+        let rasterized = self.rasterized(text, style, alignment);
+        let bounding_box = rasterized.bounding_box;
+        // One more pixel to the right and below, for the half pixel that sticks out
+        let (width, height) = (
+            bounding_box.size.width as usize + 1,
+            bounding_box.size.height as usize + 1,
+        );
+        samples.clear();
+        samples.resize(width * 2 * height * 2, None);
+        for run in &rasterized.runs {
+            let Point { x, y } = run.start - bounding_box.top_left;
+            for x in x..x + run.len as i32 {
+                let Point { x, y } = Point::new(x, y) * 2 + half;
+                for (x, y) in [(x, y), (x + 1, y), (x, y + 1), (x + 1, y + 1)] {
+                    samples[y as usize * width * 2 + x as usize] = Some(run.color);
+                }
+            }
+        }
+
+        // Each pixel is the average of its four samples
+        let top_left = whole + bounding_box.top_left;
+        let pixels = (0..height).flat_map(|y| {
+            let samples = &samples;
+            (0..width).filter_map(move |x| {
+                let at = |dx, dy| samples[(y * 2 + dy) * width * 2 + x * 2 + dx];
+                let four = [at(0, 0), at(1, 0), at(0, 1), at(1, 1)];
+                if four.iter().all(Option::is_none) {
+                    return None;
+                }
+                let mut sum = [0u32; 3];
+                for sample in four {
+                    let channels = color565::split(sample.unwrap_or(background));
+                    for (sum, channel) in sum.iter_mut().zip(channels) {
+                        *sum += u32::from(channel);
+                    }
+                }
+                let color = color565::merge(sum.map(|sum| (sum / 4) as u8));
+                Some(Pixel(top_left + Point::new(x as i32, y as i32), color))
+            })
+        });
+        let drawn = target.draw_iter(pixels);
+        self.samples = samples;
+        drawn?;
+        let size = bounding_box.size + Size::new(half.x as u32, half.y as u32);
+        Ok(Rectangle::new(top_left, size))
+    }
+}
+
+fn rasterize(
+    text: &str,
+    style: MonoTextStyle<'static, Rgb565>,
+    alignment: Alignment,
+    edge: Edge,
+) -> Rasterized {
+    let text = Text::with_alignment(text, Point::zero(), style, alignment);
+    let mut recorder = Recorder(Vec::new());
+    // Recording never fails
+    text.draw(&mut recorder).unwrap_or_else(|e| match e {});
+    let mut bounding_box = text.bounding_box();
+    let mut pixels = edged(recorder.0, edge);
+    if edge.outline.is_some() {
+        bounding_box = bounding_box.offset(1);
+    }
+    // The shadow sticks out 1px past the outline too
+    if edge.shadow.is_some() {
+        bounding_box.size += Size::new(1, 1);
+    }
+    // Stable, so that the last one drawn wins if a pixel is drawn twice
+    pixels.sort_by_key(|Pixel(point, _)| (point.y, point.x));
+    let mut unique: Vec<Pixel<Rgb565>> = Vec::with_capacity(pixels.len());
+    for pixel in pixels {
+        match unique.last_mut() {
+            Some(last) if last.0 == pixel.0 => *last = pixel,
+            _ => unique.push(pixel),
+        }
+    }
+
+    let mut runs: Vec<Run> = Vec::new();
+    for Pixel(point, color) in unique {
+        match runs.last_mut() {
+            Some(run)
+                if run.start.y == point.y
+                    && run.start.x + run.len as i32 == point.x
+                    && run.color == color =>
+            {
+                run.len += 1;
+            }
+            _ => runs.push(Run {
+                start: point,
+                len: 1,
+                color,
+            }),
+        }
+    }
+
+    Rasterized {
+        text: text.text.into(),
+        style,
+        alignment,
+        edge,
+        runs,
+        bounding_box,
+    }
+}
+
+/// `glyphs` with the shadow, then the outline around them, so that the glyphs are drawn last
+fn edged(glyphs: Vec<Pixel<Rgb565>>, edge: Edge) -> Vec<Pixel<Rgb565>> {
+    if edge == Edge::default() {
+        return glyphs;
+    }
+    let covered: BTreeSet<(i32, i32)> = glyphs.iter().map(|Pixel(p, _)| (p.x, p.y)).collect();
+    let around = |offsets: &[(i32, i32)], color| {
+        let covered = &covered;
+        let points: BTreeSet<_> = covered
+            .iter()
+            .flat_map(|&(x, y)| offsets.iter().map(move |(dx, dy)| (x + dx, y + dy)))
+            .filter(|point| !covered.contains(point))
+            .collect();
+        points
+            .into_iter()
+            .map(move |(x, y)| Pixel(Point::new(x, y), color))
+    };
+    let mut pixels = Vec::new();
+    if let Some(color) = edge.shadow {
+        let offset = edge.shadow_offset();
+        pixels.extend(around(&[(offset, offset)], color));
+    }
+    if let Some(color) = edge.outline {
+        let neighbors = [
+            (-1, -1),
+            (0, -1),
+            (1, -1),
+            (-1, 0),
+            (1, 0),
+            (-1, 1),
+            (0, 1),
+            (1, 1),
+        ];
+        pixels.extend(around(&neighbors, color));
+    }
+    pixels.extend(glyphs);
+    pixels
+}
+
+/// Collects whatever is drawn on it, anywhere
+struct Recorder(Vec<Pixel<Rgb565>>);
+
+impl Dimensions for Recorder {
+    fn bounding_box(&self) -> Rectangle {
+        Rectangle::with_center(Point::zero(), Size::new_equal(u16::MAX.into()))
+    }
+}
+
+impl DrawTarget for Recorder {
+    type Color = Rgb565;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        self.0.extend(pixels);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_graphics::{
+        mock_display::MockDisplay,
+        mono_font::{ascii::FONT_6X10, MonoTextStyleBuilder},
+        pixelcolor::RgbColor,
+        primitives::PointsIter,
+    };
+
+    use super::*;
+
+    fn draw_both(
+        sprite: &mut TextSprite,
+        text: &str,
+        position: Point,
+        style: MonoTextStyle<'static, Rgb565>,
+    ) {
+        let mut expected = MockDisplay::new();
+        Text::with_alignment(text, position, style, Alignment::Center)
+            .draw(&mut expected)
+            .unwrap();
+        let mut display = MockDisplay::new();
+        let bounding_box = sprite
+            .draw(text, position, style, Alignment::Center, &mut display)
+            .unwrap();
+        display.assert_eq(&expected);
+        assert_eq!(
+            bounding_box,
+            Text::with_alignment(text, position, style, Alignment::Center).bounding_box()
+        );
+    }
+
+    #[test]
+    fn draws_the_same_as_text() {
+        let mut sprite = TextSprite::default();
+        let style = MonoTextStyle::new(&FONT_6X10, Rgb565::WHITE);
+        draw_both(&mut sprite, "Android.bp", Point::new(32, 20), style);
+        // Cached, somewhere else
+        draw_both(&mut sprite, "Android.bp", Point::new(30, 22), style);
+        // Lines are aligned separately
+        draw_both(&mut sprite, "two\nlines", Point::new(30, 22), style);
+        let style = MonoTextStyleBuilder::from(&style)
+            .background_color(Rgb565::BLUE)
+            .build();
+        draw_both(&mut sprite, "two\nlines", Point::new(30, 22), style);
+    }
+
+    #[test]
+    fn draws_edges_around_glyphs() {
+        let style = MonoTextStyle::new(&FONT_6X10, Rgb565::WHITE);
+        let position = Point::new(20, 20);
+        let mut plain = MockDisplay::new();
+        Text::with_alignment("Ab", position, style, Alignment::Center)
+            .draw(&mut plain)
+            .unwrap();
+        let glyph =
+            |display: &MockDisplay<Rgb565>, point| display.get_pixel(point) == Some(Rgb565::WHITE);
+        for edge in [
+            Edge {
+                outline: Some(Rgb565::BLACK),
+                shadow: None,
+            },
+            Edge {
+                outline: None,
+                shadow: Some(Rgb565::BLUE),
+            },
+            Edge {
+                outline: Some(Rgb565::BLACK),
+                shadow: Some(Rgb565::BLUE),
+            },
+        ] {
+            let mut sprite = TextSprite::default();
+            sprite.set_edge(edge);
+            let mut display = MockDisplay::new();
+            let bounding_box = sprite
+                .draw("Ab", position, style, Alignment::Center, &mut display)
+                .unwrap();
+            for point in Rectangle::new(Point::zero(), Size::new(64, 64)).points() {
+                // Glyphs stay as they were, edges only go where they aren't
+                assert_eq!(glyph(&display, point), glyph(&plain, point), "{point:?}");
+                let Some(color) = display.get_pixel(point) else {
+                    continue;
+                };
+                assert!(
+                    bounding_box.contains(point),
+                    "{point:?} outside {bounding_box:?}"
+                );
+                let near = |offsets: &[(i32, i32)]| {
+                    offsets
+                        .iter()
+                        .any(|&(dx, dy)| glyph(&plain, point - Point::new(dx, dy)))
+                };
+                if color == Rgb565::BLACK {
+                    assert!(near(&[
+                        (-1, -1),
+                        (0, -1),
+                        (1, -1),
+                        (-1, 0),
+                        (1, 0),
+                        (-1, 1),
+                        (0, 1),
+                        (1, 1)
+                    ]));
+                } else if color == Rgb565::BLUE {
+                    let offset = edge.shadow_offset();
+                    assert!(near(&[(offset, offset)]));
+                }
+            }
+            // Not all of the shadow is hidden under the outline
+            let shown = |color| {
+                Rectangle::new(Point::zero(), Size::new(64, 64))
+                    .points()
+                    .any(|point| display.get_pixel(point) == Some(color))
+            };
+            if let Some(shadow) = edge.shadow {
+                assert!(shown(shadow), "{edge:?}");
+            }
+            // Every glyph pixel gets one below and to the right, unless covered
+            let edge_color = edge.outline.or(edge.shadow);
+            for point in Rectangle::new(Point::zero(), Size::new(63, 63)).points() {
+                let below_right = point + Point::new(1, 1);
+                if glyph(&plain, point) && !glyph(&plain, below_right) {
+                    assert!(display.get_pixel(below_right) == edge_color);
+                }
+            }
+        }
+    }
+
+    /// Sum of each channel over the whole display
+    fn light(display: &MockDisplay<Rgb565>) -> [u32; 3] {
+        let mut light = [0; 3];
+        for point in Rectangle::new(Point::zero(), Size::new(64, 64)).points() {
+            if let Some(color) = display.get_pixel(point) {
+                for (sum, channel) in light.iter_mut().zip(color565::split(color)) {
+                    *sum += u32::from(channel);
+                }
+            }
+        }
+        light
+    }
+
+    #[test]
+    fn moves_by_half_pixels() {
+        let mut sprite = TextSprite::default();
+        // Channels that quartering doesn't round
+        let style = MonoTextStyle::new(&FONT_6X10, Rgb565::new(28, 60, 28));
+        let draw = |sprite: &mut TextSprite, position| {
+            let mut display = MockDisplay::new();
+            let bounding_box = sprite
+                .draw_supersampled(
+                    "Ab",
+                    position,
+                    style,
+                    Alignment::Left,
+                    Rgb565::BLACK,
+                    &mut display,
+                )
+                .unwrap();
+            (display, bounding_box)
+        };
+        // Whole pixels are drawn as usual
+        let mut expected = MockDisplay::new();
+        Text::new("Ab", Point::new(10, 20), style)
+            .draw(&mut expected)
+            .unwrap();
+        let (whole, whole_box) = draw(&mut sprite, Point::new(20, 40));
+        whole.assert_eq(&expected);
+        // Half a pixel further, the same light is spread over one more column or row
+        for half in [Point::new(21, 40), Point::new(20, 41), Point::new(21, 41)] {
+            let (display, bounding_box) = draw(&mut sprite, half);
+            assert_eq!(light(&display), light(&whole), "{half:?}");
+            assert_ne!(display, whole);
+            assert_eq!(bounding_box.top_left, whole_box.top_left);
+            assert!(
+                bounding_box.size.width + bounding_box.size.height
+                    > whole_box.size.width + whole_box.size.height
+            );
+        }
+    }
+}
@@ -0,0 +1,476 @@
+use std::sync::Mutex;
+
+use anyhow::{bail, Result};
+use embedded_graphics::{
+    draw_target::DrawTarget, geometry::Dimensions, pixelcolor::Rgb565, primitives::Rectangle,
+    Drawable, Pixel,
+};
+use serde::Deserialize;
+
+use crate::{color565, scene::color_from_hex, text_sprite::Edge};
+
+/// Every color of the escalation, the noise ending, the panic screen and the other scenes.
+/// Colors are `0xRRGGBB`, like in HTML.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    /// The escalation background fades from the first to the second one
+    pub background: [u32; 2],
+    /// Timer, message and temperature
+    pub text: u32,
+    /// 1px around the text, so that it stands out of noise and bright backgrounds. None draws
+    /// none.
+    pub outline: Option<u32>,
+    /// 1px below and to the right of the text
+    pub shadow: Option<u32>,
+    /// Darkest and brightest color of the noise, None keeps its own colors
+    pub noise: Option<[u32; 2]>,
+    /// Dumpster fire gets recolored between these by brightness, None keeps the image colors
+    pub fire: Option<[u32; 2]>,
+    /// Background and text of the panic screen
+    pub panic: [u32; 2],
+    /// Time, and the date and weather below it, of the clock
+    pub clock: [u32; 2],
+    /// Targets of the lunch menu, and the one it picks
+    pub lunch: [u32; 2],
+    /// Name and scrolling tagline of the badge
+    pub badge: [u32; 2],
+    /// Countdown text, and the color it flashes with at T-0. The background turns to a dark
+    /// shade of the latter near the deadline.
+    pub countdown: [u32; 2],
+    /// Battery indicator, then the same when it's low and the dead battery screen
+    pub battery: [u32; 2],
+    /// Info, warning and error lines of the log overlay
+    pub log: [u32; 3],
+}
+
+const CLASSIC: Theme = Theme {
+    background: [0x000000, 0xff0000],
+    text: 0xffffff,
+    outline: None,
+    shadow: None,
+    noise: None,
+    fire: None,
+    panic: [0x000000, 0xff0000],
+    clock: [0xc0c0c0, 0x808080],
+    lunch: [0xa0c0a0, 0x00ff00],
+    badge: [0xffffff, 0xff0000],
+    countdown: [0xffffff, 0xff0000],
+    battery: [0xffffff, 0xff0000],
+    log: [0xffffff, 0xffff00, 0xff0000],
+};
+
+/// Name and theme, in the order `theme` on the console lists them
+const BUILTIN: &[(&str, Theme)] = &[
+    ("classic", CLASSIC),
+    (
+        "amber",
+        Theme {
+            background: [0x000000, 0x703800],
+            text: 0xffb000,
+            outline: None,
+            shadow: None,
+            noise: Some([0x000000, 0xffb000]),
+            fire: Some([0x301000, 0xffc040]),
+            panic: [0x000000, 0xffb000],
+            clock: [0xffb000, 0x906000],
+            lunch: [0xb07800, 0xffd060],
+            badge: [0xffb000, 0xff6000],
+            countdown: [0xffb000, 0x502000],
+            battery: [0xffb000, 0xff4000],
+            log: [0xffb000, 0xffd060, 0xff4000],
+        },
+    ),
+    (
+        "cyberpunk",
+        Theme {
+            background: [0x08001a, 0xc00070],
+            text: 0x00ffff,
+            outline: None,
+            shadow: None,
+            noise: Some([0x1a0033, 0xff00ff]),
+            fire: Some([0x200040, 0x40ffff]),
+            // Blue screen of death
+            panic: [0x0000aa, 0xffffff],
+            clock: [0x00ffff, 0xc000c0],
+            lunch: [0xc000c0, 0x00ffff],
+            badge: [0x00ffff, 0xff00ff],
+            countdown: [0x00ffff, 0xc00070],
+            battery: [0x00ffff, 0xff00ff],
+            log: [0x00ffff, 0xffff00, 0xff00ff],
+        },
+    ),
+    (
+        "grayscale",
+        Theme {
+            background: [0x000000, 0x808080],
+            text: 0xffffff,
+            outline: None,
+            shadow: None,
+            noise: Some([0x000000, 0xffffff]),
+            fire: Some([0x000000, 0xffffff]),
+            panic: [0x000000, 0xffffff],
+            clock: [0xffffff, 0xa0a0a0],
+            lunch: [0xa0a0a0, 0xffffff],
+            badge: [0xffffff, 0xa0a0a0],
+            countdown: [0xffffff, 0x404040],
+            battery: [0xffffff, 0x808080],
+            log: [0xa0a0a0, 0xd0d0d0, 0xffffff],
+        },
+    ),
+    // Blue and orange from the Okabe-Ito palette, which look different to everyone, red-blind
+    // and green-blind people included
+    (
+        "colorblind",
+        Theme {
+            background: [0x000000, 0x0072b2],
+            text: 0xffffff,
+            outline: None,
+            shadow: None,
+            noise: Some([0x000000, 0xe69f00]),
+            fire: Some([0x000000, 0xe69f00]),
+            panic: [0x000000, 0xe69f00],
+            clock: [0xffffff, 0x56b4e9],
+            lunch: [0x56b4e9, 0xf0e442],
+            badge: [0xffffff, 0xe69f00],
+            countdown: [0xffffff, 0x0072b2],
+            battery: [0xffffff, 0xe69f00],
+            log: [0xffffff, 0x56b4e9, 0xe69f00],
+        },
+    ),
+    // The background never gets bright enough to wash out the text, not even at the very end,
+    // and the outline keeps it apart from the noise
+    (
+        "high-contrast",
+        Theme {
+            background: [0x000000, 0x500000],
+            text: 0xffffff,
+            outline: Some(0x000000),
+            shadow: None,
+            noise: Some([0x000000, 0xffffff]),
+            fire: Some([0x000000, 0xffff00]),
+            panic: [0x000000, 0xffffff],
+            clock: [0xffffff, 0xffffff],
+            lunch: [0xffffff, 0xffff00],
+            badge: [0xffffff, 0xffff00],
+            countdown: [0xffffff, 0x500000],
+            battery: [0xffffff, 0xffff00],
+            log: [0xffffff, 0xffff00, 0xff8080],
+        },
+    ),
+];
+
+impl Default for Theme {
+    fn default() -> Self {
+        CLASSIC
+    }
+}
+
+impl Theme {
+    /// Background after `shade` of `shades` steps, exactly the two configured colors at both
+    /// ends
+    pub fn background(&self, shade: usize, shades: usize) -> Rgb565 {
+        let [from, to] = self.background.map(|c| color565::split(color_from_hex(c)));
+        let (shade, last) = (shade.min(shades - 1) as i32, (shades - 1).max(1) as i32);
+        let channel = |i: usize| {
+            (i32::from(from[i]) + (i32::from(to[i]) - i32::from(from[i])) * shade / last) as u8
+        };
+        color565::merge([channel(0), channel(1), channel(2)])
+    }
+
+    pub fn text(&self) -> Rgb565 {
+        color_from_hex(self.text)
+    }
+
+    pub fn text_edge(&self) -> Edge {
+        Edge {
+            outline: self.outline.map(color_from_hex),
+            shadow: self.shadow.map(color_from_hex),
+        }
+    }
+
+    pub fn noise(&self) -> Option<[Rgb565; 2]> {
+        self.noise.map(|palette| palette.map(color_from_hex))
+    }
+
+    pub fn fire(&self) -> Option<[Rgb565; 2]> {
+        self.fire.map(|palette| palette.map(color_from_hex))
+    }
+
+    /// Background and text
+    pub fn panic(&self) -> [Rgb565; 2] {
+        self.panic.map(color_from_hex)
+    }
+
+    /// Time and details
+    pub fn clock(&self) -> [Rgb565; 2] {
+        self.clock.map(color_from_hex)
+    }
+
+    /// Text and highlight
+    pub fn lunch(&self) -> [Rgb565; 2] {
+        self.lunch.map(color_from_hex)
+    }
+
+    /// Name and tagline
+    pub fn badge(&self) -> [Rgb565; 2] {
+        self.badge.map(color_from_hex)
+    }
+
+    /// Text and alarm
+    pub fn countdown(&self) -> [Rgb565; 2] {
+        self.countdown.map(color_from_hex)
+    }
+
+    /// Normal and low
+    pub fn battery(&self) -> [Rgb565; 2] {
+        self.battery.map(color_from_hex)
+    }
+
+    /// Info, warning and error
+    pub fn log(&self) -> [Rgb565; 3] {
+        self.log.map(color_from_hex)
+    }
+}
+
+/// `theme` in config.toml: name of a built-in one, or a `[theme]` table with custom colors
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Config {
+    Builtin(String),
+    Custom(Theme),
+}
+
+/// Name of the custom theme from config.toml, if there's one
+const CUSTOM: &str = "custom";
+
+struct State {
+    name: &'static str,
+    theme: Theme,
+    /// From config.toml
+    custom: Option<Theme>,
+}
+
+// Set from the console thread
+static STATE: Mutex<State> = Mutex::new(State {
+    name: "classic",
+    theme: CLASSIC,
+    custom: None,
+});
+
+pub fn init(config: Option<&Config>) {
+    match config {
+        None => {}
+        Some(Config::Builtin(name)) => {
+            if let Err(e) = set(name) {
+                log::error!("invalid theme: {e:#}");
+            }
+        }
+        Some(Config::Custom(theme)) => {
+            let mut state = STATE.lock().unwrap();
+            state.custom = Some(*theme);
+            state.name = CUSTOM;
+            state.theme = *theme;
+        }
+    }
+}
+
+pub fn current() -> Theme {
+    STATE.lock().unwrap().theme
+}
+
+/// Doesn't wait for the lock, for the panic screen. None if it's taken.
+pub fn try_current() -> Option<Theme> {
+    STATE.try_lock().ok().map(|state| state.theme)
+}
+
+/// Switches to a built-in theme, or the custom one from config.toml
+pub fn set(name: &str) -> Result<()> {
+    let mut state = STATE.lock().unwrap();
+    let found = match name {
+        CUSTOM => state.custom.map(|theme| (CUSTOM, theme)),
+        _ => builtin(name),
+    };
+    let Some((name, theme)) = found else {
+        bail!("no theme named {name}, try one of: {}", names().join(", "));
+    };
+    state.name = name;
+    state.theme = theme;
+    log::info!("theme: {name}");
+    Ok(())
+}
+
+fn builtin(name: &str) -> Option<(&'static str, Theme)> {
+    BUILTIN
+        .iter()
+        .find(|(builtin, _)| *builtin == name)
+        .copied()
+}
+
+/// Name of the current theme
+pub fn name() -> &'static str {
+    STATE.lock().unwrap().name
+}
+
+/// Of all themes that can be switched to
+pub fn names() -> Vec<&'static str> {
+    let custom = STATE.lock().unwrap().custom.map(|_| CUSTOM);
+    BUILTIN
+        .iter()
+        .map(|(name, _)| *name)
+        .chain(custom)
+        .collect()
+}
+
+/// Draws `drawable` recolored between `palette` colors by brightness, or as it is without one
+pub fn draw<D: DrawTarget<Color = Rgb565>>(
+    drawable: &impl Drawable<Color = Rgb565>,
+    target: &mut D,
+    palette: Option<[Rgb565; 2]>,
+) -> Result<(), D::Error> {
+    match palette {
+        Some(palette) => {
+            drawable.draw(&mut Recolored { target, palette })?;
+        }
+        None => {
+            drawable.draw(target)?;
+        }
+    }
+    Ok(())
+}
+
+struct Recolored<'a, D> {
+    target: &'a mut D,
+    palette: [Rgb565; 2],
+}
+
+impl<D: DrawTarget<Color = Rgb565>> Dimensions for Recolored<'_, D> {
+    fn bounding_box(&self) -> Rectangle {
+        self.target.bounding_box()
+    }
+}
+
+impl<D: DrawTarget<Color = Rgb565>> DrawTarget for Recolored<'_, D> {
+    type Color = Rgb565;
+    type Error = D::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let [dark, bright] = self.palette;
+        self.target.draw_iter(
+            pixels
+                .into_iter()
+                .map(|Pixel(point, color)| Pixel(point, color565::remap(color, dark, bright))),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_graphics::{pixelcolor::Rgb888, prelude::RgbColor};
+
+    use super::*;
+
+    #[test]
+    fn classic_background_is_shades_of_red() {
+        let theme = Theme::default();
+        for shade in 0..32 {
+            assert_eq!(theme.background(shade, 32), Rgb565::new(shade as u8, 0, 0));
+        }
+        let amber = BUILTIN[1].1;
+        assert_eq!(amber.background(0, 32), Rgb565::BLACK);
+        assert_eq!(amber.background(31, 32), color_from_hex(0x703800));
+    }
+
+    /// WCAG relative luminance, after `vision` mixes linear RGB the way the eye sees it
+    fn luminance(color: Rgb565, vision: [[f32; 3]; 3]) -> f32 {
+        let rgb = Rgb888::from(color);
+        let linear = [rgb.r(), rgb.g(), rgb.b()].map(|c| {
+            let c = f32::from(c) / 255.0;
+            match c <= 0.04045 {
+                true => c / 12.92,
+                false => ((c + 0.055) / 1.055).powf(2.4),
+            }
+        });
+        let [r, g, b] = vision.map(|row| {
+            (0..3)
+                .map(|i| row[i] * linear[i])
+                .sum::<f32>()
+                .clamp(0.0, 1.0)
+        });
+        0.2126 * r + 0.7152 * g + 0.0722 * b
+    }
+
+    fn contrast(a: Rgb565, b: Rgb565, vision: [[f32; 3]; 3]) -> f32 {
+        let (a, b) = (luminance(a, vision), luminance(b, vision));
+        (a.max(b) + 0.05) / (a.min(b) + 0.05)
+    }
+
+    #[test]
+    fn accessible_themes_stay_readable() {
+        const NORMAL: [[f32; 3]; 3] = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+        // Viénot, Brettel and Mollon, 1999
+        const PROTANOPIA: [[f32; 3]; 3] = [
+            [0.11238, 0.88762, 0.0],
+            [0.11238, 0.88762, 0.0],
+            [0.00401, -0.00401, 1.0],
+        ];
+        const DEUTERANOPIA: [[f32; 3]; 3] = [
+            [0.29275, 0.70725, 0.0],
+            [0.29275, 0.70725, 0.0],
+            [-0.02234, 0.02234, 1.0],
+        ];
+        for name in ["colorblind", "high-contrast"] {
+            let (_, theme) = builtin(name).unwrap();
+            for vision in [NORMAL, PROTANOPIA, DEUTERANOPIA] {
+                // WCAG AA for normal text
+                for shade in 0..32 {
+                    let background = theme.background(shade, 32);
+                    assert!(
+                        contrast(theme.text(), background, vision) >= 4.5,
+                        "{name} {shade}"
+                    );
+                }
+                let [background, text] = theme.panic();
+                assert!(contrast(text, background, vision) >= 4.5, "{name} panic");
+                let [text, alarm] = theme.countdown();
+                assert!(contrast(text, alarm, vision) >= 4.5, "{name} countdown");
+                for color in theme.log() {
+                    assert!(contrast(color, Rgb565::BLACK, vision) >= 4.5, "{name} log");
+                }
+            }
+        }
+        // Unlike the classic one, at the end of the escalation, and on the panic screen for the
+        // red-blind
+        let classic = Theme::default();
+        assert!(contrast(classic.text(), classic.background(31, 32), NORMAL) < 4.5);
+        let [background, text] = classic.panic();
+        assert!(contrast(text, background, PROTANOPIA) < 4.5);
+    }
+
+    #[test]
+    fn parses_config() {
+        #[derive(Deserialize)]
+        struct File {
+            theme: Config,
+        }
+        let parse = |text| toml::from_str::<File>(text).map(|file| file.theme);
+        assert!(matches!(
+            parse("theme = \"amber\"").unwrap(),
+            Config::Builtin(name) if name == "amber"
+        ));
+        let Config::Custom(theme) = parse("[theme]\ntext = 0x00ff00").unwrap() else {
+            panic!("not a custom theme");
+        };
+        assert_eq!(theme.text(), Rgb565::GREEN);
+        assert_eq!(theme.background, CLASSIC.background);
+        assert_eq!(theme.text_edge(), Edge::default());
+        let Config::Custom(theme) = parse("[theme]\nshadow = 0x000000").unwrap() else {
+            panic!("not a custom theme");
+        };
+        assert_eq!(theme.text_edge().shadow, Some(Rgb565::BLACK));
+        assert!(parse("[theme]\ntxet = 0x00ff00").is_err());
+    }
+}
@@ -0,0 +1,320 @@
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::{Dimensions, Point},
+    mono_font::{iso_8859_1::FONT_6X10, MonoTextStyle},
+    pixelcolor::{Rgb565, Rgb888},
+    prelude::RgbColor,
+    text::{Alignment, Text},
+    Drawable,
+};
+use embedded_graphics_framebuf::FrameBuf;
+use serde::Deserialize;
+
+use crate::{
+    intensity::Intensity,
+    layout,
+    scene::{self, Canvas, Scaled, Scene, SceneContext, GLITCH_LINE_PROBABILITY},
+    typewriter::{self, Typewriter},
+    viewport::{self, View},
+};
+
+/// Scene described by a list of keyframes, for when a script would be overkill. Numbers and
+/// colors fade from one keyframe to the next, the rest switches at the start of each.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Timeline {
+    keyframes: Vec<Keyframe>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Keyframe {
+    /// Seconds until the next one
+    duration: f32,
+    /// Centered on the screen
+    #[serde(default)]
+    message: String,
+    /// `0xRRGGBB`
+    #[serde(default = "Keyframe::default_background")]
+    background: u32,
+    #[serde(default = "Keyframe::default_color")]
+    color: u32,
+    /// Random offset of the message, in pixels
+    #[serde(default)]
+    shake: f32,
+    /// Keystrokes per second the message gets typed out at, 0 shows it all at once
+    #[serde(default)]
+    typewriter: f32,
+    /// How the message gets typed out, if it does. Worked out once, when parsing.
+    #[serde(skip)]
+    keystrokes: Option<Typewriter>,
+    /// Max offset of glitched lines, in pixels
+    #[serde(default)]
+    glitch: f32,
+    /// Fraction of pixels replaced with noise, 0..1
+    #[serde(default)]
+    noise: f32,
+    #[serde(default)]
+    leds: LedPattern,
+    /// Center of the view relative to the center of the screen, in pixels
+    #[serde(default)]
+    pan: [f32; 2],
+    /// 2 shows everything twice as big
+    #[serde(default = "Keyframe::default_zoom")]
+    zoom: f32,
+}
+
+impl Keyframe {
+    fn default_background() -> u32 {
+        0x000000
+    }
+
+    fn default_color() -> u32 {
+        0xffffff
+    }
+
+    fn default_zoom() -> f32 {
+        1.0
+    }
+
+    fn validate(&self) -> Result<()> {
+        if !(self.duration > 0.0 && self.duration.is_finite()) {
+            bail!("duration must be positive");
+        }
+        for (name, value) in [
+            ("shake", self.shake),
+            ("typewriter", self.typewriter),
+            ("glitch", self.glitch),
+        ] {
+            if !(value >= 0.0 && value.is_finite()) {
+                bail!("{name} can't be negative");
+            }
+        }
+        if !(0.0..=1.0).contains(&self.noise) {
+            bail!("noise must be between 0 and 1");
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum LedPattern {
+    #[default]
+    Off,
+    On,
+    /// Both at once, twice a second
+    Blink,
+    /// One at a time, twice a second
+    Alternate,
+    /// Slow breathing
+    Pulse,
+}
+
+impl LedPattern {
+    fn brightness(self, t: f32) -> [f32; 2] {
+        let half_second_on = (t * 2.0) as u32 & 1 == 0;
+        match self {
+            LedPattern::Off => [0.0; 2],
+            LedPattern::On => [1.0; 2],
+            LedPattern::Blink if half_second_on => [1.0; 2],
+            LedPattern::Blink => [0.0; 2],
+            LedPattern::Alternate if half_second_on => [1.0, 0.0],
+            LedPattern::Alternate => [0.0, 1.0],
+            LedPattern::Pulse => [(1.0 - (t * std::f32::consts::PI).cos()) / 2.0; 2],
+        }
+    }
+}
+
+impl Timeline {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let text = std::str::from_utf8(data).context("not valid UTF-8")?;
+        let mut timeline: Self = toml::from_str(text)?;
+        if timeline.keyframes.is_empty() {
+            bail!("no keyframes");
+        }
+        for (i, keyframe) in timeline.keyframes.iter_mut().enumerate() {
+            keyframe
+                .validate()
+                .with_context(|| format!("keyframe {i}"))?;
+            if keyframe.typewriter > 0.0 {
+                keyframe.keystrokes = Some(Typewriter::new(&keyframe.message));
+            }
+        }
+        Ok(timeline)
+    }
+
+    pub fn start(&self) -> TimelineScene<'_> {
+        TimelineScene(self)
+    }
+
+    /// Keyframe at `t`, the one after it, and how far between them it is
+    fn keyframe_at(&self, t: f32) -> Option<(&Keyframe, Option<&Keyframe>, f32)> {
+        let mut start = 0.0;
+        for (i, keyframe) in self.keyframes.iter().enumerate() {
+            if t < start + keyframe.duration {
+                let fraction = (t - start) / keyframe.duration;
+                return Some((keyframe, self.keyframes.get(i + 1), fraction));
+            }
+            start += keyframe.duration;
+        }
+        None
+    }
+}
+
+pub struct TimelineScene<'a>(&'a Timeline);
+
+/// Centered on `target`, moved by `offset`. Only the `typed` part of it so far, if it's being
+/// typed out.
+fn draw_message<D: DrawTarget<Color = Rgb565>>(
+    target: &mut Scaled<D>,
+    message: &str,
+    typed: Option<&str>,
+    style: MonoTextStyle<Rgb565>,
+    offset: Point,
+) -> Result<(), D::Error> {
+    let center = target.bounding_box().center();
+    let position = layout::text_block(center, message.lines().count(), &style) + offset;
+    match typed {
+        Some(typed) => {
+            typewriter::draw(target, message, typed, position, style, Alignment::Center)?
+        }
+        None => {
+            Text::with_alignment(message, position, style, Alignment::Center).draw(target)?;
+        }
+    }
+    Ok(())
+}
+
+/// The part of `keyframe`'s message typed `fraction` of the way through it, with a cursor
+/// until it's all there
+fn typed(keyframe: &Keyframe, fraction: f32) -> Option<String> {
+    let typewriter = keyframe.keystrokes.as_ref()?;
+    let keystrokes = (fraction * keyframe.duration * keyframe.typewriter) as usize;
+    let mut typed = typewriter.typed(keystrokes);
+    if keystrokes < typewriter.len() {
+        typed.push('_');
+    }
+    Some(typed)
+}
+
+impl Scene for TimelineScene<'_> {
+    fn name(&self) -> &'static str {
+        "timeline"
+    }
+
+    fn draw(&mut self, t: Duration, canvas: &mut Canvas, ctx: &mut SceneContext) -> Result<bool> {
+        let t = t.as_secs_f32();
+        let Some((keyframe, next, fraction)) = self.0.keyframe_at(t) else {
+            return Ok(false);
+        };
+        let next = next.unwrap_or(keyframe);
+        let lerp = |from: f32, to: f32| from + (to - from) * fraction;
+        let lerp_color = |from: u32, to: u32| {
+            let (from, to) = (
+                Rgb888::from(scene::color_from_hex(from)),
+                Rgb888::from(scene::color_from_hex(to)),
+            );
+            let channel = |from: u8, to: u8| lerp(from as f32, to as f32) as u8;
+            Rgb888::new(
+                channel(from.r(), to.r()),
+                channel(from.g(), to.g()),
+                channel(from.b(), to.b()),
+            )
+            .into()
+        };
+
+        let background = lerp_color(keyframe.background, next.background);
+        let style = MonoTextStyle::new(&FONT_6X10, lerp_color(keyframe.color, next.color));
+        let shake = lerp(keyframe.shake, next.shake) as i32;
+        let view = View {
+            pan: [
+                lerp(keyframe.pan[0], next.pan[0]),
+                lerp(keyframe.pan[1], next.pan[1]),
+            ],
+            zoom: lerp(keyframe.zoom, next.zoom),
+        };
+        let message_offset = crate::intensify(ctx.rng, Point::zero(), shake);
+        let typed = typed(keyframe, fraction);
+        match ctx.virtual_canvas.as_deref_mut() {
+            // Drawn at the same size as on the LCD, panned and zoomed by resampling
+            Some(virtual_canvas) => {
+                let size = virtual_canvas.size;
+                let mut target = FrameBuf::new(
+                    &mut *virtual_canvas,
+                    size.width as usize,
+                    size.height as usize,
+                );
+                target.clear(background)?;
+                draw_message(
+                    &mut Scaled {
+                        target: &mut target,
+                        scale: size.width / canvas.data.size.width,
+                    },
+                    &keyframe.message,
+                    typed.as_deref(),
+                    style,
+                    message_offset,
+                )?;
+                let display_size = canvas.data.size;
+                viewport::resample(
+                    &virtual_canvas.pixels,
+                    size,
+                    view.rectangle(size, display_size),
+                    &mut canvas.data.pixels,
+                    display_size,
+                );
+            }
+            // Whole pixels only, and no zooming out
+            None => {
+                canvas.clear(background)?;
+                draw_message(
+                    &mut Scaled {
+                        target: canvas,
+                        scale: (view.zoom + 0.5).max(1.0) as u32,
+                    },
+                    &keyframe.message,
+                    typed.as_deref(),
+                    style,
+                    message_offset - view.whole_pixel_pan(),
+                )?;
+            }
+        }
+        crate::glitch(
+            canvas,
+            ctx.rng,
+            lerp(keyframe.glitch, next.glitch) as usize,
+            GLITCH_LINE_PROBABILITY,
+        );
+        let noise = lerp(keyframe.noise, next.noise);
+        if noise > 0.0 {
+            crate::add_noise(canvas, ctx.rng, Intensity::new(noise));
+        }
+        ctx.leds = keyframe.leds.brightness(t);
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_invalid_keyframes() {
+        let keyframe = |fields: &str| format!("[[keyframes]]\nduration = 1.0\n{fields}\n");
+        assert!(Timeline::parse(keyframe("shake = 2.0").as_bytes()).is_ok());
+        for fields in [
+            "shake = -2.0",
+            "glitch = -1.0",
+            "typewriter = -5.0",
+            "noise = 1.5",
+        ] {
+            let error = Timeline::parse(keyframe(fields).as_bytes()).err();
+            assert!(error.is_some(), "{fields}");
+        }
+        assert!(Timeline::parse(b"[[keyframes]]\nduration = 0.0\n").is_err());
+    }
+}
@@ -0,0 +1,235 @@
+use std::time::{Duration, Instant};
+
+use embedded_graphics::{
+    geometry::Size,
+    pixelcolor::{Rgb565, RgbColor},
+};
+use embedded_graphics_framebuf::FrameBuf;
+use rand::{rngs::StdRng, Rng};
+use serde::Deserialize;
+
+use crate::{color565, frame_clock, intensity::Intensity, SliceFrameBufferBackend};
+
+/// Melting columns start falling at random times, up to this far into the transition
+const MELT_MAX_DELAY: f32 = 0.3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Effect {
+    Crossfade,
+    /// The new scene comes in from the left
+    Wipe,
+    /// Noise that peaks halfway through, where the scenes switch
+    StaticBurst,
+    /// Columns of the old scene slide down at random speeds, revealing the new one
+    Melt,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Easing {
+    #[default]
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    fn apply(self, t: f32) -> f32 {
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// How to switch from one scene to another
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Transition {
+    /// Scene names, as in frame stats. None matches any scene.
+    #[serde(default)]
+    pub from: Option<String>,
+    #[serde(default)]
+    pub to: Option<String>,
+    pub effect: Effect,
+    /// Seconds
+    pub duration: f32,
+    #[serde(default)]
+    pub easing: Easing,
+}
+
+impl Transition {
+    fn new(from: Option<&str>, to: &str, effect: Effect, duration: f32, easing: Easing) -> Self {
+        Self {
+            from: from.map(str::to_owned),
+            to: Some(to.to_owned()),
+            effect,
+            duration,
+            easing,
+        }
+    }
+
+    fn matches(&self, from: &str, to: &str) -> bool {
+        self.from.as_deref().map_or(true, |f| f == from)
+            && self.to.as_deref().map_or(true, |t| t == to)
+    }
+}
+
+/// Used unless config.toml says otherwise
+pub fn defaults() -> Vec<Transition> {
+    vec![
+        Transition::new(
+            Some("escalation"),
+            "noise",
+            Effect::StaticBurst,
+            0.4,
+            Easing::EaseIn,
+        ),
+        Transition::new(
+            Some("noise"),
+            "escalation",
+            Effect::Melt,
+            1.0,
+            Easing::EaseIn,
+        ),
+        Transition::new(None, "script", Effect::Crossfade, 0.5, Easing::Linear),
+        Transition::new(None, "timeline", Effect::Crossfade, 0.5, Easing::Linear),
+        Transition::new(None, "countdown", Effect::Crossfade, 0.5, Easing::Linear),
+        Transition::new(None, "clock", Effect::Crossfade, 1.0, Easing::EaseInOut),
+        Transition::new(None, "badge", Effect::StaticBurst, 0.4, Easing::Linear),
+        Transition::new(None, "lunch", Effect::StaticBurst, 0.4, Easing::Linear),
+        Transition::new(None, "success", Effect::Crossfade, 0.3, Easing::Linear),
+        Transition::new(None, "escalation", Effect::Wipe, 0.5, Easing::EaseInOut),
+    ]
+}
+
+/// Transition in progress
+struct Active {
+    effect: Effect,
+    easing: Easing,
+    duration: Duration,
+    started: Instant,
+    /// Last frame of the previous scene
+    from: Vec<Rgb565>,
+    /// What gets shown, blended from the two scenes
+    out: SliceFrameBufferBackend<Rgb565>,
+    /// For Effect::Melt, 0..MELT_MAX_DELAY for each column
+    column_delays: Vec<f32>,
+}
+
+impl Active {
+    fn render(&mut self, to: &[Rgb565], progress: f32, rng: &mut StdRng) {
+        let width = self.out.size.width as usize;
+        let height = self.out.size.height as usize;
+        let from = &self.from;
+        let out = &mut self.out.pixels;
+        match self.effect {
+            Effect::Crossfade => {
+                let progress = color565::fraction(Intensity::new(progress));
+                for ((out, &from), &to) in out.iter_mut().zip(from).zip(to) {
+                    *out = color565::lerp(from, to, progress);
+                }
+            }
+            Effect::Wipe => {
+                let edge = (progress * width as f32) as usize;
+                for (i, out) in out.iter_mut().enumerate() {
+                    *out = if i % width < edge { to[i] } else { from[i] };
+                }
+            }
+            Effect::StaticBurst => {
+                out.copy_from_slice(if progress < 0.5 { from } else { to });
+                let noise = 1.0 - (2.0 * progress - 1.0).abs();
+                let size = self.out.size;
+                let mut framebuffer =
+                    FrameBuf::new(&mut self.out, size.width as usize, size.height as usize);
+                crate::add_noise(&mut framebuffer, rng, Intensity::new(noise));
+            }
+            Effect::Melt => {
+                for (x, delay) in self.column_delays.iter().enumerate() {
+                    let fallen = ((progress - delay) / (1.0 - MELT_MAX_DELAY)).clamp(0.0, 1.0);
+                    let shift = (fallen * height as f32) as usize;
+                    for y in 0..height {
+                        out[y * width + x] = if y < shift {
+                            to[y * width + x]
+                        } else {
+                            from[(y - shift) * width + x]
+                        };
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Keeps track of which scene is on, and blends the previous one into it for a while after
+/// switching, if there's a transition configured for that
+pub struct SceneManager {
+    transitions: Vec<Transition>,
+    size: Size,
+    current: &'static str,
+    active: Option<Active>,
+}
+
+impl SceneManager {
+    pub fn new(transitions: Vec<Transition>, size: Size) -> Self {
+        Self {
+            transitions,
+            size,
+            current: "",
+            active: None,
+        }
+    }
+
+    /// Called before drawing each frame of `scene`, while `pixels` still hold the previous one
+    pub fn switch_to(&mut self, scene: &'static str, pixels: &[Rgb565], rng: &mut StdRng) {
+        if scene == self.current {
+            return;
+        }
+        let from = std::mem::replace(&mut self.current, scene);
+        let Some(transition) = self.transitions.iter().find(|t| t.matches(from, scene)) else {
+            self.active = None;
+            return;
+        };
+        log::debug!("{from} -> {scene}: {:?}", transition.effect);
+        self.active = Some(Active {
+            effect: transition.effect,
+            easing: transition.easing,
+            duration: Duration::try_from_secs_f32(transition.duration).unwrap_or_default(),
+            started: frame_clock::now(),
+            from: pixels.to_vec(),
+            out: SliceFrameBufferBackend::new(self.size, Rgb565::BLACK),
+            column_delays: match transition.effect {
+                Effect::Melt => (0..self.size.width)
+                    .map(|_| rng.gen_range(0.0..MELT_MAX_DELAY))
+                    .collect(),
+                _ => Vec::new(),
+            },
+        });
+    }
+
+    /// Whether the next `blend` shows anything but the freshly drawn pixels, or the previous
+    /// one did
+    pub fn is_blending(&self) -> bool {
+        self.active.is_some()
+    }
+
+    /// What to show instead of the freshly drawn `pixels`
+    pub fn blend<'a>(&'a mut self, pixels: &'a [Rgb565], rng: &mut StdRng) -> &'a [Rgb565] {
+        let Some(active) = &mut self.active else {
+            return pixels;
+        };
+        let t = frame_clock::elapsed(active.started).as_secs_f32() / active.duration.as_secs_f32();
+        if active.duration.is_zero() || t >= 1.0 {
+            // Frees the buffers too
+            self.active = None;
+            return pixels;
+        }
+        let progress = active.easing.apply(t);
+        active.render(pixels, progress, rng);
+        &self.active.as_ref().unwrap().out.pixels
+    }
+}
@@ -0,0 +1,275 @@
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Deserializer};
+
+use crate::{backlight, intensity::Intensity, noise::NoiseMix, screen_shake};
+
+/// Tunable parameters of the escalation. Defaults are what looks right on the real device.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Tweaks {
+    /// How long each shade of the background lasts
+    pub frames_per_shade: usize,
+    /// How many shades pass before the timer starts going crazy
+    pub unexaggerated_shades: usize,
+    /// Exaggeration after `n` frames is `base^(n^factor)` seconds
+    pub exaggeration_base: f64,
+    pub exaggeration_factor: f64,
+    /// Maximum text shake amplitude in pixels, reached at the end of the escalation
+    pub max_text_shake: i32,
+    /// How the text shake follows escalation progress
+    pub shake_curve: Curve,
+    /// Text shakes by half pixels, rendered at twice the resolution and scaled down. Smoother
+    /// at low amplitudes, a bit blurry.
+    pub supersampled_text: bool,
+    /// How LED brightness follows escalation progress
+    pub led_curve: Curve,
+    /// What drives the left and the right LED
+    pub led_roles: [LedRole; 2],
+    /// How glitchiness follows progress, from when glitching starts to the end of the escalation
+    pub glitch_curve: Curve,
+    /// Chance of each line getting glitched, once glitching starts
+    pub glitch_probability: f32,
+    /// Glitched pixels shifted off one edge of the screen come back on the other
+    pub glitch_wrap: bool,
+    /// Glitching starts around the timer and spreads to the whole screen by the end
+    pub glitch_spread: bool,
+    /// How much of the previous frames is left in each one, reached at the end of the
+    /// escalation. 0 turns ghosting off.
+    pub ghosting: f32,
+    /// Fraction of pixels replaced with noise, reached at the end of the escalation. 0 keeps it
+    /// clean until the noise ending.
+    pub max_noise: f32,
+    /// How the noise follows escalation progress
+    pub noise_curve: Curve,
+    /// Noise flavors of the ending, one picked at random each time. `noise_intensity` of older
+    /// configs still works, as color noise only.
+    #[serde(alias = "noise_intensity", deserialize_with = "noise_mix")]
+    pub noise: NoiseMix,
+    /// Whole screen shaking on glitch bursts and errors
+    pub screen_shake: screen_shake::Config,
+    /// LCD backlight dipping, breathing and blacking out along with the screen
+    pub backlight: backlight::Config,
+    /// Chance of an escalation ending with a successful build instead of noise
+    pub success_probability: f32,
+    /// None means seeding from entropy
+    pub rng_seed: Option<u64>,
+    /// Escalation frames that take longer than this on average get skipped now and then, rather
+    /// than slowing it down. 0 never skips.
+    pub frame_budget_ms: u64,
+    /// SoC temperature above which the frame rate drops and ghosting turns off. 0 never
+    /// throttles.
+    pub throttle_celsius: f32,
+}
+
+impl Default for Tweaks {
+    fn default() -> Self {
+        Self {
+            frames_per_shade: 16,
+            unexaggerated_shades: 8,
+            exaggeration_base: 1.01,
+            exaggeration_factor: 1.4,
+            max_text_shake: 3,
+            shake_curve: Curve::Linear,
+            supersampled_text: false,
+            led_curve: Curve::Exponential(3.0),
+            led_roles: [LedRole::Progress; 2],
+            glitch_curve: Curve::Linear,
+            glitch_probability: 0.25,
+            glitch_wrap: false,
+            glitch_spread: false,
+            ghosting: 0.0,
+            max_noise: 0.0,
+            noise_curve: Curve::Exponential(3.0),
+            noise: NoiseMix::default(),
+            screen_shake: screen_shake::Config::default(),
+            backlight: backlight::Config::default(),
+            success_probability: 0.001,
+            rng_seed: None,
+            // The ~30 FPS the ESP32 manages
+            frame_budget_ms: 33,
+            // Well below where the ESP32 and the Raspberry Pi start throttling themselves
+            throttle_celsius: 75.0,
+        }
+    }
+}
+
+impl Tweaks {
+    pub fn frame_budget(&self) -> Duration {
+        Duration::from_millis(self.frame_budget_ms)
+    }
+
+    /// Rejects values the escalation can't run with, like no frames at all, and probabilities
+    /// that aren't
+    pub fn validate(&self) -> Result<()> {
+        if self.frames_per_shade == 0 {
+            bail!("frames_per_shade must be at least 1");
+        }
+        if !(self.exaggeration_base > 0.0 && self.exaggeration_base.is_finite()) {
+            bail!("exaggeration_base must be above 0");
+        }
+        if !self.exaggeration_factor.is_finite() {
+            bail!("exaggeration_factor must be a number");
+        }
+        for (name, value) in [
+            ("glitch_probability", self.glitch_probability),
+            ("success_probability", self.success_probability),
+            ("ghosting", self.ghosting),
+            ("max_noise", self.max_noise),
+        ] {
+            // Also false for NaN
+            if !(0.0..=1.0).contains(&value) {
+                bail!("{name} must be between 0 and 1, not {value}");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `[tweaks.noise]`, or the one number `noise_intensity` used to be
+fn noise_mix<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NoiseMix, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Config {
+        Intensity(f32),
+        Mix(NoiseMix),
+    }
+    Ok(match Config::deserialize(deserializer)? {
+        Config::Intensity(color) => NoiseMix {
+            color,
+            monochrome: 0.0,
+            sensor: 0.0,
+            salt_and_pepper: 0.0,
+            banding: 0.0,
+        },
+        Config::Mix(mix) => mix,
+    })
+}
+
+/// Maps one intensity to another, so that different effects can be driven by the same value
+/// and still ramp up at their own pace
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Curve {
+    #[default]
+    Linear,
+    /// `x^exponent`. Exponents above 1 stay low for longer, then shoot up.
+    Exponential(f32),
+    /// Jumps between that many levels instead of changing smoothly
+    Stepped(u32),
+}
+
+impl Curve {
+    pub fn apply(self, intensity: Intensity) -> Intensity {
+        let x = intensity.get();
+        Intensity::new(match self {
+            Curve::Linear => x,
+            Curve::Exponential(exponent) => x.powf(exponent),
+            Curve::Stepped(0) => 0.0,
+            Curve::Stepped(steps) => (x * steps as f32).floor() / steps as f32,
+        })
+    }
+}
+
+/// Which part of the escalation an LED shows
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LedRole {
+    /// Escalation progress through `led_curve`, flickering while enraged
+    #[default]
+    Progress,
+    /// How glitched the screen is
+    Glitchiness,
+    /// Dark until shaken or poked
+    Rage,
+    /// Whatever the other LED shows
+    Mirror,
+    Off,
+}
+
+/// What the LEDs can show, each 0..1
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LedChannels {
+    pub progress: f32,
+    pub glitchiness: f32,
+    pub rage: f32,
+}
+
+/// Brightness of the left and the right LED with these `roles`, before scaling. With both
+/// mirroring each other, they show progress.
+pub fn led_levels(roles: [LedRole; 2], channels: &LedChannels) -> [f32; 2] {
+    let level = |role| match role {
+        LedRole::Progress | LedRole::Mirror => channels.progress,
+        LedRole::Glitchiness => channels.glitchiness,
+        LedRole::Rage => channels.rage,
+        LedRole::Off => 0.0,
+    };
+    match roles {
+        [LedRole::Mirror, role] | [role, LedRole::Mirror] => [level(role); 2],
+        [left, right] => [level(left), level(right)],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drives_leds_by_role() {
+        let channels = LedChannels {
+            progress: 0.25,
+            glitchiness: 0.5,
+            rage: 1.0,
+        };
+        let levels = |roles| led_levels(roles, &channels);
+        assert_eq!(levels([LedRole::Progress; 2]), [0.25, 0.25]);
+        assert_eq!(
+            levels([LedRole::Glitchiness, LedRole::Progress]),
+            [0.5, 0.25]
+        );
+        assert_eq!(levels([LedRole::Off, LedRole::Rage]), [0.0, 1.0]);
+        assert_eq!(levels([LedRole::Mirror, LedRole::Rage]), [1.0, 1.0]);
+        assert_eq!(levels([LedRole::Glitchiness, LedRole::Mirror]), [0.5, 0.5]);
+        assert_eq!(levels([LedRole::Mirror; 2]), [0.25, 0.25]);
+        let parsed: Tweaks = toml::from_str("led_roles = [\"glitchiness\", \"mirror\"]").unwrap();
+        assert_eq!(parsed.led_roles, [LedRole::Glitchiness, LedRole::Mirror]);
+    }
+
+    #[test]
+    fn rejects_tweaks_the_escalation_cant_run_with() {
+        assert!(Tweaks::default().validate().is_ok());
+        let invalid = [
+            "frames_per_shade = 0",
+            "exaggeration_base = 0.0",
+            "exaggeration_base = -1.01",
+            "exaggeration_base = nan",
+            "exaggeration_factor = inf",
+            "glitch_probability = 1.5",
+            "glitch_probability = nan",
+            "success_probability = -0.1",
+            "ghosting = 2.0",
+            "max_noise = nan",
+        ];
+        for config in invalid {
+            let tweaks: Tweaks = toml::from_str(config).unwrap();
+            assert!(tweaks.validate().is_err(), "{config}");
+        }
+        let tweaks: Tweaks = toml::from_str(
+            "frames_per_shade = 1
+ghosting = 1.0",
+        )
+        .unwrap();
+        assert!(tweaks.validate().is_ok());
+    }
+
+    #[test]
+    fn still_takes_noise_intensity() {
+        let parsed: Tweaks = toml::from_str("noise_intensity = 0.5").unwrap();
+        assert_eq!(parsed.noise.color, 0.5);
+        assert_eq!(parsed.noise.monochrome, 0.0);
+        let parsed: Tweaks = toml::from_str("[noise]\nsensor = 0.25").unwrap();
+        assert_eq!(parsed.noise.sensor, 0.25);
+        assert_eq!(parsed.noise.color, NoiseMix::default().color);
+    }
+}
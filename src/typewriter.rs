@@ -0,0 +1,168 @@
+//! Text typed out key by key, with the odd wrong word typed, thought better of and erased
+
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::{Dimensions, Point},
+    mono_font::MonoTextStyle,
+    pixelcolor::Rgb565,
+    text::{renderer::TextRenderer, Alignment, Text},
+    Drawable,
+};
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+/// Chance of typing the wrong word first, for each word
+const TYPO_PROBABILITY: f64 = 0.15;
+/// Keystrokes spent staring at a wrong word before erasing it
+const HESITATION: usize = 4;
+/// What the wrong words are
+const WRONG_WORDS: &[&str] = &[
+    "Makefile",
+    "ninja",
+    "soong",
+    "Blueprint",
+    "gradle",
+    "kati",
+    "bazel",
+    "cmake",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Keystroke {
+    Type(char),
+    Backspace,
+    Pause,
+}
+
+pub struct Typewriter {
+    keystrokes: Vec<Keystroke>,
+}
+
+impl Typewriter {
+    /// Makes the same typos in the same `text` every time, on every build
+    pub fn new(text: &str) -> Self {
+        // FNV-1a, std's hashers may change between Rust versions
+        let seed = text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+        });
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let mut keystrokes = Vec::new();
+        for word in text.split_inclusive(char::is_whitespace) {
+            let right = word.trim_end();
+            if !right.is_empty() && rng.gen_bool(TYPO_PROBABILITY) {
+                let wrong = WRONG_WORDS.choose(&mut rng).copied().unwrap_or_default();
+                if wrong != right {
+                    keystrokes.extend(wrong.chars().map(Keystroke::Type));
+                    keystrokes.extend([Keystroke::Pause; HESITATION]);
+                    keystrokes.extend(wrong.chars().map(|_| Keystroke::Backspace));
+                }
+            }
+            keystrokes.extend(word.chars().map(Keystroke::Type));
+        }
+        Self { keystrokes }
+    }
+
+    /// Keystrokes until the whole text is typed
+    pub fn len(&self) -> usize {
+        self.keystrokes.len()
+    }
+
+    /// What's on the screen after `keystrokes` keystrokes
+    pub fn typed(&self, keystrokes: usize) -> String {
+        let mut typed = String::new();
+        for keystroke in self.keystrokes.iter().take(keystrokes) {
+            match keystroke {
+                Keystroke::Type(c) => typed.push(*c),
+                Keystroke::Backspace => {
+                    typed.pop();
+                }
+                Keystroke::Pause => {}
+            }
+        }
+        typed
+    }
+}
+
+/// Draws `typed` so far of `text` where `Text::with_alignment(text, ...)` puts it, each line
+/// starting where it will when it's all typed, instead of moving around while it's typed
+pub fn draw<D: DrawTarget<Color = Rgb565>>(
+    target: &mut D,
+    text: &str,
+    typed: &str,
+    position: Point,
+    style: MonoTextStyle<Rgb565>,
+    alignment: Alignment,
+) -> Result<(), D::Error> {
+    let line_height = style.line_height() as i32;
+    let mut lines = text.split('\n');
+    for (i, typed) in typed.split('\n').enumerate() {
+        let position = position + Point::new(0, i as i32 * line_height);
+        let line = lines.next().unwrap_or_default();
+        let left = Text::with_alignment(line, position, style, alignment)
+            .bounding_box()
+            .top_left
+            .x;
+        Text::new(typed, Point::new(left, position.y), style).draw(target)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_graphics::{
+        mock_display::MockDisplay, mono_font::ascii::FONT_6X10, pixelcolor::RgbColor,
+        primitives::PointsIter,
+    };
+
+    use super::*;
+
+    #[test]
+    fn types_out_with_the_odd_typo() {
+        let texts = (0..20).map(|i| format!("Compiling module {i}\nof the kernel"));
+        let mut with_typos = 0;
+        for text in texts {
+            let typewriter = Typewriter::new(&text);
+            assert_eq!(typewriter.typed(0), "");
+            assert_eq!(typewriter.typed(typewriter.len()), text);
+            assert_eq!(typewriter.typed(usize::MAX), text);
+            let mut typed = (0..typewriter.len()).map(|n| typewriter.typed(n));
+            if typed.any(|typed| !text.starts_with(&typed)) {
+                with_typos += 1;
+            }
+        }
+        // Some, but not all of them
+        assert!((1..20).contains(&with_typos), "{with_typos}");
+    }
+
+    #[test]
+    fn types_where_the_whole_text_goes() {
+        let style = MonoTextStyle::new(&FONT_6X10, Rgb565::WHITE);
+        let (text, position) = ("Compiling\nthe kernel", Point::new(32, 10));
+        let draw = |typed| {
+            let mut display = MockDisplay::new();
+            draw(
+                &mut display,
+                text,
+                typed,
+                position,
+                style,
+                Alignment::Center,
+            )
+            .unwrap();
+            display
+        };
+        let mut expected = MockDisplay::new();
+        Text::with_alignment(text, position, style, Alignment::Center)
+            .draw(&mut expected)
+            .unwrap();
+        draw(text).assert_eq(&expected);
+        // The part typed so far is drawn the same as in the whole text
+        let partial = draw("Compiling\nthe");
+        assert!(partial.affected_area().size.width > 0);
+        for point in partial.affected_area().points() {
+            if let Some(color) = partial.get_pixel(point) {
+                assert_eq!(expected.get_pixel(point), Some(color), "{point:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,19 @@
+//! What exactly is running, so that deployed androids can be identified. Stamped by build.rs.
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Short hash of HEAD, uncommitted changes or not. `unknown` outside of a git checkout.
+pub const COMMIT: &str = env!("EVIL_ANDROID_GIT_COMMIT");
+/// UTC, `YYYY-MM-DD`
+pub const BUILD_DATE: &str = env!("EVIL_ANDROID_BUILD_DATE");
+/// Enabled cargo features, comma-separated
+pub const FEATURES: &str = env!("EVIL_ANDROID_FEATURES");
+
+/// `0.1.0-1a2b3c4d`, short enough for the LCD
+pub fn short() -> String {
+    format!("{VERSION}-{COMMIT}")
+}
+
+/// Everything, for logs and the HTTP status
+pub fn full() -> String {
+    format!("{} built {BUILD_DATE} with [{FEATURES}]", short())
+}
@@ -0,0 +1,189 @@
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use embedded_graphics::{
+    geometry::{Point, Size},
+    pixelcolor::Rgb565,
+    primitives::Rectangle,
+};
+
+use crate::color565;
+
+/// Zooming out further would mostly show stretched canvas edges
+const MIN_ZOOM: f32 = 0.25;
+
+/// Part of a scene's canvas that's shown on the display
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct View {
+    /// Center of the view relative to the center of the canvas, in display pixels at zoom 1.
+    /// Fractions only make a difference on canvases larger than the display.
+    pub pan: [f32; 2],
+    /// 2 shows things twice as big
+    pub zoom: f32,
+}
+
+impl Default for View {
+    fn default() -> Self {
+        Self {
+            pan: [0.0; 2],
+            zoom: 1.0,
+        }
+    }
+}
+
+impl View {
+    /// In canvas pixels. `canvas` is expected to be a whole multiple of `display`.
+    pub fn rectangle(&self, canvas: Size, display: Size) -> Rectangle {
+        let scale = (canvas.width / display.width.max(1)).max(1) as f32;
+        let zoom = self.zoom.max(MIN_ZOOM);
+        let size = Size::new(
+            round(display.width as f32 * scale / zoom).max(1) as u32,
+            round(display.height as f32 * scale / zoom).max(1) as u32,
+        );
+        let center = Point::new(
+            canvas.width as i32 / 2 + round(self.pan[0] * scale),
+            canvas.height as i32 / 2 + round(self.pan[1] * scale),
+        );
+        // Not Rectangle::with_center, which leans to the bottom right with even sizes
+        let half = Point::new(size.width as i32 / 2, size.height as i32 / 2);
+        Rectangle::new(center - half, size)
+    }
+
+    /// Pan rounded to whole display pixels, for drawing without a larger canvas
+    pub fn whole_pixel_pan(&self) -> Point {
+        Point::new(round(self.pan[0]), round(self.pan[1]))
+    }
+}
+
+/// f32::round is std only
+fn round(v: f32) -> i32 {
+    if v < 0.0 {
+        (v - 0.5) as i32
+    } else {
+        (v + 0.5) as i32
+    }
+}
+
+/// Canvas pixels covered by each of `count` display pixels, along one axis. Never empty, views
+/// smaller than the display repeat pixels.
+fn spans(view_start: i32, view_len: u32, count: u32, canvas_len: u32) -> Vec<Range<usize>> {
+    let clamp = |v: i64| v.clamp(0, i64::from(canvas_len) - 1) as usize;
+    (0..i64::from(count))
+        .map(|i| {
+            let start = i64::from(view_start) + i * i64::from(view_len) / i64::from(count);
+            let end = i64::from(view_start) + (i + 1) * i64::from(view_len) / i64::from(count);
+            // Past the canvas edges, its outermost pixels are repeated
+            let (start, end) = (clamp(start), clamp(end.max(start + 1) - 1));
+            start..end + 1
+        })
+        .collect()
+}
+
+/// Fills `display` with the `view` part of `canvas`, scaled to fit. Each display pixel is the
+/// average of the canvas pixels it covers, so views larger than the display get smoothly scaled
+/// down.
+pub fn resample(
+    canvas: &[Rgb565],
+    canvas_size: Size,
+    view: Rectangle,
+    display: &mut [Rgb565],
+    display_size: Size,
+) {
+    if canvas_size.width == 0 || canvas_size.height == 0 {
+        return;
+    }
+    let canvas_width = canvas_size.width as usize;
+    let columns = spans(
+        view.top_left.x,
+        view.size.width,
+        display_size.width,
+        canvas_size.width,
+    );
+    let rows = spans(
+        view.top_left.y,
+        view.size.height,
+        display_size.height,
+        canvas_size.height,
+    );
+    let display_rows = display.chunks_exact_mut(display_size.width as usize);
+    for (display_row, rows) in display_rows.zip(rows) {
+        for (pixel, columns) in display_row.iter_mut().zip(&columns) {
+            let mut sum = [0u32; 3];
+            for row in rows.clone() {
+                for &color in &canvas[row * canvas_width..][columns.clone()] {
+                    for (sum, channel) in sum.iter_mut().zip(color565::split(color)) {
+                        *sum += u32::from(channel);
+                    }
+                }
+            }
+            let count = (rows.len() * columns.len()) as u32;
+            *pixel = color565::merge(sum.map(|sum| (sum / count) as u8));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_graphics::pixelcolor::{raw::RawU16, RgbColor};
+
+    use super::*;
+
+    #[test]
+    fn default_view_of_double_canvas_averages_2x2_blocks() {
+        let canvas_size = Size::new(4, 2);
+        #[rustfmt::skip]
+        let canvas = [
+            Rgb565::WHITE, Rgb565::BLACK, Rgb565::RED, Rgb565::RED,
+            Rgb565::BLACK, Rgb565::WHITE, Rgb565::RED, Rgb565::RED,
+        ];
+        let display_size = Size::new(2, 1);
+        let mut display = [Rgb565::BLACK; 2];
+        let view = View::default().rectangle(canvas_size, display_size);
+        assert_eq!(view, Rectangle::new(Point::zero(), canvas_size));
+        resample(&canvas, canvas_size, view, &mut display, display_size);
+        assert_eq!(display, [Rgb565::new(15, 31, 15), Rgb565::RED]);
+    }
+
+    #[test]
+    fn panning_moves_by_canvas_pixels() {
+        let canvas_size = Size::new(8, 8);
+        let canvas: Vec<Rgb565> = (0..64).map(|i| RawU16::new(i).into()).collect();
+        let display_size = Size::new(8, 8);
+        let mut display = [Rgb565::BLACK; 64];
+        let view = View {
+            pan: [1.0, 2.0],
+            zoom: 1.0,
+        };
+        resample(
+            &canvas,
+            canvas_size,
+            view.rectangle(canvas_size, display_size),
+            &mut display,
+            display_size,
+        );
+        assert_eq!(display[0], canvas[2 * 8 + 1]);
+        // Edges are stretched past the end of the canvas
+        assert_eq!(display[63], canvas[63]);
+    }
+
+    #[test]
+    fn zooming_in_repeats_pixels() {
+        let canvas_size = Size::new(4, 4);
+        let canvas: Vec<Rgb565> = (0..16).map(|i| RawU16::new(i).into()).collect();
+        let mut display = [Rgb565::BLACK; 16];
+        let view = View {
+            pan: [0.0; 2],
+            zoom: 2.0,
+        };
+        resample(
+            &canvas,
+            canvas_size,
+            view.rectangle(canvas_size, canvas_size),
+            &mut display,
+            canvas_size,
+        );
+        // Middle 2x2 pixels, each one doubled
+        assert_eq!(&display[..4], &[canvas[5], canvas[5], canvas[6], canvas[6]]);
+        assert_eq!(&display[4..8], &display[..4]);
+    }
+}
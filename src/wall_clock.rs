@@ -0,0 +1,184 @@
+use std::{
+    sync::atomic::{AtomicI32, Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Anything earlier means the clock was never set
+const MIN_VALID_TIME: Duration = Duration::from_secs(1_704_067_200); // 2024-01-01
+
+const WEEKDAY_NAMES: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+// Set once from config.toml, before anything asks for local time
+static UTC_OFFSET_MINUTES: AtomicI32 = AtomicI32::new(0);
+
+/// Local time zone. No DST, this needs adjusting twice a year where it applies.
+pub fn set_utc_offset_minutes(minutes: i32) {
+    UTC_OFFSET_MINUTES.store(minutes, Ordering::Relaxed);
+}
+
+/// None if the clock was never set, which is the case on the ESP32 until SNTP syncs
+pub fn now() -> Option<SystemTime> {
+    let now = SystemTime::now();
+    (now >= UNIX_EPOCH + MIN_VALID_TIME).then_some(now)
+}
+
+/// Current local time, None if the clock isn't set yet
+pub fn local_now() -> Option<CivilTime> {
+    let now = now()?.duration_since(UNIX_EPOCH).ok()?;
+    let offset = UTC_OFFSET_MINUTES.load(Ordering::Relaxed);
+    Some(local(now.as_secs() as i64, offset))
+}
+
+fn local(unix_secs: i64, utc_offset_minutes: i32) -> CivilTime {
+    CivilTime::from_unix(unix_secs + i64::from(utc_offset_minutes) * 60)
+}
+
+/// Days since 1970-01-01 in the proleptic Gregorian calendar. Month and day are 1-based.
+/// See http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+pub fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Broken down date and time, in whatever time zone the timestamp was shifted to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CivilTime {
+    pub year: i64,
+    /// 1..=12
+    pub month: u32,
+    /// 1..=31
+    pub day: u32,
+    /// 0 is Monday
+    pub weekday: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+}
+
+impl CivilTime {
+    /// Inverse of `days_from_civil`, plus the time of day
+    pub fn from_unix(secs: i64) -> Self {
+        let days = secs.div_euclid(24 * 60 * 60);
+        let secs_of_day = secs.rem_euclid(24 * 60 * 60) as u32;
+
+        let z = days + 719468;
+        let era = if z >= 0 { z } else { z - 146096 } / 146097;
+        let day_of_era = z - era * 146097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let mp = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+        Self {
+            year,
+            month,
+            day,
+            // 1970-01-01 was a Thursday
+            weekday: (days + 3).rem_euclid(7) as u32,
+            hour: secs_of_day / 3600,
+            minute: secs_of_day / 60 % 60,
+            second: secs_of_day % 60,
+        }
+    }
+
+    pub fn weekday_name(&self) -> &'static str {
+        WEEKDAY_NAMES[self.weekday as usize]
+    }
+
+    pub fn minute_of_day(&self) -> u32 {
+        self.hour * 60 + self.minute
+    }
+
+    pub fn days_since_epoch(&self) -> i64 {
+        days_from_civil(self.year, self.month.into(), self.day.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn civil(year: i64, month: u32, day: u32, weekday: &str, hms: [u32; 3]) -> CivilTime {
+        let [hour, minute, second] = hms;
+        CivilTime {
+            year,
+            month,
+            day,
+            weekday: WEEKDAY_NAMES.iter().position(|&w| w == weekday).unwrap() as u32,
+            hour,
+            minute,
+            second,
+        }
+    }
+
+    #[test]
+    fn breaks_down_timestamps() {
+        assert_eq!(CivilTime::from_unix(0), civil(1970, 1, 1, "Thu", [0, 0, 0]));
+        assert_eq!(
+            CivilTime::from_unix(-1),
+            civil(1969, 12, 31, "Wed", [23, 59, 59])
+        );
+        assert_eq!(
+            CivilTime::from_unix(1_709_164_800),
+            civil(2024, 2, 29, "Thu", [0, 0, 0])
+        );
+    }
+
+    #[test]
+    fn counts_leap_days() {
+        let days_in_february = |year| days_from_civil(year, 3, 1) - days_from_civil(year, 2, 1);
+        assert_eq!(days_in_february(2023), 28);
+        assert_eq!(days_in_february(2024), 29);
+        // Every 100 years there's none, except every 400 years
+        assert_eq!(days_in_february(1900), 28);
+        assert_eq!(days_in_february(2000), 29);
+        assert_eq!(days_in_february(2100), 28);
+    }
+
+    #[test]
+    fn goes_day_by_day_across_month_and_year_boundaries() {
+        let month_length = |year: i64, month: u32| match month {
+            2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        };
+        let mut previous = CivilTime::from_unix(days_from_civil(1899, 12, 31) * 24 * 60 * 60);
+        for days in days_from_civil(1900, 1, 1)..days_from_civil(2101, 1, 1) {
+            let date = CivilTime::from_unix(days * 24 * 60 * 60 + 12 * 60 * 60);
+            assert_eq!(date.days_since_epoch(), days, "{date:?}");
+            assert_eq!(date.weekday, (previous.weekday + 1) % 7, "{date:?}");
+            let next_day = (previous.year, previous.month, previous.day + 1);
+            let next_month = match previous.month {
+                12 => (previous.year + 1, 1, 1),
+                month => (previous.year, month + 1, 1),
+            };
+            let ymd = (date.year, date.month, date.day);
+            match previous.day == month_length(previous.year, previous.month) {
+                true => assert_eq!(ymd, next_month, "{previous:?}"),
+                false => assert_eq!(ymd, next_day, "{previous:?}"),
+            }
+            previous = date;
+        }
+    }
+
+    #[test]
+    fn shifts_by_the_utc_offset() {
+        // 2024-03-01 02:30 UTC
+        let utc = 1_709_260_200;
+        assert_eq!(local(utc, 0), civil(2024, 3, 1, "Fri", [2, 30, 0]));
+        // Back over a leap day
+        assert_eq!(local(utc, -3 * 60), civil(2024, 2, 29, "Thu", [23, 30, 0]));
+        assert_eq!(local(utc, 5 * 60 + 30), civil(2024, 3, 1, "Fri", [8, 0, 0]));
+        // Back over New Year
+        let utc = 1_735_691_400;
+        assert_eq!(local(utc, -60), civil(2024, 12, 31, "Tue", [23, 30, 0]));
+    }
+}
@@ -0,0 +1,225 @@
+use std::{
+    sync::mpsc::{self, Sender},
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+
+use crate::platform::FrameStats;
+
+// Read at build time, so that they work on the ESP32 too
+const URLS: Option<&str> = option_env!("EVIL_ANDROID_WEBHOOKS");
+const MIN_INTERVAL_SECS: Option<&str> = option_env!("EVIL_ANDROID_WEBHOOK_MIN_INTERVAL_SECS");
+// The escalation only takes a minute or so, announcing every one of them would be spam
+const DEFAULT_MIN_INTERVAL: Duration = Duration::from_secs(15 * 60);
+// TLS handshakes need a lot of stack
+const WORKER_STACK_SIZE: usize = 12 * 1024;
+
+/// Points of the escalation worth announcing
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Milestone {
+    /// The build timer shows at least that many seconds
+    ShownTime {
+        secs: u64,
+        label: &'static str,
+    },
+    DumpsterFire,
+    /// The noise ending, as bad as it gets
+    PeakChaos,
+}
+
+/// Build timer values worth announcing: (seconds, label)
+const SHOWN_TIME_MILESTONES: &[(u64, &str)] = &[
+    (60 * 60, "1 hour"),
+    (24 * 60 * 60, "1 day"),
+    (365 * 24 * 60 * 60, "1 year"),
+];
+
+impl Milestone {
+    fn id(self) -> &'static str {
+        match self {
+            Milestone::ShownTime { label, .. } => label,
+            Milestone::DumpsterFire => "dumpster-fire",
+            Milestone::PeakChaos => "peak-chaos",
+        }
+    }
+
+    fn message(self) -> String {
+        match self {
+            Milestone::ShownTime { label, .. } => {
+                format!("The build is {label} in. Still analyzing Android.bp.")
+            }
+            Milestone::DumpsterFire => "Dumpster fire reached.".to_owned(),
+            Milestone::PeakChaos => "PEAK CHAOS. The build has consumed itself.".to_owned(),
+        }
+    }
+}
+
+/// Where to send notifications. The payload format is picked based on the URL.
+#[derive(Clone, Debug, PartialEq)]
+enum Webhook {
+    /// `{"text": ...}`
+    Slack(String),
+    /// `{"content": ...}`
+    Discord(String),
+    /// `{"event": ..., "message": ...}`
+    Generic(String),
+}
+
+impl Webhook {
+    fn from_url(url: &str) -> Self {
+        if url.contains("hooks.slack.com") {
+            Webhook::Slack(url.to_owned())
+        } else if url.contains("discord.com/api/webhooks") {
+            Webhook::Discord(url.to_owned())
+        } else {
+            Webhook::Generic(url.to_owned())
+        }
+    }
+
+    fn url(&self) -> &str {
+        match self {
+            Webhook::Slack(url) | Webhook::Discord(url) | Webhook::Generic(url) => url,
+        }
+    }
+
+    fn payload(&self, milestone: Milestone) -> String {
+        let message = json_string(&milestone.message());
+        match self {
+            Webhook::Slack(_) => format!(r#"{{"text":{message}}}"#),
+            Webhook::Discord(_) => format!(r#"{{"content":{message}}}"#),
+            Webhook::Generic(_) => format!(
+                r#"{{"event":{},"message":{message}}}"#,
+                json_string(milestone.id())
+            ),
+        }
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Fires webhooks as the escalation crosses milestones. Each milestone gets announced at most
+/// once per escalation, and no more often than the configured interval. Requests are sent from a background thread, so
+/// slow networks don't stall the animation.
+pub struct Notifier {
+    webhooks: Vec<Webhook>,
+    min_interval: Duration,
+    last_sent: Vec<(Milestone, Instant)>,
+    /// In the current escalation
+    reached: Vec<Milestone>,
+    sender: Option<Sender<(String, String)>>,
+}
+
+impl Notifier {
+    pub fn from_build_env() -> Result<Self> {
+        let webhooks: Vec<_> = URLS
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(Webhook::from_url)
+            .collect();
+        let min_interval = match MIN_INTERVAL_SECS.map(str::parse) {
+            Some(Ok(secs)) => Duration::from_secs(secs),
+            Some(Err(e)) => {
+                log::warn!("invalid EVIL_ANDROID_WEBHOOK_MIN_INTERVAL_SECS: {e}");
+                DEFAULT_MIN_INTERVAL
+            }
+            None => DEFAULT_MIN_INTERVAL,
+        };
+
+        let sender = if webhooks.is_empty() {
+            None
+        } else if !cfg!(feature = "net") {
+            log::warn!("webhooks configured, but built without the net feature");
+            None
+        } else {
+            log::info!("{} webhook(s) configured", webhooks.len());
+            let (sender, receiver) = mpsc::channel::<(String, String)>();
+            std::thread::Builder::new()
+                .name("webhooks".to_owned())
+                .stack_size(WORKER_STACK_SIZE)
+                .spawn(move || {
+                    for (url, payload) in receiver {
+                        match crate::platform::http_post(
+                            &url,
+                            "application/json",
+                            payload.as_bytes(),
+                        ) {
+                            Ok(status) if (200..300).contains(&status) => {}
+                            Ok(status) => log::warn!("webhook {url} returned {status}"),
+                            Err(e) => log::warn!("webhook {url} failed: {e:?}"),
+                        }
+                    }
+                })?;
+            Some(sender)
+        };
+
+        Ok(Self {
+            webhooks,
+            min_interval,
+            last_sent: Vec::new(),
+            reached: Vec::new(),
+            sender,
+        })
+    }
+
+    /// Called once per escalation frame
+    pub fn on_frame(&mut self, stats: &FrameStats) {
+        for &(secs, label) in SHOWN_TIME_MILESTONES {
+            let crossed = match stats.shown_time {
+                Some(shown) => shown.as_secs() >= secs,
+                // Off the charts counts as crossing all of them
+                None => true,
+            };
+            if crossed {
+                self.reach(Milestone::ShownTime { secs, label });
+            }
+        }
+        if stats.glitchiness > 0 {
+            self.reach(Milestone::DumpsterFire);
+        }
+    }
+
+    pub fn on_escalation_done(&mut self) {
+        self.reach(Milestone::PeakChaos);
+        self.reached.clear();
+    }
+
+    fn reach(&mut self, milestone: Milestone) {
+        if self.reached.contains(&milestone) {
+            return;
+        }
+        self.reached.push(milestone);
+        let Some(sender) = &self.sender else {
+            return;
+        };
+        let last_sent = self.last_sent.iter_mut().find(|(m, _)| *m == milestone);
+        match last_sent {
+            Some((_, t)) if t.elapsed() < self.min_interval => {
+                log::info!("rate limited, not announcing {}", milestone.id());
+                return;
+            }
+            Some((_, t)) => *t = Instant::now(),
+            None => self.last_sent.push((milestone, Instant::now())),
+        }
+        log::info!("announcing {}", milestone.id());
+        for webhook in &self.webhooks {
+            let request = (webhook.url().to_owned(), webhook.payload(milestone));
+            if sender.send(request).is_err() {
+                log::error!("webhook thread is gone");
+            }
+        }
+    }
+}
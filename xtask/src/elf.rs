@@ -0,0 +1,127 @@
+use anyhow::{ensure, Context, Result};
+
+/// Section takes up memory at run time
+const SHF_ALLOC: u32 = 0x2;
+/// Section has no contents in the file, like .bss
+const SHT_NOBITS: u32 = 8;
+/// Names of the sections that end up in internal RAM on all ESP32 variants. IRAM counts too,
+/// code there takes space heap and stacks could use.
+const RAM_SECTIONS: &[&str] = &[".dram0.", ".iram0.", ".noinit"];
+
+/// What a firmware image needs, in bytes
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Sizes {
+    /// Everything stored in the app partition: code, constants, initial values of statics.
+    /// The image adds a few headers and padding on top.
+    pub flash: u64,
+    /// Statics and code in internal RAM, what's left goes to the heap and task stacks
+    pub ram: u64,
+}
+
+/// Of a 32-bit little-endian ELF file, which all ESP32 variants build
+pub fn sizes(elf: &[u8]) -> Result<Sizes> {
+    ensure!(
+        elf.starts_with(b"\x7fELF\x01\x01"),
+        "not a 32-bit little-endian ELF file"
+    );
+    let u16_at = |offset: usize| -> Result<u16> {
+        let bytes = elf.get(offset..offset + 2).context("truncated ELF file")?;
+        Ok(u16::from_le_bytes(bytes.try_into().unwrap()))
+    };
+    let u32_at = |offset: usize| -> Result<u32> {
+        let bytes = elf.get(offset..offset + 4).context("truncated ELF file")?;
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+    };
+    let header_offset = u32_at(0x20)? as usize;
+    let header_size = u16_at(0x2e)? as usize;
+    let header_count = u16_at(0x30)? as usize;
+    let header = |index: usize| header_offset + index * header_size;
+    let names_offset = u32_at(header(u16_at(0x32)? as usize) + 0x10)? as usize;
+
+    let mut sizes = Sizes::default();
+    for index in 0..header_count {
+        let header = header(index);
+        let kind = u32_at(header + 0x4)?;
+        let flags = u32_at(header + 0x8)?;
+        let size = u64::from(u32_at(header + 0x14)?);
+        if flags & SHF_ALLOC == 0 {
+            continue;
+        }
+        if kind != SHT_NOBITS {
+            sizes.flash += size;
+        }
+        let name = name_at(elf, names_offset + u32_at(header)? as usize)?;
+        if RAM_SECTIONS.iter().any(|prefix| name.starts_with(prefix)) {
+            sizes.ram += size;
+        }
+    }
+    Ok(sizes)
+}
+
+fn name_at(elf: &[u8], offset: usize) -> Result<&str> {
+    let rest = elf.get(offset..).context("section name out of bounds")?;
+    let end = rest
+        .iter()
+        .position(|&b| b == 0)
+        .context("unterminated section name")?;
+    std::str::from_utf8(&rest[..end]).context("section name not UTF-8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Smallest ELF file with these sections: name, type, flags, size
+    fn elf(sections: &[(&str, u32, u32, u32)]) -> Vec<u8> {
+        const HEADER_SIZE: u32 = 52;
+        const SECTION_HEADER_SIZE: u32 = 40;
+        let mut names = b"\0.shstrtab\0".to_vec();
+        // Null section first, as always, then the names, then the rest
+        let count = sections.len() as u32 + 2;
+        let names_offset = HEADER_SIZE + count * SECTION_HEADER_SIZE;
+        let mut headers = vec![[0; 10], [1, 3, 0, 0, names_offset, 0, 0, 0, 1, 0]];
+        for &(name, kind, flags, size) in sections {
+            headers.push([names.len() as u32, kind, flags, 0, 0, size, 0, 0, 1, 0]);
+            names.extend_from_slice(name.as_bytes());
+            names.push(0);
+        }
+        headers[1][5] = names.len() as u32;
+
+        let mut elf = b"\x7fELF\x01\x01\x01".to_vec();
+        elf.resize(HEADER_SIZE as usize, 0);
+        elf[0x20..0x24].copy_from_slice(&HEADER_SIZE.to_le_bytes());
+        elf[0x2e..0x30].copy_from_slice(&(SECTION_HEADER_SIZE as u16).to_le_bytes());
+        elf[0x30..0x32].copy_from_slice(&(count as u16).to_le_bytes());
+        elf[0x32..0x34].copy_from_slice(&1u16.to_le_bytes());
+        elf.extend(
+            headers
+                .iter()
+                .flatten()
+                .flat_map(|field| field.to_le_bytes()),
+        );
+        elf.extend(names);
+        elf
+    }
+
+    #[test]
+    fn counts_flash_and_ram() {
+        const PROGBITS: u32 = 1;
+        let elf = elf(&[
+            (".flash.text", PROGBITS, SHF_ALLOC, 1000),
+            (".flash.rodata", PROGBITS, SHF_ALLOC, 500),
+            (".dram0.data", PROGBITS, SHF_ALLOC, 30),
+            (".dram0.bss", SHT_NOBITS, SHF_ALLOC, 200),
+            (".iram0.text", PROGBITS, SHF_ALLOC, 40),
+            (".debug_info", PROGBITS, 0, 9000),
+        ]);
+        assert_eq!(
+            sizes(&elf).unwrap(),
+            Sizes {
+                flash: 1570,
+                ram: 270
+            }
+        );
+        assert!(sizes(b"#!/bin/sh").is_err());
+        assert!(sizes(&elf[..60]).is_err());
+    }
+}
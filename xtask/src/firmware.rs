@@ -0,0 +1,82 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{bail, Result};
+
+use crate::{run, settings};
+
+/// On top of whatever else gets enabled. Same as the defaults in Cargo.toml, minus the
+/// optional parts and plus the ESP-IDF build plumbing.
+pub const BASE_FEATURES: &[&str] = &["std", "embassy", "esp-idf-svc/native", "esp32"];
+/// Can be left out to save space, all of them are in the defaults in Cargo.toml
+pub const OPTIONAL_FEATURES: &[&str] = &["fire", "scripting", "net", "touch"];
+
+/// Where the main crate is
+pub fn root() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("xtask is not in a subdirectory")
+}
+
+/// Rustup toolchain and target triple for `mcu`, as in the MCU setting of ESP-IDF builds
+fn toolchain_and_target(mcu: &str) -> Result<(&'static str, &'static str)> {
+    match mcu {
+        "esp32" => Ok(("esp", "xtensa-esp32-espidf")),
+        "esp32s3" => Ok(("esp", "xtensa-esp32s3-espidf")),
+        // RISC-V, no need for the Xtensa fork
+        "esp32c3" => Ok(("nightly", "riscv32imc-esp-espidf")),
+        _ => bail!("unsupported MCU {mcu}, expected esp32, esp32s3 or esp32c3"),
+    }
+}
+
+/// Everything a normal build has, and `extra`
+pub fn default_features(extra: &[String]) -> Vec<&str> {
+    BASE_FEATURES
+        .iter()
+        .chain(OPTIONAL_FEATURES)
+        .copied()
+        .chain(extra.iter().map(String::as_str))
+        .collect()
+}
+
+/// Builds the release firmware for `mcu` with exactly `features` and the settings from
+/// device.env. Returns the path of the ELF file, which gets overwritten by the next build for
+/// the same MCU.
+pub fn build(mcu: &str, features: &[&str]) -> Result<PathBuf> {
+    let (toolchain, target) = toolchain_and_target(mcu)?;
+    let mut cargo = Command::new("cargo");
+    // build-std, the linker and rustflags come from .cargo/config.toml
+    cargo
+        .current_dir(root())
+        .arg(format!("+{toolchain}"))
+        .args([
+            "build",
+            "--release",
+            "--target",
+            target,
+            "--no-default-features",
+        ])
+        .args(["--features", &features.join(",")])
+        .env("MCU", mcu)
+        .envs(settings::load()?);
+    // Instead of uncommenting it in .cargo/config.toml
+    let sdkconfigs: Vec<_> = [("psram", "sdkconfig.psram"), ("ble", "sdkconfig.ble")]
+        .into_iter()
+        .filter(|(feature, _)| features.contains(feature))
+        .map(|(_, sdkconfig)| sdkconfig)
+        .collect();
+    if !sdkconfigs.is_empty() {
+        cargo.env(
+            "ESP_IDF_SDKCONFIG_DEFAULTS",
+            format!("sdkconfig.defaults;{}", sdkconfigs.join(";")),
+        );
+    }
+    run(&mut cargo)?;
+    Ok(root()
+        .join("target")
+        .join(target)
+        .join("release")
+        .join("evil-android"))
+}
@@ -0,0 +1,55 @@
+use std::process::Command;
+
+use anyhow::Result;
+
+use crate::{
+    firmware::{self, root},
+    run, storage, Options,
+};
+
+/// Used by `monitor` when none is given. Boards with the original ESP32 talk through a
+/// USB-to-UART bridge, the S3 and C3 ones through their native USB.
+fn default_port(mcu: &str) -> &'static str {
+    match mcu {
+        "esp32" => "/dev/ttyUSB0",
+        _ => "/dev/ttyACM0",
+    }
+}
+
+/// Builds the firmware with the default features and flashes it, along with the partition
+/// table
+pub fn flash(options: &Options) -> Result<()> {
+    let elf = firmware::build(&options.mcu, &firmware::default_features(&options.features))?;
+    // First, so that the firmware finds it on the first boot
+    if options.storage {
+        storage::write(options)?;
+    }
+    let mut espflash = Command::new("espflash");
+    espflash
+        .current_dir(root())
+        .args(["flash", "--partition-table", "partitions.csv"]);
+    if let Some(port) = &options.port {
+        espflash.args(["--port", port]);
+    }
+    run(espflash.arg(elf))?;
+    if options.monitor {
+        monitor(options)?;
+    }
+    Ok(())
+}
+
+/// Shows the log with the PC build as the host side, which can tell the log apart from
+/// streamed frames. See `--serial-monitor`.
+pub fn monitor(options: &Options) -> Result<()> {
+    let port = options
+        .port
+        .as_deref()
+        .unwrap_or_else(|| default_port(&options.mcu));
+    run(Command::new("cargo").current_dir(root()).args([
+        "run",
+        "--release",
+        "--",
+        "--serial-monitor",
+        port,
+    ]))
+}
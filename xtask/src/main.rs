@@ -0,0 +1,123 @@
+//! `cargo xtask <task>`: build chores that take more than one cargo command
+
+use std::process::Command;
+
+use anyhow::{bail, ensure, Context, Result};
+
+mod elf;
+mod firmware;
+mod flash;
+mod no_std;
+mod partitions;
+mod settings;
+mod size_report;
+mod storage;
+
+const USAGE: &str = "\
+usage: cargo xtask <task> [options]
+
+tasks:
+  flash [--storage] [--monitor]
+      Builds the firmware with the default features and flashes it. --storage also packs the
+      storage directory and writes it to its partition, --monitor shows the log afterwards.
+  monitor
+      Shows the log of a connected ESP32, leaving out streamed frames. Lines typed in go to
+      its serial console.
+  pack-storage
+      Packs the storage directory into target/storage.bin, a LittleFS image for the storage
+      partition
+  config
+      Writes device.env with every build-time setting of the firmware commented out, or
+      lists the ones set there. Firmware builds done by the tasks here use them.
+  size-report
+      Builds the firmware with each optional feature left out in turn, and prints how much
+      flash and RAM each one takes
+  check-no-std
+      Builds the library without std for thumbv6m-none-eabi, which needs
+      `rustup target add thumbv6m-none-eabi` once
+  help
+      Shows this
+
+options:
+  --mcu esp32|esp32s3|esp32c3  chip to build for, MCU in .cargo/config.toml by default
+  --features <a,b>             features to build with on top of what the task picks
+  --port <path>                serial port. espflash finds one on its own, monitor uses
+                               /dev/ttyUSB0 (/dev/ttyACM0 for native USB on the S3 and C3).
+";
+
+/// Everything the tasks take, each one ignores what it doesn't need
+pub struct Options {
+    /// As in the MCU setting of ESP-IDF builds
+    pub mcu: String,
+    /// On top of what the task builds with
+    pub features: Vec<String>,
+    pub port: Option<String>,
+    /// Write the storage partition too when flashing
+    pub storage: bool,
+    /// Show the log after flashing
+    pub monitor: bool,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self> {
+        let mut options = Self {
+            // Set by .cargo/config.toml, for everything cargo runs
+            mcu: std::env::var("MCU").unwrap_or_else(|_| "esp32".to_owned()),
+            features: Vec::new(),
+            port: None,
+            storage: false,
+            monitor: false,
+        };
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().with_context(|| format!("{arg} needs a value"));
+            match arg.as_str() {
+                "--mcu" => options.mcu = value()?,
+                "--features" => options
+                    .features
+                    .extend(value()?.split(',').map(str::to_owned)),
+                "--port" => options.port = Some(value()?),
+                "--storage" => options.storage = true,
+                "--monitor" => options.monitor = true,
+                _ => bail!("unknown option {arg}"),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// Runs `command` to completion, failing if it does
+pub fn run(command: &mut Command) -> Result<()> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let status = command
+        .status()
+        .with_context(|| format!("failed to run {program}, is it installed?"))?;
+    ensure!(status.success(), "{program} failed: {status}");
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let mut args = std::env::args().skip(1);
+    let task = args.next();
+    let options = Options::parse(args)?;
+    match task.as_deref() {
+        Some("flash") => flash::flash(&options),
+        Some("monitor") => flash::monitor(&options),
+        Some("pack-storage") => {
+            let (image, partition) = storage::pack()?;
+            println!(
+                "{} is ready for the storage partition at {:#x}",
+                image.display(),
+                partition.offset
+            );
+            Ok(())
+        }
+        Some("config") => settings::init(),
+        Some("size-report") => size_report::run(&options),
+        Some("check-no-std") => no_std::check(),
+        Some("help") | None => {
+            print!("{USAGE}");
+            Ok(())
+        }
+        Some(task) => bail!("unknown task {task}\n\n{USAGE}"),
+    }
+}
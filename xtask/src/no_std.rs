@@ -0,0 +1,26 @@
+use std::process::Command;
+
+use anyhow::Result;
+
+use crate::{firmware, run};
+
+/// Cortex-M0, no atomics to speak of and certainly no std
+const TARGET: &str = "thumbv6m-none-eabi";
+
+/// Builds the library and the frame codec for a bare-metal target, which fails as soon as
+/// anything in them or their dependencies needs std
+pub fn check() -> Result<()> {
+    run(Command::new("cargo").current_dir(firmware::root()).args([
+        "build",
+        "--lib",
+        "--no-default-features",
+        "--target",
+        TARGET,
+        "--package",
+        "evil-android",
+        "--package",
+        "evil-android-frame-codec",
+    ]))?;
+    println!("the library builds for {TARGET}");
+    Ok(())
+}
@@ -0,0 +1,86 @@
+use anyhow::{bail, Context, Result};
+
+/// Where the partition table ends, nothing can start before that
+const TABLE_END: u32 = 0x9000;
+/// Of partitions without an explicit offset
+const APP_ALIGNMENT: u32 = 0x10000;
+const DATA_ALIGNMENT: u32 = 0x1000;
+
+#[derive(Debug, PartialEq)]
+pub struct Partition {
+    pub offset: u32,
+    pub size: u32,
+}
+
+/// Finds `name` in a partition table like partitions.csv. Empty offsets get filled in the way
+/// ESP-IDF does it: right after the previous partition, apps aligned to 64 KB.
+pub fn find(csv: &str, name: &str) -> Result<Partition> {
+    let mut next = TABLE_END;
+    for line in csv.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let [partition_name, kind, _subtype, offset, size, ..] = fields[..] else {
+            bail!("invalid partition table line: {line}");
+        };
+        let alignment = match kind {
+            "app" => APP_ALIGNMENT,
+            _ => DATA_ALIGNMENT,
+        };
+        let offset = match offset {
+            "" => next.next_multiple_of(alignment),
+            offset => parse_number(offset)?,
+        };
+        let size = parse_number(size)?;
+        if partition_name == name {
+            return Ok(Partition { offset, size });
+        }
+        next = offset + size;
+    }
+    bail!("no {name} partition in the partition table")
+}
+
+/// Hex or decimal, with an optional K or M suffix
+fn parse_number(s: &str) -> Result<u32> {
+    let (digits, multiplier) = match s.as_bytes().last() {
+        Some(b'K' | b'k') => (&s[..s.len() - 1], 1024),
+        Some(b'M' | b'm') => (&s[..s.len() - 1], 1024 * 1024),
+        _ => (s, 1),
+    };
+    let value = match digits.strip_prefix("0x").or(digits.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => digits.parse(),
+    };
+    let value = value.with_context(|| format!("invalid number in partition table: {s}"))?;
+    value
+        .checked_mul(multiplier)
+        .with_context(|| format!("too large for the partition table: {s}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_in_offsets() {
+        let table = include_str!("../../partitions.csv");
+        assert_eq!(
+            find(table, "storage").unwrap(),
+            Partition {
+                offset: 0x300000,
+                size: 0x100000
+            }
+        );
+        let table = "nvs, data, nvs, , 24K,\nfactory, app, factory, , 1M,\n";
+        assert_eq!(
+            find(table, "factory").unwrap(),
+            Partition {
+                offset: 0x10000,
+                size: 0x100000
+            }
+        );
+        assert!(find(table, "storage").is_err());
+        assert!(find("nvs, data, nvs, 0x9000, lots,", "nvs").is_err());
+    }
+}
@@ -0,0 +1,123 @@
+use std::{collections::BTreeMap, fs, io::ErrorKind, path::Path};
+
+use anyhow::{ensure, Context, Result};
+
+use crate::firmware::root;
+
+/// Build-time settings of the firmware, `KEY=value` per line. Kept out of git, it's where WiFi
+/// passwords go.
+const FILE: &str = "device.env";
+/// Of the environment variables the firmware reads at build time
+const PREFIX: &str = "EVIL_ANDROID_";
+
+/// Settings from device.env, none if there isn't one
+pub fn load() -> Result<Vec<(String, String)>> {
+    let path = root().join(FILE);
+    match fs::read_to_string(&path) {
+        Ok(text) => parse(&text).with_context(|| format!("invalid {}", path.display())),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e).with_context(|| format!("cannot read {}", path.display())),
+    }
+}
+
+fn parse(text: &str) -> Result<Vec<(String, String)>> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let (key, value) = line
+                .split_once('=')
+                .with_context(|| format!("expected KEY=value, got {line}"))?;
+            let key = key.trim();
+            ensure!(key.starts_with(PREFIX), "{key} doesn't start with {PREFIX}");
+            Ok((key.to_owned(), value.trim().to_owned()))
+        })
+        .collect()
+}
+
+/// Writes device.env with every setting the firmware reads at build time commented out,
+/// grouped by the file that reads it. If there's one already, lists what it sets instead.
+pub fn init() -> Result<()> {
+    let path = root().join(FILE);
+    if path.exists() {
+        for (key, value) in load()? {
+            println!("{key}={value}");
+        }
+        return Ok(());
+    }
+    let mut found = BTreeMap::new();
+    collect(&root().join("src"), &mut found)?;
+    let mut text = String::from(
+        "# Build-time settings of the firmware, used by builds done with `cargo xtask`.\n\
+         # Uncomment the ones you need, README explains what they do.\n",
+    );
+    for (file, names) in found {
+        text += &format!("\n# {file}\n");
+        for name in names {
+            text += &format!("#{name}=\n");
+        }
+    }
+    fs::write(&path, text).with_context(|| format!("cannot write {}", path.display()))?;
+    println!("wrote {}", path.display());
+    Ok(())
+}
+
+/// Settings read by the sources in `dir` and below, by path relative to the crate
+fn collect(dir: &Path, found: &mut BTreeMap<String, Vec<String>>) -> Result<()> {
+    let entries = fs::read_dir(dir).with_context(|| format!("cannot list {}", dir.display()))?;
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect(&path, found)?;
+            continue;
+        }
+        if path.extension() != Some("rs".as_ref()) {
+            continue;
+        }
+        let source =
+            fs::read_to_string(&path).with_context(|| format!("cannot read {}", path.display()))?;
+        let names: Vec<String> = settings_in(&source).map(str::to_owned).collect();
+        if !names.is_empty() {
+            let relative = path.strip_prefix(root()).unwrap_or(&path);
+            found.insert(relative.display().to_string(), names);
+        }
+    }
+    Ok(())
+}
+
+/// Names of the settings `source` reads with `option_env!`
+fn settings_in(source: &str) -> impl Iterator<Item = &str> {
+    source
+        .split("option_env!(\"")
+        .skip(1)
+        .filter_map(|rest| Some(rest.split_once('"')?.0))
+        .filter(|name| name.starts_with(PREFIX))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_settings() {
+        let source = r#"
+            const SSID: Option<&str> = option_env!("EVIL_ANDROID_WIFI_SSID");
+            const HOME: Option<&str> = option_env!("HOME");
+            const PORT: Option<&str> = option_env!("EVIL_ANDROID_OSC_PORT");
+        "#;
+        assert_eq!(
+            settings_in(source).collect::<Vec<_>>(),
+            ["EVIL_ANDROID_WIFI_SSID", "EVIL_ANDROID_OSC_PORT"]
+        );
+        assert_eq!(
+            parse("# WiFi\nEVIL_ANDROID_WIFI_SSID = office\n\nEVIL_ANDROID_WIFI_PASSWORD=a=b\n")
+                .unwrap(),
+            [
+                ("EVIL_ANDROID_WIFI_SSID".to_owned(), "office".to_owned()),
+                ("EVIL_ANDROID_WIFI_PASSWORD".to_owned(), "a=b".to_owned()),
+            ]
+        );
+        assert!(parse("PATH=/bin").is_err());
+        assert!(parse("EVIL_ANDROID_WIFI_SSID").is_err());
+    }
+}
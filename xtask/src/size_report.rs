@@ -0,0 +1,86 @@
+use anyhow::{Context, Result};
+
+use crate::{
+    elf::{self, Sizes},
+    firmware::{self, BASE_FEATURES, OPTIONAL_FEATURES},
+    Options,
+};
+
+pub fn run(options: &Options) -> Result<()> {
+    let mut rows = Vec::new();
+    for (label, features) in combinations() {
+        let features: Vec<&str> = BASE_FEATURES
+            .iter()
+            .copied()
+            .chain(features)
+            .chain(options.features.iter().map(String::as_str))
+            .collect();
+        let path = firmware::build(&options.mcu, &features)?;
+        let data =
+            std::fs::read(&path).with_context(|| format!("cannot read {}", path.display()))?;
+        let sizes = elf::sizes(&data).with_context(|| format!("{} is broken", path.display()))?;
+        rows.push((label, sizes));
+    }
+    print!("{}", report(&options.mcu, &rows));
+    Ok(())
+}
+
+/// Everything first, then each optional feature left out on its own, then all of them
+fn combinations() -> Vec<(String, Vec<&'static str>)> {
+    let mut combinations = vec![("everything".to_owned(), OPTIONAL_FEATURES.to_vec())];
+    for &left_out in OPTIONAL_FEATURES {
+        let rest = OPTIONAL_FEATURES.iter().copied();
+        combinations.push((
+            format!("without {left_out}"),
+            rest.filter(|&f| f != left_out).collect(),
+        ));
+    }
+    combinations.push(("none of them".to_owned(), Vec::new()));
+    combinations
+}
+
+/// First row in full, the rest as differences from it
+fn report(mcu: &str, rows: &[(String, Sizes)]) -> String {
+    let Some((_, first)) = rows.first() else {
+        return String::new();
+    };
+    let mut out = format!("{mcu}, in bytes:\n{:<20}{:>12}{:>12}\n", "", "flash", "RAM");
+    for (index, (label, sizes)) in rows.iter().enumerate() {
+        let [flash, ram] = match index {
+            0 => [sizes.flash, sizes.ram].map(|size| size.to_string()),
+            _ => [delta(first.flash, sizes.flash), delta(first.ram, sizes.ram)],
+        };
+        out += &format!("{label:<20}{flash:>12}{ram:>12}\n");
+    }
+    out
+}
+
+fn delta(from: u64, to: u64) -> String {
+    let delta = to as i64 - from as i64;
+    format!("{delta:+}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prints_differences_from_the_first_build() {
+        let sizes = |flash, ram| Sizes { flash, ram };
+        let rows = [
+            ("everything".to_owned(), sizes(1_200_000, 90_000)),
+            ("without fire".to_owned(), sizes(1_150_000, 90_000)),
+            ("none of them".to_owned(), sizes(900_000, 91_000)),
+        ];
+        assert_eq!(
+            report("esp32", &rows),
+            "esp32, in bytes:
+                           flash         RAM
+everything               1200000       90000
+without fire              -50000          +0
+none of them             -300000       +1000
+"
+        );
+        assert_eq!(combinations().len(), OPTIONAL_FEATURES.len() + 2);
+    }
+}
@@ -0,0 +1,44 @@
+use std::{fs, path::PathBuf, process::Command};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    firmware::root,
+    partitions::{self, Partition},
+    run, Options,
+};
+
+/// Packed into the partition of the same name
+const STORAGE: &str = "storage";
+/// What the ESP-IDF LittleFS driver expects
+const BLOCK_SIZE: u32 = 4096;
+const PAGE_SIZE: u32 = 256;
+
+/// Packs the storage directory into a LittleFS image the size of its partition, with
+/// mklittlefs. Returns the path of the image and the partition it's for.
+pub fn pack() -> Result<(PathBuf, Partition)> {
+    let table =
+        fs::read_to_string(root().join("partitions.csv")).context("cannot read partitions.csv")?;
+    let partition = partitions::find(&table, STORAGE)?;
+    let image = root().join("target").join("storage.bin");
+    fs::create_dir_all(root().join("target")).context("cannot create target")?;
+    run(Command::new("mklittlefs")
+        .current_dir(root())
+        .args(["-c", STORAGE])
+        .args(["-b", &BLOCK_SIZE.to_string()])
+        .args(["-p", &PAGE_SIZE.to_string()])
+        .args(["-s", &partition.size.to_string()])
+        .arg(&image))?;
+    Ok((image, partition))
+}
+
+/// Packs the storage directory and writes it to its partition
+pub fn write(options: &Options) -> Result<()> {
+    let (image, partition) = pack()?;
+    let mut espflash = Command::new("espflash");
+    espflash.args(["write-bin", &format!("{:#x}", partition.offset)]);
+    if let Some(port) = &options.port {
+        espflash.args(["--port", port]);
+    }
+    run(espflash.arg(image))
+}